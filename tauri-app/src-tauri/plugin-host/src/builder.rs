@@ -0,0 +1,257 @@
+//! Wiring the runtime together for an embedding application
+
+use anyhow::{Context, Result};
+use extism::Function;
+use semver::Version;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::clipboard::Clipboard;
+use crate::db::{migrations, Database};
+use crate::host_functions::HostFunctionFactory;
+use crate::jobs::{JobEventSink, JobManager};
+use crate::mail::Mailer;
+use crate::plugins::{DiscoveryReport, PluginManager, SandboxProfile};
+use crate::scheduler::Scheduler;
+use crate::secrets::SecretStore;
+use crate::settings::WorkerCounts;
+
+/// Where the host's database comes from
+enum DatabaseSource {
+    Path(PathBuf),
+    Open(Arc<Database>),
+}
+
+/// Builds a [`Host`]
+///
+/// Only the plugins directory is required. Plugins' data directories, the
+/// trash and the default secret store are created next to it, so give each host a directory of its own,
+/// e.g. `<app data>/plugins`. Without a database the `db_*`, file and
+/// plugin-call host functions are not linked and jobs are unavailable.
+pub struct HostBuilder {
+    plugins_dir: PathBuf,
+    database: Option<DatabaseSource>,
+    host_functions: Option<HostFunctionFactory>,
+    trusted_plugins: Vec<String>,
+    max_sandbox: SandboxProfile,
+    job_events: Option<Arc<dyn JobEventSink>>,
+    app_version: Option<Version>,
+    workers: WorkerCounts,
+    secret_store: Option<Arc<dyn SecretStore>>,
+    clipboard: Option<Arc<dyn Clipboard>>,
+    mailer: Option<Arc<dyn Mailer>>,
+}
+
+impl HostBuilder {
+    pub fn new(plugins_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            plugins_dir: plugins_dir.into(),
+            database: None,
+            host_functions: None,
+            trusted_plugins: Vec::new(),
+            max_sandbox: SandboxProfile::Trusted,
+            job_events: None,
+            app_version: None,
+            workers: WorkerCounts::default(),
+            secret_store: None,
+            clipboard: None,
+            mailer: None,
+        }
+    }
+
+    /// Use an open database; pending migrations are run by [`HostBuilder::build`]
+    pub fn with_db(mut self, database: impl Into<Arc<Database>>) -> Self {
+        self.database = Some(DatabaseSource::Open(database.into()));
+        self
+    }
+
+    /// Open (or create) the SQLite database at `path`
+    pub fn with_db_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.database = Some(DatabaseSource::Path(path.into()));
+        self
+    }
+
+    /// Link extra host functions into every plugin, next to the built-in ones
+    ///
+    /// `factory` gets the ID of the plugin being loaded and returns its
+    /// functions; see [`HostFunctionFactory`]. Functions without a namespace
+    /// are imported from `extism:host/user`, like the built-in ones.
+    pub fn with_host_fns<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&str) -> Vec<Function> + Send + Sync + 'static,
+    {
+        self.host_functions = Some(Arc::new(factory));
+        self
+    }
+
+    /// Plugins that get their capabilities without an approval prompt
+    pub fn with_trusted_plugins(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.trusted_plugins.extend(names);
+        self
+    }
+
+    /// Loosest sandbox any plugin may run in, whatever its manifest asks for
+    pub fn with_max_sandbox(mut self, profile: SandboxProfile) -> Self {
+        self.max_sandbox = profile;
+        self
+    }
+
+    /// Receive job status changes, e.g. to forward them to a UI
+    pub fn with_job_events(mut self, sink: Arc<dyn JobEventSink>) -> Self {
+        self.job_events = Some(sink);
+        self
+    }
+
+    /// Version of the embedding application, which plugins' `min_app_version`
+    /// is checked against; defaults to this crate's version
+    pub fn with_app_version(mut self, version: Version) -> Self {
+        self.app_version = Some(version);
+        self
+    }
+
+    /// Where `secret://` values in plugin config are looked up, e.g. an OS
+    /// keychain; defaults to an encrypted file next to the plugins directory
+    pub fn with_secret_store(mut self, store: Arc<dyn SecretStore>) -> Self {
+        self.secret_store = Some(store);
+        self
+    }
+
+    /// Clipboard plugins granted the `clipboard` capability read and write;
+    /// without one the clipboard host functions fail
+    pub fn with_clipboard(mut self, clipboard: Arc<dyn Clipboard>) -> Self {
+        self.clipboard = Some(clipboard);
+        self
+    }
+
+    /// How plugins granted the `email` capability send email; defaults to
+    /// the SMTP server in the `smtp` setting
+    pub fn with_mailer(mut self, mailer: Arc<dyn Mailer>) -> Self {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    /// Sizes of the plugin execution and job worker pools
+    pub fn with_workers(mut self, workers: WorkerCounts) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Open the database, run its migrations and create the plugin manager,
+    /// job manager and scheduler
+    ///
+    /// No plugins are loaded yet; call [`Host::discover_plugins`]. Must be
+    /// called inside a Tokio runtime if jobs a previous run left behind are
+    /// to be resumed.
+    pub fn build(self) -> Result<Host> {
+        self.workers.validate()?;
+        let database = match self.database {
+            Some(DatabaseSource::Path(path)) => {
+                Some(Arc::new(Database::new(path).context("Failed to open database")?))
+            }
+            Some(DatabaseSource::Open(database)) => Some(database),
+            None => None,
+        };
+
+        let plugin_manager = match &database {
+            Some(database) => {
+                database
+                    .with_connection(|conn| Ok(migrations::run_migrations(conn)))
+                    .context("Failed to access database")?
+                    .context("Failed to run database migrations")?;
+                PluginManager::new_with_database(self.plugins_dir, database.clone())?
+            }
+            None => PluginManager::new(self.plugins_dir)?,
+        };
+        plugin_manager.set_execution_workers(self.workers.plugin_workers);
+        plugin_manager.set_trusted_plugins(self.trusted_plugins);
+        plugin_manager.set_max_sandbox(self.max_sandbox);
+        if let Some(factory) = self.host_functions {
+            plugin_manager.set_host_functions(factory);
+        }
+        if let Some(store) = self.secret_store {
+            plugin_manager.set_secret_store(store);
+        }
+        if let Some(clipboard) = self.clipboard {
+            plugin_manager.set_clipboard(clipboard);
+        }
+        if let Some(mailer) = self.mailer {
+            plugin_manager.set_mailer(mailer);
+        }
+        if let Some(version) = self.app_version {
+            plugin_manager.set_app_version(version);
+        }
+        let plugins = Arc::new(RwLock::new(plugin_manager));
+
+        let (jobs, scheduler) = match &database {
+            Some(database) => {
+                let jobs = Arc::new(JobManager::new(
+                    database.clone(),
+                    plugins.clone(),
+                    self.job_events,
+                    self.workers.job_workers,
+                )?);
+                let scheduler = Arc::new(Scheduler::new(database.clone(), jobs.clone()));
+                (Some(jobs), Some(scheduler))
+            }
+            None => (None, None),
+        };
+
+        Ok(Host {
+            plugins,
+            database,
+            jobs,
+            scheduler,
+        })
+    }
+}
+
+/// A plugin runtime: plugins, their database, background jobs and schedules
+pub struct Host {
+    pub plugins: Arc<RwLock<PluginManager>>,
+    pub database: Option<Arc<Database>>,
+    /// Background plugin calls; needs the database
+    pub jobs: Option<Arc<JobManager>>,
+    /// Schedules declared by plugin manifests; needs the database
+    pub scheduler: Option<Arc<Scheduler>>,
+}
+
+impl Host {
+    /// Load every installed plugin and register the schedules their manifests declare
+    pub async fn discover_plugins(&self) -> Result<DiscoveryReport> {
+        let plugins = self.plugins.read().await;
+        let report = plugins.discover_plugins().await?;
+        if let Some(scheduler) = &self.scheduler {
+            for manifest in plugins.list_plugins().await {
+                if let Err(e) = scheduler.sync_manifest(&manifest) {
+                    tracing::warn!("Failed to register schedules of {}: {:#}", manifest.id(), e);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Start the scheduler, job lease renewal, trash purging, host event
+    /// routing and bus message routing on the current Tokio runtime
+    ///
+    /// Embedders that supervise their own tasks can run
+    /// [`Scheduler::run`], [`JobManager::run_leases`],
+    /// [`crate::trash::TrashBin::run`], [`crate::events::route_events`] and
+    /// [`crate::bus::route_messages`] themselves instead.
+    pub async fn spawn_background_tasks(&self) -> Vec<JoinHandle<Result<(), String>>> {
+        let mut tasks = Vec::new();
+        if let Some(scheduler) = &self.scheduler {
+            tasks.push(tokio::spawn(scheduler.clone().run()));
+        }
+        if let Some(jobs) = &self.jobs {
+            tasks.push(tokio::spawn(jobs.clone().run_leases()));
+        }
+        if let Some(trash) = self.plugins.read().await.trash() {
+            tasks.push(tokio::spawn(trash.run()));
+        }
+        tasks.push(tokio::spawn(crate::events::route_events(self.plugins.clone())));
+        tasks.push(tokio::spawn(crate::bus::route_messages(self.plugins.clone())));
+        tasks
+    }
+}
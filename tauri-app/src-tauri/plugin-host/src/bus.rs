@@ -0,0 +1,153 @@
+//! Message bus plugins publish to and subscribe on
+//!
+//! Unlike host events, bus messages come from plugins: `bus_publish` sends a
+//! payload on a topic such as `auth.login`, and other plugins receive it
+//! either pushed, through the `bus_subscriptions` in their manifest, or
+//! pulled, by calling `bus_poll` with a topic pattern. Patterns are a topic,
+//! a prefix ending in `.*` (`auth.*` matches `auth.login` and
+//! `auth.token.revoked`), or `*` for everything. Every message carries the
+//! ID of the plugin that published it, which the host sets, so subscribers
+//! can tell who sent it; a plugin never receives its own messages.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::RwLock;
+
+use crate::plugins::PluginManager;
+
+/// Longest topic or pattern accepted
+pub const MAX_TOPIC_LEN: usize = 128;
+
+/// Largest payload accepted, in bytes of JSON
+pub const MAX_PAYLOAD_BYTES: usize = 256 * 1024;
+
+/// Most messages kept for a pattern between polls; older ones are dropped
+pub const MAX_QUEUED_MESSAGES: usize = 1000;
+
+/// A message a plugin published
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusMessage {
+    pub topic: String,
+    /// ID of the plugin that published it
+    pub source: String,
+    pub payload: Value,
+    /// Unix timestamp (milliseconds)
+    pub timestamp: i64,
+}
+
+/// Whether a string is a topic: dot-separated segments of lowercase letters,
+/// digits, `_` and `-`
+pub fn is_topic(topic: &str) -> bool {
+    topic.len() <= MAX_TOPIC_LEN
+        && topic.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'))
+        })
+}
+
+/// Whether a string is a topic pattern: a topic, a topic followed by `.*`, or `*`
+pub fn is_pattern(pattern: &str) -> bool {
+    pattern == "*" || is_topic(pattern.strip_suffix(".*").unwrap_or(pattern))
+}
+
+/// Whether a topic matches a pattern
+pub fn matches(pattern: &str, topic: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) => topic.starts_with(prefix),
+        None => pattern == topic,
+    }
+}
+
+/// The broker: fans published messages out to push subscribers, through
+/// [`route_messages`], and to the queues of plugins polling for them
+pub struct MessageBus {
+    sender: broadcast::Sender<BusMessage>,
+    /// Messages waiting to be polled, by plugin ID and pattern
+    queues: Mutex<HashMap<String, HashMap<String, VecDeque<BusMessage>>>>,
+}
+
+impl Default for MessageBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(256).0,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl MessageBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publish a message from a plugin; the topic must be valid
+    pub fn publish(&self, source: &str, topic: &str, payload: Value) {
+        let message = BusMessage {
+            topic: topic.to_string(),
+            source: source.to_string(),
+            payload,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        };
+        for (plugin, patterns) in self.queues.lock().unwrap().iter_mut() {
+            if plugin == source {
+                continue;
+            }
+            for (pattern, queue) in patterns.iter_mut() {
+                if !matches(pattern, topic) {
+                    continue;
+                }
+                if queue.len() == MAX_QUEUED_MESSAGES {
+                    queue.pop_front();
+                    tracing::warn!("Dropped a '{}' message queued for plugin '{}'; it is not polling often enough", topic, plugin);
+                }
+                queue.push_back(message.clone());
+            }
+        }
+        // Nobody subscribed is fine
+        let _ = self.sender.send(message);
+    }
+
+    /// Messages matching a pattern published since the plugin last polled
+    /// it, oldest first. The first poll of a pattern starts queueing and
+    /// returns nothing.
+    pub fn poll(&self, plugin: &str, pattern: &str) -> Vec<BusMessage> {
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(plugin.to_string())
+            .or_default()
+            .entry(pattern.to_string())
+            .or_default()
+            .drain(..)
+            .collect()
+    }
+
+    /// Stop queueing messages for a plugin
+    pub fn remove_plugin(&self, plugin: &str) {
+        self.queues.lock().unwrap().remove(plugin);
+    }
+
+    /// Receive every message published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BusMessage> {
+        self.sender.subscribe()
+    }
+}
+
+/// Call plugins whose `bus_subscriptions` match each published message until
+/// the bus closes
+pub async fn route_messages(plugins: Arc<RwLock<PluginManager>>) -> Result<(), String> {
+    let mut messages = plugins.read().await.bus().subscribe();
+    loop {
+        match messages.recv().await {
+            Ok(message) => plugins.read().await.dispatch_bus_message(&message).await,
+            Err(RecvError::Lagged(skipped)) => tracing::warn!("Missed {} bus messages", skipped),
+            Err(RecvError::Closed) => return Err("Message bus closed".to_string()),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! The system clipboard, as plugins with the `clipboard` capability see it
+//!
+//! The runtime has no clipboard of its own: an embedder that has one, such as
+//! a desktop app, plugs it in with [`HostBuilder::with_clipboard`] or
+//! [`PluginManager::set_clipboard`]. Until then the clipboard host functions
+//! are linked but fail with an error.
+//!
+//! [`HostBuilder::with_clipboard`]: crate::HostBuilder::with_clipboard
+//! [`PluginManager::set_clipboard`]: crate::plugins::PluginManager::set_clipboard
+
+use anyhow::Result;
+
+/// Largest text plugins may read from or write to the clipboard, in bytes
+pub const MAX_CLIPBOARD_BYTES: usize = 1024 * 1024;
+
+/// Text access to a clipboard; implementations must be safe to share between plugins
+pub trait Clipboard: Send + Sync {
+    /// The clipboard's text, or None if it holds no text
+    fn read_text(&self) -> Result<Option<String>>;
+
+    /// Replace the clipboard's contents with text
+    fn write_text(&self, text: &str) -> Result<()>;
+}
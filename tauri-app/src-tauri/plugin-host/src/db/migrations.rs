@@ -0,0 +1,890 @@
+//! Database schema migrations
+//!
+//! Migrations are listed in [`MIGRATIONS`] and applied in order, each in its
+//! own transaction. A database migrated by a newer build is refused rather
+//! than opened, since this build doesn't know its schema.
+//!
+//! Plugins can ship migrations for tables of their own; their versions are
+//! tracked per plugin in `plugin_schema_version` by [`run_plugin_migrations`].
+//! Those run under an authorizer that confines them to tables named with
+//! [`plugin_table_prefix`].
+
+use anyhow::Result;
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// A schema migration
+struct Migration {
+    version: i32,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// All migrations, in order
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        sql: MIGRATION_V1,
+    },
+    Migration {
+        version: 2,
+        description: "Audit logs",
+        sql: MIGRATION_V2,
+    },
+    Migration {
+        version: 3,
+        description: "Application settings",
+        sql: MIGRATION_V3,
+    },
+    Migration {
+        version: 4,
+        description: "Remote access logs",
+        sql: MIGRATION_V4,
+    },
+    Migration {
+        version: 5,
+        description: "Background plugin jobs",
+        sql: MIGRATION_V5,
+    },
+    Migration {
+        version: 6,
+        description: "Scheduled plugin executions",
+        sql: MIGRATION_V6,
+    },
+    Migration {
+        version: 7,
+        description: "Recorded plugin module checksums",
+        sql: MIGRATION_V7,
+    },
+    Migration {
+        version: 8,
+        description: "User roles",
+        sql: MIGRATION_V8,
+    },
+    Migration {
+        version: 9,
+        description: "Forced password resets",
+        sql: MIGRATION_V9,
+    },
+    Migration {
+        version: 10,
+        description: "Service accounts and API keys",
+        sql: MIGRATION_V10,
+    },
+    Migration {
+        version: 11,
+        description: "Runtime plugin configuration",
+        sql: MIGRATION_V11,
+    },
+    Migration {
+        version: 12,
+        description: "Structured job errors",
+        sql: MIGRATION_V12,
+    },
+    Migration {
+        version: 13,
+        description: "Job leases",
+        sql: MIGRATION_V13,
+    },
+    Migration {
+        version: 14,
+        description: "Plugin capability decisions",
+        sql: MIGRATION_V14,
+    },
+    Migration {
+        version: 15,
+        description: "Trashed files",
+        sql: MIGRATION_V15,
+    },
+    Migration {
+        version: 16,
+        description: "Remote plugin sources",
+        sql: MIGRATION_V16,
+    },
+    Migration {
+        version: 17,
+        description: "Execution output files",
+        sql: MIGRATION_V17,
+    },
+    Migration {
+        version: 18,
+        description: "Plugin network egress log",
+        sql: MIGRATION_V18,
+    },
+    Migration {
+        version: 19,
+        description: "Capability decisions under their taxonomy names",
+        sql: MIGRATION_V19,
+    },
+    Migration {
+        version: 20,
+        description: "Trusted plugin authors",
+        sql: MIGRATION_V20,
+    },
+    Migration {
+        version: 21,
+        description: "Schema versions of plugin-owned tables",
+        sql: MIGRATION_V21,
+    },
+    Migration {
+        version: 22,
+        description: "Plugin key-value store",
+        sql: MIGRATION_V22,
+    },
+    Migration {
+        version: 23,
+        description: "Plugin crypto keys",
+        sql: MIGRATION_V23,
+    },
+    Migration {
+        version: 24,
+        description: "Execution traces",
+        sql: MIGRATION_V24,
+    },
+    Migration {
+        version: 25,
+        description: "Audit logs kept for deleted users",
+        sql: MIGRATION_V25,
+    },
+    Migration {
+        version: 26,
+        description: "Plugin signers",
+        sql: MIGRATION_V26,
+    },
+    Migration {
+        version: 27,
+        description: "Owners of background jobs",
+        sql: MIGRATION_V27,
+    },
+    Migration {
+        version: 28,
+        description: "Drop the remote access log",
+        sql: MIGRATION_V28,
+    },
+];
+
+/// Tables the migrations above create; plugins may not touch them whatever
+/// their names start with
+pub const CORE_TABLES: &[&str] = &[
+    "access_logs",
+    "api_keys",
+    "audit_logs",
+    "egress_log",
+    "email_verification_tokens",
+    "execution_outputs",
+    "execution_traces",
+    "jobs",
+    "password_reset_tokens",
+    "plugin_capabilities",
+    "plugin_checksums",
+    "plugin_config",
+    "plugin_keys",
+    "plugin_kv",
+    "plugin_schema_version",
+    "plugin_signers",
+    "plugin_sources",
+    "schedules",
+    "schema_version",
+    "service_accounts",
+    "sessions",
+    "settings",
+    "trashed_files",
+    "trusted_authors",
+    "user_roles",
+    "users",
+];
+
+/// A migration that has not been applied yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingMigration {
+    pub version: i32,
+    pub description: String,
+    pub sql: String,
+    /// Error the migration failed with when run in a rolled-back transaction
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationPreview {
+    pub current_version: i32,
+    /// Latest schema version this build knows
+    pub latest_version: i32,
+    pub pending: Vec<PendingMigration>,
+}
+
+/// Latest schema version this build knows
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Run all database migrations
+pub fn run_migrations(conn: &Connection) -> Result<()> {
+    let current_version = checked_schema_version(conn)?;
+    
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current_version) {
+        tracing::info!("Running migration v{}: {}", migration.version, migration.description);
+        
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (?1, strftime('%s', 'now'))",
+            [migration.version],
+        )?;
+        tx.commit()?;
+        
+        tracing::info!("Migration v{} complete", migration.version);
+    }
+    
+    tracing::info!("Database migrations complete. Current version: {}", get_schema_version(conn)?);
+    Ok(())
+}
+
+/// List pending migrations and dry-run them in a transaction that is rolled back
+///
+/// Migrations after the first failing one are not run.
+pub fn preview_migrations(conn: &Connection) -> Result<MigrationPreview> {
+    let current_version = checked_schema_version(conn)?;
+    
+    let tx = conn.unchecked_transaction()?;
+    let mut failed = false;
+    let pending = MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current_version)
+        .map(|migration| {
+            let error = if failed {
+                Some("Not run: an earlier migration failed".to_string())
+            } else {
+                tx.execute_batch(migration.sql).err().map(|e| e.to_string())
+            };
+            failed |= error.is_some();
+            PendingMigration {
+                version: migration.version,
+                description: migration.description.to_string(),
+                sql: migration.sql.trim().to_string(),
+                error,
+            }
+        })
+        .collect();
+    tx.rollback()?;
+    
+    Ok(MigrationPreview {
+        current_version,
+        latest_version: latest_version(),
+        pending,
+    })
+}
+
+/// Prefix of the names of a plugin's own tables: its ID with `/` as `__`
+/// and other characters that are not letters or digits as `_`, then `_`,
+/// e.g. `acme__text_converter_` for `acme/text-converter`
+pub fn plugin_table_prefix(plugin: &str) -> String {
+    let mut prefix: String = plugin
+        .replace('/', "__")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    prefix.push('_');
+    prefix
+}
+
+/// Whether `table` is one of `plugin`'s own tables
+pub fn is_plugin_table(plugin: &str, table: &str) -> bool {
+    let table = table.to_ascii_lowercase();
+    table.starts_with(&plugin_table_prefix(plugin)) && !CORE_TABLES.contains(&table.as_str())
+}
+
+/// Why a plugin migration may not take `action`, if it may not
+///
+/// Migrations create, change, fill and drop the plugin's own tables and
+/// indexes. SQLite's bookkeeping in its schema tables is allowed; anything
+/// else, including reading other tables, is refused.
+fn migration_refusal(plugin: &str, action: &AuthAction<'_>) -> Option<String> {
+    let own = |table: &str| {
+        (!is_plugin_table(plugin, table)).then(|| {
+            format!(
+                "Plugin migrations may only use the plugin's own tables, named '{}...', not '{}'",
+                plugin_table_prefix(plugin),
+                table
+            )
+        })
+    };
+    let schema = |table: &str| ["sqlite_master", "sqlite_schema", "sqlite_temp_master", "sqlite_temp_schema", "sqlite_sequence"].contains(&table);
+    match action {
+        AuthAction::Select | AuthAction::Recursive | AuthAction::Function { .. } => None,
+        AuthAction::Read { table_name, .. }
+        | AuthAction::Insert { table_name }
+        | AuthAction::Update { table_name, .. }
+        | AuthAction::Delete { table_name }
+            if schema(table_name) =>
+        {
+            None
+        }
+        AuthAction::CreateTable { table_name }
+        | AuthAction::DropTable { table_name }
+        | AuthAction::AlterTable { table_name, .. }
+        | AuthAction::CreateIndex { table_name, .. }
+        | AuthAction::DropIndex { table_name, .. }
+        | AuthAction::Read { table_name, .. }
+        | AuthAction::Insert { table_name }
+        | AuthAction::Update { table_name, .. }
+        | AuthAction::Delete { table_name } => own(table_name),
+        AuthAction::Attach { .. } | AuthAction::Detach { .. } => {
+            Some("Plugin migrations may not attach databases".to_string())
+        }
+        AuthAction::Pragma { .. } => Some("Plugin migrations may not run pragmas".to_string()),
+        AuthAction::Transaction { .. } | AuthAction::Savepoint { .. } => {
+            Some("Plugin migrations run in a transaction of their own and may not control it".to_string())
+        }
+        AuthAction::Reindex { index_name } => own(index_name),
+        _ => Some("Plugin migrations may only create, change, fill and drop tables and indexes".to_string()),
+    }
+}
+
+/// Run the migrations a plugin ships that have not been applied yet, each
+/// in its own transaction; `migrations` are (file, SQL) pairs in order, the
+/// first being version 1. Returns the plugin's schema version.
+///
+/// A migration may only use the plugin's own tables (see
+/// [`is_plugin_table`]); one that reaches for anything else fails.
+pub fn run_plugin_migrations(conn: &Connection, plugin: &str, migrations: &[(String, String)]) -> Result<i32> {
+    let current_version = plugin_schema_version(conn, plugin)?;
+    if current_version as usize > migrations.len() {
+        anyhow::bail!(
+            "Tables of plugin '{}' are at schema version {}, newer than its {} migration(s); \
+             install the version of the plugin that created them",
+            plugin,
+            current_version,
+            migrations.len()
+        );
+    }
+    
+    if current_version as usize == migrations.len() {
+        return Ok(current_version);
+    }
+    // Names one plugin's prefix covers must not be another's tables
+    let prefix = plugin_table_prefix(plugin);
+    let mut stmt = conn.prepare("SELECT plugin FROM plugin_schema_version WHERE plugin != ?1")?;
+    let others = stmt.query_map([plugin], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+    if let Some(other) = others.iter().find(|other| {
+        let theirs = plugin_table_prefix(other);
+        theirs.starts_with(&prefix) || prefix.starts_with(&theirs)
+    }) {
+        anyhow::bail!(
+            "Tables of plugin '{}' would be named like those of plugin '{}' ('{}...'); rename one of them",
+            plugin,
+            other,
+            prefix
+        );
+    }
+    
+    for (version, (file, sql)) in (1..).zip(migrations).skip(current_version as usize) {
+        tracing::info!("Running migration v{} of plugin '{}': {}", version, plugin, file);
+        
+        let tx = conn.unchecked_transaction()?;
+        let before = schema_objects(&tx)?;
+        let refused = Arc::new(Mutex::new(None::<String>));
+        let reason = refused.clone();
+        let owner = plugin.to_string();
+        tx.authorizer(Some(move |context: AuthContext<'_>| match migration_refusal(&owner, &context.action) {
+            Some(refusal) => {
+                reason.lock().unwrap().get_or_insert(refusal);
+                Authorization::Deny
+            }
+            None => Authorization::Allow,
+        }));
+        let applied = tx.execute_batch(sql);
+        tx.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        applied.map_err(|e| {
+            let error = refused.lock().unwrap().take().unwrap_or_else(|| e.to_string());
+            anyhow::anyhow!("Migration {} of plugin '{}' failed: {}", file, plugin, error)
+        })?;
+        // The authorizer sees the table renamed, not the name it is given
+        if let Some(name) = schema_objects(&tx)?
+            .difference(&before)
+            .find(|name| !is_plugin_table(plugin, name))
+        {
+            anyhow::bail!(
+                "Migration {} of plugin '{}' failed: Plugin migrations may only name tables and indexes '{}...', not '{}'",
+                file,
+                plugin,
+                plugin_table_prefix(plugin),
+                name
+            );
+        }
+        tx.execute(
+            "INSERT INTO plugin_schema_version (plugin, version, applied_at) VALUES (?1, ?2, strftime('%s', 'now'))
+             ON CONFLICT(plugin) DO UPDATE SET version = excluded.version, applied_at = excluded.applied_at",
+            rusqlite::params![plugin, version],
+        )?;
+        tx.commit()?;
+    }
+    
+    Ok(migrations.len() as i32)
+}
+
+/// Names of the tables, indexes, views and triggers in the database, but
+/// SQLite's own
+fn schema_objects(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE name NOT LIKE 'sqlite\\_%' ESCAPE '\\'")?;
+    let names = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    Ok(names)
+}
+
+/// Schema version of a plugin's own tables; 0 if it has run no migrations
+pub fn plugin_schema_version(conn: &Connection, plugin: &str) -> Result<i32> {
+    let version = conn
+        .query_row(
+            "SELECT version FROM plugin_schema_version WHERE plugin = ?1",
+            [plugin],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Get the schema version, refusing databases migrated by a newer build
+fn checked_schema_version(conn: &Connection) -> Result<i32> {
+    // Create version table if it doesn't exist
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    
+    let current_version = get_schema_version(conn)?;
+    if current_version > latest_version() {
+        anyhow::bail!(
+            "Database schema version {} is newer than this build supports ({}); \
+             update the application or restore a backup made by this version",
+            current_version,
+            latest_version()
+        );
+    }
+    Ok(current_version)
+}
+
+/// Get current schema version
+fn get_schema_version(conn: &Connection) -> rusqlite::Result<i32> {
+    let version: i32 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap_or(0);
+    Ok(version)
+}
+
+/// Migration v1: Initial schema
+const MIGRATION_V1: &str = "
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            uuid TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL UNIQUE,
+            email TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            email_verified INTEGER NOT NULL DEFAULT 0,
+            avatar TEXT,
+            bio TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX idx_users_uuid ON users(uuid);
+        CREATE INDEX idx_users_email ON users(email);
+        CREATE INDEX idx_users_name ON users(name);
+        
+        CREATE TABLE sessions (
+            id TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_sessions_user_uuid ON sessions(user_uuid);
+        CREATE INDEX idx_sessions_expires_at ON sessions(expires_at);
+        
+        CREATE TABLE email_verification_tokens (
+            token TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_email_tokens_user_uuid ON email_verification_tokens(user_uuid);
+        CREATE INDEX idx_email_tokens_expires_at ON email_verification_tokens(expires_at);
+        
+        CREATE TABLE password_reset_tokens (
+            token TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_password_tokens_user_uuid ON password_reset_tokens(user_uuid);
+        CREATE INDEX idx_password_tokens_expires_at ON password_reset_tokens(expires_at);
+";
+
+/// Migration v2: Audit logs
+const MIGRATION_V2: &str = "
+        CREATE TABLE audit_logs (
+            id TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            action TEXT NOT NULL,
+            resource_type TEXT,
+            resource_id TEXT,
+            metadata TEXT,
+            ip_address TEXT,
+            user_agent TEXT,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_audit_user_uuid ON audit_logs(user_uuid);
+        CREATE INDEX idx_audit_action ON audit_logs(action);
+        CREATE INDEX idx_audit_created_at ON audit_logs(created_at);
+        CREATE INDEX idx_audit_resource ON audit_logs(resource_type, resource_id);
+";
+
+/// Migration v26: The key each plugin was signed with when it was first
+/// installed, which trusting the plugin by ID is limited to
+const MIGRATION_V26: &str = "
+        CREATE TABLE plugin_signers (
+            plugin TEXT PRIMARY KEY,
+            public_key TEXT NOT NULL,
+            recorded_at INTEGER NOT NULL
+        );
+";
+
+/// Migration v27: The user a job was submitted by; NULL for jobs the host
+/// submits itself, such as scheduled runs
+const MIGRATION_V27: &str = "
+        ALTER TABLE jobs ADD COLUMN owner_id TEXT;
+        CREATE INDEX idx_jobs_owner_id ON jobs(owner_id);
+";
+
+/// Migration v28: The remote access log of v4 was never written, as there is
+/// no remote HTTP/WebSocket API to log requests to
+const MIGRATION_V28: &str = "
+        DROP TABLE IF EXISTS access_logs;
+";
+
+/// Migration v3: Application settings
+const MIGRATION_V3: &str = "
+        CREATE TABLE settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+";
+
+/// Migration v4: Remote access logs
+const MIGRATION_V4: &str = "
+        CREATE TABLE access_logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            method TEXT NOT NULL,
+            route TEXT NOT NULL,
+            api_key_id TEXT,
+            status INTEGER NOT NULL,
+            latency_ms INTEGER NOT NULL,
+            bytes_in INTEGER NOT NULL DEFAULT 0,
+            bytes_out INTEGER NOT NULL DEFAULT 0,
+            remote_addr TEXT,
+            created_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX idx_access_logs_created_at ON access_logs(created_at);
+        CREATE INDEX idx_access_logs_api_key_id ON access_logs(api_key_id);
+";
+
+/// Migration v5: Background plugin jobs
+const MIGRATION_V5: &str = "
+        CREATE TABLE jobs (
+            id TEXT PRIMARY KEY,
+            plugin_name TEXT NOT NULL,
+            function TEXT NOT NULL,
+            input TEXT NOT NULL,
+            status TEXT NOT NULL,
+            progress REAL NOT NULL DEFAULT 0,
+            progress_message TEXT,
+            result TEXT,
+            error TEXT,
+            created_at INTEGER NOT NULL,
+            started_at INTEGER,
+            finished_at INTEGER
+        );
+        
+        CREATE INDEX idx_jobs_status ON jobs(status);
+        CREATE INDEX idx_jobs_created_at ON jobs(created_at);
+";
+
+/// Migration v6: Scheduled plugin executions
+const MIGRATION_V6: &str = "
+        CREATE TABLE schedules (
+            id TEXT PRIMARY KEY,
+            plugin_name TEXT NOT NULL,
+            function TEXT NOT NULL,
+            input TEXT NOT NULL,
+            interval_seconds INTEGER,
+            cron TEXT,
+            source TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            next_run_at INTEGER NOT NULL,
+            last_run_at INTEGER,
+            last_job_id TEXT,
+            created_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX idx_schedules_next_run ON schedules(enabled, next_run_at);
+        CREATE INDEX idx_schedules_plugin ON schedules(plugin_name);
+";
+
+/// Migration v7: Recorded plugin module checksums
+const MIGRATION_V7: &str = "
+        CREATE TABLE plugin_checksums (
+            plugin_name TEXT PRIMARY KEY,
+            wasm_sha256 TEXT NOT NULL,
+            recorded_at INTEGER NOT NULL
+        );
+";
+
+/// Migration v8: User roles
+const MIGRATION_V8: &str = "
+        CREATE TABLE user_roles (
+            user_uuid TEXT NOT NULL,
+            role TEXT NOT NULL,
+            granted_at INTEGER NOT NULL,
+            PRIMARY KEY (user_uuid, role),
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_user_roles_role ON user_roles(role);
+        
+        -- The oldest existing account becomes the first admin
+        INSERT INTO user_roles (user_uuid, role, granted_at)
+        SELECT uuid, 'admin', strftime('%s', 'now') FROM users ORDER BY created_at LIMIT 1;
+";
+
+/// Migration v9: Forced password resets
+const MIGRATION_V9: &str = "
+        ALTER TABLE users ADD COLUMN password_reset_required INTEGER NOT NULL DEFAULT 0;
+";
+
+/// Migration v10: Service accounts and API keys
+const MIGRATION_V10: &str = "
+        CREATE TABLE service_accounts (
+            user_uuid TEXT PRIMARY KEY,
+            description TEXT,
+            created_by TEXT,
+            disabled INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE TABLE api_keys (
+            id TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            key_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_used_at INTEGER,
+            revoked_at INTEGER,
+            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
+        );
+        
+        CREATE INDEX idx_api_keys_user_uuid ON api_keys(user_uuid);
+";
+
+/// Migration v11: Runtime plugin configuration
+const MIGRATION_V11: &str = "
+        CREATE TABLE plugin_config (
+            plugin_name TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (plugin_name, key)
+        );
+";
+
+/// Migration v12: Error code and retriable flag of failed jobs
+const MIGRATION_V12: &str = "
+        ALTER TABLE jobs ADD COLUMN error_code TEXT;
+        ALTER TABLE jobs ADD COLUMN retriable INTEGER NOT NULL DEFAULT 0;
+";
+
+/// Migration v13: Leases and attempt counts of queued and running jobs
+const MIGRATION_V13: &str = "
+        ALTER TABLE jobs ADD COLUMN lease_owner TEXT;
+        ALTER TABLE jobs ADD COLUMN heartbeat_at INTEGER;
+        ALTER TABLE jobs ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE jobs ADD COLUMN max_attempts INTEGER NOT NULL DEFAULT 1;
+        
+        CREATE INDEX idx_jobs_heartbeat_at ON jobs(heartbeat_at);
+";
+
+/// Migration v14: Capabilities the user granted or denied each plugin
+const MIGRATION_V14: &str = "
+        CREATE TABLE plugin_capabilities (
+            plugin_name TEXT NOT NULL,
+            capability TEXT NOT NULL,
+            granted INTEGER NOT NULL,
+            decided_at INTEGER NOT NULL,
+            PRIMARY KEY (plugin_name, capability)
+        );
+";
+
+/// Migration v15: Files moved to the trash instead of being deleted or overwritten
+const MIGRATION_V15: &str = "
+        CREATE TABLE trashed_files (
+            id TEXT PRIMARY KEY,
+            original_path TEXT NOT NULL,
+            trash_path TEXT NOT NULL,
+            plugin TEXT,
+            size INTEGER NOT NULL,
+            trashed_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX idx_trashed_files_trashed_at ON trashed_files(trashed_at);
+";
+
+/// Migration v16: URLs remote plugins were downloaded from, with their HTTP
+/// cache validators
+const MIGRATION_V16: &str = "
+        CREATE TABLE plugin_sources (
+            plugin TEXT NOT NULL,
+            url TEXT NOT NULL,
+            source_url TEXT NOT NULL,
+            etag TEXT,
+            last_modified TEXT,
+            fetched_at INTEGER NOT NULL,
+            PRIMARY KEY (plugin, url)
+        );
+        
+        CREATE INDEX idx_plugin_sources_source_url ON plugin_sources(source_url);
+";
+
+/// Migration v17: Files written through `write_output_file`, by execution
+const MIGRATION_V17: &str = "
+        CREATE TABLE execution_outputs (
+            execution_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            written_at INTEGER NOT NULL,
+            PRIMARY KEY (execution_id, path)
+        );
+";
+
+/// Migration v18: Outbound HTTP requests made by plugins
+const MIGRATION_V18: &str = "
+        CREATE TABLE egress_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            plugin TEXT NOT NULL,
+            method TEXT NOT NULL,
+            host TEXT NOT NULL,
+            path TEXT NOT NULL,
+            status INTEGER,
+            bytes_sent INTEGER NOT NULL DEFAULT 0,
+            bytes_received INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            created_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX idx_egress_log_created_at ON egress_log(created_at);
+        CREATE INDEX idx_egress_log_plugin ON egress_log(plugin);
+";
+
+/// Migration v19: Capability decisions stored under the names of the capability taxonomy
+const MIGRATION_V19: &str = "
+        UPDATE plugin_capabilities SET capability = 'net' WHERE capability = 'network';
+        UPDATE plugin_capabilities SET capability = 'fs' WHERE capability = 'filesystem';
+        UPDATE plugin_capabilities SET capability = 'db:write' WHERE capability = 'db_write';
+";
+
+/// Migration v20: Authors whose signed plugins install without quarantine
+const MIGRATION_V20: &str = "
+        CREATE TABLE trusted_authors (
+            public_key TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            added_at INTEGER NOT NULL
+        );
+";
+
+/// Migration v21: Schema versions of the tables plugins create with their own migrations
+const MIGRATION_V21: &str = "
+        CREATE TABLE plugin_schema_version (
+            plugin TEXT PRIMARY KEY,
+            version INTEGER NOT NULL,
+            applied_at INTEGER NOT NULL
+        );
+";
+
+/// Migration v22: State plugins keep with the `kv_*` host functions, by plugin
+const MIGRATION_V22: &str = "
+        CREATE TABLE plugin_kv (
+            plugin_name TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (plugin_name, key)
+        );
+";
+
+/// Migration v23: Keys plugins use through the `crypto_*` host functions,
+/// referenced by ID so their material never enters the plugin
+const MIGRATION_V23: &str = "
+        CREATE TABLE plugin_keys (
+            plugin_name TEXT NOT NULL,
+            key_id TEXT NOT NULL,
+            algorithm TEXT NOT NULL,
+            material BLOB NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (plugin_name, key_id)
+        );
+";
+
+/// Migration v24: Host function calls of executions, kept when trace
+/// persistence is on
+const MIGRATION_V24: &str = "
+        CREATE TABLE execution_traces (
+            execution_id TEXT PRIMARY KEY,
+            plugin TEXT NOT NULL,
+            function TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            trace TEXT NOT NULL
+        );
+        
+        CREATE INDEX idx_execution_traces_started_at ON execution_traces(started_at);
+";
+
+/// Migration v25: Audit logs without the foreign key that deleted them with
+/// their user, so deleting an account can keep its audit trail
+const MIGRATION_V25: &str = "
+        CREATE TABLE audit_logs_new (
+            id TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL,
+            action TEXT NOT NULL,
+            resource_type TEXT,
+            resource_id TEXT,
+            metadata TEXT,
+            ip_address TEXT,
+            user_agent TEXT,
+            created_at INTEGER NOT NULL
+        );
+        
+        INSERT INTO audit_logs_new SELECT id, user_uuid, action, resource_type, resource_id,
+            metadata, ip_address, user_agent, created_at FROM audit_logs;
+        DROP TABLE audit_logs;
+        ALTER TABLE audit_logs_new RENAME TO audit_logs;
+        
+        CREATE INDEX idx_audit_user_uuid ON audit_logs(user_uuid);
+        CREATE INDEX idx_audit_action ON audit_logs(action);
+        CREATE INDEX idx_audit_created_at ON audit_logs(created_at);
+        CREATE INDEX idx_audit_resource ON audit_logs(resource_type, resource_id);
+";
@@ -0,0 +1,297 @@
+use rusqlite::{ffi, Connection, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::ids::{self, IdKind};
+
+pub mod schema;
+pub mod migrations;
+pub mod operations;
+
+/// Work to do once the transaction it was queued in commits
+type AfterCommit = Box<dyn FnOnce() + Send>;
+
+/// Longest a plugin call's transaction may stay open before it is rolled back
+pub const MAX_TRANSACTION_DURATION: Duration = Duration::from_secs(10);
+
+/// Longest a caller waits for another thread's transaction to end
+pub const MAX_CONNECTION_WAIT: Duration = Duration::from_secs(30);
+
+/// A transaction a plugin call opened
+///
+/// The connection is shared, so while it is open only the thread the call
+/// runs on may use it; everyone else waits for the commit or rollback.
+struct OpenTransaction {
+    id: String,
+    /// Execution ID of the call that opened it
+    owner: String,
+    thread: ThreadId,
+    after_commit: Vec<AfterCommit>,
+    /// When it is rolled back if it is still open
+    deadline: Instant,
+}
+
+/// A transaction rolled back for staying open too long, remembered until
+/// the call that opened it ends it so the call's later writes fail rather
+/// than commit one by one
+struct ExpiredTransaction {
+    id: String,
+    owner: String,
+    thread: ThreadId,
+}
+
+#[derive(Default)]
+struct TransactionSlot {
+    open: Mutex<Option<OpenTransaction>>,
+    /// Signalled when the open transaction ends
+    ended: Condvar,
+    expired: Mutex<Vec<ExpiredTransaction>>,
+}
+
+/// Run `wait`, which blocks, without holding up the async tasks of the
+/// runtime worker it may be called on
+fn block_off_runtime<R>(wait: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => tokio::task::block_in_place(wait),
+        // A current-thread runtime has no other worker to hand its tasks to
+        _ => wait(),
+    }
+}
+
+/// Database wrapper with thread-safe connection
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+    transaction: Arc<TransactionSlot>,
+    max_transaction: Duration,
+    max_wait: Duration,
+}
+
+impl Database {
+    /// Create a new database connection
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+            transaction: Arc::new(TransactionSlot::default()),
+            max_transaction: MAX_TRANSACTION_DURATION,
+            max_wait: MAX_CONNECTION_WAIT,
+        })
+    }
+    
+    /// Set how long a transaction may stay open and how long callers wait
+    /// for one to end, instead of [`MAX_TRANSACTION_DURATION`] and
+    /// [`MAX_CONNECTION_WAIT`]
+    pub fn with_transaction_limits(mut self, max_transaction: Duration, max_wait: Duration) -> Self {
+        self.max_transaction = max_transaction;
+        self.max_wait = max_wait;
+        self
+    }
+    
+    /// Get access to the connection
+    ///
+    /// Waits while another thread has a transaction open, failing with
+    /// `SQLITE_BUSY` if it waits longer than the connection wait limit. On
+    /// a multi-threaded async runtime the wait hands the worker's other tasks
+    /// to another worker first, so async callers don't stall the runtime.
+    /// Fails with `SQLITE_ABORT` on a thread whose transaction was rolled
+    /// back for staying open too long, until the call ends it.
+    pub fn with_connection<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Connection) -> Result<R>,
+    {
+        let open = self.wait_for_transaction()?;
+        let conn = self.conn.lock().unwrap();
+        drop(open);
+        f(&conn)
+    }
+    
+    /// Lock the transaction slot once no other thread has a transaction
+    /// open, rolling back one that outstayed its deadline
+    fn wait_for_transaction(&self) -> Result<MutexGuard<'_, Option<OpenTransaction>>> {
+        let current = thread::current().id();
+        let give_up = Instant::now() + self.max_wait;
+        let mut open = self.transaction.open.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let deadline = match open.as_ref() {
+                None => break,
+                Some(transaction) if transaction.deadline <= now => {
+                    self.expire(&mut open);
+                    continue;
+                }
+                Some(transaction) if transaction.thread == current => break,
+                Some(transaction) => transaction.deadline,
+            };
+            if now >= give_up {
+                return Err(error(
+                    ffi::SQLITE_BUSY,
+                    format!("Timed out after {:?} waiting for another call's transaction to end", self.max_wait),
+                ));
+            }
+            let timeout = deadline.min(give_up) - now;
+            open = block_off_runtime(|| self.transaction.ended.wait_timeout(open, timeout).unwrap().0);
+        }
+        if self.transaction.expired.lock().unwrap().iter().any(|expired| expired.thread == current) {
+            return Err(error(ffi::SQLITE_ABORT, self.expired_message()));
+        }
+        Ok(open)
+    }
+    
+    /// Roll back the open transaction, which outstayed its deadline
+    fn expire(&self, open: &mut MutexGuard<'_, Option<OpenTransaction>>) {
+        let Some(transaction) = open.take() else {
+            return;
+        };
+        tracing::warn!(
+            "Rolling back the transaction of call {}, open longer than {:?}",
+            transaction.owner,
+            self.max_transaction
+        );
+        let _ = self.conn.lock().unwrap().execute_batch("ROLLBACK");
+        self.transaction.expired.lock().unwrap().push(ExpiredTransaction {
+            id: transaction.id,
+            owner: transaction.owner,
+            thread: transaction.thread,
+        });
+        self.transaction.ended.notify_all();
+    }
+    
+    fn expired_message(&self) -> String {
+        format!(
+            "The transaction was rolled back after staying open longer than {:?}",
+            self.max_transaction
+        )
+    }
+    
+    /// Begin a transaction for the plugin call `owner` on this thread,
+    /// returning its ID
+    ///
+    /// Until it ends, other threads wait for the connection. Only one
+    /// transaction is open at a time, so a call cannot begin a second one.
+    pub fn begin_transaction(&self, owner: &str) -> anyhow::Result<String> {
+        let mut open = self.wait_for_transaction()?;
+        if open.is_some() {
+            anyhow::bail!("A transaction is already open; commit or roll it back first");
+        }
+        self.conn.lock().unwrap().execute_batch("BEGIN")?;
+        let id = ids::new_id(IdKind::Other);
+        *open = Some(OpenTransaction {
+            id: id.clone(),
+            owner: owner.to_string(),
+            thread: thread::current().id(),
+            after_commit: Vec::new(),
+            deadline: Instant::now() + self.max_transaction,
+        });
+        Ok(id)
+    }
+    
+    /// Commit a transaction `owner` opened; it is rolled back if the commit
+    /// fails or it is past its deadline
+    pub fn commit_transaction(&self, owner: &str, id: &str) -> anyhow::Result<()> {
+        let (transaction, conn) = self.end_transaction(owner, id)?;
+        if transaction.deadline <= Instant::now() {
+            let _ = conn.execute_batch("ROLLBACK");
+            drop(conn);
+            self.transaction.ended.notify_all();
+            anyhow::bail!(self.expired_message());
+        }
+        let result = conn.execute_batch("COMMIT");
+        if result.is_err() {
+            let _ = conn.execute_batch("ROLLBACK");
+        }
+        drop(conn);
+        self.transaction.ended.notify_all();
+        result?;
+        for work in transaction.after_commit {
+            work();
+        }
+        Ok(())
+    }
+    
+    /// Roll back a transaction `owner` opened
+    pub fn rollback_transaction(&self, owner: &str, id: &str) -> anyhow::Result<()> {
+        let (_, conn) = self.end_transaction(owner, id)?;
+        let result = conn.execute_batch("ROLLBACK");
+        drop(conn);
+        self.transaction.ended.notify_all();
+        Ok(result?)
+    }
+    
+    /// Roll back the transaction `owner` left open, if any; true if there was one
+    pub fn rollback_open_transaction(&self, owner: &str) -> anyhow::Result<bool> {
+        let mut expired = self.transaction.expired.lock().unwrap();
+        let before = expired.len();
+        expired.retain(|transaction| transaction.owner != owner);
+        if expired.len() != before {
+            return Ok(true);
+        }
+        drop(expired);
+        let id = match &*self.transaction.open.lock().unwrap() {
+            Some(transaction) if transaction.owner == owner => transaction.id.clone(),
+            _ => return Ok(false),
+        };
+        self.rollback_transaction(owner, &id).map(|()| true)
+    }
+    
+    /// Run `work` once the transaction this thread has open commits, and
+    /// never if it is rolled back; right away if none is open
+    pub fn after_commit(&self, work: impl FnOnce() + Send + 'static) {
+        let current = thread::current().id();
+        let mut open = self.transaction.open.lock().unwrap();
+        match open.as_mut().filter(|transaction| transaction.thread == current) {
+            Some(transaction) => transaction.after_commit.push(Box::new(work)),
+            None => {
+                drop(open);
+                work();
+            }
+        }
+    }
+    
+    /// Take the open transaction if it is `id` and `owner` opened it,
+    /// locking the connection before other threads can use it
+    fn end_transaction(&self, owner: &str, id: &str) -> anyhow::Result<(OpenTransaction, MutexGuard<'_, Connection>)> {
+        let mut open = self.transaction.open.lock().unwrap();
+        let ours = open
+            .as_ref()
+            .is_some_and(|transaction| transaction.owner == owner && transaction.id == id);
+        if !ours {
+            let mut expired = self.transaction.expired.lock().unwrap();
+            if let Some(i) = expired.iter().position(|transaction| transaction.owner == owner && transaction.id == id) {
+                expired.remove(i);
+                anyhow::bail!(self.expired_message());
+            }
+            anyhow::bail!("No open transaction with that ID in this call");
+        }
+        let conn = self.conn.lock().unwrap();
+        Ok((open.take().unwrap(), conn))
+    }
+    
+    /// Write a consistent copy of the database to `path`
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        self.with_connection(|conn| {
+            conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+            Ok(())
+        })
+    }
+}
+
+impl Clone for Database {
+    fn clone(&self) -> Self {
+        Database {
+            conn: Arc::clone(&self.conn),
+            transaction: Arc::clone(&self.transaction),
+            max_transaction: self.max_transaction,
+            max_wait: self.max_wait,
+        }
+    }
+}
+
+/// A SQLite error with `code` and `message`
+fn error(code: std::os::raw::c_int, message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(code), Some(message))
+}
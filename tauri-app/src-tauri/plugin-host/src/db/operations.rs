@@ -0,0 +1,1739 @@
+use rusqlite::{Connection, Result, params, OptionalExtension};
+use std::collections::HashMap;
+use crate::db::schema::*;
+use crate::error::AppError;
+
+// ============================================================================
+// User Operations
+// ============================================================================
+
+/// Create a new user
+pub fn create_user(
+    conn: &Connection,
+    uuid: &str,
+    name: &str,
+    email: &str,
+    password_hash: &str,
+    created_at: i64,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO users (uuid, name, email, password_hash, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![uuid, name, email, password_hash, created_at],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Require a user to choose a new password at next sign-in
+pub fn set_password_reset_required(conn: &Connection, uuid: &str, required: bool) -> Result<()> {
+    conn.execute(
+        "UPDATE users SET password_reset_required = ?1 WHERE uuid = ?2",
+        params![required, uuid],
+    )?;
+    Ok(())
+}
+
+/// Get user by email
+pub fn get_user_by_email(conn: &Connection, email: &str) -> Result<Option<User>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, uuid, name, email, password_hash, email_verified, 
+                avatar, bio, created_at, updated_at, password_reset_required
+         FROM users WHERE email = ?1"
+    )?;
+    
+    let user = stmt.query_row(params![email], |row| {
+        Ok(User {
+            id: row.get(0)?,
+            uuid: row.get(1)?,
+            name: row.get(2)?,
+            email: row.get(3)?,
+            password_hash: row.get(4)?,
+            email_verified: row.get(5)?,
+            avatar: row.get(6)?,
+            bio: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+            password_reset_required: row.get(10)?,
+        })
+    }).optional()?;
+    
+    Ok(user)
+}
+
+/// Get user by UUID
+pub fn get_user_by_uuid(conn: &Connection, uuid: &str) -> Result<Option<User>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, uuid, name, email, password_hash, email_verified, 
+                avatar, bio, created_at, updated_at, password_reset_required
+         FROM users WHERE uuid = ?1"
+    )?;
+    
+    let user = stmt.query_row(params![uuid], |row| {
+        Ok(User {
+            id: row.get(0)?,
+            uuid: row.get(1)?,
+            name: row.get(2)?,
+            email: row.get(3)?,
+            password_hash: row.get(4)?,
+            email_verified: row.get(5)?,
+            avatar: row.get(6)?,
+            bio: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+            password_reset_required: row.get(10)?,
+        })
+    }).optional()?;
+    
+    Ok(user)
+}
+
+/// Get user by name
+pub fn get_user_by_name(conn: &Connection, name: &str) -> Result<Option<User>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, uuid, name, email, password_hash, email_verified, 
+                avatar, bio, created_at, updated_at, password_reset_required
+         FROM users WHERE name = ?1"
+    )?;
+    
+    let user = stmt.query_row(params![name], |row| {
+        Ok(User {
+            id: row.get(0)?,
+            uuid: row.get(1)?,
+            name: row.get(2)?,
+            email: row.get(3)?,
+            password_hash: row.get(4)?,
+            email_verified: row.get(5)?,
+            avatar: row.get(6)?,
+            bio: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+            password_reset_required: row.get(10)?,
+        })
+    }).optional()?;
+    
+    Ok(user)
+}
+
+/// List one page of users, optionally only those whose name or email
+/// contains `search` (case-insensitively for ASCII)
+pub fn list_users(
+    conn: &Connection,
+    search: Option<&str>,
+    sort: UserSort,
+    descending: bool,
+    limit: i32,
+    offset: i32,
+) -> Result<UserPage> {
+    // Match the search text literally rather than as a LIKE pattern
+    let pattern = search.map(|search| {
+        let escaped = search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        format!("%{}%", escaped)
+    });
+    let filter = "?1 IS NULL OR name LIKE ?1 ESCAPE '\\' OR email LIKE ?1 ESCAPE '\\'";
+
+    let total = conn.query_row(
+        &format!("SELECT COUNT(*) FROM users WHERE {}", filter),
+        params![pattern],
+        |row| row.get(0),
+    )?;
+
+    let direction = if descending { "DESC" } else { "ASC" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, uuid, name, email, password_hash, email_verified,
+                avatar, bio, created_at, updated_at, password_reset_required
+         FROM users WHERE {}
+         ORDER BY {} {}, id {}
+         LIMIT ?2 OFFSET ?3",
+        filter,
+        sort.column(),
+        direction,
+        direction
+    ))?;
+    let users = stmt.query_map(params![pattern, limit, offset], |row| {
+        Ok(User {
+            id: row.get(0)?,
+            uuid: row.get(1)?,
+            name: row.get(2)?,
+            email: row.get(3)?,
+            password_hash: row.get(4)?,
+            email_verified: row.get(5)?,
+            avatar: row.get(6)?,
+            bio: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+            password_reset_required: row.get(10)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(UserPage { users, total })
+}
+
+/// Update user password
+pub fn update_user_password(
+    conn: &Connection,
+    uuid: &str,
+    password_hash: &str,
+    updated_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE users SET password_hash = ?1, updated_at = ?2, password_reset_required = 0 WHERE uuid = ?3",
+        params![password_hash, updated_at, uuid],
+    )?;
+    Ok(())
+}
+
+/// Update user email verification status
+pub fn update_user_email_verified(
+    conn: &Connection,
+    uuid: &str,
+    verified: bool,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE users SET email_verified = ?1, updated_at = strftime('%s', 'now') WHERE uuid = ?2",
+        params![verified, uuid],
+    )?;
+    Ok(())
+}
+
+/// Update user profile
+pub fn update_user_profile(
+    conn: &Connection,
+    uuid: &str,
+    name: Option<&str>,
+    bio: Option<&str>,
+    avatar: Option<&str>,
+) -> Result<()> {
+    // Build query dynamically but execute with named parameters
+    match (name, bio, avatar) {
+        (Some(n), Some(b), Some(a)) => conn.execute(
+            "UPDATE users SET name = ?1, bio = ?2, avatar = ?3, updated_at = strftime('%s', 'now') WHERE uuid = ?4",
+            params![n, b, a, uuid],
+        )?,
+        (Some(n), Some(b), None) => conn.execute(
+            "UPDATE users SET name = ?1, bio = ?2, updated_at = strftime('%s', 'now') WHERE uuid = ?3",
+            params![n, b, uuid],
+        )?,
+        (Some(n), None, Some(a)) => conn.execute(
+            "UPDATE users SET name = ?1, avatar = ?2, updated_at = strftime('%s', 'now') WHERE uuid = ?3",
+            params![n, a, uuid],
+        )?,
+        (None, Some(b), Some(a)) => conn.execute(
+            "UPDATE users SET bio = ?1, avatar = ?2, updated_at = strftime('%s', 'now') WHERE uuid = ?3",
+            params![b, a, uuid],
+        )?,
+        (Some(n), None, None) => conn.execute(
+            "UPDATE users SET name = ?1, updated_at = strftime('%s', 'now') WHERE uuid = ?2",
+            params![n, uuid],
+        )?,
+        (None, Some(b), None) => conn.execute(
+            "UPDATE users SET bio = ?1, updated_at = strftime('%s', 'now') WHERE uuid = ?2",
+            params![b, uuid],
+        )?,
+        (None, None, Some(a)) => conn.execute(
+            "UPDATE users SET avatar = ?1, updated_at = strftime('%s', 'now') WHERE uuid = ?2",
+            params![a, uuid],
+        )?,
+        // Nothing to update, just update timestamp
+        (None, None, None) => conn.execute(
+            "UPDATE users SET updated_at = strftime('%s', 'now') WHERE uuid = ?1",
+            params![uuid],
+        )?,
+    };
+    Ok(())
+}
+
+/// Delete a user and the rows that belong to them, keeping their audit logs
+/// unless `delete_audit_logs` is set
+///
+/// Returns `None` if there is no such user. The deletion is all or nothing;
+/// it runs in a savepoint, so it also works inside an open transaction.
+pub fn delete_user(conn: &Connection, uuid: &str, delete_audit_logs: bool) -> Result<Option<DeletedUserRows>> {
+    conn.execute_batch("SAVEPOINT delete_user")?;
+    let deleted = delete_user_rows(conn, uuid, delete_audit_logs);
+    match deleted {
+        Ok(Some(_)) => conn.execute_batch("RELEASE delete_user")?,
+        _ => conn.execute_batch("ROLLBACK TO delete_user; RELEASE delete_user")?,
+    }
+    deleted
+}
+
+fn delete_user_rows(conn: &Connection, uuid: &str, delete_audit_logs: bool) -> Result<Option<DeletedUserRows>> {
+    // Rows are deleted before the user, which would cascade to most of them,
+    // so they can be counted
+    let delete = |table: &str| conn.execute(&format!("DELETE FROM {} WHERE user_uuid = ?1", table), params![uuid]);
+    let mut deleted = DeletedUserRows {
+        sessions: delete("sessions")?,
+        email_verification_tokens: delete("email_verification_tokens")?,
+        password_reset_tokens: delete("password_reset_tokens")?,
+        roles: delete("user_roles")?,
+        api_keys: delete("api_keys")?,
+        audit_logs: 0,
+    };
+    if delete_audit_logs {
+        deleted.audit_logs = delete("audit_logs")?;
+    }
+    delete("service_accounts")?;
+    if conn.execute("DELETE FROM users WHERE uuid = ?1", params![uuid])? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(deleted))
+}
+
+// ============================================================================
+// Session Operations
+// ============================================================================
+
+/// Create a new session
+pub fn create_session(
+    conn: &Connection,
+    id: &str,
+    user_uuid: &str,
+    created_at: i64,
+    expires_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO sessions (id, user_uuid, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![id, user_uuid, created_at, expires_at],
+    )?;
+    Ok(())
+}
+
+/// Get session by ID (only if not expired)
+pub fn get_session(conn: &Connection, id: &str) -> Result<Option<Session>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_uuid, created_at, expires_at
+         FROM sessions WHERE id = ?1 AND expires_at > strftime('%s', 'now')"
+    )?;
+    
+    let session = stmt.query_row(params![id], |row| {
+        Ok(Session {
+            id: row.get(0)?,
+            user_uuid: row.get(1)?,
+            created_at: row.get(2)?,
+            expires_at: row.get(3)?,
+        })
+    }).optional()?;
+    
+    Ok(session)
+}
+
+/// Delete session by ID
+pub fn delete_session(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Delete all sessions for a user
+pub fn delete_user_sessions(conn: &Connection, user_uuid: &str) -> Result<()> {
+    conn.execute("DELETE FROM sessions WHERE user_uuid = ?1", params![user_uuid])?;
+    Ok(())
+}
+
+/// Clean up expired sessions
+pub fn cleanup_expired_sessions(conn: &Connection) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM sessions WHERE expires_at <= strftime('%s', 'now')",
+        [],
+    )?;
+    Ok(deleted)
+}
+
+// ============================================================================
+// Email Verification Token Operations
+// ============================================================================
+
+/// Create email verification token
+pub fn create_email_verification_token(
+    conn: &Connection,
+    token: &str,
+    user_uuid: &str,
+    created_at: i64,
+    expires_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO email_verification_tokens (token, user_uuid, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![token, user_uuid, created_at, expires_at],
+    )?;
+    Ok(())
+}
+
+/// Get email verification token (only if not expired)
+pub fn get_email_verification_token(
+    conn: &Connection,
+    token: &str,
+) -> Result<Option<EmailVerificationToken>> {
+    let mut stmt = conn.prepare(
+        "SELECT token, user_uuid, created_at, expires_at
+         FROM email_verification_tokens 
+         WHERE token = ?1 AND expires_at > strftime('%s', 'now')"
+    )?;
+    
+    let token_record = stmt.query_row(params![token], |row| {
+        Ok(EmailVerificationToken {
+            token: row.get(0)?,
+            user_uuid: row.get(1)?,
+            created_at: row.get(2)?,
+            expires_at: row.get(3)?,
+        })
+    }).optional()?;
+    
+    Ok(token_record)
+}
+
+/// Delete email verification token
+pub fn delete_email_verification_token(conn: &Connection, token: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM email_verification_tokens WHERE token = ?1",
+        params![token],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Password Reset Token Operations
+// ============================================================================
+
+/// Create password reset token
+pub fn create_password_reset_token(
+    conn: &Connection,
+    token: &str,
+    user_uuid: &str,
+    created_at: i64,
+    expires_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO password_reset_tokens (token, user_uuid, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![token, user_uuid, created_at, expires_at],
+    )?;
+    Ok(())
+}
+
+/// Get password reset token (only if not expired)
+pub fn get_password_reset_token(
+    conn: &Connection,
+    token: &str,
+) -> Result<Option<PasswordResetToken>> {
+    let mut stmt = conn.prepare(
+        "SELECT token, user_uuid, created_at, expires_at
+         FROM password_reset_tokens 
+         WHERE token = ?1 AND expires_at > strftime('%s', 'now')"
+    )?;
+    
+    let token_record = stmt.query_row(params![token], |row| {
+        Ok(PasswordResetToken {
+            token: row.get(0)?,
+            user_uuid: row.get(1)?,
+            created_at: row.get(2)?,
+            expires_at: row.get(3)?,
+        })
+    }).optional()?;
+    
+    Ok(token_record)
+}
+
+/// Delete password reset token
+pub fn delete_password_reset_token(conn: &Connection, token: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM password_reset_tokens WHERE token = ?1",
+        params![token],
+    )?;
+    Ok(())
+}
+
+/// Delete all password reset tokens for a user
+pub fn delete_user_password_reset_tokens(conn: &Connection, user_uuid: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM password_reset_tokens WHERE user_uuid = ?1",
+        params![user_uuid],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Audit Log Operations
+// ============================================================================
+
+/// Create an audit log entry
+// One parameter per column, matching the host function's input
+#[allow(clippy::too_many_arguments)]
+pub fn create_audit_log(
+    conn: &Connection,
+    id: &str,
+    user_uuid: &str,
+    action: &str,
+    resource_type: Option<&str>,
+    resource_id: Option<&str>,
+    metadata: Option<&str>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+    created_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_logs (id, user_uuid, action, resource_type, resource_id, 
+                                 metadata, ip_address, user_agent, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            id,
+            user_uuid,
+            action,
+            resource_type,
+            resource_id,
+            metadata,
+            ip_address,
+            user_agent,
+            created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Get audit logs for a user with pagination
+pub fn get_user_audit_logs(
+    conn: &Connection,
+    user_uuid: &str,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<AuditLog>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_uuid, action, resource_type, resource_id, 
+                metadata, ip_address, user_agent, created_at
+         FROM audit_logs 
+         WHERE user_uuid = ?1
+         ORDER BY created_at DESC
+         LIMIT ?2 OFFSET ?3"
+    )?;
+    
+    let audit_logs = stmt.query_map(params![user_uuid, limit, offset], |row| {
+        Ok(AuditLog {
+            id: row.get(0)?,
+            user_uuid: row.get(1)?,
+            action: row.get(2)?,
+            resource_type: row.get(3)?,
+            resource_id: row.get(4)?,
+            metadata: row.get(5)?,
+            ip_address: row.get(6)?,
+            user_agent: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    
+    Ok(audit_logs)
+}
+
+/// Get audit logs with filters
+// Every filter is optional, so they are passed separately rather than as a struct
+#[allow(clippy::too_many_arguments)]
+pub fn get_audit_logs_filtered(
+    conn: &Connection,
+    user_uuid: Option<&str>,
+    action: Option<&str>,
+    resource_type: Option<&str>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<AuditLog>> {
+    let mut query = String::from(
+        "SELECT id, user_uuid, action, resource_type, resource_id, 
+                metadata, ip_address, user_agent, created_at
+         FROM audit_logs WHERE 1=1"
+    );
+    
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    
+    if let Some(uuid) = user_uuid {
+        query.push_str(" AND user_uuid = ?");
+        params.push(Box::new(uuid.to_string()));
+    }
+    
+    if let Some(act) = action {
+        query.push_str(" AND action = ?");
+        params.push(Box::new(act.to_string()));
+    }
+    
+    if let Some(res_type) = resource_type {
+        query.push_str(" AND resource_type = ?");
+        params.push(Box::new(res_type.to_string()));
+    }
+    
+    if let Some(start) = start_time {
+        query.push_str(" AND created_at >= ?");
+        params.push(Box::new(start));
+    }
+    
+    if let Some(end) = end_time {
+        query.push_str(" AND created_at <= ?");
+        params.push(Box::new(end));
+    }
+    
+    query.push_str(" ORDER BY created_at DESC LIMIT ? OFFSET ?");
+    params.push(Box::new(limit));
+    params.push(Box::new(offset));
+    
+    let mut stmt = conn.prepare(&query)?;
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    
+    let audit_logs = stmt.query_map(param_refs.as_slice(), |row| {
+        Ok(AuditLog {
+            id: row.get(0)?,
+            user_uuid: row.get(1)?,
+            action: row.get(2)?,
+            resource_type: row.get(3)?,
+            resource_id: row.get(4)?,
+            metadata: row.get(5)?,
+            ip_address: row.get(6)?,
+            user_agent: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    
+    Ok(audit_logs)
+}
+
+/// Count total audit logs for a user
+pub fn count_user_audit_logs(conn: &Connection, user_uuid: &str) -> Result<i64> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM audit_logs WHERE user_uuid = ?1",
+        params![user_uuid],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
+/// Delete old audit logs (cleanup older than specified timestamp)
+pub fn delete_old_audit_logs(conn: &Connection, older_than: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM audit_logs WHERE created_at < ?1",
+        params![older_than],
+    )?;
+    Ok(deleted)
+}
+
+// ============================================================================
+// Settings Operations
+// ============================================================================
+
+/// Get a setting's raw JSON value
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Insert or update a setting's raw JSON value
+pub fn set_setting(conn: &Connection, key: &str, value: &str, updated_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![key, value, updated_at],
+    )?;
+    Ok(())
+}
+
+/// List all settings as (key, raw JSON value) pairs
+pub fn list_settings(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+    let settings = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(settings)
+}
+
+// ============================================================================
+// Egress Log Operations
+// ============================================================================
+
+/// Record a plugin's outbound HTTP request (the `id` field is ignored and assigned by SQLite)
+pub fn create_egress_log(conn: &Connection, entry: &EgressLog) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO egress_log (plugin, method, host, path, status,
+                                 bytes_sent, bytes_received, error, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            entry.plugin,
+            entry.method,
+            entry.host,
+            entry.path,
+            entry.status,
+            entry.bytes_sent,
+            entry.bytes_received,
+            entry.error,
+            entry.created_at
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Get egress log entries, newest first, optionally filtered by plugin, host and start time
+pub fn get_egress_logs(
+    conn: &Connection,
+    plugin: Option<&str>,
+    host: Option<&str>,
+    since: Option<i64>,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<EgressLog>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, plugin, method, host, path, status,
+                bytes_sent, bytes_received, error, created_at
+         FROM egress_log
+         WHERE (?1 IS NULL OR plugin = ?1)
+           AND (?2 IS NULL OR host = ?2)
+           AND (?3 IS NULL OR created_at >= ?3)
+         ORDER BY created_at DESC, id DESC
+         LIMIT ?4 OFFSET ?5"
+    )?;
+    
+    let logs = stmt.query_map(params![plugin, host, since, limit, offset], |row| {
+        Ok(EgressLog {
+            id: row.get(0)?,
+            plugin: row.get(1)?,
+            method: row.get(2)?,
+            host: row.get(3)?,
+            path: row.get(4)?,
+            status: row.get(5)?,
+            bytes_sent: row.get(6)?,
+            bytes_received: row.get(7)?,
+            error: row.get(8)?,
+            created_at: row.get(9)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+    
+    Ok(logs)
+}
+
+// ============================================================================
+// Job Operations
+// ============================================================================
+
+fn row_to_job(row: &rusqlite::Row) -> Result<Job> {
+    Ok(Job {
+        id: row.get(0)?,
+        plugin_name: row.get(1)?,
+        function: row.get(2)?,
+        input: row.get(3)?,
+        status: row.get(4)?,
+        progress: row.get(5)?,
+        progress_message: row.get(6)?,
+        result: row.get(7)?,
+        error: row.get(8)?,
+        error_code: row.get(9)?,
+        retriable: row.get(10)?,
+        lease_owner: row.get(11)?,
+        heartbeat_at: row.get(12)?,
+        attempts: row.get(13)?,
+        max_attempts: row.get(14)?,
+        created_at: row.get(15)?,
+        started_at: row.get(16)?,
+        finished_at: row.get(17)?,
+        owner_id: row.get(18)?,
+    })
+}
+
+const JOB_COLUMNS: &str = "id, plugin_name, function, input, status, progress, progress_message,
+                           result, error, error_code, retriable, lease_owner, heartbeat_at,
+                           attempts, max_attempts, created_at, started_at, finished_at, owner_id";
+
+/// Create a queued job
+// One parameter per column
+#[allow(clippy::too_many_arguments)]
+pub fn create_job(
+    conn: &Connection,
+    id: &str,
+    plugin_name: &str,
+    function: &str,
+    input: &str,
+    max_attempts: i32,
+    owner_id: Option<&str>,
+    created_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO jobs (id, plugin_name, function, input, status, max_attempts, owner_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, 'queued', ?5, ?6, ?7)",
+        params![id, plugin_name, function, input, max_attempts, owner_id, created_at],
+    )?;
+    Ok(())
+}
+
+/// Lease a job to an instance of the application
+pub fn lease_job(conn: &Connection, id: &str, lease_owner: &str, now: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE jobs SET lease_owner = ?1, heartbeat_at = ?2 WHERE id = ?3",
+        params![lease_owner, now, id],
+    )?;
+    Ok(())
+}
+
+/// Get a job by ID
+pub fn get_job(conn: &Connection, id: &str) -> Result<Option<Job>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS))?;
+    stmt.query_row(params![id], row_to_job).optional()
+}
+
+/// List jobs, newest first, optionally filtered by status and owner
+pub fn list_jobs(
+    conn: &Connection,
+    status: Option<&str>,
+    owner_id: Option<&str>,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<Job>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM jobs
+         WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR owner_id = ?2)
+         ORDER BY created_at DESC
+         LIMIT ?3 OFFSET ?4",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map(params![status, owner_id, limit, offset], row_to_job)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(jobs)
+}
+
+/// Mark a queued job as running and count the attempt
+///
+/// Returns false if the job is no longer queued (it was cancelled).
+pub fn mark_job_running(conn: &Connection, id: &str, started_at: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE jobs SET status = 'running', started_at = ?1, attempts = attempts + 1
+         WHERE id = ?2 AND status = 'queued'",
+        params![started_at, id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Refresh the heartbeat of every unfinished job leased to `lease_owner`
+pub fn renew_job_leases(conn: &Connection, lease_owner: &str, now: i64) -> Result<usize> {
+    conn.execute(
+        "UPDATE jobs SET heartbeat_at = ?1
+         WHERE lease_owner = ?2 AND status IN ('queued', 'running')",
+        params![now, lease_owner],
+    )
+}
+
+/// Queued or running jobs whose lease was last renewed before `expired_before`
+pub fn get_expired_jobs(conn: &Connection, expired_before: i64) -> Result<Vec<Job>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM jobs
+         WHERE status IN ('queued', 'running')
+           AND (heartbeat_at IS NULL OR heartbeat_at < ?1)
+         ORDER BY created_at",
+        JOB_COLUMNS
+    ))?;
+    let jobs = stmt
+        .query_map(params![expired_before], row_to_job)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(jobs)
+}
+
+/// Take over the expired lease of a job and queue it again
+///
+/// Returns false if the job finished or its lease was renewed in the meantime.
+pub fn requeue_expired_job(
+    conn: &Connection,
+    id: &str,
+    lease_owner: &str,
+    now: i64,
+    expired_before: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE jobs SET status = 'queued', lease_owner = ?1, heartbeat_at = ?2
+         WHERE id = ?3 AND status IN ('queued', 'running')
+           AND (heartbeat_at IS NULL OR heartbeat_at < ?4)",
+        params![lease_owner, now, id, expired_before],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Release the expired lease of a running job, leaving it for a manual retry
+pub fn interrupt_expired_job(
+    conn: &Connection,
+    id: &str,
+    error: &AppError,
+    expired_before: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE jobs SET status = 'interrupted', lease_owner = NULL,
+                         error = ?1, error_code = ?2, retriable = ?3
+         WHERE id = ?4 AND status = 'running'
+           AND (heartbeat_at IS NULL OR heartbeat_at < ?5)",
+        params![error.message, error.code.as_str(), error.retriable, id, expired_before],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Queue an interrupted job again for one more attempt
+pub fn retry_job(conn: &Connection, id: &str, lease_owner: &str, now: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE jobs SET status = 'queued', lease_owner = ?1, heartbeat_at = ?2,
+                         max_attempts = attempts + 1, progress = 0, progress_message = NULL,
+                         error = NULL, error_code = NULL, retriable = 0, finished_at = NULL
+         WHERE id = ?3 AND status = 'interrupted'",
+        params![lease_owner, now, id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Update a job's progress
+pub fn update_job_progress(
+    conn: &Connection,
+    id: &str,
+    progress: f64,
+    message: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE jobs SET progress = ?1, progress_message = ?2 WHERE id = ?3",
+        params![progress, message, id],
+    )?;
+    Ok(())
+}
+
+/// Move a job into a terminal state (completed, failed or cancelled)
+///
+/// Jobs that already reached a terminal state are left untouched. Interrupted
+/// jobs can only be cancelled.
+pub fn finish_job(
+    conn: &Connection,
+    id: &str,
+    status: &str,
+    result: Option<&str>,
+    error: Option<&AppError>,
+    finished_at: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE jobs SET status = ?1, result = ?2, error = ?3, error_code = ?4, retriable = ?5,
+                         finished_at = ?6,
+                         progress = CASE WHEN ?1 = 'completed' THEN 1.0 ELSE progress END
+         WHERE id = ?7 AND (status IN ('queued', 'running')
+                            OR (status = 'interrupted' AND ?1 = 'cancelled'))",
+        params![
+            status,
+            result,
+            error.map(|e| e.message.as_str()),
+            error.map(|e| e.code.as_str()),
+            error.is_some_and(|e| e.retriable),
+            finished_at,
+            id
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+// ============================================================================
+// Schedule Operations
+// ============================================================================
+
+const SCHEDULE_COLUMNS: &str = "id, plugin_name, function, input, interval_seconds, cron, source,
+                                enabled, next_run_at, last_run_at, last_job_id, created_at";
+
+fn row_to_schedule(row: &rusqlite::Row) -> Result<Schedule> {
+    Ok(Schedule {
+        id: row.get(0)?,
+        plugin_name: row.get(1)?,
+        function: row.get(2)?,
+        input: row.get(3)?,
+        interval_seconds: row.get(4)?,
+        cron: row.get(5)?,
+        source: row.get(6)?,
+        enabled: row.get(7)?,
+        next_run_at: row.get(8)?,
+        last_run_at: row.get(9)?,
+        last_job_id: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+/// Create a schedule
+pub fn create_schedule(conn: &Connection, schedule: &Schedule) -> Result<()> {
+    conn.execute(
+        "INSERT INTO schedules (id, plugin_name, function, input, interval_seconds, cron, source,
+                                enabled, next_run_at, last_run_at, last_job_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            schedule.id,
+            schedule.plugin_name,
+            schedule.function,
+            schedule.input,
+            schedule.interval_seconds,
+            schedule.cron,
+            schedule.source,
+            schedule.enabled,
+            schedule.next_run_at,
+            schedule.last_run_at,
+            schedule.last_job_id,
+            schedule.created_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// List schedules, optionally for a single plugin
+pub fn list_schedules(conn: &Connection, plugin_name: Option<&str>) -> Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM schedules
+         WHERE (?1 IS NULL OR plugin_name = ?1)
+         ORDER BY plugin_name, created_at",
+        SCHEDULE_COLUMNS
+    ))?;
+    let schedules = stmt
+        .query_map(params![plugin_name], row_to_schedule)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(schedules)
+}
+
+/// Get enabled schedules that are due at `now`
+pub fn get_due_schedules(conn: &Connection, now: i64) -> Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM schedules
+         WHERE enabled = 1 AND next_run_at <= ?1
+         ORDER BY next_run_at",
+        SCHEDULE_COLUMNS
+    ))?;
+    let schedules = stmt
+        .query_map(params![now], row_to_schedule)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(schedules)
+}
+
+/// Record a run and set the next run time
+pub fn record_schedule_run(
+    conn: &Connection,
+    id: &str,
+    ran_at: i64,
+    job_id: Option<&str>,
+    next_run_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE schedules SET last_run_at = ?1, last_job_id = ?2, next_run_at = ?3 WHERE id = ?4",
+        params![ran_at, job_id, next_run_at, id],
+    )?;
+    Ok(())
+}
+
+/// Enable or disable a schedule
+pub fn set_schedule_enabled(conn: &Connection, id: &str, enabled: bool) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE schedules SET enabled = ?1 WHERE id = ?2",
+        params![enabled, id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Delete a schedule
+pub fn delete_schedule(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM schedules WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Delete a plugin's schedules from one source ("manifest" or "api"), or all of them
+pub fn delete_plugin_schedules(conn: &Connection, plugin_name: &str, source: Option<&str>) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM schedules WHERE plugin_name = ?1 AND (?2 IS NULL OR source = ?2)",
+        params![plugin_name, source],
+    )
+}
+
+// ============================================================================
+// Plugin Checksum Operations
+// ============================================================================
+
+/// Get the recorded SHA-256 of a plugin's WASM module
+pub fn get_plugin_checksum(conn: &Connection, plugin_name: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT wasm_sha256 FROM plugin_checksums WHERE plugin_name = ?1",
+        params![plugin_name],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Record the SHA-256 of a plugin's WASM module
+pub fn set_plugin_checksum(conn: &Connection, plugin_name: &str, sha256: &str, recorded_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_checksums (plugin_name, wasm_sha256, recorded_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(plugin_name) DO UPDATE SET wasm_sha256 = excluded.wasm_sha256,
+                                                recorded_at = excluded.recorded_at",
+        params![plugin_name, sha256, recorded_at],
+    )?;
+    Ok(())
+}
+
+/// Forget a plugin's recorded checksum
+pub fn delete_plugin_checksum(conn: &Connection, plugin_name: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM plugin_checksums WHERE plugin_name = ?1",
+        params![plugin_name],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Plugin Signer Operations
+// ============================================================================
+
+/// Get the public key a plugin was signed with when it was first installed
+pub fn get_plugin_signer(conn: &Connection, plugin: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT public_key FROM plugin_signers WHERE plugin = ?1",
+        params![plugin],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Record the public key a plugin was signed with, unless one is recorded
+/// already
+pub fn set_plugin_signer(conn: &Connection, plugin: &str, public_key: &str, recorded_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_signers (plugin, public_key, recorded_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(plugin) DO NOTHING",
+        params![plugin, public_key, recorded_at],
+    )?;
+    Ok(())
+}
+
+/// Forget the key a plugin was signed with
+pub fn delete_plugin_signer(conn: &Connection, plugin: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_signers WHERE plugin = ?1", params![plugin])?;
+    Ok(())
+}
+
+// ============================================================================
+// Plugin Config Operations
+// ============================================================================
+
+/// Get a plugin's config overrides
+pub fn get_plugin_config(conn: &Connection, plugin_name: &str) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT key, value FROM plugin_config WHERE plugin_name = ?1")?;
+    let config = stmt
+        .query_map(params![plugin_name], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<String, String>>>()?;
+    Ok(config)
+}
+
+/// Set one config override of a plugin
+pub fn set_plugin_config_value(
+    conn: &Connection,
+    plugin_name: &str,
+    key: &str,
+    value: &str,
+    updated_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_config (plugin_name, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(plugin_name, key) DO UPDATE SET value = excluded.value,
+                                                     updated_at = excluded.updated_at",
+        params![plugin_name, key, value, updated_at],
+    )?;
+    Ok(())
+}
+
+/// Remove one config override of a plugin
+pub fn delete_plugin_config_value(conn: &Connection, plugin_name: &str, key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM plugin_config WHERE plugin_name = ?1 AND key = ?2",
+        params![plugin_name, key],
+    )?;
+    Ok(())
+}
+
+/// Remove all config overrides of a plugin
+pub fn delete_plugin_config(conn: &Connection, plugin_name: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_config WHERE plugin_name = ?1", params![plugin_name])?;
+    Ok(())
+}
+
+// ============================================================================
+// Plugin Capability Operations
+// ============================================================================
+
+/// Get the capability decisions of a plugin, as capability -> granted
+pub fn get_plugin_capabilities(conn: &Connection, plugin_name: &str) -> Result<HashMap<String, bool>> {
+    let mut stmt = conn.prepare("SELECT capability, granted FROM plugin_capabilities WHERE plugin_name = ?1")?;
+    let decisions = stmt
+        .query_map(params![plugin_name], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<String, bool>>>()?;
+    Ok(decisions)
+}
+
+/// Record whether a plugin may use a capability
+pub fn set_plugin_capability(
+    conn: &Connection,
+    plugin_name: &str,
+    capability: &str,
+    granted: bool,
+    decided_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_capabilities (plugin_name, capability, granted, decided_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(plugin_name, capability) DO UPDATE SET granted = excluded.granted,
+                                                            decided_at = excluded.decided_at",
+        params![plugin_name, capability, granted, decided_at],
+    )?;
+    Ok(())
+}
+
+/// Forget all capability decisions of a plugin
+pub fn delete_plugin_capabilities(conn: &Connection, plugin_name: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_capabilities WHERE plugin_name = ?1", params![plugin_name])?;
+    Ok(())
+}
+
+// ============================================================================
+// Role Operations
+// ============================================================================
+
+/// Role that may run privileged commands
+pub const ROLE_ADMIN: &str = "admin";
+
+/// Get the roles granted to a user
+pub fn get_user_roles(conn: &Connection, user_uuid: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT role FROM user_roles WHERE user_uuid = ?1 ORDER BY role")?;
+    let roles = stmt
+        .query_map(params![user_uuid], |row| row.get(0))?
+        .collect::<Result<Vec<String>>>()?;
+    Ok(roles)
+}
+
+/// Grant a role to a user
+pub fn grant_user_role(conn: &Connection, user_uuid: &str, role: &str, granted_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO user_roles (user_uuid, role, granted_at) VALUES (?1, ?2, ?3)",
+        params![user_uuid, role, granted_at],
+    )?;
+    Ok(())
+}
+
+/// Revoke a role from a user
+pub fn revoke_user_role(conn: &Connection, user_uuid: &str, role: &str) -> Result<bool> {
+    let revoked = conn.execute(
+        "DELETE FROM user_roles WHERE user_uuid = ?1 AND role = ?2",
+        params![user_uuid, role],
+    )?;
+    Ok(revoked > 0)
+}
+
+/// Count users holding a role
+pub fn count_users_with_role(conn: &Connection, role: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM user_roles WHERE role = ?1",
+        params![role],
+        |row| row.get(0),
+    )
+}
+
+/// Grant a role to a user if nobody holds it yet; returns whether it was granted
+pub fn grant_role_if_unclaimed(conn: &Connection, user_uuid: &str, role: &str, granted_at: i64) -> Result<bool> {
+    let granted = conn.execute(
+        "INSERT INTO user_roles (user_uuid, role, granted_at)
+         SELECT ?1, ?2, ?3 WHERE NOT EXISTS (SELECT 1 FROM user_roles WHERE role = ?2)",
+        params![user_uuid, role, granted_at],
+    )?;
+    Ok(granted > 0)
+}
+
+// ============================================================================
+// Service Account Operations
+// ============================================================================
+
+/// Create a service account: a password-less user plus its account record
+pub fn create_service_account(
+    conn: &Connection,
+    uuid: &str,
+    name: &str,
+    email: &str,
+    description: Option<&str>,
+    created_by: Option<&str>,
+    created_at: i64,
+) -> Result<()> {
+    create_user(conn, uuid, name, email, "", created_at)?;
+    conn.execute(
+        "INSERT INTO service_accounts (user_uuid, description, created_by) VALUES (?1, ?2, ?3)",
+        params![uuid, description, created_by],
+    )?;
+    Ok(())
+}
+
+const SERVICE_ACCOUNT_COLUMNS: &str =
+    "SELECT u.uuid, u.name, s.description, s.created_by, s.disabled, u.created_at,
+            (SELECT k.id FROM api_keys k
+             WHERE k.user_uuid = u.uuid AND k.revoked_at IS NULL
+             ORDER BY k.created_at DESC LIMIT 1)
+     FROM service_accounts s JOIN users u ON u.uuid = s.user_uuid";
+
+fn service_account_from_row(row: &rusqlite::Row) -> Result<ServiceAccount> {
+    Ok(ServiceAccount {
+        uuid: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        created_by: row.get(3)?,
+        disabled: row.get(4)?,
+        created_at: row.get(5)?,
+        active_key_id: row.get(6)?,
+    })
+}
+
+/// Get a service account by user UUID
+pub fn get_service_account(conn: &Connection, uuid: &str) -> Result<Option<ServiceAccount>> {
+    let mut stmt = conn.prepare(&format!("{} WHERE s.user_uuid = ?1", SERVICE_ACCOUNT_COLUMNS))?;
+    stmt.query_row(params![uuid], service_account_from_row).optional()
+}
+
+/// List all service accounts
+pub fn list_service_accounts(conn: &Connection) -> Result<Vec<ServiceAccount>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY u.name", SERVICE_ACCOUNT_COLUMNS))?;
+    let accounts = stmt
+        .query_map([], service_account_from_row)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(accounts)
+}
+
+/// Whether a user is a service account
+pub fn is_service_account(conn: &Connection, uuid: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM service_accounts WHERE user_uuid = ?1)",
+        params![uuid],
+        |row| row.get(0),
+    )
+}
+
+/// Enable or disable a service account
+pub fn set_service_account_disabled(conn: &Connection, uuid: &str, disabled: bool) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE service_accounts SET disabled = ?1 WHERE user_uuid = ?2",
+        params![disabled, uuid],
+    )?;
+    Ok(updated > 0)
+}
+
+// ============================================================================
+// API Key Operations
+// ============================================================================
+
+/// Store a new API key
+pub fn create_api_key(
+    conn: &Connection,
+    id: &str,
+    user_uuid: &str,
+    key_hash: &str,
+    created_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO api_keys (id, user_uuid, key_hash, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![id, user_uuid, key_hash, created_at],
+    )?;
+    Ok(())
+}
+
+/// Get an API key by ID
+pub fn get_api_key(conn: &Connection, id: &str) -> Result<Option<ApiKey>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_uuid, key_hash, created_at, last_used_at, revoked_at
+         FROM api_keys WHERE id = ?1"
+    )?;
+    
+    stmt.query_row(params![id], |row| {
+        Ok(ApiKey {
+            id: row.get(0)?,
+            user_uuid: row.get(1)?,
+            key_hash: row.get(2)?,
+            created_at: row.get(3)?,
+            last_used_at: row.get(4)?,
+            revoked_at: row.get(5)?,
+        })
+    }).optional()
+}
+
+/// Revoke every active key of a user
+pub fn revoke_api_keys(conn: &Connection, user_uuid: &str, revoked_at: i64) -> Result<usize> {
+    conn.execute(
+        "UPDATE api_keys SET revoked_at = ?1 WHERE user_uuid = ?2 AND revoked_at IS NULL",
+        params![revoked_at, user_uuid],
+    )
+}
+
+/// Record that an API key was used
+pub fn touch_api_key(conn: &Connection, id: &str, used_at: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE api_keys SET last_used_at = ?1 WHERE id = ?2",
+        params![used_at, id],
+    )?;
+    Ok(())
+}
+
+// ============================================================================
+// Trash Operations
+// ============================================================================
+
+const TRASHED_FILE_COLUMNS: &str = "id, original_path, trash_path, plugin, size, trashed_at";
+
+fn trashed_file_from_row(row: &rusqlite::Row) -> Result<TrashedFile> {
+    Ok(TrashedFile {
+        id: row.get(0)?,
+        original_path: row.get(1)?,
+        trash_path: row.get(2)?,
+        plugin: row.get(3)?,
+        size: row.get(4)?,
+        trashed_at: row.get(5)?,
+    })
+}
+
+/// Record a file moved to the trash
+pub fn create_trashed_file(conn: &Connection, file: &TrashedFile) -> Result<()> {
+    conn.execute(
+        "INSERT INTO trashed_files (id, original_path, trash_path, plugin, size, trashed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![file.id, file.original_path, file.trash_path, file.plugin, file.size, file.trashed_at],
+    )?;
+    Ok(())
+}
+
+/// Get a trashed file by ID
+pub fn get_trashed_file(conn: &Connection, id: &str) -> Result<Option<TrashedFile>> {
+    let sql = format!("SELECT {} FROM trashed_files WHERE id = ?1", TRASHED_FILE_COLUMNS);
+    conn.query_row(&sql, params![id], trashed_file_from_row).optional()
+}
+
+/// Get trashed files, newest first
+pub fn list_trashed_files(conn: &Connection) -> Result<Vec<TrashedFile>> {
+    let sql = format!("SELECT {} FROM trashed_files ORDER BY trashed_at DESC", TRASHED_FILE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let files = stmt.query_map([], trashed_file_from_row)?.collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// Get files trashed before `cutoff`
+pub fn get_trashed_files_before(conn: &Connection, cutoff: i64) -> Result<Vec<TrashedFile>> {
+    let sql = format!("SELECT {} FROM trashed_files WHERE trashed_at < ?1", TRASHED_FILE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let files = stmt
+        .query_map(params![cutoff], trashed_file_from_row)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// Forget a trashed file, after it was restored or purged
+pub fn delete_trashed_file(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute("DELETE FROM trashed_files WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+// ============================================================================
+// Plugin Source Operations
+// ============================================================================
+
+const PLUGIN_SOURCE_COLUMNS: &str = "plugin, url, source_url, etag, last_modified, fetched_at";
+
+fn plugin_source_from_row(row: &rusqlite::Row) -> Result<PluginSource> {
+    Ok(PluginSource {
+        plugin: row.get(0)?,
+        url: row.get(1)?,
+        source_url: row.get(2)?,
+        etag: row.get(3)?,
+        last_modified: row.get(4)?,
+        fetched_at: row.get(5)?,
+    })
+}
+
+/// Replace the recorded sources of a plugin
+pub fn replace_plugin_sources(conn: &Connection, plugin: &str, sources: &[PluginSource]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM plugin_sources WHERE plugin = ?1", params![plugin])?;
+    for source in sources {
+        tx.execute(
+            "INSERT INTO plugin_sources (plugin, url, source_url, etag, last_modified, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![plugin, source.url, source.source_url, source.etag, source.last_modified, source.fetched_at],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Get the sources of a plugin
+pub fn get_plugin_sources(conn: &Connection, plugin: &str) -> Result<Vec<PluginSource>> {
+    let sql = format!("SELECT {} FROM plugin_sources WHERE plugin = ?1 ORDER BY url", PLUGIN_SOURCE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let sources = stmt.query_map(params![plugin], plugin_source_from_row)?.collect::<Result<Vec<_>>>()?;
+    Ok(sources)
+}
+
+/// Get the sources of every plugin installed from a URL
+pub fn list_plugin_sources(conn: &Connection) -> Result<Vec<PluginSource>> {
+    let sql = format!("SELECT {} FROM plugin_sources ORDER BY plugin, url", PLUGIN_SOURCE_COLUMNS);
+    let mut stmt = conn.prepare(&sql)?;
+    let sources = stmt.query_map([], plugin_source_from_row)?.collect::<Result<Vec<_>>>()?;
+    Ok(sources)
+}
+
+/// Get the plugin last installed from `source_url`
+pub fn get_plugin_by_source_url(conn: &Connection, source_url: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT plugin FROM plugin_sources WHERE source_url = ?1 ORDER BY fetched_at DESC LIMIT 1",
+        params![source_url],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Forget the sources of a plugin
+pub fn delete_plugin_sources(conn: &Connection, plugin: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_sources WHERE plugin = ?1", params![plugin])?;
+    Ok(())
+}
+
+// ============================================================================
+// Execution Output Operations
+// ============================================================================
+
+const EXECUTION_OUTPUT_COLUMNS: &str = "execution_id, path, sha256, size, written_at";
+
+fn execution_output_from_row(row: &rusqlite::Row) -> Result<ExecutionOutput> {
+    Ok(ExecutionOutput {
+        execution_id: row.get(0)?,
+        path: row.get(1)?,
+        sha256: row.get(2)?,
+        size: row.get(3)?,
+        written_at: row.get(4)?,
+    })
+}
+
+/// Record a file written during an execution, replacing an earlier write to the same path
+pub fn record_execution_output(conn: &Connection, output: &ExecutionOutput) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO execution_outputs (execution_id, path, sha256, size, written_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![output.execution_id, output.path, output.sha256, output.size, output.written_at],
+    )?;
+    Ok(())
+}
+
+/// Get the files written during an execution, oldest first
+pub fn list_execution_outputs(conn: &Connection, execution_id: &str) -> Result<Vec<ExecutionOutput>> {
+    let sql = format!(
+        "SELECT {} FROM execution_outputs WHERE execution_id = ?1 ORDER BY written_at, path",
+        EXECUTION_OUTPUT_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let outputs = stmt.query_map(params![execution_id], execution_output_from_row)?.collect::<Result<Vec<_>>>()?;
+    Ok(outputs)
+}
+
+/// Forget the files of an execution, e.g. before a job is run again
+pub fn delete_execution_outputs(conn: &Connection, execution_id: &str) -> Result<()> {
+    conn.execute("DELETE FROM execution_outputs WHERE execution_id = ?1", params![execution_id])?;
+    Ok(())
+}
+
+// ============================================================================
+// Execution Trace Operations
+// ============================================================================
+
+/// Store an execution's trace, replacing an earlier one with its ID, and
+/// delete all but the newest `keep` traces
+pub fn save_execution_trace(conn: &Connection, trace: &ExecutionTrace, keep: usize) -> Result<()> {
+    let json = serde_json::to_string(trace).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO execution_traces (execution_id, plugin, function, started_at, trace)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![trace.execution_id, trace.plugin, trace.function, trace.started_at, json],
+    )?;
+    conn.execute(
+        "DELETE FROM execution_traces WHERE execution_id NOT IN
+         (SELECT execution_id FROM execution_traces ORDER BY started_at DESC LIMIT ?1)",
+        params![keep as i64],
+    )?;
+    Ok(())
+}
+
+/// Get the stored trace of an execution
+pub fn get_execution_trace(conn: &Connection, execution_id: &str) -> Result<Option<ExecutionTrace>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT trace FROM execution_traces WHERE execution_id = ?1",
+            params![execution_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    json.map(|json| {
+        serde_json::from_str(&json)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+    })
+    .transpose()
+}
+
+// ============================================================================
+// Trusted Author Operations
+// ============================================================================
+
+fn trusted_author_from_row(row: &rusqlite::Row) -> Result<TrustedAuthor> {
+    Ok(TrustedAuthor {
+        public_key: row.get(0)?,
+        name: row.get(1)?,
+        added_at: row.get(2)?,
+    })
+}
+
+/// Add an author to the trust store, renaming it if it is already there
+pub fn add_trusted_author(conn: &Connection, author: &TrustedAuthor) -> Result<()> {
+    conn.execute(
+        "INSERT INTO trusted_authors (public_key, name, added_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(public_key) DO UPDATE SET name = excluded.name",
+        params![author.public_key, author.name, author.added_at],
+    )?;
+    Ok(())
+}
+
+/// Get a trusted author by public key
+pub fn get_trusted_author(conn: &Connection, public_key: &str) -> Result<Option<TrustedAuthor>> {
+    conn.query_row(
+        "SELECT public_key, name, added_at FROM trusted_authors WHERE public_key = ?1",
+        params![public_key],
+        trusted_author_from_row,
+    )
+    .optional()
+}
+
+/// Get the trust store, oldest first
+pub fn list_trusted_authors(conn: &Connection) -> Result<Vec<TrustedAuthor>> {
+    let mut stmt = conn.prepare("SELECT public_key, name, added_at FROM trusted_authors ORDER BY added_at, name")?;
+    let authors = stmt
+        .query_map([], trusted_author_from_row)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(authors)
+}
+
+/// Remove an author from the trust store; false if it was not there
+pub fn delete_trusted_author(conn: &Connection, public_key: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM trusted_authors WHERE public_key = ?1", params![public_key])?;
+    Ok(deleted > 0)
+}
+
+// ============================================================================
+// Plugin Key-Value Operations
+// ============================================================================
+
+/// Get a value a plugin stored, as JSON
+pub fn kv_get(conn: &Connection, plugin_name: &str, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM plugin_kv WHERE plugin_name = ?1 AND key = ?2",
+        params![plugin_name, key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Store a value for a plugin, replacing any value under the same key
+pub fn kv_set(conn: &Connection, plugin_name: &str, key: &str, value: &str, updated_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_kv (plugin_name, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(plugin_name, key) DO UPDATE SET value = excluded.value,
+                                                     updated_at = excluded.updated_at",
+        params![plugin_name, key, value, updated_at],
+    )?;
+    Ok(())
+}
+
+/// Remove a value a plugin stored; false if there was none
+pub fn kv_delete(conn: &Connection, plugin_name: &str, key: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM plugin_kv WHERE plugin_name = ?1 AND key = ?2",
+        params![plugin_name, key],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Keys a plugin stored that start with `prefix`, in order, after `after` if given
+pub fn kv_list(
+    conn: &Connection,
+    plugin_name: &str,
+    prefix: &str,
+    after: Option<&str>,
+    limit: i64,
+) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT key FROM plugin_kv
+         WHERE plugin_name = ?1 AND substr(key, 1, length(?2)) = ?2 AND (?3 IS NULL OR key > ?3)
+         ORDER BY key LIMIT ?4",
+    )?;
+    let keys = stmt
+        .query_map(params![plugin_name, prefix, after, limit], |row| row.get(0))?
+        .collect::<Result<Vec<String>>>()?;
+    Ok(keys)
+}
+
+/// Number of keys a plugin stores, and bytes of keys and values
+pub fn kv_usage(conn: &Connection, plugin_name: &str) -> Result<(i64, i64)> {
+    conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(length(CAST(key AS BLOB)) + length(CAST(value AS BLOB))), 0)
+         FROM plugin_kv WHERE plugin_name = ?1",
+        params![plugin_name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Remove everything a plugin stored
+pub fn delete_plugin_kv(conn: &Connection, plugin_name: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_kv WHERE plugin_name = ?1", params![plugin_name])?;
+    Ok(())
+}
+
+// ============================================================================
+// Plugin Key Operations
+// ============================================================================
+
+/// Store a key a plugin generated or imported
+pub fn create_plugin_key(
+    conn: &Connection,
+    plugin_name: &str,
+    key_id: &str,
+    algorithm: &str,
+    material: &[u8],
+    created_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO plugin_keys (plugin_name, key_id, algorithm, material, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![plugin_name, key_id, algorithm, material, created_at],
+    )?;
+    Ok(())
+}
+
+/// A plugin's key as its algorithm and material
+pub fn get_plugin_key(conn: &Connection, plugin_name: &str, key_id: &str) -> Result<Option<(String, Vec<u8>)>> {
+    conn.query_row(
+        "SELECT algorithm, material FROM plugin_keys WHERE plugin_name = ?1 AND key_id = ?2",
+        params![plugin_name, key_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Remove one of a plugin's keys; false if there was none
+pub fn delete_plugin_key(conn: &Connection, plugin_name: &str, key_id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM plugin_keys WHERE plugin_name = ?1 AND key_id = ?2",
+        params![plugin_name, key_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Number of keys a plugin holds
+pub fn count_plugin_keys(conn: &Connection, plugin_name: &str) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM plugin_keys WHERE plugin_name = ?1", params![plugin_name], |row| row.get(0))
+}
+
+/// Remove every key a plugin holds
+pub fn delete_plugin_keys(conn: &Connection, plugin_name: &str) -> Result<()> {
+    conn.execute("DELETE FROM plugin_keys WHERE plugin_name = ?1", params![plugin_name])?;
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+
+/// User record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub uuid: String,
+    pub name: String,
+    pub email: String,
+    pub password_hash: String,
+    pub email_verified: bool,
+    pub avatar: Option<String>,
+    pub bio: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Set for accounts created with a temporary password
+    #[serde(default)]
+    pub password_reset_required: bool,
+}
+
+/// Order users are listed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    Name,
+    Email,
+    #[default]
+    CreatedAt,
+}
+
+impl UserSort {
+    pub fn column(self) -> &'static str {
+        match self {
+            UserSort::Name => "name",
+            UserSort::Email => "email",
+            UserSort::CreatedAt => "created_at",
+        }
+    }
+}
+
+/// One page of a user listing, with how many users match in all
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    pub total: i64,
+}
+
+/// Rows removed along with a deleted user, by table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedUserRows {
+    pub sessions: usize,
+    pub email_verification_tokens: usize,
+    pub password_reset_tokens: usize,
+    pub roles: usize,
+    pub api_keys: usize,
+    /// Zero when the audit logs were kept
+    pub audit_logs: usize,
+}
+
+/// Session record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_uuid: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Email verification token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailVerificationToken {
+    pub token: String,
+    pub user_uuid: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Password reset token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordResetToken {
+    pub token: String,
+    pub user_uuid: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLog {
+    pub id: String,
+    pub user_uuid: String,
+    pub action: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub metadata: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+}
+
+/// Outbound HTTP request made by a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EgressLog {
+    pub id: i64,
+    pub plugin: String,
+    pub method: String,
+    pub host: String,
+    /// URL path, without the query string
+    pub path: String,
+    /// None if the request was refused or failed before a response arrived
+    pub status: Option<i64>,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+/// Background plugin job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub plugin_name: String,
+    pub function: String,
+    pub input: String,
+    pub status: String,
+    pub progress: f64,
+    pub progress_message: Option<String>,
+    /// Output of a completed job; base64 for binary entry points
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// `ErrorCode` of a failed job
+    pub error_code: Option<String>,
+    /// Whether a failed job may succeed if run again
+    pub retriable: bool,
+    /// Instance of the application holding the job's lease
+    pub lease_owner: Option<String>,
+    /// Last time the lease owner confirmed it is still working on the job
+    pub heartbeat_at: Option<i64>,
+    /// Times the job has started running
+    pub attempts: i32,
+    /// Times the job may be started before an interruption needs a manual retry
+    pub max_attempts: i32,
+    /// User who submitted the job; None for jobs the host submits itself
+    pub owner_id: Option<String>,
+    pub created_at: i64,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+}
+
+/// Recurring plugin execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub plugin_name: String,
+    pub function: String,
+    pub input: String,
+    pub interval_seconds: Option<i64>,
+    pub cron: Option<String>,
+    /// "manifest" or "api"
+    pub source: String,
+    pub enabled: bool,
+    pub next_run_at: i64,
+    pub last_run_at: Option<i64>,
+    pub last_job_id: Option<String>,
+    pub created_at: i64,
+}
+
+/// Non-interactive account that authenticates with API keys only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub uuid: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// UUID of the user who created it
+    pub created_by: Option<String>,
+    pub disabled: bool,
+    pub created_at: i64,
+    /// ID of the current, unrevoked API key
+    pub active_key_id: Option<String>,
+}
+
+/// API key of a service account; only a hash of the secret is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_uuid: String,
+    pub key_hash: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+/// A file moved to the trash, restorable until the retention period ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedFile {
+    pub id: String,
+    pub original_path: String,
+    /// Where the file is kept inside the trash directory
+    pub trash_path: String,
+    /// Plugin that deleted or overwrote the file
+    pub plugin: Option<String>,
+    pub size: i64,
+    pub trashed_at: i64,
+}
+
+/// A file a plugin produced through `write_output_file` during an execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionOutput {
+    pub execution_id: String,
+    pub path: String,
+    /// Hash of the data the plugin produced, even if the policy kept an
+    /// existing file instead
+    pub sha256: String,
+    pub size: i64,
+    pub written_at: i64,
+}
+
+/// One host function call a plugin made during an execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCallTrace {
+    /// Plugin that made the call; differs from the execution's for calls
+    /// made by plugins it called through `call_plugin`
+    pub plugin: String,
+    pub function: String,
+    /// When the call started, in microseconds since the execution started
+    pub started_us: u64,
+    pub duration_us: u64,
+    /// Bytes of the arguments and the result passed through plugin memory
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Why the call failed, whether it aborted the plugin or returned an
+    /// error response
+    pub error: Option<String>,
+}
+
+/// The host function calls made during an execution, in the order they started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    pub execution_id: String,
+    pub plugin: String,
+    pub function: String,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+    pub duration_us: u64,
+    pub error: Option<String>,
+    pub calls: Vec<HostCallTrace>,
+    /// Calls left out because the execution made more than the trace keeps
+    pub dropped_calls: u64,
+}
+
+/// A URL a remote plugin was downloaded from, with the validators the server
+/// sent for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginSource {
+    pub plugin: String,
+    /// The manifest or module URL that was fetched
+    pub url: String,
+    /// URL the plugin was installed from; `url` itself or the manifest that
+    /// referenced it
+    pub source_url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: i64,
+}
+
+/// An author whose signed plugins install without quarantine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedAuthor {
+    /// Base64 of the author's Ed25519 public key
+    pub public_key: String,
+    pub name: String,
+    pub added_at: i64,
+}
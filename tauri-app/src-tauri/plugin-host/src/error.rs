@@ -0,0 +1,192 @@
+//! Structured errors surfaced to the frontend and to callers of plugins
+//!
+//! A plugin reports a failure callers can act on by returning an error whose
+//! message is a JSON object:
+//!
+//! ```json
+//! {"code": "invalid_input", "message": "Count must be between 1 and 100", "retriable": false}
+//! ```
+//!
+//! With extism-pdk that is `Err(WithReturnCode::new(Error::msg(json), status))`.
+//! Extism does not pass the status of calls made with a host context back to
+//! the host, so the code travels in the JSON. `retriable` may be omitted, in
+//! which case it follows the code. Errors with a plain-text message map to
+//! `plugin_error`, and failures of the host itself to `internal`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Category of a failure, telling callers whether and how to react
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The input was rejected; retrying it unchanged will fail again
+    InvalidInput,
+    NotFound,
+    Unauthorized,
+    Conflict,
+    Timeout,
+    /// A dependency (network, service, resource) is temporarily unavailable
+    Unavailable,
+    RateLimited,
+    Cancelled,
+    /// A call went over one of the plugin's resource quotas
+    QuotaExceeded,
+    /// A plugin failed without saying why in a structured way
+    PluginError,
+    Internal,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::InvalidInput,
+        ErrorCode::NotFound,
+        ErrorCode::Unauthorized,
+        ErrorCode::Conflict,
+        ErrorCode::Timeout,
+        ErrorCode::Unavailable,
+        ErrorCode::RateLimited,
+        ErrorCode::Cancelled,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::PluginError,
+        ErrorCode::Internal,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidInput => "invalid_input",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::Unavailable => "unavailable",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::Cancelled => "cancelled",
+            ErrorCode::QuotaExceeded => "quota_exceeded",
+            ErrorCode::PluginError => "plugin_error",
+            ErrorCode::Internal => "internal",
+        }
+    }
+
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_str() == code)
+    }
+
+    /// Whether failures with this code are transient unless the plugin says otherwise
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, ErrorCode::Timeout | ErrorCode::Unavailable | ErrorCode::RateLimited)
+    }
+}
+
+/// An error as seen by the frontend and the job runner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Plugin that failed, for errors raised by a plugin call
+    pub plugin: Option<String>,
+    pub function: Option<String>,
+    /// Whether the same call may succeed if tried again
+    pub retriable: bool,
+    /// The quota a call went over, for `quota_exceeded` errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaViolation>,
+}
+
+/// A resource quota a plugin declares in its manifest's `quotas`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quota {
+    ExecutionTime,
+    OutputSize,
+    ConcurrentCalls,
+}
+
+/// How a call went over a quota; amounts are in milliseconds, bytes or calls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaViolation {
+    pub quota: Quota,
+    pub limit: u64,
+    /// What the call used, or would have
+    pub actual: u64,
+}
+
+/// Structured error a plugin returns as its error message
+#[derive(Deserialize)]
+struct PluginErrorPayload {
+    code: String,
+    message: String,
+    retriable: Option<bool>,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            plugin: None,
+            function: None,
+            retriable: code.is_retriable(),
+            quota: None,
+        }
+    }
+
+    /// A call went over a quota; only the concurrency limit is worth retrying
+    pub fn quota_exceeded(quota: Quota, limit: u64, actual: u64) -> Self {
+        let message = match quota {
+            Quota::ExecutionTime => format!("Call ran for {} ms, over the plugin's limit of {} ms", actual, limit),
+            Quota::OutputSize => format!("Output of {} bytes is over the plugin's limit of {} bytes", actual, limit),
+            Quota::ConcurrentCalls => format!("Plugin already has {} call(s) in flight, its limit", limit),
+        };
+        Self {
+            retriable: quota == Quota::ConcurrentCalls,
+            quota: Some(QuotaViolation { quota, limit, actual }),
+            ..Self::new(ErrorCode::QuotaExceeded, message)
+        }
+    }
+
+    /// Map a failed call of `plugin`'s `function` into an error
+    ///
+    /// The innermost error carries the plugin's own message, which is parsed
+    /// as a structured payload when it is one.
+    pub fn from_plugin(plugin: &str, function: &str, error: &anyhow::Error) -> Self {
+        let payload = serde_json::from_str::<PluginErrorPayload>(&error.root_cause().to_string()).ok();
+        let mut app_error = match payload {
+            Some(payload) => {
+                let code = ErrorCode::parse(&payload.code).unwrap_or_else(|| {
+                    tracing::debug!("Plugin '{}' returned unknown error code '{}'", plugin, payload.code);
+                    ErrorCode::PluginError
+                });
+                let mut app_error = Self::new(code, payload.message);
+                app_error.retriable = payload.retriable.unwrap_or(app_error.retriable);
+                app_error
+            }
+            None => Self::new(ErrorCode::PluginError, format!("{:#}", error)),
+        };
+        app_error.plugin = Some(plugin.to_string());
+        app_error.function = Some(function.to_string());
+        app_error
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.plugin, &self.function) {
+            (Some(plugin), Some(function)) => write!(f, "{}::{}: {}", plugin, function, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<anyhow::Error> for AppError {
+    /// Recover a structured error raised further down, or treat it as internal
+    fn from(error: anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<AppError>())
+            .cloned()
+            .unwrap_or_else(|| Self::new(ErrorCode::Internal, format!("{:#}", error)))
+    }
+}
@@ -0,0 +1,104 @@
+//! Host events plugins subscribe to in their manifest
+//!
+//! Events are published on a process-wide channel: [`TICK`] by the app's
+//! tick loop, database changes such as [`USER_CREATED`] by the host functions
+//! that make them, and app events such as [`PLUGIN_INSTALLED`] by the plugin
+//! manager. Embedders can publish events of their own. [`route_events`] hands
+//! each one to [`PluginManager::dispatch_event`], which calls the plugins
+//! whose manifest `subscriptions` match it.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::RwLock;
+
+use crate::plugins::{Capability, DbAccess, PluginManager};
+
+/// A tick of the app's tick loop; the payload has the tick number as `tick`
+pub const TICK: &str = "tick";
+
+/// A user was created; the payload has their `uuid`, `name` and `email`
+pub const USER_CREATED: &str = "user.created";
+
+/// A user's password, email verification or profile changed; the payload has their `uuid`
+pub const USER_UPDATED: &str = "user.updated";
+
+/// A user was deleted; the payload has their `uuid`
+pub const USER_DELETED: &str = "user.deleted";
+
+/// A user signed in; the payload has the session `id` and `user_uuid`
+pub const SESSION_CREATED: &str = "session.created";
+
+/// A session was deleted; the payload has its `id`
+pub const SESSION_DELETED: &str = "session.deleted";
+
+/// A plugin was installed or updated; the payload has its `plugin` ID and `version`
+pub const PLUGIN_INSTALLED: &str = "plugin.installed";
+
+/// A plugin was uninstalled; the payload has its `plugin` ID
+pub const PLUGIN_UNINSTALLED: &str = "plugin.uninstalled";
+
+static EVENTS: LazyLock<broadcast::Sender<HostEvent>> = LazyLock::new(|| broadcast::channel(256).0);
+
+/// Something that happened in the host, as passed to subscribed plugins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostEvent {
+    pub event: String,
+    /// Plugin whose host call caused the event; it is not called with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub payload: Value,
+    /// Unix timestamp (milliseconds)
+    pub timestamp: i64,
+}
+
+/// Publish an event to the plugins subscribed to it
+pub fn publish(event: &str, source: Option<&str>, payload: Value) {
+    let _ = EVENTS.send(HostEvent {
+        event: event.to_string(),
+        source: source.map(str::to_string),
+        payload,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    });
+}
+
+/// Receive every event published from now on
+pub fn subscribe() -> broadcast::Receiver<HostEvent> {
+    EVENTS.subscribe()
+}
+
+/// Whether a name can be subscribed to: lowercase letters, digits, `.`, `_` and `-`
+pub fn is_event_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Capability a plugin must declare to subscribe to an event, since the
+/// payload reveals what the capability guards
+pub fn required_capability(event: &str) -> Option<Capability> {
+    let read = |resource| Capability::Db {
+        resource: Some(resource),
+        access: DbAccess::Read,
+    };
+    match event {
+        TICK => Some(Capability::Tick),
+        USER_CREATED | USER_UPDATED | USER_DELETED => Some(read("users")),
+        SESSION_CREATED | SESSION_DELETED => Some(read("sessions")),
+        _ => None,
+    }
+}
+
+/// Call subscribed plugins with every published event until the channel closes
+pub async fn route_events(plugins: Arc<RwLock<PluginManager>>) -> Result<(), String> {
+    let mut events = subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => plugins.read().await.dispatch_event(&event).await,
+            Err(RecvError::Lagged(skipped)) => tracing::warn!("Missed {} host events", skipped),
+            Err(RecvError::Closed) => return Err("Host event channel closed".to_string()),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! Byte payloads passed between host functions and plugins
+//!
+//! Host functions that take or return bytes pass them as an Extism memory
+//! block holding exactly those bytes, the block's length being theirs, rather
+//! than as a JSON array or base64 inside a JSON document. Plugins built on the
+//! Rust PDK declare such parameters and results as `Vec<u8>`. Functions that
+//! may fail still answer in a JSON envelope, so byte results are only used
+//! where the call either succeeds or traps.
+
+use extism::{CurrentPlugin, Val};
+
+/// Bytes the plugin passed in `input`
+pub fn read_bytes(plugin: &mut CurrentPlugin, input: &Val) -> Result<Vec<u8>, extism::Error> {
+    plugin.memory_get_val(input)
+}
+
+/// Return `bytes` to the plugin through `output`
+pub fn write_bytes(plugin: &mut CurrentPlugin, output: &mut Val, bytes: &[u8]) -> Result<(), extism::Error> {
+    plugin.memory_set_val(output, bytes)
+}
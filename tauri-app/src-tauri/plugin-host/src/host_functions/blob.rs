@@ -0,0 +1,266 @@
+//! Files too large for plugin memory, read and written in chunks
+//!
+//! `blob_open` opens a file under one of the plugin's mounts, by the path
+//! the plugin sees it at, and returns a blob ID; `blob_read_chunk` and
+//! `blob_write_chunk` move the file's bytes a chunk at a time as raw byte
+//! payloads, and `blob_close` closes it. A blob belongs to the call that
+//! opened it and is closed when the call ends.
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use super::{binary, trace::traced, HostFunctionState, HostResponse};
+use crate::ids::{self, IdKind};
+use crate::plugins::ExecutionContext;
+
+/// Most bytes one `blob_read_chunk` or `blob_write_chunk` moves
+pub const MAX_CHUNK_BYTES: i64 = 4 * 1024 * 1024;
+
+/// Most blobs a call may have open at once
+pub const MAX_OPEN_BLOBS: usize = 16;
+
+/// A host directory the plugin sees at a guest path
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub guest: PathBuf,
+    pub host: PathBuf,
+    pub read_only: bool,
+}
+
+impl Mount {
+    /// Mounts of a manifest's resolved `allowed_paths`, which map host paths
+    /// to guest paths; a `ro:` prefix makes the host path read-only
+    pub fn from_allowed_paths(allowed_paths: &HashMap<String, String>) -> Vec<Mount> {
+        allowed_paths
+            .iter()
+            .map(|(host, guest)| {
+                let (host, read_only) = match host.strip_prefix("ro:") {
+                    Some(host) => (host, true),
+                    None => (host.as_str(), false),
+                };
+                Mount {
+                    guest: PathBuf::from(guest),
+                    host: PathBuf::from(host),
+                    read_only,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A file a call opened with `blob_open`
+pub struct OpenBlob {
+    file: File,
+    writable: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BlobMode {
+    #[default]
+    Read,
+    /// Create the file, or empty it if it exists
+    Write,
+    /// Create the file, or add to the end of it if it exists
+    Append,
+}
+
+#[derive(Deserialize)]
+struct OpenRequest {
+    path: String,
+    #[serde(default)]
+    mode: BlobMode,
+}
+
+#[derive(Serialize)]
+struct OpenedBlob {
+    blob_id: String,
+    /// Size of the file when it was opened
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Written {
+    written: usize,
+}
+
+/// Host path of the guest path `path`, and whether its mount is read-only
+fn resolve(mounts: &[Mount], path: &str) -> Result<(PathBuf, bool), String> {
+    let path = Path::new(path);
+    if !path.has_root() || path.components().any(|c| c == Component::ParentDir) {
+        return Err(format!("Blob paths must be absolute and without '..', not '{}'", path.display()));
+    }
+    mounts
+        .iter()
+        .filter_map(|mount| path.strip_prefix(&mount.guest).ok().map(|rest| (mount, rest)))
+        .max_by_key(|(mount, _)| mount.guest.components().count())
+        .map(|(mount, rest)| (mount.host.join(rest), mount.read_only))
+        .ok_or_else(|| format!("'{}' is not in a directory this plugin may access", path.display()))
+}
+
+fn blob_open(state: &HostFunctionState, context: &ExecutionContext, input: &str) -> HostResponse<OpenedBlob> {
+    let request: OpenRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let (path, read_only) = match resolve(&state.mounts, &request.path) {
+        Ok(resolved) => resolved,
+        Err(e) => return HostResponse::error(e),
+    };
+    let writable = !matches!(request.mode, BlobMode::Read);
+    if writable && read_only {
+        return HostResponse::error(format!("'{}' is read-only", request.path));
+    }
+    let mut blobs = context.blobs.lock().unwrap();
+    if blobs.len() >= MAX_OPEN_BLOBS {
+        return HostResponse::error(format!("A call may have at most {} blobs open", MAX_OPEN_BLOBS));
+    }
+
+    let opened = match request.mode {
+        BlobMode::Read => File::open(&path),
+        BlobMode::Write => File::create(&path),
+        BlobMode::Append => OpenOptions::new().append(true).create(true).open(&path),
+    };
+    let (file, size) = match opened.and_then(|file| file.metadata().map(|metadata| (file, metadata.len()))) {
+        Ok(opened) => opened,
+        Err(e) => return HostResponse::error(format!("Failed to open '{}': {}", request.path, e)),
+    };
+    let blob_id = ids::new_id(IdKind::Other);
+    blobs.insert(blob_id.clone(), OpenBlob { file, writable });
+    HostResponse::success(OpenedBlob { blob_id, size })
+}
+
+/// Up to `max_bytes` of the blob from where the last read ended; empty at
+/// the end of the file
+fn blob_read_chunk(context: &ExecutionContext, blob_id: &str, max_bytes: i64) -> Result<Vec<u8>, String> {
+    if !(1..=MAX_CHUNK_BYTES).contains(&max_bytes) {
+        return Err(format!("Chunks must be 1 to {} bytes, not {}", MAX_CHUNK_BYTES, max_bytes));
+    }
+    let mut blobs = context.blobs.lock().unwrap();
+    let Some(blob) = blobs.get_mut(blob_id) else {
+        return Err(format!("No open blob '{}'", blob_id));
+    };
+    if blob.writable {
+        return Err(format!("Blob '{}' was opened for writing", blob_id));
+    }
+    let mut chunk = Vec::new();
+    (&mut blob.file)
+        .take(max_bytes as u64)
+        .read_to_end(&mut chunk)
+        .map_err(|e| format!("Failed to read blob '{}': {}", blob_id, e))?;
+    Ok(chunk)
+}
+
+fn blob_write_chunk(context: &ExecutionContext, blob_id: &str, chunk: &[u8]) -> HostResponse<Written> {
+    if chunk.len() as i64 > MAX_CHUNK_BYTES {
+        return HostResponse::error(format!("Chunks must be at most {} bytes", MAX_CHUNK_BYTES));
+    }
+    let mut blobs = context.blobs.lock().unwrap();
+    let Some(blob) = blobs.get_mut(blob_id) else {
+        return HostResponse::error(format!("No open blob '{}'", blob_id));
+    };
+    if !blob.writable {
+        return HostResponse::error(format!("Blob '{}' was opened for reading", blob_id));
+    }
+    match blob.file.write_all(chunk) {
+        Ok(()) => HostResponse::success(Written { written: chunk.len() }),
+        Err(e) => HostResponse::error(format!("Failed to write blob '{}': {}", blob_id, e)),
+    }
+}
+
+fn blob_close(context: &ExecutionContext, blob_id: &str) -> HostResponse<()> {
+    let Some(mut blob) = context.blobs.lock().unwrap().remove(blob_id) else {
+        return HostResponse::error(format!("No open blob '{}'", blob_id));
+    };
+    if blob.writable {
+        if let Err(e) = blob.file.flush() {
+            return HostResponse::error(format!("Failed to close blob '{}': {}", blob_id, e));
+        }
+    }
+    HostResponse::success(())
+}
+
+fn execution_context(plugin: &mut CurrentPlugin) -> Result<ExecutionContext, extism::Error> {
+    Ok(plugin.host_context::<ExecutionContext>()?.clone())
+}
+
+/// `blob_open` takes `{"path", "mode"}`, mode being `read` (the default),
+/// `write` or `append`, and returns `{"blob_id", "size"}`
+pub fn blob_open_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "blob_open",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let context = execution_context(plugin)?;
+            let response = blob_open(&state, &context, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `blob_read_chunk` takes a blob ID and a byte count as an i64 and returns
+/// the bytes read (see [`binary`]); failures abort the call
+pub fn blob_read_chunk_host() -> Function {
+    traced(
+        "blob_read_chunk",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let blob_id: String = plugin.memory_get_val(&inputs[0])?;
+            let max_bytes: i64 = plugin.memory_get_val(&inputs[1])?;
+            let context = execution_context(plugin)?;
+            let chunk = blob_read_chunk(&context, &blob_id, max_bytes).map_err(extism::Error::msg)?;
+            binary::write_bytes(plugin, &mut outputs[0], &chunk)
+        },
+    )
+}
+
+/// `blob_write_chunk` takes a blob ID and the bytes to write and returns
+/// `{"written"}`
+pub fn blob_write_chunk_host() -> Function {
+    traced(
+        "blob_write_chunk",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let blob_id: String = plugin.memory_get_val(&inputs[0])?;
+            let chunk = binary::read_bytes(plugin, &inputs[1])?;
+            let context = execution_context(plugin)?;
+            let response = blob_write_chunk(&context, &blob_id, &chunk);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `blob_close` takes a blob ID
+pub fn blob_close_host() -> Function {
+    traced(
+        "blob_close",
+        [PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let blob_id: String = plugin.memory_get_val(&inputs[0])?;
+            let context = execution_context(plugin)?;
+            let response = blob_close(&context, &blob_id);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
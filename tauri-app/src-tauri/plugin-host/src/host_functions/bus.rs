@@ -0,0 +1,77 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde_json::Value;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::bus::{self, BusMessage, MAX_PAYLOAD_BYTES};
+
+/// Publish a payload, given as a JSON string or empty for null, on a topic
+fn bus_publish(state: &HostFunctionState, topic: &str, payload_json: &str) -> HostResponse<()> {
+    if !bus::is_topic(topic) {
+        return HostResponse::error(format!(
+            "'{}' is not a topic; use dot-separated lowercase letters, digits, '_' and '-'",
+            topic
+        ));
+    }
+    if payload_json.len() > MAX_PAYLOAD_BYTES {
+        return HostResponse::error(format!("Bus payloads may be at most {} bytes of JSON", MAX_PAYLOAD_BYTES));
+    }
+    let payload = if payload_json.trim().is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_str(payload_json) {
+            Ok(payload) => payload,
+            Err(e) => return HostResponse::error(format!("Payload is not valid JSON: {}", e)),
+        }
+    };
+    state.bus.publish(&state.plugin_name, topic, payload);
+    HostResponse::success(())
+}
+
+/// Messages on topics matching a pattern since the plugin last polled it
+fn bus_poll(state: &HostFunctionState, pattern: &str) -> HostResponse<Vec<BusMessage>> {
+    if !bus::is_pattern(pattern) {
+        return HostResponse::error(format!(
+            "'{}' is not a topic pattern; use a topic, a topic followed by '.*', or '*'",
+            pattern
+        ));
+    }
+    HostResponse::success(state.bus.poll(&state.plugin_name, pattern))
+}
+
+pub fn bus_publish_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "bus_publish",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let topic: String = plugin.memory_get_val(&inputs[0])?;
+            let payload_json: String = plugin.memory_get_val(&inputs[1])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = bus_publish(&state, &topic, &payload_json);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+pub fn bus_poll_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "bus_poll",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let pattern: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = bus_poll(&state, &pattern);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
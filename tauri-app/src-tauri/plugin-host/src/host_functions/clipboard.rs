@@ -0,0 +1,80 @@
+//! Reading and writing the clipboard's text, for plugins granted the
+//! `clipboard` capability
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::clipboard::MAX_CLIPBOARD_BYTES;
+
+const NO_CLIPBOARD: &str = "No clipboard is available to plugins";
+
+fn clipboard_read_text(state: &HostFunctionState) -> HostResponse<Option<String>> {
+    let Some(clipboard) = &state.clipboard else {
+        return HostResponse::error(NO_CLIPBOARD.to_string());
+    };
+    match clipboard.read_text() {
+        Ok(Some(text)) if text.len() > MAX_CLIPBOARD_BYTES => HostResponse::error(format!(
+            "The clipboard holds {} bytes of text; plugins may read at most {}",
+            text.len(),
+            MAX_CLIPBOARD_BYTES
+        )),
+        Ok(text) => {
+            tracing::info!("Plugin '{}' read the clipboard", state.plugin_name);
+            HostResponse::success(text)
+        }
+        Err(e) => HostResponse::error(format!("Failed to read the clipboard: {:#}", e)),
+    }
+}
+
+fn clipboard_write_text(state: &HostFunctionState, text: &str) -> HostResponse<()> {
+    let Some(clipboard) = &state.clipboard else {
+        return HostResponse::error(NO_CLIPBOARD.to_string());
+    };
+    if text.len() > MAX_CLIPBOARD_BYTES {
+        return HostResponse::error(format!("Plugins may write at most {} bytes to the clipboard", MAX_CLIPBOARD_BYTES));
+    }
+    match clipboard.write_text(text) {
+        Ok(()) => {
+            tracing::info!("Plugin '{}' wrote to the clipboard", state.plugin_name);
+            HostResponse::success(())
+        }
+        Err(e) => HostResponse::error(format!("Failed to write the clipboard: {:#}", e)),
+    }
+}
+
+/// `clipboard_read_text` takes nothing and returns the clipboard's text, or
+/// `null` if it holds none
+pub fn clipboard_read_text_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "clipboard_read_text",
+        [],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = clipboard_read_text(&state);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `clipboard_write_text` takes the text to put on the clipboard
+pub fn clipboard_write_text_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "clipboard_write_text",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let text: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = clipboard_write_text(&state, &text);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,58 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+
+#[derive(Deserialize)]
+struct ConfigRequest {
+    key: String,
+}
+
+/// The plugin's config value for `{key}`, or null if it has none. Values are
+/// the manifest's `wasm_config.config` and `env` with the user's overrides
+/// applied, as of when the plugin was loaded.
+fn get_plugin_config(state: &HostFunctionState, input: &str) -> HostResponse<Option<String>> {
+    let request: ConfigRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    HostResponse::success(state.config.get(&request.key).cloned())
+}
+
+pub fn get_plugin_config_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "get_plugin_config",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = get_plugin_config(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+// Every config value the plugin has, as an object sorted by key
+pub fn list_config_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "list_config",
+        [],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let config: BTreeMap<&String, &String> = state.config.iter().collect();
+            let output = serde_json::to_string(&HostResponse::success(config)).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
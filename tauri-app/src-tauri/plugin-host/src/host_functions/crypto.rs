@@ -0,0 +1,340 @@
+//! Hashing, HMAC and authenticated encryption for plugins
+//!
+//! Plugins call these instead of compiling their own crypto into WASM. Keys
+//! are generated or imported host-side and kept in the `plugin_keys` table
+//! under the plugin's ID; a plugin only ever sees a key's ID, so its material
+//! cannot leak through the plugin's memory or output. Every key a plugin holds
+//! is removed when it is uninstalled.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::db::operations;
+use crate::ids::{self, IdKind};
+
+/// Most keys a plugin may hold at once
+pub const MAX_KEYS_PER_PLUGIN: i64 = 100;
+
+/// Longest HMAC key that can be imported, in bytes
+const MAX_HMAC_KEY_BYTES: usize = 1024;
+
+/// Digests `crypto_hash` computes
+const HASH_ALGORITHMS: &[&str] = &["sha256", "sha384", "sha512"];
+
+/// Algorithms keys can be generated or imported for
+const KEY_ALGORITHMS: &[&str] = &["hmac-sha256", "hmac-sha512", "aes-256-gcm"];
+
+/// How binary values are written in requests and responses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+impl Encoding {
+    fn decode(self, value: &str, what: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Utf8 => Ok(value.as_bytes().to_vec()),
+            Encoding::Base64 => BASE64.decode(value).map_err(|e| format!("{} is not valid base64: {}", what, e)),
+        }
+    }
+
+    fn encode(self, bytes: Vec<u8>, what: &str) -> Result<String, String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8; ask for base64 instead", what)),
+            Encoding::Base64 => Ok(BASE64.encode(bytes)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HashRequest {
+    algorithm: String,
+    data: String,
+    #[serde(default)]
+    encoding: Encoding,
+}
+
+#[derive(Deserialize)]
+struct GenerateKeyRequest {
+    algorithm: String,
+}
+
+#[derive(Deserialize)]
+struct ImportKeyRequest {
+    algorithm: String,
+    /// The key's material, base64-encoded
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct KeyRequest {
+    key_id: String,
+}
+
+#[derive(Deserialize)]
+struct SignRequest {
+    key_id: String,
+    data: String,
+    #[serde(default)]
+    encoding: Encoding,
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    key_id: String,
+    data: String,
+    #[serde(default)]
+    encoding: Encoding,
+    /// Hex-encoded signature from `crypto_hmac_sign` or the other party
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct EncryptRequest {
+    key_id: String,
+    plaintext: String,
+    #[serde(default)]
+    encoding: Encoding,
+    /// Data authenticated along with the plaintext but not encrypted
+    #[serde(default)]
+    aad: String,
+}
+
+#[derive(Deserialize)]
+struct DecryptRequest {
+    key_id: String,
+    /// Base64 of the nonce, ciphertext and tag, as `crypto_encrypt` returns it
+    ciphertext: String,
+    /// How to return the plaintext
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    aad: String,
+}
+
+#[derive(Serialize)]
+struct KeyCreated {
+    key_id: String,
+}
+
+fn parse<'a, T: Deserialize<'a>>(input: &'a str) -> Result<T, String> {
+    serde_json::from_str(input).map_err(|e| format!("JSON parse error: {}", e))
+}
+
+fn respond<T>(result: Result<T, String>) -> HostResponse<T> {
+    match result {
+        Ok(data) => HostResponse::success(data),
+        Err(e) => HostResponse::error(e),
+    }
+}
+
+/// Load one of the plugin's keys, checking it is for one of `algorithms`
+fn load_key(state: &HostFunctionState, key_id: &str, algorithms: &[&str]) -> Result<(String, Vec<u8>), String> {
+    let key = state
+        .database
+        .with_connection(|conn| operations::get_plugin_key(conn, &state.plugin_name, key_id))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No key '{}'", key_id))?;
+    if !algorithms.contains(&key.0.as_str()) {
+        return Err(format!("Key '{}' is a {} key; this needs one of: {}", key_id, key.0, algorithms.join(", ")));
+    }
+    Ok(key)
+}
+
+/// Store a key for the plugin and return its new ID
+fn store_key(state: &HostFunctionState, algorithm: &str, material: &[u8]) -> Result<KeyCreated, String> {
+    let key_id = ids::new_id(IdKind::Other);
+    let stored = state.database.with_connection(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        if operations::count_plugin_keys(&tx, &state.plugin_name)? >= MAX_KEYS_PER_PLUGIN {
+            return Ok(Err(format!("Plugins may hold at most {} keys; delete one first", MAX_KEYS_PER_PLUGIN)));
+        }
+        let now = chrono::Utc::now().timestamp();
+        operations::create_plugin_key(&tx, &state.plugin_name, &key_id, algorithm, material, now)?;
+        tx.commit()?;
+        Ok(Ok(()))
+    });
+    stored.map_err(|e| e.to_string())??;
+    Ok(KeyCreated { key_id })
+}
+
+fn hmac_algorithm(algorithm: &str) -> hmac::Algorithm {
+    if algorithm == "hmac-sha512" {
+        hmac::HMAC_SHA512
+    } else {
+        hmac::HMAC_SHA256
+    }
+}
+
+fn aead_key(material: &[u8]) -> Result<LessSafeKey, String> {
+    let key = UnboundKey::new(&AES_256_GCM, material).map_err(|_| "Stored key is corrupt".to_string())?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Hex digest of `{algorithm, data, encoding}`
+fn crypto_hash(_state: &HostFunctionState, input: &str) -> HostResponse<String> {
+    respond((|| {
+        let request: HashRequest = parse(input)?;
+        let algorithm = match request.algorithm.as_str() {
+            "sha256" => &digest::SHA256,
+            "sha384" => &digest::SHA384,
+            "sha512" => &digest::SHA512,
+            other => return Err(format!("Unknown hash algorithm '{}'; expected one of: {}", other, HASH_ALGORITHMS.join(", "))),
+        };
+        let data = request.encoding.decode(&request.data, "data")?;
+        Ok(hex::encode(digest::digest(algorithm, &data)))
+    })())
+}
+
+/// Generate a random key for `{algorithm}`
+fn crypto_generate_key(state: &HostFunctionState, input: &str) -> HostResponse<KeyCreated> {
+    respond((|| {
+        let request: GenerateKeyRequest = parse(input)?;
+        let length = match request.algorithm.as_str() {
+            "hmac-sha256" | "aes-256-gcm" => 32,
+            "hmac-sha512" => 64,
+            other => return Err(format!("Unknown key algorithm '{}'; expected one of: {}", other, KEY_ALGORITHMS.join(", "))),
+        };
+        let mut material = vec![0u8; length];
+        SystemRandom::new().fill(&mut material).map_err(|_| "Failed to generate key".to_string())?;
+        store_key(state, &request.algorithm, &material)
+    })())
+}
+
+/// Import `{algorithm, key}`, e.g. a webhook secret shared with another service
+fn crypto_import_key(state: &HostFunctionState, input: &str) -> HostResponse<KeyCreated> {
+    respond((|| {
+        let request: ImportKeyRequest = parse(input)?;
+        let material = BASE64.decode(&request.key).map_err(|e| format!("Key is not valid base64: {}", e))?;
+        match request.algorithm.as_str() {
+            "hmac-sha256" | "hmac-sha512" if material.is_empty() || material.len() > MAX_HMAC_KEY_BYTES => {
+                return Err(format!("HMAC keys must be 1 to {} bytes", MAX_HMAC_KEY_BYTES));
+            }
+            "hmac-sha256" | "hmac-sha512" => {}
+            "aes-256-gcm" if material.len() != AES_256_GCM.key_len() => {
+                return Err(format!("AES-256-GCM keys must be {} bytes", AES_256_GCM.key_len()));
+            }
+            "aes-256-gcm" => {}
+            other => return Err(format!("Unknown key algorithm '{}'; expected one of: {}", other, KEY_ALGORITHMS.join(", "))),
+        }
+        store_key(state, &request.algorithm, &material)
+    })())
+}
+
+/// Delete `{key_id}`; returns whether the plugin held it
+fn crypto_delete_key(state: &HostFunctionState, input: &str) -> HostResponse<bool> {
+    respond((|| {
+        let request: KeyRequest = parse(input)?;
+        state
+            .database
+            .with_connection(|conn| operations::delete_plugin_key(conn, &state.plugin_name, &request.key_id))
+            .map_err(|e| e.to_string())
+    })())
+}
+
+/// Hex HMAC of `{key_id, data, encoding}`
+fn crypto_hmac_sign(state: &HostFunctionState, input: &str) -> HostResponse<String> {
+    respond((|| {
+        let request: SignRequest = parse(input)?;
+        let (algorithm, material) = load_key(state, &request.key_id, &["hmac-sha256", "hmac-sha512"])?;
+        let data = request.encoding.decode(&request.data, "data")?;
+        let key = hmac::Key::new(hmac_algorithm(&algorithm), &material);
+        Ok(hex::encode(hmac::sign(&key, &data)))
+    })())
+}
+
+/// Whether `signature` is the HMAC of `{key_id, data, encoding}`, compared in
+/// constant time
+fn crypto_hmac_verify(state: &HostFunctionState, input: &str) -> HostResponse<bool> {
+    respond((|| {
+        let request: VerifyRequest = parse(input)?;
+        let (algorithm, material) = load_key(state, &request.key_id, &["hmac-sha256", "hmac-sha512"])?;
+        let data = request.encoding.decode(&request.data, "data")?;
+        let Ok(signature) = hex::decode(request.signature.trim()) else {
+            return Ok(false);
+        };
+        let key = hmac::Key::new(hmac_algorithm(&algorithm), &material);
+        Ok(hmac::verify(&key, &data, &signature).is_ok())
+    })())
+}
+
+/// Encrypt `{key_id, plaintext, encoding, aad}` with AES-256-GCM under a
+/// random nonce; returns base64 of the nonce, ciphertext and tag
+fn crypto_encrypt(state: &HostFunctionState, input: &str) -> HostResponse<String> {
+    respond((|| {
+        let request: EncryptRequest = parse(input)?;
+        let (_, material) = load_key(state, &request.key_id, &["aes-256-gcm"])?;
+        let mut sealed = request.encoding.decode(&request.plaintext, "plaintext")?;
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).map_err(|_| "Failed to generate nonce".to_string())?;
+        aead_key(&material)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(request.aad.as_bytes()), &mut sealed)
+            .map_err(|_| "Encryption failed".to_string())?;
+        Ok(BASE64.encode([nonce.as_slice(), &sealed].concat()))
+    })())
+}
+
+/// Decrypt `{key_id, ciphertext, encoding, aad}` from `crypto_encrypt`,
+/// failing if it was tampered with or the AAD differs
+fn crypto_decrypt(state: &HostFunctionState, input: &str) -> HostResponse<String> {
+    respond((|| {
+        let request: DecryptRequest = parse(input)?;
+        let (_, material) = load_key(state, &request.key_id, &["aes-256-gcm"])?;
+        let mut sealed = BASE64.decode(&request.ciphertext).map_err(|e| format!("Ciphertext is not valid base64: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Ciphertext is too short".to_string());
+        }
+        let mut ciphertext = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|_| "Ciphertext is too short".to_string())?;
+        let plaintext = aead_key(&material)?
+            .open_in_place(nonce, Aad::from(request.aad.as_bytes()), &mut ciphertext)
+            .map_err(|_| "Decryption failed: wrong key, wrong AAD or tampered ciphertext".to_string())?;
+        request.encoding.encode(plaintext.to_vec(), "Plaintext")
+    })())
+}
+
+/// A host function taking and returning JSON, run with the plugin's own keys
+fn crypto_function<T: Serialize + 'static>(
+    name: &str,
+    state: Arc<HostFunctionState>,
+    handler: fn(&HostFunctionState, &str) -> HostResponse<T>,
+) -> Function {
+    traced(
+        name,
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = handler(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// Every crypto host function, for a plugin's state
+pub fn crypto_functions(state: &Arc<HostFunctionState>) -> Vec<Function> {
+    vec![
+        crypto_function("crypto_hash", state.clone(), crypto_hash),
+        crypto_function("crypto_generate_key", state.clone(), crypto_generate_key),
+        crypto_function("crypto_import_key", state.clone(), crypto_import_key),
+        crypto_function("crypto_delete_key", state.clone(), crypto_delete_key),
+        crypto_function("crypto_hmac_sign", state.clone(), crypto_hmac_sign),
+        crypto_function("crypto_hmac_verify", state.clone(), crypto_hmac_verify),
+        crypto_function("crypto_encrypt", state.clone(), crypto_encrypt),
+        crypto_function("crypto_decrypt", state.clone(), crypto_decrypt),
+    ]
+}
@@ -1,9 +1,14 @@
-use extism::{host_fn, Function, UserData, PTR};
+use extism::{host_fn, CurrentPlugin, Function, UserData, Val, PTR};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::sync::Arc;
 
-use super::HostFunctionState;
+use super::{trace::traced, HostFunctionState, HostResponse};
 use crate::db::{operations, schema::*};
+use crate::events;
+use crate::ids::{self, IdKind};
+use crate::plugins::{Capability, DbAccess};
 
 /// Request types
 #[derive(Deserialize, Serialize)]
@@ -37,6 +42,39 @@ struct UpdateEmailVerifiedRequest {
     verified: bool,
 }
 
+/// Most users `db_list_users` returns per page
+pub const MAX_USERS_PER_PAGE: u32 = 100;
+
+#[derive(Deserialize, Serialize)]
+struct ListUsersRequest {
+    /// Text the name or email must contain
+    search: Option<String>,
+    #[serde(default)]
+    sort: UserSort,
+    #[serde(default)]
+    descending: bool,
+    /// Page to return, starting at 1
+    #[serde(default = "first_page")]
+    page: u32,
+    #[serde(default = "default_users_per_page")]
+    limit: u32,
+}
+
+fn first_page() -> u32 {
+    1
+}
+
+fn default_users_per_page() -> u32 {
+    25
+}
+
+#[derive(Deserialize, Serialize)]
+struct DeleteUserRequest {
+    uuid: String,
+    #[serde(default)]
+    delete_audit_logs: bool,
+}
+
 #[derive(Deserialize, Serialize)]
 struct CreateSessionRequest {
     id: String,
@@ -71,30 +109,53 @@ struct TokenRequest {
     token: String,
 }
 
-/// Generic response
-#[derive(Serialize, Deserialize)]
-struct HostResponse<T> {
-    success: bool,
-    data: Option<T>,
-    error: Option<String>,
+/// Publish a host event once the transaction the call has open commits, or
+/// right away outside one, so subscribers never hear of rolled back writes
+fn publish_after_commit(state: &HostFunctionState, event: &'static str, payload: Value) {
+    let source = state.plugin_name.clone();
+    state.database.after_commit(move || events::publish(event, Some(&source), payload));
+}
+
+fn create_user(conn: &Connection, request: &CreateUserRequest) -> rusqlite::Result<i64> {
+    operations::create_user(conn, &request.uuid, &request.name, &request.email, &request.password_hash, request.created_at)
 }
 
-impl<T> HostResponse<T> {
-    fn success(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
+impl ListUsersRequest {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.page == 0 {
+            anyhow::bail!("Pages start at 1");
         }
+        if !(1..=MAX_USERS_PER_PAGE).contains(&self.limit) {
+            anyhow::bail!("The limit must be 1 to {} users", MAX_USERS_PER_PAGE);
+        }
+        Ok(())
     }
+}
 
-    fn error(error: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error),
-        }
+/// List the page of users a validated request asks for
+fn list_users(conn: &Connection, request: &ListUsersRequest) -> rusqlite::Result<UserPage> {
+    let offset = (request.page - 1).saturating_mul(request.limit).min(i32::MAX as u32);
+    let search = request.search.as_deref().filter(|search| !search.is_empty());
+    operations::list_users(conn, search, request.sort, request.descending, request.limit as i32, offset as i32)
+}
+
+/// Delete a user unless they are the last admin
+fn delete_user(conn: &Connection, request: &DeleteUserRequest) -> rusqlite::Result<Result<DeletedUserRows, String>> {
+    let admin = operations::get_user_roles(conn, &request.uuid)?.iter().any(|role| role == operations::ROLE_ADMIN);
+    if admin && operations::count_users_with_role(conn, operations::ROLE_ADMIN)? <= 1 {
+        return Ok(Err("Cannot delete the last admin".to_string()));
     }
+    Ok(operations::delete_user(conn, &request.uuid, request.delete_audit_logs)?
+        .ok_or_else(|| format!("No user with UUID '{}'", request.uuid)))
+}
+
+/// Create a session; false for service accounts, which authenticate with API keys only
+fn create_session(conn: &Connection, request: &CreateSessionRequest) -> rusqlite::Result<bool> {
+    if operations::is_service_account(conn, &request.user_uuid)? {
+        return Ok(false);
+    }
+    operations::create_session(conn, &request.id, &request.user_uuid, request.created_at, request.expires_at)?;
+    Ok(true)
 }
 
 // Define host functions using Extism 1.13 host_fn! macro
@@ -109,9 +170,15 @@ host_fn!(db_create_user(user_data: Arc<HostFunctionState>; input: String) -> Str
         }
     };
 
-    let result = state.database.with_connection(|conn| {
-        operations::create_user(conn, &request.uuid, &request.name, &request.email, &request.password_hash, request.created_at)
-    });
+    let result = state.database.with_connection(|conn| create_user(conn, &request));
+
+    if result.is_ok() {
+        publish_after_commit(
+            &state,
+            events::USER_CREATED,
+            serde_json::json!({ "uuid": request.uuid, "name": request.name, "email": request.email }),
+        );
+    }
 
     let response = match result {
         Ok(id) => HostResponse::success(id),
@@ -143,6 +210,54 @@ host_fn!(db_get_user_by_uuid(user_data: Arc<HostFunctionState>; uuid: String) ->
     Ok(serde_json::to_string(&response).unwrap_or_default())
 });
 
+host_fn!(db_list_users(user_data: Arc<HostFunctionState>; input: String) -> String {
+    let state = user_data.get()?;
+    let state = state.lock().unwrap();
+    let request: ListUsersRequest = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = HostResponse::<UserPage>::error(format!("JSON parse error: {}", e));
+            return Ok(serde_json::to_string(&resp).unwrap_or_default());
+        }
+    };
+    if let Err(e) = request.validate() {
+        return Ok(serde_json::to_string(&HostResponse::<UserPage>::error(e.to_string())).unwrap_or_default());
+    }
+    let result = state.database.with_connection(|conn| list_users(conn, &request));
+    let response = match result {
+        Ok(page) => HostResponse::success(page),
+        Err(e) => HostResponse::error(e.to_string()),
+    };
+    Ok(serde_json::to_string(&response).unwrap_or_default())
+});
+
+host_fn!(db_delete_user(user_data: Arc<HostFunctionState>; input: String) -> String {
+    let state = user_data.get()?;
+    let state = state.lock().unwrap();
+    let request: DeleteUserRequest = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = HostResponse::<DeletedUserRows>::error(format!("JSON parse error: {}", e));
+            return Ok(serde_json::to_string(&resp).unwrap_or_default());
+        }
+    };
+    let audit_write = Capability::Db { resource: Some("audit"), access: DbAccess::Write };
+    if request.delete_audit_logs && !state.db_capabilities.iter().any(|c| c.covers(&audit_write)) {
+        let resp = HostResponse::<DeletedUserRows>::error(format!("Deleting audit logs needs the {} capability", audit_write));
+        return Ok(serde_json::to_string(&resp).unwrap_or_default());
+    }
+    let result = state.database.with_connection(|conn| delete_user(conn, &request));
+    let response = match result {
+        Ok(Ok(deleted)) => {
+            publish_after_commit(&state, events::USER_DELETED, json!({ "uuid": request.uuid }));
+            HostResponse::success(deleted)
+        }
+        Ok(Err(e)) => HostResponse::error(e),
+        Err(e) => HostResponse::error(e.to_string()),
+    };
+    Ok(serde_json::to_string(&response).unwrap_or_default())
+});
+
 host_fn!(db_update_user_password(user_data: Arc<HostFunctionState>; input: String) -> String {
     let state = user_data.get()?;
     let state = state.lock().unwrap();
@@ -157,6 +272,9 @@ host_fn!(db_update_user_password(user_data: Arc<HostFunctionState>; input: Strin
     let result = state.database.with_connection(|conn| {
         operations::update_user_password(conn, &request.uuid, &request.password_hash, request.updated_at)
     });
+    if result.is_ok() {
+        publish_after_commit(&state, events::USER_UPDATED, serde_json::json!({ "uuid": request.uuid }));
+    }
 
     let response = match result {
         Ok(_) => HostResponse::success(true),
@@ -176,12 +294,18 @@ host_fn!(db_create_session(user_data: Arc<HostFunctionState>; input: String) ->
         }
     };
 
-    let result = state.database.with_connection(|conn| {
-        operations::create_session(conn, &request.id, &request.user_uuid, request.created_at, request.expires_at)
-    });
+    let result = state.database.with_connection(|conn| create_session(conn, &request));
+    if let Ok(true) = result {
+        publish_after_commit(
+            &state,
+            events::SESSION_CREATED,
+            serde_json::json!({ "id": request.id, "user_uuid": request.user_uuid }),
+        );
+    }
 
     let response = match result {
-        Ok(_) => HostResponse::success(true),
+        Ok(false) => HostResponse::error("Service accounts cannot sign in interactively".to_string()),
+        Ok(true) => HostResponse::success(true),
         Err(e) => HostResponse::error(e.to_string()),
     };
     Ok(serde_json::to_string(&response).unwrap_or_default())
@@ -202,6 +326,9 @@ host_fn!(db_delete_session(user_data: Arc<HostFunctionState>; session_id: String
     let state = user_data.get()?;
     let state = state.lock().unwrap();
     let result = state.database.with_connection(|conn| operations::delete_session(conn, &session_id));
+    if result.is_ok() {
+        publish_after_commit(&state, events::SESSION_DELETED, serde_json::json!({ "id": session_id }));
+    }
     let response = match result {
         Ok(_) => HostResponse::success(true),
         Err(e) => HostResponse::error(e.to_string()),
@@ -212,7 +339,7 @@ host_fn!(db_delete_session(user_data: Arc<HostFunctionState>; session_id: String
 // Public functions to create Function objects from host_fn definitions
 
 pub fn create_user_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_create_user",
         [PTR],
         [PTR],
@@ -222,7 +349,7 @@ pub fn create_user_host(state: Arc<HostFunctionState>) -> Function {
 }
 
 pub fn get_user_by_email_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_get_user_by_email",
         [PTR],
         [PTR],
@@ -232,7 +359,7 @@ pub fn get_user_by_email_host(state: Arc<HostFunctionState>) -> Function {
 }
 
 pub fn get_user_by_uuid_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_get_user_by_uuid",
         [PTR],
         [PTR],
@@ -241,8 +368,16 @@ pub fn get_user_by_uuid_host(state: Arc<HostFunctionState>) -> Function {
     )
 }
 
+pub fn list_users_host(state: Arc<HostFunctionState>) -> Function {
+    traced("db_list_users", [PTR], [PTR], UserData::new(state), db_list_users)
+}
+
+pub fn delete_user_host(state: Arc<HostFunctionState>) -> Function {
+    traced("db_delete_user", [PTR], [PTR], UserData::new(state), db_delete_user)
+}
+
 pub fn update_user_password_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_update_user_password",
         [PTR],
         [PTR],
@@ -252,7 +387,7 @@ pub fn update_user_password_host(state: Arc<HostFunctionState>) -> Function {
 }
 
 pub fn create_session_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_create_session",
         [PTR],
         [PTR],
@@ -262,7 +397,7 @@ pub fn create_session_host(state: Arc<HostFunctionState>) -> Function {
 }
 
 pub fn get_session_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_get_session",
         [PTR],
         [PTR],
@@ -272,7 +407,7 @@ pub fn get_session_host(state: Arc<HostFunctionState>) -> Function {
 }
 
 pub fn delete_session_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new(
+    traced(
         "db_delete_session",
         [PTR],
         [PTR],
@@ -298,6 +433,9 @@ host_fn!(db_update_user_email_verified(user_data: Arc<HostFunctionState>; input:
     let result = state.database.with_connection(|conn| {
         operations::update_user_email_verified(conn, &request.uuid, request.verified)
     });
+    if result.is_ok() {
+        publish_after_commit(&state, events::USER_UPDATED, serde_json::json!({ "uuid": request.uuid }));
+    }
 
     let response = match result {
         Ok(_) => HostResponse::success(()),
@@ -308,7 +446,7 @@ host_fn!(db_update_user_email_verified(user_data: Arc<HostFunctionState>; input:
 });
 
 pub fn update_user_email_verified_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_update_user_email_verified", [PTR], [PTR], UserData::new(state), db_update_user_email_verified)
+    traced("db_update_user_email_verified", [PTR], [PTR], UserData::new(state), db_update_user_email_verified)
 }
 
 host_fn!(db_update_user_profile(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -331,6 +469,9 @@ host_fn!(db_update_user_profile(user_data: Arc<HostFunctionState>; input: String
             request.avatar.as_deref()
         )
     });
+    if result.is_ok() {
+        publish_after_commit(&state, events::USER_UPDATED, serde_json::json!({ "uuid": request.uuid }));
+    }
 
     let response = match result {
         Ok(_) => HostResponse::success(()),
@@ -341,7 +482,7 @@ host_fn!(db_update_user_profile(user_data: Arc<HostFunctionState>; input: String
 });
 
 pub fn update_user_profile_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_update_user_profile", [PTR], [PTR], UserData::new(state), db_update_user_profile)
+    traced("db_update_user_profile", [PTR], [PTR], UserData::new(state), db_update_user_profile)
 }
 
 host_fn!(db_delete_user_sessions(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -368,21 +509,21 @@ host_fn!(db_delete_user_sessions(user_data: Arc<HostFunctionState>; input: Strin
 });
 
 pub fn delete_user_sessions_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_delete_user_sessions", [PTR], [PTR], UserData::new(state), db_delete_user_sessions)
+    traced("db_delete_user_sessions", [PTR], [PTR], UserData::new(state), db_delete_user_sessions)
 }
 
 pub fn cleanup_expired_sessions_host(state: Arc<HostFunctionState>) -> Function {
     host_fn!(stub_cleanup_sessions(user_data: Arc<HostFunctionState>;) -> String {
         let state = user_data.get()?;
         let state = state.lock().unwrap();
-        let result = state.database.with_connection(|conn| operations::cleanup_expired_sessions(conn));
+        let result = state.database.with_connection(operations::cleanup_expired_sessions);
         let response = match result {
             Ok(count) => HostResponse::success(count),
             Err(e) => HostResponse::error(e.to_string()),
         };
         Ok(serde_json::to_string(&response).unwrap_or_default())
     });
-    Function::new("db_cleanup_expired_sessions", [PTR], [PTR], UserData::new(state), stub_cleanup_sessions)
+    traced("db_cleanup_expired_sessions", [PTR], [PTR], UserData::new(state), stub_cleanup_sessions)
 }
 
 host_fn!(db_create_email_verification_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -409,7 +550,7 @@ host_fn!(db_create_email_verification_token(user_data: Arc<HostFunctionState>; i
 });
 
 pub fn create_email_verification_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_create_email_verification_token", [PTR], [PTR], UserData::new(state), db_create_email_verification_token)
+    traced("db_create_email_verification_token", [PTR], [PTR], UserData::new(state), db_create_email_verification_token)
 }
 
 host_fn!(db_get_email_verification_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -436,7 +577,7 @@ host_fn!(db_get_email_verification_token(user_data: Arc<HostFunctionState>; inpu
 });
 
 pub fn get_email_verification_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_get_email_verification_token", [PTR], [PTR], UserData::new(state), db_get_email_verification_token)
+    traced("db_get_email_verification_token", [PTR], [PTR], UserData::new(state), db_get_email_verification_token)
 }
 
 host_fn!(db_delete_email_verification_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -463,7 +604,7 @@ host_fn!(db_delete_email_verification_token(user_data: Arc<HostFunctionState>; i
 });
 
 pub fn delete_email_verification_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_delete_email_verification_token", [PTR], [PTR], UserData::new(state), db_delete_email_verification_token)
+    traced("db_delete_email_verification_token", [PTR], [PTR], UserData::new(state), db_delete_email_verification_token)
 }
 
 host_fn!(db_create_password_reset_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -490,7 +631,7 @@ host_fn!(db_create_password_reset_token(user_data: Arc<HostFunctionState>; input
 });
 
 pub fn create_password_reset_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_create_password_reset_token", [PTR], [PTR], UserData::new(state), db_create_password_reset_token)
+    traced("db_create_password_reset_token", [PTR], [PTR], UserData::new(state), db_create_password_reset_token)
 }
 
 host_fn!(db_get_password_reset_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -517,7 +658,7 @@ host_fn!(db_get_password_reset_token(user_data: Arc<HostFunctionState>; input: S
 });
 
 pub fn get_password_reset_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_get_password_reset_token", [PTR], [PTR], UserData::new(state), db_get_password_reset_token)
+    traced("db_get_password_reset_token", [PTR], [PTR], UserData::new(state), db_get_password_reset_token)
 }
 
 host_fn!(db_delete_password_reset_token(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -544,7 +685,7 @@ host_fn!(db_delete_password_reset_token(user_data: Arc<HostFunctionState>; input
 });
 
 pub fn delete_password_reset_token_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_delete_password_reset_token", [PTR], [PTR], UserData::new(state), db_delete_password_reset_token)
+    traced("db_delete_password_reset_token", [PTR], [PTR], UserData::new(state), db_delete_password_reset_token)
 }
 
 host_fn!(db_delete_user_password_reset_tokens(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -571,7 +712,7 @@ host_fn!(db_delete_user_password_reset_tokens(user_data: Arc<HostFunctionState>;
 });
 
 pub fn delete_user_password_reset_tokens_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_delete_user_password_reset_tokens", [PTR], [PTR], UserData::new(state), db_delete_user_password_reset_tokens)
+    traced("db_delete_user_password_reset_tokens", [PTR], [PTR], UserData::new(state), db_delete_user_password_reset_tokens)
 }
 
 // ============================================================================
@@ -580,7 +721,9 @@ pub fn delete_user_password_reset_tokens_host(state: Arc<HostFunctionState>) ->
 
 #[derive(Deserialize, Serialize)]
 struct CreateAuditLogRequest {
-    id: String,
+    /// Generated by the host if the plugin leaves it out
+    #[serde(default)]
+    id: Option<String>,
     user_uuid: String,
     action: String,
     resource_type: Option<String>,
@@ -588,7 +731,9 @@ struct CreateAuditLogRequest {
     metadata: Option<String>,
     ip_address: Option<String>,
     user_agent: Option<String>,
-    created_at: i64,
+    /// Now, if the plugin leaves it out
+    #[serde(default)]
+    created_at: Option<i64>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -598,6 +743,11 @@ struct GetAuditLogsRequest {
     offset: i32,
 }
 
+#[derive(Deserialize, Serialize)]
+struct DeleteOldAuditLogsRequest {
+    older_than: i64,
+}
+
 #[derive(Deserialize, Serialize)]
 struct GetAuditLogsFilteredRequest {
     user_uuid: Option<String>,
@@ -620,10 +770,12 @@ host_fn!(db_create_audit_log(user_data: Arc<HostFunctionState>; input: String) -
         }
     };
 
+    let id = request.id.unwrap_or_else(|| ids::new_id(IdKind::AuditLog));
+    let created_at = request.created_at.unwrap_or_else(|| chrono::Utc::now().timestamp());
     let result = state.database.with_connection(|conn| {
         operations::create_audit_log(
             conn,
-            &request.id,
+            &id,
             &request.user_uuid,
             &request.action,
             request.resource_type.as_deref(),
@@ -631,7 +783,7 @@ host_fn!(db_create_audit_log(user_data: Arc<HostFunctionState>; input: String) -
             request.metadata.as_deref(),
             request.ip_address.as_deref(),
             request.user_agent.as_deref(),
-            request.created_at,
+            created_at,
         )
     });
 
@@ -644,7 +796,7 @@ host_fn!(db_create_audit_log(user_data: Arc<HostFunctionState>; input: String) -
 });
 
 pub fn create_audit_log_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_create_audit_log", [PTR], [PTR], UserData::new(state), db_create_audit_log)
+    traced("db_create_audit_log", [PTR], [PTR], UserData::new(state), db_create_audit_log)
 }
 
 host_fn!(db_get_user_audit_logs(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -671,7 +823,7 @@ host_fn!(db_get_user_audit_logs(user_data: Arc<HostFunctionState>; input: String
 });
 
 pub fn get_user_audit_logs_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_get_user_audit_logs", [PTR], [PTR], UserData::new(state), db_get_user_audit_logs)
+    traced("db_get_user_audit_logs", [PTR], [PTR], UserData::new(state), db_get_user_audit_logs)
 }
 
 host_fn!(db_get_audit_logs_filtered(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -707,7 +859,7 @@ host_fn!(db_get_audit_logs_filtered(user_data: Arc<HostFunctionState>; input: St
 });
 
 pub fn get_audit_logs_filtered_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_get_audit_logs_filtered", [PTR], [PTR], UserData::new(state), db_get_audit_logs_filtered)
+    traced("db_get_audit_logs_filtered", [PTR], [PTR], UserData::new(state), db_get_audit_logs_filtered)
 }
 
 host_fn!(db_count_user_audit_logs(user_data: Arc<HostFunctionState>; input: String) -> String {
@@ -734,5 +886,250 @@ host_fn!(db_count_user_audit_logs(user_data: Arc<HostFunctionState>; input: Stri
 });
 
 pub fn count_user_audit_logs_host(state: Arc<HostFunctionState>) -> Function {
-    Function::new("db_count_user_audit_logs", [PTR], [PTR], UserData::new(state), db_count_user_audit_logs)
-}
\ No newline at end of file
+    traced("db_count_user_audit_logs", [PTR], [PTR], UserData::new(state), db_count_user_audit_logs)
+}
+host_fn!(db_delete_old_audit_logs(user_data: Arc<HostFunctionState>; input: String) -> String {
+    let state = user_data.get()?;
+    let state = state.lock().unwrap();
+    let request: DeleteOldAuditLogsRequest = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = HostResponse::<usize>::error(format!("JSON parse error: {}", e));
+            return Ok(serde_json::to_string(&resp).unwrap_or_default());
+        }
+    };
+
+    let result = state.database.with_connection(|conn| {
+        operations::delete_old_audit_logs(conn, request.older_than)
+    });
+
+    let response = match result {
+        Ok(deleted) => HostResponse::success(deleted),
+        Err(e) => HostResponse::error(e.to_string()),
+    };
+
+    Ok(serde_json::to_string(&response).unwrap_or_default())
+});
+
+pub fn delete_old_audit_logs_host(state: Arc<HostFunctionState>) -> Function {
+    traced("db_delete_old_audit_logs", [PTR], [PTR], UserData::new(state), db_delete_old_audit_logs)
+}
+
+// ============================================================================
+// Batches
+// ============================================================================
+
+/// Most operations one `db_batch` call may run
+pub const MAX_BATCH_OPERATIONS: usize = 1000;
+
+/// One operation of a batch: a `db_*` function and the input it would take
+#[derive(Deserialize)]
+struct BatchOperation {
+    op: String,
+    #[serde(default)]
+    input: Value,
+}
+
+/// A host event an operation raises, published once the batch is done
+type BatchEvent = (&'static str, Value);
+
+fn parse<T: serde::de::DeserializeOwned>(input: Value) -> anyhow::Result<T> {
+    serde_json::from_value(input).map_err(|e| anyhow::anyhow!("JSON parse error: {}", e))
+}
+
+/// Run one operation on a connection the batch holds, returning what the
+/// function would have and the event it raises, if any
+fn run_batch_operation(conn: &Connection, op: &str, input: Value) -> anyhow::Result<(Value, Option<BatchEvent>)> {
+    let result = match op {
+        "db_create_user" => {
+            let request: CreateUserRequest = parse(input)?;
+            let id = create_user(conn, &request)?;
+            let event = json!({ "uuid": request.uuid, "name": request.name, "email": request.email });
+            return Ok((json!(id), Some((events::USER_CREATED, event))));
+        }
+        "db_get_user_by_email" => json!(operations::get_user_by_email(conn, &parse::<String>(input)?)?),
+        "db_get_user_by_uuid" => json!(operations::get_user_by_uuid(conn, &parse::<String>(input)?)?),
+        "db_list_users" => {
+            let request: ListUsersRequest = parse(input)?;
+            request.validate()?;
+            json!(list_users(conn, &request)?)
+        }
+        "db_update_user_password" => {
+            let request: UpdatePasswordRequest = parse(input)?;
+            operations::update_user_password(conn, &request.uuid, &request.password_hash, request.updated_at)?;
+            return Ok((json!(true), Some((events::USER_UPDATED, json!({ "uuid": request.uuid })))));
+        }
+        "db_update_user_email_verified" => {
+            let request: UpdateEmailVerifiedRequest = parse(input)?;
+            operations::update_user_email_verified(conn, &request.uuid, request.verified)?;
+            return Ok((Value::Null, Some((events::USER_UPDATED, json!({ "uuid": request.uuid })))));
+        }
+        "db_update_user_profile" => {
+            let request: UpdateUserProfileRequest = parse(input)?;
+            operations::update_user_profile(conn, &request.uuid, request.name.as_deref(), request.bio.as_deref(), request.avatar.as_deref())?;
+            return Ok((Value::Null, Some((events::USER_UPDATED, json!({ "uuid": request.uuid })))));
+        }
+        "db_create_session" => {
+            let request: CreateSessionRequest = parse(input)?;
+            if !create_session(conn, &request)? {
+                anyhow::bail!("Service accounts cannot sign in interactively");
+            }
+            let event = json!({ "id": request.id, "user_uuid": request.user_uuid });
+            return Ok((json!(true), Some((events::SESSION_CREATED, event))));
+        }
+        "db_get_session" => json!(operations::get_session(conn, &parse::<String>(input)?)?),
+        "db_delete_session" => {
+            let id: String = parse(input)?;
+            operations::delete_session(conn, &id)?;
+            return Ok((json!(true), Some((events::SESSION_DELETED, json!({ "id": id })))));
+        }
+        "db_delete_user_sessions" => {
+            operations::delete_user_sessions(conn, &parse::<GetUserRequest>(input)?.uuid)?;
+            Value::Null
+        }
+        "db_cleanup_expired_sessions" => json!(operations::cleanup_expired_sessions(conn)?),
+        "db_create_email_verification_token" => {
+            let request: CreateEmailVerificationTokenRequest = parse(input)?;
+            operations::create_email_verification_token(conn, &request.user_uuid, &request.token, request.created_at, request.expires_at)?;
+            Value::Null
+        }
+        "db_get_email_verification_token" => {
+            json!(operations::get_email_verification_token(conn, &parse::<TokenRequest>(input)?.token)?)
+        }
+        "db_delete_email_verification_token" => {
+            operations::delete_email_verification_token(conn, &parse::<TokenRequest>(input)?.token)?;
+            Value::Null
+        }
+        "db_create_password_reset_token" => {
+            let request: CreatePasswordResetTokenRequest = parse(input)?;
+            operations::create_password_reset_token(conn, &request.user_uuid, &request.token, request.created_at, request.expires_at)?;
+            Value::Null
+        }
+        "db_get_password_reset_token" => json!(operations::get_password_reset_token(conn, &parse::<TokenRequest>(input)?.token)?),
+        "db_delete_password_reset_token" => {
+            operations::delete_password_reset_token(conn, &parse::<TokenRequest>(input)?.token)?;
+            Value::Null
+        }
+        "db_delete_user_password_reset_tokens" => {
+            operations::delete_user_password_reset_tokens(conn, &parse::<GetUserRequest>(input)?.uuid)?;
+            Value::Null
+        }
+        "db_create_audit_log" => {
+            let request: CreateAuditLogRequest = parse(input)?;
+            let id = request.id.unwrap_or_else(|| ids::new_id(IdKind::AuditLog));
+            let created_at = request.created_at.unwrap_or_else(|| chrono::Utc::now().timestamp());
+            operations::create_audit_log(
+                conn,
+                &id,
+                &request.user_uuid,
+                &request.action,
+                request.resource_type.as_deref(),
+                request.resource_id.as_deref(),
+                request.metadata.as_deref(),
+                request.ip_address.as_deref(),
+                request.user_agent.as_deref(),
+                created_at,
+            )?;
+            Value::Null
+        }
+        "db_get_user_audit_logs" => {
+            let request: GetAuditLogsRequest = parse(input)?;
+            json!(operations::get_user_audit_logs(conn, &request.user_uuid, request.limit, request.offset)?)
+        }
+        "db_get_audit_logs_filtered" => {
+            let request: GetAuditLogsFilteredRequest = parse(input)?;
+            json!(operations::get_audit_logs_filtered(
+                conn,
+                request.user_uuid.as_deref(),
+                request.action.as_deref(),
+                request.resource_type.as_deref(),
+                request.start_time,
+                request.end_time,
+                request.limit,
+                request.offset,
+            )?)
+        }
+        "db_count_user_audit_logs" => json!(operations::count_user_audit_logs(conn, &parse::<GetUserRequest>(input)?.uuid)?),
+        "db_delete_old_audit_logs" => {
+            json!(operations::delete_old_audit_logs(conn, parse::<DeleteOldAuditLogsRequest>(input)?.older_than)?)
+        }
+        _ => anyhow::bail!("'{}' cannot be batched", op),
+    };
+    Ok((result, None))
+}
+
+/// Run a batch of operations in order while holding the connection once
+///
+/// Every operation must be one of the plugin's linked `db_*` functions,
+/// otherwise nothing runs. The batch stops at the first failing operation;
+/// the ones before it are not rolled back. The response holds the result of
+/// each operation that ran.
+fn db_batch(state: &HostFunctionState, input: &str) -> HostResponse<Vec<HostResponse<Value>>> {
+    let operations: Vec<BatchOperation> = match serde_json::from_str(input) {
+        Ok(operations) => operations,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    if operations.len() > MAX_BATCH_OPERATIONS {
+        return HostResponse::error(format!("A batch may hold at most {} operations", MAX_BATCH_OPERATIONS));
+    }
+    if let Some((index, operation)) = operations
+        .iter()
+        .enumerate()
+        .find(|(_, operation)| !state.db_functions.contains(&operation.op.as_str()))
+    {
+        return HostResponse::error(format!(
+            "Operation {} calls '{}', which is not a db_* function this plugin may use",
+            index, operation.op
+        ));
+    }
+
+    let count = operations.len();
+    let mut raised = Vec::new();
+    let outcome = state.database.with_connection(|conn| {
+        let mut results = Vec::with_capacity(count);
+        for (index, operation) in operations.into_iter().enumerate() {
+            match run_batch_operation(conn, &operation.op, operation.input) {
+                Ok((result, event)) => {
+                    results.push(HostResponse::success(result));
+                    raised.extend(event);
+                }
+                Err(e) => {
+                    results.push(HostResponse::error(e.to_string()));
+                    return Ok((results, Some(format!("Operation {} ({}) failed: {}", index, operation.op, e))));
+                }
+            }
+        }
+        Ok((results, None))
+    });
+    for (event, payload) in raised {
+        publish_after_commit(state, event, payload);
+    }
+
+    match outcome {
+        Ok((results, None)) => HostResponse::success(results),
+        Ok((results, Some(error))) => HostResponse {
+            success: false,
+            data: Some(results),
+            error: Some(error),
+        },
+        Err(e) => HostResponse::error(e.to_string()),
+    }
+}
+
+/// `db_batch` takes a JSON array of `{"op", "input"}` operations
+pub fn batch_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "db_batch",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = db_batch(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
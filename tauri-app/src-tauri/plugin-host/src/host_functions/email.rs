@@ -0,0 +1,64 @@
+//! Email sent for plugins granted the `email` capability
+//!
+//! `send_email` hands a message to the host's [`Mailer`], by default the SMTP
+//! server in settings, so plugins such as the auth plugin can deliver
+//! verification and password reset links without holding mail credentials.
+//!
+//! [`Mailer`]: crate::mail::Mailer
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::mail::Email;
+
+/// The message `send_email`'s arguments describe; empty bodies are left out
+fn email(to: &str, subject: String, body_html: String, body_text: String) -> Email {
+    let body = |body: String| (!body.is_empty()).then_some(body);
+    Email {
+        to: to
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(str::to_string)
+            .collect(),
+        subject,
+        body_html: body(body_html),
+        body_text: body(body_text),
+    }
+}
+
+fn send_email(state: &HostFunctionState, email: &Email) -> HostResponse<()> {
+    if let Err(e) = email.validate() {
+        return HostResponse::error(format!("{:#}", e));
+    }
+    match state.mailer.send(email) {
+        Ok(()) => {
+            tracing::info!("Plugin '{}' sent an email to {}", state.plugin_name, email.to.join(", "));
+            HostResponse::success(())
+        }
+        Err(e) => HostResponse::error(format!("Failed to send email: {:#}", e)),
+    }
+}
+
+/// `send_email` takes the recipients, separated by commas, the subject and
+/// the HTML and text bodies, either of which may be empty but not both
+pub fn send_email_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "send_email",
+        [PTR, PTR, PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let to: String = plugin.memory_get_val(&inputs[0])?;
+            let subject: String = plugin.memory_get_val(&inputs[1])?;
+            let body_html: String = plugin.memory_get_val(&inputs[2])?;
+            let body_text: String = plugin.memory_get_val(&inputs[3])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = send_email(&state, &email(&to, subject, body_html, body_text));
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,106 @@
+//! Events plugins push to the embedding application's UI
+//!
+//! `emit_event` lets a long-running plugin report progress or live data while
+//! a call is still running, instead of only returning a final result. Events
+//! are published on a process-wide channel; the app forwards them to its
+//! frontend, prefixed with the plugin's ID.
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::broadcast;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::ExecutionContext;
+
+/// Longest event name accepted
+pub const MAX_EVENT_NAME_LEN: usize = 64;
+
+/// Largest payload accepted, in bytes of JSON
+pub const MAX_EVENT_PAYLOAD_BYTES: usize = 256 * 1024;
+
+static EMITTED: LazyLock<broadcast::Sender<PluginEmittedEvent>> = LazyLock::new(|| broadcast::channel(256).0);
+
+/// An event a plugin emitted for the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginEmittedEvent {
+    /// ID of the plugin that emitted it
+    pub plugin: String,
+    pub event: String,
+    pub payload: Value,
+    /// The call that emitted it, if it was emitted during one
+    #[serde(default)]
+    pub execution_id: Option<String>,
+    /// Unix timestamp (milliseconds)
+    pub timestamp: i64,
+}
+
+/// Receive every event plugins emit from now on
+pub fn subscribe() -> broadcast::Receiver<PluginEmittedEvent> {
+    EMITTED.subscribe()
+}
+
+/// Whether a plugin may emit an event under a name: letters, digits, `-`,
+/// `_`, `:` and `/`, the characters frontend event names allow
+pub fn is_emitted_event_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_EVENT_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '/'))
+}
+
+fn emit_event(state: &HostFunctionState, execution_id: Option<String>, name: String, payload_json: &str) -> HostResponse<()> {
+    if !is_emitted_event_name(&name) {
+        return HostResponse::error(format!(
+            "Invalid event name '{}': use 1 to {} letters, digits, '-', '_', ':' or '/'",
+            name, MAX_EVENT_NAME_LEN
+        ));
+    }
+    if payload_json.len() > MAX_EVENT_PAYLOAD_BYTES {
+        return HostResponse::error(format!("Event payloads may be at most {} bytes of JSON", MAX_EVENT_PAYLOAD_BYTES));
+    }
+    let payload = if payload_json.trim().is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_str(payload_json) {
+            Ok(payload) => payload,
+            Err(e) => return HostResponse::error(format!("Payload is not valid JSON: {}", e)),
+        }
+    };
+
+    publish(&state.plugin_name, name, payload, execution_id);
+    HostResponse::success(())
+}
+
+/// Send an event from `plugin` to everyone subscribed
+pub(crate) fn publish(plugin: &str, event: String, payload: Value, execution_id: Option<String>) {
+    // Nobody listening is not the plugin's problem
+    let _ = EMITTED.send(PluginEmittedEvent {
+        plugin: plugin.to_string(),
+        event,
+        payload,
+        execution_id,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    });
+}
+
+/// `emit_event` takes the event name and its payload as a JSON string
+pub fn emit_event_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "emit_event",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let name: String = plugin.memory_get_val(&inputs[0])?;
+            let payload_json: String = plugin.memory_get_val(&inputs[1])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let execution_id = plugin.host_context::<ExecutionContext>().ok().map(|c| c.execution_id.clone());
+
+            let response = emit_event(&state, execution_id, name, &payload_json);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
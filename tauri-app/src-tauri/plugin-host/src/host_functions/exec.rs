@@ -0,0 +1,141 @@
+//! Host programs run for trusted plugins
+//!
+//! `exec_command` runs one of the manifest's `allowed_commands` with the
+//! arguments and standard input the plugin gives it and returns what it
+//! printed. It is only linked for plugins holding the `exec` capability,
+//! which the user approves even for trusted plugins and which only the
+//! trusted sandbox keeps. Programs run directly, never through a shell, in
+//! the plugin's data directory and with an environment cut down to
+//! [`PASSED_ENV`].
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::{binary, trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::PLUGIN_DATA_GUEST_PATH;
+
+/// Longest a program may run before it is killed
+pub const EXEC_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Most bytes a program may print to standard output or standard error
+pub const MAX_EXEC_OUTPUT: u64 = 16 * 1024 * 1024;
+
+/// Environment variables programs inherit from the host; the rest are cleared
+pub const PASSED_ENV: &[&str] = &["PATH", "HOME", "LANG", "TMPDIR", "TEMP", "TMP", "SystemRoot"];
+
+#[derive(Serialize)]
+struct Finished {
+    /// None if the program was ended by a signal
+    exit_code: Option<i32>,
+    /// Standard output as base64
+    stdout: String,
+    stderr: String,
+}
+
+/// Read all of `pipe` on another thread, failing past [`MAX_EXEC_OUTPUT`]
+/// but still draining it so the program does not block writing
+fn capture(mut pipe: impl Read + Send + 'static, name: &'static str) -> JoinHandle<Result<Vec<u8>, String>> {
+    thread::spawn(move || {
+        let mut captured = Vec::new();
+        (&mut pipe)
+            .take(MAX_EXEC_OUTPUT + 1)
+            .read_to_end(&mut captured)
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        if captured.len() as u64 > MAX_EXEC_OUTPUT {
+            let _ = io::copy(&mut pipe, &mut io::sink());
+            return Err(format!("Program printed more than {} bytes to {}", MAX_EXEC_OUTPUT, name));
+        }
+        Ok(captured)
+    })
+}
+
+fn exec_command(state: &HostFunctionState, program: &str, args: &str, stdin: Vec<u8>) -> Result<Finished, String> {
+    if !state.allowed_commands.iter().any(|command| command == program) {
+        return Err(format!("'{}' is not one of this plugin's allowed_commands", program));
+    }
+    let args: Vec<String> = if args.trim().is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(args).map_err(|e| format!("Arguments must be a JSON array of strings: {}", e))?
+    };
+
+    let mut command = Command::new(program);
+    command
+        .args(&args)
+        .env_clear()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for name in PASSED_ENV {
+        if let Some(value) = std::env::var_os(name) {
+            command.env(name, value);
+        }
+    }
+    if let Some(data_dir) = state.mounts.iter().find(|mount| mount.guest == Path::new(PLUGIN_DATA_GUEST_PATH)) {
+        command.current_dir(&data_dir.host);
+    }
+    tracing::info!("Plugin '{}' runs '{}' with arguments {:?}", state.plugin_name, program, args);
+    let mut child = command.spawn().map_err(|e| format!("Failed to run '{}': {}", program, e))?;
+
+    let mut input = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || {
+        // Programs may exit without reading all of it
+        let _ = input.write_all(&stdin);
+    });
+    let stdout = capture(child.stdout.take().expect("stdout is piped"), "standard output");
+    let stderr = capture(child.stderr.take().expect("stderr is piped"), "standard error");
+
+    let deadline = Instant::now() + EXEC_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("'{}' ran longer than {} seconds and was killed", program, EXEC_TIMEOUT.as_secs()));
+            }
+            Err(e) => return Err(format!("Failed to wait for '{}': {}", program, e)),
+        }
+    };
+    let _ = writer.join();
+    let stdout = stdout.join().map_err(|_| "Failed to read standard output".to_string())??;
+    let stderr = stderr.join().map_err(|_| "Failed to read standard error".to_string())??;
+    Ok(Finished {
+        exit_code: status.code(),
+        stdout: BASE64.encode(stdout),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+    })
+}
+
+/// `exec_command` takes a program, its arguments as a JSON array of strings
+/// and the bytes to feed its standard input, and returns `{"exit_code",
+/// "stdout", "stderr"}` once it exits, standard output being base64
+pub fn exec_command_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "exec_command",
+        [PTR, PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let program: String = plugin.memory_get_val(&inputs[0])?;
+            let args: String = plugin.memory_get_val(&inputs[1])?;
+            let stdin = binary::read_bytes(plugin, &inputs[2])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = match exec_command(&state, &program, &args, stdin) {
+                Ok(finished) => HostResponse::success(finished),
+                Err(e) => HostResponse::error(e),
+            };
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,127 @@
+use base64::Engine;
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::db::operations;
+use crate::db::schema::{ExecutionOutput, TrashedFile};
+use crate::output::{self, OutputFile, OutputRequest};
+use crate::plugins::ExecutionContext;
+use crate::settings::{OutputPolicy, SettingsStore};
+
+#[derive(Deserialize)]
+struct WriteOutputRequest {
+    /// Base name of the output, usually the input file's stem
+    name: String,
+    ext: String,
+    /// Named preset whose overrides apply
+    #[serde(default)]
+    preset: Option<String>,
+    /// Base64-encoded file contents
+    data: String,
+}
+
+/// Write a result file where the output policy puts it, recording it
+/// against the execution so runs can be compared later
+fn write_output_file(state: &HostFunctionState, execution_id: &str, input: &str) -> HostResponse<OutputFile> {
+    let request: WriteOutputRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let data = match base64::engine::general_purpose::STANDARD.decode(&request.data) {
+        Ok(data) => data,
+        Err(e) => return HostResponse::error(format!("Invalid base64 data: {}", e)),
+    };
+
+    // Read on every call so policy changes apply without reloading plugins
+    let policy = OutputPolicy::load(&SettingsStore::new(state.database.clone()));
+    let request = OutputRequest {
+        plugin: &state.plugin_name,
+        preset: request.preset.as_deref(),
+        name: &request.name,
+        ext: &request.ext,
+    };
+    match output::write(&policy, &request, &data, &state.trash) {
+        Ok(file) => {
+            tracing::info!(
+                "Plugin '{}' {} output {:?}",
+                state.plugin_name,
+                if file.written { "wrote" } else { "skipped existing" },
+                file.path
+            );
+            let output = ExecutionOutput {
+                execution_id: execution_id.to_string(),
+                path: file.path.to_string_lossy().into_owned(),
+                sha256: hex::encode(Sha256::digest(&data)),
+                size: data.len() as i64,
+                written_at: chrono::Utc::now().timestamp(),
+            };
+            if let Err(e) = state
+                .database
+                .with_connection(|conn| operations::record_execution_output(conn, &output))
+            {
+                tracing::warn!("Failed to record output {:?} of execution {}: {}", file.path, execution_id, e);
+            }
+            HostResponse::success(file)
+        }
+        Err(e) => HostResponse::error(format!("{:#}", e)),
+    }
+}
+
+pub fn write_output_file_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "write_output_file",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let context = plugin.host_context::<ExecutionContext>()?.clone();
+
+            let response = write_output_file(&state, &context.execution_id, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+#[derive(Deserialize)]
+struct DeleteRequest {
+    path: PathBuf,
+}
+
+/// Move a file in an output directory to the trash
+fn fs_delete(state: &HostFunctionState, input: &str) -> HostResponse<TrashedFile> {
+    let request: DeleteRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let policy = OutputPolicy::load(&SettingsStore::new(state.database.clone()));
+    match output::delete(&policy, &state.plugin_name, &request.path, &state.trash) {
+        Ok(file) => HostResponse::success(file),
+        Err(e) => HostResponse::error(format!("{:#}", e)),
+    }
+}
+
+pub fn fs_delete_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "fs_delete",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = fs_delete(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,61 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse, HOST_API_LEVEL};
+use crate::plugins::{compatibility, PLUGIN_DATA_GUEST_PATH};
+
+/// What plugins learn about the host they run in
+#[derive(Serialize)]
+struct HostInfo {
+    /// `windows`, `macos`, `linux`, `android` or `ios`
+    os: &'static str,
+    /// `x86_64`, `aarch64`, `x86` or `arm`
+    arch: &'static str,
+    /// `windows` or `unix`
+    family: &'static str,
+    /// `<os>-<arch>`, as a manifest's `platforms` names it
+    platform: String,
+    /// Separator of host paths, e.g. in `exec_command` arguments; guest paths
+    /// always use `/`
+    path_separator: &'static str,
+    app_version: String,
+    host_version: String,
+    host_api_level: u32,
+    /// Guest path of the plugin's data directory, or null if it has none
+    data_dir: Option<&'static str>,
+}
+
+// The host's OS and architecture, the app and host versions, the host API
+// level and whether the plugin has a data directory, so plugins can adapt
+// instead of guessing
+pub fn get_host_info_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "get_host_info",
+        [],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let state = user_data.get()?.lock().unwrap().clone();
+            let info = HostInfo {
+                os: std::env::consts::OS,
+                arch: std::env::consts::ARCH,
+                family: std::env::consts::FAMILY,
+                platform: compatibility::current_platform(),
+                path_separator: std::path::MAIN_SEPARATOR_STR,
+                app_version: state.app_version.clone(),
+                host_version: compatibility::host_version().to_string(),
+                host_api_level: HOST_API_LEVEL,
+                data_dir: state
+                    .mounts
+                    .iter()
+                    .any(|mount| mount.guest == Path::new(PLUGIN_DATA_GUEST_PATH))
+                    .then_some(PLUGIN_DATA_GUEST_PATH),
+            };
+            let output = serde_json::to_string(&HostResponse::success(info)).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,223 @@
+//! Outbound HTTP for plugins
+//!
+//! Replaces Extism's built-in `http_request`, `http_status_code` and
+//! `http_headers` so that, besides matching the plugin's `allowed_hosts`, a
+//! request must not match its `denied_hosts` or the global deny list in
+//! settings. The PDKs call these through `extism:host/env`, so plugins need
+//! no changes. Every request, including refused ones, is recorded in the
+//! egress log.
+
+use anyhow::Context;
+use extism::{CurrentPlugin, Function, UserData, Val, ValType, EXTISM_ENV_MODULE, PTR};
+use extism_manifest::HttpRequest;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::trace::traced;
+use crate::db::schema::EgressLog;
+use crate::db::{operations, Database};
+use crate::hosts;
+use crate::settings::{SettingsStore, NETWORK_DENIED_HOSTS_KEY};
+
+/// Timeout of requests made by plugins without a call timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Largest response body read unless the manifest sets `max_http_response_bytes`
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Status, headers and body of a response
+type Response = (u16, BTreeMap<String, String>, Vec<u8>);
+
+/// Network policy and last response of one plugin instance
+struct HttpAccess {
+    plugin_name: String,
+    /// The plugin's `denied_hosts`
+    denied_hosts: Vec<String>,
+    /// Where the global deny list is read from and requests are logged; None
+    /// for plugins loaded without host functions
+    database: Option<Arc<Database>>,
+    status: u16,
+    headers: Option<BTreeMap<String, String>>,
+}
+
+impl HttpAccess {
+    /// Fail unless the plugin may reach the host of a URL
+    fn check(&self, allowed_hosts: Option<&[String]>, url: &reqwest::Url) -> anyhow::Result<()> {
+        let host = url.host_str().unwrap_or_default();
+        let denied_globally = match &self.database {
+            Some(database) => {
+                let denied: Vec<String> = SettingsStore::new(database.clone()).get_or_default(NETWORK_DENIED_HOSTS_KEY)?;
+                hosts::matches_any(&denied, host)
+            }
+            None => false,
+        };
+        let allowed = allowed_hosts.is_some_and(|allowed| hosts::matches_any(allowed, host))
+            && !hosts::matches_any(&self.denied_hosts, host)
+            && !denied_globally;
+        if !allowed {
+            anyhow::bail!("HTTP request to {} is not allowed", url);
+        }
+        Ok(())
+    }
+    
+    /// Record a request in the egress log; failing to do so doesn't fail the request
+    fn log(&self, method: &str, url: &reqwest::Url, bytes_sent: usize, outcome: &anyhow::Result<Response>) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let (status, bytes_received, error) = match outcome {
+            Ok((status, _, body)) => (Some(*status as i64), body.len() as i64, None),
+            Err(e) => (None, 0, Some(format!("{:#}", e))),
+        };
+        let entry = EgressLog {
+            id: 0,
+            plugin: self.plugin_name.clone(),
+            method: method.to_string(),
+            host: url.host_str().unwrap_or_default().to_string(),
+            path: url.path().to_string(),
+            status,
+            bytes_sent: bytes_sent as i64,
+            bytes_received,
+            error,
+            created_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = database.with_connection(|conn| operations::create_egress_log(conn, &entry)) {
+            tracing::warn!("Failed to log HTTP request of plugin '{}': {:#}", self.plugin_name, e);
+        }
+    }
+}
+
+/// Send a request and read the response, at most `max_bytes` of body
+fn send(
+    request: &HttpRequest,
+    method: &str,
+    url: reqwest::Url,
+    body: Option<Vec<u8>>,
+    timeout: Duration,
+    max_bytes: u64,
+) -> anyhow::Result<Response> {
+    let method = reqwest::Method::from_bytes(method.as_bytes()).with_context(|| format!("Invalid HTTP method '{}'", method))?;
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+    let mut builder = client.request(method, url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = body {
+        builder = builder.body(body);
+    }
+
+    // Host functions run on the blocking pool (see PluginManager::execute_plugin_with_context)
+    let runtime = tokio::runtime::Handle::try_current().context("HTTP requests need a Tokio runtime")?;
+    runtime.block_on(async move {
+        let mut response = builder.send().await?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() as u64 > max_bytes {
+                anyhow::bail!("HTTP response exceeds the configured maximum number of bytes: {}", max_bytes);
+            }
+        }
+        Ok((status, headers, body))
+    })
+}
+
+fn http_request(plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], access: &mut HttpAccess) -> anyhow::Result<()> {
+    access.status = 0;
+    access.headers = None;
+    outputs[0] = Val::I64(0);
+
+    let request_handle = plugin
+        .memory_from_val(&inputs[0])
+        .context("Invalid handle offset for HTTP request")?;
+    let request: HttpRequest = serde_json::from_slice(plugin.memory_bytes(request_handle)?)?;
+    plugin.memory_free(request_handle)?;
+    let body = match plugin.memory_from_val(&inputs[1]) {
+        Some(handle) => {
+            let body = plugin.memory_bytes(handle)?.to_vec();
+            plugin.memory_free(handle)?;
+            Some(body)
+        }
+        None => None,
+    };
+
+    let url = reqwest::Url::parse(&request.url).with_context(|| format!("Invalid URL: {}", request.url))?;
+    let method = request.method.as_deref().unwrap_or("GET").to_uppercase();
+    let bytes_sent = body.as_ref().map_or(0, Vec::len);
+    let outcome = access.check(plugin.manifest().allowed_hosts.as_deref(), &url).and_then(|()| {
+        let timeout = plugin.time_remaining().unwrap_or(DEFAULT_TIMEOUT);
+        let max_bytes = plugin
+            .manifest()
+            .memory
+            .max_http_response_bytes
+            .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+        send(&request, &method, url.clone(), body, timeout, max_bytes)
+    });
+    access.log(&method, &url, bytes_sent, &outcome);
+    let (status, headers, body) = outcome?;
+
+    access.status = status;
+    access.headers = Some(headers);
+    if !body.is_empty() {
+        let handle = plugin.memory_new(&body)?;
+        outputs[0] = plugin.memory_to_val(handle);
+    }
+    Ok(())
+}
+
+/// The functions replacing Extism's HTTP builtins, sharing one state
+pub fn http_functions(plugin_name: &str, denied_hosts: Vec<String>, database: Option<Arc<Database>>) -> Vec<Function> {
+    let access = UserData::new(HttpAccess {
+        plugin_name: plugin_name.to_string(),
+        denied_hosts,
+        database,
+        status: 0,
+        headers: None,
+    });
+    vec![
+        traced(
+            "http_request",
+            [PTR, PTR],
+            [PTR],
+            access.clone(),
+            |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<HttpAccess>| {
+                let access = user_data.get()?;
+                let mut access = access.lock().unwrap();
+                http_request(plugin, inputs, outputs, &mut access)
+            },
+        )
+        .with_namespace(EXTISM_ENV_MODULE),
+        traced(
+            "http_status_code",
+            [],
+            [ValType::I32],
+            access.clone(),
+            |_plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<HttpAccess>| {
+                outputs[0] = Val::I32(user_data.get()?.lock().unwrap().status as i32);
+                Ok(())
+            },
+        )
+        .with_namespace(EXTISM_ENV_MODULE),
+        traced(
+            "http_headers",
+            [],
+            [PTR],
+            access,
+            |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<HttpAccess>| {
+                let headers = user_data.get()?.lock().unwrap().headers.clone();
+                match headers {
+                    Some(headers) => plugin.memory_set_val(&mut outputs[0], serde_json::to_string(&headers)?)?,
+                    None => outputs[0] = Val::I64(0),
+                }
+                Ok(())
+            },
+        )
+        .with_namespace(EXTISM_ENV_MODULE),
+    ]
+}
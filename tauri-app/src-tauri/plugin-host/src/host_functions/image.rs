@@ -0,0 +1,247 @@
+//! Images decoded, resized and encoded by the host
+//!
+//! Converter plugins hand encoded PNG, JPEG or WebP files to `image_decode`
+//! and get back their pixels as 8-bit RGBA, row by row; `image_resize` scales
+//! such pixels and `image_encode` turns them back into a file. Pixels carry no
+//! dimensions of their own, so `image_resize` and `image_encode` are told
+//! them, and `image_info` reads them from a file without decoding it.
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{ExtendedColorType, ImageEncoder, ImageFormat, ImageReader, Limits, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use super::{binary, trace::traced, HostResponse};
+
+/// Most pixels an image decoded, resized or encoded by the host may have
+pub const MAX_IMAGE_PIXELS: u64 = 40_000_000;
+
+/// Widest or tallest image the host decodes, resizes or encodes
+pub const MAX_IMAGE_DIMENSION: u32 = 16_384;
+
+/// JPEG quality used when `image_encode` is not given one
+pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Png,
+    Jpeg,
+    /// Encoded losslessly; `quality` does not apply
+    Webp,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Filter {
+    Nearest,
+    #[default]
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ImageInfo {
+    format: Format,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct ResizeRequest {
+    width: u32,
+    height: u32,
+    to_width: u32,
+    to_height: u32,
+    #[serde(default)]
+    filter: Filter,
+}
+
+#[derive(Deserialize)]
+struct EncodeRequest {
+    width: u32,
+    height: u32,
+    format: Format,
+    quality: Option<u8>,
+}
+
+fn check_dimensions(width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 || width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(format!(
+            "Images must be 1 to {} pixels wide and tall, not {}x{}",
+            MAX_IMAGE_DIMENSION, width, height
+        ));
+    }
+    if width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+        return Err(format!("Images may have at most {} pixels, not {}x{}", MAX_IMAGE_PIXELS, width, height));
+    }
+    Ok(())
+}
+
+type Reader<'a> = ImageReader<Cursor<&'a [u8]>>;
+
+/// Reader for an encoded image, its format guessed from its contents
+fn reader(bytes: &[u8]) -> Result<(Reader<'_>, Format), String> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = match reader.format() {
+        Some(ImageFormat::Png) => Format::Png,
+        Some(ImageFormat::Jpeg) => Format::Jpeg,
+        Some(ImageFormat::WebP) => Format::Webp,
+        _ => return Err("Images must be PNG, JPEG or WebP".to_string()),
+    };
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_IMAGE_PIXELS * 4);
+    reader.limits(limits);
+    Ok((reader, format))
+}
+
+/// Pixels of size `width` by `height`, checked against their length
+fn pixels(rgba: Vec<u8>, width: u32, height: u32) -> Result<RgbaImage, String> {
+    check_dimensions(width, height)?;
+    let expected = width as u64 * height as u64 * 4;
+    if rgba.len() as u64 != expected {
+        return Err(format!("{}x{} RGBA pixels take {} bytes, not {}", width, height, expected, rgba.len()));
+    }
+    RgbaImage::from_raw(width, height, rgba).ok_or_else(|| "Invalid RGBA pixels".to_string())
+}
+
+fn image_info(bytes: &[u8]) -> HostResponse<ImageInfo> {
+    let info = reader(bytes).and_then(|(reader, format)| {
+        let (width, height) = reader.into_dimensions().map_err(|e| format!("Failed to read image: {}", e))?;
+        Ok(ImageInfo { format, width, height })
+    });
+    match info {
+        Ok(info) => HostResponse::success(info),
+        Err(e) => HostResponse::error(e),
+    }
+}
+
+fn image_decode(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let (reader, _) = reader(bytes)?;
+    let image = reader.decode().map_err(|e| format!("Failed to decode image: {}", e))?;
+    check_dimensions(image.width(), image.height())?;
+    Ok(image.into_rgba8().into_raw())
+}
+
+fn image_resize(rgba: Vec<u8>, request: &str) -> Result<Vec<u8>, String> {
+    let request: ResizeRequest = serde_json::from_str(request).map_err(|e| format!("JSON parse error: {}", e))?;
+    let image = pixels(rgba, request.width, request.height)?;
+    check_dimensions(request.to_width, request.to_height)?;
+    Ok(imageops::resize(&image, request.to_width, request.to_height, request.filter.into()).into_raw())
+}
+
+fn image_encode(rgba: Vec<u8>, request: &str) -> Result<Vec<u8>, String> {
+    let request: EncodeRequest = serde_json::from_str(request).map_err(|e| format!("JSON parse error: {}", e))?;
+    let image = pixels(rgba, request.width, request.height)?;
+    let (width, height) = image.dimensions();
+    let mut encoded = Vec::new();
+    let written = match request.format {
+        Format::Png => PngEncoder::new(&mut encoded).write_image(&image, width, height, ExtendedColorType::Rgba8),
+        Format::Jpeg => {
+            let quality = request.quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+            if !(1..=100).contains(&quality) {
+                return Err(format!("JPEG quality must be 1 to 100, not {}", quality));
+            }
+            // JPEG has no alpha channel, so it is dropped
+            let rgb = image::DynamicImage::ImageRgba8(image).into_rgb8();
+            JpegEncoder::new_with_quality(&mut encoded, quality).write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+        }
+        Format::Webp => {
+            WebPEncoder::new_lossless(&mut encoded).write_image(&image, width, height, ExtendedColorType::Rgba8)
+        }
+    };
+    written.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(encoded)
+}
+
+/// `image_info` takes an encoded image and returns `{"format", "width",
+/// "height"}` without decoding its pixels
+pub fn image_info_host() -> Function {
+    traced(
+        "image_info",
+        [PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let bytes = binary::read_bytes(plugin, &inputs[0])?;
+            let output = serde_json::to_string(&image_info(&bytes)).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `image_decode` takes an encoded image and returns its RGBA pixels (see
+/// [`binary`]); failures abort the call
+pub fn image_decode_host() -> Function {
+    traced(
+        "image_decode",
+        [PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let bytes = binary::read_bytes(plugin, &inputs[0])?;
+            let rgba = image_decode(&bytes).map_err(extism::Error::msg)?;
+            binary::write_bytes(plugin, &mut outputs[0], &rgba)
+        },
+    )
+}
+
+/// `image_resize` takes RGBA pixels and `{"width", "height", "to_width",
+/// "to_height", "filter"}`, filter being `nearest`, `triangle` (the default),
+/// `catmull_rom`, `gaussian` or `lanczos3`, and returns the resized pixels;
+/// failures abort the call
+pub fn image_resize_host() -> Function {
+    traced(
+        "image_resize",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let rgba = binary::read_bytes(plugin, &inputs[0])?;
+            let request: String = plugin.memory_get_val(&inputs[1])?;
+            let resized = image_resize(rgba, &request).map_err(extism::Error::msg)?;
+            binary::write_bytes(plugin, &mut outputs[0], &resized)
+        },
+    )
+}
+
+/// `image_encode` takes RGBA pixels and `{"width", "height", "format",
+/// "quality"}`, format being `png`, `jpeg` or `webp`, and returns the encoded
+/// image; failures abort the call
+pub fn image_encode_host() -> Function {
+    traced(
+        "image_encode",
+        [PTR, PTR],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let rgba = binary::read_bytes(plugin, &inputs[0])?;
+            let request: String = plugin.memory_get_val(&inputs[1])?;
+            let encoded = image_encode(rgba, &request).map_err(extism::Error::msg)?;
+            binary::write_bytes(plugin, &mut outputs[0], &encoded)
+        },
+    )
+}
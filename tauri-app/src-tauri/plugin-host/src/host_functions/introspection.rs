@@ -0,0 +1,108 @@
+//! Host functions describing themselves, for feature detection
+//!
+//! `list_host_functions` returns the name, signature and capability
+//! requirement of every host function linked into the calling plugin, so a
+//! plugin can check for one before calling it instead of failing to load.
+//! [`PluginManager::host_functions`](crate::plugins::PluginManager::host_functions)
+//! describes every function the host provides, linked or not.
+
+use extism::{CurrentPlugin, Function, UserData, Val, ValType, EXTISM_ENV_MODULE, EXTISM_USER_MODULE, PTR};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    declared_capability, trace::traced, transaction::TRANSACTION_FUNCTIONS, HostResponse, DEPRECATED_HOST_FUNCTIONS,
+    HOST_FUNCTION_NAMES,
+};
+
+const LIST_HOST_FUNCTIONS: &str = "list_host_functions";
+const LIST_HOST_FUNCTIONS_PARAMS: [ValType; 0] = [];
+const LIST_HOST_FUNCTIONS_RESULTS: [ValType; 1] = [PTR];
+
+/// A host function: its name, signature and what a plugin needs to get it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostFunctionInfo {
+    pub name: String,
+    /// Module plugins import it from: `extism:host/user`, or `extism:host/env`
+    /// for the HTTP functions that replace Extism's own
+    pub namespace: String,
+    /// Wasm types of the parameters, e.g. `i64`; pointers to plugin memory are `i64`
+    pub params: Vec<String>,
+    pub results: Vec<String>,
+    /// Capability the manifest must declare for the function to be linked,
+    /// if any; `<resource>` stands for any resource
+    pub capability: Option<String>,
+    /// Function to call instead, if this one is deprecated
+    pub replaced_by: Option<String>,
+    /// False for functions the embedding application adds
+    pub builtin: bool,
+}
+
+/// Capability a plugin needs for `name` to be linked, as `HostFunctionInfo` names it
+fn capability(name: &str) -> Option<String> {
+    match name {
+        "db_query" => Some("db:<resource>:read".to_string()),
+        name if name == "db_execute" || TRANSACTION_FUNCTIONS.contains(&name) => Some("db:<resource>:write".to_string()),
+        name => declared_capability(name).map(|capability| capability.to_string()),
+    }
+}
+
+fn type_name(value: &ValType) -> String {
+    match value {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::FuncRef => "funcref",
+        ValType::ExternRef => "externref",
+    }
+    .to_string()
+}
+
+fn info(name: &str, namespace: &str, params: &[ValType], results: &[ValType]) -> HostFunctionInfo {
+    HostFunctionInfo {
+        name: name.to_string(),
+        namespace: namespace.to_string(),
+        params: params.iter().map(type_name).collect(),
+        results: results.iter().map(type_name).collect(),
+        capability: capability(name),
+        replaced_by: DEPRECATED_HOST_FUNCTIONS
+            .iter()
+            .find(|(deprecated, _)| *deprecated == name)
+            .map(|(_, replacement)| replacement.to_string()),
+        builtin: namespace == EXTISM_ENV_MODULE || HOST_FUNCTION_NAMES.contains(&name),
+    }
+}
+
+/// Describe `functions` and `list_host_functions`, sorted by name
+pub fn describe(functions: &[Function]) -> Vec<HostFunctionInfo> {
+    let mut described: Vec<HostFunctionInfo> = functions
+        .iter()
+        .filter(|function| function.name() != LIST_HOST_FUNCTIONS)
+        .map(|function| {
+            let namespace = function.namespace().unwrap_or(EXTISM_USER_MODULE);
+            info(function.name(), namespace, function.params(), function.results())
+        })
+        .collect();
+    described.push(info(LIST_HOST_FUNCTIONS, EXTISM_USER_MODULE, &LIST_HOST_FUNCTIONS_PARAMS, &LIST_HOST_FUNCTIONS_RESULTS));
+    described.sort_by(|a, b| a.name.cmp(&b.name));
+    described
+}
+
+/// Add `list_host_functions` to the functions linked into a plugin,
+/// describing them and itself
+pub fn link_list_host_functions(functions: &mut Vec<Function>) {
+    let described = describe(functions);
+    functions.push(traced(
+        LIST_HOST_FUNCTIONS,
+        LIST_HOST_FUNCTIONS_PARAMS,
+        LIST_HOST_FUNCTIONS_RESULTS,
+        UserData::new(described),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<Vec<HostFunctionInfo>>| {
+            let described = user_data.get()?.lock().unwrap().clone();
+            let output = serde_json::to_string(&HostResponse::success(described)).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    ));
+}
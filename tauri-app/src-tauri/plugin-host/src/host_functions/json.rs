@@ -0,0 +1,56 @@
+use extism::{host_fn, Function, UserData, PTR};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::HostResponse;
+use super::trace::traced;
+use crate::json_diff;
+
+#[derive(Deserialize, Serialize)]
+struct JsonDiffRequest {
+    a: Value,
+    b: Value,
+}
+
+#[derive(Deserialize, Serialize)]
+struct JsonPatchRequest {
+    doc: Value,
+    patch: Value,
+}
+
+host_fn!(json_diff_impl(_user_data: (); input: String) -> String {
+    let request: JsonDiffRequest = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = HostResponse::<Value>::error(format!("JSON parse error: {}", e));
+            return Ok(serde_json::to_string(&resp).unwrap_or_default());
+        }
+    };
+
+    let response = HostResponse::success(json_diff::diff(&request.a, &request.b));
+    Ok(serde_json::to_string(&response).unwrap_or_default())
+});
+
+pub fn json_diff_host() -> Function {
+    traced("json_diff", [PTR], [PTR], UserData::new(()), json_diff_impl)
+}
+
+host_fn!(json_patch_impl(_user_data: (); input: String) -> String {
+    let request: JsonPatchRequest = match serde_json::from_str(&input) {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = HostResponse::<Value>::error(format!("JSON parse error: {}", e));
+            return Ok(serde_json::to_string(&resp).unwrap_or_default());
+        }
+    };
+
+    let response = match json_diff::apply(&request.doc, &request.patch) {
+        Ok(doc) => HostResponse::success(doc),
+        Err(e) => HostResponse::error(format!("{:#}", e)),
+    };
+    Ok(serde_json::to_string(&response).unwrap_or_default())
+});
+
+pub fn json_patch_host() -> Function {
+    traced("json_patch", [PTR], [PTR], UserData::new(()), json_patch_impl)
+}
@@ -0,0 +1,181 @@
+//! A key-value store each plugin has to itself
+//!
+//! Values are any JSON, kept in the `plugin_kv` table under the plugin's ID,
+//! so counters, caches and cursors survive restarts without the plugin
+//! shipping a table and migration of its own. Everything a plugin stored is
+//! removed when it is uninstalled.
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::db::operations;
+
+/// Longest key accepted, in bytes
+pub const MAX_KEY_BYTES: usize = 256;
+
+/// Largest value accepted, in bytes of JSON
+pub const MAX_VALUE_BYTES: usize = 1024 * 1024;
+
+/// Most a plugin may store in total, keys and values
+pub const MAX_PLUGIN_BYTES: i64 = 16 * 1024 * 1024;
+
+/// Most keys `kv_list` returns at once
+const MAX_LIST_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+struct KeyRequest {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct SetRequest {
+    key: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ListRequest {
+    #[serde(default)]
+    prefix: String,
+    /// Key the previous page ended with
+    #[serde(default)]
+    after: Option<String>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+fn check_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_BYTES {
+        return Err(format!("Keys must be 1 to {} bytes long", MAX_KEY_BYTES));
+    }
+    Ok(())
+}
+
+/// The value stored under `{key}`, or null
+fn kv_get(state: &HostFunctionState, input: &str) -> HostResponse<serde_json::Value> {
+    let request: KeyRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let value = state
+        .database
+        .with_connection(|conn| operations::kv_get(conn, &state.plugin_name, &request.key));
+    match value {
+        Ok(Some(value)) => match serde_json::from_str(&value) {
+            Ok(value) => HostResponse::success(value),
+            Err(e) => HostResponse::error(format!("Stored value is corrupt: {}", e)),
+        },
+        Ok(None) => HostResponse::success(serde_json::Value::Null),
+        Err(e) => HostResponse::error(e.to_string()),
+    }
+}
+
+/// Store `{key, value}`, replacing the key's previous value
+fn kv_set(state: &HostFunctionState, input: &str) -> HostResponse<()> {
+    let request: SetRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    if let Err(e) = check_key(&request.key) {
+        return HostResponse::error(e);
+    }
+    let value = request.value.to_string();
+    if value.len() > MAX_VALUE_BYTES {
+        return HostResponse::error(format!("Values may be at most {} bytes of JSON", MAX_VALUE_BYTES));
+    }
+    let stored = state.database.with_connection(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        let (_, used) = operations::kv_usage(&tx, &state.plugin_name)?;
+        let replaced = operations::kv_get(&tx, &state.plugin_name, &request.key)?
+            .map_or(0, |old| (request.key.len() + old.len()) as i64);
+        let needed = used - replaced + (request.key.len() + value.len()) as i64;
+        if needed > MAX_PLUGIN_BYTES {
+            return Ok(Err(format!(
+                "Storing '{}' would take the plugin's store to {} bytes, over its limit of {}",
+                request.key, needed, MAX_PLUGIN_BYTES
+            )));
+        }
+        let now = chrono::Utc::now().timestamp();
+        operations::kv_set(&tx, &state.plugin_name, &request.key, &value, now)?;
+        tx.commit()?;
+        Ok(Ok(()))
+    });
+    match stored {
+        Ok(Ok(())) => HostResponse::success(()),
+        Ok(Err(e)) => HostResponse::error(e),
+        Err(e) => HostResponse::error(e.to_string()),
+    }
+}
+
+/// Remove `{key}`; returns whether it was set
+fn kv_delete(state: &HostFunctionState, input: &str) -> HostResponse<bool> {
+    let request: KeyRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    match state
+        .database
+        .with_connection(|conn| operations::kv_delete(conn, &state.plugin_name, &request.key))
+    {
+        Ok(deleted) => HostResponse::success(deleted),
+        Err(e) => HostResponse::error(e.to_string()),
+    }
+}
+
+/// Keys starting with `prefix`, in order; pass the last key returned as
+/// `after` to get the next page
+fn kv_list(state: &HostFunctionState, input: &str) -> HostResponse<Vec<String>> {
+    let request: ListRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let limit = request.limit.unwrap_or(MAX_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+    let keys = state.database.with_connection(|conn| {
+        operations::kv_list(conn, &state.plugin_name, &request.prefix, request.after.as_deref(), limit)
+    });
+    match keys {
+        Ok(keys) => HostResponse::success(keys),
+        Err(e) => HostResponse::error(e.to_string()),
+    }
+}
+
+/// A host function taking and returning JSON, run against the plugin's own store
+fn kv_function<T: serde::Serialize + 'static>(
+    name: &str,
+    state: Arc<HostFunctionState>,
+    handler: fn(&HostFunctionState, &str) -> HostResponse<T>,
+) -> Function {
+    traced(
+        name,
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = handler(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+pub fn kv_get_host(state: Arc<HostFunctionState>) -> Function {
+    kv_function("kv_get", state, kv_get)
+}
+
+pub fn kv_set_host(state: Arc<HostFunctionState>) -> Function {
+    kv_function("kv_set", state, kv_set)
+}
+
+pub fn kv_delete_host(state: Arc<HostFunctionState>) -> Function {
+    kv_function("kv_delete", state, kv_delete)
+}
+
+pub fn kv_list_host(state: Arc<HostFunctionState>) -> Function {
+    kv_function("kv_list", state, kv_list)
+}
@@ -0,0 +1,117 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::Level;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::{ExecutionContext, PluginLogEntry};
+
+#[derive(Deserialize, Serialize)]
+struct LogRequest {
+    #[serde(default = "default_level")]
+    level: String,
+    message: String,
+    #[serde(default)]
+    fields: serde_json::Value,
+}
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+/// Parse a plugin-supplied level name into a tracing level
+fn parse_level(level: &str) -> Option<Level> {
+    match level.to_ascii_lowercase().as_str() {
+        "trace" => Some(Level::TRACE),
+        "debug" => Some(Level::DEBUG),
+        "info" => Some(Level::INFO),
+        "warn" | "warning" => Some(Level::WARN),
+        "error" => Some(Level::ERROR),
+        _ => None,
+    }
+}
+
+/// Emit a plugin's log message as a tracing event and keep it in the log store
+///
+/// Events are recorded inside a `plugin` span carrying the plugin's name and,
+/// during a call, the execution ID, so the usual `RUST_LOG` filter applies to
+/// them, e.g. `RUST_LOG=info,[plugin{plugin=my-plugin}]=debug`.
+fn record(state: &HostFunctionState, execution_id: Option<&str>, level: &str, message: String, fields: serde_json::Value) -> HostResponse<()> {
+    let Some(level) = parse_level(level) else {
+        return HostResponse::error(format!("Unknown log level: {}", level));
+    };
+
+    let span = tracing::info_span!("plugin", plugin = %state.plugin_name, execution_id = execution_id.unwrap_or_default());
+    let _entered = span.enter();
+    match level {
+        Level::TRACE => tracing::trace!(%fields, "{}", message),
+        Level::DEBUG => tracing::debug!(%fields, "{}", message),
+        Level::INFO => tracing::info!(%fields, "{}", message),
+        Level::WARN => tracing::warn!(%fields, "{}", message),
+        Level::ERROR => tracing::error!(%fields, "{}", message),
+    }
+
+    state.logs.push(PluginLogEntry {
+        plugin: state.plugin_name.clone(),
+        level: level.as_str().to_ascii_lowercase(),
+        message,
+        fields,
+        execution_id: execution_id.map(str::to_string),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    });
+    HostResponse::success(())
+}
+
+/// `log` takes `{level, message, fields}` as one JSON document
+pub fn log_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "log",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let execution_id = plugin.host_context::<ExecutionContext>().ok().map(|c| c.execution_id.clone());
+
+            let response = match serde_json::from_str::<LogRequest>(&input) {
+                Ok(request) => record(&state, execution_id.as_deref(), &request.level, request.message, request.fields),
+                Err(e) => HostResponse::error(format!("JSON parse error: {}", e)),
+            };
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `host_log` takes the level, the message and the fields as three strings,
+/// so logging needs no JSON envelope; fields may be empty or a JSON document
+pub fn host_log_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "host_log",
+        [PTR, PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let level: String = plugin.memory_get_val(&inputs[0])?;
+            let message: String = plugin.memory_get_val(&inputs[1])?;
+            let fields_json: String = plugin.memory_get_val(&inputs[2])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let execution_id = plugin.host_context::<ExecutionContext>().ok().map(|c| c.execution_id.clone());
+
+            let fields = if fields_json.trim().is_empty() {
+                Ok(serde_json::Value::Null)
+            } else {
+                serde_json::from_str(&fields_json)
+            };
+            let response = match fields {
+                Ok(fields) => record(&state, execution_id.as_deref(), &level, message, fields),
+                Err(e) => HostResponse::error(format!("Fields are not valid JSON: {}", e)),
+            };
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
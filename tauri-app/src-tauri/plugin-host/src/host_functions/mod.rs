@@ -0,0 +1,551 @@
+pub mod binary;
+pub mod blob;
+pub mod bus;
+pub mod clipboard;
+pub mod config;
+pub mod crypto;
+pub mod database;
+pub mod email;
+pub mod emit;
+pub mod exec;
+pub mod fs;
+pub mod host_info;
+pub mod http;
+pub mod image;
+pub mod introspection;
+pub mod json;
+pub mod kv;
+pub mod logging;
+pub mod plugin_call;
+pub mod progress;
+pub mod settings;
+pub mod sleep;
+pub mod sql;
+pub mod stream;
+pub mod trace;
+pub mod transaction;
+pub mod user;
+
+use extism::{Function, UserData, CurrentPlugin, Val, PTR};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::bus::MessageBus;
+use crate::clipboard::Clipboard;
+use crate::db::Database;
+use crate::ids::{self, IdKind};
+use crate::mail::Mailer;
+use crate::trash::TrashBin;
+use crate::plugins::{
+    Capability, DbAccess, MetricsRegistry, PluginLogStore, PluginRegistry, SettingWatches, DB_RESOURCES,
+    PLUGIN_DATA_GUEST_PATH,
+};
+use tokio::sync::RwLock;
+use trace::traced;
+
+/// User data passed to host functions containing app state
+pub struct HostFunctionState {
+    /// Name of the plugin these host functions are registered for
+    pub plugin_name: String,
+    pub database: Arc<Database>,
+    pub logs: Arc<PluginLogStore>,
+    /// Plugins this plugin declared as dependencies and may call
+    pub dependencies: Vec<String>,
+    pub plugins: PluginRegistry,
+    /// Where calls to other plugins are counted, shared with the plugin manager
+    pub metrics: Arc<RwLock<MetricsRegistry>>,
+    /// Where files the plugin deletes or overwrites go
+    pub trash: Arc<TrashBin>,
+    /// Settings plugins asked to be told about, shared with the plugin manager
+    pub setting_watches: SettingWatches,
+    /// Hosts the plugin's manifest denies it, on top of the global deny list
+    pub denied_hosts: Vec<String>,
+    /// Config the plugin was loaded with: packaged values, the manifest's
+    /// env and the user's overrides, with secrets resolved
+    pub config: HashMap<String, String>,
+    /// Broker for messages plugins publish to each other
+    pub bus: Arc<MessageBus>,
+    /// Longest a single `host_sleep` may wait, in milliseconds
+    pub max_sleep_ms: u64,
+    /// The embedder's clipboard, if it has one
+    pub clipboard: Option<Arc<dyn Clipboard>>,
+    /// `db_*` functions linked for the plugin, which `db_batch` may run;
+    /// filled in by [`register_host_functions`]
+    pub db_functions: Vec<&'static str>,
+    /// `db:<resource>:<access>` capabilities the plugin holds, which decide
+    /// the tables `db_query` and `db_execute` may touch; filled in by
+    /// [`register_host_functions`]
+    pub db_capabilities: Vec<Capability>,
+    /// Whether the plugin ships migrations, and so has tables of its own
+    /// that `db_query` and `db_execute` may touch whatever it holds
+    pub own_tables: bool,
+    /// Host API level the plugin's manifest says it was built against, which
+    /// decides the form of results that changed since
+    pub host_api_level: Option<u32>,
+    /// Directories the plugin may open blobs in: its data directory and the
+    /// manifest's `allowed_paths`, unless the filesystem is withheld
+    pub mounts: Vec<blob::Mount>,
+    /// Programs `exec_command` may run: the manifest's `allowed_commands`,
+    /// unless `exec` is withheld
+    pub allowed_commands: Vec<String>,
+    /// How `send_email` delivers messages
+    pub mailer: Arc<dyn Mailer>,
+    /// Version of the embedding application
+    pub app_version: String,
+}
+
+/// Builds the host functions an embedding application adds to a plugin, given
+/// the plugin's ID
+///
+/// Called each time a plugin is loaded, so every instance gets its own
+/// functions and user data, and with an empty ID to learn the functions'
+/// names for the compatibility check.
+pub type HostFunctionFactory = Arc<dyn Fn(&str) -> Vec<Function> + Send + Sync>;
+
+/// Generic response envelope returned by JSON host functions
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HostResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> HostResponse<T> {
+    pub fn success(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(error: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Most bytes one `generate_random_bytes` call returns
+pub const MAX_RANDOM_BYTES: i64 = 1024 * 1024;
+
+/// Host API level from which `generate_random_bytes` returns raw bytes
+/// rather than a JSON array of numbers
+pub const RAW_RANDOM_BYTES_LEVEL: u32 = 15;
+
+// Generate random bytes; takes the count as an i64 and returns the raw bytes
+// (see [`binary`]), or a JSON array of them for plugins built against a host
+// API level below [`RAW_RANDOM_BYTES_LEVEL`]
+pub fn generate_random_bytes_host(json: bool) -> Function {
+    random_bytes_host("generate_random_bytes", json)
+}
+
+// Deprecated: generate random bytes as a JSON array string, whatever level
+// the plugin was built against; use `generate_random_bytes`
+pub fn generate_random_bytes_json_host() -> Function {
+    random_bytes_host("generate_random_bytes_json", true)
+}
+
+fn random_bytes_host(name: &'static str, json: bool) -> Function {
+    traced(
+        name,
+        [PTR],
+        [PTR],
+        UserData::new(()),
+        move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            use rand::RngCore;
+            let length: i64 = plugin.memory_get_val(&inputs[0])?;
+            if !(0..=MAX_RANDOM_BYTES).contains(&length) {
+                return Err(extism::Error::msg(format!(
+                    "Random byte counts must be 0 to {}, not {}",
+                    MAX_RANDOM_BYTES, length
+                )));
+            }
+            let mut bytes = vec![0u8; length as usize];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            if json {
+                plugin.memory_set_val(&mut outputs[0], serde_json::to_string(&bytes).unwrap_or_default())?;
+                return Ok(());
+            }
+            binary::write_bytes(plugin, &mut outputs[0], &bytes)
+        },
+    )
+}
+
+// Get current timestamp in seconds host function
+//
+// Like every value the PDK's `#[host_fn]` returns, the timestamp goes through
+// memory: the output is the offset of its 8 little-endian bytes.
+pub fn get_timestamp_host() -> Function {
+    traced(
+        "get_timestamp",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            use std::time::{SystemTime, UNIX_EPOCH};
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            plugin.memory_set_val(&mut outputs[0], timestamp)?;
+            Ok(())
+        },
+    )
+}
+
+// Get current timestamp in nanoseconds host function
+pub fn get_timestamp_nanos_host() -> Function {
+    traced(
+        "get_timestamp_nanos",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            use std::time::{SystemTime, UNIX_EPOCH};
+            let timestamp_nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as i64;
+            plugin.memory_set_val(&mut outputs[0], timestamp_nanos)?;
+            Ok(())
+        },
+    )
+}
+
+// Generate an ID of the given kind (user, session, token, audit_log, job,
+// execution, schedule, other) using the host's ID strategy
+extism::host_fn!(new_id_impl(user_data: (); kind: String) -> String {
+    let kind = IdKind::parse(&kind).ok_or_else(|| {
+        let kinds: Vec<&str> = IdKind::ALL.iter().map(IdKind::as_str).collect();
+        extism::Error::msg(format!("Unknown ID kind '{}'; expected one of: {}", kind, kinds.join(", ")))
+    })?;
+    Ok(ids::new_id(kind))
+});
+
+pub fn new_id_host() -> Function {
+    traced("new_id", [PTR], [PTR], UserData::new(()), new_id_impl)
+}
+
+// Generate a random RFC 4122 version 4 UUID, whatever the host's ID strategy
+pub fn generate_uuid_v4_host() -> Function {
+    traced(
+        "generate_uuid_v4",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            plugin.memory_set_val(&mut outputs[0], uuid::Uuid::new_v4().to_string())?;
+            Ok(())
+        },
+    )
+}
+
+// Path of the plugin's data directory inside the guest; readable and writable with WASI
+pub fn get_plugin_data_dir_host() -> Function {
+    traced(
+        "get_plugin_data_dir",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            plugin.memory_set_val(&mut outputs[0], PLUGIN_DATA_GUEST_PATH)?;
+            Ok(())
+        },
+    )
+}
+
+/// Level of the host function interface this build provides
+///
+/// Raised whenever host functions are added or change in a way plugins can
+/// depend on; plugins built against a higher level are refused at load time.
+pub const HOST_API_LEVEL: u32 = 25;
+
+/// Names of every host function this build provides, for checking what
+/// plugin modules import without registering them
+pub const HOST_FUNCTION_NAMES: &[&str] = &[
+    "generate_random_bytes",
+    "generate_random_bytes_json",
+    "get_timestamp",
+    "get_timestamp_nanos",
+    "get_plugin_data_dir",
+    "get_host_info",
+    "list_host_functions",
+    "get_current_user",
+    "new_id",
+    "generate_uuid_v4",
+    "json_diff",
+    "json_patch",
+    "log",
+    "host_log",
+    "emit_event",
+    "report_progress",
+    "bus_publish",
+    "bus_poll",
+    "host_sleep",
+    "clipboard_read_text",
+    "clipboard_write_text",
+    "stream_chunk",
+    "write_output_file",
+    "fs_delete",
+    "blob_open",
+    "blob_read_chunk",
+    "blob_write_chunk",
+    "blob_close",
+    "image_info",
+    "image_decode",
+    "image_resize",
+    "image_encode",
+    "exec_command",
+    "send_email",
+    "call_plugin",
+    "watch_setting",
+    "get_plugin_config",
+    "list_config",
+    "kv_get",
+    "kv_set",
+    "kv_delete",
+    "kv_list",
+    "crypto_hash",
+    "crypto_generate_key",
+    "crypto_import_key",
+    "crypto_delete_key",
+    "crypto_hmac_sign",
+    "crypto_hmac_verify",
+    "crypto_encrypt",
+    "crypto_decrypt",
+    "db_create_user",
+    "db_get_user_by_email",
+    "db_get_user_by_uuid",
+    "db_list_users",
+    "db_update_user_password",
+    "db_update_user_email_verified",
+    "db_update_user_profile",
+    "db_delete_user",
+    "db_create_session",
+    "db_get_session",
+    "db_delete_session",
+    "db_delete_user_sessions",
+    "db_cleanup_expired_sessions",
+    "db_create_email_verification_token",
+    "db_get_email_verification_token",
+    "db_delete_email_verification_token",
+    "db_create_password_reset_token",
+    "db_get_password_reset_token",
+    "db_delete_password_reset_token",
+    "db_delete_user_password_reset_tokens",
+    "db_create_audit_log",
+    "db_get_user_audit_logs",
+    "db_get_audit_logs_filtered",
+    "db_count_user_audit_logs",
+    "db_delete_old_audit_logs",
+    "db_batch",
+    "db_begin",
+    "db_commit",
+    "db_rollback",
+    "db_query",
+    "db_execute",
+];
+
+/// Host functions kept for plugins that still import them, and what to use
+/// instead; importing one logs a warning when the plugin loads
+pub const DEPRECATED_HOST_FUNCTIONS: &[(&str, &str)] = &[("generate_random_bytes_json", "generate_random_bytes")];
+
+/// `db:<resource>:<access>`, for the table below
+const fn db(resource: &'static str, access: DbAccess) -> Capability {
+    Capability::Db {
+        resource: Some(resource),
+        access,
+    }
+}
+
+/// Capability a plugin must declare for each `db_*` host function to be linked
+const DB_FUNCTION_CAPABILITIES: &[(&str, Capability)] = &[
+    ("db_create_user", db("users", DbAccess::Write)),
+    ("db_get_user_by_email", db("users", DbAccess::Read)),
+    ("db_get_user_by_uuid", db("users", DbAccess::Read)),
+    ("db_list_users", db("users", DbAccess::Read)),
+    ("db_update_user_password", db("users", DbAccess::Write)),
+    ("db_update_user_email_verified", db("users", DbAccess::Write)),
+    ("db_update_user_profile", db("users", DbAccess::Write)),
+    ("db_delete_user", db("users", DbAccess::Write)),
+    ("db_create_session", db("sessions", DbAccess::Write)),
+    ("db_get_session", db("sessions", DbAccess::Read)),
+    ("db_delete_session", db("sessions", DbAccess::Write)),
+    ("db_delete_user_sessions", db("sessions", DbAccess::Write)),
+    ("db_cleanup_expired_sessions", db("sessions", DbAccess::Write)),
+    ("db_create_email_verification_token", db("email_verification", DbAccess::Write)),
+    ("db_get_email_verification_token", db("email_verification", DbAccess::Read)),
+    ("db_delete_email_verification_token", db("email_verification", DbAccess::Write)),
+    ("db_create_password_reset_token", db("password_reset", DbAccess::Write)),
+    ("db_get_password_reset_token", db("password_reset", DbAccess::Read)),
+    ("db_delete_password_reset_token", db("password_reset", DbAccess::Write)),
+    ("db_delete_user_password_reset_tokens", db("password_reset", DbAccess::Write)),
+    ("db_create_audit_log", db("audit", DbAccess::Write)),
+    ("db_get_user_audit_logs", db("audit", DbAccess::Read)),
+    ("db_get_audit_logs_filtered", db("audit", DbAccess::Read)),
+    ("db_count_user_audit_logs", db("audit", DbAccess::Read)),
+    ("db_delete_old_audit_logs", db("audit", DbAccess::Write)),
+];
+
+/// Capability a plugin must declare for a host function to be linked, if any
+pub fn declared_capability(function: &str) -> Option<Capability> {
+    match function {
+        "write_output_file" | "fs_delete" => Some(Capability::Filesystem),
+        "clipboard_read_text" | "clipboard_write_text" => Some(Capability::Clipboard),
+        "exec_command" => Some(Capability::Exec),
+        "send_email" => Some(Capability::Email),
+        _ => DB_FUNCTION_CAPABILITIES
+            .iter()
+            .find(|(name, _)| *name == function)
+            .map(|(_, capability)| *capability),
+    }
+}
+
+/// Register the host functions of a plugin, leaving out those that need a
+/// capability it does not declare or whose approval is in `withheld`,
+/// `db_query` if it may read no table, and `db_execute` and the transaction
+/// functions if it may write none; a plugin's own tables count for both
+pub fn register_host_functions(mut state: HostFunctionState, declared: &[Capability], withheld: &[Capability]) -> Vec<Function> {
+    let holds = |capability: &Capability| {
+        declared.iter().any(|c| c.covers(capability))
+            && capability.approval().is_none_or(|approval| !withheld.contains(&approval))
+    };
+    let linked = |name: &str| declared_capability(name).is_none_or(|capability| holds(&capability));
+    state.db_functions = DB_FUNCTION_CAPABILITIES
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| linked(name))
+        .collect();
+    state.db_capabilities = DB_RESOURCES
+        .iter()
+        .flat_map(|resource| [db(resource, DbAccess::Read), db(resource, DbAccess::Write)])
+        .filter(|capability| holds(capability))
+        .collect();
+    let holds_any = |access: DbAccess| {
+        state
+            .db_capabilities
+            .iter()
+            .any(|capability| matches!(capability, Capability::Db { access: a, .. } if *a == access))
+    };
+    let (query, execute) = (
+        state.own_tables || holds_any(DbAccess::Read),
+        state.own_tables || holds_any(DbAccess::Write),
+    );
+    let mut functions = all_host_functions(state);
+    functions.retain(|function| {
+        linked(function.name())
+            && match function.name() {
+                "db_query" => query,
+                "db_execute" => execute,
+                name => execute || !transaction::TRANSACTION_FUNCTIONS.contains(&name),
+            }
+    });
+    functions
+}
+
+pub(crate) fn all_host_functions(state: HostFunctionState) -> Vec<Function> {
+    let state = Arc::new(state);
+    let http = http::http_functions(&state.plugin_name, state.denied_hosts.clone(), Some(state.database.clone()));
+    let crypto = crypto::crypto_functions(&state);
+    
+    let mut functions = vec![
+        // Utility functions - use () as user_data since they don't need database state
+        generate_random_bytes_host(state.host_api_level.is_none_or(|level| level < RAW_RANDOM_BYTES_LEVEL)),
+        generate_random_bytes_json_host(),
+        get_timestamp_host(),
+        get_timestamp_nanos_host(),
+        get_plugin_data_dir_host(),
+        host_info::get_host_info_host(state.clone()),
+        user::get_current_user_host(),
+        new_id_host(),
+        generate_uuid_v4_host(),
+        json::json_diff_host(),
+        json::json_patch_host(),
+        logging::log_host(state.clone()),
+        logging::host_log_host(state.clone()),
+        emit::emit_event_host(state.clone()),
+        progress::report_progress_host(state.clone()),
+        bus::bus_publish_host(state.clone()),
+        bus::bus_poll_host(state.clone()),
+        sleep::host_sleep_host(state.clone()),
+        clipboard::clipboard_read_text_host(state.clone()),
+        clipboard::clipboard_write_text_host(state.clone()),
+        stream::stream_chunk_host(),
+        fs::write_output_file_host(state.clone()),
+        fs::fs_delete_host(state.clone()),
+        blob::blob_open_host(state.clone()),
+        blob::blob_read_chunk_host(),
+        blob::blob_write_chunk_host(),
+        blob::blob_close_host(),
+        image::image_info_host(),
+        image::image_decode_host(),
+        image::image_resize_host(),
+        image::image_encode_host(),
+        exec::exec_command_host(state.clone()),
+        email::send_email_host(state.clone()),
+        plugin_call::call_plugin_host(state.clone()),
+        settings::watch_setting_host(state.clone()),
+        config::get_plugin_config_host(state.clone()),
+        config::list_config_host(state.clone()),
+        
+        // Plugin-scoped key-value store
+        kv::kv_get_host(state.clone()),
+        kv::kv_set_host(state.clone()),
+        kv::kv_delete_host(state.clone()),
+        kv::kv_list_host(state.clone()),
+        
+        // User operations
+        database::create_user_host(state.clone()),
+        database::get_user_by_email_host(state.clone()),
+        database::get_user_by_uuid_host(state.clone()),
+        database::list_users_host(state.clone()),
+        database::update_user_password_host(state.clone()),
+        database::update_user_email_verified_host(state.clone()),
+        database::update_user_profile_host(state.clone()),
+        database::delete_user_host(state.clone()),
+        
+        // Session operations
+        database::create_session_host(state.clone()),
+        database::get_session_host(state.clone()),
+        database::delete_session_host(state.clone()),
+        database::delete_user_sessions_host(state.clone()),
+        database::cleanup_expired_sessions_host(state.clone()),
+        
+        // Email verification token operations
+        database::create_email_verification_token_host(state.clone()),
+        database::get_email_verification_token_host(state.clone()),
+        database::delete_email_verification_token_host(state.clone()),
+        
+        // Password reset token operations
+        database::create_password_reset_token_host(state.clone()),
+        database::get_password_reset_token_host(state.clone()),
+        database::delete_password_reset_token_host(state.clone()),
+        database::delete_user_password_reset_tokens_host(state.clone()),
+        
+        // Audit log operations
+        database::create_audit_log_host(state.clone()),
+        database::get_user_audit_logs_host(state.clone()),
+        database::get_audit_logs_filtered_host(state.clone()),
+        database::count_user_audit_logs_host(state.clone()),
+        database::delete_old_audit_logs_host(state.clone()),
+        // Several of the above in one call, or atomically across calls
+        database::batch_host(state.clone()),
+        transaction::begin_host(state.clone()),
+        transaction::commit_host(state.clone()),
+        transaction::rollback_host(state.clone()),
+        // SQL the plugin writes, against the tables its capabilities allow
+        sql::query_host(state.clone()),
+        sql::execute_host(state.clone()),
+    ];
+    
+    // Hashing, HMAC and encryption with keys held host-side
+    functions.extend(crypto);
+    
+    // Shadow Extism's HTTP builtins
+    functions.extend(http);
+    functions
+}
@@ -0,0 +1,142 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::{resolve_plugin_id, ExecutionContext};
+
+/// Maximum length of a chain of plugin-to-plugin calls
+const MAX_CALL_DEPTH: usize = 8;
+
+/// How long to wait for a target plugin that is busy with another call
+const TARGET_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Serialize)]
+struct CallPluginRequest {
+    target: String,
+    function: String,
+    #[serde(default)]
+    payload: Value,
+}
+
+/// Call a function on another plugin
+///
+/// Only plugins listed in the caller's `dependencies` may be called. Calls
+/// that would re-enter a plugin already on the call chain, or exceed
+/// `MAX_CALL_DEPTH`, are rejected. The target's aliases, payload checks and
+/// quotas apply as they would to a direct call, and the call is counted in
+/// its metrics.
+fn call_plugin(state: &HostFunctionState, context: &ExecutionContext, input: &str) -> HostResponse<Value> {
+    let request: CallPluginRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+
+    // Host functions run on the blocking pool (see PluginManager::execute_plugin_with_context)
+    let (target_id, target) = {
+        let plugins = state.plugins.blocking_read();
+        let target_id = match resolve_plugin_id(&plugins, &request.target) {
+            Ok(id) => id,
+            Err(e) => return HostResponse::error(e.to_string()),
+        };
+        // Dependencies may be declared by ID or by short name
+        let declared = state
+            .dependencies
+            .iter()
+            .any(|dep| resolve_plugin_id(&plugins, dep).is_ok_and(|id| id == target_id));
+        if !declared {
+            return HostResponse::error(format!(
+                "Plugin '{}' does not declare a dependency on '{}'",
+                state.plugin_name, request.target
+            ));
+        }
+        let target = plugins[&target_id].clone();
+        (target_id, target)
+    };
+
+    let nested = context.nested(&state.plugin_name);
+    if nested.call_stack.contains(&target_id) {
+        return HostResponse::error(format!(
+            "Call cycle detected: {} -> {}",
+            nested.call_stack.join(" -> "),
+            target_id
+        ));
+    }
+    if nested.call_stack.len() >= MAX_CALL_DEPTH {
+        return HostResponse::error(format!("Maximum call depth of {} exceeded", MAX_CALL_DEPTH));
+    }
+
+    let payload = match serde_json::to_vec(&request.payload) {
+        Ok(payload) => payload,
+        Err(e) => return HostResponse::error(format!("Failed to encode payload: {}", e)),
+    };
+    // Aliases, payload checks and quotas apply as they do to direct calls
+    let function = match target.prepare_call(&target_id, &request.function, &payload) {
+        Ok(function) => function,
+        Err(e) => return HostResponse::error(format!("{:#}", e)),
+    };
+    let _call = match target.begin_call() {
+        Ok(call) => call,
+        Err(e) => return HostResponse::error(format!("{:#}", anyhow::Error::from(e))),
+    };
+    let runtime = match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime,
+        Err(_) => return HostResponse::error("Plugin calls need a Tokio runtime".to_string()),
+    };
+
+    // The target may be busy with an unrelated call that is itself waiting on
+    // the caller, so give up instead of blocking forever
+    let deadline = Instant::now() + TARGET_BUSY_TIMEOUT;
+    let mut loader = loop {
+        match target.loader.try_lock() {
+            Ok(loader) => break loader,
+            Err(std::sync::TryLockError::Poisoned(e)) => break e.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                return HostResponse::error(format!("Plugin '{}' is busy", request.target));
+            }
+        }
+    };
+
+    let (result, elapsed, fuel) = target.run_call(&mut loader, &target_id, function, &payload, &nested, &runtime);
+    drop(loader);
+    let result = target.check_output(&target_id, function, result);
+
+    let error = result.as_ref().err().map(|e| format!("{:#}", e));
+    state
+        .metrics
+        .blocking_write()
+        .record(&target_id, function, elapsed, error.as_deref(), fuel);
+
+    match result {
+        Ok(output) => HostResponse::success(
+            serde_json::from_slice(&output)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&output).into_owned())),
+        ),
+        Err(_) => HostResponse::error(error.unwrap_or_default()),
+    }
+}
+
+pub fn call_plugin_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "call_plugin",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            // Don't hold the user data lock across the nested call
+            let state = user_data.get()?.lock().unwrap().clone();
+            let context = plugin.host_context::<ExecutionContext>()?.clone();
+
+            let response = call_plugin(&state, &context, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,77 @@
+//! Progress of long-running calls, for progress bars in the UI
+//!
+//! `report_progress` publishes a `progress` event scoped to the plugin and the
+//! execution, which the app forwards to its frontend like those of
+//! `emit_event`, and stores the progress on the call's job record when the
+//! call runs as a background job. Reports closer together than
+//! [`PROGRESS_INTERVAL`] are dropped, except the one at 100%, so a plugin can
+//! report from a tight loop.
+
+use extism::{CurrentPlugin, Function, UserData, Val, ValType, PTR};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{emit, trace::traced, HostFunctionState, HostResponse};
+use crate::db::operations;
+use crate::plugins::ExecutionContext;
+
+/// Shortest time between progress reports that are passed on
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Longest progress message accepted, in bytes
+pub const MAX_PROGRESS_MESSAGE_BYTES: usize = 1024;
+
+/// Name of the events progress is published as
+pub const PROGRESS_EVENT: &str = "progress";
+
+fn report_progress(state: &HostFunctionState, context: Option<&ExecutionContext>, percent: f64, message: String) -> HostResponse<()> {
+    if !(0.0..=100.0).contains(&percent) {
+        return HostResponse::error(format!("Progress must be 0 to 100 percent, not {}", percent));
+    }
+    if message.len() > MAX_PROGRESS_MESSAGE_BYTES {
+        return HostResponse::error(format!("Progress messages may be at most {} bytes", MAX_PROGRESS_MESSAGE_BYTES));
+    }
+    let Some(context) = context else {
+        return HostResponse::success(());
+    };
+    if !context.progress_due(PROGRESS_INTERVAL, percent == 100.0) {
+        return HostResponse::success(());
+    }
+    let message = (!message.is_empty()).then_some(message);
+    // Only calls run as jobs have a job record with the execution's ID
+    let stored = state
+        .database
+        .with_connection(|conn| operations::update_job_progress(conn, &context.execution_id, percent / 100.0, message.as_deref()));
+    if let Err(e) = stored {
+        tracing::warn!("Failed to store the progress of execution {}: {:#}", context.execution_id, e);
+    }
+    emit::publish(
+        &state.plugin_name,
+        PROGRESS_EVENT.to_string(),
+        json!({ "percent": percent, "message": message }),
+        Some(context.execution_id.clone()),
+    );
+    HostResponse::success(())
+}
+
+/// `report_progress` takes how far the call is, as a percentage (an `f64`),
+/// and a message to show with it, which may be empty
+pub fn report_progress_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "report_progress",
+        [ValType::F64, PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let percent = inputs[0].unwrap_f64();
+            let message: String = plugin.memory_get_val(&inputs[1])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let context = plugin.host_context::<ExecutionContext>().ok().cloned();
+            let response = report_progress(&state, context.as_ref(), percent, message);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
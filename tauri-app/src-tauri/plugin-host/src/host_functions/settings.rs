@@ -0,0 +1,51 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::settings::SettingsStore;
+
+#[derive(Deserialize)]
+struct WatchRequest {
+    key: String,
+}
+
+/// Subscribe the plugin to changes of a setting and return its current value,
+/// null if it was never set. Changes are delivered to the plugin's
+/// `on_setting_changed` export as `{key, value, changed_at}`.
+fn watch_setting(state: &HostFunctionState, input: &str) -> HostResponse<serde_json::Value> {
+    let request: WatchRequest = match serde_json::from_str(input) {
+        Ok(r) => r,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let value = match SettingsStore::new(state.database.clone()).get::<serde_json::Value>(&request.key) {
+        Ok(value) => value.unwrap_or(serde_json::Value::Null),
+        Err(e) => return HostResponse::error(format!("{:#}", e)),
+    };
+    state
+        .setting_watches
+        .lock()
+        .unwrap()
+        .entry(state.plugin_name.clone())
+        .or_default()
+        .insert(request.key);
+    HostResponse::success(value)
+}
+
+pub fn watch_setting_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "watch_setting",
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+
+            let response = watch_setting(&state, &input);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
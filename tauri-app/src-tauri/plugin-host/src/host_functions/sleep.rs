@@ -0,0 +1,50 @@
+//! Pausing a call without burning fuel, bounded by the plugin's
+//! `quotas.max_sleep_ms` and woken early by cancellation
+
+use extism::{CurrentPlugin, Function, UserData, Val, ValType, PTR};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::ExecutionContext;
+
+/// Longest a single `host_sleep` may wait unless the manifest's
+/// `quotas.max_sleep_ms` says otherwise
+pub const DEFAULT_MAX_SLEEP_MS: u64 = 10_000;
+
+/// Block the call for up to the plugin's maximum sleep, e.g. between retries,
+/// without burning fuel. Cancelling the call, or it running past its
+/// execution time quota, wakes it and aborts the call.
+pub fn host_sleep_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "host_sleep",
+        [ValType::I64],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let ms = inputs[0].unwrap_i64();
+            let max_sleep_ms = user_data.get()?.lock().unwrap().max_sleep_ms;
+            let context = plugin.host_context::<ExecutionContext>().ok().cloned();
+
+            let response = if ms < 0 || ms as u64 > max_sleep_ms {
+                HostResponse::error(format!("Sleeps must be 0 to {} ms, not {}", max_sleep_ms, ms))
+            } else {
+                let duration = Duration::from_millis(ms as u64);
+                let completed = match &context {
+                    Some(context) => context.sleep(duration),
+                    None => {
+                        std::thread::sleep(duration);
+                        true
+                    }
+                };
+                if !completed {
+                    return Err(extism::Error::msg("Call was cancelled while sleeping"));
+                }
+                HostResponse::success(())
+            };
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
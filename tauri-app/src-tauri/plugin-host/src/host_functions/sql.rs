@@ -0,0 +1,253 @@
+//! SQL plugins write themselves, against the tables their capabilities allow
+//!
+//! `db_query` runs a statement that only reads and returns its rows;
+//! `db_execute` runs one that changes rows and returns how many it changed.
+//! An authorizer checks every table a statement reads or writes while it is
+//! prepared: a table is readable with its resource's `db:<resource>:read`
+//! and writable with `db:<resource>:write`, which also lets `db_execute` read
+//! it so statements can pick rows with `WHERE`. A plugin that ships
+//! migrations may also read and write its own tables, those named with
+//! [`plugin_table_prefix`](crate::db::migrations::plugin_table_prefix).
+//! Schema changes, pragmas, attached databases and transaction control are
+//! refused outright. Rows changed this way raise no host events.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, Statement};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::db::migrations::is_plugin_table;
+use crate::plugins::{Capability, DbAccess};
+
+/// Table each database resource covers
+pub const DB_RESOURCE_TABLES: &[(&str, &str)] = &[
+    ("users", "users"),
+    ("sessions", "sessions"),
+    ("email_verification", "email_verification_tokens"),
+    ("password_reset", "password_reset_tokens"),
+    ("audit", "audit_logs"),
+];
+
+/// Most rows `db_query` returns; queries matching more fail
+pub const MAX_QUERY_ROWS: usize = 1000;
+
+#[derive(Serialize)]
+struct Changes {
+    changes: usize,
+    last_insert_rowid: i64,
+}
+
+/// Whether a plugin holding `capabilities` has `access` to `table`; `owner`
+/// is the plugin if it has tables of its own
+fn may_access(owner: Option<&str>, capabilities: &[Capability], table: &str, access: DbAccess) -> bool {
+    if owner.is_some_and(|plugin| is_plugin_table(plugin, table)) {
+        return true;
+    }
+    DB_RESOURCE_TABLES
+        .iter()
+        .find(|(_, t)| t.eq_ignore_ascii_case(table))
+        .is_some_and(|(resource, _)| {
+            capabilities.iter().any(|c| c.covers(&Capability::Db { resource: Some(resource), access }))
+        })
+}
+
+/// Why a statement may not take `action`, if it may not
+fn refusal(owner: Option<&str>, capabilities: &[Capability], writable: bool, action: &AuthAction<'_>) -> Option<String> {
+    let write = |table: &str| {
+        if table.starts_with("sqlite_") {
+            Some("Statements may only read and write rows".to_string())
+        } else if !writable {
+            Some("db_query only runs statements that read; change rows with db_execute".to_string())
+        } else if !may_access(owner, capabilities, table, DbAccess::Write) {
+            Some(format!("This plugin may not write table '{}'", table))
+        } else {
+            None
+        }
+    };
+    match action {
+        AuthAction::Select | AuthAction::Recursive | AuthAction::Function { .. } => None,
+        AuthAction::Read { table_name, .. } => {
+            let readable = may_access(owner, capabilities, table_name, DbAccess::Read)
+                || (writable && may_access(owner, capabilities, table_name, DbAccess::Write));
+            (!readable).then(|| format!("This plugin may not read table '{}'", table_name))
+        }
+        AuthAction::Insert { table_name } | AuthAction::Delete { table_name } | AuthAction::Update { table_name, .. } => {
+            write(table_name)
+        }
+        AuthAction::Transaction { .. } | AuthAction::Savepoint { .. } => {
+            Some("Transactions are opened with db_begin, not in SQL".to_string())
+        }
+        _ => Some("Statements may only read and write rows".to_string()),
+    }
+}
+
+/// Bind a JSON array of positional parameters or an object of named ones
+fn bind(statement: &mut Statement<'_>, params: Value) -> rusqlite::Result<()> {
+    match params {
+        Value::Array(values) => {
+            if values.len() != statement.parameter_count() {
+                return Err(rusqlite::Error::InvalidParameterCount(values.len(), statement.parameter_count()));
+            }
+            for (index, value) in values.into_iter().enumerate() {
+                statement.raw_bind_parameter(index + 1, to_sql(value))?;
+            }
+        }
+        Value::Object(values) => {
+            for (name, value) in values {
+                let name = if name.starts_with([':', '@', '$']) { name } else { format!(":{}", name) };
+                let Some(index) = statement.parameter_index(&name)? else {
+                    return Err(rusqlite::Error::InvalidParameterName(name));
+                };
+                statement.raw_bind_parameter(index, to_sql(value))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn to_sql(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Number(n) => n
+            .as_i64()
+            .map(SqlValue::Integer)
+            .unwrap_or_else(|| SqlValue::Real(n.as_f64().unwrap_or_default())),
+        Value::String(s) => SqlValue::Text(s),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Column value as JSON; blobs become base64
+fn to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => Value::String(BASE64.encode(blob)),
+    }
+}
+
+/// Prepare `sql` under the plugin's table allow-list, bind `params` and run
+/// `f` on the statement
+fn run_statement<T>(
+    state: &HostFunctionState,
+    sql: &str,
+    params: &str,
+    writable: bool,
+    f: impl FnOnce(&Connection, &mut Statement<'_>) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let params: Value = if params.trim().is_empty() {
+        Value::Array(Vec::new())
+    } else {
+        serde_json::from_str(params).map_err(|e| format!("JSON parse error: {}", e))?
+    };
+    if !params.is_array() && !params.is_object() {
+        return Err("Parameters must be a JSON array or object".to_string());
+    }
+
+    let owner = state.own_tables.then(|| state.plugin_name.clone());
+    let capabilities = state.db_capabilities.clone();
+    let refused = Arc::new(Mutex::new(None::<String>));
+    let reason = refused.clone();
+    let outcome = state.database.with_connection(|conn| {
+        conn.authorizer(Some(move |context: AuthContext<'_>| {
+            match refusal(owner.as_deref(), &capabilities, writable, &context.action) {
+                Some(refusal) => {
+                    reason.lock().unwrap().get_or_insert(refusal);
+                    Authorization::Deny
+                }
+                None => Authorization::Allow,
+            }
+        }));
+        let result = conn.prepare(sql).and_then(|mut statement| {
+            bind(&mut statement, params)?;
+            f(conn, &mut statement)
+        });
+        conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        result
+    });
+    outcome.map_err(|e| refused.lock().unwrap().take().unwrap_or_else(|| e.to_string()))
+}
+
+fn db_query(state: &HostFunctionState, sql: &str, params: &str) -> HostResponse<Vec<Map<String, Value>>> {
+    let rows = run_statement(state, sql, params, false, |_, statement| {
+        let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+        let mut rows = statement.raw_query();
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            if results.len() == MAX_QUERY_ROWS {
+                return Ok(None);
+            }
+            let mut result = Map::new();
+            for (index, column) in columns.iter().enumerate() {
+                result.insert(column.clone(), to_json(row.get_ref(index)?));
+            }
+            results.push(result);
+        }
+        Ok(Some(results))
+    });
+    match rows {
+        Ok(Some(rows)) => HostResponse::success(rows),
+        Ok(None) => HostResponse::error(format!(
+            "Query matched more than {} rows; narrow it or add a LIMIT",
+            MAX_QUERY_ROWS
+        )),
+        Err(e) => HostResponse::error(e),
+    }
+}
+
+fn db_execute(state: &HostFunctionState, sql: &str, params: &str) -> HostResponse<Changes> {
+    let changes = run_statement(state, sql, params, true, |conn, statement| {
+        let changes = statement.raw_execute()?;
+        Ok(Changes {
+            changes,
+            last_insert_rowid: conn.last_insert_rowid(),
+        })
+    });
+    match changes {
+        Ok(changes) => HostResponse::success(changes),
+        Err(e) => HostResponse::error(e),
+    }
+}
+
+fn sql_host<T: Serialize + 'static>(
+    name: &'static str,
+    run: fn(&HostFunctionState, &str, &str) -> HostResponse<T>,
+    state: Arc<HostFunctionState>,
+) -> Function {
+    traced(
+        name,
+        [PTR, PTR],
+        [PTR],
+        UserData::new(state),
+        move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let sql: String = plugin.memory_get_val(&inputs[0])?;
+            let params: String = plugin.memory_get_val(&inputs[1])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = run(&state, &sql, &params);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `db_query` takes a statement and a JSON array or object of parameters and
+/// returns the rows as objects keyed by column name
+pub fn query_host(state: Arc<HostFunctionState>) -> Function {
+    sql_host("db_query", db_query, state)
+}
+
+/// `db_execute` takes a statement and a JSON array or object of parameters
+/// and returns `{"changes", "last_insert_rowid"}`
+pub fn execute_host(state: Arc<HostFunctionState>) -> Function {
+    sql_host("db_execute", db_execute, state)
+}
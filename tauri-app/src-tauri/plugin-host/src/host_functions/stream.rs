@@ -0,0 +1,22 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+
+use super::trace::traced;
+use crate::plugins::ExecutionContext;
+
+// Emit a chunk of output for the current execution. In streaming mode the
+// chunk is forwarded to the caller immediately; otherwise it is buffered and
+// prepended to the function's return value.
+pub fn stream_chunk_host() -> Function {
+    traced(
+        "stream_chunk",
+        [PTR],
+        [],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], _outputs: &mut [Val], _user_data: UserData<()>| {
+            let chunk: Vec<u8> = plugin.memory_get_val(&inputs[0])?;
+            let context = plugin.host_context::<ExecutionContext>()?;
+            context.push_chunk(&chunk);
+            Ok(())
+        },
+    )
+}
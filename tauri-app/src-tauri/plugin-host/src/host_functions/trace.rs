@@ -0,0 +1,72 @@
+//! Recording host function calls in the execution's trace
+//!
+//! Host functions are built with [`traced`] rather than `Function::new`, so
+//! each call lands in the trace of the execution it is made in; see
+//! [`crate::plugins::TraceStore`]. Embedders can build the functions they add
+//! with it too.
+
+use extism::{CurrentPlugin, Error, Function, UserData, Val, ValType};
+use std::time::Instant;
+
+use super::HostResponse;
+use crate::plugins::ExecutionContext;
+
+/// Start of the JSON of a [`HostResponse`] reporting an error
+const ERROR_RESPONSE: &[u8] = br#"{"success":false"#;
+
+/// Bytes of the memory blocks `values` point to; values that are not
+/// pointers to a block count as none
+fn payload_bytes(plugin: &mut CurrentPlugin, values: &[Val]) -> u64 {
+    values
+        .iter()
+        .map(|value| match value {
+            Val::I64(offset) if *offset > 0 => plugin.memory_length(*offset as u64).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The error in a JSON error response the function returned, if it did
+fn response_error(plugin: &mut CurrentPlugin, outputs: &[Val]) -> Option<String> {
+    let Some(Val::I64(offset)) = outputs.first() else {
+        return None;
+    };
+    let handle = plugin.memory_handle(*offset as u64)?;
+    let bytes = plugin.memory_bytes(handle).ok()?;
+    if !bytes.starts_with(ERROR_RESPONSE) {
+        return None;
+    }
+    serde_json::from_slice::<HostResponse<serde_json::Value>>(bytes)
+        .ok()
+        .and_then(|response| response.error)
+}
+
+/// `Function::new`, with calls recorded in the trace of the execution they
+/// are made in: their duration, the bytes passed in and out, and the error
+/// if they abort the plugin or return an error response
+pub fn traced<T: 'static, F>(
+    name: impl Into<String>,
+    params: impl IntoIterator<Item = ValType>,
+    results: impl IntoIterator<Item = ValType>,
+    user_data: UserData<T>,
+    f: F,
+) -> Function
+where
+    F: 'static + Fn(&mut CurrentPlugin, &[Val], &mut [Val], UserData<T>) -> Result<(), Error> + Sync + Send,
+{
+    let name = name.into();
+    let function = name.clone();
+    Function::new(name, params, results, user_data, move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<T>| {
+        let started = Instant::now();
+        let input_bytes = payload_bytes(plugin, inputs);
+        let result = f(plugin, inputs, outputs, user_data);
+        let (output_bytes, error) = match &result {
+            Ok(()) => (payload_bytes(plugin, outputs), response_error(plugin, outputs)),
+            Err(e) => (0, Some(format!("{:#}", e))),
+        };
+        if let Ok(context) = plugin.host_context::<ExecutionContext>() {
+            context.record_host_call(&function, started, input_bytes, output_bytes, error);
+        }
+        result
+    })
+}
@@ -0,0 +1,101 @@
+//! Transactions spanning several `db_*` calls of one plugin call
+//!
+//! `db_begin` opens a transaction on the shared connection and returns its
+//! ID; `db_commit` and `db_rollback` end it. Until then other users of the
+//! database wait, so the transaction belongs to the plugin call that opened
+//! it: calls it makes to other plugins run inside it, and the plugin manager
+//! rolls it back if the call ends with it still open. One open longer than
+//! the database's transaction limit is rolled back too, and the call's
+//! later `db_*` calls fail until it ends it. Host events the `db_*`
+//! functions raise inside it are only published once it commits.
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::{trace::traced, HostFunctionState, HostResponse};
+use crate::plugins::ExecutionContext;
+
+/// Transaction functions, linked for plugins that may write some table
+pub const TRANSACTION_FUNCTIONS: &[&str] = &["db_begin", "db_commit", "db_rollback"];
+
+#[derive(Deserialize, Serialize)]
+struct TransactionHandle {
+    transaction_id: String,
+}
+
+fn execution_id(plugin: &mut CurrentPlugin) -> Option<String> {
+    plugin.host_context::<ExecutionContext>().ok().map(|c| c.execution_id.clone())
+}
+
+fn db_begin(state: &HostFunctionState, execution_id: Option<String>) -> HostResponse<TransactionHandle> {
+    let Some(execution_id) = execution_id else {
+        return HostResponse::error("Transactions can only be opened during a plugin call".to_string());
+    };
+    match state.database.begin_transaction(&execution_id) {
+        Ok(transaction_id) => HostResponse::success(TransactionHandle { transaction_id }),
+        Err(e) => HostResponse::error(format!("{:#}", e)),
+    }
+}
+
+/// Commit or roll back the transaction named in `input`
+fn db_end(state: &HostFunctionState, execution_id: Option<String>, input: &str, commit: bool) -> HostResponse<()> {
+    let handle: TransactionHandle = match serde_json::from_str(input) {
+        Ok(handle) => handle,
+        Err(e) => return HostResponse::error(format!("JSON parse error: {}", e)),
+    };
+    let execution_id = execution_id.unwrap_or_default();
+    let result = if commit {
+        state.database.commit_transaction(&execution_id, &handle.transaction_id)
+    } else {
+        state.database.rollback_transaction(&execution_id, &handle.transaction_id)
+    };
+    match result {
+        Ok(()) => HostResponse::success(()),
+        Err(e) => HostResponse::error(format!("{:#}", e)),
+    }
+}
+
+/// `db_begin` takes nothing and returns `{"transaction_id"}`
+pub fn begin_host(state: Arc<HostFunctionState>) -> Function {
+    traced(
+        "db_begin",
+        [],
+        [PTR],
+        UserData::new(state),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = db_begin(&state, execution_id(plugin));
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+fn end_host(name: &'static str, commit: bool, state: Arc<HostFunctionState>) -> Function {
+    traced(
+        name,
+        [PTR],
+        [PTR],
+        UserData::new(state),
+        move |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<HostFunctionState>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let state = user_data.get()?.lock().unwrap().clone();
+            let response = db_end(&state, execution_id(plugin), &input, commit);
+            let output = serde_json::to_string(&response).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
+
+/// `db_commit` takes `{"transaction_id"}`
+pub fn commit_host(state: Arc<HostFunctionState>) -> Function {
+    end_host("db_commit", true, state)
+}
+
+/// `db_rollback` takes `{"transaction_id"}`
+pub fn rollback_host(state: Arc<HostFunctionState>) -> Function {
+    end_host("db_rollback", false, state)
+}
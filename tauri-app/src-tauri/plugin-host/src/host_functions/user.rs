@@ -0,0 +1,52 @@
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::HostResponse;
+use super::trace::traced;
+use crate::plugins::{CurrentUser, ExecutionContext};
+
+/// What plugins learn about the user a call is made for
+#[derive(Serialize)]
+struct CallingUser {
+    user_uuid: String,
+    /// Digest identifying the session; session IDs are bearer tokens, so
+    /// plugins never see them
+    session: Option<String>,
+    roles: Vec<String>,
+}
+
+impl From<CurrentUser> for CallingUser {
+    fn from(user: CurrentUser) -> Self {
+        Self {
+            user_uuid: user.user_uuid,
+            session: user
+                .session_id
+                .map(|id| hex::encode(&Sha256::digest(id.as_bytes())[..8])),
+            roles: user.roles,
+        }
+    }
+}
+
+// The signed-in user the call is made for, or null for calls nobody is
+// signed in to, such as scheduled runs; calls made through `call_plugin`
+// keep the caller's user, and background jobs run for the user who
+// submitted them, without a session
+pub fn get_current_user_host() -> Function {
+    traced(
+        "get_current_user",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            let user = plugin
+                .host_context::<ExecutionContext>()
+                .ok()
+                .and_then(|context| context.user.clone())
+                .map(CallingUser::from);
+            let output = serde_json::to_string(&HostResponse::success(user)).unwrap_or_default();
+            plugin.memory_set_val(&mut outputs[0], output)?;
+            Ok(())
+        },
+    )
+}
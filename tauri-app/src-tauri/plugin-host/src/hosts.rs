@@ -0,0 +1,58 @@
+//! Host patterns for plugin network access
+//!
+//! `allowed_hosts` and `denied_hosts` in plugin manifests, and the global
+//! deny list in settings, hold hostnames that may contain wildcards: `*`
+//! matches any run of characters (so `*.example.com` covers every subdomain
+//! but not `example.com` itself) and `?` a single one. Matching ignores case.
+//! A host is reachable if it matches an allowed pattern and no denied one.
+
+use anyhow::Result;
+
+/// Check that a pattern is a bare hostname, optionally with wildcards
+pub fn validate(pattern: &str) -> Result<()> {
+    if pattern.is_empty() {
+        anyhow::bail!("Host pattern must not be empty");
+    }
+    if let Some(c) = pattern
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '*' | '?')))
+    {
+        anyhow::bail!(
+            "Host pattern must be a hostname such as 'api.example.com' or '*.example.com', without scheme, port or path; found '{}' in '{}'",
+            c,
+            pattern
+        );
+    }
+    Ok(())
+}
+
+/// Whether a host matches a pattern
+pub fn matches(pattern: &str, host: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let host: Vec<char> = host.to_lowercase().chars().collect();
+
+    // Greedy wildcard matching, backtracking to the last `*` on a mismatch
+    let (mut p, mut h) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while h < host.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == host[h]) {
+            p += 1;
+            h += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, h));
+            p += 1;
+        } else if let Some((star_p, star_h)) = star {
+            p = star_p + 1;
+            h = star_h + 1;
+            star = Some((star_p, star_h + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether any of the patterns matches a host
+pub fn matches_any<S: AsRef<str>>(patterns: &[S], host: &str) -> bool {
+    patterns.iter().any(|pattern| matches(pattern.as_ref(), host))
+}
@@ -0,0 +1,88 @@
+//! ID generation for records created by the host and by plugins
+//!
+//! Record IDs follow the configured [`IdStrategy`], UUIDv7 by default: they
+//! sort by creation time, so inserts into the audit, job and execution tables
+//! append to the end of their indexes. IDs that double as bearer secrets
+//! (sessions, tokens) are always random UUIDv4 regardless of the strategy.
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// How record IDs are generated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// Time-ordered UUIDs
+    #[default]
+    UuidV7,
+    /// Fully random UUIDs
+    UuidV4,
+}
+
+/// What an ID identifies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    User,
+    Session,
+    Token,
+    AuditLog,
+    Job,
+    Execution,
+    Schedule,
+    Other,
+}
+
+impl IdKind {
+    pub const ALL: &'static [IdKind] = &[
+        IdKind::User,
+        IdKind::Session,
+        IdKind::Token,
+        IdKind::AuditLog,
+        IdKind::Job,
+        IdKind::Execution,
+        IdKind::Schedule,
+        IdKind::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdKind::User => "user",
+            IdKind::Session => "session",
+            IdKind::Token => "token",
+            IdKind::AuditLog => "audit_log",
+            IdKind::Job => "job",
+            IdKind::Execution => "execution",
+            IdKind::Schedule => "schedule",
+            IdKind::Other => "other",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.as_str() == kind)
+    }
+
+    /// IDs that grant access must not reveal when they were created
+    fn is_secret(&self) -> bool {
+        matches!(self, IdKind::Session | IdKind::Token)
+    }
+}
+
+static STRATEGY: RwLock<IdStrategy> = RwLock::new(IdStrategy::UuidV7);
+
+/// Set the strategy for IDs generated from now on
+pub fn set_strategy(strategy: IdStrategy) {
+    *STRATEGY.write().unwrap() = strategy;
+}
+
+/// Generate a new ID of the given kind
+pub fn new_id(kind: IdKind) -> String {
+    let strategy = if kind.is_secret() {
+        IdStrategy::UuidV4
+    } else {
+        *STRATEGY.read().unwrap()
+    };
+    match strategy {
+        IdStrategy::UuidV7 => uuid::Uuid::now_v7().to_string(),
+        IdStrategy::UuidV4 => uuid::Uuid::new_v4().to_string(),
+    }
+}
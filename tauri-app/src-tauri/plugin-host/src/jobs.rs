@@ -0,0 +1,785 @@
+//! Background plugin jobs
+//!
+//! `execute_plugin_async` hands a call to the job manager, which returns a job
+//! ID immediately and runs the call on a job worker. Job state lives in the
+//! `jobs` table so status and results survive the window being closed, and
+//! every state change is passed to the embedder's [`JobEventSink`] (the app
+//! emits them as `job:<id>` events, plus `job-completed` once the job
+//! reaches a terminal state).
+//!
+//! A job ends with exactly one terminal status (completed, failed or
+//! cancelled). Callers can wait for that with [`JobManager::await_job`]
+//! instead of polling, or listen to the events and then call
+//! [`JobManager::catch_up`] to replay the ones they missed.
+//!
+//! Unfinished jobs are leased to the running instance of the application,
+//! which renews the lease with a heartbeat. When the application is killed the
+//! leases expire: queued jobs are queued again, running jobs are retried while
+//! they have attempts left and are otherwise marked `interrupted` until they
+//! are retried or cancelled by hand.
+//!
+//! Jobs belong to the user who submitted them and run on their behalf, with
+//! the roles they hold when the job starts. Callers pass a [`JobScope`] to
+//! read or act on jobs; jobs outside it are reported as not found.
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::db::schema::Job;
+use crate::db::{operations, Database};
+use crate::error::{AppError, ErrorCode};
+use crate::ids::{self, IdKind};
+use crate::plugins::{CurrentUser, ExecutionContext, PayloadFormat, PluginManager};
+use crate::worker_pool::WorkerPool;
+
+/// How often the leases of this instance's jobs are renewed
+const LEASE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a lease lasts without a heartbeat before its job is recovered
+const LEASE_TIMEOUT_SECS: i64 = 30;
+
+/// Lifecycle state of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Was running when its lease expired; waits to be retried or cancelled
+    Interrupted,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Interrupted => "interrupted",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            "interrupted" => Some(JobStatus::Interrupted),
+            _ => None,
+        }
+    }
+
+    /// Whether the job has finished; no further events follow a terminal status
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+
+    /// Whether the job will not progress without outside action
+    pub fn is_settled(&self) -> bool {
+        self.is_terminal() || *self == JobStatus::Interrupted
+    }
+}
+
+/// Which jobs a caller may see and act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobScope<'a> {
+    /// Every job; for admins and the host itself
+    All,
+    /// Only the jobs this user submitted
+    Owner(&'a str),
+}
+
+impl JobScope<'_> {
+    pub fn includes(&self, job: &Job) -> bool {
+        match self {
+            JobScope::All => true,
+            JobScope::Owner(user) => job.owner_id.as_deref() == Some(*user),
+        }
+    }
+
+    fn owner(&self) -> Option<&str> {
+        match self {
+            JobScope::All => None,
+            JobScope::Owner(user) => Some(user),
+        }
+    }
+}
+
+/// Payload of `job:<id>` and `job-completed` events
+#[derive(Debug, Clone, Serialize)]
+pub struct JobEvent {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub error: Option<AppError>,
+}
+
+/// Receives every job status change, e.g. to forward it to a UI
+pub trait JobEventSink: Send + Sync {
+    fn job_event(&self, event: &JobEvent);
+}
+
+/// Publishes job events to the embedder and to in-process waiters
+#[derive(Clone)]
+struct JobEvents {
+    sink: Option<Arc<dyn JobEventSink>>,
+    updates: broadcast::Sender<JobEvent>,
+}
+
+impl JobEvents {
+    fn emit(&self, job_id: &str, status: JobStatus, error: Option<AppError>) {
+        let event = JobEvent {
+            job_id: job_id.to_string(),
+            status,
+            error,
+        };
+        if let Some(sink) = &self.sink {
+            sink.job_event(&event);
+        }
+        // No receivers just means nobody is waiting
+        let _ = self.updates.send(event);
+    }
+}
+
+/// Runs plugin calls in the background and tracks them in the database
+pub struct JobManager {
+    database: Arc<Database>,
+    plugin_manager: Arc<RwLock<PluginManager>>,
+    events: JobEvents,
+    pool: WorkerPool,
+    /// Worker tasks of jobs that have not finished yet
+    tasks: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+    /// Lease owner identifying this instance of the application
+    instance_id: String,
+}
+
+impl JobManager {
+    pub fn new(
+        database: Arc<Database>,
+        plugin_manager: Arc<RwLock<PluginManager>>,
+        events: Option<Arc<dyn JobEventSink>>,
+        workers: usize,
+    ) -> Result<Self> {
+        let manager = Self {
+            database,
+            plugin_manager,
+            events: JobEvents {
+                sink: events,
+                updates: broadcast::channel(256).0,
+            },
+            pool: WorkerPool::new(workers),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            instance_id: ids::new_id(IdKind::Other),
+        };
+
+        // Pick up jobs a previous run left behind
+        manager.recover_expired_leases()?;
+        Ok(manager)
+    }
+
+    /// Queue a plugin call and return its job ID
+    ///
+    /// A job interrupted by the application exiting is run again on the next
+    /// start until it has been started `max_attempts` times. `owner_id` is the
+    /// user submitting it, or None when the host does.
+    pub fn submit(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: serde_json::Value,
+        max_attempts: u32,
+        owner_id: Option<&str>,
+    ) -> Result<String> {
+        let job_id = ids::new_id(IdKind::Job);
+        let input = serde_json::to_string(&input)?;
+        let max_attempts = max_attempts.clamp(1, i32::MAX as u32) as i32;
+        let now = chrono::Utc::now().timestamp();
+
+        self.database
+            .with_connection(|conn| {
+                operations::create_job(conn, &job_id, plugin_name, function, &input, max_attempts, owner_id, now)?;
+                operations::lease_job(conn, &job_id, &self.instance_id, now)
+            })
+            .context("Failed to create job")?;
+
+        self.spawn_worker(&job_id, plugin_name, function, input, owner_id);
+        Ok(job_id)
+    }
+
+    /// Run a queued job on a worker
+    fn spawn_worker(&self, job_id: &str, plugin_name: &str, function: &str, input: String, owner_id: Option<&str>) {
+        let worker = JobWorker {
+            job_id: job_id.to_string(),
+            plugin_name: plugin_name.to_string(),
+            function: function.to_string(),
+            input,
+            owner_id: owner_id.map(str::to_string),
+            database: self.database.clone(),
+            plugin_manager: self.plugin_manager.clone(),
+            events: self.events.clone(),
+            pool: self.pool.clone(),
+            tasks: self.tasks.clone(),
+        };
+
+        // Announced before the worker exists so its events can't overtake this one
+        self.events.emit(job_id, JobStatus::Queued, None);
+
+        // Hold the task map while spawning so the worker can't remove its own
+        // entry before it has been inserted
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.insert(job_id.to_string(), tokio::spawn(worker.run()));
+        drop(tasks);
+    }
+
+    /// Lease maintenance loop, run under the task supervisor
+    ///
+    /// Renews this instance's leases, then recovers jobs whose leases expired.
+    pub async fn run_leases(self: Arc<Self>) -> Result<(), String> {
+        let mut ticker = tokio::time::interval(LEASE_HEARTBEAT_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let now = chrono::Utc::now().timestamp();
+            self.database
+                .with_connection(|conn| operations::renew_job_leases(conn, &self.instance_id, now))
+                .map_err(|e| format!("Failed to renew job leases: {}", e))?;
+            self.recover_expired_leases().map_err(|e| format!("{:#}", e))?;
+        }
+    }
+
+    /// Requeue or interrupt jobs whose lease holder stopped sending heartbeats
+    fn recover_expired_leases(&self) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let expired_before = now - LEASE_TIMEOUT_SECS;
+        let expired = self
+            .database
+            .with_connection(|conn| operations::get_expired_jobs(conn, expired_before))?;
+
+        for job in expired {
+            let retry = job.status == JobStatus::Queued.as_str() || job.attempts < job.max_attempts;
+            if retry {
+                let requeued = self.database.with_connection(|conn| {
+                    operations::requeue_expired_job(conn, &job.id, &self.instance_id, now, expired_before)
+                })?;
+                if requeued {
+                    tracing::warn!("Requeued job {} left unfinished by a previous run", job.id);
+                    self.spawn_worker(&job.id, &job.plugin_name, &job.function, job.input, job.owner_id.as_deref());
+                }
+                continue;
+            }
+
+            let error = AppError {
+                code: ErrorCode::Unavailable,
+                message: "Interrupted by application shutdown".to_string(),
+                plugin: Some(job.plugin_name.clone()),
+                function: Some(job.function.clone()),
+                retriable: true,
+                quota: None,
+            };
+            let interrupted = self.database.with_connection(|conn| {
+                operations::interrupt_expired_job(conn, &job.id, &error, expired_before)
+            })?;
+            if interrupted {
+                tracing::warn!(
+                    "Job {} was interrupted after {} attempt(s); it needs a manual retry",
+                    job.id,
+                    job.attempts
+                );
+                self.events.emit(&job.id, JobStatus::Interrupted, Some(error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run an interrupted job again; returns false if it isn't interrupted
+    pub fn retry(&self, job_id: &str, scope: JobScope) -> Result<bool> {
+        self.check_scope(job_id, scope)?;
+        let now = chrono::Utc::now().timestamp();
+        let retried = self
+            .database
+            .with_connection(|conn| operations::retry_job(conn, job_id, &self.instance_id, now))?;
+        if !retried {
+            return Ok(false);
+        }
+
+        let job = self.get(job_id, JobScope::All)?.context("Job disappeared while being retried")?;
+        self.spawn_worker(&job.id, &job.plugin_name, &job.function, job.input, job.owner_id.as_deref());
+        Ok(true)
+    }
+
+    /// Get a job by ID; None if it doesn't exist or is outside `scope`
+    pub fn get(&self, job_id: &str, scope: JobScope) -> Result<Option<Job>> {
+        let job = self
+            .database
+            .with_connection(|conn| operations::get_job(conn, job_id))?;
+        Ok(job.filter(|job| scope.includes(job)))
+    }
+
+    /// Fail as if the job didn't exist unless it is within `scope`
+    fn check_scope(&self, job_id: &str, scope: JobScope) -> Result<()> {
+        if scope != JobScope::All && self.get(job_id, scope)?.is_none() {
+            bail!("Job not found: {}", job_id);
+        }
+        Ok(())
+    }
+
+    /// Wait until a job is terminal or interrupted, or `timeout` elapses
+    ///
+    /// Returns the job as it is when the wait ends, or `None` if it doesn't
+    /// exist or is outside `scope`.
+    pub async fn await_job(&self, job_id: &str, timeout: Duration, scope: JobScope<'_>) -> Result<Option<Job>> {
+        // Subscribe before reading so an event between the two isn't missed
+        let mut updates = self.events.updates.subscribe();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let Some(job) = self.get(job_id, scope)? else {
+                return Ok(None);
+            };
+            if JobStatus::parse(&job.status).is_some_and(|s| s.is_settled()) {
+                return Ok(Some(job));
+            }
+
+            loop {
+                match tokio::time::timeout_at(deadline, updates.recv()).await {
+                    Err(_) => return self.get(job_id, scope),
+                    Ok(Ok(event)) if event.job_id != job_id => continue,
+                    // Our job changed, or events were dropped: re-read it
+                    Ok(_) => break,
+                }
+            }
+        }
+    }
+
+    /// Events of the job's current attempt, reconstructed from its stored state
+    ///
+    /// Subscribers listen to `job:<id>` first and then replay these; the last
+    /// one is the job's current state, and an event also received live has the
+    /// same status as its replayed copy.
+    pub fn catch_up(&self, job_id: &str, scope: JobScope) -> Result<Option<(Job, Vec<JobEvent>)>> {
+        let Some(job) = self.get(job_id, scope)? else {
+            return Ok(None);
+        };
+        let status = JobStatus::parse(&job.status)
+            .with_context(|| format!("Job {} has unknown status '{}'", job_id, job.status))?;
+
+        let event = |status, error| JobEvent {
+            job_id: job.id.clone(),
+            status,
+            error,
+        };
+        let mut events = vec![event(JobStatus::Queued, None)];
+        if status != JobStatus::Queued && job.started_at.is_some() {
+            events.push(event(JobStatus::Running, None));
+        }
+        if status.is_settled() {
+            let error = job.error.as_ref().map(|message| AppError {
+                code: job
+                    .error_code
+                    .as_deref()
+                    .and_then(ErrorCode::parse)
+                    .unwrap_or(ErrorCode::Internal),
+                message: message.clone(),
+                plugin: Some(job.plugin_name.clone()),
+                function: Some(job.function.clone()),
+                retriable: job.retriable,
+                quota: None,
+            });
+            events.push(event(status, error));
+        }
+        Ok(Some((job, events)))
+    }
+
+    /// List the jobs within `scope`, newest first
+    pub fn list(&self, status: Option<JobStatus>, limit: i32, offset: i32, scope: JobScope) -> Result<Vec<Job>> {
+        Ok(self.database.with_connection(|conn| {
+            operations::list_jobs(conn, status.map(|s| s.as_str()), scope.owner(), limit, offset)
+        })?)
+    }
+
+    /// Cancel a queued, running or interrupted job
+    ///
+    /// Queued jobs are dropped before they start; running jobs have their
+    /// plugin call interrupted. Returns false if the job had already finished.
+    pub async fn cancel(&self, job_id: &str, scope: JobScope<'_>) -> Result<bool> {
+        self.check_scope(job_id, scope)?;
+        let now = chrono::Utc::now().timestamp();
+        let cancelled = self.database.with_connection(|conn| {
+            operations::finish_job(conn, job_id, JobStatus::Cancelled.as_str(), None, None, now)
+        })?;
+        if !cancelled {
+            return Ok(false);
+        }
+
+        // The job ID doubles as the execution ID of the plugin call
+        self.plugin_manager.read().await.cancel_execution(job_id);
+        if let Some(task) = self.tasks.lock().unwrap().remove(job_id) {
+            task.abort();
+        }
+
+        self.events.emit(job_id, JobStatus::Cancelled, None);
+        Ok(true)
+    }
+
+    /// Change the number of job workers
+    pub fn set_workers(&self, count: usize) {
+        self.pool.resize(count);
+    }
+}
+
+/// Everything a worker task needs to run one job
+struct JobWorker {
+    job_id: String,
+    plugin_name: String,
+    function: String,
+    input: String,
+    /// User the job runs for; None for jobs the host submitted
+    owner_id: Option<String>,
+    database: Arc<Database>,
+    plugin_manager: Arc<RwLock<PluginManager>>,
+    events: JobEvents,
+    pool: WorkerPool,
+    tasks: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+}
+
+impl JobWorker {
+    /// The job's owner as a plugin sees them, with the roles they hold now;
+    /// the session they submitted from is not kept with the job
+    fn owner(&self) -> Result<Option<CurrentUser>> {
+        let Some(owner_id) = &self.owner_id else {
+            return Ok(None);
+        };
+        let roles = self
+            .database
+            .with_connection(|conn| operations::get_user_roles(conn, owner_id))?;
+        Ok(Some(CurrentUser {
+            user_uuid: owner_id.clone(),
+            session_id: None,
+            roles,
+        }))
+    }
+
+    async fn run(self) {
+        let _permit = self.pool.acquire().await;
+
+        let now = chrono::Utc::now().timestamp();
+        match self
+            .database
+            .with_connection(|conn| operations::mark_job_running(conn, &self.job_id, now))
+        {
+            Ok(true) => {
+                // Files of an earlier attempt are not this run's output
+                if let Err(e) = self
+                    .database
+                    .with_connection(|conn| operations::delete_execution_outputs(conn, &self.job_id))
+                {
+                    tracing::warn!("Failed to clear outputs of job {}: {}", self.job_id, e);
+                }
+                self.events.emit(&self.job_id, JobStatus::Running, None)
+            }
+            // Cancelled while queued; the cancellation was already announced
+            Ok(false) => {
+                self.tasks.lock().unwrap().remove(&self.job_id);
+                return;
+            }
+            Err(e) => tracing::error!("Failed to start job {}: {}", self.job_id, e),
+        }
+
+        let result = async {
+            let context = ExecutionContext::with_id(self.job_id.clone()).with_user(self.owner()?);
+            // Hold the manager only to look the plugin up, so installs and
+            // other writers don't wait for the call
+            let (executor, (input_format, output_format)) = {
+                let manager = self.plugin_manager.read().await;
+                (manager.executor(), manager.payload_formats(&self.plugin_name, &self.function).await)
+            };
+            // Inputs are stored as JSON; text and binary entry points get them decoded
+            let input = serde_json::from_str(&self.input).context("Stored job input is not JSON")?;
+            let input = input_format.encode_input(&input)?;
+            let output = executor
+                .execute_plugin_with_context(&self.plugin_name, &self.function, &input, &context)
+                .await?;
+            Ok::<_, anyhow::Error>(match output_format {
+                PayloadFormat::Binary => BASE64.encode(output),
+                _ => String::from_utf8_lossy(&output).into_owned(),
+            })
+        }
+        .await;
+
+        let (status, output, error) = match result {
+            Ok(output) => (JobStatus::Completed, Some(output), None),
+            Err(e) => (JobStatus::Failed, None, Some(AppError::from(e))),
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let finished = self.database.with_connection(|conn| {
+            operations::finish_job(conn, &self.job_id, status.as_str(), output.as_deref(), error.as_ref(), now)
+        });
+
+        // A job cancelled mid-call already recorded and announced its final state
+        match finished {
+            Ok(true) => self.events.emit(&self.job_id, status, error),
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to record result of job {}: {}", self.job_id, e),
+        }
+
+        self.tasks.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations;
+    use serde_json::json;
+    use std::path::{Path, PathBuf};
+
+    /// Records every job event it is sent
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<JobEvent>>);
+
+    impl JobEventSink for RecordingSink {
+        fn job_event(&self, event: &JobEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    impl RecordingSink {
+        fn statuses(&self, job_id: &str) -> Vec<JobStatus> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| event.job_id == job_id)
+                .map(|event| event.status)
+                .collect()
+        }
+    }
+
+    fn database() -> Arc<Database> {
+        let database = Database::new(PathBuf::from(":memory:")).unwrap();
+        database.with_connection(|conn| Ok(migrations::run_migrations(conn))).unwrap().unwrap();
+        Arc::new(database)
+    }
+
+    /// Job manager over `database`, with the `sleeper` test fixture
+    /// installed from `root`
+    async fn job_manager_with(root: &Path, database: Arc<Database>, sink: Option<Arc<dyn JobEventSink>>) -> JobManager {
+        let plugins = PluginManager::new_with_database(root.join("plugins"), database.clone()).unwrap();
+        install_fixture(&plugins, "sleeper").await;
+        JobManager::new(database, Arc::new(RwLock::new(plugins)), sink, 2).unwrap()
+    }
+
+    async fn install_fixture(plugins: &PluginManager, name: &str) {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+        // Fixtures are unsigned, so they are approved out of quarantine
+        let id = plugins.install_plugin(&fixture).await.unwrap();
+        plugins.approve_quarantined_plugin(&id, false).await.unwrap();
+    }
+
+    /// Job manager over a fresh in-memory database
+    async fn job_manager(root: &Path) -> JobManager {
+        job_manager_with(root, database(), None).await
+    }
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("a2e-jobs-{}", uuid::Uuid::new_v4()))
+    }
+
+    async fn wait_for_status(jobs: &JobManager, job_id: &str, status: JobStatus) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while jobs.get(job_id, JobScope::All).unwrap().unwrap().status != status.as_str() {
+            assert!(tokio::time::Instant::now() < deadline, "Job {} never became {}", job_id, status.as_str());
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_running_jobs_leave_the_plugin_manager_free() {
+        let root = temp_root();
+        let jobs = job_manager(&root).await;
+        let job_id = jobs.submit("sleeper", "nap", json!("600"), 1, None).unwrap();
+        wait_for_status(&jobs, &job_id, JobStatus::Running).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Installs and other writers get the manager while the call sleeps
+        let write = tokio::time::timeout(Duration::from_millis(250), jobs.plugin_manager.write()).await;
+        assert!(write.is_ok(), "The job held the plugin manager for its whole call");
+        drop(write);
+
+        let job = jobs.await_job(&job_id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+        assert_eq!(job.status, "completed", "Job failed: {:?}", job.error);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_await_job_waits_for_the_job_to_settle() {
+        let root = temp_root();
+        let jobs = job_manager(&root).await;
+        assert!(jobs.await_job("no-such-job", Duration::from_secs(1), JobScope::All).await.unwrap().is_none());
+
+        let job_id = jobs.submit("sleeper", "nap", json!("50"), 1, None).unwrap();
+        let job = jobs.await_job(&job_id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+        assert_eq!(job.status, "completed", "Job failed: {:?}", job.error);
+
+        // A wait that runs out returns the job as it is
+        let job_id = jobs.submit("sleeper", "nap", json!("600"), 1, None).unwrap();
+        let job = jobs.await_job(&job_id, Duration::from_millis(50), JobScope::All).await.unwrap().unwrap();
+        assert!(!JobStatus::parse(&job.status).unwrap().is_settled(), "{}", job.status);
+        let job = jobs.await_job(&job_id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+        assert_eq!(job.status, "completed");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_catch_up_replays_the_events_sent_live() {
+        let root = temp_root();
+        let sink = Arc::new(RecordingSink::default());
+        let jobs = job_manager_with(&root, database(), Some(sink.clone())).await;
+
+        let completed = jobs.submit("sleeper", "nap", json!("10"), 1, None).unwrap();
+        let failed = jobs.submit("sleeper", "no_such_function", json!("10"), 1, None).unwrap();
+        for (job_id, status) in [(&completed, JobStatus::Completed), (&failed, JobStatus::Failed)] {
+            jobs.await_job(job_id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+            let (job, events) = jobs.catch_up(job_id, JobScope::All).unwrap().unwrap();
+            assert_eq!(job.status, status.as_str());
+            let replayed: Vec<_> = events.iter().map(|event| event.status).collect();
+            assert_eq!(replayed, [JobStatus::Queued, JobStatus::Running, status]);
+            assert_eq!(sink.statuses(job_id), replayed);
+            assert_eq!(events[2].error.is_some(), status == JobStatus::Failed);
+        }
+        assert!(jobs.catch_up("no-such-job", JobScope::All).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancelled_jobs_end_once() {
+        let root = temp_root();
+        let sink = Arc::new(RecordingSink::default());
+        let jobs = job_manager_with(&root, database(), Some(sink.clone())).await;
+        let job_id = jobs.submit("sleeper", "nap", json!("900"), 1, None).unwrap();
+        wait_for_status(&jobs, &job_id, JobStatus::Running).await;
+
+        let (job, cancelled) = tokio::join!(jobs.await_job(&job_id, Duration::from_secs(5), JobScope::All), jobs.cancel(&job_id, JobScope::All));
+        assert!(cancelled.unwrap());
+        assert_eq!(job.unwrap().unwrap().status, "cancelled");
+        assert!(!jobs.cancel(&job_id, JobScope::All).await.unwrap(), "A finished job can't be cancelled again");
+
+        // The interrupted call doesn't report a result after the cancellation
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert_eq!(sink.statuses(&job_id), [JobStatus::Queued, JobStatus::Running, JobStatus::Cancelled]);
+        assert_eq!(jobs.get(&job_id, JobScope::All).unwrap().unwrap().status, "cancelled");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_jobs_left_behind_are_requeued_or_interrupted() {
+        let root = temp_root();
+        let database = database();
+        let now = chrono::Utc::now().timestamp();
+        let stale = now - LEASE_TIMEOUT_SECS - 60;
+        // (id, times started, max attempts, last heartbeat)
+        let left_behind = [
+            ("queued", 0, 1, stale),
+            ("retried", 1, 2, stale),
+            ("exhausted", 1, 1, stale),
+            ("still-leased", 1, 1, now),
+        ];
+        database
+            .with_connection(|conn| {
+                for (id, attempts, max_attempts, heartbeat) in left_behind {
+                    operations::create_job(conn, id, "sleeper", "nap", "\"10\"", max_attempts, None, now)?;
+                    operations::lease_job(conn, id, "previous-run", heartbeat)?;
+                    if attempts > 0 {
+                        operations::mark_job_running(conn, id, stale)?;
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let jobs = job_manager_with(&root, database, None).await;
+        for id in ["queued", "retried"] {
+            let job = jobs.await_job(id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+            assert_eq!(job.status, "completed", "Job {} failed: {:?}", id, job.error);
+        }
+        let exhausted = jobs.get("exhausted", JobScope::All).unwrap().unwrap();
+        assert_eq!(exhausted.status, "interrupted");
+        assert!(exhausted.retriable);
+        // A job whose holder is still sending heartbeats is left alone
+        assert_eq!(jobs.get("still-leased", JobScope::All).unwrap().unwrap().status, "running");
+        assert!(!jobs.retry("still-leased", JobScope::All).unwrap());
+
+        assert!(jobs.retry("exhausted", JobScope::All).unwrap());
+        let job = jobs.await_job("exhausted", Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+        assert_eq!(job.status, "completed", "Retry failed: {:?}", job.error);
+        assert_eq!(job.attempts, 2);
+        assert!(!jobs.retry("exhausted", JobScope::All).unwrap(), "Only interrupted jobs can be retried");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_users_only_see_and_touch_their_own_jobs() {
+        let root = temp_root();
+        let jobs = job_manager(&root).await;
+        let (alice, bob) = (JobScope::Owner("alice"), JobScope::Owner("bob"));
+        let job_id = jobs.submit("sleeper", "nap", json!("600"), 1, Some("alice")).unwrap();
+        let host_job = jobs.submit("sleeper", "nap", json!("10"), 1, None).unwrap();
+
+        // Someone else's job looks like one that doesn't exist
+        assert!(jobs.get(&job_id, bob).unwrap().is_none());
+        assert!(jobs.await_job(&job_id, Duration::from_millis(50), bob).await.unwrap().is_none());
+        assert!(jobs.catch_up(&job_id, bob).unwrap().is_none());
+        assert!(jobs.list(None, 100, 0, bob).unwrap().is_empty());
+        let error = jobs.cancel(&job_id, bob).await.unwrap_err();
+        assert_eq!(error.to_string(), format!("Job not found: {}", job_id));
+        assert!(jobs.retry(&job_id, bob).is_err());
+        assert_ne!(jobs.get(&job_id, JobScope::All).unwrap().unwrap().status, "cancelled");
+
+        let owned: Vec<_> = jobs.list(None, 100, 0, alice).unwrap().into_iter().map(|job| job.id).collect();
+        assert_eq!(owned, std::slice::from_ref(&job_id));
+        assert!(jobs.get(&host_job, alice).unwrap().is_none(), "Host jobs belong to nobody");
+        assert_eq!(jobs.list(None, 100, 0, JobScope::All).unwrap().len(), 2);
+        assert_eq!(jobs.get(&job_id, alice).unwrap().unwrap().owner_id.as_deref(), Some("alice"));
+        assert!(jobs.cancel(&job_id, alice).await.unwrap());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_jobs_run_for_the_user_who_submitted_them() {
+        let root = temp_root();
+        let database = database();
+        let jobs = job_manager_with(&root, database.clone(), None).await;
+        install_fixture(&*jobs.plugin_manager.read().await, "whoami").await;
+        database
+            .with_connection(|conn| {
+                operations::create_user(conn, "alice", "Alice", "alice@example.com", "hash", 0)?;
+                operations::grant_user_role(conn, "alice", "editor", 0)
+            })
+            .unwrap();
+
+        let whoami = |owner: Option<&'static str>| {
+            let jobs = &jobs;
+            async move {
+                let job_id = jobs.submit("whoami", "whoami", json!({}), 1, owner).unwrap();
+                let job = jobs.await_job(&job_id, Duration::from_secs(5), JobScope::All).await.unwrap().unwrap();
+                assert_eq!(job.status, "completed", "Job failed: {:?}", job.error);
+                serde_json::from_str::<serde_json::Value>(&job.result.unwrap()).unwrap()
+            }
+        };
+        let submitted = whoami(Some("alice")).await;
+        assert_eq!(submitted["data"], json!({ "user_uuid": "alice", "session": null, "roles": ["editor"] }));
+        let hosted = whoami(None).await;
+        assert_eq!(hosted["data"], serde_json::Value::Null);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,29 @@
+//! JSON diff and patch utilities (RFC 6902 JSON Patch / RFC 7386 Merge Patch)
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Compute an RFC 6902 JSON Patch that transforms `a` into `b`
+pub fn diff(a: &Value, b: &Value) -> Value {
+    let patch = json_patch::diff(a, b);
+    serde_json::to_value(patch).unwrap_or_else(|_| Value::Array(Vec::new()))
+}
+
+/// Apply a patch to a document and return the patched copy
+///
+/// An array is treated as an RFC 6902 JSON Patch (list of operations),
+/// anything else as an RFC 7386 JSON Merge Patch.
+pub fn apply(doc: &Value, patch: &Value) -> Result<Value> {
+    let mut result = doc.clone();
+
+    if patch.is_array() {
+        let operations: json_patch::Patch = serde_json::from_value(patch.clone())
+            .context("Invalid JSON Patch document")?;
+        json_patch::patch(&mut result, &operations)
+            .context("Failed to apply JSON Patch")?;
+    } else {
+        json_patch::merge(&mut result, patch);
+    }
+
+    Ok(result)
+}
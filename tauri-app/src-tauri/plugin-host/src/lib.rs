@@ -0,0 +1,45 @@
+//! Embeddable WASM plugin runtime
+//!
+//! Loads Extism plugins described by `plugin.json` (or `plugin.toml`)
+//! manifests, links them to the host functions (database, files, HTTP,
+//! plugin-to-plugin calls), and runs calls directly, as background jobs or on
+//! a schedule. The desktop app is one embedder; others start with
+//! [`HostBuilder`]:
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use plugin_host::HostBuilder;
+//!
+//! let host = HostBuilder::new("/var/lib/my-app/plugins")
+//!     .with_db_path("/var/lib/my-app/app.db")
+//!     .build()?;
+//! host.discover_plugins().await?;
+//! let output = host.plugins.read().await.execute_plugin("my-plugin", "convert", b"input").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bus;
+pub mod clipboard;
+pub mod db;
+pub mod error;
+pub mod events;
+pub mod host_functions;
+pub mod hosts;
+pub mod ids;
+pub mod jobs;
+pub mod json_diff;
+pub mod mail;
+pub mod output;
+pub mod paths;
+pub mod plugins;
+pub mod scheduler;
+pub mod secrets;
+pub mod settings;
+pub mod templates;
+pub mod trash;
+pub mod worker_pool;
+
+mod builder;
+
+pub use builder::{Host, HostBuilder};
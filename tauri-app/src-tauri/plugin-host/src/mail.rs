@@ -0,0 +1,191 @@
+//! Email plugins with the `email` capability send
+//!
+//! Messages go through a [`Mailer`]. By default that is an [`SmtpMailer`]
+//! relaying them through the SMTP server in the [`SMTP_KEY`] setting, read
+//! when each message is sent, with the password kept in the secret store
+//! (the OS keychain where the embedder plugs one in) rather than in settings.
+//! Embedders that deliver mail some other way set their own with
+//! [`HostBuilder::with_mailer`] or [`PluginManager::set_mailer`].
+//!
+//! [`SMTP_KEY`]: crate::settings::SMTP_KEY
+//! [`HostBuilder::with_mailer`]: crate::HostBuilder::with_mailer
+//! [`PluginManager::set_mailer`]: crate::plugins::PluginManager::set_mailer
+
+use anyhow::{Context, Result};
+use lettre::message::{header::ContentType, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::Database;
+use crate::secrets::{self, SecretStore};
+use crate::settings::{SettingsStore, SMTP_KEY};
+
+/// Most recipients one message may have
+pub const MAX_RECIPIENTS: usize = 50;
+
+/// Largest body, HTML and text together, in bytes
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Longest the SMTP server may take to answer before sending fails
+pub const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A message to send
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Email {
+    /// Recipient addresses, e.g. `ada@example.com` or `Ada <ada@example.com>`
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body_html: Option<String>,
+    pub body_text: Option<String>,
+}
+
+impl Email {
+    /// Check the recipients and that there is a body of a sendable size
+    pub fn validate(&self) -> Result<()> {
+        if self.to.is_empty() || self.to.len() > MAX_RECIPIENTS {
+            anyhow::bail!("Messages must have 1 to {} recipients, not {}", MAX_RECIPIENTS, self.to.len());
+        }
+        for address in &self.to {
+            address
+                .parse::<Mailbox>()
+                .with_context(|| format!("'{}' is not an email address", address))?;
+        }
+        if self.subject.contains(['\r', '\n']) {
+            anyhow::bail!("Subjects may not contain line breaks");
+        }
+        let size = self.body_html.as_ref().map_or(0, String::len) + self.body_text.as_ref().map_or(0, String::len);
+        if self.body_html.is_none() && self.body_text.is_none() {
+            anyhow::bail!("Messages need an HTML or a text body");
+        }
+        if size > MAX_BODY_BYTES {
+            anyhow::bail!("Message bodies may be at most {} bytes, not {}", MAX_BODY_BYTES, size);
+        }
+        Ok(())
+    }
+}
+
+/// Delivers email; implementations must be safe to share between plugins
+pub trait Mailer: Send + Sync {
+    /// Send a message that passed [`Email::validate`]
+    fn send(&self, email: &Email) -> Result<()>;
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// Upgraded to TLS with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// Unencrypted, for local relays only
+    None,
+}
+
+/// The SMTP server email is sent through, stored under [`SMTP_KEY`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// The security mode's usual port if unset
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Name of the secret holding the password
+    #[serde(default)]
+    pub password_secret: Option<String>,
+    /// Sender of every message, e.g. `App <noreply@example.com>`
+    pub from: String,
+}
+
+impl SmtpSettings {
+    /// Check the host, sender and secret name
+    pub fn validate(&self) -> Result<()> {
+        if self.host.trim().is_empty() {
+            anyhow::bail!("The SMTP host must be set");
+        }
+        self.from
+            .parse::<Mailbox>()
+            .with_context(|| format!("'{}' is not an email address", self.from))?;
+        if let Some(name) = &self.password_secret {
+            secrets::validate_name(name)?;
+            if self.username.is_none() {
+                anyhow::bail!("A password needs a username");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends email through the SMTP server in the [`SMTP_KEY`] setting
+pub struct SmtpMailer {
+    settings: SettingsStore,
+    secrets: Arc<dyn SecretStore>,
+}
+
+impl SmtpMailer {
+    pub fn new(database: Arc<Database>, secrets: Arc<dyn SecretStore>) -> Self {
+        Self {
+            settings: SettingsStore::new(database),
+            secrets,
+        }
+    }
+
+    fn transport(&self, settings: &SmtpSettings) -> Result<SmtpTransport> {
+        let mut builder = match settings.security {
+            SmtpSecurity::Tls => SmtpTransport::relay(&settings.host)?,
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&settings.host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&settings.host),
+        };
+        if let Some(port) = settings.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &settings.username {
+            let password = match &settings.password_secret {
+                Some(name) => self
+                    .secrets
+                    .get(name)?
+                    .with_context(|| format!("The SMTP password secret '{}' is not set", name))?,
+                None => String::new(),
+            };
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(builder.timeout(Some(SMTP_TIMEOUT)).build())
+    }
+}
+
+/// `email` as a MIME message from `from`
+fn message(from: Mailbox, email: &Email) -> Result<Message> {
+    let mut builder = Message::builder().from(from).subject(&email.subject);
+    for address in &email.to {
+        builder = builder.to(address.parse()?);
+    }
+    let message = match (&email.body_html, &email.body_text) {
+        (Some(html), Some(text)) => builder.multipart(MultiPart::alternative_plain_html(text.clone(), html.clone()))?,
+        (Some(html), None) => builder.singlepart(SinglePart::html(html.clone()))?,
+        (None, text) => builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(text.clone().unwrap_or_default())?,
+    };
+    Ok(message)
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        let settings: SmtpSettings = self
+            .settings
+            .get(SMTP_KEY)?
+            .context("No SMTP server is configured")?;
+        let message = message(settings.from.parse()?, email)?;
+        self.transport(&settings)?
+            .send(&message)
+            .with_context(|| format!("Failed to send through {}", settings.host))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! Placement of files produced by plugins
+//!
+//! Plugins hand their results to the `write_output_file` host function rather
+//! than choosing paths themselves, so every converter follows the same
+//! [`OutputPolicy`]: one output directory, one naming template and one rule
+//! for files that already exist, each of which a named preset may override.
+//! Files that are overwritten or deleted go to the [`TrashBin`] first.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::db::schema::TrashedFile;
+use crate::paths;
+use crate::settings::{ConflictPolicy, OutputPolicy};
+use crate::trash::TrashBin;
+
+/// Highest `-<n>` suffix tried when renaming around existing files
+const MAX_RENAME_ATTEMPTS: u32 = 10_000;
+
+/// A file a plugin wants to write
+pub struct OutputRequest<'a> {
+    pub plugin: &'a str,
+    pub preset: Option<&'a str>,
+    /// Base name of the output, usually derived from the input file
+    pub name: &'a str,
+    /// Extension, without the leading dot
+    pub ext: &'a str,
+}
+
+/// Where an output ended up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    /// False if the file existed and the policy said to skip it
+    pub written: bool,
+}
+
+/// Write `data` where the policy puts `request`
+pub fn write(policy: &OutputPolicy, request: &OutputRequest, data: &[u8], trash: &TrashBin) -> Result<OutputFile> {
+    let preset = request
+        .preset
+        .map(|name| {
+            policy
+                .presets
+                .get(name)
+                .with_context(|| format!("Unknown output preset '{}'", name))
+        })
+        .transpose()?;
+
+    let directory = match preset.and_then(|p| p.directory.as_ref()).or(policy.directory.as_ref()) {
+        Some(directory) => paths::resolve(&directory.to_string_lossy(), None)?,
+        None => dirs::download_dir().context("No output directory is configured and there is no downloads folder")?,
+    };
+    let template = preset
+        .and_then(|p| p.name_template.as_deref())
+        .unwrap_or(&policy.name_template);
+    let conflict = preset.and_then(|p| p.conflict).unwrap_or(policy.conflict);
+
+    std::fs::create_dir_all(&directory)
+        .with_context(|| format!("Failed to create output directory {:?}", directory))?;
+    let path = directory.join(render(template, request));
+
+    match conflict {
+        ConflictPolicy::Overwrite => {
+            if path.is_file() {
+                trash.trash(&path, Some(request.plugin))?;
+            }
+            std::fs::write(&path, data).with_context(|| format!("Failed to write {:?}", path))?;
+            Ok(OutputFile { path, written: true })
+        }
+        ConflictPolicy::Skip => {
+            let written = write_new(&path, data)?;
+            Ok(OutputFile { path, written })
+        }
+        ConflictPolicy::Rename => {
+            for n in 0..=MAX_RENAME_ATTEMPTS {
+                let candidate = if n == 0 { path.clone() } else { numbered(&path, n) };
+                if write_new(&candidate, data)? {
+                    return Ok(OutputFile {
+                        path: candidate,
+                        written: true,
+                    });
+                }
+            }
+            anyhow::bail!("No free file name for {:?}", path)
+        }
+    }
+}
+
+/// Move a file in one of the policy's output directories to the trash
+///
+/// Plugins can only delete what they could have written.
+pub fn delete(policy: &OutputPolicy, plugin: &str, path: &Path, trash: &TrashBin) -> Result<TrashedFile> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {:?}", path))?;
+    let directories = policy
+        .directory
+        .clone()
+        .or_else(dirs::download_dir)
+        .into_iter()
+        .chain(policy.presets.values().filter_map(|preset| preset.directory.clone()));
+    let allowed = directories
+        .filter_map(|dir| paths::resolve(&dir.to_string_lossy(), None).ok())
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| path.starts_with(dir));
+    if !allowed {
+        anyhow::bail!("{:?} is not in an output directory", path);
+    }
+    trash.trash(&path, Some(plugin))
+}
+
+/// Write to a file that must not exist yet; returns false if it does
+fn write_new(path: &Path, data: &[u8]) -> Result<bool> {
+    let file = std::fs::OpenOptions::new().write(true).create_new(true).open(path);
+    match file {
+        Ok(mut file) => {
+            file.write_all(data)
+                .with_context(|| format!("Failed to write {:?}", path))?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to create {:?}", path)),
+    }
+}
+
+/// `dir/stem.ext` -> `dir/stem-n.ext`
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// Fill in a name template; placeholder values never contain path separators
+fn render(template: &str, request: &OutputRequest) -> String {
+    let now = chrono::Local::now();
+    let name = template
+        .replace("{name}", &sanitize(request.name))
+        .replace("{ext}", &sanitize(request.ext.trim_start_matches('.')))
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string())
+        .replace("{plugin}", &sanitize(request.plugin))
+        .replace("{preset}", &sanitize(request.preset.unwrap_or_default()));
+    let name = name.trim_end_matches('.').trim();
+    if name.is_empty() {
+        "output".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Replace characters that aren't allowed in file names on common platforms
+fn sanitize(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace()).to_string()
+}
@@ -0,0 +1,59 @@
+//! Virtual path tokens
+//!
+//! Host paths in plugin manifests (`allowed_paths`) and in the output policy
+//! may start with a token such as `$DOWNLOADS/converted`. Tokens are resolved
+//! to the platform's directories when the path is used, so the same plugin
+//! bundle or policy works on every OS and for every user.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::plugins::is_relative_subpath;
+
+/// The plugin's own data directory; only meaningful in a plugin manifest
+pub const DATA_TOKEN: &str = "$DATA";
+
+/// Every token a path may start with
+pub const PATH_TOKENS: &[&str] = &[DATA_TOKEN, "$DOWNLOADS", "$DOCUMENTS", "$HOME", "$TEMP"];
+
+/// Split a path into its leading token and the rest; None if it does not start with one
+pub fn split_token(path: &str) -> Option<(&str, &str)> {
+    if !path.starts_with('$') {
+        return None;
+    }
+    Some(path.split_once(['/', '\\']).unwrap_or((path, "")))
+}
+
+/// Check that a path starting with a token names a known token and stays inside it
+pub fn validate(path: &str) -> Result<()> {
+    let Some((token, rest)) = split_token(path) else {
+        return Ok(());
+    };
+    if !PATH_TOKENS.contains(&token) {
+        anyhow::bail!("Unknown path token '{}'; expected one of: {}", token, PATH_TOKENS.join(", "));
+    }
+    if !rest.is_empty() && !is_relative_subpath(rest) {
+        anyhow::bail!("Path after {} must be relative and stay inside it: {}", token, rest);
+    }
+    Ok(())
+}
+
+/// Resolve the token a path starts with; paths without one are returned as they are
+///
+/// `data_dir` is what [`DATA_TOKEN`] stands for, if anything.
+pub fn resolve(path: &str, data_dir: Option<&Path>) -> Result<PathBuf> {
+    let Some((token, rest)) = split_token(path) else {
+        return Ok(PathBuf::from(path));
+    };
+    validate(path)?;
+    let base = match token {
+        DATA_TOKEN => data_dir.map(Path::to_path_buf),
+        "$DOWNLOADS" => dirs::download_dir(),
+        "$DOCUMENTS" => dirs::document_dir(),
+        "$HOME" => dirs::home_dir(),
+        "$TEMP" => Some(std::env::temp_dir()),
+        _ => None,
+    }
+    .with_context(|| format!("{} is not available here", token))?;
+    Ok(if rest.is_empty() { base } else { base.join(rest) })
+}
@@ -0,0 +1,274 @@
+//! Plugin capabilities and approval of the sensitive ones
+//!
+//! Manifests declare what a plugin may do as [`Capability`] values; anything
+//! outside the taxonomy is rejected when the manifest is loaded. Host function
+//! registration, network and filesystem access all key off them.
+//!
+//! Installing a plugin that asks for a capability the user has not decided on
+//! yet pauses until the frontend answers a [`CapabilityRequest`]. Decisions
+//! are stored per plugin, so updates only ask about capabilities that are new.
+//! A capability that is not granted is withheld when the plugin is loaded.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+
+use crate::ids::{self, IdKind};
+
+/// Parts of the app database a plugin can limit its access to, as
+/// `db:<resource>:read` or `db:<resource>:write`
+pub const DB_RESOURCES: &[&str] = &["users", "sessions", "email_verification", "password_reset", "audit"];
+
+/// Kind of access to the app database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DbAccess {
+    Read,
+    Write,
+}
+
+/// Something a plugin may do, declared in its manifest's `capabilities`
+///
+/// Written as `net`, `fs`, `tick`, `wasi`, `clipboard`, `exec`, `email`, `db:read`, `db:write`, or
+/// `db:<resource>:read` and `db:<resource>:write` for one of
+/// [`DB_RESOURCES`]. `network` and `filesystem` are accepted for `net` and `fs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Capability {
+    /// Outbound HTTP to the manifest's `allowed_hosts`, which imply it
+    Network,
+    /// Host files outside the plugin's data directory: `allowed_paths` there
+    /// (which imply it) and the file-writing host functions
+    Filesystem,
+    /// Being run by the host on the manifest's `schedules` and called with
+    /// `tick` events
+    Tick,
+    /// WASI (stdio, clocks, filesystem); also needs the plugin to be trusted
+    Wasi,
+    /// Reading and writing the system clipboard's text
+    Clipboard,
+    /// Running the manifest's `allowed_commands` as host processes; only in
+    /// the trusted sandbox
+    Exec,
+    /// Sending email through the host's mail server
+    Email,
+    /// The `db_*` host functions of one part of the database, or of every
+    /// part when `resource` is None. Write access does not include read access.
+    Db {
+        resource: Option<&'static str>,
+        access: DbAccess,
+    },
+}
+
+impl Capability {
+    /// Writes anywhere in the app database; how the user approves any database write
+    pub const DB_WRITE: Capability = Capability::Db {
+        resource: None,
+        access: DbAccess::Write,
+    };
+
+    /// Capabilities the user is asked to approve before a plugin may use them
+    pub const SENSITIVE: &'static [Capability] = &[Capability::Network, Capability::Filesystem, Capability::Clipboard, Capability::Exec, Capability::Email, Capability::DB_WRITE];
+
+    /// Sensitive capabilities the user is asked to approve even for trusted plugins
+    pub const ASKED_OF_TRUSTED: &'static [Capability] = &[Capability::Exec];
+
+    /// Whether declaring this capability covers `other`, e.g. `db:write`
+    /// covers `db:users:write`
+    pub fn covers(&self, other: &Capability) -> bool {
+        match (self, other) {
+            (
+                Capability::Db { resource: None, access },
+                Capability::Db { access: other_access, .. },
+            ) => access == other_access,
+            _ => self == other,
+        }
+    }
+
+    /// The sensitive capability the user approves for this one, if any
+    pub fn approval(&self) -> Option<Capability> {
+        match self {
+            Capability::Db { access: DbAccess::Write, .. } => Some(Capability::DB_WRITE),
+            capability if Self::SENSITIVE.contains(capability) => Some(*capability),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = |access: &DbAccess| match access {
+            DbAccess::Read => "read",
+            DbAccess::Write => "write",
+        };
+        match self {
+            Capability::Network => f.write_str("net"),
+            Capability::Filesystem => f.write_str("fs"),
+            Capability::Tick => f.write_str("tick"),
+            Capability::Wasi => f.write_str("wasi"),
+            Capability::Clipboard => f.write_str("clipboard"),
+            Capability::Exec => f.write_str("exec"),
+            Capability::Email => f.write_str("email"),
+            Capability::Db { resource: None, access: a } => write!(f, "db:{}", access(a)),
+            Capability::Db { resource: Some(resource), access: a } => write!(f, "db:{}:{}", resource, access(a)),
+        }
+    }
+}
+
+impl FromStr for Capability {
+    type Err = anyhow::Error;
+
+    fn from_str(capability: &str) -> anyhow::Result<Self> {
+        let parse_access = |access: &str| match access {
+            "read" => Some(DbAccess::Read),
+            "write" => Some(DbAccess::Write),
+            _ => None,
+        };
+        let parsed = match capability {
+            "net" | "network" => Some(Capability::Network),
+            "fs" | "filesystem" => Some(Capability::Filesystem),
+            "tick" => Some(Capability::Tick),
+            "wasi" => Some(Capability::Wasi),
+            "clipboard" => Some(Capability::Clipboard),
+            "exec" => Some(Capability::Exec),
+            "email" => Some(Capability::Email),
+            _ => match capability.strip_prefix("db:").map(|rest| rest.split_once(':')) {
+                Some(None) => parse_access(&capability[3..]).map(|access| Capability::Db { resource: None, access }),
+                Some(Some((resource, access))) => DB_RESOURCES
+                    .iter()
+                    .find(|r| **r == resource)
+                    .zip(parse_access(access))
+                    .map(|(resource, access)| Capability::Db { resource: Some(resource), access }),
+                None => None,
+            },
+        };
+        parsed.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown capability '{}'; expected net, fs, tick, wasi, clipboard, exec, email, db:read, db:write, or db:<resource>:read or db:<resource>:write where resource is one of: {}",
+                capability,
+                DB_RESOURCES.join(", ")
+            )
+        })
+    }
+}
+
+impl Serialize for Capability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Capability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let capability = String::deserialize(deserializer)?;
+        capability.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How long an install waits for an answer before denying the request
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A plugin asking for capabilities during install
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityRequest {
+    pub id: String,
+    pub plugin: String,
+    pub version: String,
+    /// Capabilities awaiting a decision
+    pub capabilities: Vec<Capability>,
+}
+
+/// Capabilities granted and denied to a plugin
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapabilityDecisions {
+    pub granted: Vec<Capability>,
+    pub denied: Vec<Capability>,
+}
+
+/// An open request and the channel its answer, the granted capabilities, goes to
+type PendingRequest = (CapabilityRequest, oneshot::Sender<Vec<Capability>>);
+
+/// Open capability requests and the installs waiting on them
+pub struct CapabilityApprovals {
+    requests: broadcast::Sender<CapabilityRequest>,
+    pending: Mutex<HashMap<String, PendingRequest>>,
+}
+
+impl Default for CapabilityApprovals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CapabilityApprovals {
+    pub fn new() -> Self {
+        let (requests, _) = broadcast::channel(16);
+        Self {
+            requests,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receive requests as they are opened, to prompt the user
+    pub fn subscribe(&self) -> broadcast::Receiver<CapabilityRequest> {
+        self.requests.subscribe()
+    }
+
+    /// Requests still waiting for an answer
+    pub fn pending(&self) -> Vec<CapabilityRequest> {
+        self.pending
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(request, _)| request.clone())
+            .collect()
+    }
+
+    /// Ask for `capabilities` and wait for the answer
+    ///
+    /// Everything is denied if nobody is listening for requests or no answer
+    /// arrives within [`APPROVAL_TIMEOUT`].
+    pub async fn request(&self, plugin: &str, version: &str, capabilities: Vec<Capability>) -> CapabilityDecisions {
+        let request = CapabilityRequest {
+            id: ids::new_id(IdKind::Other),
+            plugin: plugin.to_string(),
+            version: version.to_string(),
+            capabilities,
+        };
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(request.id.clone(), (request.clone(), tx));
+
+        let granted = if self.requests.send(request.clone()).is_err() {
+            tracing::warn!("Nobody to approve capabilities of plugin '{}'; denying them", plugin);
+            Vec::new()
+        } else {
+            match tokio::time::timeout(APPROVAL_TIMEOUT, rx).await {
+                Ok(Ok(granted)) => granted,
+                _ => {
+                    tracing::warn!("Capability request of plugin '{}' was not answered; denying it", plugin);
+                    Vec::new()
+                }
+            }
+        };
+        self.pending.lock().unwrap().remove(&request.id);
+
+        let (granted, denied) = request
+            .capabilities
+            .into_iter()
+            .partition(|capability| granted.contains(capability));
+        CapabilityDecisions { granted, denied }
+    }
+
+    /// Answer a request, granting `granted` and denying the rest; false if it is not open
+    pub fn respond(&self, request_id: &str, granted: Vec<Capability>) -> bool {
+        match self.pending.lock().unwrap().remove(request_id) {
+            Some((_, tx)) => tx.send(granted).is_ok(),
+            None => false,
+        }
+    }
+}
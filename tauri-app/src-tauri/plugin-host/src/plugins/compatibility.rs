@@ -0,0 +1,233 @@
+//! Compatibility of installed plugins with this build of the host
+//!
+//! Checked after the app was updated, so plugins the new version cannot run
+//! are reported with a link to a newer version instead of failing to load or
+//! failing when called.
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::loader::PluginLoader;
+use super::manifest::PluginManifest;
+use crate::host_functions::{self, HOST_API_LEVEL, HOST_FUNCTION_NAMES};
+
+/// Import module of the host functions plugins declare with the PDK
+const HOST_FUNCTION_MODULE: &str = "extism:host/user";
+
+/// An installed plugin that needs an update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCompatibility {
+    /// Plugin ID, or the directory name if the manifest cannot be read
+    pub plugin: String,
+    pub version: Option<String>,
+    pub issues: Vec<String>,
+    /// Where to get a newer version, if known
+    pub link: Option<String>,
+}
+
+/// Result of checking every installed plugin against this build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    pub app_version: String,
+    /// Number of plugins checked
+    pub checked: usize,
+    pub incompatible: Vec<PluginCompatibility>,
+    pub generated_at: i64,
+}
+
+/// Operating systems a manifest's `platforms` can name, besides `desktop`
+/// (Windows, macOS and Linux) and `mobile` (Android and iOS)
+pub const PLATFORM_OSES: &[&str] = &["windows", "macos", "linux", "android", "ios"];
+
+/// CPU architectures a platform can be narrowed to, as `<os>-<arch>`
+pub const PLATFORM_ARCHES: &[&str] = &["x86_64", "aarch64", "x86", "arm"];
+
+/// This host's platform as `<os>-<arch>`, e.g. `linux-x86_64`
+pub fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Check an entry of a manifest's `platforms`: an OS, `desktop` or
+/// `mobile`, optionally followed by `-<arch>`
+pub fn validate_platform(platform: &str) -> anyhow::Result<()> {
+    let (os, arch) = match platform.split_once('-') {
+        Some((os, arch)) => (os, Some(arch)),
+        None => (platform, None),
+    };
+    if !PLATFORM_OSES.contains(&os) && os != "desktop" && os != "mobile" {
+        anyhow::bail!(
+            "Unknown platform '{}'; use one of {}, desktop or mobile",
+            os,
+            PLATFORM_OSES.join(", ")
+        );
+    }
+    if let Some(arch) = arch.filter(|arch| !PLATFORM_ARCHES.contains(arch)) {
+        anyhow::bail!("Unknown architecture '{}'; use one of {}", arch, PLATFORM_ARCHES.join(", "));
+    }
+    Ok(())
+}
+
+/// Whether a `platforms` entry covers the OS and architecture given
+fn platform_matches(platform: &str, os: &str, arch: &str) -> bool {
+    let (target_os, target_arch) = match platform.split_once('-') {
+        Some((target_os, target_arch)) => (target_os, Some(target_arch)),
+        None => (platform, None),
+    };
+    let os_matches = match target_os {
+        "desktop" => matches!(os, "windows" | "macos" | "linux"),
+        "mobile" => matches!(os, "android" | "ios"),
+        target_os => target_os == os,
+    };
+    os_matches && target_arch.is_none_or(|target_arch| target_arch == arch)
+}
+
+/// Why the plugin does not run on this platform, if its `platforms` leave it out
+pub fn platform_issue(manifest: &PluginManifest) -> Option<String> {
+    let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    if manifest.platforms.is_empty() || manifest.platforms.iter().any(|platform| platform_matches(platform, os, arch)) {
+        return None;
+    }
+    Some(format!(
+        "Unsupported on this platform ({}); the plugin supports {}",
+        current_platform(),
+        manifest.platforms.join(", ")
+    ))
+}
+
+/// Version of this crate, which plugins' `min_host_version` is checked
+/// against; also the default app version of a plugin manager
+pub fn host_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("Package version is valid semver")
+}
+
+/// Reasons the plugin cannot run on this build of the plugin runtime,
+/// whatever app embeds it, or on this platform
+///
+/// Plugins with any are refused at load time rather than failing when called.
+pub fn host_requirements(manifest: &PluginManifest) -> Vec<String> {
+    let mut issues: Vec<String> = platform_issue(manifest).into_iter().collect();
+
+    if let Some(min_host_version) = &manifest.min_host_version {
+        let host_version = host_version();
+        match Version::parse(min_host_version) {
+            Ok(required) if required > host_version => issues.push(format!(
+                "Requires plugin host version {} or newer; this is {}",
+                required, host_version
+            )),
+            Ok(_) => {}
+            Err(e) => issues.push(format!("Invalid min_host_version '{}': {}", min_host_version, e)),
+        }
+    }
+
+    if let Some(level) = manifest.host_api_level {
+        if level > HOST_API_LEVEL {
+            issues.push(format!(
+                "Targets host API level {}; this host supports up to level {}",
+                level, HOST_API_LEVEL
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Reasons the plugin in `plugin_dir` cannot run on `app_version`, given the
+/// host functions the embedder adds to the built-in ones
+pub fn check_plugin(
+    manifest: &PluginManifest,
+    plugin_dir: &Path,
+    app_version: &Version,
+    extra_functions: &[String],
+) -> Vec<String> {
+    let mut issues = host_requirements(manifest);
+
+    if let Some(min_app_version) = &manifest.min_app_version {
+        match Version::parse(min_app_version) {
+            Ok(required) if &required > app_version => issues.push(format!(
+                "Requires app version {} or newer; this is {}",
+                required, app_version
+            )),
+            Ok(_) => {}
+            Err(e) => issues.push(format!("Invalid min_app_version '{}': {}", min_app_version, e)),
+        }
+    }
+
+    for module in manifest.wasm_module.paths() {
+        let path = plugin_dir.join(module);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                issues.push(format!("Cannot read WASM module {}: {}", module, e));
+                continue;
+            }
+        };
+        for name in host_imports(&bytes) {
+            if !HOST_FUNCTION_NAMES.contains(&name.as_str()) && !extra_functions.contains(&name) {
+                issues.push(format!("Imports host function '{}', which this app does not provide", name));
+            }
+        }
+    }
+    for name in &manifest.required_host_functions {
+        if !HOST_FUNCTION_NAMES.contains(&name.as_str()) && !extra_functions.contains(name) {
+            issues.push(format!("Requires host function '{}', which this app does not provide", name));
+        }
+    }
+    issues.extend(undeclared_capabilities(manifest, plugin_dir));
+
+    issues
+}
+
+/// Host functions the plugin's modules import without declaring the
+/// capability they need, which leaves them unlinked
+///
+/// Modules that cannot be read are skipped; loading reports those.
+pub fn undeclared_capabilities(manifest: &PluginManifest, plugin_dir: &Path) -> Vec<String> {
+    let mut issues = Vec::new();
+    for module in manifest.wasm_module.paths() {
+        let Ok(bytes) = std::fs::read(plugin_dir.join(module)) else {
+            continue;
+        };
+        for name in host_imports(&bytes) {
+            let Some(capability) = host_functions::declared_capability(&name) else {
+                continue;
+            };
+            if !manifest.declares(&capability) {
+                issues.push(format!(
+                    "Imports host function '{}' without declaring the '{}' capability",
+                    name, capability
+                ));
+            }
+        }
+    }
+    issues
+}
+
+/// Warnings for the deprecated host functions a plugin's modules import
+pub fn deprecated_host_functions(manifest: &PluginManifest, plugin_dir: &Path) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for module in manifest.wasm_module.paths() {
+        let Ok(bytes) = std::fs::read(plugin_dir.join(module)) else {
+            continue;
+        };
+        for name in host_imports(&bytes) {
+            if let Some((_, replacement)) = host_functions::DEPRECATED_HOST_FUNCTIONS.iter().find(|(n, _)| *n == name) {
+                warnings.push(format!(
+                    "Plugin '{}' imports deprecated host function '{}'; use '{}' instead",
+                    manifest.id(),
+                    name,
+                    replacement
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+/// Names of the host functions a core module imports
+pub(super) fn host_imports(bytes: &[u8]) -> impl Iterator<Item = String> {
+    PluginLoader::wasm_imports(bytes)
+        .into_iter()
+        .filter(|(module, _)| module == HOST_FUNCTION_MODULE)
+        .map(|(_, name)| name)
+}
@@ -0,0 +1,374 @@
+//! Loader for WebAssembly Component Model plugins
+//!
+//! Components export typed WIT functions rather than Extism's bytes-in,
+//! bytes-out calls. Entry points are invoked dynamically: the JSON input is
+//! converted to the function's parameter types and the results back to JSON.
+//! A function taking a single `string` receives the raw input, and a `string`
+//! result is returned as-is, so string-based plugins behave like their Extism
+//! counterparts. A `result` return is unwrapped, with `err` failing the call.
+//!
+//! Components may import the host interface described in `wit/plugin.wit`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Number, Value};
+use std::path::Path;
+use std::sync::Arc;
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Instance, Linker, Type, Val};
+use wasmtime::{Config, Engine, Store, StoreContextMut, StoreLimits, StoreLimitsBuilder};
+
+use super::logs::{PluginLogEntry, PluginLogStore};
+
+/// Host interface components import, as declared in `wit/plugin.wit`
+pub const HOST_INTERFACE: &str = "a2e:plugin/host@0.1.0";
+
+struct HostState {
+    plugin_name: String,
+    logs: Arc<PluginLogStore>,
+    limits: StoreLimits,
+}
+
+pub struct ComponentPlugin {
+    engine: Engine,
+    store: Store<HostState>,
+    instance: Instance,
+    exports: Vec<String>,
+    /// Fuel each call starts with, if calls are metered
+    fuel_limit: Option<u64>,
+}
+
+impl ComponentPlugin {
+    /// Compile and instantiate a component
+    pub fn load(
+        plugin_name: &str,
+        wasm_path: &Path,
+        logs: Arc<PluginLogStore>,
+        fuel_limit: Option<u64>,
+        memory_max_pages: Option<u32>,
+    ) -> Result<Self> {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+        // Each component has its own engine, so bumping the epoch interrupts
+        // only this plugin
+        config.epoch_interruption(true);
+        config.consume_fuel(fuel_limit.is_some());
+        let engine = Engine::new(&config)?;
+
+        let component = Component::from_file(&engine, wasm_path)
+            .with_context(|| format!("Failed to compile component {:?}", wasm_path))?;
+        let exports = component
+            .component_type()
+            .exports(&engine)
+            .filter(|(_, item)| matches!(item, ComponentItem::ComponentFunc(_)))
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        let mut linker = Linker::new(&engine);
+        Self::link_host(&mut linker)?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                plugin_name: plugin_name.to_string(),
+                logs,
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(memory_max_pages.map_or(usize::MAX, |pages| pages as usize * 65536))
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_epoch_deadline(1);
+        if let Some(fuel) = fuel_limit {
+            store.set_fuel(fuel)?;
+        }
+        let instance = linker
+            .instantiate(&mut store, &component)
+            .with_context(|| format!("Failed to instantiate component '{}'", plugin_name))?;
+
+        Ok(Self {
+            engine,
+            store,
+            instance,
+            exports,
+            fuel_limit,
+        })
+    }
+
+    fn link_host(linker: &mut Linker<HostState>) -> Result<()> {
+        let mut host = linker.instance(HOST_INTERFACE)?;
+
+        host.func_wrap(
+            "log",
+            |store: StoreContextMut<HostState>, (level, message): (String, String)| {
+                let state = store.data();
+                tracing::info!(plugin = %state.plugin_name, %level, "{}", message);
+                state.logs.push(PluginLogEntry {
+                    plugin: state.plugin_name.clone(),
+                    level: level.to_ascii_lowercase(),
+                    message,
+                    fields: Value::Null,
+                    execution_id: None,
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                });
+                Ok(())
+            },
+        )?;
+
+        host.func_wrap("get-timestamp", |_store: StoreContextMut<HostState>, (): ()| {
+            Ok((chrono::Utc::now().timestamp(),))
+        })?;
+
+        Ok(())
+    }
+
+    /// Call an exported function
+    pub fn call(&mut self, function: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let func = self
+            .instance
+            .get_func(&mut self.store, function)
+            .ok_or_else(|| anyhow!("Component does not export function '{}'", function))?;
+
+        let params = func.params(&self.store);
+        let args = Self::arguments(&params, input)?;
+        let mut results = vec![Val::Bool(false); func.results(&self.store).len()];
+
+        self.store.set_epoch_deadline(1);
+        if let Some(fuel) = self.fuel_limit {
+            self.store.set_fuel(fuel)?;
+        }
+        if let Err(e) = func.call(&mut self.store, &args, &mut results) {
+            if self.store.get_fuel().is_ok_and(|fuel| fuel == 0) {
+                return Err(e.context("plugin ran out of fuel"));
+            }
+            return Err(e.context(format!("Failed to call component function: {}", function)));
+        }
+        func.post_return(&mut self.store)?;
+
+        // A single `result` is unwrapped: `ok` is the output, `err` fails the call
+        let output = match results.as_slice() {
+            [] => None,
+            [Val::Result(Ok(ok))] => ok.as_deref(),
+            [Val::Result(Err(err))] => {
+                let err = err.as_deref().map(to_json).transpose()?.unwrap_or(Value::Null);
+                return Err(anyhow!(err.to_string()))
+                    .with_context(|| format!("Component function '{}' returned an error", function));
+            }
+            [single] => Some(single),
+            many => {
+                let values = many.iter().map(to_json).collect::<Result<Vec<_>>>()?;
+                return Ok(serde_json::to_vec(&values)?);
+            }
+        };
+
+        match output {
+            None => Ok(Vec::new()),
+            Some(Val::String(s)) => Ok(s.clone().into_bytes()),
+            Some(val) => Ok(serde_json::to_vec(&to_json(val)?)?),
+        }
+    }
+
+    /// Fuel consumed by the last call, if calls are metered
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        let remaining = self.store.get_fuel().ok()?;
+        self.fuel_limit.map(|limit| limit.saturating_sub(remaining))
+    }
+
+    /// Convert call input to the function's parameters
+    ///
+    /// The input is a JSON object keyed by parameter name, a JSON array of
+    /// positional arguments, or the value of the only parameter.
+    fn arguments(params: &[(String, Type)], input: &[u8]) -> Result<Vec<Val>> {
+        if let [(_, Type::String)] = params {
+            let input = std::str::from_utf8(input).context("Input is not valid UTF-8")?;
+            return Ok(vec![Val::String(input.to_string())]);
+        }
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let value: Value = serde_json::from_slice(input).context("Input is not valid JSON")?;
+        match (&value, params) {
+            (Value::Object(map), _) if params.iter().all(|(name, _)| map.contains_key(name)) => params
+                .iter()
+                .map(|(name, ty)| from_json(ty, &map[name]).with_context(|| format!("Parameter '{}'", name)))
+                .collect(),
+            (Value::Array(items), _) if params.len() > 1 && items.len() == params.len() => params
+                .iter()
+                .zip(items)
+                .map(|((name, ty), item)| from_json(ty, item).with_context(|| format!("Parameter '{}'", name)))
+                .collect(),
+            (_, [(name, ty)]) => Ok(vec![from_json(ty, &value).with_context(|| format!("Parameter '{}'", name))?]),
+            _ => bail!(
+                "Expected an object with fields {:?}",
+                params.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>()
+            ),
+        }
+    }
+
+    /// Engine whose epoch interrupts calls on this component
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn has_function(&self, function: &str) -> bool {
+        self.exports.iter().any(|name| name == function)
+    }
+}
+
+/// Convert a JSON value to a component value of the given type
+///
+/// Variants are `{"<case>": payload}` (or the bare case name), results are
+/// `{"ok": ..}` / `{"err": ..}` and flags are arrays of names.
+fn from_json(ty: &Type, value: &Value) -> Result<Val> {
+    let int = || value.as_i64().ok_or_else(|| anyhow!("Expected an integer, got {}", value));
+    let uint = || value.as_u64().ok_or_else(|| anyhow!("Expected an unsigned integer, got {}", value));
+    let float = || value.as_f64().ok_or_else(|| anyhow!("Expected a number, got {}", value));
+    let string = || value.as_str().ok_or_else(|| anyhow!("Expected a string, got {}", value));
+    let array = || value.as_array().ok_or_else(|| anyhow!("Expected an array, got {}", value));
+
+    Ok(match ty {
+        Type::Bool => Val::Bool(value.as_bool().ok_or_else(|| anyhow!("Expected a boolean, got {}", value))?),
+        Type::S8 => Val::S8(int()?.try_into()?),
+        Type::U8 => Val::U8(uint()?.try_into()?),
+        Type::S16 => Val::S16(int()?.try_into()?),
+        Type::U16 => Val::U16(uint()?.try_into()?),
+        Type::S32 => Val::S32(int()?.try_into()?),
+        Type::U32 => Val::U32(uint()?.try_into()?),
+        Type::S64 => Val::S64(int()?),
+        Type::U64 => Val::U64(uint()?),
+        Type::Float32 => Val::Float32(float()? as f32),
+        Type::Float64 => Val::Float64(float()?),
+        Type::Char => {
+            let mut chars = string()?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Val::Char(c),
+                _ => bail!("Expected a single character, got {}", value),
+            }
+        }
+        Type::String => Val::String(string()?.to_string()),
+        Type::List(list) => {
+            let ty = list.ty();
+            Val::List(array()?.iter().map(|item| from_json(&ty, item)).collect::<Result<_>>()?)
+        }
+        Type::Record(record) => {
+            let map = value.as_object().ok_or_else(|| anyhow!("Expected an object, got {}", value))?;
+            Val::Record(
+                record
+                    .fields()
+                    .map(|field| {
+                        let item = map.get(field.name).unwrap_or(&Value::Null);
+                        Ok((field.name.to_string(), from_json(&field.ty, item).with_context(|| format!("Field '{}'", field.name))?))
+                    })
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Type::Tuple(tuple) => {
+            let items = array()?;
+            if items.len() != tuple.types().len() {
+                bail!("Expected a tuple of {} items, got {}", tuple.types().len(), items.len());
+            }
+            Val::Tuple(tuple.types().zip(items).map(|(ty, item)| from_json(&ty, item)).collect::<Result<_>>()?)
+        }
+        Type::Variant(variant) => {
+            let (name, payload) = match value {
+                Value::String(name) => (name.as_str(), None),
+                Value::Object(map) if map.len() == 1 => {
+                    let (name, payload) = map.iter().next().unwrap();
+                    (name.as_str(), Some(payload))
+                }
+                _ => bail!("Expected a variant case, got {}", value),
+            };
+            let case = variant
+                .cases()
+                .find(|case| case.name == name)
+                .ok_or_else(|| anyhow!("Unknown variant case '{}'", name))?;
+            let payload = match (case.ty, payload) {
+                (Some(ty), Some(payload)) => Some(Box::new(from_json(&ty, payload)?)),
+                (Some(_), None) => bail!("Variant case '{}' requires a payload", name),
+                (None, _) => None,
+            };
+            Val::Variant(name.to_string(), payload)
+        }
+        Type::Enum(enum_ty) => {
+            let name = string()?;
+            if !enum_ty.names().any(|n| n == name) {
+                bail!("Unknown enum case '{}'", name);
+            }
+            Val::Enum(name.to_string())
+        }
+        Type::Option(option) => match value {
+            Value::Null => Val::Option(None),
+            value => Val::Option(Some(Box::new(from_json(&option.ty(), value)?))),
+        },
+        Type::Result(result) => {
+            let map = value.as_object().filter(|map| map.len() == 1);
+            let payload = |ty: Option<Type>, item: &Value| -> Result<Option<Box<Val>>> {
+                ty.map(|ty| from_json(&ty, item).map(Box::new)).transpose()
+            };
+            match map.and_then(|map| map.iter().next()) {
+                Some((key, item)) if key == "ok" => Val::Result(Ok(payload(result.ok(), item)?)),
+                Some((key, item)) if key == "err" => Val::Result(Err(payload(result.err(), item)?)),
+                _ => bail!("Expected {{\"ok\": ..}} or {{\"err\": ..}}, got {}", value),
+            }
+        }
+        Type::Flags(flags) => {
+            let names = array()?
+                .iter()
+                .map(|item| item.as_str().map(str::to_string).ok_or_else(|| anyhow!("Expected a flag name, got {}", item)))
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(unknown) = names.iter().find(|name| !flags.names().any(|n| n == name.as_str())) {
+                bail!("Unknown flag '{}'", unknown);
+            }
+            Val::Flags(names)
+        }
+        other => bail!("Unsupported parameter type: {:?}", other),
+    })
+}
+
+/// Convert a component value to JSON, using the encoding of [`from_json`]
+fn to_json(val: &Val) -> Result<Value> {
+    let boxed = |val: &Option<Box<Val>>| -> Result<Value> {
+        val.as_deref().map(to_json).transpose().map(|v| v.unwrap_or(Value::Null))
+    };
+
+    Ok(match val {
+        Val::Bool(b) => Value::Bool(*b),
+        Val::S8(n) => Value::from(*n),
+        Val::U8(n) => Value::from(*n),
+        Val::S16(n) => Value::from(*n),
+        Val::U16(n) => Value::from(*n),
+        Val::S32(n) => Value::from(*n),
+        Val::U32(n) => Value::from(*n),
+        Val::S64(n) => Value::from(*n),
+        Val::U64(n) => Value::from(*n),
+        Val::Float32(n) => Number::from_f64(f64::from(*n)).map_or(Value::Null, Value::Number),
+        Val::Float64(n) => Number::from_f64(*n).map_or(Value::Null, Value::Number),
+        Val::Char(c) => Value::String(c.to_string()),
+        Val::String(s) => Value::String(s.clone()),
+        Val::List(items) | Val::Tuple(items) => Value::Array(items.iter().map(to_json).collect::<Result<_>>()?),
+        Val::Record(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, val)| Ok((name.clone(), to_json(val)?)))
+                .collect::<Result<Map<_, _>>>()?,
+        ),
+        Val::Variant(name, None) | Val::Enum(name) => Value::String(name.clone()),
+        Val::Variant(name, payload) => {
+            let mut map = Map::new();
+            map.insert(name.clone(), boxed(payload)?);
+            Value::Object(map)
+        }
+        Val::Option(None) => Value::Null,
+        Val::Option(Some(val)) => to_json(val)?,
+        Val::Result(result) => {
+            let mut map = Map::new();
+            match result {
+                Ok(val) => map.insert("ok".to_string(), boxed(val)?),
+                Err(val) => map.insert("err".to_string(), boxed(val)?),
+            };
+            Value::Object(map)
+        }
+        Val::Flags(names) => Value::Array(names.iter().cloned().map(Value::String).collect()),
+        other => bail!("Unsupported result type: {:?}", other),
+    })
+}
@@ -0,0 +1,212 @@
+//! Per-call execution context shared with host functions
+//!
+//! An `ExecutionContext` is attached to every plugin call through Extism's host
+//! context, so host functions can reach state that belongs to the current call
+//! rather than to the plugin as a whole. Extism takes ownership of the context
+//! for the duration of the call, so the context is a cheap clone around shared
+//! state that the caller can inspect afterwards.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::trace::TraceRecorder;
+use crate::db::schema::HostCallTrace;
+use crate::host_functions::blob::OpenBlob;
+use crate::ids::{self, IdKind};
+
+/// Callback receiving streamed output chunks as `(sequence, bytes)`
+pub type ChunkSink = Arc<dyn Fn(u64, &[u8]) + Send + Sync>;
+
+#[derive(Default)]
+struct OutputState {
+    buffered: Vec<u8>,
+    chunks_sent: u64,
+}
+
+/// The signed-in user a call is made on behalf of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CurrentUser {
+    pub user_uuid: String,
+    /// Set for users signed in with a session rather than an API key
+    pub session_id: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ExecutionContext {
+    /// Unique ID of this execution
+    pub execution_id: String,
+    /// Where streamed chunks go; None means chunks are buffered into the output
+    stream: Option<ChunkSink>,
+    output: Arc<Mutex<OutputState>>,
+    /// Plugins above this call in a chain of `call_plugin` invocations
+    pub call_stack: Vec<String>,
+    /// User the call is made for; None for calls the host makes on its own,
+    /// such as scheduled runs and event handlers
+    pub user: Option<CurrentUser>,
+    /// Set once the call is cancelled, so host functions that wait can stop
+    /// early; shared with nested calls
+    cancelled: Arc<(Mutex<bool>, Condvar)>,
+    /// Files opened with `blob_open`, by blob ID; each call has its own
+    pub(crate) blobs: Arc<Mutex<HashMap<String, OpenBlob>>>,
+    /// Plugin the context was handed to; set by the loader for each call
+    pub(crate) plugin: String,
+    /// Host function calls made so far; shared with nested calls
+    trace: Arc<Mutex<TraceRecorder>>,
+    /// When `report_progress` last passed a report on; shared with nested calls
+    progress_reported: Arc<Mutex<Option<Instant>>>,
+}
+
+impl ExecutionContext {
+    pub fn new() -> Self {
+        Self::with_id(ids::new_id(IdKind::Execution))
+    }
+
+    pub fn with_id(execution_id: String) -> Self {
+        Self {
+            execution_id,
+            stream: None,
+            output: Arc::new(Mutex::new(OutputState::default())),
+            call_stack: Vec::new(),
+            user: None,
+            cancelled: Arc::new((Mutex::new(false), Condvar::new())),
+            blobs: Arc::new(Mutex::new(HashMap::new())),
+            plugin: String::new(),
+            trace: Arc::new(Mutex::new(TraceRecorder::new())),
+            progress_reported: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Context for a nested call made by `caller` through `call_plugin`
+    ///
+    /// The nested call shares the execution ID and user but buffers its own
+    /// output, which is returned to the calling plugin.
+    pub fn nested(&self, caller: &str) -> Self {
+        let mut call_stack = self.call_stack.clone();
+        call_stack.push(caller.to_string());
+        Self {
+            call_stack,
+            user: self.user.clone(),
+            cancelled: self.cancelled.clone(),
+            trace: self.trace.clone(),
+            progress_reported: self.progress_reported.clone(),
+            ..Self::with_id(self.execution_id.clone())
+        }
+    }
+
+    /// Forward chunks emitted through `stream_chunk` to a sink instead of buffering them
+    pub fn with_stream(mut self, sink: ChunkSink) -> Self {
+        self.stream = Some(sink);
+        self
+    }
+
+    /// Make the call on behalf of `user`
+    pub fn with_user(mut self, user: Option<CurrentUser>) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Handle a chunk emitted by the plugin
+    pub fn push_chunk(&self, chunk: &[u8]) {
+        let mut output = self.output.lock().unwrap();
+        match &self.stream {
+            Some(sink) => {
+                sink(output.chunks_sent, chunk);
+                output.chunks_sent += 1;
+            }
+            None => output.buffered.extend_from_slice(chunk),
+        }
+    }
+
+    /// Number of chunks forwarded to the stream sink
+    pub fn chunks_sent(&self) -> u64 {
+        self.output.lock().unwrap().chunks_sent
+    }
+
+    /// Take chunks that were buffered because no stream sink was attached
+    pub fn take_buffered(&self) -> Vec<u8> {
+        std::mem::take(&mut self.output.lock().unwrap().buffered)
+    }
+
+    /// Close the blobs the call left open; returns how many there were
+    pub fn close_blobs(&self) -> usize {
+        let mut blobs = self.blobs.lock().unwrap();
+        let open = blobs.len();
+        blobs.clear();
+        open
+    }
+
+    /// Record a host function call that started at `started`
+    pub(crate) fn record_host_call(
+        &self,
+        function: &str,
+        started: Instant,
+        input_bytes: u64,
+        output_bytes: u64,
+        error: Option<String>,
+    ) {
+        let mut trace = self.trace.lock().unwrap();
+        let call = HostCallTrace {
+            plugin: self.plugin.clone(),
+            function: function.to_string(),
+            started_us: trace.offset_us(started),
+            duration_us: started.elapsed().as_micros() as u64,
+            input_bytes,
+            output_bytes,
+            error,
+        };
+        trace.record(call);
+    }
+
+    /// Take the host function calls recorded so far and the number dropped
+    /// for going over [`MAX_TRACED_CALLS`](super::trace::MAX_TRACED_CALLS)
+    pub(crate) fn take_trace(&self) -> (Vec<HostCallTrace>, u64) {
+        self.trace.lock().unwrap().take()
+    }
+
+    /// Whether a progress report should be passed on: the final one, or the
+    /// first in `interval`
+    pub(crate) fn progress_due(&self, interval: Duration, last: bool) -> bool {
+        let mut reported = self.progress_reported.lock().unwrap();
+        let due = last || reported.is_none_or(|at| at.elapsed() >= interval);
+        if due {
+            *reported = Some(Instant::now());
+        }
+        due
+    }
+
+    /// Mark the call cancelled and wake host functions waiting in it
+    pub fn cancel(&self) {
+        let (cancelled, wake) = &*self.cancelled;
+        *cancelled.lock().unwrap() = true;
+        wake.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.0.lock().unwrap()
+    }
+
+    /// Wait for `duration` unless the call is cancelled first; returns false
+    /// if it was
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let (cancelled, wake) = &*self.cancelled;
+        let mut cancelled = cancelled.lock().unwrap();
+        while !*cancelled {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            cancelled = wake.wait_timeout(cancelled, deadline - now).unwrap().0;
+        }
+        false
+    }
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
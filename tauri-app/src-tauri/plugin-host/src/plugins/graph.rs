@@ -0,0 +1,159 @@
+//! Dependency graph of the installed plugins
+//!
+//! Nodes are installed plugins plus any dependency that is not installed;
+//! edges point from a plugin to each plugin it depends on. A plugin is
+//! available when every plugin it depends on, directly or transitively, is
+//! installed at a version in the range it asks for.
+
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::PluginManifest;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyNode {
+    /// Plugin ID, or the dependency name as declared for plugins not installed
+    pub id: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub installed: bool,
+    /// Whether all of its dependencies are satisfied, transitively
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    /// ID of the dependent plugin
+    pub from: String,
+    /// ID of the dependency
+    pub to: String,
+    /// Semver range the dependent declared
+    pub required_version: String,
+    /// Whether the dependency is installed at a version in that range
+    pub satisfied: bool,
+    /// Why the edge is not satisfied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+/// Build the graph of `installed` plugins
+pub fn build(installed: &[PluginManifest]) -> DependencyGraph {
+    let mut nodes: Vec<DependencyNode> = installed
+        .iter()
+        .map(|manifest| DependencyNode {
+            id: manifest.id(),
+            name: manifest.name.clone(),
+            version: Some(manifest.version.clone()),
+            installed: true,
+            available: true,
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    let mut missing = HashSet::new();
+    for manifest in installed {
+        for (dep, required_version) in &manifest.dependencies {
+            let target = resolve(installed, dep);
+            if target.is_none() && missing.insert(dep.clone()) {
+                nodes.push(DependencyNode {
+                    id: dep.clone(),
+                    name: dep.clone(),
+                    version: None,
+                    installed: false,
+                    available: false,
+                });
+            }
+            let issue = match target {
+                Some(target) => version_issue(manifest, dep, required_version, &target.version),
+                None => Some(format!("Dependency '{}' is not installed", dep)),
+            };
+            edges.push(DependencyEdge {
+                from: manifest.id(),
+                satisfied: issue.is_none(),
+                to: target.map_or_else(|| dep.clone(), |target| target.id()),
+                required_version: required_version.clone(),
+                issue,
+            });
+        }
+    }
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    edges.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+
+    // Unavailability spreads from unsatisfied edges to everything depending on them
+    let mut unavailable: HashSet<String> = edges
+        .iter()
+        .filter(|edge| !edge.satisfied)
+        .map(|edge| edge.from.clone())
+        .collect();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in &edges {
+        dependents.entry(edge.to.as_str()).or_default().push(edge.from.as_str());
+    }
+    let mut queue: Vec<String> = unavailable.iter().cloned().collect();
+    while let Some(id) = queue.pop() {
+        for dependent in dependents.get(id.as_str()).into_iter().flatten() {
+            if unavailable.insert(dependent.to_string()) {
+                queue.push(dependent.to_string());
+            }
+        }
+    }
+    for node in &mut nodes {
+        node.available &= !unavailable.contains(&node.id);
+    }
+
+    DependencyGraph { nodes, edges }
+}
+
+/// Dependencies of `manifest` installed at versions outside the range it
+/// declares, one message per dependency; dependencies that are not installed
+/// are left to the plugin calls that need them
+pub fn version_issues(manifest: &PluginManifest, installed: &[PluginManifest]) -> Vec<String> {
+    let id = manifest.id();
+    let others: Vec<&PluginManifest> = installed.iter().filter(|p| p.id() != id).collect();
+    let mut dependencies: Vec<_> = manifest.dependencies.iter().collect();
+    dependencies.sort();
+    dependencies
+        .into_iter()
+        .filter_map(|(dep, required_version)| {
+            let target = others
+                .iter()
+                .find(|p| &p.id() == dep)
+                .or_else(|| others.iter().find(|p| &p.name == dep))?;
+            version_issue(manifest, dep, required_version, &target.version)
+        })
+        .collect()
+}
+
+/// The installed plugin a dependency refers to; dependencies are declared by ID or by plain name
+fn resolve<'a>(installed: &'a [PluginManifest], dep: &str) -> Option<&'a PluginManifest> {
+    installed
+        .iter()
+        .find(|p| p.id() == dep)
+        .or_else(|| installed.iter().find(|p| p.name == dep))
+}
+
+/// Why `version` of a dependency does not satisfy the range `manifest` declares for it
+fn version_issue(manifest: &PluginManifest, dep: &str, required_version: &str, version: &str) -> Option<String> {
+    let required = match VersionReq::parse(required_version) {
+        Ok(required) => required,
+        Err(e) => return Some(format!("'{}' is not a semver range for dependency '{}': {}", required_version, dep, e)),
+    };
+    match Version::parse(version) {
+        Ok(version) if required.matches(&version) => None,
+        Ok(version) => Some(format!(
+            "Dependency '{}' is installed at {}, but '{}' requires {}",
+            dep,
+            version,
+            manifest.id(),
+            required
+        )),
+        Err(e) => Some(format!("Dependency '{}' has an invalid version '{}': {}", dep, version, e)),
+    }
+}
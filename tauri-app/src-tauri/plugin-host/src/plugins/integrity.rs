@@ -0,0 +1,82 @@
+//! Checksums of installed plugins
+//!
+//! A SHA-256 covering each plugin's modules, migrations and UI assets is
+//! recorded when the installer writes them, and nowhere else. Loading a
+//! plugin whose files no longer match, or that has no recorded checksum, is
+//! refused; the periodic verifier unloads and disables plugins whose files
+//! changed while loaded.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use super::PluginManifest;
+
+/// A plugin whose files don't match its recorded checksum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityViolation {
+    pub plugin: String,
+    pub expected_sha256: String,
+    /// None if its files could not be read
+    pub actual_sha256: Option<String>,
+}
+
+/// Checksum covering a plugin's modules and every other file its manifest
+/// refers to
+///
+/// A plugin that refers to no other files hashes as [`sha256_modules`], so
+/// checksums recorded before those files were covered stay valid.
+pub fn sha256_plugin(manifest: &PluginManifest, plugin_dir: &Path) -> Result<String> {
+    let modules = sha256_modules(&manifest.wasm_paths(plugin_dir))?;
+    let files = manifest.referenced_files(plugin_dir)?;
+    if files.is_empty() {
+        return Ok(modules);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(modules.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(sha256_files(plugin_dir, &files)?.as_bytes());
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Checksum covering all of a plugin's modules
+///
+/// A single module hashes to the plain SHA-256 of its file, so checksums
+/// recorded before multi-module plugins existed stay valid.
+pub fn sha256_modules(paths: &[PathBuf]) -> Result<String> {
+    let modules = paths
+        .iter()
+        .map(|path| std::fs::read(path).with_context(|| format!("Failed to read {:?}", path)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(sha256_bytes(&modules.iter().map(Vec::as_slice).collect::<Vec<_>>()))
+}
+
+/// SHA-256 of each of a plugin's other files, as `<path> <sha256>` lines
+///
+/// `files` are relative to `plugin_dir`, as [`PluginManifest::referenced_files`]
+/// lists them.
+///
+/// [`PluginManifest::referenced_files`]: super::PluginManifest::referenced_files
+pub fn sha256_files(plugin_dir: &Path, files: &[String]) -> Result<String> {
+    let mut lines = String::new();
+    for file in files {
+        let path = plugin_dir.join(file);
+        let bytes = std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        lines.push_str(&format!("{} {}\n", file, hex::encode(Sha256::digest(bytes))));
+    }
+    Ok(lines)
+}
+
+/// [`sha256_modules`] of modules already in memory
+pub fn sha256_bytes(modules: &[&[u8]]) -> String {
+    if let [module] = modules {
+        return hex::encode(Sha256::digest(module));
+    }
+    let mut hasher = Sha256::new();
+    for module in modules {
+        hasher.update(hex::encode(Sha256::digest(module)).as_bytes());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
@@ -0,0 +1,180 @@
+//! Licenses of installed plugins
+//!
+//! A manifest's `license` is an SPDX license expression such as `MIT` or
+//! `Apache-2.0 OR MIT`. The expression's syntax is checked when the manifest
+//! is loaded; identifiers are not matched against the SPDX list, so new and
+//! `LicenseRef-` licenses are accepted. The [`LicenseReport`] lists what app
+//! distributors need to comply with.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// License of one installed plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginLicense {
+    pub plugin: String,
+    pub version: String,
+    pub author: Option<String>,
+    /// SPDX license expression, if the plugin declares one
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+}
+
+/// Licenses of every installed plugin, loaded or not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseReport {
+    pub plugins: Vec<PluginLicense>,
+    /// IDs of the plugins using each license identifier
+    pub licenses: BTreeMap<String, Vec<String>>,
+    /// IDs of the plugins that declare no license
+    pub unlicensed: Vec<String>,
+    pub generated_at: i64,
+}
+
+impl LicenseReport {
+    pub fn new(mut plugins: Vec<PluginLicense>) -> Self {
+        plugins.sort_by(|a, b| a.plugin.cmp(&b.plugin));
+        let mut licenses: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut unlicensed = Vec::new();
+        for plugin in &plugins {
+            let Some(expression) = &plugin.license else {
+                unlicensed.push(plugin.plugin.clone());
+                continue;
+            };
+            // Manifests are validated on load, but a hand-edited one may not parse
+            let identifiers = identifiers(expression).unwrap_or_else(|_| vec![expression.clone()]);
+            for identifier in identifiers {
+                let users = licenses.entry(identifier).or_default();
+                if !users.contains(&plugin.plugin) {
+                    users.push(plugin.plugin.clone());
+                }
+            }
+        }
+        Self {
+            plugins,
+            licenses,
+            unlicensed,
+            generated_at: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Check an SPDX license expression and return the license and exception
+/// identifiers it names, in order
+pub fn identifiers(expression: &str) -> Result<Vec<String>> {
+    let mut parser = Parser {
+        tokens: tokenize(expression),
+        pos: 0,
+        identifiers: Vec::new(),
+    };
+    if parser.tokens.is_empty() {
+        anyhow::bail!("License expression is empty");
+    }
+    parser.or_expression()?;
+    if let Some(token) = parser.peek() {
+        anyhow::bail!("Unexpected '{}' in license expression '{}'", token, expression);
+    }
+    Ok(parser.identifiers)
+}
+
+fn tokenize(expression: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in expression.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(s) = start.take() {
+                tokens.push(&expression[s..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&expression[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&expression[s..]);
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    identifiers: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn or_expression(&mut self) -> Result<()> {
+        self.and_expression()?;
+        while self.peek() == Some("OR") {
+            self.pos += 1;
+            self.and_expression()?;
+        }
+        Ok(())
+    }
+
+    fn and_expression(&mut self) -> Result<()> {
+        self.with_expression()?;
+        while self.peek() == Some("AND") {
+            self.pos += 1;
+            self.with_expression()?;
+        }
+        Ok(())
+    }
+
+    fn with_expression(&mut self) -> Result<()> {
+        if self.peek() == Some("(") {
+            self.pos += 1;
+            self.or_expression()?;
+            return match self.next() {
+                Some(")") => Ok(()),
+                _ => anyhow::bail!("Unclosed '(' in license expression"),
+            };
+        }
+        self.license(true)?;
+        if self.peek() == Some("WITH") {
+            self.pos += 1;
+            self.license(false)?;
+        }
+        Ok(())
+    }
+
+    /// A license identifier, `+` suffix and `LicenseRef-` forms included, or
+    /// with `license` false an exception identifier
+    fn license(&mut self, license: bool) -> Result<()> {
+        let what = if license { "license" } else { "exception" };
+        let token = match self.next() {
+            Some(token) if !matches!(token, "(" | ")" | "AND" | "OR" | "WITH") => token,
+            Some(token) => anyhow::bail!("Expected a {} identifier, found '{}'", what, token),
+            None => anyhow::bail!("License expression ends where a {} identifier is expected", what),
+        };
+        let id = if license { token.strip_suffix('+').unwrap_or(token) } else { token };
+        let id = match id.split_once(':') {
+            Some((document, id)) if license && is_idstring(document) && document.starts_with("DocumentRef-") => id,
+            Some(_) => anyhow::bail!("'{}' is not a valid {} identifier", token, what),
+            None => id,
+        };
+        if !is_idstring(id) {
+            anyhow::bail!("'{}' is not a valid {} identifier", token, what);
+        }
+        self.identifiers.push(token.to_string());
+        Ok(())
+    }
+}
+
+/// Letters, digits, `.` and `-`
+fn is_idstring(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'))
+}
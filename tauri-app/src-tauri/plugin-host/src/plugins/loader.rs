@@ -0,0 +1,398 @@
+//! Plugin loader using Extism runtime, or wasmtime for Component Model plugins
+
+use super::compatibility;
+use super::component::ComponentPlugin;
+use super::context::ExecutionContext;
+use super::logs::PluginLogStore;
+use super::manifest::{PluginManifest, WasmModules};
+use crate::error::AppError;
+use anyhow::{Context, Result};
+use extism::{Function, Manifest, Plugin, PluginBuilder, Wasm};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+use wasmparser::{Parser, Payload};
+
+enum Runtime {
+    Extism(Box<Plugin>),
+    Component(ComponentPlugin),
+}
+
+/// Interrupts the call currently running on a plugin
+pub enum CancelHandle {
+    Extism(extism::CancelHandle),
+    Component(wasmtime::Engine),
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) -> Result<()> {
+        match self {
+            CancelHandle::Extism(handle) => handle.cancel(),
+            CancelHandle::Component(engine) => {
+                engine.increment_epoch();
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct PluginLoader {
+    manifest: PluginManifest,
+    runtime: Runtime,
+}
+
+impl PluginLoader {
+    /// Whether the module at `wasm_path` is a Component Model binary
+    pub fn is_component(wasm_path: &Path) -> Result<bool> {
+        use std::io::Read;
+        
+        let mut header = [0u8; 8];
+        let mut file = std::fs::File::open(wasm_path)
+            .with_context(|| format!("WASM module not found: {:?}", wasm_path))?;
+        let read = file.read(&mut header)?;
+        Ok(wasmparser::Parser::is_component(&header[..read]))
+    }
+    
+    /// Names of the functions a WASM module or component exports
+    pub fn wasm_exports(wasm_bytes: &[u8]) -> Vec<String> {
+        let mut exports = Vec::new();
+        
+        if Parser::is_component(wasm_bytes) {
+            for payload in Parser::new(0).parse_all(wasm_bytes) {
+                if let Ok(Payload::ComponentExportSection(reader)) = payload {
+                    for export in reader.into_iter().flatten() {
+                        if matches!(export.kind, wasmparser::ComponentExternalKind::Func) {
+                            exports.push(export.name.0.to_string());
+                        }
+                    }
+                }
+            }
+            return exports;
+        }
+        
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            if let Ok(Payload::ExportSection(reader)) = payload {
+                for export in reader.into_iter().flatten() {
+                    if matches!(export.kind, wasmparser::ExternalKind::Func) {
+                        exports.push(export.name.to_string());
+                    }
+                }
+            }
+        }
+        
+        exports
+    }
+    
+    /// Host functions a core WASM module imports, as (module, name) pairs;
+    /// empty for components
+    pub fn wasm_imports(wasm_bytes: &[u8]) -> Vec<(String, String)> {
+        let mut imports = Vec::new();
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            if let Ok(Payload::ImportSection(reader)) = payload {
+                for import in reader.into_iter().flatten() {
+                    if matches!(import.ty, wasmparser::TypeRef::Func(_)) {
+                        imports.push((import.module.to_string(), import.name.to_string()));
+                    }
+                }
+            }
+        }
+        imports
+    }
+    
+    /// Check that the main module exports the function of every entry point
+    ///
+    /// Extism only calls the main module, so a missing export would otherwise
+    /// only surface as an opaque error at call time.
+    fn check_entry_points(plugin_manifest: &PluginManifest, plugin_dir: &Path) -> Result<()> {
+        let wasm_path = plugin_manifest.wasm_path(plugin_dir);
+        let bytes = std::fs::read(&wasm_path)
+            .with_context(|| format!("Failed to read WASM module {:?}", wasm_path))?;
+        let exports = Self::wasm_exports(&bytes);
+        
+        let missing: Vec<String> = plugin_manifest
+            .entry_points
+            .iter()
+            .filter(|entry_point| !exports.contains(&entry_point.function))
+            .map(|entry_point| format!("{} -> {}", entry_point.name, entry_point.function))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Plugin '{}': entry points reference functions the WASM module doesn't export: {} (exports: {})",
+                plugin_manifest.name,
+                missing.join(", "),
+                exports.join(", ")
+            );
+        }
+        Ok(())
+    }
+    
+    /// Check that every host function the plugin requires or imports is
+    /// among `host_fns`
+    ///
+    /// Extism would otherwise fail to link the module with a message that
+    /// doesn't say which function is missing.
+    fn check_host_functions(plugin_manifest: &PluginManifest, plugin_dir: &Path, host_fns: &[Function]) -> Result<()> {
+        let mut needed = plugin_manifest.required_host_functions.clone();
+        for wasm_path in plugin_manifest.wasm_paths(plugin_dir) {
+            let bytes = std::fs::read(&wasm_path)
+                .with_context(|| format!("Failed to read WASM module {:?}", wasm_path))?;
+            needed.extend(compatibility::host_imports(&bytes));
+        }
+        
+        let mut missing: Vec<String> = needed
+            .into_iter()
+            .filter(|name| !host_fns.iter().any(|function| function.name() == name))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        match missing.as_slice() {
+            [] => Ok(()),
+            [name] => anyhow::bail!("Plugin '{}': missing host function {}", plugin_manifest.name, name),
+            names => anyhow::bail!("Plugin '{}': missing host functions {}", plugin_manifest.name, names.join(", ")),
+        }
+    }
+    
+    /// Extism sources for every module of a plugin, named for linking
+    fn wasm_sources(plugin_manifest: &PluginManifest, plugin_dir: &Path) -> Result<Vec<Wasm>> {
+        match &plugin_manifest.wasm_module {
+            WasmModules::Single(path) => Ok(vec![Wasm::file(plugin_dir.join(path))]),
+            WasmModules::Multiple(modules) => modules
+                .iter()
+                .map(|module| {
+                    let path = plugin_dir.join(&module.path);
+                    if !path.exists() {
+                        anyhow::bail!("WASM module not found: {:?}", path);
+                    }
+                    let wasm = Wasm::file(path);
+                    Ok(match &module.name {
+                        Some(name) => wasm.with_name(name),
+                        None => wasm,
+                    })
+                })
+                .collect(),
+        }
+    }
+    
+    /// Load a Component Model plugin
+    pub fn load_component(
+        plugin_manifest: PluginManifest,
+        plugin_dir: &Path,
+        logs: Arc<PluginLogStore>,
+    ) -> Result<Self> {
+        info!("Loading component plugin: {}", plugin_manifest.name);
+        
+        plugin_manifest.validate()?;
+        if plugin_manifest.wasm_config.wasi {
+            anyhow::bail!("Plugin '{}': WASI is not available to component plugins", plugin_manifest.name);
+        }
+        if matches!(plugin_manifest.wasm_module, WasmModules::Multiple(_)) {
+            anyhow::bail!("Plugin '{}': component plugins must be a single module", plugin_manifest.name);
+        }
+        
+        let wasm_path = plugin_manifest.wasm_path(plugin_dir);
+        let component = ComponentPlugin::load(
+            &plugin_manifest.name,
+            &wasm_path,
+            logs,
+            plugin_manifest.wasm_config.fuel_limit,
+            plugin_manifest.wasm_config.memory_max_pages,
+        )?;
+        for entry_point in &plugin_manifest.entry_points {
+            if !component.has_function(&entry_point.name) {
+                anyhow::bail!(
+                    "Plugin '{}': component does not export entry point '{}'",
+                    plugin_manifest.name,
+                    entry_point.name
+                );
+            }
+        }
+        
+        info!("Successfully loaded component plugin: {}", plugin_manifest.name);
+        
+        Ok(Self {
+            manifest: plugin_manifest,
+            runtime: Runtime::Component(component),
+        })
+    }
+
+    /// Load a plugin from its manifest with host functions
+    pub fn load_with_host_functions(
+        plugin_manifest: PluginManifest,
+        plugin_dir: &Path,
+        host_fns: Vec<Function>,
+    ) -> Result<Self> {
+        info!("Loading plugin: {} with {} host functions", plugin_manifest.name, host_fns.len());
+        
+        // Validate manifest
+        plugin_manifest.validate()?;
+        
+        // Get WASM module path
+        let wasm_path = plugin_manifest.wasm_path(plugin_dir);
+        debug!("WASM module path: {:?}", wasm_path);
+        
+        if !wasm_path.exists() {
+            anyhow::bail!("WASM module not found: {:?}", wasm_path);
+        }
+        Self::check_entry_points(&plugin_manifest, plugin_dir)?;
+        
+        // Build Extism manifest
+        let mut manifest = Manifest::new(Self::wasm_sources(&plugin_manifest, plugin_dir)?);
+        
+        // Add configuration
+        for (key, value) in &plugin_manifest.wasm_config.config {
+            manifest = manifest.with_config_key(key, value);
+        }
+        
+        // Add allowed hosts
+        for host in &plugin_manifest.wasm_config.allowed_hosts {
+            manifest = manifest.with_allowed_host(host);
+        }
+        
+        // Add allowed paths
+        for (guest, host) in &plugin_manifest.wasm_config.allowed_paths {
+            manifest = manifest.with_allowed_path(guest.clone(), host);
+        }
+        
+        // Cap memory
+        if let Some(pages) = plugin_manifest.wasm_config.memory_max_pages {
+            manifest = manifest.with_memory_max(pages);
+        }
+        
+        // Create plugin with host functions
+        Self::check_host_functions(&plugin_manifest, plugin_dir, &host_fns)?;
+        let plugin = Self::build_plugin(&manifest, host_fns, &plugin_manifest)
+            .map_err(|e| anyhow::anyhow!("Failed to create Extism plugin for '{}' from {:?}: {:?}", plugin_manifest.name, wasm_path, e))?;
+        
+        info!("Successfully loaded plugin: {}", plugin_manifest.name);
+        
+        Ok(Self {
+            manifest: plugin_manifest,
+            runtime: Runtime::Extism(Box::new(plugin)),
+        })
+    }
+
+    /// Instantiate an Extism plugin, metering fuel if the manifest sets a limit
+    fn build_plugin(
+        manifest: &Manifest,
+        host_fns: impl IntoIterator<Item = Function>,
+        plugin_manifest: &PluginManifest,
+    ) -> Result<Plugin> {
+        let mut builder = PluginBuilder::new(manifest)
+            .with_wasi(plugin_manifest.wasm_config.wasi)
+            .with_functions(host_fns);
+        if let Some(fuel) = plugin_manifest.wasm_config.fuel_limit {
+            builder = builder.with_fuel_limit(fuel);
+        }
+        builder.build()
+    }
+    
+    /// Load a plugin from its manifest (without host functions)
+    pub fn load(plugin_manifest: PluginManifest, plugin_dir: &Path) -> Result<Self> {
+        info!("Loading plugin: {}", plugin_manifest.name);
+        
+        // Validate manifest
+        plugin_manifest.validate()?;
+        
+        // Get WASM module path
+        let wasm_path = plugin_manifest.wasm_path(plugin_dir);
+        debug!("WASM module path: {:?}", wasm_path);
+        
+        if !wasm_path.exists() {
+            anyhow::bail!("WASM module not found: {:?}", wasm_path);
+        }
+        Self::check_entry_points(&plugin_manifest, plugin_dir)?;
+        
+        // Build Extism manifest
+        let mut manifest = Manifest::new(Self::wasm_sources(&plugin_manifest, plugin_dir)?);
+        
+        // Add configuration
+        for (key, value) in &plugin_manifest.wasm_config.config {
+            manifest = manifest.with_config_key(key, value);
+        }
+        
+        // Add allowed hosts
+        for host in &plugin_manifest.wasm_config.allowed_hosts {
+            manifest = manifest.with_allowed_host(host);
+        }
+        
+        // Add allowed paths
+        for (guest, host) in &plugin_manifest.wasm_config.allowed_paths {
+            manifest = manifest.with_allowed_path(guest.clone(), host);
+        }
+        
+        // Cap memory
+        if let Some(pages) = plugin_manifest.wasm_config.memory_max_pages {
+            manifest = manifest.with_memory_max(pages);
+        }
+        
+        // Create plugin, with only the HTTP functions so denied_hosts still applies
+        let http = crate::host_functions::http::http_functions(&plugin_manifest.name, plugin_manifest.wasm_config.denied_hosts.clone(), None);
+        Self::check_host_functions(&plugin_manifest, plugin_dir, &http)?;
+        let plugin = Self::build_plugin(&manifest, http, &plugin_manifest)
+            .context("Failed to create Extism plugin")?;
+        
+        info!("✅ Plugin loaded: {}", plugin_manifest.name);
+        
+        Ok(PluginLoader {
+            manifest: plugin_manifest,
+            runtime: Runtime::Extism(Box::new(plugin)),
+        })
+    }
+    
+    /// Call a plugin function
+    ///
+    /// The execution context is made available to host functions for the
+    /// duration of the call. Chunks the plugin emitted without a stream sink
+    /// attached are prepended to the returned output. Failures are returned as
+    /// an [`AppError`] naming the plugin and function.
+    pub fn call(&mut self, function: &str, input: &[u8], context: &ExecutionContext) -> Result<Vec<u8>> {
+        debug!(
+            "Calling function '{}' on plugin '{}' (execution {})",
+            function, self.manifest.name, context.execution_id
+        );
+        
+        let mut call_context = context.clone();
+        call_context.plugin = self.manifest.id();
+        let result = match &mut self.runtime {
+            Runtime::Extism(plugin) => plugin
+                .call_with_host_context::<&[u8], &[u8], _>(function, input, call_context)
+                .map(|result| {
+                    let mut output = context.take_buffered();
+                    output.extend_from_slice(result);
+                    output
+                }),
+            Runtime::Component(component) => component.call(function, input),
+        };
+        
+        result.map_err(|e| AppError::from_plugin(&self.manifest.id(), function, &e).into())
+    }
+    
+    /// Fuel consumed by the last call, if the plugin is metered
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        match &self.runtime {
+            Runtime::Extism(plugin) => plugin.fuel_consumed(),
+            Runtime::Component(component) => component.fuel_consumed(),
+        }
+    }
+    
+    /// Handle that interrupts the call currently running on this plugin
+    pub fn cancel_handle(&self) -> CancelHandle {
+        match &self.runtime {
+            Runtime::Extism(plugin) => CancelHandle::Extism(plugin.cancel_handle()),
+            Runtime::Component(component) => CancelHandle::Component(component.engine().clone()),
+        }
+    }
+    
+    /// Check if plugin has a function
+    pub fn has_function(&self, function: &str) -> bool {
+        match &self.runtime {
+            Runtime::Extism(plugin) => plugin.function_exists(function),
+            Runtime::Component(component) => component.has_function(function),
+        }
+    }
+    
+    /// Get plugin manifest
+    pub fn manifest(&self) -> &PluginManifest {
+        &self.manifest
+    }
+}
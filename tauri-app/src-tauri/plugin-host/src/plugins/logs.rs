@@ -0,0 +1,58 @@
+//! In-memory ring buffer of structured log entries emitted by plugins
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Maximum number of log entries retained per plugin
+const MAX_ENTRIES_PER_PLUGIN: usize = 1000;
+
+/// A single log entry emitted by a plugin through the `log` or `host_log`
+/// host function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginLogEntry {
+    pub plugin: String,
+    pub level: String,
+    pub message: String,
+    pub fields: serde_json::Value,
+    /// The call that logged it, if it was logged during one
+    #[serde(default)]
+    pub execution_id: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+}
+
+/// Thread-safe store of recent plugin log entries
+///
+/// Uses a std Mutex because it is written from synchronous host functions.
+#[derive(Debug, Default)]
+pub struct PluginLogStore {
+    entries: Mutex<HashMap<String, VecDeque<PluginLogEntry>>>,
+}
+
+impl PluginLogStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry, evicting the oldest one when the plugin's buffer is full
+    pub fn push(&self, entry: PluginLogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        let buffer = entries.entry(entry.plugin.clone()).or_default();
+        if buffer.len() == MAX_ENTRIES_PER_PLUGIN {
+            buffer.pop_front();
+        }
+        buffer.push_back(entry);
+    }
+
+    /// Get the most recent entries for a plugin (oldest first)
+    pub fn get(&self, plugin: &str, limit: Option<usize>) -> Vec<PluginLogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let Some(buffer) = entries.get(plugin) else {
+            return Vec::new();
+        };
+
+        let skip = limit.map_or(0, |limit| buffer.len().saturating_sub(limit));
+        buffer.iter().skip(skip).cloned().collect()
+    }
+}
@@ -0,0 +1,2766 @@
+//! Plugin manager for discovering and managing plugins
+
+use super::capabilities::{Capability, CapabilityApprovals, CapabilityDecisions};
+use super::compatibility::{self, CompatibilityReport, PluginCompatibility};
+use super::graph::{self, DependencyGraph};
+use super::integrity::{self, IntegrityViolation};
+use super::license::{LicenseReport, PluginLicense};
+use super::payload::{PayloadFormat, PayloadSchemas};
+use super::sandbox::SandboxProfile;
+use super::trace::{TraceStore, MAX_PERSISTED_TRACES};
+use super::trust;
+use super::validation::{self, ValidationReport};
+use super::{
+    CancelHandle, ExecutionContext, MetricsRegistry, PluginLoader, PluginLogEntry, PluginLogStore, PluginManifest,
+    PluginMetricsSnapshot,
+};
+use crate::plugins::manifest::{self, find_manifest, EntryPoint, LifecycleEvent, MANIFEST_FILES};
+use crate::bus::{self, BusMessage, MessageBus};
+use crate::db::schema::{ExecutionTrace, PluginSource, TrustedAuthor};
+use crate::db::{migrations as db_migrations, operations, Database};
+use crate::error::{AppError, ErrorCode, Quota};
+use crate::events::{self, HostEvent};
+use crate::settings::{
+    SettingChange, SettingsStore, WorkerCounts, DEFAULT_PLUGIN_DRAIN_TIMEOUT_SECS, DISABLED_PLUGINS_KEY, PERSIST_EXECUTION_TRACES_KEY,
+    PLUGIN_DRAIN_TIMEOUT_KEY,
+};
+use crate::host_functions::introspection::{self, HostFunctionInfo};
+use crate::host_functions::{blob, http, sleep, HostFunctionFactory, HostFunctionState};
+use crate::paths;
+use crate::clipboard::Clipboard;
+use crate::mail::{Mailer, SmtpMailer};
+use crate::secrets::{self, FileSecretStore, SecretStore};
+use crate::templates;
+use crate::trash::TrashBin;
+use crate::worker_pool::WorkerPool;
+use anyhow::{Context, Result};
+use extism::Function;
+use semver::Version;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock as StdRwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use reqwest;
+use serde::{Deserialize, Serialize};
+
+/// Where a plugin's data directory is mounted inside the guest (with WASI)
+pub const PLUGIN_DATA_GUEST_PATH: &str = "/data";
+
+/// A plugin's `wasm_config.config`, split into manifest defaults and runtime overrides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// Values from the plugin's manifest
+    pub defaults: HashMap<String, String>,
+    /// Values set at runtime, stored in the database
+    pub overrides: HashMap<String, String>,
+    /// What the plugin sees: defaults with overrides applied
+    pub effective: HashMap<String, String>,
+}
+
+/// Plugin directory that failed to load during discovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginLoadFailure {
+    pub dir: PathBuf,
+    pub error: String,
+}
+
+/// Outcome of a discovery pass
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryReport {
+    /// IDs of the plugins loaded
+    pub loaded: Vec<String>,
+    pub failed: Vec<PluginLoadFailure>,
+    /// Plugins skipped because their manifest leaves out this platform
+    pub unsupported: Vec<PluginLoadFailure>,
+}
+
+/// Plugins loaded and unloaded when the set of disabled plugins changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginSetChange {
+    /// IDs of the plugins that were loaded
+    pub enabled: Vec<String>,
+    /// IDs of the plugins that were unloaded
+    pub disabled: Vec<String>,
+    pub failed: Vec<PluginLoadFailure>,
+}
+
+/// A version of a plugin loaded next to the current one, to be tried out
+/// before it is promoted or rolled back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginCanary {
+    /// Name the canary is called by: `id@version`
+    pub key: String,
+    pub plugin: String,
+    pub version: String,
+    /// Version callers get when they do not name one; None if not loaded
+    pub current_version: Option<String>,
+}
+
+/// A plugin held back at install until the user approves it, because it is
+/// unsigned or its author is not in the trust store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedPlugin {
+    pub id: String,
+    pub version: String,
+    pub author: Option<String>,
+    /// Key the plugin is signed with; None if it is unsigned
+    pub public_key: Option<String>,
+    pub reason: String,
+}
+
+/// A loaded plugin; the loader is locked for the duration of each call
+pub struct LoadedPlugin {
+    pub manifest: PluginManifest,
+    /// Directory the plugin was loaded from
+    pub dir: PathBuf,
+    pub loader: Mutex<PluginLoader>,
+    /// Input and output schemas of the entry points that declare them, by function
+    pub schemas: HashMap<String, PayloadSchemas>,
+    /// Interrupts the running call without waiting for the loader lock
+    cancel: CancelHandle,
+    /// Calls routed to this instance that have not returned, running or queued
+    calls: AtomicUsize,
+    /// Set once a replaced instance stops accepting the calls still queued on it
+    retired: AtomicBool,
+}
+
+impl LoadedPlugin {
+    /// Lock the loader, tolerating a previous call that panicked
+    pub fn lock(&self) -> MutexGuard<'_, PluginLoader> {
+        self.loader.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Count a call as in flight until the guard is dropped, unless the
+    /// plugin's `max_concurrent_calls` already are
+    pub(crate) fn begin_call(self: &Arc<Self>) -> Result<CallGuard, AppError> {
+        let in_flight = self.calls.fetch_add(1, Ordering::AcqRel);
+        let guard = CallGuard(self.clone());
+        match self.manifest.quotas.max_concurrent_calls {
+            Some(max) if in_flight >= max as usize => Err(AppError::quota_exceeded(
+                Quota::ConcurrentCalls,
+                max.into(),
+                in_flight as u64 + 1,
+            )),
+            _ => Ok(guard),
+        }
+    }
+    
+    /// Resolve `function`, which may be an alias or empty for the default
+    /// entry point, and check `input` against its declared format and schema
+    ///
+    /// Shared by direct calls and by plugins calling this one through
+    /// `call_plugin`, so both see the same entry points and checks.
+    pub(crate) fn prepare_call<'a>(&'a self, plugin_name: &str, function: &'a str, input: &[u8]) -> Result<&'a str> {
+        let Some(function) = self.manifest.resolve_function(function) else {
+            let mut error = AppError::new(
+                ErrorCode::NotFound,
+                format!("Plugin '{}' has no default function; name the function to call", plugin_name),
+            );
+            error.plugin = Some(plugin_name.to_string());
+            return Err(error.into());
+        };
+        for warning in self.manifest.deprecation_warnings(function) {
+            warn!("{}", warning);
+        }
+        if let Some((input_format, _)) = self.manifest.payload_formats(function) {
+            input_format
+                .check(input, &format!("Input of '{}'", function), ErrorCode::InvalidInput)
+                .map_err(|e| attribute(e, plugin_name, function))?;
+        }
+        if let Some(schemas) = self.schemas.get(function) {
+            schemas.check_input(input).map_err(|e| attribute(e, plugin_name, function))?;
+        }
+        Ok(function)
+    }
+    
+    /// Run a prepared call on the locked loader, interrupting it once it has
+    /// run for longer than `max_execution_ms`
+    ///
+    /// Returns the result with how long the call took and the fuel it used.
+    /// The watchdog is spawned on `runtime`, since the call blocks this thread.
+    pub(crate) fn run_call(
+        &self,
+        loader: &mut PluginLoader,
+        plugin_name: &str,
+        function: &str,
+        input: &[u8],
+        context: &ExecutionContext,
+        runtime: &tokio::runtime::Handle,
+    ) -> (Result<Vec<u8>>, Duration, Option<u64>) {
+        let limit = self.manifest.quotas.max_execution_ms;
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let watchdog = limit.map(|limit| {
+            let cancel = loader.cancel_handle();
+            let timed_out = timed_out.clone();
+            // A nested call shares its cancel flag with the caller, which
+            // may go on after the nested call is cut off
+            let context = context.call_stack.is_empty().then(|| context.clone());
+            runtime.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(limit)).await;
+                timed_out.store(true, Ordering::Release);
+                if let Some(context) = context {
+                    context.cancel();
+                }
+                if let Err(e) = cancel.cancel() {
+                    warn!("Failed to interrupt a call over its time quota: {:#}", e);
+                }
+            })
+        });
+        
+        let started = Instant::now();
+        let mut result = loader.call(function, input, context);
+        let elapsed = started.elapsed();
+        let fuel = loader.fuel_consumed();
+        
+        let left_open = context.close_blobs();
+        if left_open > 0 {
+            debug!("Closed {} blobs '{}/{}' left open", left_open, plugin_name, function);
+        }
+        
+        if let Some(watchdog) = watchdog {
+            watchdog.abort();
+        }
+        if let (Err(_), Some(limit)) = (&result, limit) {
+            if timed_out.load(Ordering::Acquire) {
+                let error = AppError::quota_exceeded(Quota::ExecutionTime, limit, elapsed.as_millis() as u64);
+                result = Err(attribute(error, plugin_name, function));
+            }
+        }
+        (result, elapsed, fuel)
+    }
+    
+    /// Check a call's output against `max_output_bytes` and the function's
+    /// declared format and schema
+    pub(crate) fn check_output(&self, plugin_name: &str, function: &str, result: Result<Vec<u8>>) -> Result<Vec<u8>> {
+        let output = result?;
+        if let Some(limit) = self.manifest.quotas.max_output_bytes {
+            if output.len() as u64 > limit {
+                let error = AppError::quota_exceeded(Quota::OutputSize, limit, output.len() as u64);
+                return Err(attribute(error, plugin_name, function));
+            }
+        }
+        if let Some((_, output_format)) = self.manifest.payload_formats(function) {
+            output_format
+                .check(&output, &format!("Output of '{}'", function), ErrorCode::PluginError)
+                .map_err(|e| attribute(e, plugin_name, function))?;
+        }
+        if let Some(schemas) = self.schemas.get(function) {
+            schemas.check_output(&output).map_err(|e| attribute(e, plugin_name, function))?;
+        }
+        Ok(output)
+    }
+    
+    /// Wait for the calls routed to this instance to return; once `timeout`
+    /// passes, the running call is interrupted and queued ones are refused
+    ///
+    /// Returns the number of calls that were cut off.
+    async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        while self.calls.load(Ordering::Acquire) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        let remaining = self.calls.load(Ordering::Acquire);
+        if remaining > 0 {
+            self.retired.store(true, Ordering::Release);
+            if let Err(e) = self.cancel.cancel() {
+                warn!("Failed to interrupt call to replaced plugin '{}': {:#}", self.manifest.id(), e);
+            }
+        }
+        remaining
+    }
+}
+
+/// Tag an error with the plugin and function it came from
+fn attribute(mut error: AppError, plugin_name: &str, function: &str) -> anyhow::Error {
+    error.plugin = Some(plugin_name.to_string());
+    error.function = Some(function.to_string());
+    error.into()
+}
+
+/// Marks a call to a [`LoadedPlugin`] as in flight
+pub(crate) struct CallGuard(Arc<LoadedPlugin>);
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.calls.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// How often a replaced instance is checked for calls still in flight
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Loaded plugins by ID, shared with host functions that call other plugins
+pub type PluginRegistry = Arc<RwLock<HashMap<String, Arc<LoadedPlugin>>>>;
+
+/// Keys of the settings each plugin watches, by plugin ID
+pub type SettingWatches = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+
+/// Export called with a [`SettingChange`] when a watched setting is written
+pub const SETTING_CHANGED_FUNCTION: &str = "on_setting_changed";
+
+/// Registry key of a plugin version loaded next to the current one
+pub fn canary_key(id: &str, version: &str) -> String {
+    format!("{}@{}", id, version)
+}
+
+/// Resolve a plugin ID, or a short name that matches exactly one loaded plugin
+///
+/// Either may be followed by `@version` to address a specific loaded version;
+/// without one, the current version is used and canaries are never matched.
+pub fn resolve_plugin_id(plugins: &HashMap<String, Arc<LoadedPlugin>>, name: &str) -> Result<String> {
+    if plugins.contains_key(name) {
+        return Ok(name.to_string());
+    }
+    
+    let (base, version) = match name.split_once('@') {
+        Some((base, version)) => (base, Some(version)),
+        None => (name, None),
+    };
+    let mut matches: Vec<&String> = plugins
+        .iter()
+        .filter(|(key, plugin)| {
+            let id = plugin.manifest.id();
+            let named = plugin.manifest.name == base || (version.is_some() && id == base);
+            named
+                && match version {
+                    Some(version) => plugin.manifest.version == version,
+                    None => **key == id,
+                }
+        })
+        .map(|(id, _)| id)
+        .collect();
+    match matches.len() {
+        0 => Err(AppError::new(ErrorCode::NotFound, format!("Plugin not found: {}", name)).into()),
+        1 => Ok(matches[0].clone()),
+        _ => {
+            matches.sort();
+            let message = format!(
+                "Plugin name '{}' is ambiguous; use one of: {}",
+                name,
+                matches.iter().map(|id| id.as_str()).collect::<Vec<_>>().join(", ")
+            );
+            Err(AppError::new(ErrorCode::Conflict, message).into())
+        }
+    }
+}
+
+/// Runs calls to the loaded plugins; see [`PluginManager::executor`]
+#[derive(Clone)]
+pub struct PluginExecutor {
+    plugins: PluginRegistry,
+    database: Option<Arc<Database>>,
+    metrics: Arc<RwLock<MetricsRegistry>>,
+    execution_pool: WorkerPool,
+    running: Arc<Mutex<HashMap<String, (CancelHandle, ExecutionContext)>>>,
+    traces: Arc<TraceStore>,
+}
+
+impl PluginExecutor {
+    /// Execute a plugin function with a caller-provided execution context
+    ///
+    /// `function` may be an entry point's alias, or empty for the plugin's
+    /// default entry point.
+    pub async fn execute_plugin_with_context(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: &[u8],
+        context: &ExecutionContext,
+    ) -> Result<Vec<u8>> {
+        let (plugin_name, plugin) = {
+            let plugins = self.plugins.read().await;
+            let id = resolve_plugin_id(&plugins, plugin_name)?;
+            let plugin = plugins[&id].clone();
+            (id, plugin)
+        };
+        let plugin_name = plugin_name.as_str();
+        let function = plugin.prepare_call(plugin_name, function, input)?.to_string();
+        let function = function.as_str();
+        
+        // Calls block on WASM execution (and on other plugins they invoke),
+        // so they run on the blocking pool rather than an async worker
+        let _call = plugin
+            .begin_call()
+            .map_err(|e| attribute(e, plugin_name, function))?;
+        // Calls waiting for a worker count against the plugin's concurrency quota
+        let _permit = self.execution_pool.acquire().await;
+        let running = self.running.clone();
+        let call_plugin = plugin_name.to_string();
+        let call_function = function.to_string();
+        let input = input.to_vec();
+        // Calls recorded since the context was made belong to no execution
+        context.take_trace();
+        let execution = context.clone();
+        let context = context.clone();
+        let database = self.database.clone();
+        let replaced = AppError::new(
+            ErrorCode::Unavailable,
+            format!("Plugin '{}' was replaced before the call could run", plugin_name),
+        );
+        let runtime = tokio::runtime::Handle::current();
+        let started_at = chrono::Utc::now().timestamp_millis();
+        let loaded = plugin.clone();
+        let (result, elapsed, fuel) = tokio::task::spawn_blocking(move || {
+            let mut loader = plugin.lock();
+            if plugin.retired.load(Ordering::Acquire) {
+                return (Err(replaced.into()), Duration::ZERO, None);
+            }
+            running
+                .lock()
+                .unwrap()
+                .insert(context.execution_id.clone(), (loader.cancel_handle(), context.clone()));
+            
+            let outcome = plugin.run_call(&mut loader, &call_plugin, &call_function, &input, &context, &runtime);
+            
+            // Don't leave the database waiting on a transaction nobody will end
+            if let Some(database) = &database {
+                match database.rollback_open_transaction(&context.execution_id) {
+                    Ok(true) => warn!("Rolled back the transaction '{}/{}' left open", call_plugin, call_function),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to roll back the transaction '{}/{}' left open: {:#}", call_plugin, call_function, e),
+                }
+            }
+            running.lock().unwrap().remove(&context.execution_id);
+            outcome
+        })
+        .await
+        .context("Plugin call panicked")?;
+        let result = loaded.check_output(plugin_name, function, result);
+        
+        let error = result.as_ref().err().map(|e| format!("{:#}", e));
+        self.metrics
+            .write()
+            .await
+            .record(plugin_name, function, elapsed, error.as_deref(), fuel);
+        let (calls, dropped_calls) = execution.take_trace();
+        self.record_trace(ExecutionTrace {
+            execution_id: execution.execution_id,
+            plugin: plugin_name.to_string(),
+            function: function.to_string(),
+            started_at,
+            duration_us: elapsed.as_micros() as u64,
+            error,
+            calls,
+            dropped_calls,
+        });
+        
+        result
+    }
+    
+    /// Keep an execution's trace, and store it if trace persistence is on
+    fn record_trace(&self, trace: ExecutionTrace) {
+        if let Some(database) = &self.database {
+            let persist = SettingsStore::new(database.clone())
+                .get_or_default::<bool>(PERSIST_EXECUTION_TRACES_KEY)
+                .unwrap_or(false);
+            if persist {
+                if let Err(e) = database.with_connection(|conn| operations::save_execution_trace(conn, &trace, MAX_PERSISTED_TRACES)) {
+                    warn!("Failed to store the trace of execution {}: {:#}", trace.execution_id, e);
+                }
+            }
+        }
+        self.traces.push(trace);
+    }
+}
+
+pub struct PluginManager {
+    plugins_dir: PathBuf,
+    /// Parent of the per-plugin data directories, next to `plugins_dir`
+    data_dir: PathBuf,
+    plugins: PluginRegistry,
+    database: Option<Arc<Database>>,
+    metrics: Arc<RwLock<MetricsRegistry>>,
+    logs: Arc<PluginLogStore>,
+    execution_pool: WorkerPool,
+    /// Cancel handles of in-flight calls, keyed by execution ID
+    running: Arc<Mutex<HashMap<String, (CancelHandle, ExecutionContext)>>>,
+    /// Plugins allowed to enable privileged features such as WASI
+    trusted: StdRwLock<HashSet<String>>,
+    /// Stricter sandboxes the user moved plugins to, by ID
+    sandbox_overrides: StdRwLock<HashMap<String, SandboxProfile>>,
+    /// Loosest sandbox the host lets any plugin run in
+    max_sandbox: StdRwLock<SandboxProfile>,
+    /// Installed plugins that are not loaded
+    disabled: StdRwLock<HashSet<String>>,
+    approvals: Arc<CapabilityApprovals>,
+    /// Recycle bin for files plugins delete or overwrite; needs the database
+    trash: Option<Arc<TrashBin>>,
+    setting_watches: SettingWatches,
+    /// Broker for messages plugins publish to each other
+    bus: Arc<MessageBus>,
+    /// Host functions the embedding application links into every plugin
+    host_functions: StdRwLock<Option<HostFunctionFactory>>,
+    /// Version plugins' `min_app_version` is checked against
+    app_version: StdRwLock<Version>,
+    /// Where `secret://` config values are looked up
+    secrets: StdRwLock<Arc<dyn SecretStore>>,
+    /// Clipboard plugins with the `clipboard` capability use
+    clipboard: StdRwLock<Option<Arc<dyn Clipboard>>>,
+    /// Mailer plugins with the `email` capability send through, if not SMTP
+    mailer: StdRwLock<Option<Arc<dyn Mailer>>>,
+    /// Host function calls of recent executions
+    traces: Arc<TraceStore>,
+}
+
+impl PluginManager {
+    /// Create a new plugin manager with database access
+    pub fn new_with_database(plugins_dir: PathBuf, database: Arc<Database>) -> Result<Self> {
+        info!("Initializing plugin manager at {:?} with database", plugins_dir);
+        
+        // Create plugins directory if it doesn't exist
+        if !plugins_dir.exists() {
+            std::fs::create_dir_all(&plugins_dir)
+                .context("Failed to create plugins directory")?;
+        }
+        
+        let trash = TrashBin::new(Self::app_root(&plugins_dir).join("trash"), database.clone());
+        Ok(Self {
+            data_dir: Self::data_root(&plugins_dir),
+            secrets: StdRwLock::new(Self::default_secret_store(&plugins_dir)),
+            plugins_dir,
+            plugins: Arc::new(RwLock::new(HashMap::new())),
+            database: Some(database),
+            metrics: Arc::new(RwLock::new(MetricsRegistry::new())),
+            logs: Arc::new(PluginLogStore::new()),
+            execution_pool: WorkerPool::new(WorkerCounts::default().plugin_workers),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            trusted: StdRwLock::new(HashSet::new()),
+            sandbox_overrides: StdRwLock::new(HashMap::new()),
+            max_sandbox: StdRwLock::new(SandboxProfile::Trusted),
+            disabled: StdRwLock::new(HashSet::new()),
+            approvals: Arc::new(CapabilityApprovals::new()),
+            trash: Some(Arc::new(trash)),
+            setting_watches: Arc::new(Mutex::new(HashMap::new())),
+            bus: Arc::new(MessageBus::new()),
+            host_functions: StdRwLock::new(None),
+            app_version: StdRwLock::new(compatibility::host_version()),
+            clipboard: StdRwLock::new(None),
+            mailer: StdRwLock::new(None),
+            traces: Arc::new(TraceStore::new()),
+        })
+    }
+
+    /// Create a new plugin manager
+    pub fn new(plugins_dir: PathBuf) -> Result<Self> {
+        if !plugins_dir.exists() {
+            std::fs::create_dir_all(&plugins_dir)
+                .context("Failed to create plugins directory")?;
+        }
+        
+        Ok(PluginManager {
+            data_dir: Self::data_root(&plugins_dir),
+            secrets: StdRwLock::new(Self::default_secret_store(&plugins_dir)),
+            plugins_dir,
+            plugins: Arc::new(RwLock::new(HashMap::new())),
+            database: None,
+            metrics: Arc::new(RwLock::new(MetricsRegistry::new())),
+            logs: Arc::new(PluginLogStore::new()),
+            execution_pool: WorkerPool::new(WorkerCounts::default().plugin_workers),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            trusted: StdRwLock::new(HashSet::new()),
+            sandbox_overrides: StdRwLock::new(HashMap::new()),
+            max_sandbox: StdRwLock::new(SandboxProfile::Trusted),
+            disabled: StdRwLock::new(HashSet::new()),
+            approvals: Arc::new(CapabilityApprovals::new()),
+            trash: None,
+            setting_watches: Arc::new(Mutex::new(HashMap::new())),
+            bus: Arc::new(MessageBus::new()),
+            host_functions: StdRwLock::new(None),
+            app_version: StdRwLock::new(compatibility::host_version()),
+            clipboard: StdRwLock::new(None),
+            mailer: StdRwLock::new(None),
+            traces: Arc::new(TraceStore::new()),
+        })
+    }
+    
+    fn app_root(plugins_dir: &Path) -> &Path {
+        plugins_dir.parent().unwrap_or(plugins_dir)
+    }
+    
+    fn default_secret_store(plugins_dir: &Path) -> Arc<dyn SecretStore> {
+        Arc::new(FileSecretStore::new(Self::app_root(plugins_dir).join("secrets")))
+    }
+    
+    fn data_root(plugins_dir: &Path) -> PathBuf {
+        Self::app_root(plugins_dir).join("data")
+    }
+    
+    /// Scratch directory of a plugin, mounted at [`PLUGIN_DATA_GUEST_PATH`]
+    pub fn plugin_data_dir(&self, manifest: &PluginManifest) -> PathBuf {
+        self.data_dir.join(manifest.install_dir_name())
+    }
+    
+    /// Directory of a canary, next to the plugin's own directory
+    fn canary_dir(&self, manifest: &PluginManifest) -> PathBuf {
+        self.plugins_dir.join(format!("{}@{}", manifest.install_dir_name(), manifest.version))
+    }
+    
+    /// Key of a plugin in the registry: its ID, or `id@version` when loaded
+    /// from a canary directory
+    fn registry_key(&self, manifest: &PluginManifest, plugin_dir: &Path) -> String {
+        if plugin_dir == self.canary_dir(manifest) {
+            canary_key(&manifest.id(), &manifest.version)
+        } else {
+            manifest.id()
+        }
+    }
+    
+    /// Directories of the installed plugins, sorted
+    fn installed_dirs(&self) -> Result<Vec<PathBuf>> {
+        let entries = std::fs::read_dir(&self.plugins_dir)
+            .context("Failed to read plugins directory")?;
+        
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            
+            // Hidden directories hold backups of plugins being reinstalled
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            
+            // Look for plugin.json or plugin.toml in each subdirectory
+            if path.is_dir() && !hidden && MANIFEST_FILES.iter().any(|file| path.join(file).exists()) {
+                dirs.push(path);
+            }
+        }
+        dirs.sort();
+        Ok(dirs)
+    }
+    
+    /// Check every installed plugin, loaded or not, against this build of the app
+    pub fn compatibility_report(&self) -> Result<CompatibilityReport> {
+        let app_version = self.app_version();
+        let extra_functions: Vec<String> = self
+            .extra_host_functions("")
+            .iter()
+            .map(|function| function.name().to_string())
+            .collect();
+        let dirs = self.installed_dirs()?;
+        
+        let mut incompatible = Vec::new();
+        for dir in &dirs {
+            let (plugin, version, issues, homepage) = match PluginManifest::load_from_file(&find_manifest(dir)) {
+                Ok(manifest) => (
+                    manifest.id(),
+                    Some(manifest.version.clone()),
+                    compatibility::check_plugin(&manifest, dir, &app_version, &extra_functions),
+                    manifest.homepage.clone(),
+                ),
+                Err(e) => (
+                    dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                    None,
+                    vec![format!("Invalid manifest: {:#}", e)],
+                    None,
+                ),
+            };
+            if issues.is_empty() {
+                continue;
+            }
+            
+            let link = match (homepage, &self.database) {
+                (Some(homepage), _) => Some(homepage),
+                (None, Some(db)) => db
+                    .with_connection(|conn| operations::get_plugin_sources(conn, &plugin))?
+                    .into_iter()
+                    .next()
+                    .map(|source| source.source_url),
+                (None, None) => None,
+            };
+            incompatible.push(PluginCompatibility { plugin, version, issues, link });
+        }
+        
+        Ok(CompatibilityReport {
+            app_version: app_version.to_string(),
+            checked: dirs.len(),
+            incompatible,
+            generated_at: chrono::Utc::now().timestamp(),
+        })
+    }
+    
+    /// Manifests of the installed plugins, loaded or not, leaving out those that cannot be read
+    fn installed_manifests(&self) -> Result<Vec<PluginManifest>> {
+        Ok(self
+            .installed_dirs()?
+            .iter()
+            .filter_map(|dir| PluginManifest::load_from_file(&find_manifest(dir)).ok())
+            .collect())
+    }
+    
+    /// Licenses of every installed plugin, loaded or not
+    pub fn license_report(&self) -> Result<LicenseReport> {
+        let mut plugins = Vec::new();
+        for dir in self.installed_dirs()? {
+            match PluginManifest::load_from_file(&find_manifest(&dir)) {
+                Ok(manifest) => plugins.push(PluginLicense {
+                    plugin: manifest.id(),
+                    version: manifest.version,
+                    author: manifest.author,
+                    license: manifest.license,
+                    homepage: manifest.homepage,
+                }),
+                Err(e) => warn!("Leaving {:?} out of the license report: {:#}", dir, e),
+            }
+        }
+        Ok(LicenseReport::new(plugins))
+    }
+    
+    /// Discover and load all plugins
+    ///
+    /// Manifests are parsed and modules compiled on a bounded set of threads;
+    /// a plugin that fails to load is reported without affecting the others.
+    pub async fn discover_plugins(&self) -> Result<DiscoveryReport> {
+        info!("Discovering plugins in: {:?}", self.plugins_dir);
+        
+        let mut report = DiscoveryReport::default();
+        let mut dirs = self.installed_dirs()?;
+        dirs.retain(|dir| match PluginManifest::load_from_file(&find_manifest(dir)) {
+            Ok(manifest) if self.is_disabled(&manifest.id()) => {
+                info!("Skipping disabled plugin '{}'", manifest.id());
+                false
+            }
+            Ok(manifest) => match compatibility::platform_issue(&manifest) {
+                Some(issue) => {
+                    info!("Skipping plugin '{}': {}", manifest.id(), issue);
+                    report.unsupported.push(PluginLoadFailure {
+                        dir: dir.clone(),
+                        error: issue,
+                    });
+                    false
+                }
+                None => true,
+            },
+            _ => true,
+        });
+        let workers = std::thread::available_parallelism()
+            .map_or(4, |n| n.get())
+            .min(dirs.len().max(1));
+        let next = AtomicUsize::new(0);
+        let mut prepared: Vec<(usize, Result<(String, PluginLoader)>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(dir) = dirs.get(index) else {
+                                break;
+                            };
+                            results.push((index, self.prepare_plugin(&find_manifest(dir), dir)));
+                        }
+                        results
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("Plugin loader thread panicked"))
+                .collect()
+        });
+        
+        // Register and enable in directory order so duplicate IDs resolve the same way every time
+        prepared.sort_by_key(|(index, _)| *index);
+        for (index, result) in prepared {
+            let dir = &dirs[index];
+            let result = match result {
+                Ok((plugin_name, loader)) => self.enable(plugin_name, dir, loader).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(plugin_name) => report.loaded.push(plugin_name),
+                Err(e) => {
+                    warn!("Failed to load plugin from {:?}: {:#}", dir, e);
+                    report.failed.push(PluginLoadFailure {
+                        dir: dir.clone(),
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+        
+        info!(
+            "✅ Loaded {} plugins ({} failed, {} unsupported on this platform)",
+            report.loaded.len(),
+            report.failed.len(),
+            report.unsupported.len()
+        );
+        Ok(report)
+    }
+    
+    /// Load a plugin from its manifest file
+    async fn load_plugin_from_manifest(
+        &self,
+        manifest_path: &Path,
+        plugin_dir: &Path,
+    ) -> Result<String> {
+        let (plugin_name, loader) = self.prepare_plugin(manifest_path, plugin_dir)?;
+        self.register(plugin_name, plugin_dir, loader).await
+    }
+    
+    /// Check a plugin and instantiate its module, without registering it;
+    /// returns the key to register it under
+    fn prepare_plugin(&self, manifest_path: &Path, plugin_dir: &Path) -> Result<(String, PluginLoader)> {
+        let mut manifest = PluginManifest::load_from_file(manifest_path)?;
+        Self::check_host_requirements(&manifest)?;
+        // Installed rather than loaded plugins, since discovery loads them in parallel
+        let issues = graph::version_issues(&manifest, &self.installed_manifests()?);
+        if !issues.is_empty() {
+            anyhow::bail!("Plugin '{}' cannot be loaded: {}", manifest.id(), issues.join("; "));
+        }
+        let plugin_name = manifest.id();
+        let key = self.registry_key(&manifest, plugin_dir);
+        // Packaged config, then the manifest's env, then the user's overrides
+        let env = self.render_env(&manifest)?;
+        manifest.wasm_config.config.extend(env);
+        manifest.wasm_config.config.extend(self.config_overrides(&plugin_name)?);
+        let secret_store = self.secrets.read().unwrap().clone();
+        secrets::resolve(&mut manifest.wasm_config.config, secret_store.as_ref())
+            .with_context(|| format!("Plugin '{}' has an unresolved secret", plugin_name))?;
+        
+        // The sandbox caps memory and fuel, and can withhold approved capabilities
+        let sandbox = self.sandbox_profile(&manifest);
+        sandbox.restrict(&mut manifest.wasm_config);
+        
+        // Withhold sensitive capabilities the user has not granted
+        let signer = trust::verify_plugin(plugin_dir).ok().flatten();
+        let mut withheld = self.withheld_capabilities(&manifest, signer.as_deref())?;
+        for capability in sandbox.withheld() {
+            if !withheld.contains(capability) {
+                withheld.push(*capability);
+            }
+        }
+        if !withheld.is_empty() {
+            info!("Withholding capabilities {:?} from plugin '{}'", withheld, plugin_name);
+        }
+        if withheld.contains(&Capability::Network) {
+            manifest.wasm_config.allowed_hosts.clear();
+        }
+        if withheld.contains(&Capability::Filesystem) {
+            manifest.wasm_config.allowed_paths.clear();
+        }
+        if withheld.contains(&Capability::Exec) {
+            manifest.wasm_config.allowed_commands.clear();
+        }
+        
+        // Every plugin gets its own data directory; it replaces any other mount at the same guest path
+        let data_dir = self.plugin_data_dir(&manifest);
+        std::fs::create_dir_all(&data_dir)
+            .with_context(|| format!("Failed to create plugin data directory {:?}", data_dir))?;
+        
+        // Resolve path tokens like `$DOWNLOADS` to this platform's directories
+        let mut allowed_paths = HashMap::new();
+        for (host, guest) in std::mem::take(&mut manifest.wasm_config.allowed_paths) {
+            if guest == PLUGIN_DATA_GUEST_PATH {
+                continue;
+            }
+            let resolved = paths::resolve(&host, Some(&data_dir))
+                .with_context(|| format!("Plugin '{}' has an invalid allowed path '{}'", plugin_name, host))?;
+            if resolved.starts_with(&data_dir) {
+                std::fs::create_dir_all(&resolved)
+                    .with_context(|| format!("Failed to create plugin data directory {:?}", resolved))?;
+            }
+            allowed_paths.insert(resolved.to_string_lossy().into_owned(), guest);
+        }
+        manifest.wasm_config.allowed_paths = allowed_paths;
+        manifest.wasm_config.allowed_paths.insert(
+            data_dir.to_string_lossy().into_owned(),
+            PLUGIN_DATA_GUEST_PATH.to_string(),
+        );
+        
+        if manifest.wasm_config.wasi {
+            if !manifest.declares(&Capability::Wasi) {
+                anyhow::bail!(
+                    "Plugin '{}' enables WASI without declaring the '{}' capability",
+                    plugin_name,
+                    Capability::Wasi
+                );
+            }
+            if !self.is_trusted(&plugin_name) {
+                anyhow::bail!("Plugin '{}' enables WASI but is not trusted", plugin_name);
+            }
+            if !sandbox.allows_wasi() {
+                anyhow::bail!("Plugin '{}' enables WASI but runs in the '{}' sandbox", plugin_name, sandbox);
+            }
+        }
+        
+        // Refuse files modified, or put in place, outside the installer
+        let checksum = integrity::sha256_plugin(&manifest, plugin_dir)?;
+        match self.recorded_checksum(&key)? {
+            Some(recorded) if recorded == checksum => {}
+            // Installs from before migrations and UI assets were checked
+            // recorded the modules alone; cover the rest from now on
+            Some(recorded) if recorded == integrity::sha256_modules(&manifest.wasm_paths(plugin_dir))? => {
+                info!("Recording the checksum of the migrations and UI assets of plugin '{}'", key);
+                self.record_checksum(&key, Some(&checksum))?;
+            }
+            Some(recorded) => anyhow::bail!(
+                "Files of plugin '{}' changed outside the installer (expected sha256 {}, found {})",
+                key,
+                recorded,
+                checksum
+            ),
+            None if self.database.is_some() => anyhow::bail!(
+                "Plugin '{}' was not installed through the installer (no checksum is recorded for its files); install it to load it",
+                key
+            ),
+            None => {}
+        }
+        
+        Self::check_entry_point_modules(&manifest, plugin_dir)?;
+        
+        // Name the missing declarations rather than fail to link the imports
+        let undeclared = compatibility::undeclared_capabilities(&manifest, plugin_dir);
+        if !undeclared.is_empty() {
+            anyhow::bail!("Plugin '{}' {}", plugin_name, undeclared.join("; "));
+        }
+        for warning in compatibility::deprecated_host_functions(&manifest, plugin_dir) {
+            warn!("{}", warning);
+        }
+        
+        // Create host functions if database is available
+        let loader = if PluginLoader::is_component(&manifest.wasm_path(plugin_dir))? {
+            PluginLoader::load_component(manifest, plugin_dir, self.logs.clone())?
+        } else if let (Some(db), Some(trash)) = (&self.database, &self.trash) {
+            let state = HostFunctionState {
+                dependencies: manifest.dependencies.keys().cloned().collect(),
+                denied_hosts: manifest.wasm_config.denied_hosts.clone(),
+                config: manifest.wasm_config.config.clone(),
+                max_sleep_ms: manifest.quotas.max_sleep_ms.unwrap_or(sleep::DEFAULT_MAX_SLEEP_MS),
+                host_api_level: manifest.host_api_level,
+                mounts: blob::Mount::from_allowed_paths(&manifest.wasm_config.allowed_paths),
+                allowed_commands: manifest.wasm_config.allowed_commands.clone(),
+                own_tables: !manifest.migrations.is_empty(),
+                ..self.host_function_state(&plugin_name, db, trash)
+            };
+            let mut host_fns = crate::host_functions::register_host_functions(state, &manifest.capabilities, &withheld);
+            host_fns.extend(self.extra_host_functions(&plugin_name));
+            introspection::link_list_host_functions(&mut host_fns);
+            PluginLoader::load_with_host_functions(manifest, plugin_dir, host_fns)?
+        } else {
+            // Without a database only the HTTP and embedder's functions are linked
+            let mut host_fns = http::http_functions(&plugin_name, manifest.wasm_config.denied_hosts.clone(), None);
+            host_fns.extend(self.extra_host_functions(&plugin_name));
+            introspection::link_list_host_functions(&mut host_fns);
+            PluginLoader::load_with_host_functions(manifest, plugin_dir, host_fns)?
+        };
+        
+        Ok((key, loader))
+    }
+    
+    /// Add a prepared plugin to the registry, replacing an earlier load from the same directory
+    async fn register(&self, plugin_name: String, plugin_dir: &Path, loader: PluginLoader) -> Result<String> {
+        let schemas = PayloadSchemas::compile(loader.manifest())?;
+        let mut plugins = self.plugins.write().await;
+        if let Some(existing) = plugins.get(&plugin_name) {
+            if existing.dir != plugin_dir {
+                anyhow::bail!(
+                    "Plugin '{}' is already loaded from {:?}",
+                    plugin_name,
+                    existing.dir
+                );
+            }
+        }
+        let previous = plugins.insert(
+            plugin_name.clone(),
+            Arc::new(LoadedPlugin {
+                manifest: loader.manifest().clone(),
+                dir: plugin_dir.to_path_buf(),
+                cancel: loader.cancel_handle(),
+                loader: Mutex::new(loader),
+                schemas,
+                calls: AtomicUsize::new(0),
+                retired: AtomicBool::new(false),
+            }),
+        );
+        drop(plugins);
+        
+        // New calls already go to the new instance
+        if let Some(previous) = previous {
+            self.retire(&plugin_name, previous);
+        }
+        
+        Ok(plugin_name)
+    }
+    
+    /// Let the calls in flight on a replaced instance finish in the background,
+    /// within the drain timeout
+    fn retire(&self, plugin_name: &str, previous: Arc<LoadedPlugin>) {
+        if previous.calls.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let timeout = Duration::from_secs(
+            self.database
+                .as_ref()
+                .and_then(|db| SettingsStore::new(db.clone()).get(PLUGIN_DRAIN_TIMEOUT_KEY).ok().flatten())
+                .unwrap_or(DEFAULT_PLUGIN_DRAIN_TIMEOUT_SECS),
+        );
+        let plugin_name = plugin_name.to_string();
+        tokio::spawn(async move {
+            let cut_off = previous.drain(timeout).await;
+            if cut_off > 0 {
+                warn!(
+                    "Interrupted {} call(s) still running on the replaced instance of '{}' after {:?}",
+                    cut_off, plugin_name, timeout
+                );
+            }
+        });
+    }
+    
+    /// Register a prepared plugin and run its `on_enable` hook, unloading it again if the hook fails
+    async fn enable(&self, plugin_name: String, plugin_dir: &Path, loader: PluginLoader) -> Result<String> {
+        let plugin_name = self.register(plugin_name, plugin_dir, loader).await?;
+        
+        // Canaries run no lifecycle hooks
+        if self.canary(&plugin_name).await.is_some() {
+            return Ok(plugin_name);
+        }
+        let enabled = async {
+            self.migrate_plugin(&plugin_name, plugin_dir).await?;
+            self.run_hook(&plugin_name, LifecycleEvent::Enable).await
+        };
+        if let Err(e) = enabled.await {
+            self.plugins.write().await.remove(&plugin_name);
+            return Err(e);
+        }
+        
+        Ok(plugin_name)
+    }
+    
+    /// Load a plugin and run its `on_enable` hook
+    async fn load_and_enable(&self, manifest_path: &Path, plugin_dir: &Path) -> Result<String> {
+        let (plugin_name, loader) = self.prepare_plugin(manifest_path, plugin_dir)?;
+        self.enable(plugin_name, plugin_dir, loader).await
+    }
+    
+    /// Apply the migrations a plugin ships for its own tables that have not
+    /// been applied yet
+    async fn migrate_plugin(&self, plugin_name: &str, plugin_dir: &Path) -> Result<()> {
+        let Some(manifest) = self.get_plugin(plugin_name).await else {
+            anyhow::bail!("Plugin not found: {}", plugin_name);
+        };
+        if manifest.migrations.is_empty() {
+            return Ok(());
+        }
+        let Some(db) = &self.database else {
+            anyhow::bail!("Plugin '{}' has database migrations but there is no database", plugin_name);
+        };
+        let signer = trust::verify_plugin(plugin_dir).ok().flatten();
+        if self.withheld_capabilities(&manifest, signer.as_deref())?.contains(&Capability::DB_WRITE)
+            || self.sandbox_profile(&manifest).withheld().contains(&Capability::DB_WRITE)
+        {
+            anyhow::bail!(
+                "Plugin '{}' has database migrations but may not use '{}'",
+                plugin_name,
+                Capability::DB_WRITE
+            );
+        }
+        let migrations = manifest
+            .migrations
+            .iter()
+            .map(|path| {
+                std::fs::read_to_string(plugin_dir.join(path))
+                    .map(|sql| (path.clone(), sql))
+                    .with_context(|| format!("Failed to read migration {} of plugin '{}'", path, plugin_name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let version = db.with_connection(|conn| Ok(db_migrations::run_plugin_migrations(conn, &manifest.id(), &migrations)))??;
+        info!("Tables of plugin '{}' are at schema version {}", plugin_name, version);
+        Ok(())
+    }
+    
+    /// Run a plugin's hook for a lifecycle event, if it declares one
+    async fn run_hook(&self, plugin_name: &str, event: LifecycleEvent) -> Result<()> {
+        let Some(manifest) = self.get_plugin(plugin_name).await else {
+            anyhow::bail!("Plugin not found: {}", plugin_name);
+        };
+        let Some(function) = manifest.hooks.function_for(event) else {
+            return Ok(());
+        };
+        
+        info!("Running {} hook '{}' of plugin '{}'", event.as_str(), function, plugin_name);
+        let input = serde_json::to_vec(&serde_json::json!({
+            "event": event.as_str(),
+            "version": manifest.version,
+        }))?;
+        
+        self.execute_plugin(plugin_name, function, &input)
+            .await
+            .with_context(|| format!("{} hook of plugin '{}' failed", event.as_str(), plugin_name))?;
+        Ok(())
+    }
+    
+    /// Load a freshly installed plugin, check its assets and run its install and enable hooks
+    ///
+    /// On failure the installed files are removed and the previous version, if
+    /// one was moved to `backup`, is restored and reloaded.
+    async fn activate_install(
+        &self,
+        manifest_path: &Path,
+        plugin_dir: &Path,
+        backup: Option<PathBuf>,
+    ) -> Result<String> {
+        // The installer is the one place allowed to change a plugin's checksum
+        let manifest = PluginManifest::load_from_file(manifest_path)?;
+        let previous_checksum = self.recorded_checksum(&manifest.id())?;
+        let checksum = integrity::sha256_plugin(&manifest, plugin_dir)?;
+        self.record_checksum(&manifest.id(), Some(&checksum))?;
+        
+        let result = async {
+            let plugin_name = self.load_plugin_from_manifest(manifest_path, plugin_dir).await?;
+            let hooks = async {
+                if let Some(manifest) = self.get_plugin(&plugin_name).await {
+                    manifest.assets.validate_files(plugin_dir)?;
+                }
+                self.migrate_plugin(&plugin_name, plugin_dir).await?;
+                self.run_hook(&plugin_name, LifecycleEvent::Install).await?;
+                self.run_hook(&plugin_name, LifecycleEvent::Enable).await
+            }
+            .await;
+            if hooks.is_err() {
+                self.plugins.write().await.remove(&plugin_name);
+            }
+            hooks.map(|()| plugin_name)
+        }
+        .await;
+        if let Ok(plugin_name) = &result {
+            events::publish(
+                events::PLUGIN_INSTALLED,
+                None,
+                serde_json::json!({ "plugin": plugin_name, "version": manifest.version }),
+            );
+        }
+        
+        match (result, backup) {
+            (Ok(plugin_name), Some(backup)) => {
+                if let Err(e) = std::fs::remove_dir_all(&backup) {
+                    warn!("Failed to remove plugin backup {:?}: {}", backup, e);
+                }
+                Ok(plugin_name)
+            }
+            (Ok(plugin_name), None) => Ok(plugin_name),
+            (Err(e), backup) => {
+                warn!("Rolling back install of {:?}: {:#}", plugin_dir, e);
+                self.record_checksum(&manifest.id(), previous_checksum.as_deref())?;
+                let _ = std::fs::remove_dir_all(plugin_dir);
+                if let Some(backup) = backup {
+                    std::fs::rename(&backup, plugin_dir)
+                        .context("Failed to restore previous plugin version")?;
+                    if let Err(restore_err) = self
+                        .load_and_enable(&find_manifest(plugin_dir), plugin_dir)
+                        .await
+                    {
+                        warn!("Failed to reload previous plugin version: {:#}", restore_err);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Move an existing plugin directory aside so a failed install can restore it
+    fn backup_existing(&self, plugin_dir: &Path) -> Result<Option<PathBuf>> {
+        if !plugin_dir.exists() {
+            return Ok(None);
+        }
+        
+        let name = plugin_dir
+            .file_name()
+            .context("Invalid plugin directory")?
+            .to_string_lossy();
+        let backup = self.plugins_dir.join(format!(".{}.backup", name));
+        if backup.exists() {
+            std::fs::remove_dir_all(&backup)?;
+        }
+        std::fs::rename(plugin_dir, &backup).context("Failed to back up existing plugin")?;
+        Ok(Some(backup))
+    }
+    
+    /// Uninstall a plugin, running its `on_uninstall` hook first
+    ///
+    /// If the hook fails the plugin stays installed.
+    pub async fn uninstall_plugin(&self, name: &str) -> Result<String> {
+        let id = self.resolve_id(name).await?;
+        if let Some(canary) = self.canary(&id).await {
+            anyhow::bail!("'{}' is a canary of plugin '{}'; roll it back instead", id, canary.plugin);
+        }
+        info!("Uninstalling plugin: {}", id);
+        
+        self.run_hook(&id, LifecycleEvent::Uninstall).await?;
+        
+        let plugin = self
+            .plugins
+            .write()
+            .await
+            .remove(&id)
+            .context(format!("Plugin not found: {}", id))?;
+        
+        std::fs::remove_dir_all(&plugin.dir)
+            .with_context(|| format!("Failed to remove plugin directory {:?}", plugin.dir))?;
+        let data_dir = self.plugin_data_dir(&plugin.manifest);
+        if data_dir.exists() {
+            std::fs::remove_dir_all(&data_dir)
+                .with_context(|| format!("Failed to remove plugin data directory {:?}", data_dir))?;
+        }
+        self.record_checksum(&id, None)?;
+        if let Some(db) = &self.database {
+            db.with_connection(|conn| operations::delete_plugin_signer(conn, &id))?;
+            db.with_connection(|conn| operations::delete_plugin_config(conn, &id))?;
+            db.with_connection(|conn| operations::delete_plugin_kv(conn, &id))?;
+            db.with_connection(|conn| operations::delete_plugin_keys(conn, &id))?;
+            db.with_connection(|conn| operations::delete_plugin_capabilities(conn, &id))?;
+            db.with_connection(|conn| operations::delete_plugin_sources(conn, &id))?;
+        }
+        self.setting_watches.lock().unwrap().remove(&id);
+        self.bus.remove_plugin(&id);
+        for canary in self.list_canaries().await {
+            if canary.plugin == id {
+                self.rollback_canary(&canary.key).await?;
+            }
+        }
+        events::publish(events::PLUGIN_UNINSTALLED, None, serde_json::json!({ "plugin": id }));
+        
+        Ok(id)
+    }
+    
+    /// Reload a plugin from its directory, picking up manifest and config changes
+    ///
+    /// Calls already running or queued finish on the old instance, which is
+    /// interrupted once [`PLUGIN_DRAIN_TIMEOUT_KEY`] passes; lifecycle hooks
+    /// are not run.
+    pub async fn reload_plugin(&self, name: &str) -> Result<String> {
+        let id = self.resolve_id(name).await?;
+        let dir = self
+            .get_plugin_dir(&id)
+            .await
+            .context(format!("Plugin not found: {}", id))?;
+        self.load_plugin_from_manifest(&find_manifest(&dir), &dir).await
+    }
+    
+    /// Get a plugin's configuration
+    pub async fn get_plugin_config(&self, name: &str) -> Result<PluginConfig> {
+        let id = self.resolve_id(name).await?;
+        let dir = self
+            .get_plugin_dir(&id)
+            .await
+            .context(format!("Plugin not found: {}", id))?;
+        let defaults = PluginManifest::load_from_file(&find_manifest(&dir))?.wasm_config.config;
+        let overrides = self.config_overrides(&id)?;
+        let mut effective = defaults.clone();
+        effective.extend(overrides.clone());
+        Ok(PluginConfig {
+            defaults,
+            overrides,
+            effective,
+        })
+    }
+    
+    /// Set (or with None, remove) runtime config overrides and reload the plugin
+    pub async fn set_plugin_config(
+        &self,
+        name: &str,
+        values: &HashMap<String, Option<String>>,
+    ) -> Result<PluginConfig> {
+        let id = self.resolve_id(name).await?;
+        let db = self
+            .database
+            .as_ref()
+            .context("Plugin configuration requires a database")?;
+        let now = chrono::Utc::now().timestamp();
+        db.with_connection(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            for (key, value) in values {
+                match value {
+                    Some(value) => operations::set_plugin_config_value(&tx, &id, key, value, now)?,
+                    None => operations::delete_plugin_config_value(&tx, &id, key)?,
+                }
+            }
+            tx.commit()
+        })?;
+        
+        self.reload_plugin(&id).await?;
+        info!("Updated {} config value(s) of plugin {}", values.len(), id);
+        self.get_plugin_config(&id).await
+    }
+    
+    /// Runtime config overrides of a plugin; always empty without a database
+    fn config_overrides(&self, name: &str) -> Result<HashMap<String, String>> {
+        match &self.database {
+            Some(db) => Ok(db.with_connection(|conn| operations::get_plugin_config(conn, name))?),
+            None => Ok(HashMap::new()),
+        }
+    }
+    
+    /// The manifest's `env` map with its setting placeholders filled in
+    fn render_env(&self, manifest: &PluginManifest) -> Result<HashMap<String, String>> {
+        let settings = self.database.clone().map(SettingsStore::new);
+        manifest
+            .env
+            .iter()
+            .map(|(name, template)| {
+                let value = templates::render(template, |key| match &settings {
+                    Some(settings) => settings.get(key),
+                    None => anyhow::bail!("Settings are unavailable without a database"),
+                })
+                .with_context(|| format!("Plugin '{}' has an invalid env value '{}'", manifest.id(), name))?;
+                Ok((name.clone(), value))
+            })
+            .collect()
+    }
+    
+    /// Capabilities granted (true) or denied (false) to a plugin; None without a database
+    fn capability_decisions(&self, name: &str) -> Result<Option<HashMap<Capability, bool>>> {
+        let Some(db) = &self.database else {
+            return Ok(None);
+        };
+        let stored = db.with_connection(|conn| operations::get_plugin_capabilities(conn, name))?;
+        let mut decisions = HashMap::new();
+        for (capability, granted) in stored {
+            match capability.parse() {
+                Ok(capability) => {
+                    decisions.insert(capability, granted);
+                }
+                Err(e) => warn!("Ignoring stored decision of plugin '{}': {}", name, e),
+            }
+        }
+        Ok(Some(decisions))
+    }
+    
+    /// Sensitive capabilities a plugin signed by `signer` asks for but was
+    /// not granted
+    ///
+    /// Trusted plugins signed by the key they were first installed with get
+    /// everything but [`Capability::ASKED_OF_TRUSTED`]; without a database
+    /// nothing else is enforced, and those are withheld.
+    fn withheld_capabilities(&self, manifest: &PluginManifest, signer: Option<&str>) -> Result<Vec<Capability>> {
+        let plugin_name = manifest.id();
+        let trusted = self.is_trusted_signer(&plugin_name, signer)?;
+        let decisions = self.capability_decisions(&plugin_name)?;
+        Ok(manifest
+            .sensitive_capabilities()
+            .into_iter()
+            .filter(|capability| {
+                let asked = Capability::ASKED_OF_TRUSTED.contains(capability);
+                match &decisions {
+                    Some(decisions) => (asked || !trusted) && decisions.get(capability) != Some(&true),
+                    None => asked,
+                }
+            })
+            .collect())
+    }
+    
+    /// Ask the user about sensitive capabilities of a plugin being installed
+    /// that have not been decided on, and record the answer
+    async fn approve_capabilities(&self, manifest: &PluginManifest, signer: Option<&str>) -> Result<()> {
+        let plugin_name = manifest.id();
+        let Some(decisions) = self.capability_decisions(&plugin_name)? else {
+            return Ok(());
+        };
+        let trusted = self.is_trusted_signer(&plugin_name, signer)?;
+        let undecided: Vec<Capability> = manifest
+            .sensitive_capabilities()
+            .into_iter()
+            .filter(|capability| !trusted || Capability::ASKED_OF_TRUSTED.contains(capability))
+            .filter(|capability| !decisions.contains_key(capability))
+            .collect();
+        if undecided.is_empty() {
+            return Ok(());
+        }
+        
+        info!("Plugin '{}' requests capabilities {:?}; waiting for approval", plugin_name, undecided);
+        let answer = self.approvals.request(&plugin_name, &manifest.version, undecided).await;
+        let now = chrono::Utc::now().timestamp();
+        for (capabilities, granted) in [(&answer.granted, true), (&answer.denied, false)] {
+            for capability in capabilities {
+                self.record_capability(&plugin_name, capability, granted, now)?;
+            }
+        }
+        Ok(())
+    }
+    
+    fn record_capability(&self, name: &str, capability: &Capability, granted: bool, decided_at: i64) -> Result<()> {
+        if let Some(db) = &self.database {
+            let capability = capability.to_string();
+            db.with_connection(|conn| operations::set_plugin_capability(conn, name, &capability, granted, decided_at))?;
+        }
+        Ok(())
+    }
+    
+    /// Recycle bin for files plugins delete or overwrite; None without a database
+    pub fn trash(&self) -> Option<Arc<TrashBin>> {
+        self.trash.clone()
+    }
+    
+    /// Broker for messages plugins publish to each other
+    pub fn bus(&self) -> Arc<MessageBus> {
+        self.bus.clone()
+    }
+    
+    /// Open capability requests, for prompting the user
+    pub fn capability_approvals(&self) -> Arc<CapabilityApprovals> {
+        self.approvals.clone()
+    }
+    
+    /// Capabilities granted and denied to a plugin
+    pub async fn get_plugin_capabilities(&self, name: &str) -> Result<CapabilityDecisions> {
+        let id = self.resolve_id(name).await?;
+        let mut result = CapabilityDecisions::default();
+        for (capability, granted) in self.capability_decisions(&id)?.unwrap_or_default() {
+            if granted {
+                result.granted.push(capability);
+            } else {
+                result.denied.push(capability);
+            }
+        }
+        result.granted.sort();
+        result.denied.sort();
+        Ok(result)
+    }
+    
+    /// Grant or deny a sensitive capability and reload the plugin so it applies
+    pub async fn set_plugin_capability(&self, name: &str, capability: &str, granted: bool) -> Result<()> {
+        let capability: Capability = capability.parse()?;
+        if !Capability::SENSITIVE.contains(&capability) {
+            anyhow::bail!(
+                "Capability '{}' needs no approval; expected one of: {}",
+                capability,
+                Capability::SENSITIVE.iter().map(Capability::to_string).collect::<Vec<_>>().join(", ")
+            );
+        }
+        if self.database.is_none() {
+            anyhow::bail!("Capability decisions need a database");
+        }
+        let id = self.resolve_id(name).await?;
+        self.record_capability(&id, &capability, granted, chrono::Utc::now().timestamp())?;
+        self.reload_plugin(&id).await?;
+        Ok(())
+    }
+    
+    /// Resolve a plugin ID or unambiguous short name to the plugin's ID
+    pub async fn resolve_id(&self, name: &str) -> Result<String> {
+        resolve_plugin_id(&*self.plugins.read().await, name)
+    }
+    
+    /// Checksum recorded for a plugin's module; always None without a database
+    fn recorded_checksum(&self, name: &str) -> Result<Option<String>> {
+        match &self.database {
+            Some(db) => Ok(db.with_connection(|conn| operations::get_plugin_checksum(conn, name))?),
+            None => Ok(None),
+        }
+    }
+    
+    /// Record (or with None, forget) the checksum of a plugin's files
+    fn record_checksum(&self, name: &str, checksum: Option<&str>) -> Result<()> {
+        let Some(db) = &self.database else {
+            return Ok(());
+        };
+        let now = chrono::Utc::now().timestamp();
+        db.with_connection(|conn| match checksum {
+            Some(checksum) => operations::set_plugin_checksum(conn, name, checksum, now),
+            None => operations::delete_plugin_checksum(conn, name),
+        })?;
+        Ok(())
+    }
+    
+    /// Re-hash every loaded plugin's files, and unload and disable any that
+    /// changed
+    pub async fn verify_plugins(&self) -> Result<Vec<IntegrityViolation>> {
+        let loaded: Vec<(String, Arc<LoadedPlugin>)> = self
+            .plugins
+            .read()
+            .await
+            .iter()
+            .map(|(key, plugin)| (key.clone(), plugin.clone()))
+            .collect();
+        
+        let mut violations = Vec::new();
+        for (name, plugin) in loaded {
+            let Some(expected) = self.recorded_checksum(&name)? else {
+                continue;
+            };
+            let actual = integrity::sha256_plugin(&plugin.manifest, &plugin.dir).ok();
+            if actual.as_deref() == Some(expected.as_str()) {
+                continue;
+            }
+            
+            warn!("Plugin '{}' failed verification; disabling it", name);
+            if let Some(plugin) = self.plugins.write().await.remove(&name) {
+                self.disable_until_reinstalled(&plugin.manifest.id())?;
+            }
+            violations.push(IntegrityViolation {
+                plugin: name,
+                expected_sha256: expected,
+                actual_sha256: actual,
+            });
+        }
+        
+        Ok(violations)
+    }
+    
+    /// Install a plugin from a directory, returning its name
+    pub async fn install_plugin(&self, source: &Path) -> Result<String> {
+        info!("Installing plugin from: {:?}", source);
+        
+        let manifest_path = find_manifest(source);
+        if !manifest_path.exists() {
+            anyhow::bail!("plugin.json or plugin.toml not found in: {:?}", source);
+        }
+        
+        let manifest = PluginManifest::load_from_file(&manifest_path)?;
+        Self::check_host_requirements(&manifest)?;
+        let signer = trust::verify_plugin(source)
+            .with_context(|| format!("Plugin '{}' has an invalid signature", manifest.id()))?;
+        if let Some(reason) = self.quarantine_reason(&manifest, signer.as_deref())? {
+            return self.quarantine(source, &manifest, &reason);
+        }
+        self.install_approved(source, manifest, signer.as_deref()).await
+    }
+    
+    /// Install a plugin whose author checks out, signed by `signer`
+    async fn install_approved(&self, source: &Path, manifest: PluginManifest, signer: Option<&str>) -> Result<String> {
+        let dest_dir = self.plugins_dir.join(manifest.install_dir_name());
+        self.approve_capabilities(&manifest, signer).await?;
+        
+        // Copy plugin directory, keeping any previous version until the install succeeds;
+        // a plugin.toml is installed as plugin.json
+        let backup = self.backup_existing(&dest_dir)?;
+        if let Err(e) = copy_dir_all(source, &dest_dir).and_then(|_| manifest::normalize_manifest(&dest_dir)) {
+            let _ = std::fs::remove_dir_all(&dest_dir);
+            if let Some(backup) = backup {
+                std::fs::rename(&backup, &dest_dir)?;
+            }
+            return Err(e);
+        }
+        
+        // Load the plugin and run its hooks
+        let id = self.activate_install(&find_manifest(&dest_dir), &dest_dir, backup)
+            .await?;
+        // Installed from a directory now; the URL installer records its sources afterwards
+        if let Some(db) = &self.database {
+            db.with_connection(|conn| operations::delete_plugin_sources(conn, &id))?;
+            // Trusting the plugin by ID covers later versions from this signer only
+            if let Some(public_key) = signer {
+                let now = chrono::Utc::now().timestamp();
+                db.with_connection(|conn| operations::set_plugin_signer(conn, &id, public_key, now))?;
+            }
+        }
+        Ok(id)
+    }
+    
+    /// Where plugins wait for approval; hidden, so discovery skips it
+    fn quarantine_root(&self) -> PathBuf {
+        self.plugins_dir.join(".quarantine")
+    }
+    
+    /// Why a plugin has to be approved before it is installed; None if it or
+    /// its author is trusted, or without a database to keep a trust store in
+    fn quarantine_reason(&self, manifest: &PluginManifest, signer: Option<&str>) -> Result<Option<String>> {
+        let Some(db) = &self.database else {
+            return Ok(None);
+        };
+        if self.is_trusted_signer(&manifest.id(), signer)? {
+            return Ok(None);
+        }
+        let Some(public_key) = signer else {
+            return Ok(Some("The plugin is not signed".to_string()));
+        };
+        let author = db.with_connection(|conn| operations::get_trusted_author(conn, public_key))?;
+        Ok(author
+            .is_none()
+            .then(|| format!("The plugin is signed by an author who is not trusted ({})", public_key)))
+    }
+    
+    /// Copy a plugin into quarantine, replacing an earlier copy of it
+    fn quarantine(&self, source: &Path, manifest: &PluginManifest, reason: &str) -> Result<String> {
+        let dir = self.quarantine_root().join(manifest.install_dir_name());
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).context("Failed to replace quarantined plugin")?;
+        }
+        if let Err(e) = copy_dir_all(source, &dir).and_then(|_| manifest::normalize_manifest(&dir)) {
+            let _ = std::fs::remove_dir_all(&dir);
+            return Err(e);
+        }
+        warn!("Quarantined plugin '{}' until it is approved: {}", manifest.id(), reason);
+        Ok(manifest.id())
+    }
+    
+    /// Plugins waiting for the user's approval
+    pub fn quarantined_plugins(&self) -> Result<Vec<QuarantinedPlugin>> {
+        let mut plugins = Vec::new();
+        for (dir, manifest) in self.quarantined_dirs()? {
+            let public_key = trust::verify_plugin(&dir).ok().flatten();
+            let reason = self
+                .quarantine_reason(&manifest, public_key.as_deref())?
+                .unwrap_or_else(|| "The plugin's author was trusted after it was quarantined".to_string());
+            plugins.push(QuarantinedPlugin {
+                id: manifest.id(),
+                version: manifest.version,
+                author: manifest.author,
+                public_key,
+                reason,
+            });
+        }
+        plugins.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(plugins)
+    }
+    
+    /// Whether a plugin is waiting for approval
+    pub fn is_quarantined(&self, id: &str) -> bool {
+        self.quarantined_dir(id).is_ok()
+    }
+    
+    /// Install a quarantined plugin, and trust its author from now on if
+    /// `trust_author` is set
+    pub async fn approve_quarantined_plugin(&self, id: &str, trust_author: bool) -> Result<String> {
+        let dir = self.quarantined_dir(id)?;
+        let manifest = PluginManifest::load_from_file(&find_manifest(&dir))?;
+        // The files may have changed while the plugin waited
+        let signer = trust::verify_plugin(&dir)
+            .with_context(|| format!("Plugin '{}' has an invalid signature", id))?;
+        if trust_author {
+            let Some(public_key) = &signer else {
+                anyhow::bail!("Plugin '{}' is not signed; there is no author to trust", id);
+            };
+            let name = manifest.author.clone().unwrap_or_else(|| manifest.id());
+            self.trust_author(public_key, &name)?;
+        }
+        info!("Installing quarantined plugin '{}'", id);
+        let installed = self.install_approved(&dir, manifest, signer.as_deref()).await?;
+        std::fs::remove_dir_all(&dir).context("Failed to remove plugin from quarantine")?;
+        Ok(installed)
+    }
+    
+    /// Delete a quarantined plugin without installing it
+    pub fn reject_quarantined_plugin(&self, id: &str) -> Result<()> {
+        let dir = self.quarantined_dir(id)?;
+        std::fs::remove_dir_all(&dir).context("Failed to remove plugin from quarantine")?;
+        info!("Rejected quarantined plugin '{}'", id);
+        Ok(())
+    }
+    
+    /// Quarantined plugin directories and their manifests
+    fn quarantined_dirs(&self) -> Result<Vec<(PathBuf, PluginManifest)>> {
+        let root = self.quarantine_root();
+        if !root.exists() {
+            return Ok(Vec::new());
+        }
+        let mut dirs = Vec::new();
+        for entry in std::fs::read_dir(&root).context("Failed to read quarantine")? {
+            let dir = entry?.path();
+            if let Ok(manifest) = PluginManifest::load_from_file(&find_manifest(&dir)) {
+                dirs.push((dir, manifest));
+            }
+        }
+        Ok(dirs)
+    }
+    
+    /// Directory of a quarantined plugin, by ID or name
+    fn quarantined_dir(&self, id: &str) -> Result<PathBuf> {
+        self.quarantined_dirs()?
+            .into_iter()
+            .find(|(_, manifest)| manifest.id() == id || manifest.name == id)
+            .map(|(dir, _)| dir)
+            .with_context(|| format!("Plugin '{}' is not quarantined", id))
+    }
+    
+    /// Authors whose signed plugins install without quarantine
+    pub fn trusted_authors(&self) -> Result<Vec<TrustedAuthor>> {
+        match &self.database {
+            Some(db) => Ok(db.with_connection(operations::list_trusted_authors)?),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// Add an author's public key (base64 Ed25519) to the trust store
+    pub fn trust_author(&self, public_key: &str, name: &str) -> Result<()> {
+        trust::validate_public_key(public_key)?;
+        let Some(db) = &self.database else {
+            anyhow::bail!("The trust store needs a database");
+        };
+        let author = TrustedAuthor {
+            public_key: public_key.to_string(),
+            name: name.to_string(),
+            added_at: chrono::Utc::now().timestamp(),
+        };
+        db.with_connection(|conn| operations::add_trusted_author(conn, &author))?;
+        info!("Trusting plugins signed by '{}' ({})", name, public_key);
+        Ok(())
+    }
+    
+    /// Remove an author from the trust store; installed plugins stay installed
+    pub fn untrust_author(&self, public_key: &str) -> Result<bool> {
+        match &self.database {
+            Some(db) => Ok(db.with_connection(|conn| operations::delete_trusted_author(conn, public_key))?),
+            None => Ok(false),
+        }
+    }
+    
+    /// Install another version of an installed plugin next to the current one
+    ///
+    /// The canary is called as `id@version` and runs no lifecycle hooks;
+    /// callers that do not name a version keep getting the current one until
+    /// the canary is promoted. It shares the plugin's data directory, config
+    /// and capability decisions. Returns the canary's key.
+    pub async fn install_canary(&self, source: &Path) -> Result<String> {
+        info!("Installing canary from: {:?}", source);
+        
+        let manifest_path = find_manifest(source);
+        if !manifest_path.exists() {
+            anyhow::bail!("plugin.json or plugin.toml not found in: {:?}", source);
+        }
+        
+        let manifest = PluginManifest::load_from_file(&manifest_path)?;
+        Self::check_host_requirements(&manifest)?;
+        let id = manifest.id();
+        let signer = trust::verify_plugin(source)
+            .with_context(|| format!("Plugin '{}' has an invalid signature", id))?;
+        if let Some(reason) = self.quarantine_reason(&manifest, signer.as_deref())? {
+            anyhow::bail!("Canaries of plugin '{}' must come from a trusted author: {}", id, reason);
+        }
+        let key = canary_key(&id, &manifest.version);
+        let current_version = self
+            .plugins
+            .read()
+            .await
+            .get(&id)
+            .map(|plugin| plugin.manifest.version.clone());
+        match current_version {
+            None => anyhow::bail!("Plugin '{}' is not loaded; install it before adding a canary", id),
+            Some(version) if version == manifest.version => {
+                anyhow::bail!("Version {} of plugin '{}' is already the current one", version, id)
+            }
+            Some(_) => {}
+        }
+        self.approve_capabilities(&manifest, signer.as_deref()).await?;
+        
+        // Replace an earlier canary of the same version
+        let dest_dir = self.canary_dir(&manifest);
+        self.plugins.write().await.remove(&key);
+        if dest_dir.exists() {
+            std::fs::remove_dir_all(&dest_dir).context("Failed to remove previous canary")?;
+        }
+        
+        let result = async {
+            copy_dir_all(source, &dest_dir)?;
+            manifest::normalize_manifest(&dest_dir)?;
+            let checksum = integrity::sha256_plugin(&manifest, &dest_dir)?;
+            self.record_checksum(&key, Some(&checksum))?;
+            self.load_plugin_from_manifest(&find_manifest(&dest_dir), &dest_dir).await
+        }
+        .await;
+        if result.is_err() {
+            let _ = std::fs::remove_dir_all(&dest_dir);
+            self.record_checksum(&key, None)?;
+        }
+        result
+    }
+    
+    /// Make a canary the current version of its plugin
+    ///
+    /// The install and enable hooks run as for an upgrade; if either fails,
+    /// the previous version is restored and the canary is gone.
+    pub async fn promote_canary(&self, name: &str) -> Result<String> {
+        let key = self.resolve_id(name).await?;
+        if self.canary(&key).await.is_none() {
+            anyhow::bail!("'{}' is not a canary", key);
+        }
+        let canary = self
+            .plugins
+            .write()
+            .await
+            .remove(&key)
+            .context(format!("Plugin not found: {}", key))?;
+        self.record_checksum(&key, None)?;
+        info!("Promoting {} to the current version", key);
+        
+        let dest_dir = self.plugins_dir.join(canary.manifest.install_dir_name());
+        let backup = self.backup_existing(&dest_dir)?;
+        if let Err(e) = std::fs::rename(&canary.dir, &dest_dir) {
+            if let Some(backup) = backup {
+                std::fs::rename(&backup, &dest_dir)?;
+            }
+            return Err(e).context("Failed to move canary into place");
+        }
+        self.activate_install(&find_manifest(&dest_dir), &dest_dir, backup)
+            .await
+    }
+    
+    /// Unload a canary and delete its files, leaving the current version as it is
+    pub async fn rollback_canary(&self, name: &str) -> Result<String> {
+        let key = self.resolve_id(name).await?;
+        if self.canary(&key).await.is_none() {
+            anyhow::bail!("'{}' is not a canary", key);
+        }
+        let canary = self
+            .plugins
+            .write()
+            .await
+            .remove(&key)
+            .context(format!("Plugin not found: {}", key))?;
+        info!("Rolling back canary {}", key);
+        
+        std::fs::remove_dir_all(&canary.dir)
+            .with_context(|| format!("Failed to remove canary directory {:?}", canary.dir))?;
+        self.record_checksum(&key, None)?;
+        Ok(key)
+    }
+    
+    /// Call [`SETTING_CHANGED_FUNCTION`] of the plugins watching the changed
+    /// setting; failures are logged, not returned, so one plugin cannot keep
+    /// the others from hearing about the change
+    pub async fn dispatch_setting_change(&self, change: &SettingChange) {
+        let watchers: Vec<String> = self
+            .setting_watches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, keys)| keys.contains(&change.key))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if watchers.is_empty() {
+            return;
+        }
+        let input = match serde_json::to_vec(change) {
+            Ok(input) => input,
+            Err(e) => {
+                warn!("Failed to encode change of setting '{}': {}", change.key, e);
+                return;
+            }
+        };
+        
+        for id in watchers {
+            let exported = match self.plugins.read().await.get(&id) {
+                Some(plugin) => plugin.lock().has_function(SETTING_CHANGED_FUNCTION),
+                None => false,
+            };
+            if !exported {
+                continue;
+            }
+            if let Err(e) = self.execute_plugin(&id, SETTING_CHANGED_FUNCTION, &input).await {
+                warn!("Plugin '{}' failed to handle change of setting '{}': {:#}", id, change.key, e);
+            }
+        }
+    }
+    
+    /// Call the plugins subscribed to a host event with it
+    ///
+    /// The plugin whose host call caused the event is left out, so handling
+    /// an event cannot trigger it again. Failures are logged.
+    pub async fn dispatch_event(&self, event: &HostEvent) {
+        let tick = event.payload.get("tick").and_then(|tick| tick.as_u64());
+        let subscribers: Vec<(String, String)> = self
+            .list_plugins()
+            .await
+            .into_iter()
+            .filter(|manifest| event.source.as_deref() != Some(manifest.id().as_str()))
+            .flat_map(|manifest| {
+                let id = manifest.id();
+                manifest
+                    .subscriptions
+                    .into_iter()
+                    .filter(|subscription| {
+                        subscription.event == event.event
+                            && subscription.every.is_none_or(|every| tick.is_some_and(|tick| tick % every == 0))
+                    })
+                    .map(move |subscription| (id.clone(), subscription.function))
+            })
+            .collect();
+        if subscribers.is_empty() {
+            return;
+        }
+        let input = match serde_json::to_vec(event) {
+            Ok(input) => input,
+            Err(e) => {
+                warn!("Failed to encode event '{}': {}", event.event, e);
+                return;
+            }
+        };
+        
+        for (id, function) in subscribers {
+            if let Err(e) = self.execute_plugin(&id, &function, &input).await {
+                warn!("Plugin '{}' failed to handle event '{}': {:#}", id, event.event, e);
+            }
+        }
+    }
+    
+    /// Call the plugins whose `bus_subscriptions` match a bus message, other
+    /// than the one that published it
+    pub async fn dispatch_bus_message(&self, message: &BusMessage) {
+        let subscribers: Vec<(String, String)> = self
+            .list_plugins()
+            .await
+            .into_iter()
+            .filter(|manifest| manifest.id() != message.source)
+            .flat_map(|manifest| {
+                let id = manifest.id();
+                manifest
+                    .bus_subscriptions
+                    .into_iter()
+                    .filter(|subscription| bus::matches(&subscription.topic, &message.topic))
+                    .map(move |subscription| (id.clone(), subscription.function))
+            })
+            .collect();
+        if subscribers.is_empty() {
+            return;
+        }
+        let input = match serde_json::to_vec(message) {
+            Ok(input) => input,
+            Err(e) => {
+                warn!("Failed to encode bus message on '{}': {}", message.topic, e);
+                return;
+            }
+        };
+        
+        for (id, function) in subscribers {
+            if let Err(e) = self.execute_plugin(&id, &function, &input).await {
+                warn!("Plugin '{}' failed to handle bus message on '{}': {:#}", id, message.topic, e);
+            }
+        }
+    }
+    
+    /// Execute a plugin function
+    pub async fn execute_plugin(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: &[u8],
+    ) -> Result<Vec<u8>> {
+        self.execute_plugin_with_context(plugin_name, function, input, &ExecutionContext::new())
+            .await
+    }
+    
+    /// Execute a plugin function with a caller-provided execution context
+    ///
+    /// `function` may be an entry point's alias, or empty for the plugin's
+    /// default entry point.
+    pub async fn execute_plugin_with_context(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: &[u8],
+        context: &ExecutionContext,
+    ) -> Result<Vec<u8>> {
+        self.executor()
+            .execute_plugin_with_context(plugin_name, function, input, context)
+            .await
+    }
+    
+    /// A handle that runs calls to the loaded plugins, for callers that
+    /// share the manager behind a lock and should not hold it for the call
+    pub fn executor(&self) -> PluginExecutor {
+        PluginExecutor {
+            plugins: self.plugins.clone(),
+            database: self.database.clone(),
+            metrics: self.metrics.clone(),
+            execution_pool: self.execution_pool.clone(),
+            running: self.running.clone(),
+            traces: self.traces.clone(),
+        }
+    }
+    
+    /// Host function calls made during an execution, from memory or, for
+    /// older ones, the database if they were stored
+    pub fn execution_trace(&self, execution_id: &str) -> Result<Option<ExecutionTrace>> {
+        if let Some(trace) = self.traces.get(execution_id) {
+            return Ok(Some(trace));
+        }
+        match &self.database {
+            Some(database) => Ok(database.with_connection(|conn| operations::get_execution_trace(conn, execution_id))?),
+            None => Ok(None),
+        }
+    }
+    
+    /// Deprecation warnings for a call to a plugin function; see
+    /// [`PluginManifest::deprecation_warnings`]
+    pub async fn deprecation_warnings(&self, plugin_name: &str, function: &str) -> Vec<String> {
+        let plugins = self.plugins.read().await;
+        match resolve_plugin_id(&plugins, plugin_name) {
+            Ok(id) => {
+                let manifest = &plugins[&id].manifest;
+                manifest
+                    .resolve_function(function)
+                    .map_or_else(Vec::new, |function| manifest.deprecation_warnings(function))
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+    
+    /// Interrupt an in-flight execution
+    ///
+    /// Returns false if no call with this execution ID is currently running.
+    pub fn cancel_execution(&self, execution_id: &str) -> bool {
+        match self.running.lock().unwrap().get(execution_id) {
+            Some((handle, context)) => {
+                context.cancel();
+                handle.cancel().is_ok()
+            }
+            None => false,
+        }
+    }
+    
+    /// Replace the set of trusted plugins; applies to plugins loaded afterwards
+    pub fn set_trusted_plugins(&self, names: impl IntoIterator<Item = String>) {
+        *self.trusted.write().unwrap() = names.into_iter().collect();
+    }
+    
+    /// Whether a plugin may enable privileged features
+    pub fn is_trusted(&self, name: &str) -> bool {
+        self.trusted.read().unwrap().contains(name)
+    }
+    
+    /// Whether a plugin signed by `signer` skips quarantine and capability
+    /// prompts: it must be trusted and signed by the key recorded when it
+    /// was first installed, so a package that only claims its ID does not
+    fn is_trusted_signer(&self, name: &str, signer: Option<&str>) -> Result<bool> {
+        let (Some(db), Some(signer)) = (&self.database, signer) else {
+            return Ok(false);
+        };
+        if !self.is_trusted(name) {
+            return Ok(false);
+        }
+        let recorded = db.with_connection(|conn| operations::get_plugin_signer(conn, name))?;
+        Ok(recorded.as_deref() == Some(signer))
+    }
+    
+    /// Replace the sandboxes the user moved plugins to, by plugin ID; applies
+    /// to plugins loaded afterwards
+    pub fn set_sandbox_overrides(&self, overrides: impl IntoIterator<Item = (String, SandboxProfile)>) {
+        *self.sandbox_overrides.write().unwrap() = overrides.into_iter().collect();
+    }
+    
+    /// Cap the sandbox of every plugin; applies to plugins loaded afterwards
+    pub fn set_max_sandbox(&self, profile: SandboxProfile) {
+        *self.max_sandbox.write().unwrap() = profile;
+    }
+    
+    /// Sandbox a plugin runs in: the one its manifest asks for, lowered to
+    /// the user's choice and the host's cap. Only trusted plugins get `trusted`.
+    pub fn sandbox_profile(&self, manifest: &PluginManifest) -> SandboxProfile {
+        let id = manifest.id();
+        let mut profile = manifest.sandbox.unwrap_or_default().min(*self.max_sandbox.read().unwrap());
+        if let Some(chosen) = self.sandbox_overrides.read().unwrap().get(&id) {
+            profile = profile.min(*chosen);
+        }
+        if profile == SandboxProfile::Trusted && !self.is_trusted(&id) {
+            profile = SandboxProfile::Standard;
+        }
+        profile
+    }
+    
+    /// Replace the set of disabled plugins; applies to the next discovery
+    pub fn set_disabled_plugins(&self, names: impl IntoIterator<Item = String>) {
+        *self.disabled.write().unwrap() = names.into_iter().collect();
+    }
+    
+    /// Add a plugin to the disabled plugins, stored under
+    /// [`DISABLED_PLUGINS_KEY`] so discovery and restarts skip it too;
+    /// installing it again enables it
+    fn disable_until_reinstalled(&self, id: &str) -> Result<()> {
+        self.disabled.write().unwrap().insert(id.to_string());
+        if let Some(db) = &self.database {
+            SettingsStore::new(db.clone()).update(DISABLED_PLUGINS_KEY, |disabled: &mut Vec<String>| {
+                if !disabled.iter().any(|disabled| disabled == id) {
+                    disabled.push(id.to_string());
+                    disabled.sort();
+                }
+            })?;
+        }
+        Ok(())
+    }
+    
+    /// Set what builds the host functions linked into every plugin next to
+    /// the built-in ones; applies to plugins loaded afterwards
+    pub fn set_host_functions(&self, factory: HostFunctionFactory) {
+        *self.host_functions.write().unwrap() = Some(factory);
+    }
+    
+    /// The embedder's host functions for a plugin
+    fn extra_host_functions(&self, plugin_id: &str) -> Vec<Function> {
+        self.host_functions
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or_else(Vec::new, |factory| factory(plugin_id))
+    }
+    
+    /// Host function state for `plugin_name` with none of its manifest's
+    /// settings applied
+    fn host_function_state(&self, plugin_name: &str, db: &Arc<Database>, trash: &Arc<TrashBin>) -> HostFunctionState {
+        HostFunctionState {
+            plugin_name: plugin_name.to_string(),
+            database: db.clone(),
+            logs: self.logs.clone(),
+            dependencies: Vec::new(),
+            plugins: self.plugins.clone(),
+            metrics: self.metrics.clone(),
+            trash: trash.clone(),
+            setting_watches: self.setting_watches.clone(),
+            denied_hosts: Vec::new(),
+            config: HashMap::new(),
+            bus: self.bus.clone(),
+            max_sleep_ms: sleep::DEFAULT_MAX_SLEEP_MS,
+            clipboard: self.clipboard.read().unwrap().clone(),
+            mailer: self.mailer.read().unwrap().clone().unwrap_or_else(|| {
+                Arc::new(SmtpMailer::new(db.clone(), self.secrets.read().unwrap().clone()))
+            }),
+            db_functions: Vec::new(),
+            db_capabilities: Vec::new(),
+            own_tables: false,
+            host_api_level: None,
+            mounts: Vec::new(),
+            allowed_commands: Vec::new(),
+            app_version: self.app_version().to_string(),
+        }
+    }
+    
+    /// Every host function a plugin could be linked to, built-in or the
+    /// embedder's, with its signature and the capability it needs
+    pub fn host_functions(&self) -> Vec<HostFunctionInfo> {
+        let mut functions = match (&self.database, &self.trash) {
+            (Some(db), Some(trash)) => crate::host_functions::all_host_functions(self.host_function_state("", db, trash)),
+            _ => http::http_functions("", Vec::new(), None),
+        };
+        functions.extend(self.extra_host_functions(""));
+        introspection::describe(&functions)
+    }
+    
+    /// Set where `secret://` config values are looked up, e.g. an OS
+    /// keychain; applies to plugins loaded afterwards
+    pub fn set_secret_store(&self, store: Arc<dyn SecretStore>) {
+        *self.secrets.write().unwrap() = store;
+    }
+    
+    /// Set the clipboard plugins with the `clipboard` capability read and
+    /// write; applies to plugins loaded afterwards
+    pub fn set_clipboard(&self, clipboard: Arc<dyn Clipboard>) {
+        *self.clipboard.write().unwrap() = Some(clipboard);
+    }
+    
+    /// Set how plugins with the `email` capability send email instead of the
+    /// SMTP server in settings; applies to plugins loaded afterwards
+    pub fn set_mailer(&self, mailer: Arc<dyn Mailer>) {
+        *self.mailer.write().unwrap() = Some(mailer);
+    }
+    
+    /// Names of the secrets plugin config can refer to; never their values
+    pub fn secret_names(&self) -> Result<Vec<String>> {
+        self.secrets.read().unwrap().names()
+    }
+    
+    /// Create or replace a secret and reload the plugins whose config refers to it
+    ///
+    /// Returns the IDs of the plugins reloaded. Plugins that failed to load
+    /// because the secret was missing are loaded by the next discovery.
+    pub async fn set_secret(&self, name: &str, value: &str) -> Result<Vec<String>> {
+        let store = self.secrets.read().unwrap().clone();
+        store.set(name, value)?;
+        info!("Set secret '{}'", name);
+        
+        let mut reloaded = Vec::new();
+        for manifest in self.list_plugins().await {
+            let id = manifest.id();
+            let config = self.get_plugin_config(&id).await?;
+            if config.effective.values().any(|value| secrets::reference(value) == Some(name)) {
+                self.reload_plugin(&id).await?;
+                reloaded.push(id);
+            }
+        }
+        Ok(reloaded)
+    }
+    
+    /// Remove a secret; plugins already loaded keep its value until they are reloaded
+    pub fn delete_secret(&self, name: &str) -> Result<bool> {
+        self.secrets.read().unwrap().delete(name)
+    }
+    
+    /// Set the version of the embedding application
+    pub fn set_app_version(&self, version: Version) {
+        *self.app_version.write().unwrap() = version;
+    }
+    
+    /// Version plugins' `min_app_version` is checked against
+    pub fn app_version(&self) -> Version {
+        self.app_version.read().unwrap().clone()
+    }
+    
+    /// Whether a plugin is installed but kept unloaded
+    pub fn is_disabled(&self, name: &str) -> bool {
+        self.disabled.read().unwrap().contains(name)
+    }
+    
+    /// IDs of the installed plugins, loaded or not; canaries are not listed
+    pub fn installed_plugin_ids(&self) -> Result<Vec<String>> {
+        Ok(self
+            .installed_dirs()?
+            .iter()
+            .filter_map(|dir| {
+                let manifest = PluginManifest::load_from_file(&find_manifest(dir)).ok()?;
+                (self.registry_key(&manifest, dir) == manifest.id()).then(|| manifest.id())
+            })
+            .collect())
+    }
+    
+    /// Disable exactly the plugins in `disabled`: unload those that are
+    /// loaded and load every other installed plugin that is not
+    ///
+    /// Unloading runs no hooks; calls already running finish on the old
+    /// instance. Plugins that fail to load are reported and stay unloaded.
+    pub async fn apply_disabled_plugins(&self, disabled: impl IntoIterator<Item = String>) -> Result<PluginSetChange> {
+        self.set_disabled_plugins(disabled);
+        let mut change = PluginSetChange::default();
+        
+        {
+            let mut plugins = self.plugins.write().await;
+            let unload: Vec<String> = plugins
+                .iter()
+                .filter(|(_, plugin)| self.is_disabled(&plugin.manifest.id()))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for id in unload {
+                plugins.remove(&id);
+                info!("Disabled plugin '{}'", id);
+                change.disabled.push(id);
+            }
+        }
+        
+        for dir in self.installed_dirs()? {
+            let manifest_path = find_manifest(&dir);
+            let Ok(manifest) = PluginManifest::load_from_file(&manifest_path) else {
+                continue;
+            };
+            let key = self.registry_key(&manifest, &dir);
+            if self.is_disabled(&manifest.id()) || self.plugins.read().await.contains_key(&key) {
+                continue;
+            }
+            match self.load_and_enable(&manifest_path, &dir).await {
+                Ok(id) => {
+                    info!("Enabled plugin '{}'", id);
+                    change.enabled.push(id);
+                }
+                Err(e) => {
+                    warn!("Failed to enable plugin from {:?}: {:#}", dir, e);
+                    change.failed.push(PluginLoadFailure {
+                        dir,
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+        
+        Ok(change)
+    }
+    
+    /// Set the maximum number of concurrent plugin executions
+    pub fn set_execution_workers(&self, count: usize) {
+        self.execution_pool.resize(count);
+    }
+    
+    /// Get execution metrics for one plugin, or all plugins when `name` is None
+    pub async fn get_metrics(&self, name: Option<&str>) -> Vec<PluginMetricsSnapshot> {
+        let id = match name {
+            Some(name) => Some(self.resolve_id(name).await.unwrap_or_else(|_| name.to_string())),
+            None => None,
+        };
+        let metrics = self.metrics.read().await;
+        match id {
+            Some(id) => metrics.snapshot(&id).into_iter().collect(),
+            None => metrics.snapshot_all(),
+        }
+    }
+    
+    /// Get recent log entries emitted by a plugin
+    pub async fn get_logs(&self, name: &str, limit: Option<usize>) -> Vec<PluginLogEntry> {
+        let id = self.resolve_id(name).await.unwrap_or_else(|_| name.to_string());
+        self.logs.get(&id, limit)
+    }
+    
+    /// List all loaded plugins, at their current versions
+    pub async fn list_plugins(&self) -> Vec<PluginManifest> {
+        let plugins = self.plugins.read().await;
+        plugins
+            .iter()
+            .filter(|(key, plugin)| **key == plugin.manifest.id())
+            .map(|(_, plugin)| plugin.manifest.clone())
+            .collect()
+    }
+    
+    /// Installed plugins that are not loaded because they need a newer
+    /// plugin host or another platform, with the reasons; disabled plugins
+    /// are left out
+    pub async fn unsupported_plugins(&self) -> Result<Vec<(PluginManifest, Vec<String>)>> {
+        let loaded = self.plugins.read().await;
+        let mut unsupported = Vec::new();
+        for dir in self.installed_dirs()? {
+            let Ok(manifest) = PluginManifest::load_from_file(&find_manifest(&dir)) else {
+                continue;
+            };
+            if loaded.contains_key(&manifest.id()) || self.is_disabled(&manifest.id()) {
+                continue;
+            }
+            let issues = compatibility::host_requirements(&manifest);
+            if !issues.is_empty() {
+                unsupported.push((manifest, issues));
+            }
+        }
+        Ok(unsupported)
+    }
+    
+    /// List the loaded canaries, sorted by key
+    pub async fn list_canaries(&self) -> Vec<PluginCanary> {
+        let plugins = self.plugins.read().await;
+        let mut canaries: Vec<PluginCanary> = plugins
+            .iter()
+            .filter(|(key, plugin)| **key != plugin.manifest.id())
+            .map(|(key, plugin)| PluginCanary {
+                key: key.clone(),
+                plugin: plugin.manifest.id(),
+                version: plugin.manifest.version.clone(),
+                current_version: plugins
+                    .get(&plugin.manifest.id())
+                    .map(|current| current.manifest.version.clone()),
+            })
+            .collect();
+        canaries.sort_by(|a, b| a.key.cmp(&b.key));
+        canaries
+    }
+    
+    /// The canary registered under `key`, if it is one
+    async fn canary(&self, key: &str) -> Option<PluginCanary> {
+        self.list_canaries().await.into_iter().find(|canary| canary.key == key)
+    }
+    
+    /// Get a specific plugin
+    pub async fn get_plugin(&self, name: &str) -> Option<PluginManifest> {
+        let plugins = self.plugins.read().await;
+        let id = resolve_plugin_id(&plugins, name).ok()?;
+        Some(plugins[&id].manifest.clone())
+    }
+    
+    /// Input and output formats of a call to `function` of a plugin, so
+    /// callers holding JSON can encode its input and decode its output;
+    /// JSON for functions no entry point declares
+    pub async fn payload_formats(&self, name: &str, function: &str) -> (PayloadFormat, PayloadFormat) {
+        let plugins = self.plugins.read().await;
+        resolve_plugin_id(&plugins, name)
+            .ok()
+            .and_then(|id| {
+                let manifest = &plugins[&id].manifest;
+                manifest.payload_formats(manifest.resolve_function(function)?)
+            })
+            .unwrap_or((PayloadFormat::Json, PayloadFormat::Json))
+    }
+    
+    /// Dependency graph of the loaded plugins
+    pub async fn dependency_graph(&self) -> DependencyGraph {
+        graph::build(&self.list_plugins().await)
+    }
+    
+    /// Directory a plugin was loaded from
+    pub async fn get_plugin_dir(&self, name: &str) -> Option<PathBuf> {
+        let plugins = self.plugins.read().await;
+        let id = resolve_plugin_id(&plugins, name).ok()?;
+        Some(plugins[&id].dir.clone())
+    }
+    
+    /// Directory holding a plugin's bundled UI assets
+    pub async fn get_ui_assets_dir(&self, name: &str) -> Option<PathBuf> {
+        let plugins = self.plugins.read().await;
+        let id = resolve_plugin_id(&plugins, name).ok()?;
+        let plugin = &plugins[&id];
+        Some(plugin.dir.join(&plugin.manifest.ui.assets_dir))
+    }
+    
+    /// Refuse a plugin built for a newer plugin host, saying what it needs
+    fn check_host_requirements(manifest: &PluginManifest) -> Result<()> {
+        let issues = compatibility::host_requirements(manifest);
+        if !issues.is_empty() {
+            anyhow::bail!("Plugin '{}' cannot run on this host: {}", manifest.id(), issues.join("; "));
+        }
+        Ok(())
+    }
+    
+    /// Check entry points that name a module are exported by it
+    fn check_entry_point_modules(manifest: &PluginManifest, plugin_dir: &Path) -> Result<()> {
+        let mut exports: HashMap<&str, Vec<String>> = HashMap::new();
+        for entry_point in &manifest.entry_points {
+            let Some(module) = entry_point.module.as_deref() else {
+                continue;
+            };
+            // The main module is checked by the loader
+            if !exports.contains_key(module) {
+                let path = manifest
+                    .wasm_module
+                    .module_path(module)
+                    .with_context(|| format!("Unknown WASM module '{}'", module))?;
+                let bytes = std::fs::read(plugin_dir.join(path))
+                    .with_context(|| format!("Failed to read WASM module {}", path))?;
+                exports.insert(module, PluginLoader::wasm_exports(&bytes));
+            }
+            if !exports[module].contains(&entry_point.function) {
+                anyhow::bail!(
+                    "Entry point '{}': WASM module '{}' does not export '{}'",
+                    entry_point.name,
+                    module,
+                    entry_point.function
+                );
+            }
+        }
+        Ok(())
+    }
+    
+    /// Install a plugin from a URL (WASM file or manifest URL)
+    ///
+    /// A plugin installed from the same URL before is only downloaded again
+    /// when the server reports that one of its files changed.
+    pub async fn install_plugin_from_url(&self, url: &str) -> Result<RemoteInstall> {
+        info!("Installing plugin from URL: {}", url);
+        
+        if let Some(db) = &self.database {
+            let installed = db.with_connection(|conn| operations::get_plugin_by_source_url(conn, url))?;
+            if let Some(plugin) = installed {
+                let sources = db.with_connection(|conn| operations::get_plugin_sources(conn, &plugin))?;
+                if self.get_plugin(&plugin).await.is_some() && !sources_changed(&sources).await? {
+                    info!("Plugin '{}' from {} is up to date", plugin, url);
+                    return Ok(RemoteInstall { plugin, updated: false });
+                }
+            }
+        }
+        
+        let staging = staging_dir();
+        let result = match download_plugin(url, &staging).await {
+            Ok(downloads) => self.install_plugin(&staging).await.map(|plugin| (plugin, downloads)),
+            Err(e) => Err(e),
+        };
+        let _ = std::fs::remove_dir_all(&staging);
+        
+        let (plugin, downloads) = result?;
+        if let Some(db) = &self.database {
+            let now = chrono::Utc::now().timestamp();
+            let sources: Vec<PluginSource> = downloads
+                .into_iter()
+                .map(|download| PluginSource {
+                    plugin: plugin.clone(),
+                    url: download.url,
+                    source_url: url.to_string(),
+                    etag: download.etag,
+                    last_modified: download.last_modified,
+                    fetched_at: now,
+                })
+                .collect();
+            db.with_connection(|conn| operations::replace_plugin_sources(conn, &plugin, &sources))?;
+        }
+        info!("✅ Plugin installed successfully from URL");
+        Ok(RemoteInstall { plugin, updated: true })
+    }
+    
+    /// Ask the servers of plugins installed from a URL whether their files changed
+    pub async fn check_plugin_updates(&self) -> Result<Vec<PluginUpdate>> {
+        let Some(db) = &self.database else {
+            return Ok(Vec::new());
+        };
+        let mut by_plugin: HashMap<String, Vec<PluginSource>> = HashMap::new();
+        for source in db.with_connection(operations::list_plugin_sources)? {
+            by_plugin.entry(source.plugin.clone()).or_default().push(source);
+        }
+        
+        let mut updates = Vec::new();
+        for (plugin, sources) in by_plugin {
+            if self.get_plugin(&plugin).await.is_none() {
+                continue;
+            }
+            let source_url = sources[0].source_url.clone();
+            let (update_available, error) = match sources_changed(&sources).await {
+                Ok(changed) => (changed, None),
+                Err(e) => (false, Some(format!("{:#}", e))),
+            };
+            updates.push(PluginUpdate { plugin, source_url, update_available, error });
+        }
+        updates.sort_by(|a, b| a.plugin.cmp(&b.plugin));
+        Ok(updates)
+    }
+    
+    /// Run every install check on a plugin directory or URL without installing it
+    ///
+    /// URLs are downloaded to a temporary directory, never into the plugins directory.
+    pub async fn validate_plugin(&self, source: &str) -> ValidationReport {
+        let installed = self.list_plugins().await;
+        let is_trusted = |id: &str| self.is_trusted(id);
+        
+        if !(source.starts_with("http://") || source.starts_with("https://")) {
+            return validation::validate_dir(source, Path::new(source), &installed, is_trusted);
+        }
+        
+        let staging = staging_dir();
+        let report = match download_plugin(source, &staging).await {
+            Ok(_) => validation::validate_dir(source, &staging, &installed, is_trusted),
+            Err(e) => ValidationReport::failed(source, "download", format!("{:#}", e)),
+        };
+        let _ = std::fs::remove_dir_all(&staging);
+        report
+    }
+}
+
+/// Outcome of installing a plugin from a URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteInstall {
+    pub plugin: String,
+    /// False when the installed copy was already up to date and nothing was downloaded
+    pub updated: bool,
+}
+
+/// Whether a plugin installed from a URL has a newer version there
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginUpdate {
+    pub plugin: String,
+    pub source_url: String,
+    pub update_available: bool,
+    /// Why the check failed, if it did
+    pub error: Option<String>,
+}
+
+/// A downloaded URL and the cache validators its server sent
+struct Download {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Response to a possibly conditional GET
+enum Fetched {
+    NotModified,
+    Content(Vec<u8>, Download),
+}
+
+/// GET `url`, conditional on the validators of `cached` if given
+async fn fetch(url: &str, cached: Option<&PluginSource>) -> Result<Fetched> {
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let response = response
+        .error_for_status()
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let download = Download {
+        url: url.to_string(),
+        etag: header(reqwest::header::ETAG),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+    };
+    let content = response
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+    Ok(Fetched::Content(content.to_vec(), download))
+}
+
+/// GET `url` unconditionally
+async fn fetch_content(url: &str) -> Result<(Vec<u8>, Download)> {
+    match fetch(url, None).await? {
+        Fetched::Content(content, download) => Ok((content, download)),
+        Fetched::NotModified => anyhow::bail!("Unexpected 304 Not Modified from {}", url),
+    }
+}
+
+/// Whether any recorded source of a plugin changed since it was downloaded;
+/// a source its server sent no validators for always counts as changed
+async fn sources_changed(sources: &[PluginSource]) -> Result<bool> {
+    if sources.is_empty() {
+        return Ok(true);
+    }
+    for source in sources {
+        if source.etag.is_none() && source.last_modified.is_none() {
+            return Ok(true);
+        }
+        if let Fetched::Content(..) = fetch(&source.url, Some(source)).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// A fresh temporary directory to download a plugin into
+fn staging_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("a2e-plugin-{}", uuid::Uuid::new_v4()))
+}
+
+/// Download a plugin (a WASM file or a manifest URL) into `dir`, returning
+/// the manifest and module downloads
+async fn download_plugin(url: &str, dir: &Path) -> Result<Vec<Download>> {
+    let (content, download) = fetch_content(url).await.context("Failed to fetch plugin from URL")?;
+    let mut downloads = vec![download];
+    
+    std::fs::create_dir_all(dir)?;
+    let manifest_path = dir.join("plugin.json");
+    
+    // Determine if it's a WASM file or manifest
+    if url.ends_with(".wasm") {
+        // For WASM files, create a minimal manifest
+        let plugin_name = url
+            .rsplit('/')
+            .next()
+            .unwrap_or("remote-plugin")
+            .trim_end_matches(".wasm");
+        
+        // Save the WASM file
+        std::fs::write(dir.join("plugin.wasm"), &content)?;
+        
+        // Extract exported functions from WASM
+        let exported_functions = PluginLoader::wasm_exports(&content);
+        // A module with one function needs no function name to be called
+        let single_export = exported_functions.len() == 1;
+        let entry_points: Vec<EntryPoint> = exported_functions
+            .into_iter()
+            .map(|func_name| EntryPoint {
+                name: func_name.clone(),
+                function: func_name.clone(),
+                aliases: Vec::new(),
+                default: single_export,
+                description: format!("Exported function: {}", func_name),
+                input_format: "json".to_string(),
+                output_format: "json".to_string(),
+                module: None,
+                input_schema: None,
+                output_schema: None,
+                deprecated: false,
+                replacement: None,
+            })
+            .collect();
+        
+        // Create a basic manifest
+        let manifest = PluginManifest {
+            name: plugin_name.to_string(),
+            namespace: None,
+            version: "0.1.0".to_string(),
+            description: format!("Plugin loaded from {}", url),
+            author: Some("Remote".to_string()),
+            license: None,
+            plugin_type: "remote".to_string(),
+            categories: Vec::new(),
+            tags: Vec::new(),
+            wasm_module: "plugin.wasm".into(),
+            wasm_config: Default::default(),
+            quotas: Default::default(),
+            sandbox: None,
+            capabilities: vec![],
+            entry_points,
+            dependencies: Default::default(),
+            env: Default::default(),
+            hooks: Default::default(),
+            ui: Default::default(),
+            schedules: Vec::new(),
+            subscriptions: Vec::new(),
+            bus_subscriptions: Vec::new(),
+            assets: Default::default(),
+            migrations: Vec::new(),
+            required_host_functions: Vec::new(),
+            platforms: Vec::new(),
+            min_app_version: None,
+            min_host_version: None,
+            host_api_level: None,
+            homepage: None,
+            i18n: Default::default(),
+            deprecated: false,
+            replacement: None,
+            signature: None,
+        };
+        
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+        return Ok(downloads);
+    }
+    
+    // Assume it's a manifest JSON
+    let manifest = PluginManifest::from_slice(&content)?;
+    std::fs::write(&manifest_path, &content)?;
+    
+    // Download modules referenced by remote URL and point the manifest at the local files
+    let mut local_manifest = manifest.clone();
+    let mut downloaded = false;
+    for module_path in local_manifest.wasm_module.paths_mut() {
+        if !(module_path.starts_with("http://") || module_path.starts_with("https://")) {
+            continue;
+        }
+        let wasm_url = module_path.clone();
+        let (wasm_content, download) = fetch_content(&wasm_url).await.context("Failed to fetch WASM module")?;
+        downloads.push(download);
+        
+        // Save with a local filename
+        let wasm_filename = wasm_url
+            .rsplit('/')
+            .next()
+            .unwrap_or("plugin.wasm");
+        std::fs::write(dir.join(wasm_filename), wasm_content)?;
+        
+        *module_path = wasm_filename.to_string();
+        downloaded = true;
+    }
+    if downloaded {
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&local_manifest)?)?;
+    }
+    
+    // Download the icon and screenshots, which are relative to the manifest URL
+    manifest.validate()?;
+    let base_url = reqwest::Url::parse(url).context("Invalid plugin URL")?;
+    for asset in manifest.assets.paths() {
+        let asset_url = base_url.join(asset).context("Invalid asset path")?;
+        let asset_content = reqwest::get(asset_url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch asset {}", asset))?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to download asset {}", asset))?;
+        
+        let asset_path = dir.join(asset);
+        if let Some(parent) = asset_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&asset_path, asset_content)?;
+    }
+    
+    Ok(downloads)
+}
+
+/// Recursively copy a directory
+fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
+    std::fs::create_dir_all(dst)?;
+    
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        
+        // Skip .git directories
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        
+        if ty.is_dir() {
+            copy_dir_all(&src_path, &dst_path)?;
+        } else {
+            std::fs::copy(&src_path, &dst_path)?;
+        }
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,1389 @@
+//! Plugin manifest definition
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use anyhow::{Context, Result};
+
+use super::capabilities::Capability;
+use super::payload::PayloadFormat;
+use super::sandbox::SandboxProfile;
+use super::trust::ManifestSignature;
+
+/// Plugin manifest describing a WASM plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Plugin name
+    pub name: String,
+    
+    /// Publisher namespace; the plugin's ID is `namespace/name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    
+    /// Plugin version
+    pub version: String,
+    
+    /// Human-readable description
+    pub description: String,
+    
+    /// Plugin author
+    pub author: Option<String>,
+    
+    /// SPDX license expression, e.g. `MIT` or `Apache-2.0 OR MIT`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    
+    /// Plugin type (service, converter, processor, ui)
+    pub plugin_type: String,
+    
+    /// Catalog sections the plugin is listed under, e.g. `images`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    
+    /// Keywords search matches the plugin by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    
+    /// Path to the WASM module (relative to manifest), or a list of modules
+    /// Extism links together
+    pub wasm_module: WasmModules,
+    
+    /// WASM runtime configuration
+    #[serde(default)]
+    pub wasm_config: WasmConfig,
+    
+    /// Limits on the resources a call may use
+    #[serde(default)]
+    pub quotas: ResourceQuotas,
+    
+    /// Sandbox the plugin asks to run in; `standard` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxProfile>,
+    
+    /// Plugin capabilities
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    
+    /// Entry points (exported functions)
+    #[serde(default)]
+    pub entry_points: Vec<EntryPoint>,
+    
+    /// Plugins this one calls, by ID or name, with the semver range of
+    /// versions it works with, e.g. `^1.2` or `>=1.0, <3`
+    #[serde(default)]
+    pub dependencies: HashMap<String, String>,
+    
+    /// Config values the host fills in when the plugin loads; values may
+    /// contain `${<setting key>}` placeholders (see [`crate::templates`])
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    
+    /// Functions invoked at lifecycle events
+    #[serde(default)]
+    pub hooks: LifecycleHooks,
+    
+    /// Bundled UI panels
+    #[serde(default)]
+    pub ui: UiConfig,
+    
+    /// Functions the host runs on a schedule
+    #[serde(default)]
+    pub schedules: Vec<ScheduleSpec>,
+    
+    /// Host events the plugin is called with, e.g. ticks or new users
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subscriptions: Vec<EventSubscription>,
+    
+    /// Message bus topics the plugin is called with messages on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bus_subscriptions: Vec<BusSubscription>,
+    
+    /// Icon and screenshots shown in the plugin catalog
+    #[serde(default)]
+    pub assets: PluginAssets,
+    
+    /// SQL files (relative to the manifest) creating and changing the
+    /// plugin's own tables, in order; the host runs those not yet applied
+    /// when the plugin is installed or enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub migrations: Vec<String>,
+    
+    /// Oldest app version (semver) the plugin works with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_app_version: Option<String>,
+    
+    /// Oldest version (semver) of the plugin runtime the plugin works with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_host_version: Option<String>,
+    
+    /// Host function interface level the plugin was built against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_api_level: Option<u32>,
+    
+    /// Host functions the plugin cannot run without; loading fails naming
+    /// any that are not registered, as it does for those its modules import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_host_functions: Vec<String>,
+    
+    /// Platforms the plugin runs on, e.g. `windows` or `linux-aarch64`; all
+    /// of them if empty. Discovery skips it elsewhere.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
+    
+    /// Page where newer versions of the plugin are published, e.g. its registry page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    
+    /// Translated metadata by locale (a BCP 47 tag such as `de` or `pt-BR`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub i18n: HashMap<String, LocalizedMetadata>,
+    
+    /// The plugin still works but should no longer be used; calls to it
+    /// come back with a deprecation warning
+    #[serde(default)]
+    pub deprecated: bool,
+    
+    /// ID of the plugin that replaces this deprecated one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    
+    /// The author's signature over the rest of the manifest and the modules;
+    /// written by [`super::sign_plugin`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ManifestSignature>,
+}
+
+/// A plugin's metadata in one locale; anything left out falls back to the manifest's own
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalizedMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Entry point descriptions by entry point name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub entry_points: HashMap<String, String>,
+}
+
+/// Extism's name for the module whose exports are called
+pub const MAIN_MODULE: &str = "main";
+
+/// The WASM module(s) of a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WasmModules {
+    Single(String),
+    /// Modules are linked by name. The main module is the one named `main`,
+    /// or else the last one, which may then be unnamed.
+    Multiple(Vec<WasmModule>),
+}
+
+/// One module of a multi-module plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmModule {
+    /// Name other modules import it by
+    pub name: Option<String>,
+    
+    /// Path to the module (relative to manifest)
+    pub path: String,
+}
+
+impl WasmModules {
+    /// All module paths
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            WasmModules::Single(path) => vec![path.as_str()],
+            WasmModules::Multiple(modules) => modules.iter().map(|m| m.path.as_str()).collect(),
+        }
+    }
+    
+    /// Mutable access to every module path
+    pub fn paths_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            WasmModules::Single(path) => vec![path],
+            WasmModules::Multiple(modules) => modules.iter_mut().map(|m| &mut m.path).collect(),
+        }
+    }
+    
+    /// Path of the main module
+    pub fn main_path(&self) -> &str {
+        match self {
+            WasmModules::Single(path) => path,
+            WasmModules::Multiple(modules) => modules
+                .iter()
+                .find(|m| m.name.as_deref() == Some(MAIN_MODULE))
+                .or(modules.last())
+                .map_or("", |m| m.path.as_str()),
+        }
+    }
+    
+    /// Path of the module with the given name
+    pub fn module_path(&self, name: &str) -> Option<&str> {
+        match self {
+            WasmModules::Single(path) => (name == MAIN_MODULE).then_some(path.as_str()),
+            WasmModules::Multiple(_) if name == MAIN_MODULE => Some(self.main_path()),
+            WasmModules::Multiple(modules) => modules
+                .iter()
+                .find(|m| m.name.as_deref() == Some(name))
+                .map(|m| m.path.as_str()),
+        }
+    }
+    
+    fn validate(&self) -> Result<()> {
+        let WasmModules::Multiple(modules) = self else {
+            if self.main_path().is_empty() {
+                anyhow::bail!("WASM module path cannot be empty");
+            }
+            return Ok(());
+        };
+        
+        if modules.is_empty() {
+            anyhow::bail!("At least one WASM module is required");
+        }
+        let mut names = std::collections::HashSet::new();
+        for (i, module) in modules.iter().enumerate() {
+            if module.path.is_empty() {
+                anyhow::bail!("WASM module path cannot be empty");
+            }
+            match &module.name {
+                Some(name) if !names.insert(name.as_str()) => {
+                    anyhow::bail!("Duplicate WASM module name: {}", name)
+                }
+                Some(_) => {}
+                None if i + 1 < modules.len() => {
+                    anyhow::bail!("Every WASM module except the last needs a name: {}", module.path)
+                }
+                None if names.contains(MAIN_MODULE) => {
+                    anyhow::bail!("Unnamed module {} conflicts with the module named 'main'", module.path)
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for WasmModules {
+    fn from(path: &str) -> Self {
+        WasmModules::Single(path.to_string())
+    }
+}
+
+/// Image files shipped with a plugin, relative to the manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginAssets {
+    pub icon: Option<String>,
+    
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+}
+
+/// Image types accepted for icons and screenshots
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg"];
+
+/// Largest accepted icon
+const MAX_ICON_BYTES: u64 = 1024 * 1024;
+
+/// Largest accepted screenshot
+const MAX_SCREENSHOT_BYTES: u64 = 5 * 1024 * 1024;
+
+impl PluginAssets {
+    /// All declared asset paths
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.icon.iter().chain(&self.screenshots).map(String::as_str)
+    }
+    
+    /// Check declared paths are safe relative image paths
+    fn validate(&self) -> Result<()> {
+        for path in self.paths() {
+            if !is_relative_subpath(path) {
+                anyhow::bail!("Asset path must be relative and stay inside the plugin: {}", path);
+            }
+            let extension = Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(str::to_ascii_lowercase)
+                .unwrap_or_default();
+            if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+                anyhow::bail!("Unsupported asset type: {}", path);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Check the declared files exist, are within size limits and look like images
+    pub fn validate_files(&self, plugin_dir: &Path) -> Result<()> {
+        let files = self
+            .icon
+            .iter()
+            .map(|path| (path, MAX_ICON_BYTES))
+            .chain(self.screenshots.iter().map(|path| (path, MAX_SCREENSHOT_BYTES)));
+        
+        for (path, max_bytes) in files {
+            let full_path = plugin_dir.join(path);
+            let size = std::fs::metadata(&full_path)
+                .with_context(|| format!("Asset not found: {}", path))?
+                .len();
+            if size > max_bytes {
+                anyhow::bail!("Asset {} is {} bytes, the limit is {}", path, size, max_bytes);
+            }
+            
+            let bytes = std::fs::read(&full_path)?;
+            if !looks_like_image(path, &bytes) {
+                anyhow::bail!("Asset {} does not match its file type", path);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+/// Check an image's contents against its extension
+fn looks_like_image(path: &str, bytes: &[u8]) -> bool {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    
+    match extension.as_str() {
+        "png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "jpg" | "jpeg" => bytes.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "gif" => bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a"),
+        "webp" => bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP",
+        "svg" => std::str::from_utf8(bytes).is_ok_and(|text| text.contains("<svg")),
+        _ => false,
+    }
+}
+
+/// A function to run periodically, either every N seconds or on a cron expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleSpec {
+    /// Function to call
+    pub function: String,
+    
+    /// Run every this many seconds
+    pub every_seconds: Option<u64>,
+    
+    /// Five-field cron expression, evaluated in local time
+    pub cron: Option<String>,
+    
+    /// Input passed to the function
+    #[serde(default)]
+    pub input: serde_json::Value,
+}
+
+/// A host event the plugin wants to be called with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSubscription {
+    /// Event name, e.g. `tick` or `user.created`
+    pub event: String,
+    
+    /// Function called with the event as its JSON input
+    pub function: String,
+    
+    /// For `tick`, only call on every this many ticks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<u64>,
+}
+
+/// Bus messages the plugin wants to be called with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusSubscription {
+    /// Topic pattern, e.g. `auth.login`, `auth.*` or `*`
+    pub topic: String,
+    
+    /// Function called with the message as its JSON input
+    pub function: String,
+}
+
+/// Static UI assets a plugin ships for its own panels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Directory (relative to the manifest) served over the `plugin-ui` protocol
+    #[serde(default = "default_ui_assets_dir")]
+    pub assets_dir: String,
+    
+    /// Panels the frontend can embed
+    #[serde(default)]
+    pub panels: Vec<UiPanel>,
+    
+    /// Menu items, panels and routes the frontend adds to its navigation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contributions: Vec<UiContribution>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            assets_dir: default_ui_assets_dir(),
+            panels: Vec::new(),
+            contributions: Vec::new(),
+        }
+    }
+}
+
+fn default_ui_assets_dir() -> String {
+    "ui".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiPanel {
+    /// Panel identifier, unique within the plugin
+    pub id: String,
+    
+    /// Title shown in the frontend
+    pub title: String,
+    
+    /// HTML entry file, relative to the assets directory
+    pub entry: String,
+}
+
+/// Where a UI contribution appears in the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UiContributionKind {
+    /// An item in one of the frontend's menus
+    MenuItem,
+    /// A panel shown next to the frontend's own
+    Panel,
+    /// A page of its own, under `/plugins/<plugin ID>`
+    Route,
+}
+
+/// A place in the frontend's navigation that opens a panel or runs an entry point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiContribution {
+    /// Identifier, unique within the plugin
+    pub id: String,
+    
+    pub kind: UiContributionKind,
+    
+    /// Text shown in the navigation
+    pub label: String,
+    
+    /// Name of the entry point it runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_point: Option<String>,
+    
+    /// ID of the panel in `ui.panels` it shows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub panel: Option<String>,
+    
+    /// Path of a route below the plugin's prefix, e.g. `/history`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    
+    /// Menu a menu item goes in, e.g. `tools`; the frontend decides which menus exist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu: Option<String>,
+    
+    /// Position among contributions of the same kind, lowest first
+    #[serde(default)]
+    pub order: i32,
+}
+
+impl UiContribution {
+    /// Whether a route path is `/` or `/`-separated segments of letters,
+    /// digits, `-` and `_`
+    pub fn is_valid_path(path: &str) -> bool {
+        let Some(rest) = path.strip_prefix('/') else {
+            return false;
+        };
+        rest.is_empty()
+            || rest
+                .split('/')
+                .all(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')))
+    }
+}
+
+/// Exported functions the host calls at lifecycle events
+///
+/// Each hook receives `{"event": "<name>", "version": "<plugin version>"}` as
+/// input. A failing `on_install` rolls back the install, a failing
+/// `on_enable` leaves the plugin unloaded, and a failing `on_uninstall`
+/// aborts the uninstall.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LifecycleHooks {
+    /// Called once after the plugin is installed
+    pub on_install: Option<String>,
+    
+    /// Called whenever the plugin is loaded and enabled
+    pub on_enable: Option<String>,
+    
+    /// Called before the plugin is removed
+    pub on_uninstall: Option<String>,
+}
+
+/// A lifecycle event with an optional hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Install,
+    Enable,
+    Uninstall,
+}
+
+impl LifecycleEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleEvent::Install => "install",
+            LifecycleEvent::Enable => "enable",
+            LifecycleEvent::Uninstall => "uninstall",
+        }
+    }
+}
+
+impl LifecycleHooks {
+    /// The function registered for an event, if any
+    pub fn function_for(&self, event: LifecycleEvent) -> Option<&str> {
+        match event {
+            LifecycleEvent::Install => self.on_install.as_deref(),
+            LifecycleEvent::Enable => self.on_enable.as_deref(),
+            LifecycleEvent::Uninstall => self.on_uninstall.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WasmConfig {
+    /// Hosts the plugin may make HTTP requests to; may contain `*` and `?`
+    /// wildcards, e.g. `*.example.com`
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    
+    /// Hosts the plugin may never reach, even if `allowed_hosts` matches them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_hosts: Vec<String>,
+    
+    /// Allowed filesystem paths
+    #[serde(default)]
+    pub allowed_paths: HashMap<String, String>,
+    
+    /// Programs `exec_command` may run, as a name looked up on `PATH` or an
+    /// absolute path. Requires the `exec` capability.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_commands: Vec<String>,
+    
+    /// Custom configuration key-value pairs
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+    
+    /// Memory limit in pages (64KB per page)
+    pub memory_max_pages: Option<u32>,
+    
+    /// Fuel a single call may consume before it is aborted, roughly one unit
+    /// per WASM instruction. Calls are unmetered if unset.
+    #[serde(default)]
+    pub fuel_limit: Option<u64>,
+    
+    /// Give the plugin WASI (stdio, clocks, filesystem). Requires the `wasi`
+    /// capability and the plugin being trusted.
+    #[serde(default)]
+    pub wasi: bool,
+}
+
+/// Per-plugin limits the plugin manager enforces on every call; calls that
+/// go over one fail with a `quota_exceeded` error naming it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceQuotas {
+    /// Longest a call may run, in milliseconds, before it is interrupted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_execution_ms: Option<u64>,
+    
+    /// Largest output a call may return, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<u64>,
+    
+    /// Most calls that may be running or queued on the plugin at once;
+    /// further calls are refused rather than queued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_calls: Option<u32>,
+    
+    /// Longest a single `host_sleep` may wait, in milliseconds; 10 seconds
+    /// if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sleep_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryPoint {
+    /// Function name as seen by users
+    pub name: String,
+    
+    /// Actual WASM function to call
+    pub function: String,
+    
+    /// Other names the function can be called by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    
+    /// Called when a call names no function; at most one entry point is the default
+    #[serde(default)]
+    pub default: bool,
+    
+    /// Description of what this function does
+    pub description: String,
+    
+    /// Input format: json (the default), text or binary. Calls with input
+    /// that is not in this format are rejected before the plugin runs.
+    #[serde(default)]
+    pub input_format: String,
+    
+    /// Output format, in the same terms; output that is not in it fails the call
+    #[serde(default)]
+    pub output_format: String,
+    
+    /// Module that implements the function, for multi-module plugins. The
+    /// main module must export (or re-export) it, since calls go through main.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    
+    /// JSON Schema the input must match; checked before the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    
+    /// JSON Schema the output must match; checked after the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    
+    /// The function still works but should no longer be called
+    #[serde(default)]
+    pub deprecated: bool,
+    
+    /// Name of the entry point that replaces this deprecated one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+impl PluginManifest {
+    /// Load a manifest from a plugin.json or plugin.toml file; both are
+    /// checked against [`MANIFEST_SCHEMA`]
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        Self::from_value(read_manifest_value(path)?)
+    }
+    
+    /// Parse a JSON manifest, reporting every way it deviates from [`MANIFEST_SCHEMA`]
+    pub fn from_slice(content: &[u8]) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(content)
+            .context("Plugin manifest is not valid JSON")?;
+        Self::from_value(value)
+    }
+    
+    fn from_value(value: serde_json::Value) -> Result<Self> {
+        let mut problems = schema_problems(&value);
+        problems.extend(capability_problems(&value));
+        if !problems.is_empty() {
+            return Err(ManifestErrors(problems).into());
+        }
+        serde_json::from_value(value)
+            .context("Failed to parse plugin manifest")
+    }
+    
+    /// Validate the manifest, reporting every problem at once
+    pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ManifestErrors(problems).into())
+        }
+    }
+    
+    /// Everything wrong with the manifest: the schema applied to its
+    /// serialized form, then the checks a schema cannot express
+    pub fn problems(&self) -> Vec<ManifestProblem> {
+        let mut problems = match serde_json::to_value(self) {
+            Ok(value) => schema_problems(&value),
+            Err(e) => vec![ManifestProblem::new("", e.to_string())],
+        };
+        
+        if let Err(e) = self.wasm_module.validate() {
+            problems.push(ManifestProblem::new("/wasm_module", e.to_string()));
+        }
+        if let Some(Err(e)) = self.license.as_deref().map(super::license::identifiers) {
+            problems.push(ManifestProblem::new("/license", e.to_string()));
+        }
+        for (field, labels) in [("categories", &self.categories), ("tags", &self.tags)] {
+            for (i, message) in super::search::label_problems(labels) {
+                problems.push(ManifestProblem::new(&format!("/{}/{}", field, i), message));
+            }
+        }
+        if self.tags.len() > super::search::MAX_TAGS {
+            problems.push(ManifestProblem::new(
+                "/tags",
+                format!("At most {} tags are allowed", super::search::MAX_TAGS),
+            ));
+        }
+        for (i, platform) in self.platforms.iter().enumerate() {
+            if let Err(e) = super::compatibility::validate_platform(platform) {
+                problems.push(ManifestProblem::new(&format!("/platforms/{}", i), e.to_string()));
+            }
+        }
+        if self.wasm_config.wasi && self.sandbox != Some(SandboxProfile::Trusted) {
+            problems.push(ManifestProblem::new(
+                "/wasm_config/wasi",
+                format!("WASI needs the '{}' sandbox", SandboxProfile::Trusted),
+            ));
+        }
+        if !self.schedules.is_empty() && !self.capabilities.contains(&Capability::Tick) {
+            problems.push(ManifestProblem::new(
+                "/schedules",
+                format!("Schedules need the '{}' capability", Capability::Tick),
+            ));
+        }
+        for (i, subscription) in self.subscriptions.iter().enumerate() {
+            let pointer = format!("/subscriptions/{}", i);
+            if !crate::events::is_event_name(&subscription.event) {
+                problems.push(ManifestProblem::new(
+                    &format!("{}/event", pointer),
+                    format!(
+                        "'{}' is not an event name; use lowercase letters, digits, '.', '_' and '-'",
+                        subscription.event
+                    ),
+                ));
+            }
+            if subscription.every.is_some() && subscription.event != crate::events::TICK {
+                problems.push(ManifestProblem::new(
+                    &format!("{}/every", pointer),
+                    format!("Only '{}' subscriptions can skip events", crate::events::TICK),
+                ));
+            }
+            if let Some(capability) = crate::events::required_capability(&subscription.event) {
+                if !self.declares(&capability) {
+                    problems.push(ManifestProblem::new(
+                        &pointer,
+                        format!("Subscribing to '{}' needs the '{}' capability", subscription.event, capability),
+                    ));
+                }
+            }
+        }
+        for (i, subscription) in self.bus_subscriptions.iter().enumerate() {
+            if !crate::bus::is_pattern(&subscription.topic) {
+                problems.push(ManifestProblem::new(
+                    &format!("/bus_subscriptions/{}/topic", i),
+                    format!(
+                        "'{}' is not a topic pattern; use a topic such as 'auth.login', a topic followed by '.*', or '*'",
+                        subscription.topic
+                    ),
+                ));
+            }
+        }
+        let host_lists = [("allowed_hosts", &self.wasm_config.allowed_hosts), ("denied_hosts", &self.wasm_config.denied_hosts)];
+        for (field, patterns) in host_lists {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(e) = crate::hosts::validate(pattern) {
+                    problems.push(ManifestProblem::new(&format!("/wasm_config/{}/{}", field, i), e.to_string()));
+                }
+            }
+        }
+        for host in self.wasm_config.allowed_paths.keys() {
+            if let Err(e) = crate::paths::validate(host) {
+                problems.push(ManifestProblem::new(
+                    &format!("/wasm_config/allowed_paths/{}", host.replace('~', "~0").replace('/', "~1")),
+                    e.to_string(),
+                ));
+            }
+        }
+        for (i, command) in self.wasm_config.allowed_commands.iter().enumerate() {
+            let path = std::path::Path::new(command);
+            if command.trim().is_empty() || (!path.is_absolute() && path.components().count() != 1) {
+                problems.push(ManifestProblem::new(
+                    &format!("/wasm_config/allowed_commands/{}", i),
+                    format!("'{}' is neither a program name nor an absolute path", command),
+                ));
+            }
+        }
+        for (key, value) in &self.wasm_config.config {
+            if let Some(Err(e)) = crate::secrets::reference(value).map(crate::secrets::validate_name) {
+                problems.push(ManifestProblem::new(
+                    &format!("/wasm_config/config/{}", key.replace('~', "~0").replace('/', "~1")),
+                    e.to_string(),
+                ));
+            }
+        }
+        for (name, template) in &self.env {
+            if let Err(e) = crate::templates::validate(template) {
+                problems.push(ManifestProblem::new(
+                    &format!("/env/{}", name.replace('~', "~0").replace('/', "~1")),
+                    e.to_string(),
+                ));
+            }
+        }
+        for (i, entry_point) in self.entry_points.iter().enumerate() {
+            let formats = [("input_format", &entry_point.input_format), ("output_format", &entry_point.output_format)];
+            for (field, format) in formats {
+                if PayloadFormat::parse(format).is_none() {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/{}", i, field),
+                        format!("Unknown format '{}'; use one of {}", format, PayloadFormat::NAMES.join(", ")),
+                    ));
+                }
+            }
+            let schemas = [("input_schema", &entry_point.input_schema), ("output_schema", &entry_point.output_schema)];
+            for (field, schema) in schemas {
+                if let Some(Err(e)) = schema.as_ref().map(jsonschema::validator_for) {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/{}", i, field),
+                        format!("Invalid JSON Schema: {}", e),
+                    ));
+                }
+            }
+            if let Some(module) = &entry_point.module {
+                if self.wasm_module.module_path(module).is_none() {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/module", i),
+                        format!("Entry point '{}' refers to unknown WASM module '{}'", entry_point.name, module),
+                    ));
+                }
+            }
+            if let Some(replacement) = &entry_point.replacement {
+                if !entry_point.deprecated {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/replacement", i),
+                        "Only deprecated entry points have a replacement".to_string(),
+                    ));
+                } else if !self.entry_points.iter().any(|ep| &ep.name == replacement && !ep.deprecated) {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/replacement", i),
+                        format!("Replacement '{}' is not an entry point that is still supported", replacement),
+                    ));
+                }
+            }
+        }
+        let defaults = self.entry_points.iter().filter(|ep| ep.default).count();
+        if defaults > 1 {
+            problems.push(ManifestProblem::new(
+                "/entry_points",
+                format!("{} entry points are marked default; at most one may be", defaults),
+            ));
+        }
+        for (i, entry_point) in self.entry_points.iter().enumerate() {
+            for (j, alias) in entry_point.aliases.iter().enumerate() {
+                let taken = self.entry_points.iter().enumerate().any(|(k, other)| {
+                    other.function == *alias
+                        || other.aliases.iter().enumerate().any(|(l, other_alias)| other_alias == alias && (k, l) != (i, j))
+                });
+                if taken {
+                    problems.push(ManifestProblem::new(
+                        &format!("/entry_points/{}/aliases/{}", i, j),
+                        format!("Alias '{}' is already the name of another function or alias", alias),
+                    ));
+                }
+            }
+        }
+        if self.replacement.is_some() && !self.deprecated {
+            problems.push(ManifestProblem::new("/replacement", "Only deprecated plugins have a replacement".to_string()));
+        }
+        
+        let ui_paths = std::iter::once(("/ui/assets_dir".to_string(), &self.ui.assets_dir)).chain(
+            self.ui
+                .panels
+                .iter()
+                .enumerate()
+                .map(|(i, panel)| (format!("/ui/panels/{}/entry", i), &panel.entry)),
+        );
+        for (pointer, path) in ui_paths {
+            if !is_relative_subpath(path) {
+                problems.push(ManifestProblem::new(
+                    &pointer,
+                    format!("UI path must be relative and stay inside the plugin: {}", path),
+                ));
+            }
+        }
+        
+        for (i, contribution) in self.ui.contributions.iter().enumerate() {
+            let pointer = format!("/ui/contributions/{}", i);
+            let mut problem = |field: &str, message: String| {
+                problems.push(ManifestProblem::new(&format!("{}{}", pointer, field), message))
+            };
+            if self.ui.contributions[..i].iter().any(|other| other.id == contribution.id) {
+                problem("/id", format!("Contribution '{}' is declared twice", contribution.id));
+            }
+            if contribution.entry_point.is_none() && contribution.panel.is_none() {
+                problem("", format!("Contribution '{}' needs an entry_point or a panel to open", contribution.id));
+            }
+            if let Some(name) = &contribution.entry_point {
+                if !self.entry_points.iter().any(|ep| &ep.name == name) {
+                    problem("/entry_point", format!("Unknown entry point '{}'", name));
+                }
+            }
+            if let Some(id) = &contribution.panel {
+                if !self.ui.panels.iter().any(|panel| &panel.id == id) {
+                    problem("/panel", format!("Unknown panel '{}'", id));
+                }
+            }
+            match (contribution.kind, &contribution.path) {
+                (UiContributionKind::Route, None) => problem("", "Routes need a path".to_string()),
+                (UiContributionKind::Route, Some(path)) if !UiContribution::is_valid_path(path) => problem(
+                    "/path",
+                    format!("'{}' must start with '/' and contain only letters, digits, '-', '_' and '/'", path),
+                ),
+                (UiContributionKind::Route, Some(path)) => {
+                    let taken = self.ui.contributions[..i]
+                        .iter()
+                        .any(|other| other.kind == UiContributionKind::Route && other.path.as_ref() == Some(path));
+                    if taken {
+                        problem("/path", format!("Another route already uses '{}'", path));
+                    }
+                }
+                (_, Some(_)) => problem("/path", "Only routes have a path".to_string()),
+                (_, None) => {}
+            }
+            if contribution.menu.is_some() && contribution.kind != UiContributionKind::MenuItem {
+                problem("/menu", "Only menu items go in a menu".to_string());
+            }
+        }
+        
+        if let Err(e) = self.assets.validate() {
+            problems.push(ManifestProblem::new("/assets", e.to_string()));
+        }
+        
+        if !self.migrations.is_empty() && !self.declares(&Capability::DB_WRITE) {
+            problems.push(ManifestProblem::new(
+                "/migrations",
+                format!("Migrations need the '{}' capability", Capability::DB_WRITE),
+            ));
+        }
+        for (i, path) in self.migrations.iter().enumerate() {
+            if !is_relative_subpath(path) || !path.ends_with(".sql") {
+                problems.push(ManifestProblem::new(
+                    &format!("/migrations/{}", i),
+                    format!("Migration must be a .sql file inside the plugin: {}", path),
+                ));
+            }
+        }
+        
+        for (locale, metadata) in &self.i18n {
+            let pointer = format!("/i18n/{}", locale.replace('~', "~0").replace('/', "~1"));
+            if !is_locale_tag(locale) {
+                problems.push(ManifestProblem::new(
+                    &pointer,
+                    format!("'{}' is not a locale tag such as 'de' or 'pt-BR'", locale),
+                ));
+            }
+            for name in metadata.entry_points.keys() {
+                if !self.entry_points.iter().any(|entry_point| &entry_point.name == name) {
+                    problems.push(ManifestProblem::new(
+                        &format!("{}/entry_points", pointer),
+                        format!("Translation for unknown entry point '{}'", name),
+                    ));
+                }
+            }
+        }
+        
+        problems
+    }
+    
+    /// The translation that best matches a locale, if any
+    ///
+    /// Tries the exact tag, then ever shorter prefixes of it (`zh-Hant-TW`,
+    /// `zh-Hant`, `zh`), then any translation in the same language.
+    pub fn localized(&self, locale: &str) -> Option<&LocalizedMetadata> {
+        let wanted = normalize_locale(locale);
+        let find = |tag: &str| {
+            self.i18n
+                .iter()
+                .find(|(candidate, _)| normalize_locale(candidate) == tag)
+                .map(|(_, metadata)| metadata)
+        };
+        let mut tag = wanted.as_str();
+        loop {
+            if let Some(metadata) = find(tag) {
+                return Some(metadata);
+            }
+            match tag.rsplit_once('-') {
+                Some((prefix, _)) => tag = prefix,
+                None => break,
+            }
+        }
+        let mut same_language: Vec<_> = self
+            .i18n
+            .iter()
+            .filter(|(candidate, _)| normalize_locale(candidate).split('-').next() == Some(tag))
+            .collect();
+        same_language.sort_by(|a, b| a.0.cmp(b.0));
+        same_language.first().map(|(_, metadata)| *metadata)
+    }
+    
+    /// Unique plugin ID: `namespace/name`, or just the name without a namespace
+    pub fn id(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, self.name),
+            None => self.name.clone(),
+        }
+    }
+    
+    /// Sensitive capabilities the plugin asks for, declared or implied by its config
+    pub fn sensitive_capabilities(&self) -> Vec<Capability> {
+        Capability::SENSITIVE
+            .iter()
+            .copied()
+            .filter(|&capability| {
+                self.capabilities.iter().any(|c| c.approval() == Some(capability))
+                    || match capability {
+                        Capability::Network => !self.wasm_config.allowed_hosts.is_empty(),
+                        Capability::Filesystem => self
+                            .wasm_config
+                            .allowed_paths
+                            .iter()
+                            .any(|(host, guest)| {
+                                guest != super::PLUGIN_DATA_GUEST_PATH
+                                    && crate::paths::split_token(host).is_none_or(|(token, _)| token != crate::paths::DATA_TOKEN)
+                            }),
+                        _ => false,
+                    }
+            })
+            .collect()
+    }
+    
+    /// The WASM function a call to `function` runs: the default entry point's
+    /// for an empty name, the entry point's for one of its aliases, and
+    /// `function` itself otherwise. None for an empty name without a default.
+    pub fn resolve_function<'a>(&'a self, function: &'a str) -> Option<&'a str> {
+        if function.is_empty() {
+            return self
+                .entry_points
+                .iter()
+                .find(|ep| ep.default)
+                .map(|ep| ep.function.as_str());
+        }
+        let aliased = self
+            .entry_points
+            .iter()
+            .find(|ep| ep.aliases.iter().any(|alias| alias == function));
+        Some(aliased.map_or(function, |ep| ep.function.as_str()))
+    }
+    
+    /// Input and output formats of the entry point for the WASM function
+    /// `function`; None if no entry point declares it
+    pub fn payload_formats(&self, function: &str) -> Option<(PayloadFormat, PayloadFormat)> {
+        let entry_point = self.entry_points.iter().find(|ep| ep.function == function)?;
+        // Unknown formats are manifest problems, so they never get this far
+        let parse = |format: &str| PayloadFormat::parse(format).unwrap_or(PayloadFormat::Json);
+        Some((parse(&entry_point.input_format), parse(&entry_point.output_format)))
+    }
+    
+    /// Warnings for a call to `function`: whether it or the whole plugin is
+    /// deprecated, and what to use instead
+    pub fn deprecation_warnings(&self, function: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(entry_point) = self
+            .entry_points
+            .iter()
+            .find(|ep| ep.function == function && ep.deprecated)
+        {
+            warnings.push(match &entry_point.replacement {
+                Some(replacement) => format!(
+                    "Function '{}' of plugin '{}' is deprecated; use '{}' instead",
+                    entry_point.name,
+                    self.id(),
+                    replacement
+                ),
+                None => format!("Function '{}' of plugin '{}' is deprecated", entry_point.name, self.id()),
+            });
+        }
+        if self.deprecated {
+            warnings.push(match &self.replacement {
+                Some(replacement) => format!("Plugin '{}' is deprecated; use '{}' instead", self.id(), replacement),
+                None => format!("Plugin '{}' is deprecated", self.id()),
+            });
+        }
+        warnings
+    }
+    
+    /// Whether the plugin declares a capability covering `capability`
+    pub fn declares(&self, capability: &Capability) -> bool {
+        self.capabilities.iter().any(|c| c.covers(capability))
+    }
+    
+    /// Name of the directory the plugin is installed into
+    pub fn install_dir_name(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}__{}", namespace, self.name),
+            None => self.name.clone(),
+        }
+    }
+    
+    /// Get the full path to the main WASM module
+    pub fn wasm_path(&self, plugin_dir: &Path) -> std::path::PathBuf {
+        plugin_dir.join(self.wasm_module.main_path())
+    }
+    
+    /// Get the full paths to every WASM module
+    pub fn wasm_paths(&self, plugin_dir: &Path) -> Vec<std::path::PathBuf> {
+        self.wasm_module
+            .paths()
+            .into_iter()
+            .map(|path| plugin_dir.join(path))
+            .collect()
+    }
+    
+    /// Files other than its modules the plugin is made of: its migrations,
+    /// catalog assets and everything in its UI assets directory
+    ///
+    /// Paths are relative to the plugin directory, with `/` separators,
+    /// sorted and without duplicates.
+    pub fn referenced_files(&self, plugin_dir: &Path) -> Result<Vec<String>> {
+        let mut files: BTreeSet<String> = self
+            .migrations
+            .iter()
+            .map(String::as_str)
+            .chain(self.assets.paths())
+            .map(|path| path.trim_start_matches("./").replace('\\', "/"))
+            .collect();
+        let ui_dir = self.ui.assets_dir.trim_start_matches("./").trim_end_matches('/');
+        if plugin_dir.join(ui_dir).is_dir() {
+            list_files(&plugin_dir.join(ui_dir), ui_dir, &mut files)?;
+        }
+        Ok(files.into_iter().collect())
+    }
+}
+
+/// Add the files under `dir` to `files`, as `prefix/<path within dir>`
+fn list_files(dir: &Path, prefix: &str, files: &mut BTreeSet<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let path = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// JSON Schema of `plugin.json`, and of `plugin.toml` converted to JSON;
+/// `semver`, `semver-range` and `plugin-identifier` are formats the validator adds
+pub const MANIFEST_SCHEMA: &str = include_str!("manifest.schema.json");
+
+static SCHEMA_VALIDATOR: LazyLock<jsonschema::Validator> = LazyLock::new(|| {
+    let schema: serde_json::Value = serde_json::from_str(MANIFEST_SCHEMA).expect("Manifest schema is valid JSON");
+    jsonschema::options()
+        .should_validate_formats(true)
+        .with_format("semver", |version| semver::Version::parse(version).is_ok())
+        .with_format("semver-range", |range| semver::VersionReq::parse(range).is_ok())
+        .with_format("plugin-identifier", is_identifier)
+        .build(&schema)
+        .expect("Manifest schema is a valid JSON Schema")
+});
+
+/// A problem with a manifest, located by the JSON pointer of the offending value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestProblem {
+    /// JSON pointer, e.g. `/entry_points/0/function`; empty for the whole manifest
+    pub path: String,
+    pub message: String,
+}
+
+impl ManifestProblem {
+    fn new(path: &str, message: String) -> Self {
+        Self {
+            path: path.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ManifestProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Every problem found in a manifest
+#[derive(Debug, Clone)]
+pub struct ManifestErrors(pub Vec<ManifestProblem>);
+
+impl fmt::Display for ManifestErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid plugin manifest ({} problem(s))", self.0.len())?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestErrors {}
+
+/// Check a manifest's JSON against [`MANIFEST_SCHEMA`]
+fn schema_problems(value: &serde_json::Value) -> Vec<ManifestProblem> {
+    SCHEMA_VALIDATOR
+        .iter_errors(value)
+        .map(|error| ManifestProblem::new(error.instance_path.as_str(), error.to_string()))
+        .collect()
+}
+
+/// Manifest file names a plugin directory may have, in order of preference
+pub const MANIFEST_FILES: &[&str] = &["plugin.json", "plugin.toml"];
+
+/// Path of the manifest in a plugin directory: plugin.json, or plugin.toml if
+/// there is only that; plugin.json if there is neither, for error messages
+pub fn find_manifest(dir: &Path) -> PathBuf {
+    MANIFEST_FILES
+        .iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(MANIFEST_FILES[0]))
+}
+
+/// Read a manifest file as JSON, converting TOML
+pub(super) fn read_manifest_value(path: &Path) -> Result<serde_json::Value> {
+    let content = std::fs::read_to_string(path)
+        .context("Failed to read plugin manifest")?;
+    if path.extension().is_some_and(|extension| extension == "toml") {
+        toml::from_str(&content).context("Plugin manifest is not valid TOML")
+    } else {
+        serde_json::from_str(&content).context("Plugin manifest is not valid JSON")
+    }
+}
+
+/// Replace a plugin directory's plugin.toml with the equivalent plugin.json,
+/// so installed plugins always have a JSON manifest
+pub fn normalize_manifest(dir: &Path) -> Result<()> {
+    let path = find_manifest(dir);
+    if path.extension().is_none_or(|extension| extension != "toml") {
+        return Ok(());
+    }
+    let value = read_manifest_value(&path)?;
+    std::fs::write(dir.join(MANIFEST_FILES[0]), serde_json::to_vec_pretty(&value)?)
+        .context("Failed to write plugin.json")?;
+    std::fs::remove_file(&path).context("Failed to remove plugin.toml")?;
+    Ok(())
+}
+
+/// Capabilities in a manifest's JSON that are not part of the [`Capability`] taxonomy
+fn capability_problems(value: &serde_json::Value) -> Vec<ManifestProblem> {
+    let Some(capabilities) = value.get("capabilities").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+    capabilities
+        .iter()
+        .enumerate()
+        .filter_map(|(i, capability)| {
+            let error = capability.as_str()?.parse::<Capability>().err()?;
+            Some(ManifestProblem::new(&format!("/capabilities/{}", i), error.to_string()))
+        })
+        .collect()
+}
+
+/// Whether a name or namespace is a single safe path component
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('.')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Lowercase a locale tag and use `-` as its separator, so `pt_BR` matches `pt-BR`
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Whether a string looks like a BCP 47 tag: alphanumeric subtags of up to 8
+/// characters, the first of them a 2-3 letter language
+fn is_locale_tag(locale: &str) -> bool {
+    let mut subtags = locale.split(['-', '_']);
+    let language = subtags.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Whether a manifest-supplied path is relative and never leaves its base directory
+pub fn is_relative_subpath(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest() -> serde_json::Value {
+        json!({
+            "name": "text-converter",
+            "version": "1.2.3-beta.1",
+            "description": "Converts text",
+            "plugin_type": "converter",
+            "wasm_module": "plugin.wasm",
+            "dependencies": { "acme.base64": "^1.0", "hasher": ">=0.2, <0.4" },
+            "min_app_version": "0.1.0",
+        })
+    }
+
+    /// JSON pointers of the problems found in `value`
+    fn problem_paths(value: serde_json::Value) -> Vec<String> {
+        let error = PluginManifest::from_slice(value.to_string().as_bytes()).unwrap_err();
+        let errors = error.downcast_ref::<ManifestErrors>().expect("schema problems are reported together");
+        errors.0.iter().map(|problem| problem.path.clone()).collect()
+    }
+
+    #[test]
+    fn test_valid_versions_and_ranges_are_accepted() {
+        let manifest = PluginManifest::from_slice(manifest().to_string().as_bytes()).unwrap();
+        assert_eq!(manifest.version, "1.2.3-beta.1");
+        assert!(manifest.problems().is_empty(), "{:?}", manifest.problems());
+    }
+
+    #[test]
+    fn test_versions_must_be_semver() {
+        for version in ["1.0", "v1.0.0", "latest", "1.0.0.0"] {
+            let mut value = manifest();
+            value["version"] = json!(version);
+            assert_eq!(problem_paths(value), ["/version"], "'{}' was accepted", version);
+        }
+        let mut value = manifest();
+        value["min_app_version"] = json!("1");
+        value["min_host_version"] = json!("0.x");
+        let mut paths = problem_paths(value);
+        paths.sort();
+        assert_eq!(paths, ["/min_app_version", "/min_host_version"]);
+    }
+
+    #[test]
+    fn test_dependency_ranges_must_be_semver_ranges() {
+        let mut value = manifest();
+        value["dependencies"]["hasher"] = json!("~> 1.0");
+        value["dependencies"]["other"] = json!("");
+        let mut paths = problem_paths(value);
+        paths.sort();
+        assert_eq!(paths, ["/dependencies/hasher", "/dependencies/other"]);
+    }
+
+    #[test]
+    fn test_every_schema_problem_is_reported_at_once() {
+        let mut value = manifest();
+        value.as_object_mut().unwrap().remove("description");
+        value["name"] = json!("../escape");
+        value["plugin_type"] = json!("daemon");
+        value["entry_points"] = json!([{ "name": "run", "function": "", "description": "Runs" }]);
+        let mut paths = problem_paths(value);
+        paths.sort();
+        assert_eq!(paths, ["", "/entry_points/0/function", "/name", "/plugin_type"]);
+
+        let error = PluginManifest::from_slice(b"{ not json").unwrap_err();
+        assert_eq!(error.to_string(), "Plugin manifest is not valid JSON");
+    }
+
+    #[test]
+    fn test_problems_checks_manifests_changed_after_parsing() {
+        let mut manifest = PluginManifest::from_slice(manifest().to_string().as_bytes()).unwrap();
+        manifest.version = "next".to_string();
+        manifest.dependencies.insert("hasher".to_string(), "any".to_string());
+        let mut paths: Vec<_> = manifest.problems().into_iter().map(|problem| problem.path).collect();
+        paths.sort();
+        assert_eq!(paths, ["/dependencies/hasher", "/version"]);
+        assert!(manifest.validate().unwrap_err().to_string().starts_with("Invalid plugin manifest (2 problem(s))"));
+    }
+}
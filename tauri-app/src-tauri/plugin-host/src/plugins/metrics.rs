@@ -0,0 +1,148 @@
+//! Per-plugin execution metrics (call counts, latency percentiles, errors)
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Number of recent latency samples kept per plugin/function for percentiles
+const LATENCY_WINDOW: usize = 1024;
+
+/// Running statistics for a plugin or a single plugin function
+#[derive(Debug, Default)]
+struct CallStats {
+    total_calls: u64,
+    error_count: u64,
+    latencies_us: VecDeque<u64>,
+    last_error: Option<String>,
+    last_error_at: Option<i64>,
+    /// Fuel consumed by metered calls
+    total_fuel: u64,
+    last_fuel: Option<u64>,
+}
+
+impl CallStats {
+    fn record(&mut self, latency: Duration, error: Option<&str>, fuel: Option<u64>) {
+        self.total_calls += 1;
+        if let Some(fuel) = fuel {
+            self.total_fuel = self.total_fuel.saturating_add(fuel);
+            self.last_fuel = Some(fuel);
+        }
+
+        if self.latencies_us.len() == LATENCY_WINDOW {
+            self.latencies_us.pop_front();
+        }
+        self.latencies_us.push_back(latency.as_micros() as u64);
+
+        if let Some(error) = error {
+            self.error_count += 1;
+            self.last_error = Some(error.to_string());
+            self.last_error_at = Some(chrono::Utc::now().timestamp());
+        }
+    }
+
+    /// Latency percentile in milliseconds over the recent sample window
+    fn percentile_ms(&self, percentile: f64) -> f64 {
+        if self.latencies_us.is_empty() {
+            return 0.0;
+        }
+
+        let mut samples: Vec<u64> = self.latencies_us.iter().copied().collect();
+        samples.sort_unstable();
+
+        let rank = ((percentile / 100.0) * samples.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, samples.len()) - 1;
+        samples[index] as f64 / 1000.0
+    }
+
+    fn snapshot(&self) -> CallStatsSnapshot {
+        CallStatsSnapshot {
+            total_calls: self.total_calls,
+            error_count: self.error_count,
+            p50_latency_ms: self.percentile_ms(50.0),
+            p95_latency_ms: self.percentile_ms(95.0),
+            last_error: self.last_error.clone(),
+            last_error_at: self.last_error_at,
+            total_fuel_consumed: self.total_fuel,
+            last_fuel_consumed: self.last_fuel,
+        }
+    }
+}
+
+/// Serializable view of call statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallStatsSnapshot {
+    pub total_calls: u64,
+    pub error_count: u64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+    /// Fuel consumed by all metered calls
+    pub total_fuel_consumed: u64,
+    /// Fuel consumed by the most recent metered call
+    pub last_fuel_consumed: Option<u64>,
+}
+
+/// Serializable metrics for a plugin, with a per-function breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginMetricsSnapshot {
+    pub plugin: String,
+    #[serde(flatten)]
+    pub overall: CallStatsSnapshot,
+    pub functions: HashMap<String, CallStatsSnapshot>,
+}
+
+#[derive(Debug, Default)]
+struct PluginMetrics {
+    overall: CallStats,
+    functions: HashMap<String, CallStats>,
+}
+
+/// Registry of execution metrics for all plugins
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    plugins: HashMap<String, PluginMetrics>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of a single plugin function call
+    ///
+    /// `fuel` is the fuel the call consumed, for plugins with a fuel limit.
+    pub fn record(&mut self, plugin: &str, function: &str, latency: Duration, error: Option<&str>, fuel: Option<u64>) {
+        let metrics = self.plugins.entry(plugin.to_string()).or_default();
+        metrics.overall.record(latency, error, fuel);
+        metrics
+            .functions
+            .entry(function.to_string())
+            .or_default()
+            .record(latency, error, fuel);
+    }
+
+    /// Get metrics for a single plugin
+    pub fn snapshot(&self, plugin: &str) -> Option<PluginMetricsSnapshot> {
+        self.plugins.get(plugin).map(|metrics| PluginMetricsSnapshot {
+            plugin: plugin.to_string(),
+            overall: metrics.overall.snapshot(),
+            functions: metrics
+                .functions
+                .iter()
+                .map(|(name, stats)| (name.clone(), stats.snapshot()))
+                .collect(),
+        })
+    }
+
+    /// Get metrics for every plugin that has been called
+    pub fn snapshot_all(&self) -> Vec<PluginMetricsSnapshot> {
+        let mut snapshots: Vec<_> = self
+            .plugins
+            .keys()
+            .filter_map(|plugin| self.snapshot(plugin))
+            .collect();
+        snapshots.sort_by(|a, b| a.plugin.cmp(&b.plugin));
+        snapshots
+    }
+}
@@ -0,0 +1,41 @@
+//! Plugin system for loading and managing WASM plugins
+
+mod capabilities;
+pub(crate) mod compatibility;
+mod component;
+mod context;
+mod graph;
+mod integrity;
+mod license;
+mod manifest;
+mod manager;
+mod loader;
+mod logs;
+mod metrics;
+mod payload;
+mod sandbox;
+mod search;
+mod trace;
+mod trust;
+mod validation;
+
+pub use capabilities::{Capability, CapabilityApprovals, CapabilityDecisions, CapabilityRequest, DbAccess, DB_RESOURCES};
+pub use compatibility::CompatibilityReport;
+pub use context::{CurrentUser, ExecutionContext};
+pub use graph::DependencyGraph;
+pub use integrity::IntegrityViolation;
+pub use license::{LicenseReport, PluginLicense};
+pub use manifest::{is_relative_subpath, BusSubscription, EventSubscription, PluginManifest, UiContribution, UiContributionKind, UiPanel};
+pub use manager::{
+    resolve_plugin_id, DiscoveryReport, PluginCanary, PluginConfig, PluginExecutor, PluginManager, PluginRegistry, PluginSetChange, PluginUpdate, QuarantinedPlugin, SettingWatches,
+    PLUGIN_DATA_GUEST_PATH,
+};
+pub use loader::{CancelHandle, PluginLoader};
+pub use logs::{PluginLogEntry, PluginLogStore};
+pub use metrics::{MetricsRegistry, PluginMetricsSnapshot};
+pub use payload::PayloadFormat;
+pub use sandbox::SandboxProfile;
+pub use search::PluginQuery;
+pub use trace::{TraceStore, MAX_PERSISTED_TRACES, MAX_TRACED_CALLS};
+pub use trust::{generate_author_key, sign_plugin, validate_public_key, verify_plugin, AuthorKey, ManifestSignature};
+pub use validation::ValidationReport;
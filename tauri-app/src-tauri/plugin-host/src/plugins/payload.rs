@@ -0,0 +1,154 @@
+//! Validation of entry point inputs and outputs against the formats and
+//! JSON Schemas declared in the manifest
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use jsonschema::Validator;
+use std::collections::HashMap;
+
+use super::manifest::PluginManifest;
+use crate::error::{AppError, ErrorCode};
+
+/// How an entry point's input or output is encoded, from its `input_format`
+/// or `output_format`; an empty format is JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Json,
+    /// UTF-8 text
+    Text,
+    /// Any bytes
+    Binary,
+}
+
+impl PayloadFormat {
+    /// Format names a manifest may use
+    pub const NAMES: &'static [&'static str] = &["json", "text", "binary"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "" | "json" => Some(PayloadFormat::Json),
+            "text" => Some(PayloadFormat::Text),
+            "binary" => Some(PayloadFormat::Binary),
+            _ => None,
+        }
+    }
+
+    /// Reject a payload that is not in this format: JSON that does not
+    /// parse, or text that is not UTF-8
+    pub fn check(self, payload: &[u8], what: &str, code: ErrorCode) -> Result<(), AppError> {
+        match self {
+            PayloadFormat::Json => serde_json::from_slice::<serde_json::Value>(payload)
+                .map(|_| ())
+                .map_err(|e| AppError::new(code, format!("{} is not valid JSON: {}", what, e))),
+            PayloadFormat::Text => std::str::from_utf8(payload)
+                .map(|_| ())
+                .map_err(|e| AppError::new(code, format!("{} is not UTF-8 text: {}", what, e))),
+            PayloadFormat::Binary => Ok(()),
+        }
+    }
+
+    /// Bytes to pass a function for an input given as JSON, e.g. by the
+    /// frontend: JSON as it is, text as the string's contents, and binary as
+    /// a base64 string or an array of byte values
+    pub fn encode_input(self, input: &serde_json::Value) -> Result<Vec<u8>, AppError> {
+        let invalid = |message: String| AppError::new(ErrorCode::InvalidInput, message);
+        match (self, input) {
+            (PayloadFormat::Json, input) => serde_json::to_vec(input).map_err(|e| invalid(e.to_string())),
+            (PayloadFormat::Text, serde_json::Value::String(text)) => Ok(text.as_bytes().to_vec()),
+            (PayloadFormat::Text, _) => Err(invalid("Text input must be a string".to_string())),
+            (PayloadFormat::Binary, serde_json::Value::String(encoded)) => BASE64
+                .decode(encoded)
+                .map_err(|e| invalid(format!("Binary input is not valid base64: {}", e))),
+            (PayloadFormat::Binary, serde_json::Value::Array(bytes)) => bytes
+                .iter()
+                .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| invalid("Binary input arrays may only hold numbers from 0 to 255".to_string())),
+            (PayloadFormat::Binary, _) => Err(invalid("Binary input must be a base64 string or an array of bytes".to_string())),
+        }
+    }
+
+    /// Output bytes as JSON for a caller: JSON parsed, text as a string and
+    /// binary as a base64 string
+    pub fn decode_output(self, output: &[u8]) -> Result<serde_json::Value, AppError> {
+        match self {
+            PayloadFormat::Json => serde_json::from_slice(output).map_err(|e| {
+                AppError::new(ErrorCode::PluginError, format!("Plugin returned invalid JSON: {}", e))
+            }),
+            PayloadFormat::Text => String::from_utf8(output.to_vec())
+                .map(serde_json::Value::String)
+                .map_err(|e| AppError::new(ErrorCode::PluginError, format!("Plugin returned invalid UTF-8 text: {}", e))),
+            PayloadFormat::Binary => Ok(serde_json::Value::String(BASE64.encode(output))),
+        }
+    }
+}
+
+/// Compiled `input_schema` and `output_schema` of an entry point
+pub struct PayloadSchemas {
+    input: Option<Validator>,
+    output: Option<Validator>,
+}
+
+impl PayloadSchemas {
+    /// Compile the schemas of every entry point that declares one, by function name
+    pub fn compile(manifest: &PluginManifest) -> Result<HashMap<String, PayloadSchemas>> {
+        let mut schemas = HashMap::new();
+        for entry_point in &manifest.entry_points {
+            if entry_point.input_schema.is_none() && entry_point.output_schema.is_none() {
+                continue;
+            }
+            let compile = |schema: &Option<serde_json::Value>, which: &str| {
+                schema
+                    .as_ref()
+                    .map(jsonschema::validator_for)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("Invalid {} of entry point '{}': {}", which, entry_point.name, e))
+            };
+            schemas.insert(
+                entry_point.function.clone(),
+                PayloadSchemas {
+                    input: compile(&entry_point.input_schema, "input_schema")?,
+                    output: compile(&entry_point.output_schema, "output_schema")?,
+                },
+            );
+        }
+        Ok(schemas)
+    }
+
+    /// Reject an input that does not match the input schema
+    pub fn check_input(&self, input: &[u8]) -> Result<(), AppError> {
+        match &self.input {
+            Some(validator) => check(validator, input, "Input", ErrorCode::InvalidInput),
+            None => Ok(()),
+        }
+    }
+
+    /// Reject an output that does not match the output schema; the plugin broke its contract
+    pub fn check_output(&self, output: &[u8]) -> Result<(), AppError> {
+        match &self.output {
+            Some(validator) => check(validator, output, "Output", ErrorCode::PluginError),
+            None => Ok(()),
+        }
+    }
+}
+
+fn check(validator: &Validator, payload: &[u8], what: &str, code: ErrorCode) -> Result<(), AppError> {
+    let value: serde_json::Value = serde_json::from_slice(payload)
+        .map_err(|e| AppError::new(code, format!("{} is not valid JSON: {}", what, e)))?;
+    let problems: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|error| match error.instance_path.as_str() {
+            "" => error.to_string(),
+            path => format!("{}: {}", path, error),
+        })
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("{} does not match the entry point's schema ({} problem(s))", what, problems.len());
+    for problem in problems {
+        message.push_str("\n  - ");
+        message.push_str(&problem);
+    }
+    Err(AppError::new(code, message))
+}
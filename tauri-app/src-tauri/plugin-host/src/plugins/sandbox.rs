@@ -0,0 +1,87 @@
+//! Preset sandboxes plugins run in
+//!
+//! A manifest requests a [`SandboxProfile`] as `sandbox` instead of setting
+//! each limit itself. The profile caps memory and fuel, decides whether WASI
+//! is available and withholds capabilities the user may have approved. The
+//! user can move a plugin to a stricter profile and the host can cap the
+//! profile of every plugin, but neither can loosen what the manifest asked
+//! for; `trusted` also needs the plugin to be trusted.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::capabilities::Capability;
+use super::manifest::WasmConfig;
+
+/// A preset bundle of sandbox limits, from strictest to loosest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxProfile {
+    /// 16 MiB of memory, 100 million fuel per call, no network, files or
+    /// database writes
+    Strict,
+    /// 256 MiB of memory and the capabilities the user approved, except
+    /// running host programs
+    #[default]
+    Standard,
+    /// No limits beyond the manifest's own, WASI and host programs
+    Trusted,
+}
+
+impl SandboxProfile {
+    /// Most memory a plugin may grow to, in 64 KiB pages
+    pub fn memory_max_pages(self) -> Option<u32> {
+        match self {
+            SandboxProfile::Strict => Some(256),
+            SandboxProfile::Standard => Some(4096),
+            SandboxProfile::Trusted => None,
+        }
+    }
+
+    /// Most fuel a single call may consume
+    pub fn fuel_limit(self) -> Option<u64> {
+        match self {
+            SandboxProfile::Strict => Some(100_000_000),
+            SandboxProfile::Standard | SandboxProfile::Trusted => None,
+        }
+    }
+
+    /// Whether plugins in this sandbox may enable WASI
+    pub fn allows_wasi(self) -> bool {
+        self == SandboxProfile::Trusted
+    }
+
+    /// Sensitive capabilities withheld in this sandbox, whatever the user approved
+    pub fn withheld(self) -> &'static [Capability] {
+        match self {
+            SandboxProfile::Strict => Capability::SENSITIVE,
+            SandboxProfile::Standard => &[Capability::Exec],
+            SandboxProfile::Trusted => &[],
+        }
+    }
+
+    /// Cap a plugin's memory and fuel at this profile's; limits the manifest
+    /// sets itself are kept if they are tighter
+    pub fn restrict(self, config: &mut WasmConfig) {
+        config.memory_max_pages = tighter(config.memory_max_pages, self.memory_max_pages());
+        config.fuel_limit = tighter(config.fuel_limit, self.fuel_limit());
+    }
+}
+
+/// The lower of two optional limits, where None is unlimited
+fn tighter<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl fmt::Display for SandboxProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SandboxProfile::Strict => "strict",
+            SandboxProfile::Standard => "standard",
+            SandboxProfile::Trusted => "trusted",
+        })
+    }
+}
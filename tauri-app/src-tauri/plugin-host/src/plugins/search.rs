@@ -0,0 +1,110 @@
+//! Filtering and text search over installed plugins
+//!
+//! Manifests can declare `categories` (broad groups a catalog is browsed by,
+//! e.g. `images`) and `tags` (free-form keywords). A [`PluginQuery`] narrows
+//! the installed plugins down by those and by words in their names and
+//! descriptions, and ranks what is left so the best matches come first.
+
+use serde::{Deserialize, Serialize};
+
+use super::manifest::PluginManifest;
+
+/// Longest category or tag accepted
+pub const MAX_LABEL_LEN: usize = 64;
+
+/// Most tags a manifest may declare
+pub const MAX_TAGS: usize = 32;
+
+/// Which plugins to list; an empty query matches every plugin
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginQuery {
+    /// Words that must each appear in the plugin's ID, name, description,
+    /// categories or tags, in any of its translations; case is ignored
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Plugins in any of these categories
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Plugins with every one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub plugin_type: Option<String>,
+}
+
+impl PluginQuery {
+    /// How well a plugin matches the query, higher being better; None if it
+    /// does not match
+    pub fn score(&self, manifest: &PluginManifest) -> Option<u32> {
+        let has = |labels: &[String], wanted: &String| labels.iter().any(|label| label.eq_ignore_ascii_case(wanted));
+        if !self.categories.is_empty() && !self.categories.iter().any(|c| has(&manifest.categories, c)) {
+            return None;
+        }
+        if !self.tags.iter().all(|tag| has(&manifest.tags, tag)) {
+            return None;
+        }
+        if self.plugin_type.as_ref().is_some_and(|t| !t.eq_ignore_ascii_case(&manifest.plugin_type)) {
+            return None;
+        }
+
+        let Some(text) = &self.text else {
+            return Some(0);
+        };
+        let lower = |s: &str| s.to_lowercase();
+        let id = lower(&manifest.id());
+        let mut names = vec![lower(&manifest.name)];
+        let mut descriptions = vec![lower(&manifest.description)];
+        for metadata in manifest.i18n.values() {
+            names.extend(metadata.name.as_deref().map(lower));
+            descriptions.extend(metadata.description.as_deref().map(lower));
+        }
+        let labels: Vec<String> = manifest.categories.iter().chain(&manifest.tags).map(|l| lower(l)).collect();
+
+        let mut score = 0;
+        for word in text.split_whitespace().map(lower) {
+            score += if id == word || names.contains(&word) {
+                8
+            } else if id.contains(&word) || names.iter().any(|name| name.contains(&word)) {
+                4
+            } else if labels.contains(&word) {
+                3
+            } else if labels.iter().any(|label| label.contains(&word)) {
+                2
+            } else if descriptions.iter().any(|description| description.contains(&word)) {
+                1
+            } else {
+                return None;
+            };
+        }
+        Some(score)
+    }
+
+    /// The items whose plugin matches, best matches first and ties by ID
+    pub fn rank<T>(&self, items: Vec<T>, manifest: impl Fn(&T) -> &PluginManifest) -> Vec<T> {
+        let mut ranked: Vec<(u32, String, T)> = items
+            .into_iter()
+            .filter_map(|item| {
+                let score = self.score(manifest(&item))?;
+                Some((score, manifest(&item).id(), item))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        ranked.into_iter().map(|(_, _, item)| item).collect()
+    }
+}
+
+/// Check a manifest's categories or tags: 1 to 64 characters, without
+/// surrounding whitespace and without duplicates (ignoring case)
+pub fn label_problems(labels: &[String]) -> Vec<(usize, String)> {
+    let mut problems = Vec::new();
+    for (i, label) in labels.iter().enumerate() {
+        if label.trim().is_empty() || label.len() > MAX_LABEL_LEN {
+            problems.push((i, format!("Must be 1 to {} characters long", MAX_LABEL_LEN)));
+        } else if label.trim() != label {
+            problems.push((i, format!("'{}' has surrounding whitespace", label)));
+        } else if labels[..i].iter().any(|other| other.eq_ignore_ascii_case(label)) {
+            problems.push((i, format!("'{}' is listed twice", label)));
+        }
+    }
+    problems
+}
@@ -0,0 +1,94 @@
+//! Host function calls recorded during executions
+//!
+//! Every built-in host function is built with
+//! [`traced`](crate::host_functions::trace::traced), which adds each call to
+//! the recorder of the [`ExecutionContext`](super::ExecutionContext) it is made
+//! in, shared with the plugins the execution calls. When the execution ends the
+//! plugin manager turns the recording into an [`ExecutionTrace`], keeps the
+//! most recent ones in a [`TraceStore`] and, if the
+//! [`PERSIST_EXECUTION_TRACES_KEY`](crate::settings::PERSIST_EXECUTION_TRACES_KEY)
+//! setting is on, stores them in the database too.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::db::schema::{ExecutionTrace, HostCallTrace};
+
+/// Most host function calls recorded for one execution; later ones are counted but dropped
+pub const MAX_TRACED_CALLS: usize = 10_000;
+
+/// Most execution traces kept in memory
+const MAX_TRACES: usize = 200;
+
+/// Most execution traces kept in the database
+pub const MAX_PERSISTED_TRACES: usize = 1000;
+
+/// The calls recorded so far in an execution
+pub(crate) struct TraceRecorder {
+    started: Instant,
+    calls: Vec<HostCallTrace>,
+    dropped: u64,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            calls: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Microseconds from the start of the recording to `at`
+    pub fn offset_us(&self, at: Instant) -> u64 {
+        at.saturating_duration_since(self.started).as_micros() as u64
+    }
+
+    pub fn record(&mut self, call: HostCallTrace) {
+        if self.calls.len() < MAX_TRACED_CALLS {
+            self.calls.push(call);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// The calls and dropped call count so far, starting a new recording
+    pub fn take(&mut self) -> (Vec<HostCallTrace>, u64) {
+        let taken = (std::mem::take(&mut self.calls), self.dropped);
+        *self = Self::new();
+        taken
+    }
+}
+
+/// Thread-safe store of the most recent execution traces
+#[derive(Debug, Default)]
+pub struct TraceStore {
+    traces: Mutex<VecDeque<ExecutionTrace>>,
+}
+
+impl TraceStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a trace, replacing an earlier one with its execution ID (a retried
+    /// job's) and evicting the oldest when full
+    pub fn push(&self, trace: ExecutionTrace) {
+        let mut traces = self.traces.lock().unwrap();
+        traces.retain(|t| t.execution_id != trace.execution_id);
+        if traces.len() == MAX_TRACES {
+            traces.pop_front();
+        }
+        traces.push_back(trace);
+    }
+
+    pub fn get(&self, execution_id: &str) -> Option<ExecutionTrace> {
+        self.traces
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|trace| trace.execution_id == execution_id)
+            .cloned()
+    }
+}
@@ -0,0 +1,166 @@
+//! Author keys and signed manifests
+//!
+//! Authors sign a plugin with an Ed25519 key. The signature, in the
+//! manifest's `signature`, covers the rest of the manifest (as canonical
+//! JSON, so a plugin.toml and the plugin.json it is installed as sign alike),
+//! the SHA-256 of its modules and that of every other file the manifest
+//! refers to: migrations, catalog assets and the UI assets directory. The host keeps a trust store of approved
+//! author keys in the database: a plugin with a bad signature is refused, and
+//! one that is unsigned or signed by an author not in the store is
+//! quarantined until the user approves it.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::integrity;
+use super::manifest::{self, PluginManifest};
+
+/// A plugin's signature, in its manifest's `signature`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    /// Base64 of the author's Ed25519 public key
+    pub public_key: String,
+    /// Base64 of the Ed25519 signature
+    pub signature: String,
+}
+
+/// A newly generated author key
+pub struct AuthorKey {
+    /// PKCS#8 document holding the private key; keep it secret
+    pub pkcs8: Vec<u8>,
+    /// Base64 of the public key, to publish and add to trust stores
+    pub public_key: String,
+}
+
+/// Generate an Ed25519 key for signing plugins
+pub fn generate_author_key() -> Result<AuthorKey> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| anyhow::anyhow!("Failed to generate a key"))?;
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+        .map_err(|_| anyhow::anyhow!("Generated key is invalid"))?;
+    Ok(AuthorKey {
+        pkcs8: pkcs8.as_ref().to_vec(),
+        public_key: STANDARD.encode(key_pair.public_key().as_ref()),
+    })
+}
+
+/// Check that a string is the base64 of an Ed25519 public key
+pub fn validate_public_key(public_key: &str) -> Result<()> {
+    let bytes = STANDARD.decode(public_key).context("Public key is not valid base64")?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Public key must be 32 bytes, not {}", bytes.len());
+    }
+    Ok(())
+}
+
+/// Sign the plugin in `dir` with a PKCS#8 Ed25519 key, writing the signature
+/// into its manifest; returns the public key
+pub fn sign_plugin(dir: &Path, pkcs8: &[u8]) -> Result<String> {
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+        .map_err(|_| anyhow::anyhow!("Signing key is not a PKCS#8 Ed25519 key"))?;
+    let path = manifest::find_manifest(dir);
+    let (mut value, files) = read_plugin(&path, dir)?;
+    let signature = ManifestSignature {
+        public_key: STANDARD.encode(key_pair.public_key().as_ref()),
+        signature: STANDARD.encode(key_pair.sign(&signed_payload(&value, dir, &files)?).as_ref()),
+    };
+    value["signature"] = serde_json::to_value(&signature)?;
+    let content = if path.extension().is_some_and(|extension| extension == "toml") {
+        toml::to_string_pretty(&value).context("Failed to write manifest as TOML")?.into_bytes()
+    } else {
+        serde_json::to_vec_pretty(&value)?
+    };
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(signature.public_key)
+}
+
+/// Check the signature of the plugin in `dir`: the author's public key if it
+/// is signed, None if it is not, and an error if the signature doesn't match
+pub fn verify_plugin(dir: &Path) -> Result<Option<String>> {
+    let (value, files) = read_plugin(&manifest::find_manifest(dir), dir)?;
+    let Some(signature) = value.get("signature") else {
+        return Ok(None);
+    };
+    let signature: ManifestSignature = serde_json::from_value(signature.clone()).context("Invalid signature")?;
+    validate_public_key(&signature.public_key)?;
+    let public_key = STANDARD.decode(&signature.public_key)?;
+    let signature_bytes = STANDARD.decode(&signature.signature).context("Signature is not valid base64")?;
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&signed_payload(&value, dir, &files)?, &signature_bytes)
+        .map_err(|_| anyhow::anyhow!("Signature does not match the manifest and the files it refers to"))?;
+    Ok(Some(signature.public_key))
+}
+
+/// The files a signature covers besides the manifest
+struct PluginFiles {
+    modules: Vec<PathBuf>,
+    /// The rest, relative to the plugin directory
+    referenced: Vec<String>,
+}
+
+/// A manifest's JSON and the files it is signed with
+fn read_plugin(path: &Path, dir: &Path) -> Result<(Value, PluginFiles)> {
+    let value = manifest::read_manifest_value(path)?;
+    let manifest = PluginManifest::load_from_file(path)?;
+    let files = PluginFiles {
+        modules: manifest.wasm_paths(dir),
+        referenced: manifest.referenced_files(dir)?,
+    };
+    Ok((value, files))
+}
+
+/// What a signature covers: the manifest without its signature as
+/// canonical JSON, then the checksum of its modules, then a line with the
+/// checksum of each other file
+fn signed_payload(manifest: &Value, dir: &Path, files: &PluginFiles) -> Result<Vec<u8>> {
+    let mut unsigned = manifest.clone();
+    if let Some(fields) = unsigned.as_object_mut() {
+        fields.remove("signature");
+    }
+    let mut payload = String::new();
+    write_canonical(&unsigned, &mut payload);
+    payload.push('\n');
+    payload.push_str(&integrity::sha256_modules(&files.modules)?);
+    if !files.referenced.is_empty() {
+        payload.push('\n');
+        payload.push_str(&integrity::sha256_files(dir, &files.referenced)?);
+    }
+    Ok(payload.into_bytes())
+}
+
+/// JSON with object keys sorted and no whitespace
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical(&fields[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
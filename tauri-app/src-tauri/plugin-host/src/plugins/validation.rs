@@ -0,0 +1,236 @@
+//! Dry-run validation of a plugin before it is installed
+//!
+//! [`validate_dir`] runs the checks an install would (manifest, WASM modules,
+//! exported entry points, capabilities, dependencies, assets, migrations and
+//! signature) against a plugin directory and reports each one, without
+//! loading or installing it.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::graph;
+use super::integrity;
+use super::trust;
+use super::capabilities::Capability;
+use super::manifest::{find_manifest, WasmModules, MAIN_MODULE};
+use super::{PluginLoader, PluginManifest};
+
+/// Outcome of one validation step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationCheck {
+    pub name: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+/// Everything learned about a plugin by validating it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Directory or URL that was validated
+    pub source: String,
+    /// Whether every check passed
+    pub valid: bool,
+    pub id: Option<String>,
+    pub version: Option<String>,
+    /// Version of the installed plugin this one would replace
+    pub installed_version: Option<String>,
+    /// Checksum an install would record for the modules
+    pub sha256: Option<String>,
+    /// Functions exported by the main module (or component)
+    pub exports: Vec<String>,
+    pub checks: Vec<ValidationCheck>,
+}
+
+impl ValidationReport {
+    fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            valid: true,
+            id: None,
+            version: None,
+            installed_version: None,
+            sha256: None,
+            exports: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    /// Record a check; `Err` carries the reason it failed
+    fn check(&mut self, name: &str, result: Result<String, String>) -> bool {
+        let passed = result.is_ok();
+        self.valid &= passed;
+        self.checks.push(ValidationCheck {
+            name: name.to_string(),
+            passed,
+            message: result.unwrap_or_else(|e| e),
+        });
+        passed
+    }
+
+    /// A report for a source that could not be fetched at all
+    pub fn failed(source: &str, name: &str, error: String) -> Self {
+        let mut report = Self::new(source);
+        report.check(name, Err(error));
+        report
+    }
+}
+
+/// Validate the plugin in `dir` against the installed plugins
+pub fn validate_dir(
+    source: &str,
+    dir: &Path,
+    installed: &[PluginManifest],
+    is_trusted: impl Fn(&str) -> bool,
+) -> ValidationReport {
+    let mut report = ValidationReport::new(source);
+
+    let manifest = PluginManifest::load_from_file(&find_manifest(dir))
+        .and_then(|manifest| manifest.validate().map(|_| manifest));
+    let manifest = match manifest {
+        Ok(manifest) => {
+            report.check("manifest", Ok(format!("{} {}", manifest.id(), manifest.version)));
+            manifest
+        }
+        Err(e) => {
+            report.check("manifest", Err(format!("{:#}", e)));
+            return report;
+        }
+    };
+    let id = manifest.id();
+    report.installed_version = installed
+        .iter()
+        .find(|plugin| plugin.id() == id)
+        .map(|plugin| plugin.version.clone());
+    report.id = Some(id.clone());
+    report.version = Some(manifest.version.clone());
+
+    // Every module must exist and be valid WebAssembly
+    let mut modules: Vec<(&str, Vec<u8>)> = Vec::new();
+    let mut module_errors = Vec::new();
+    for path in manifest.wasm_module.paths() {
+        let result = std::fs::read(dir.join(path))
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(|bytes| {
+                let mut validator = wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all());
+                validator
+                    .validate_all(&bytes)
+                    .map(|_| bytes)
+                    .map_err(|e| format!("{}: {}", path, e))
+            });
+        match result {
+            Ok(bytes) => modules.push((path, bytes)),
+            Err(e) => module_errors.push(e),
+        }
+    }
+    let wasm = if module_errors.is_empty() {
+        Ok(format!("{} valid module(s)", modules.len()))
+    } else {
+        Err(module_errors.join("; "))
+    };
+    if !report.check("wasm", wasm) {
+        return report;
+    }
+    let bytes: Vec<&[u8]> = modules.iter().map(|(_, bytes)| bytes.as_slice()).collect();
+    report.sha256 = Some(integrity::sha256_bytes(&bytes));
+
+    // Entry points must be exported; Extism calls them on the main module
+    let exports_of = |module: &str| -> Vec<String> {
+        let path = manifest.wasm_module.module_path(module);
+        modules
+            .iter()
+            .find(|(p, _)| Some(*p) == path)
+            .map(|(_, bytes)| PluginLoader::wasm_exports(bytes))
+            .unwrap_or_default()
+    };
+    let main_exports = exports_of(MAIN_MODULE);
+    let is_component = modules
+        .iter()
+        .any(|(_, bytes)| wasmparser::Parser::is_component(bytes));
+    let missing: Vec<String> = manifest
+        .entry_points
+        .iter()
+        .filter(|entry_point| {
+            if is_component {
+                return !main_exports.contains(&entry_point.name);
+            }
+            !main_exports.contains(&entry_point.function)
+                || entry_point
+                    .module
+                    .as_deref()
+                    .is_some_and(|module| !exports_of(module).contains(&entry_point.function))
+        })
+        .map(|entry_point| format!("{} -> {}", entry_point.name, entry_point.function))
+        .collect();
+    let exports = if missing.is_empty() {
+        Ok(format!("{} entry point(s) exported", manifest.entry_points.len()))
+    } else {
+        Err(format!("Not exported: {}", missing.join(", ")))
+    };
+    report.check("exports", exports);
+    report.exports = main_exports;
+
+    // Privileged features
+    let capabilities = if is_component && manifest.wasm_config.wasi {
+        Err("WASI is not available to component plugins".to_string())
+    } else if is_component && matches!(manifest.wasm_module, WasmModules::Multiple(_)) {
+        Err("Component plugins must be a single module".to_string())
+    } else if manifest.wasm_config.wasi && !manifest.declares(&Capability::Wasi) {
+        Err(format!("Enables WASI without declaring the '{}' capability", Capability::Wasi))
+    } else if manifest.wasm_config.wasi && !is_trusted(&id) {
+        Err("Enables WASI but is not trusted".to_string())
+    } else if manifest.capabilities.is_empty() {
+        Ok("No capabilities requested".to_string())
+    } else {
+        let capabilities: Vec<String> = manifest.capabilities.iter().map(Capability::to_string).collect();
+        Ok(format!("Requests: {}", capabilities.join(", ")))
+    };
+    report.check("capabilities", capabilities);
+
+    // Plugins it calls must be installed, at versions in the declared ranges
+    let mut absent: Vec<&str> = manifest
+        .dependencies
+        .keys()
+        .filter(|dep| !installed.iter().any(|p| &p.id() == *dep || &p.name == *dep))
+        .map(String::as_str)
+        .collect();
+    absent.sort();
+    let incompatible = graph::version_issues(&manifest, installed);
+    let dependencies = if !absent.is_empty() {
+        Err(format!("Not installed: {}", absent.join(", ")))
+    } else if !incompatible.is_empty() {
+        Err(incompatible.join("; "))
+    } else {
+        Ok(format!("{} dependency(ies) installed", manifest.dependencies.len()))
+    };
+    report.check("dependencies", dependencies);
+
+    let assets = manifest
+        .assets
+        .validate_files(dir)
+        .map(|_| format!("{} asset(s)", manifest.assets.paths().count()))
+        .map_err(|e| format!("{:#}", e));
+    report.check("assets", assets);
+
+    let missing: Vec<&str> = manifest
+        .migrations
+        .iter()
+        .filter(|path| !dir.join(path).is_file())
+        .map(String::as_str)
+        .collect();
+    let migrations = if missing.is_empty() {
+        Ok(format!("{} migration(s)", manifest.migrations.len()))
+    } else {
+        Err(format!("Missing: {}", missing.join(", ")))
+    };
+    report.check("migrations", migrations);
+
+    // A bad signature is refused; an unsigned plugin only needs approval
+    let signature = match trust::verify_plugin(dir) {
+        Ok(Some(public_key)) => Ok(format!("Signed by {}", public_key)),
+        Ok(None) => Ok("Not signed".to_string()),
+        Err(e) => Err(format!("{:#}", e)),
+    };
+    report.check("signature", signature);
+
+    report
+}
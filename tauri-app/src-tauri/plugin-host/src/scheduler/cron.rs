@@ -0,0 +1,252 @@
+//! Minimal five-field cron expressions
+//!
+//! Supports `minute hour day-of-month month day-of-week` with `*`, lists
+//! (`1,15`), ranges (`1-5`) and steps (`*/15`, `0-30/10`). Day-of-week runs
+//! from 0 (Sunday) to 6, with 7 accepted as Sunday. As in standard cron, when
+//! both day fields are restricted a time matches if either of them does.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+/// How far ahead to search for the next matching time
+const MAX_SEARCH_YEARS: i32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            anyhow::bail!("Cron expression must have 5 fields, got {}: '{}'", fields.len(), expr);
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7).context("Invalid day-of-week field")?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).context("Invalid minute field")?,
+            hours: parse_field(hour, 0, 23).context("Invalid hour field")?,
+            days_of_month: parse_field(dom, 1, 31).context("Invalid day-of-month field")?,
+            months: parse_field(month, 1, 12).context("Invalid month field")?,
+            days_of_week,
+            dom_restricted: dom != "*",
+            dow_restricted: dow != "*",
+        })
+    }
+
+    /// The first matching time strictly after `after`, in the timezone of `after`
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(366 * MAX_SEARCH_YEARS as i64);
+        let mut t = start;
+
+        while t < limit {
+            if !contains(self.months, t.month()) {
+                t = first_of_next_month(t)?;
+                continue;
+            }
+            if !self.day_matches(t.date()) {
+                t = (t.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !contains(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !contains(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+
+            match tz.from_local_datetime(&t) {
+                LocalResult::Single(time) => return Some(time),
+                LocalResult::Ambiguous(earliest, _) => return Some(earliest),
+                // Skipped by a DST change
+                LocalResult::None => t += Duration::minutes(1),
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = contains(self.days_of_month, date.day());
+        let dow = contains(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+fn contains(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn first_of_next_month(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = if t.month() == 12 {
+        (t.year() + 1, 1)
+    } else {
+        (t.year(), t.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// Parse one field into a bitset of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            anyhow::bail!("Step must be positive");
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            let value: u32 = range.parse().with_context(|| format!("Invalid value '{}'", range))?;
+            // `5/10` means "from 5 to the end, every 10"
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            anyhow::bail!("'{}' is outside {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    /// The first `count` times `expr` fires after `after`
+    fn runs(expr: &str, after: DateTime<Utc>, count: usize) -> Vec<DateTime<Utc>> {
+        let expr = CronExpr::parse(expr).unwrap();
+        std::iter::successors(expr.next_after(&after), |time| expr.next_after(time))
+            .take(count)
+            .collect()
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            runs("0 9-11 * * *", at(2024, 1, 1, 10, 30), 3),
+            [at(2024, 1, 1, 11, 0), at(2024, 1, 2, 9, 0), at(2024, 1, 2, 10, 0)]
+        );
+        // Monday to Friday; 2024-01-05 is a Friday
+        assert_eq!(
+            runs("30 8 * * 1-5", at(2024, 1, 5, 9, 0), 2),
+            [at(2024, 1, 8, 8, 30), at(2024, 1, 9, 8, 30)]
+        );
+    }
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(
+            runs("*/15 * * * *", at(2024, 1, 1, 10, 7), 4),
+            [at(2024, 1, 1, 10, 15), at(2024, 1, 1, 10, 30), at(2024, 1, 1, 10, 45), at(2024, 1, 1, 11, 0)]
+        );
+        assert_eq!(
+            runs("0-30/10 8 * * *", at(2024, 1, 1, 0, 0), 5),
+            [at(2024, 1, 1, 8, 0), at(2024, 1, 1, 8, 10), at(2024, 1, 1, 8, 20), at(2024, 1, 1, 8, 30), at(2024, 1, 2, 8, 0)]
+        );
+        // A single value with a step runs to the end of the field
+        assert_eq!(
+            runs("5/20 * * * *", at(2024, 1, 1, 10, 7), 3),
+            [at(2024, 1, 1, 10, 25), at(2024, 1, 1, 10, 45), at(2024, 1, 1, 11, 5)]
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            runs("0 0 1,15 * *", at(2024, 1, 10, 0, 0), 3),
+            [at(2024, 1, 15, 0, 0), at(2024, 2, 1, 0, 0), at(2024, 2, 15, 0, 0)]
+        );
+        assert_eq!(
+            runs("0,30 6,18 * * *", at(2024, 1, 1, 6, 15), 3),
+            [at(2024, 1, 1, 6, 30), at(2024, 1, 1, 18, 0), at(2024, 1, 1, 18, 30)]
+        );
+    }
+
+    #[test]
+    fn test_day_of_month_and_day_of_week() {
+        // Either field matching is enough: the 13th, or any Friday.
+        // 2024-09-01 is a Sunday; 2024-10-13 is a Sunday too.
+        let days: Vec<u32> = runs("0 12 13 * 5", at(2024, 9, 1, 0, 0), 7).iter().map(|time| time.day()).collect();
+        assert_eq!(days, [6, 13, 20, 27, 4, 11, 13]);
+        // Only one restricted field decides alone
+        assert_eq!(runs("0 0 13 * *", at(2024, 9, 1, 0, 0), 1), [at(2024, 9, 13, 0, 0)]);
+        // 7 is Sunday, like 0
+        assert_eq!(runs("0 0 * * 7", at(2024, 9, 2, 0, 0), 1), [at(2024, 9, 8, 0, 0)]);
+        assert_eq!(CronExpr::parse("0 0 * * 7").unwrap(), CronExpr::parse("0 0 * * 0").unwrap());
+    }
+
+    #[test]
+    fn test_month_and_year_rollover() {
+        // Months without a 31st are skipped
+        assert_eq!(
+            runs("0 0 31 * *", at(2024, 1, 31, 0, 0), 3),
+            [at(2024, 3, 31, 0, 0), at(2024, 5, 31, 0, 0), at(2024, 7, 31, 0, 0)]
+        );
+        assert_eq!(runs("30 23 31 12 *", at(2024, 12, 31, 23, 30), 1), [at(2025, 12, 31, 23, 30)]);
+        assert_eq!(runs("0 0 29 2 *", at(2024, 3, 1, 0, 0), 1), [at(2028, 2, 29, 0, 0)]);
+        // A date that never comes is given up on
+        assert_eq!(CronExpr::parse("0 0 30 2 *").unwrap().next_after(&at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let invalid = [
+            ("", "must have 5 fields"),
+            ("* * * *", "must have 5 fields"),
+            ("* * * * * *", "must have 5 fields"),
+            ("60 * * * *", "Invalid minute field"),
+            ("* 24 * * *", "Invalid hour field"),
+            ("* * 0 * *", "Invalid day-of-month field"),
+            ("* * * 13 *", "Invalid month field"),
+            ("* * * * 8", "Invalid day-of-week field"),
+            ("*/0 * * * *", "Invalid minute field"),
+            ("5-1 * * * *", "Invalid minute field"),
+            ("a * * * *", "Invalid minute field"),
+            ("1-x * * * *", "Invalid minute field"),
+            ("1,,2 * * * *", "Invalid minute field"),
+        ];
+        for (expr, error) in invalid {
+            let message = format!("{:#}", CronExpr::parse(expr).unwrap_err());
+            assert!(message.contains(error), "Unexpected error for '{}': {}", expr, message);
+        }
+    }
+}
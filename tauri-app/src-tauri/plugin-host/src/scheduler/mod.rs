@@ -0,0 +1,350 @@
+//! Scheduled plugin execution
+//!
+//! Schedules come from plugin manifests (`schedules`) or are created at
+//! runtime, and are stored in the `schedules` table. A supervised background
+//! task checks for due schedules every second and submits each run as a
+//! background job, so results show up in the job history. Runs missed while
+//! the app was closed are coalesced into a single run at startup, and a run
+//! is skipped if the schedule's previous job is still in progress. Schedules
+//! of disabled plugins are paused; the first run after the plugin is enabled
+//! again stands in for the runs it missed.
+
+mod cron;
+
+pub use cron::CronExpr;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::db::schema::Schedule;
+use crate::db::{operations, Database};
+use crate::ids::{self, IdKind};
+use crate::jobs::{JobManager, JobScope, JobStatus};
+use crate::plugins::PluginManifest;
+
+/// How often the scheduler checks for due schedules
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+const SOURCE_MANIFEST: &str = "manifest";
+const SOURCE_API: &str = "api";
+
+/// When a schedule fires
+enum Trigger {
+    Interval(i64),
+    Cron(CronExpr),
+}
+
+impl Trigger {
+    fn new(interval_seconds: Option<i64>, cron: Option<&str>) -> Result<Self> {
+        match (interval_seconds, cron) {
+            (Some(seconds), None) if seconds > 0 => Ok(Trigger::Interval(seconds)),
+            (Some(_), None) => anyhow::bail!("Schedule interval must be positive"),
+            (None, Some(expr)) => Ok(Trigger::Cron(CronExpr::parse(expr)?)),
+            _ => anyhow::bail!("Schedule needs exactly one of an interval or a cron expression"),
+        }
+    }
+
+    /// Unix timestamp of the next run after `now`
+    fn next_run_after(&self, now: i64) -> Result<i64> {
+        match self {
+            Trigger::Interval(seconds) => Ok(now + seconds),
+            Trigger::Cron(expr) => {
+                let now = chrono::DateTime::from_timestamp(now, 0)
+                    .context("Invalid timestamp")?
+                    .with_timezone(&Local);
+                expr.next_after(&now)
+                    .map(|next| next.timestamp())
+                    .context("Cron expression never fires")
+            }
+        }
+    }
+}
+
+pub struct Scheduler {
+    database: Arc<Database>,
+    jobs: Arc<JobManager>,
+    /// Plugins whose schedules don't run
+    paused: RwLock<HashSet<String>>,
+}
+
+impl Scheduler {
+    pub fn new(database: Arc<Database>, jobs: Arc<JobManager>) -> Self {
+        Self {
+            database,
+            jobs,
+            paused: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Replace the set of plugins whose schedules are paused
+    pub fn set_paused_plugins(&self, plugins: impl IntoIterator<Item = String>) {
+        *self.paused.write().unwrap() = plugins.into_iter().collect();
+    }
+
+    /// Bring a plugin's manifest schedules in line with its manifest
+    ///
+    /// Unchanged schedules keep their next run time, so restarting the app
+    /// doesn't postpone long intervals.
+    pub fn sync_manifest(&self, manifest: &PluginManifest) -> Result<()> {
+        let existing: Vec<Schedule> = self
+            .database
+            .with_connection(|conn| operations::list_schedules(conn, Some(&manifest.id())))?
+            .into_iter()
+            .filter(|schedule| schedule.source == SOURCE_MANIFEST)
+            .collect();
+        let mut kept = Vec::new();
+
+        for spec in &manifest.schedules {
+            let input = serde_json::to_string(&spec.input)?;
+            let interval_seconds = spec.every_seconds.map(|s| s as i64);
+
+            let unchanged = existing.iter().find(|schedule| {
+                schedule.function == spec.function
+                    && schedule.input == input
+                    && schedule.interval_seconds == interval_seconds
+                    && schedule.cron == spec.cron
+                    && !kept.contains(&schedule.id)
+            });
+            if let Some(schedule) = unchanged {
+                kept.push(schedule.id.clone());
+                continue;
+            }
+
+            match self.insert(&manifest.id(), &spec.function, input, interval_seconds, spec.cron.clone(), SOURCE_MANIFEST) {
+                Ok(schedule) => kept.push(schedule.id),
+                Err(e) => tracing::warn!(
+                    "Skipping invalid schedule for {}::{}: {:#}",
+                    manifest.id(),
+                    spec.function,
+                    e
+                ),
+            }
+        }
+
+        for schedule in existing.iter().filter(|schedule| !kept.contains(&schedule.id)) {
+            self.database
+                .with_connection(|conn| operations::delete_schedule(conn, &schedule.id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a schedule at runtime
+    pub fn create(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: &serde_json::Value,
+        interval_seconds: Option<i64>,
+        cron: Option<String>,
+    ) -> Result<Schedule> {
+        let input = serde_json::to_string(input)?;
+        self.insert(plugin_name, function, input, interval_seconds, cron, SOURCE_API)
+    }
+
+    fn insert(
+        &self,
+        plugin_name: &str,
+        function: &str,
+        input: String,
+        interval_seconds: Option<i64>,
+        cron: Option<String>,
+        source: &str,
+    ) -> Result<Schedule> {
+        let trigger = Trigger::new(interval_seconds, cron.as_deref())?;
+        let now = chrono::Utc::now().timestamp();
+
+        let schedule = Schedule {
+            id: ids::new_id(IdKind::Schedule),
+            plugin_name: plugin_name.to_string(),
+            function: function.to_string(),
+            input,
+            interval_seconds,
+            cron,
+            source: source.to_string(),
+            enabled: true,
+            next_run_at: trigger.next_run_after(now)?,
+            last_run_at: None,
+            last_job_id: None,
+            created_at: now,
+        };
+
+        self.database
+            .with_connection(|conn| operations::create_schedule(conn, &schedule))?;
+        Ok(schedule)
+    }
+
+    pub fn list(&self, plugin_name: Option<&str>) -> Result<Vec<Schedule>> {
+        Ok(self
+            .database
+            .with_connection(|conn| operations::list_schedules(conn, plugin_name))?)
+    }
+
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<bool> {
+        Ok(self
+            .database
+            .with_connection(|conn| operations::set_schedule_enabled(conn, id, enabled))?)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .database
+            .with_connection(|conn| operations::delete_schedule(conn, id))?)
+    }
+
+    /// Remove all schedules of an uninstalled plugin
+    pub fn remove_plugin(&self, plugin_name: &str) -> Result<()> {
+        self.database
+            .with_connection(|conn| operations::delete_plugin_schedules(conn, plugin_name, None))?;
+        Ok(())
+    }
+
+    /// Scheduler loop, run under the task supervisor
+    pub async fn run(self: Arc<Self>) -> Result<(), String> {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.tick().await.map_err(|e| format!("{:#}", e))?;
+        }
+    }
+
+    /// Run the due schedules; one that fails is logged and the rest still run
+    async fn tick(&self) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let due = self
+            .database
+            .with_connection(|conn| operations::get_due_schedules(conn, now))?;
+
+        for schedule in due {
+            if self.paused.read().unwrap().contains(&schedule.plugin_name) {
+                continue;
+            }
+            if let Err(e) = self.run_due(&schedule, now) {
+                tracing::error!(
+                    "Failed to run schedule {} of {}::{}: {:#}",
+                    schedule.id,
+                    schedule.plugin_name,
+                    schedule.function,
+                    e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Submit a due schedule's run and set its next one
+    ///
+    /// Schedules that can never run are disabled. A run that fails to submit
+    /// is skipped, so the schedule tries again at its next run.
+    fn run_due(&self, schedule: &Schedule, now: i64) -> Result<()> {
+        let next_run_at = Trigger::new(schedule.interval_seconds, schedule.cron.as_deref())
+            .and_then(|trigger| trigger.next_run_after(now));
+        let input = serde_json::from_str(&schedule.input).context("Stored input is not JSON");
+        let (next_run_at, input) = match (next_run_at, input) {
+            (Ok(next_run_at), Ok(input)) => (next_run_at, input),
+            (Err(e), _) | (_, Err(e)) => {
+                tracing::error!("Disabling invalid schedule {}: {:#}", schedule.id, e);
+                self.set_enabled(&schedule.id, false)?;
+                return Ok(());
+            }
+        };
+
+        let job_id = if self.previous_run_active(schedule)? {
+            tracing::warn!(
+                "Skipping run of {}::{}; previous run still in progress",
+                schedule.plugin_name,
+                schedule.function
+            );
+            schedule.last_job_id.clone()
+        } else {
+            match self.jobs.submit(&schedule.plugin_name, &schedule.function, input, 1, None) {
+                Ok(job_id) => Some(job_id),
+                Err(e) => {
+                    tracing::error!(
+                        "Skipping run of {}::{}; it could not be submitted: {:#}",
+                        schedule.plugin_name,
+                        schedule.function,
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        self.database.with_connection(|conn| {
+            operations::record_schedule_run(conn, &schedule.id, now, job_id.as_deref(), next_run_at)
+        })?;
+        Ok(())
+    }
+
+    fn previous_run_active(&self, schedule: &Schedule) -> Result<bool> {
+        let Some(job_id) = &schedule.last_job_id else {
+            return Ok(false);
+        };
+        Ok(self
+            .jobs
+            .get(job_id, JobScope::All)?
+            .is_some_and(|job| job.status == JobStatus::Queued.as_str() || job.status == JobStatus::Running.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations;
+    use crate::plugins::PluginManager;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_a_schedule_that_fails_does_not_stop_the_others() {
+        let root = std::env::temp_dir().join(format!("a2e-scheduler-{}", uuid::Uuid::new_v4()));
+        let database = Database::new(PathBuf::from(":memory:")).unwrap();
+        database.with_connection(|conn| Ok(migrations::run_migrations(conn))).unwrap().unwrap();
+        let database = Arc::new(database);
+        let plugins = PluginManager::new_with_database(root.join("plugins"), database.clone()).unwrap();
+        let jobs = JobManager::new(database.clone(), Arc::new(tokio::sync::RwLock::new(plugins)), None, 1).unwrap();
+        let scheduler = Scheduler::new(database.clone(), Arc::new(jobs));
+        let schedule = |id: &str, interval_seconds: Option<i64>, cron: Option<&str>, input: &str| Schedule {
+            id: id.to_string(),
+            plugin_name: "greeter".to_string(),
+            function: "greet".to_string(),
+            input: input.to_string(),
+            interval_seconds,
+            cron: cron.map(str::to_string),
+            source: SOURCE_API.to_string(),
+            enabled: true,
+            next_run_at: 0,
+            last_run_at: None,
+            last_job_id: None,
+            created_at: 0,
+        };
+        let schedules = [
+            // February never has a 30th
+            schedule("never", None, Some("0 0 30 2 *"), "null"),
+            schedule("corrupt", Some(60), None, "{"),
+            schedule("hourly", Some(3600), None, "null"),
+        ];
+        for schedule in &schedules {
+            database.with_connection(|conn| operations::create_schedule(conn, schedule)).unwrap();
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        scheduler.tick().await.unwrap();
+        let schedules: HashMap<String, Schedule> = scheduler
+            .list(None)
+            .unwrap()
+            .into_iter()
+            .map(|schedule| (schedule.id.clone(), schedule))
+            .collect();
+        assert!(!schedules["never"].enabled && !schedules["corrupt"].enabled);
+        let hourly = &schedules["hourly"];
+        assert!(hourly.enabled && hourly.last_job_id.is_some());
+        assert!(hourly.next_run_at >= now + 3600);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
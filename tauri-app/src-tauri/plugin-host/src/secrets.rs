@@ -0,0 +1,178 @@
+//! Secrets plugin config refers to instead of holding them
+//!
+//! A `wasm_config.config` value (or runtime override) of `secret://<name>` is
+//! replaced with the named secret when the plugin is loaded, so API keys are
+//! never written to `plugin.json` or the plugin config table. Secrets live in
+//! a [`SecretStore`]: by default a [`FileSecretStore`] encrypting them with a
+//! key kept next to it, while embedders with access to an OS keychain can
+//! plug that in instead.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Prefix of config values that refer to a secret
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// Longest secret name accepted
+const MAX_NAME_LEN: usize = 128;
+
+/// Where secrets are kept; implementations must be safe to share between plugins
+pub trait SecretStore: Send + Sync {
+    /// A secret's value, or None if it is not set
+    fn get(&self, name: &str) -> Result<Option<String>>;
+
+    /// Create or replace a secret
+    fn set(&self, name: &str, value: &str) -> Result<()>;
+
+    /// Remove a secret; false if it was not set
+    fn delete(&self, name: &str) -> Result<bool>;
+
+    /// Names of the secrets set, sorted
+    fn names(&self) -> Result<Vec<String>>;
+}
+
+/// The secret a config value refers to, if it is a `secret://` reference
+pub fn reference(value: &str) -> Option<&str> {
+    value.strip_prefix(SECRET_SCHEME)
+}
+
+/// Check a secret name: up to 128 letters, digits, `_`, `-` and `.`
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        anyhow::bail!("Secret names must be 1 to {} characters long", MAX_NAME_LEN);
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+        anyhow::bail!("Secret name '{}' may only contain letters, digits, '_', '-' and '.'", name);
+    }
+    Ok(())
+}
+
+/// Replace every `secret://` reference in a plugin's config with the secret's value
+pub fn resolve(config: &mut HashMap<String, String>, store: &dyn SecretStore) -> Result<()> {
+    for (key, value) in config.iter_mut() {
+        let Some(name) = reference(value) else {
+            continue;
+        };
+        *value = store
+            .get(name)?
+            .with_context(|| format!("Config value '{}' refers to secret '{}', which is not set", key, name))?;
+    }
+    Ok(())
+}
+
+/// Secrets encrypted with AES-256-GCM in `secrets.json`, under a key in
+/// `secrets.key` that is created on first use and readable only by the owner
+pub struct FileSecretStore {
+    dir: PathBuf,
+    /// Serializes read-modify-write cycles of the secrets file
+    lock: Mutex<()>,
+}
+
+impl FileSecretStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, lock: Mutex::new(()) }
+    }
+
+    fn key(&self) -> Result<LessSafeKey> {
+        let path = self.dir.join("secrets.key");
+        let bytes = if path.exists() {
+            std::fs::read(&path).with_context(|| format!("Failed to read secrets key {:?}", path))?
+        } else {
+            let mut bytes = vec![0u8; AES_256_GCM.key_len()];
+            SystemRandom::new()
+                .fill(&mut bytes)
+                .map_err(|_| anyhow::anyhow!("Failed to generate secrets key"))?;
+            std::fs::create_dir_all(&self.dir)
+                .with_context(|| format!("Failed to create secrets directory {:?}", self.dir))?;
+            write_private(&path, &bytes)?;
+            bytes
+        };
+        let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| anyhow::anyhow!("Invalid secrets key {:?}", path))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.dir.join("secrets.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_slice(&content).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create secrets directory {:?}", self.dir))?;
+        write_private(&self.dir.join("secrets.json"), &serde_json::to_vec_pretty(secrets)?)
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let _guard = self.lock.lock().unwrap();
+        let Some(sealed) = self.load()?.remove(name) else {
+            return Ok(None);
+        };
+        let mut sealed = BASE64.decode(sealed).with_context(|| format!("Secret '{}' is corrupt", name))?;
+        if sealed.len() < NONCE_LEN {
+            anyhow::bail!("Secret '{}' is corrupt", name);
+        }
+        let mut ciphertext = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).map_err(|_| anyhow::anyhow!("Secret '{}' is corrupt", name))?;
+        // The name is authenticated too, so values cannot be swapped between secrets
+        let plaintext = self
+            .key()?
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut ciphertext)
+            .map_err(|_| anyhow::anyhow!("Secret '{}' cannot be decrypted with this key", name))?;
+        Ok(Some(String::from_utf8(plaintext.to_vec())?))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        let _guard = self.lock.lock().unwrap();
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow::anyhow!("Failed to generate nonce"))?;
+        let mut sealed = value.as_bytes().to_vec();
+        self.key()?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(name.as_bytes()), &mut sealed)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secret '{}'", name))?;
+
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), BASE64.encode([nonce.as_slice(), &sealed].concat()));
+        self.save(&secrets)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+        if secrets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&secrets)?;
+        Ok(true)
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.load()?.into_keys().collect())
+    }
+}
+
+/// Write a file only its owner can read
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {:?}", path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict permissions of {:?}", path))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,367 @@
+//! Application settings persisted as JSON values in the `settings` table
+//!
+//! Every write is broadcast as a [`SettingChange`], so long-lived subsystems
+//! and plugins can react to new values without a restart.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use tokio::sync::broadcast;
+
+use crate::db::{operations, Database};
+
+/// Setting key for worker pool sizes
+pub const WORKER_COUNTS_KEY: &str = "workers";
+
+/// Setting key for the names of trusted plugins
+pub const TRUSTED_PLUGINS_KEY: &str = "trusted_plugins";
+
+/// Setting key for the stricter sandboxes plugins were moved to, by plugin ID
+pub const PLUGIN_SANDBOXES_KEY: &str = "plugin_sandboxes";
+
+/// Setting key for how often (in seconds) installed plugin modules are re-verified
+pub const PLUGIN_VERIFY_INTERVAL_KEY: &str = "plugin_verify_interval_secs";
+
+/// Setting key for the release channel application updates come from
+pub const UPDATE_CHANNEL_KEY: &str = "update_channel";
+
+/// Setting key for how record IDs are generated
+pub const ID_STRATEGY_KEY: &str = "id_strategy";
+
+/// Setting key for where file-producing plugins write their results
+pub const OUTPUT_POLICY_KEY: &str = "output_policy";
+
+/// Setting key for how many days trashed files are kept before they are purged
+pub const TRASH_RETENTION_DAYS_KEY: &str = "trash_retention_days";
+
+/// Setting key for the IDs of installed plugins that are not loaded
+pub const DISABLED_PLUGINS_KEY: &str = "disabled_plugins";
+
+/// Setting key for the named sets of plugins that can be enabled together
+pub const PLUGIN_PROFILES_KEY: &str = "plugin_profiles";
+
+/// Setting key for the name of the plugin profile applied last
+pub const ACTIVE_PLUGIN_PROFILE_KEY: &str = "active_plugin_profile";
+
+/// Setting key for the app version that last ran, to notice updates
+pub const LAST_APP_VERSION_KEY: &str = "last_app_version";
+
+/// Setting key for how long (in seconds) calls may keep running on a plugin
+/// instance that was replaced by a reload or upgrade
+pub const PLUGIN_DRAIN_TIMEOUT_KEY: &str = "plugin_drain_timeout_secs";
+
+/// Setting key for hosts no plugin may make HTTP requests to, whatever its
+/// manifest allows
+pub const NETWORK_DENIED_HOSTS_KEY: &str = "network_denied_hosts";
+
+/// Setting key for the SMTP server plugins send email through; see
+/// [`SmtpSettings`](crate::mail::SmtpSettings)
+pub const SMTP_KEY: &str = "smtp";
+
+/// Setting key for whether execution traces are stored in the database as
+/// well as kept in memory
+pub const PERSIST_EXECUTION_TRACES_KEY: &str = "persist_execution_traces";
+
+/// Default plugin re-verification interval
+pub const DEFAULT_PLUGIN_VERIFY_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Default trash retention
+pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Default drain timeout of replaced plugin instances
+pub const DEFAULT_PLUGIN_DRAIN_TIMEOUT_SECS: u64 = 30;
+
+/// A setting that was written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub changed_at: i64,
+}
+
+/// Process-wide, as stores are created wherever settings are needed
+static CHANGES: LazyLock<broadcast::Sender<SettingChange>> = LazyLock::new(|| broadcast::channel(64).0);
+
+/// Typed access to the key-value settings store
+pub struct SettingsStore {
+    database: Arc<Database>,
+}
+
+impl SettingsStore {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+
+    /// Get a setting, returning None if it has never been set
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let raw = self
+            .database
+            .with_connection(|conn| operations::get_setting(conn, key))?;
+
+        raw.map(|value| {
+            serde_json::from_str(&value).with_context(|| format!("Invalid value for setting '{}'", key))
+        })
+        .transpose()
+    }
+
+    /// Get a setting, falling back to the type's default
+    pub fn get_or_default<T: DeserializeOwned + Default>(&self, key: &str) -> Result<T> {
+        Ok(self.get(key)?.unwrap_or_default())
+    }
+
+    /// Store a setting
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        let now = chrono::Utc::now().timestamp();
+        self.database
+            .with_connection(|conn| operations::set_setting(conn, key, &value.to_string(), now))?;
+        Self::publish(key, value, now);
+        Ok(())
+    }
+
+    /// Read, modify and store a setting atomically, so concurrent updates of
+    /// the same key are not lost; returns the stored value
+    pub fn update<T, F>(&self, key: &str, f: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        F: FnOnce(&mut T),
+    {
+        let now = chrono::Utc::now().timestamp();
+        let (value, json) = self.database.with_connection(|conn| {
+            let mut value: T = match operations::get_setting(conn, key)? {
+                Some(raw) => serde_json::from_str(&raw)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+                None => T::default(),
+            };
+            f(&mut value);
+            let json = serde_json::to_value(&value)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            operations::set_setting(conn, key, &json.to_string(), now)?;
+            Ok((value, json))
+        })?;
+        Self::publish(key, json, now);
+        Ok(value)
+    }
+
+    /// Receive every setting written from now on
+    pub fn subscribe() -> broadcast::Receiver<SettingChange> {
+        CHANGES.subscribe()
+    }
+
+    fn publish(key: &str, value: serde_json::Value, changed_at: i64) {
+        // Nobody listening is fine
+        let _ = CHANGES.send(SettingChange {
+            key: key.to_string(),
+            value,
+            changed_at,
+        });
+    }
+}
+
+/// Sizes of the host's worker pools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerCounts {
+    /// Maximum number of concurrent plugin executions
+    pub plugin_workers: usize,
+    /// Number of background job workers
+    pub job_workers: usize,
+    /// Maximum number of blocking threads (database and file I/O); applied at startup
+    pub db_blocking_threads: usize,
+}
+
+impl Default for WorkerCounts {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self {
+            plugin_workers: cpus,
+            job_workers: 2,
+            // Tokio's own default
+            db_blocking_threads: 512,
+        }
+    }
+}
+
+impl WorkerCounts {
+    /// Upper bound for any single pool
+    pub const MAX_WORKERS: usize = 1024;
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, count) in [
+            ("plugin_workers", self.plugin_workers),
+            ("job_workers", self.job_workers),
+            ("db_blocking_threads", self.db_blocking_threads),
+        ] {
+            if count == 0 || count > Self::MAX_WORKERS {
+                anyhow::bail!("{} must be between 1 and {}", name, Self::MAX_WORKERS);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read worker counts before the async runtime and app state exist
+    ///
+    /// Falls back to defaults if the database or settings table isn't there yet.
+    pub fn load_for_startup(db_path: &Path) -> Self {
+        if !db_path.exists() {
+            return Self::default();
+        }
+
+        Database::new(db_path.to_path_buf())
+            .ok()
+            .map(|db| SettingsStore::new(Arc::new(db)))
+            .and_then(|settings| settings.get::<WorkerCounts>(WORKER_COUNTS_KEY).ok().flatten())
+            .filter(|counts| counts.validate().is_ok())
+            .unwrap_or_default()
+    }
+}
+
+/// A named set of plugins; applying it enables these and disables every
+/// other installed plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginProfile {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// IDs of the plugins to enable
+    pub plugins: Vec<String>,
+}
+
+impl PluginProfile {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            anyhow::bail!("Profile name cannot be empty");
+        }
+        if let Some(plugin) = self.plugins.iter().find(|plugin| plugin.trim().is_empty()) {
+            anyhow::bail!("Invalid plugin ID in profile '{}': {:?}", self.name, plugin);
+        }
+        Ok(())
+    }
+}
+
+/// Placeholders an output file name template may use
+pub const OUTPUT_TEMPLATE_PLACEHOLDERS: &[&str] = &["{name}", "{ext}", "{date}", "{time}", "{plugin}", "{preset}"];
+
+/// What to do when an output file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Overwrite,
+    /// Leave the existing file and don't write the output
+    Skip,
+    /// Write to the first free `<stem>-<n>.<ext>`
+    #[default]
+    Rename,
+}
+
+/// Where plugins write the files they produce and what they are called
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputPolicy {
+    /// Directory outputs are written to; the user's downloads folder if unset
+    ///
+    /// May start with a path token such as `$DOCUMENTS`, resolved when writing.
+    pub directory: Option<PathBuf>,
+    /// File name template, e.g. `{name}-{date}.{ext}`
+    pub name_template: String,
+    pub conflict: ConflictPolicy,
+    /// Overrides applied when a plugin writes with a named preset
+    pub presets: HashMap<String, OutputOverrides>,
+}
+
+/// Per-preset replacements for parts of the output policy
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputOverrides {
+    pub directory: Option<PathBuf>,
+    pub name_template: Option<String>,
+    pub conflict: Option<ConflictPolicy>,
+}
+
+impl Default for OutputPolicy {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            name_template: "{name}-{date}.{ext}".to_string(),
+            conflict: ConflictPolicy::default(),
+            presets: HashMap::new(),
+        }
+    }
+}
+
+impl OutputPolicy {
+    pub fn validate(&self) -> Result<()> {
+        let presets = self.presets.iter().map(|(name, preset)| {
+            (Some(name.as_str()), preset.directory.as_ref(), preset.name_template.as_ref())
+        });
+        for (preset, directory, template) in
+            std::iter::once((None, self.directory.as_ref(), Some(&self.name_template))).chain(presets)
+        {
+            let context = preset.map(|name| format!(" (preset '{}')", name)).unwrap_or_default();
+            if let Some(directory) = directory {
+                let path = directory.to_string_lossy();
+                match crate::paths::split_token(&path) {
+                    Some((crate::paths::DATA_TOKEN, _)) => {
+                        anyhow::bail!("{} is only available to plugins{}", crate::paths::DATA_TOKEN, context);
+                    }
+                    Some(_) => crate::paths::validate(&path).with_context(|| format!("Invalid output directory{}", context))?,
+                    None if !directory.is_absolute() => {
+                        anyhow::bail!("Output directory must be absolute or start with a path token{}: {:?}", context, directory);
+                    }
+                    None => {}
+                }
+            }
+            if let Some(template) = template {
+                validate_name_template(template).with_context(|| format!("Invalid name template{}", context))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the stored policy, falling back to the default if it is invalid
+    pub fn load(settings: &SettingsStore) -> Self {
+        match settings.get::<OutputPolicy>(OUTPUT_POLICY_KEY) {
+            Ok(Some(policy)) => match policy.validate() {
+                Ok(()) => policy,
+                Err(e) => {
+                    tracing::error!("Invalid output policy, using defaults: {:#}", e);
+                    Self::default()
+                }
+            },
+            Ok(None) => Self::default(),
+            Err(e) => {
+                tracing::error!("Failed to load output policy, using defaults: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// A template renders to a single file name, so it may not contain separators
+fn validate_name_template(template: &str) -> Result<()> {
+    if template.trim().is_empty() {
+        anyhow::bail!("Template is empty");
+    }
+    if template.contains(['/', '\\']) {
+        anyhow::bail!("Template must be a file name, not a path: '{}'", template);
+    }
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed placeholder in '{}'", template))?;
+        let placeholder = &rest[start..start + end + 1];
+        if !OUTPUT_TEMPLATE_PLACEHOLDERS.contains(&placeholder) {
+            anyhow::bail!(
+                "Unknown placeholder {}; expected one of: {}",
+                placeholder,
+                OUTPUT_TEMPLATE_PLACEHOLDERS.join(", ")
+            );
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
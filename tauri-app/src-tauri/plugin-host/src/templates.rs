@@ -0,0 +1,59 @@
+//! Setting placeholders in plugin manifest values
+//!
+//! Values of a manifest's `env` map may contain `${<setting key>}`, which is
+//! replaced with that host setting when the plugin loads, e.g.
+//! `"API_URL": "${api_base_url}/v2"`. String settings are inserted as they
+//! are and any other JSON value as its JSON text, so a plugin can be pointed
+//! at endpoints, feature flags and keys chosen on the host instead of ones
+//! fixed when it was packaged.
+
+use anyhow::Result;
+
+/// Setting keys a template refers to, in order
+pub fn placeholders(template: &str) -> Result<Vec<&str>> {
+    let mut keys = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            anyhow::bail!("Unterminated placeholder in '{}'; expected ${{<setting key>}}", template);
+        };
+        let key = after[..end].trim();
+        if key.is_empty() {
+            anyhow::bail!("Empty placeholder in '{}'; expected ${{<setting key>}}", template);
+        }
+        keys.push(key);
+        rest = &after[end + 1..];
+    }
+    Ok(keys)
+}
+
+/// Check that every placeholder in a template is well-formed
+pub fn validate(template: &str) -> Result<()> {
+    placeholders(template).map(|_| ())
+}
+
+/// Replace every placeholder with the setting `lookup` returns for its key
+///
+/// A setting that is not set (or is null) is an error rather than an empty
+/// string, so a plugin never runs against a half-filled endpoint.
+pub fn render<F>(template: &str, mut lookup: F) -> Result<String>
+where
+    F: FnMut(&str) -> Result<Option<serde_json::Value>>,
+{
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    for key in placeholders(template)? {
+        let start = rest.find("${").expect("placeholders() found one");
+        let end = start + rest[start..].find('}').expect("placeholders() found its end");
+        rendered.push_str(&rest[..start]);
+        match lookup(key)? {
+            Some(serde_json::Value::String(value)) => rendered.push_str(&value),
+            Some(serde_json::Value::Null) | None => anyhow::bail!("Setting '{}' is not set", key),
+            Some(value) => rendered.push_str(&value.to_string()),
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
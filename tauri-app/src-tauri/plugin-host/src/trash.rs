@@ -0,0 +1,138 @@
+//! Recycle bin for files plugins delete or overwrite
+//!
+//! Files are moved into a host-managed trash directory instead of being
+//! removed, and stay restorable to their original path until the retention
+//! period from [`TRASH_RETENTION_DAYS_KEY`] runs out.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::schema::TrashedFile;
+use crate::db::{operations, Database};
+use crate::error::{AppError, ErrorCode};
+use crate::ids::{self, IdKind};
+use crate::settings::{SettingsStore, DEFAULT_TRASH_RETENTION_DAYS, TRASH_RETENTION_DAYS_KEY};
+
+/// How often expired files are purged
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub struct TrashBin {
+    dir: PathBuf,
+    database: Arc<Database>,
+}
+
+impl TrashBin {
+    pub fn new(dir: PathBuf, database: Arc<Database>) -> Self {
+        Self { dir, database }
+    }
+
+    /// Move `path` into the trash on behalf of `plugin`
+    pub fn trash(&self, path: &Path, plugin: Option<&str>) -> Result<TrashedFile> {
+        let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {:?}", path))?;
+        if !metadata.is_file() {
+            anyhow::bail!("Only files can be moved to the trash: {:?}", path);
+        }
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create trash directory {:?}", self.dir))?;
+
+        let id = ids::new_id(IdKind::Other);
+        let trash_path = self.dir.join(&id);
+        move_file(path, &trash_path)?;
+
+        let file = TrashedFile {
+            id,
+            original_path: path.to_string_lossy().into_owned(),
+            trash_path: trash_path.to_string_lossy().into_owned(),
+            plugin: plugin.map(str::to_string),
+            size: metadata.len() as i64,
+            trashed_at: chrono::Utc::now().timestamp(),
+        };
+        if let Err(e) = self.database.with_connection(|conn| operations::create_trashed_file(conn, &file)) {
+            // Put it back rather than leave a file nobody can restore
+            let _ = move_file(&trash_path, path);
+            return Err(e.into());
+        }
+        tracing::info!("Moved {:?} to the trash as {}", path, file.id);
+        Ok(file)
+    }
+
+    pub fn list(&self) -> Result<Vec<TrashedFile>> {
+        Ok(self.database.with_connection(operations::list_trashed_files)?)
+    }
+
+    /// Move a trashed file back to where it was; refuses to replace a file
+    /// that has since been created there
+    pub fn restore(&self, id: &str) -> Result<TrashedFile> {
+        let file = self
+            .database
+            .with_connection(|conn| operations::get_trashed_file(conn, id))?
+            .ok_or_else(|| AppError::new(ErrorCode::NotFound, format!("Trashed file not found: {}", id)))?;
+        let original = Path::new(&file.original_path);
+        if original.exists() {
+            return Err(AppError::new(
+                ErrorCode::Conflict,
+                format!("{} already exists; move it away before restoring", file.original_path),
+            )
+            .into());
+        }
+        if let Some(parent) = original.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        move_file(Path::new(&file.trash_path), original)?;
+        self.database.with_connection(|conn| operations::delete_trashed_file(conn, id))?;
+        tracing::info!("Restored {} to {:?}", id, original);
+        Ok(file)
+    }
+
+    /// Permanently delete files trashed more than `retention_days` ago
+    pub fn purge_expired(&self, retention_days: i64) -> Result<usize> {
+        let cutoff = chrono::Utc::now().timestamp() - retention_days * 24 * 60 * 60;
+        let expired = self
+            .database
+            .with_connection(|conn| operations::get_trashed_files_before(conn, cutoff))?;
+        for file in &expired {
+            match std::fs::remove_file(&file.trash_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    tracing::warn!("Failed to purge trashed file {:?}: {}", file.trash_path, e);
+                    continue;
+                }
+            }
+            self.database
+                .with_connection(|conn| operations::delete_trashed_file(conn, &file.id))?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Purge loop, run under the task supervisor; the retention setting is
+    /// re-read every round
+    pub async fn run(self: Arc<Self>) -> Result<(), String> {
+        let settings = SettingsStore::new(self.database.clone());
+        let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let retention_days: i64 = settings
+                .get(TRASH_RETENTION_DAYS_KEY)
+                .ok()
+                .flatten()
+                .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+            match self.purge_expired(retention_days) {
+                Ok(purged) if purged > 0 => tracing::info!("Purged {} expired trashed files", purged),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to purge trash: {:#}", e),
+            }
+        }
+    }
+}
+
+/// Rename, falling back to copy and delete across filesystems
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).with_context(|| format!("Failed to copy {:?} to {:?}", from, to))?;
+    std::fs::remove_file(from).with_context(|| format!("Failed to remove {:?}", from))
+}
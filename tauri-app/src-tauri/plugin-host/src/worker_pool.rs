@@ -0,0 +1,50 @@
+//! Resizable worker pool bounding concurrent work
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// A semaphore-backed pool whose size can be changed at runtime
+#[derive(Clone)]
+pub struct WorkerPool {
+    semaphore: Arc<Semaphore>,
+    size: Arc<AtomicUsize>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(size)),
+            size: Arc::new(AtomicUsize::new(size)),
+        }
+    }
+
+    /// Wait for a free worker slot; the slot is released when the permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("worker pool semaphore is never closed")
+    }
+
+    /// Grow or shrink the pool
+    ///
+    /// Shrinking waits for busy workers in the background, so in-flight work is
+    /// never interrupted.
+    pub fn resize(&self, new_size: usize) {
+        let old_size = self.size.swap(new_size, Ordering::SeqCst);
+
+        if new_size > old_size {
+            self.semaphore.add_permits(new_size - old_size);
+        } else if new_size < old_size {
+            let semaphore = self.semaphore.clone();
+            let excess = (old_size - new_size) as u32;
+            tokio::spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many(excess).await {
+                    permits.forget();
+                }
+            });
+        }
+    }
+}
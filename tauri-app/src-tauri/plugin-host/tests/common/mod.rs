@@ -0,0 +1,125 @@
+//! Harness shared by the end-to-end tests: a scratch app directory with a
+//! fresh in-memory database, and the fixtures installed into it
+// Each test crate uses a different part of the harness
+#![allow(dead_code)]
+
+use plugin_host::db::{migrations, operations, Database};
+use plugin_host::plugins::{Capability, PluginManager, PluginManifest};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Scratch app directory, removed when the test ends
+pub struct TestApp {
+    pub root: PathBuf,
+    pub database: Arc<Database>,
+    pub manager: PluginManager,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let root = std::env::temp_dir().join(format!("a2e-roundtrip-{}", uuid::Uuid::new_v4()));
+        let database = Database::new(PathBuf::from(":memory:")).expect("Failed to open database");
+        database
+            .with_connection(|conn| Ok(migrations::run_migrations(conn)))
+            .expect("Failed to lock database")
+            .expect("Failed to run migrations");
+        let database = Arc::new(database);
+        let manager = PluginManager::new_with_database(root.join("plugins"), database.clone())
+            .expect("Failed to create plugin manager");
+        Self { root, database, manager }
+    }
+
+    /// Put a fixture in the plugins directory as if it had been installed
+    /// before this run of the app; returns its directory
+    pub fn preinstall(&self, fixture: &str) -> PathBuf {
+        copy_fixture(fixture, &self.root.join("plugins"));
+        let dir = self.root.join("plugins").join(fixture);
+        record_install(&self.database, &dir);
+        dir
+    }
+
+    /// Install a fixture the way a user would, granting none of the
+    /// sensitive capabilities it asks for
+    pub async fn install(&self, fixture: &str) -> String {
+        self.install_granting(fixture, &[]).await
+    }
+
+    /// Install a fixture, granting the sensitive capabilities in `grant` when
+    /// its install asks for them
+    pub async fn install_granting(&self, fixture: &str, grant: &[Capability]) -> String {
+        self.install_dir(&fixture_dir(fixture), grant)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to install {}: {:#}", fixture, e))
+    }
+
+    /// Install the plugin in `source` through the approval path: fixtures
+    /// are unsigned, so it is approved out of quarantine, and the capability
+    /// prompt is answered with `grant`
+    pub async fn install_dir(&self, source: &Path, grant: &[Capability]) -> anyhow::Result<String> {
+        let approvals = self.manager.capability_approvals();
+        let mut requests = approvals.subscribe();
+        let grant = grant.to_vec();
+        let responder = tokio::spawn(async move {
+            while let Ok(request) = requests.recv().await {
+                let granted = request.capabilities.iter().filter(|c| grant.contains(c)).copied().collect();
+                approvals.respond(&request.id, granted);
+            }
+        });
+        let result = async {
+            let id = self.manager.install_plugin(source).await?;
+            if self.manager.is_quarantined(&id) {
+                return self.manager.approve_quarantined_plugin(&id, false).await;
+            }
+            Ok(id)
+        }
+        .await;
+        responder.abort();
+        result
+    }
+
+    pub async fn call(&self, plugin: &str, function: &str, input: Value) -> Value {
+        let output = self
+            .manager
+            .execute_plugin(plugin, function, input.to_string().as_bytes())
+            .await
+            .unwrap_or_else(|e| panic!("{}/{} failed: {:#}", plugin, function, e));
+        serde_json::from_slice(&output).expect("Plugin output should be JSON")
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+pub fn fixture_dir(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+pub fn copy_fixture(name: &str, plugins_dir: &Path) {
+    let dest = plugins_dir.join(name);
+    std::fs::create_dir_all(&dest).unwrap();
+    for entry in std::fs::read_dir(fixture_dir(name)).unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), dest.join(entry.file_name())).unwrap();
+    }
+}
+
+/// Record the checksum the installer would have for the plugin in `dir`
+pub fn record_install(database: &Database, dir: &Path) {
+    let manifest = PluginManifest::load_from_file(&dir.join("plugin.json")).unwrap();
+    let checksum = match &manifest.wasm_paths(dir)[..] {
+        [module] => hex::encode(Sha256::digest(std::fs::read(module).unwrap())),
+        modules => panic!("Fixtures have one module, not {}", modules.len()),
+    };
+    database
+        .with_connection(|conn| operations::set_plugin_checksum(conn, &manifest.id(), &checksum, 0))
+        .unwrap();
+}
+
+extism::host_fn!(pub app_greeting(name: String) -> String {
+    Ok(format!("Hello, {}!", name))
+});
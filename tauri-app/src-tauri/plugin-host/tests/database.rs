@@ -0,0 +1,695 @@
+//! End-to-end tests of the database host functions and plugin migrations
+//!
+//! Prebuilt plugins from tests/fixtures are installed through
+//! PluginManager and call the real host functions against a fresh in-memory
+//! database.
+mod common;
+
+use common::*;
+use plugin_host::db::{migrations, operations, Database};
+use plugin_host::events::{self, HostEvent};
+use plugin_host::host_functions::database::MAX_BATCH_OPERATIONS;
+use plugin_host::plugins::{Capability, CurrentUser, ExecutionContext};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signup_login_audit_roundtrip() {
+    let app = TestApp::new();
+    app.install_granting("auth-plugin", &[Capability::DB_WRITE]).await;
+    app.install_granting("audit-plugin", &[Capability::DB_WRITE]).await;
+
+    // Sign up
+    let credentials = json!({ "email": "ada@example.com", "password": "correct horse battery" });
+    let signup = app
+        .call("auth-plugin", "signup", json!({ "name": "Ada", "email": "ada@example.com", "password": "correct horse battery" }))
+        .await;
+    assert_eq!(signup["success"], true, "signup failed: {}", signup);
+    let user_uuid = signup["user_uuid"].as_str().expect("signup should return the user's UUID").to_string();
+
+    let user = app
+        .database
+        .with_connection(|conn| operations::get_user_by_uuid(conn, &user_uuid))
+        .unwrap()
+        .expect("User should be in the database");
+    assert_eq!(user.email, "ada@example.com");
+    assert_ne!(user.password_hash, "correct horse battery", "Password should be hashed");
+
+    let duplicate = app
+        .call("auth-plugin", "signup", json!({ "name": "Ada", "email": "ada@example.com", "password": "another password" }))
+        .await;
+    assert_eq!(duplicate["success"], false, "Duplicate email should be rejected");
+
+    // Log in
+    let wrong_password = app
+        .call("auth-plugin", "login", json!({ "email": "ada@example.com", "password": "wrong password" }))
+        .await;
+    assert_eq!(wrong_password["success"], false);
+    assert!(wrong_password["session_id"].is_null());
+
+    let login = app.call("auth-plugin", "login", credentials).await;
+    assert_eq!(login["success"], true, "login failed: {}", login);
+    assert_eq!(login["user"]["uuid"], user_uuid.as_str());
+    let session_id = login["session_id"].as_str().expect("login should return a session").to_string();
+
+    let verified = app
+        .call("auth-plugin", "verify_session", json!({ "session_id": session_id }))
+        .await;
+    assert_eq!(verified["valid"], true);
+    assert_eq!(verified["user_uuid"], user_uuid.as_str());
+
+    // Audit: the auth plugin's records, read back through the audit plugin
+    let logs = app
+        .call("audit-plugin", "get_user_audit_logs", json!({ "user_uuid": user_uuid }))
+        .await;
+    assert_eq!(logs["success"], true, "get_user_audit_logs failed: {}", logs);
+    let actions: Vec<&str> = logs["data"]["logs"]
+        .as_array()
+        .expect("Audit logs should be a list")
+        .iter()
+        .filter_map(|log| log["action"].as_str())
+        .collect();
+    assert!(actions.contains(&"user.signup"), "Missing signup in {:?}", actions);
+    assert!(actions.contains(&"user.login"), "Missing login in {:?}", actions);
+
+    // Log out ends the session
+    let logout = app
+        .call("auth-plugin", "logout", json!({ "session_id": session_id }))
+        .await;
+    assert_eq!(logout["success"], true);
+    let verified = app
+        .call("auth-plugin", "verify_session", json!({ "session_id": session_id }))
+        .await;
+    assert_eq!(verified["valid"], false);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugin_migrations_run_once_in_order() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let source = staging.join("text-converter");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    std::fs::create_dir(source.join("migrations")).unwrap();
+    std::fs::write(source.join("migrations/001_notes.sql"), "CREATE TABLE text_converter_notes (text TEXT NOT NULL);").unwrap();
+    std::fs::write(source.join("migrations/002_created_at.sql"), "ALTER TABLE text_converter_notes ADD COLUMN created_at INTEGER;").unwrap();
+    std::fs::write(source.join("migrations/003_broken.sql"), "ALTER TABLE no_such_table ADD COLUMN x INTEGER;").unwrap();
+    let schema_version = || {
+        app.database
+            .with_connection(|conn| Ok(migrations::plugin_schema_version(conn, "text-converter")))
+            .unwrap()
+            .unwrap()
+    };
+
+    // Arbitrary SQL needs write access to the whole database
+    manifest["migrations"] = json!(["migrations/001_notes.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&source, &[Capability::DB_WRITE]).await.unwrap_err());
+    assert!(error.contains("/migrations: Migrations need the 'db:write' capability"), "Unexpected error: {}", error);
+
+    manifest["capabilities"] = json!(["db:write"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Install failed");
+    assert_eq!(schema_version(), 1);
+    app.database
+        .with_connection(|conn| conn.execute("INSERT INTO text_converter_notes (text) VALUES ('kept')", []))
+        .unwrap();
+
+    // An update only runs the new migration
+    manifest["version"] = json!("0.2.0");
+    manifest["migrations"] = json!(["migrations/001_notes.sql", "migrations/002_created_at.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Update failed");
+    assert_eq!(schema_version(), 2);
+    let notes: i64 = app
+        .database
+        .with_connection(|conn| conn.query_row("SELECT COUNT(*) FROM text_converter_notes WHERE created_at IS NULL", [], |row| row.get(0)))
+        .unwrap();
+    assert_eq!(notes, 1);
+
+    // A failing migration fails the install, leaving the earlier ones applied
+    manifest["version"] = json!("0.3.0");
+    manifest["migrations"].as_array_mut().unwrap().push(json!("migrations/003_broken.sql"));
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&source, &[Capability::DB_WRITE]).await.unwrap_err());
+    assert!(error.contains("Migration migrations/003_broken.sql of plugin 'text-converter' failed"), "Unexpected error: {}", error);
+    assert_eq!(schema_version(), 2);
+    assert_eq!(app.manager.get_plugin("text-converter").await.unwrap().version, "0.2.0");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugin_migrations_only_reach_the_plugins_own_tables() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let source = staging.join("text-converter");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["capabilities"] = json!(["db:write"]);
+    manifest["migrations"] = json!(["migrations/001_hostile.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    std::fs::create_dir(source.join("migrations")).unwrap();
+    let core_tables = || {
+        app.database
+            .with_connection(|conn| {
+                let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")?;
+                let names = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>();
+                names
+            })
+            .unwrap()
+    };
+    let before = core_tables();
+    for table in &before {
+        assert!(migrations::CORE_TABLES.contains(&table.as_str()), "{} is missing from CORE_TABLES", table);
+    }
+
+    let hostile = [
+        ("CREATE TABLE notes (text TEXT);", "own tables, named 'text_converter_...', not 'notes'"),
+        ("CREATE TABLE other_plugin_notes (text TEXT);", "not 'other_plugin_notes'"),
+        ("DROP TABLE users;", "not 'users'"),
+        ("ALTER TABLE sessions ADD COLUMN stolen TEXT;", "not 'sessions'"),
+        ("CREATE INDEX text_converter_audit ON audit_logs (user_uuid);", "not 'audit_logs'"),
+        ("INSERT INTO user_roles (user_uuid, role) VALUES ('me', 'admin');", "not 'user_roles'"),
+        ("UPDATE settings SET value = '' WHERE 1;", "not 'settings'"),
+        (
+            "CREATE TABLE text_converter_copy (hash TEXT); INSERT INTO text_converter_copy SELECT password_hash FROM users;",
+            "not 'users'",
+        ),
+        (
+            "CREATE TABLE text_converter_notes (text TEXT); ALTER TABLE text_converter_notes RENAME TO notes;",
+            "may only name tables and indexes 'text_converter_...', not 'notes'",
+        ),
+        (
+            "CREATE TABLE text_converter_notes (text TEXT); CREATE INDEX users_by_text ON text_converter_notes (text);",
+            "not 'users_by_text'",
+        ),
+        ("ATTACH DATABASE 'stolen.db' AS stolen;", "may not attach databases"),
+        ("PRAGMA writable_schema = ON;", "may not run pragmas"),
+        ("COMMIT; DELETE FROM users;", "may not control it"),
+        ("SAVEPOINT detour;", "may not control it"),
+        (
+            "CREATE TABLE text_converter_notes (text TEXT);
+             CREATE TRIGGER text_converter_wipe AFTER INSERT ON text_converter_notes BEGIN DELETE FROM users; END;",
+            "may only create, change, fill and drop tables and indexes",
+        ),
+    ];
+    for (sql, reason) in hostile {
+        std::fs::write(source.join("migrations/001_hostile.sql"), sql).unwrap();
+        let error = format!("{:#}", app.install_dir(&source, &[Capability::DB_WRITE]).await.unwrap_err());
+        assert!(
+            error.contains("Migration migrations/001_hostile.sql of plugin 'text-converter' failed") && error.contains(reason),
+            "Unexpected error for {}: {}",
+            sql,
+            error
+        );
+    }
+    assert_eq!(core_tables(), before);
+    let version = app
+        .database
+        .with_connection(|conn| Ok(migrations::plugin_schema_version(conn, "text-converter")))
+        .unwrap()
+        .unwrap();
+    assert_eq!(version, 0);
+
+    // The plugin's own tables, with indexes and autoincrement keys
+    std::fs::write(
+        source.join("migrations/001_hostile.sql"),
+        "CREATE TABLE text_converter_notes (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT NOT NULL);
+         CREATE INDEX text_converter_notes_text ON text_converter_notes (text);
+         INSERT INTO text_converter_notes (text) VALUES ('first');
+         UPDATE text_converter_notes SET text = 'seeded' WHERE id = 1;
+         ALTER TABLE text_converter_notes ADD COLUMN created_at INTEGER;",
+    )
+    .unwrap();
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Install failed");
+    let seeded: String = app
+        .database
+        .with_connection(|conn| conn.query_row("SELECT text FROM text_converter_notes", [], |row| row.get(0)))
+        .unwrap();
+    assert_eq!(seeded, "seeded");
+
+    // Nor may another plugin's prefix take in those tables
+    let copy = app.root.join("copy");
+    copy_fixture("text-converter", &copy);
+    let copy = copy.join("text-converter");
+    manifest["name"] = json!("text-converter-notes");
+    std::fs::write(copy.join("plugin.json"), manifest.to_string()).unwrap();
+    std::fs::create_dir(copy.join("migrations")).unwrap();
+    std::fs::write(copy.join("migrations/001_hostile.sql"), "DELETE FROM text_converter_notes_x;").unwrap();
+    let error = format!("{:#}", app.install_dir(&copy, &[Capability::DB_WRITE]).await.unwrap_err());
+    assert!(
+        error.contains("Tables of plugin 'text-converter-notes' would be named like those of plugin 'text-converter'"),
+        "Unexpected error: {}",
+        error
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_batch_runs_permitted_operations_in_order() {
+    let app = TestApp::new();
+    app.install_granting("db-batch", &[Capability::DB_WRITE]).await;
+    let user_uuid = uuid::Uuid::new_v4().to_string();
+    app.database
+        .with_connection(|conn| operations::create_user(conn, &user_uuid, "Ada", "ada@example.com", "hash", 0))
+        .unwrap();
+    let log = |action: &str| json!({ "op": "db_create_audit_log", "input": { "user_uuid": user_uuid, "action": action } });
+    let count = json!({ "op": "db_count_user_audit_logs", "input": { "uuid": user_uuid } });
+
+    let batch = app.call("db-batch", "batch", json!([log("login"), log("view"), log("logout"), count])).await;
+    assert_eq!(batch["success"], true, "Batch failed: {}", batch);
+    let results = batch["data"].as_array().unwrap();
+    assert_eq!(results.len(), 4);
+    assert!(results.iter().all(|result| result["success"] == true));
+    assert_eq!(results[3]["data"], 3);
+
+    // Functions the plugin has no capability for refuse the whole batch
+    let create_user = json!({ "op": "db_create_user", "input": {} });
+    let refused = app.call("db-batch", "batch", json!([log("first"), create_user])).await;
+    assert_eq!(refused["success"], false);
+    assert!(refused["error"].as_str().unwrap().contains("Operation 1 calls 'db_create_user'"), "Unexpected error: {}", refused);
+    let counted = app.call("db-batch", "batch", json!([count])).await;
+    assert_eq!(counted["data"][0]["data"], 3, "Nothing should run when a batch is refused");
+
+    // A failing operation stops the batch; the ones before it stay applied
+    let invalid = json!({ "op": "db_create_audit_log", "input": { "action": "no user" } });
+    let failed = app.call("db-batch", "batch", json!([log("kept"), invalid, log("skipped")])).await;
+    assert_eq!(failed["success"], false);
+    assert!(failed["error"].as_str().unwrap().starts_with("Operation 1 (db_create_audit_log) failed"), "Unexpected error: {}", failed);
+    let results = failed["data"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!((&results[0]["success"], &results[1]["success"]), (&json!(true), &json!(false)));
+    let counted = app.call("db-batch", "batch", json!([count])).await;
+    assert_eq!(counted["data"][0]["data"], 4);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_batch_refuses_denied_writes_malformed_and_oversized_batches() {
+    let app = TestApp::new();
+    // db:audit:write is declared but not granted
+    app.install("db-batch").await;
+    let user_uuid = uuid::Uuid::new_v4().to_string();
+    app.database
+        .with_connection(|conn| operations::create_user(conn, &user_uuid, "Ada", "ada@example.com", "hash", 0))
+        .unwrap();
+    let log = json!({ "op": "db_create_audit_log", "input": { "user_uuid": user_uuid, "action": "login" } });
+    let count = json!({ "op": "db_count_user_audit_logs", "input": { "uuid": user_uuid } });
+
+    let denied = app.call("db-batch", "batch", json!([log])).await;
+    assert_eq!(denied["success"], false, "Writes should need db:write granted: {}", denied);
+    let counted = app.call("db-batch", "batch", json!([count])).await;
+    assert_eq!(counted["success"], true, "Reads should still be permitted: {}", counted);
+    assert_eq!(counted["data"][0]["data"], 0);
+
+    // A batch is an array of known operations
+    let not_a_list = app.call("db-batch", "batch", count.clone()).await;
+    assert_eq!(not_a_list["success"], false);
+    let unknown = app.call("db-batch", "batch", json!([{ "op": "db_drop_everything", "input": {} }])).await;
+    assert_eq!(unknown["success"], false);
+
+    let oversized = app.call("db-batch", "batch", Value::Array(vec![count; MAX_BATCH_OPERATIONS + 1])).await;
+    assert_eq!(oversized["success"], false);
+    assert!(oversized["error"].as_str().unwrap().contains("at most"), "Unexpected error: {}", oversized);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_list_users_pages_sorts_and_searches() {
+    let app = TestApp::new();
+    app.install("user-browser").await;
+    let users = [("Grace", "grace@navy.mil"), ("Ada", "ada@example.com"), ("Alan", "alan@example.com"), ("Edsger", "ed_w@example.nl")];
+    app.database
+        .with_connection(|conn| {
+            for (created_at, (name, email)) in users.iter().enumerate() {
+                operations::create_user(conn, &uuid::Uuid::new_v4().to_string(), name, email, "hash", created_at as i64)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    let names = |page: &Value| -> Vec<String> {
+        assert_eq!(page["success"], true, "Listing failed: {}", page);
+        page["data"]["users"].as_array().unwrap().iter().map(|user| user["name"].as_str().unwrap().to_string()).collect()
+    };
+
+    // Oldest first by default
+    let listed = app.call("user-browser", "list", json!({})).await;
+    assert_eq!(names(&listed), ["Grace", "Ada", "Alan", "Edsger"]);
+    assert_eq!(listed["data"]["total"], 4);
+
+    // Pages count from 1; the total covers every page
+    let second = app.call("user-browser", "list", json!({ "sort": "name", "page": 2, "limit": 3 })).await;
+    assert_eq!(names(&second), ["Grace"]);
+    assert_eq!(second["data"]["total"], 4);
+    let descending = app.call("user-browser", "list", json!({ "sort": "email", "descending": true, "limit": 2 })).await;
+    assert_eq!(names(&descending), ["Grace", "Edsger"]);
+
+    // Search matches names and emails case-insensitively, and literally
+    let searched = app.call("user-browser", "list", json!({ "search": "AL" })).await;
+    assert_eq!(names(&searched), ["Alan"]);
+    let searched = app.call("user-browser", "list", json!({ "search": "example.com", "sort": "name" })).await;
+    assert_eq!(names(&searched), ["Ada", "Alan"]);
+    assert_eq!(searched["data"]["total"], 2);
+    let literal = app.call("user-browser", "list", json!({ "search": "_" })).await;
+    assert_eq!(names(&literal), ["Edsger"]);
+
+    for (request, error) in [
+        (json!({ "page": 0 }), "Pages start at 1"),
+        (json!({ "limit": 101 }), "The limit must be 1 to 100 users"),
+        (json!({ "sort": "password_hash" }), "JSON parse error"),
+    ] {
+        let refused = app.call("user-browser", "list", request).await;
+        assert_eq!(refused["success"], false);
+        assert!(refused["error"].as_str().unwrap().contains(error), "Unexpected error: {}", refused);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_delete_user_removes_their_rows_and_keeps_audit_logs() {
+    let app = TestApp::new();
+    app.install_granting("account-deleter", &[Capability::DB_WRITE]).await;
+    let mut published = events::subscribe();
+    let (admin, user) = (uuid::Uuid::new_v4().to_string(), uuid::Uuid::new_v4().to_string());
+    app.database
+        .with_connection(|conn| {
+            operations::create_user(conn, &admin, "Admin", "admin@example.com", "hash", 0)?;
+            operations::grant_user_role(conn, &admin, operations::ROLE_ADMIN, 0)?;
+            operations::create_user(conn, &user, "Ada", "ada@example.com", "hash", 0)?;
+            operations::grant_user_role(conn, &user, "editor", 0)?;
+            for session in ["s1", "s2"] {
+                operations::create_session(conn, session, &user, 0, i64::MAX)?;
+            }
+            operations::create_email_verification_token(conn, "verify", &user, 0, i64::MAX)?;
+            operations::create_password_reset_token(conn, "reset", &user, 0, i64::MAX)?;
+            operations::create_api_key(conn, "key", &user, "key-hash", 0)?;
+            for (id, action) in [("log-1", "login"), ("log-2", "logout")] {
+                operations::create_audit_log(conn, id, &user, action, None, None, None, None, None, 0)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    let deleted = app.call("account-deleter", "delete", json!({ "uuid": user })).await;
+    assert_eq!(deleted["success"], true, "Deletion failed: {}", deleted);
+    assert_eq!(
+        deleted["data"],
+        json!({ "sessions": 2, "email_verification_tokens": 1, "password_reset_tokens": 1, "roles": 1, "api_keys": 1, "audit_logs": 0 })
+    );
+    let (gone, audit_logs) = app
+        .database
+        .with_connection(|conn| Ok((operations::get_user_by_uuid(conn, &user)?.is_none(), operations::count_user_audit_logs(conn, &user)?)))
+        .unwrap();
+    assert!(gone);
+    assert_eq!(audit_logs, 2, "Audit logs should outlive the user");
+    let event = std::iter::from_fn(|| published.try_recv().ok())
+        .find(|event| event.event == events::USER_DELETED && event.source.as_deref() == Some("account-deleter"))
+        .expect("user.deleted should be published");
+    assert_eq!(event.payload, json!({ "uuid": user }));
+
+    // Refused: unknown users, the last admin, and audit logs without db:audit:write
+    for (request, error) in [
+        (json!({ "uuid": user }), "No user with UUID"),
+        (json!({ "uuid": admin }), "Cannot delete the last admin"),
+        (json!({ "uuid": admin, "delete_audit_logs": true }), "needs the db:audit:write capability"),
+    ] {
+        let refused = app.call("account-deleter", "delete", request).await;
+        assert_eq!(refused["success"], false);
+        assert!(refused["error"].as_str().unwrap().contains(error), "Unexpected error: {}", refused);
+    }
+
+    // Audit logs go too when asked for
+    let removed = app
+        .database
+        .with_connection(|conn| {
+            operations::create_audit_log(conn, "log-3", &admin, "login", None, None, None, None, None, 0)?;
+            operations::delete_user(conn, &admin, true)
+        })
+        .unwrap()
+        .expect("The admin should exist");
+    assert_eq!(removed.roles, 1);
+    assert_eq!(removed.audit_logs, 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_transactions_commit_roll_back_and_end_with_the_call() {
+    let app = TestApp::new();
+    app.install_granting("db-transaction", &[Capability::DB_WRITE]).await;
+    let mut published = events::subscribe();
+    let signup = |email: &str| {
+        let uuid = uuid::Uuid::new_v4().to_string();
+        let user = json!({ "uuid": uuid, "name": "Ada", "email": email, "password_hash": "hash", "created_at": 0 });
+        let log = json!({ "user_uuid": uuid, "action": "user.signup" });
+        (uuid, json!([{ "op": "db_create_user", "input": user }, { "op": "db_create_audit_log", "input": log }]))
+    };
+    let user_exists = |uuid: &str| {
+        app.database
+            .with_connection(|conn| operations::get_user_by_uuid(conn, uuid))
+            .unwrap()
+            .is_some()
+    };
+    let created_events = |published: &mut tokio::sync::broadcast::Receiver<HostEvent>| {
+        std::iter::from_fn(|| published.try_recv().ok())
+            .filter(|event| event.event == events::USER_CREATED && event.source.as_deref() == Some("db-transaction"))
+            .count()
+    };
+
+    // Rolled back: neither write stays and subscribers hear nothing
+    let (uuid, batch) = signup("rolled-back@example.com");
+    assert_eq!(app.call("db-transaction", "rollback", batch).await["success"], true);
+    assert!(!user_exists(&uuid));
+    assert_eq!(created_events(&mut published), 0);
+
+    // Left open: rolled back when the call ends
+    let (uuid, batch) = signup("abandoned@example.com");
+    assert_eq!(app.call("db-transaction", "abandon", batch).await["success"], true);
+    assert!(!user_exists(&uuid));
+    assert_eq!(created_events(&mut published), 0);
+
+    // Committed: both writes stay and the event follows the commit
+    let (uuid, batch) = signup("committed@example.com");
+    assert_eq!(app.call("db-transaction", "commit", batch).await["success"], true);
+    assert!(user_exists(&uuid));
+    let logs = app
+        .database
+        .with_connection(|conn| operations::count_user_audit_logs(conn, &uuid))
+        .unwrap();
+    assert_eq!(logs, 1);
+    assert_eq!(created_events(&mut published), 1);
+
+    let second = app.call("db-transaction", "begin_twice", json!(null)).await;
+    assert_eq!(second["success"], false);
+    assert!(second["error"].as_str().unwrap().contains("already open"), "Unexpected error: {}", second);
+    // ...and the first was rolled back, so the database is free again
+    assert!(user_exists(&uuid));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_begin_needs_write_access() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("db-transaction", &staging);
+    let source = staging.join("db-transaction");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["capabilities"] = json!(["db:users:read", "db:audit:read"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&source, &[]).await.unwrap_err());
+    assert!(error.contains("missing host functions") && error.contains("db_begin"), "Unexpected error: {}", error);
+}
+
+#[test]
+fn test_transactions_are_rolled_back_at_their_deadline_and_waits_time_out() {
+    let database = Database::new(PathBuf::from(":memory:"))
+        .unwrap()
+        .with_transaction_limits(Duration::from_millis(300), Duration::from_millis(100));
+    database.with_connection(|conn| Ok(migrations::run_migrations(conn))).unwrap().unwrap();
+    let database = Arc::new(database);
+    let user_exists = |database: &Database, uuid: &str| {
+        database.with_connection(|conn| operations::get_user_by_uuid(conn, uuid)).map(|user| user.is_some())
+    };
+    let (opened, wait_for_open) = mpsc::channel();
+    let (resume, wait_to_resume) = mpsc::channel();
+    let db = database.clone();
+    let call = thread::spawn(move || {
+        let id = db.begin_transaction("call-1").unwrap();
+        db.with_connection(|conn| operations::create_user(conn, "user-1", "Ada", "ada@example.com", "hash", 0))
+            .unwrap();
+        opened.send(()).unwrap();
+        wait_to_resume.recv().unwrap();
+
+        // Rolled back behind the call's back: its later writes fail rather
+        // than commit one at a time, and so does the commit
+        let write = db
+            .with_connection(|conn| operations::create_user(conn, "user-2", "Bob", "bob@example.com", "hash", 0))
+            .unwrap_err();
+        assert!(write.to_string().contains("rolled back after staying open longer than"), "Unexpected error: {}", write);
+        let commit = db.commit_transaction("call-1", &id).unwrap_err();
+        assert!(commit.to_string().contains("rolled back after staying open longer than"), "Unexpected error: {}", commit);
+        // ...which ended it, so the call can use the database again
+        assert!(!db.rollback_open_transaction("call-1").unwrap());
+        user_exists(&db, "user-2").unwrap()
+    });
+    wait_for_open.recv().unwrap();
+
+    // Others give up waiting for the transaction...
+    let started = Instant::now();
+    let waited = user_exists(&database, "user-1").unwrap_err();
+    assert!(waited.to_string().contains("Timed out"), "Unexpected error: {}", waited);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    // ...until it passes its deadline, when the next of them rolls it back
+    thread::sleep(Duration::from_millis(300));
+    assert!(!user_exists(&database, "user-1").unwrap());
+    resume.send(()).unwrap();
+    assert!(!call.join().unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_waiting_for_a_transaction_leaves_the_runtime_free() {
+    let database = Arc::new(Database::new(PathBuf::from(":memory:")).unwrap());
+    database.with_connection(|conn| Ok(migrations::run_migrations(conn))).unwrap().unwrap();
+    let (opened, wait_for_open) = mpsc::channel();
+    let db = database.clone();
+    let call = thread::spawn(move || {
+        let id = db.begin_transaction("call-1").unwrap();
+        opened.send(()).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        db.commit_transaction("call-1", &id).unwrap();
+    });
+    wait_for_open.recv().unwrap();
+
+    // The only worker waits for the transaction; other tasks still run meanwhile
+    let started = Instant::now();
+    let db = database.clone();
+    let waiter = tokio::spawn(async move {
+        db.with_connection(|conn| operations::get_user_by_uuid(conn, "user-1")).unwrap();
+        started.elapsed()
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let ticker = tokio::spawn(async move { started.elapsed() });
+    let ticked = ticker.await.unwrap();
+    let waited = waiter.await.unwrap();
+    call.join().unwrap();
+    assert!(waited >= Duration::from_millis(400), "waited only {:?}", waited);
+    assert!(ticked < waited, "other tasks ran only after the wait ({:?})", ticked);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_query_and_execute_touch_only_permitted_tables() {
+    let app = TestApp::new();
+    app.install_granting("db-sql", &[Capability::DB_WRITE]).await;
+    let user_uuid = uuid::Uuid::new_v4().to_string();
+    app.database
+        .with_connection(|conn| operations::create_user(conn, &user_uuid, "Ada", "ada@example.com", "hash", 0))
+        .unwrap();
+    let manager = &app.manager;
+    let run = |function: &'static str, sql: &str, params: Value| {
+        let input = format!("{}\n{}", sql, params);
+        async move {
+            let output = manager.execute_plugin("db-sql", function, input.as_bytes()).await.unwrap();
+            serde_json::from_slice::<Value>(&output).unwrap()
+        }
+    };
+
+    let inserted = run(
+        "execute",
+        "INSERT INTO audit_logs (id, user_uuid, action, created_at) VALUES (?1, ?2, ?3, 0)",
+        json!(["log-1", user_uuid, "report.viewed"]),
+    )
+    .await;
+    assert_eq!(inserted["success"], true, "Insert failed: {}", inserted);
+    assert_eq!(inserted["data"]["changes"], 1);
+
+    // Named parameters, and a join across two readable tables
+    let rows = run(
+        "query",
+        "SELECT u.name, a.action FROM audit_logs a JOIN users u ON u.uuid = a.user_uuid WHERE a.user_uuid = :uuid",
+        json!({ "uuid": user_uuid }),
+    )
+    .await;
+    assert_eq!(rows["success"], true, "Query failed: {}", rows);
+    assert_eq!(rows["data"], json!([{ "name": "Ada", "action": "report.viewed" }]));
+
+    // Tables outside the plugin's capabilities, writes through db_query and
+    // anything but reading and writing rows are refused
+    let refused = [
+        ("query", "SELECT * FROM sessions", "may not read table 'sessions'"),
+        ("query", "SELECT name FROM sqlite_master", "may not read table 'sqlite_master'"),
+        ("query", "DELETE FROM audit_logs", "db_query only runs statements that read"),
+        ("execute", "UPDATE users SET name = 'Eve'", "may not write table 'users'"),
+        ("execute", "DROP TABLE audit_logs", "may only read and write rows"),
+        ("execute", "PRAGMA foreign_keys = OFF", "may only read and write rows"),
+        ("execute", "BEGIN", "opened with db_begin"),
+    ];
+    for (function, sql, error) in refused {
+        let response = run(function, sql, json!([])).await;
+        assert_eq!(response["success"], false, "{} should be refused", sql);
+        assert!(response["error"].as_str().unwrap().contains(error), "Unexpected error for {}: {}", sql, response);
+    }
+    let remaining = run("query", "SELECT count(*) AS logs FROM audit_logs", json!([])).await;
+    assert_eq!(remaining["data"][0]["logs"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_current_user_returns_the_user_a_call_is_made_for() {
+    let app = TestApp::new();
+    app.install("whoami").await;
+    let whoami = |context: ExecutionContext| {
+        let manager = &app.manager;
+        async move {
+            let output = manager.execute_plugin_with_context("whoami", "whoami", b"{}", &context).await.unwrap();
+            serde_json::from_slice::<Value>(&output).unwrap()
+        }
+    };
+
+    let user = CurrentUser {
+        user_uuid: "user-1".to_string(),
+        session_id: Some("secret-session-id".to_string()),
+        roles: vec!["admin".to_string()],
+    };
+    let signed_in = whoami(ExecutionContext::new().with_user(Some(user))).await;
+    assert_eq!(signed_in["success"], true, "get_current_user failed: {}", signed_in);
+    assert_eq!(signed_in["data"]["user_uuid"], "user-1");
+    assert_eq!(signed_in["data"]["roles"], json!(["admin"]));
+    // Plugins get a digest identifying the session, never the bearer token
+    let session = signed_in["data"]["session"].as_str().unwrap();
+    assert_eq!(session.len(), 16);
+    assert!(!signed_in.to_string().contains("secret-session-id"));
+
+    let anonymous = whoami(ExecutionContext::new()).await;
+    assert_eq!(anonymous, json!({ "success": true, "data": null, "error": null }));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_db_query_and_execute_reach_the_tables_a_plugin_migrated() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("db-sql", &staging);
+    let source = staging.join("db-sql");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["capabilities"] = json!(["db:write"]);
+    manifest["migrations"] = json!(["001_notes.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    std::fs::write(source.join("001_notes.sql"), "CREATE TABLE db_sql_notes (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT NOT NULL);").unwrap();
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Install failed");
+    let manager = &app.manager;
+    let run = |function: &'static str, sql: &str, params: Value| {
+        let input = format!("{}\n{}", sql, params);
+        async move {
+            let output = manager.execute_plugin("db-sql", function, input.as_bytes()).await.unwrap();
+            serde_json::from_slice::<Value>(&output).unwrap()
+        }
+    };
+
+    // No table capability is granted, yet the plugin's own table is open to it
+    let inserted = run("execute", "INSERT INTO db_sql_notes (text) VALUES (?1)", json!(["kept"])).await;
+    assert_eq!(inserted["success"], true, "Insert failed: {}", inserted);
+    let rows = run("query", "SELECT id, text FROM db_sql_notes", json!([])).await;
+    assert_eq!(rows["data"], json!([{ "id": 1, "text": "kept" }]));
+
+    // ...and no other
+    let refused = run("query", "SELECT * FROM users", json!([])).await;
+    assert!(refused["error"].as_str().unwrap().contains("may not read table 'users'"), "Unexpected response: {}", refused);
+}
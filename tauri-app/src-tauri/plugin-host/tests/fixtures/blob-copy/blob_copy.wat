@@ -0,0 +1,107 @@
+;; Copies /data/in.bin to /data/out.bin three bytes at a time through the
+;; blob host functions, or passes its input to blob_open, for the blob
+;; integration test.
+;; Rebuild blob_copy.wasm with:
+;;   wasm-tools parse blob_copy.wat -o blob_copy.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "store_u64" (func $store_u64 (param i64 i64)))
+  (import "extism:host/env" "load_u8" (func $load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "blob_open" (func $blob_open (param i64) (result i64)))
+  (import "extism:host/user" "blob_read_chunk" (func $blob_read_chunk (param i64 i64) (result i64)))
+  (import "extism:host/user" "blob_write_chunk" (func $blob_write_chunk (param i64 i64) (result i64)))
+  (import "extism:host/user" "blob_close" (func $blob_close (param i64) (result i64)))
+
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"path\":\"/data/in.bin\"}")
+  (data (i32.const 32) "{\"path\":\"/data/out.bin\",\"mode\":\"write\"}")
+
+  ;; Copy bytes [offset, offset + length) of this module's memory into host memory
+  (func $string (param $offset i32) (param $length i32) (result i64)
+    (local $block i64)
+    (local $i i32)
+    (local.set $block (call $alloc (i64.extend_i32_u (local.get $length))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $block) (i64.extend_i32_u (local.get $i)))
+          (i32.load8_u (i32.add (local.get $offset) (local.get $i))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  ;; The blob ID in a blob_open response: from offset 35, past
+  ;; {"success":true,"data":{"blob_id":", up to the next quote
+  (func $blob_id (param $response i64) (result i64)
+    (local $end i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $end (i64.const 35))
+    (block $found
+      (loop $next
+        (br_if $found (i32.eq (call $load_u8 (i64.add (local.get $response) (local.get $end))) (i32.const 34)))
+        (local.set $end (i64.add (local.get $end) (i64.const 1)))
+        (br $next)))
+    (local.set $block (call $alloc (i64.sub (local.get $end) (i64.const 35))))
+    (block $done
+      (loop $copy
+        (br_if $done (i64.ge_u (i64.add (local.get $i) (i64.const 35)) (local.get $end)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (call $load_u8 (i64.add (local.get $response) (i64.add (local.get $i) (i64.const 35)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $copy)))
+    (local.get $block))
+
+  ;; Copy /data/in.bin to /data/out.bin in three-byte chunks and output the
+  ;; response of closing the copy
+  (func (export "copy") (result i32)
+    (local $source i64)
+    (local $target i64)
+    (local $count i64)
+    (local $chunk i64)
+    (local $closed i64)
+    (local.set $source (call $blob_id (call $blob_open (call $string (i32.const 0) (i32.const 23)))))
+    (local.set $target (call $blob_id (call $blob_open (call $string (i32.const 32) (i32.const 39)))))
+    (local.set $count (call $alloc (i64.const 8)))
+    (call $store_u64 (local.get $count) (i64.const 3))
+    (block $done
+      (loop $next
+        (local.set $chunk (call $blob_read_chunk (local.get $source) (local.get $count)))
+        (br_if $done (i64.eqz (call $length (local.get $chunk))))
+        (drop (call $blob_write_chunk (local.get $target) (local.get $chunk)))
+        (br $next)))
+    (drop (call $blob_close (local.get $source)))
+    (local.set $closed (call $blob_close (local.get $target)))
+    (call $output_set (local.get $closed) (call $length (local.get $closed)))
+    (i32.const 0))
+
+  ;; Copy the input into host memory
+  (func $input (result i64)
+    (local $length i64)
+    (local $offset i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $offset (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  ;; Output blob_open(input)
+  (func (export "open") (result i32)
+    (local $response i64)
+    (local.set $response (call $blob_open (call $input)))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
@@ -0,0 +1,26 @@
+# Rebuilds every hand-written fixture: each <name>.wat next to a plugin.json
+# is assembled into the <name>.wasm beside it
+Write-Host "Building test fixtures..." -ForegroundColor Green
+
+if (!(Get-Command wasm-tools -ErrorAction SilentlyContinue)) {
+    Write-Host "wasm-tools not found; install it with: cargo install wasm-tools" -ForegroundColor Red
+    exit 1
+}
+
+$failed = 0
+foreach ($wat in Get-ChildItem -Path $PSScriptRoot -Filter *.wat -Recurse) {
+    $wasm = [System.IO.Path]::ChangeExtension($wat.FullName, ".wasm")
+    wasm-tools parse $wat.FullName -o $wasm
+
+    if ($LASTEXITCODE -ne 0) {
+        Write-Host "Failed: $($wat.Name)" -ForegroundColor Red
+        $failed++
+        continue
+    }
+    Write-Host "Built: $($wat.Directory.Name)\$([System.IO.Path]::GetFileName($wasm))" -ForegroundColor Green
+}
+
+if ($failed -gt 0) {
+    Write-Host "$failed fixture(s) failed to build" -ForegroundColor Red
+    exit 1
+}
@@ -0,0 +1,67 @@
+;; Publishes and polls bus messages, and re-emits pushed ones as UI events,
+;; for the message bus integration test. Rebuild bus_client.wasm with:
+;;   wasm-tools parse bus_client.wat -o bus_client.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "bus_publish" (func $bus_publish (param i64 i64) (result i64)))
+  (import "extism:host/user" "bus_poll" (func $bus_poll (param i64) (result i64)))
+  (import "extism:host/user" "emit_event" (func $emit_event (param i64 i64) (result i64)))
+
+  (memory 1)
+  (data (i32.const 0) "auth.login")
+  (data (i32.const 16) "bus-received")
+
+  ;; Copy bytes of this module's memory into a new memory block
+  (func $copy (param $offset i32) (param $size i64) (result i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $block (call $alloc (local.get $size)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $size)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (i32.load8_u (i32.add (local.get $offset) (i32.wrap_i64 (local.get $i)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  ;; Copy the input into a new memory block
+  (func $input (result i64)
+    (local $length i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $block (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  (func $output (param $block i64)
+    (call $output_set (local.get $block) (call $length (local.get $block))))
+
+  ;; Output bus_publish("auth.login", input)
+  (func (export "publish") (result i32)
+    (call $output (call $bus_publish (call $copy (i32.const 0) (i64.const 10)) (call $input)))
+    (i32.const 0))
+
+  ;; Output bus_poll(input)
+  (func (export "poll") (result i32)
+    (call $output (call $bus_poll (call $input)))
+    (i32.const 0))
+
+  ;; Output emit_event("bus-received", input)
+  (func (export "on_message") (result i32)
+    (call $output (call $emit_event (call $copy (i32.const 16) (i64.const 12)) (call $input)))
+    (i32.const 0)))
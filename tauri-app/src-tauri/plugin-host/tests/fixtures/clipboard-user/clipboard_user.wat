@@ -0,0 +1,43 @@
+;; Passes its input to the clipboard host functions, for the clipboard
+;; integration test.
+;; Rebuild clipboard_user.wasm with:
+;;   wasm-tools parse clipboard_user.wat -o clipboard_user.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "clipboard_read_text" (func $clipboard_read_text (result i64)))
+  (import "extism:host/user" "clipboard_write_text" (func $clipboard_write_text (param i64) (result i64)))
+
+  ;; Copy the input into host memory
+  (func $input (result i64)
+    (local $length i64)
+    (local $offset i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $offset (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $offset i64)
+    (call $output_set (local.get $offset) (call $length (local.get $offset))))
+
+  ;; Output clipboard_read_text()
+  (func (export "paste") (result i32)
+    (call $output (call $clipboard_read_text))
+    (i32.const 0))
+
+  ;; Output clipboard_write_text(input)
+  (func (export "copy") (result i32)
+    (call $output (call $clipboard_write_text (call $input)))
+    (i32.const 0)))
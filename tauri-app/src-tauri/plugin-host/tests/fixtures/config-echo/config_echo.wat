@@ -0,0 +1,32 @@
+;; Outputs the config value named by its input, for the integration tests.
+;; Rebuild config_echo.wasm with:
+;;   wasm-tools parse config_echo.wat -o config_echo.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/env" "config_get" (func $config_get (param i64) (result i64)))
+
+  ;; Output config[input], or nothing if it is not set
+  (func (export "get") (result i32)
+    (local $length i64)
+    (local $key i64)
+    (local $value i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $key (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $key) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $value (call $config_get (local.get $key)))
+    (if (i64.ne (local.get $value) (i64.const 0))
+      (then (call $output_set (local.get $value) (call $length (local.get $value)))))
+    (i32.const 0)))
@@ -0,0 +1,42 @@
+;; Reads its config through get_plugin_config and list_config, for the config
+;; host function integration test. Rebuild config_reader.wasm with:
+;;   wasm-tools parse config_reader.wat -o config_reader.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "get_plugin_config" (func $get_plugin_config (param i64) (result i64)))
+  (import "extism:host/user" "list_config" (func $list_config (result i64)))
+
+  ;; Copy the input into a new memory block
+  (func $input (result i64)
+    (local $length i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $block (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  (func $output (param $block i64)
+    (call $output_set (local.get $block) (call $length (local.get $block))))
+
+  ;; Output get_plugin_config(input)
+  (func (export "get") (result i32)
+    (call $output (call $get_plugin_config (call $input)))
+    (i32.const 0))
+
+  ;; Output list_config()
+  (func (export "list") (result i32)
+    (call $output (call $list_config))
+    (i32.const 0)))
@@ -0,0 +1,71 @@
+;; Passes its input to the crypto host function of the same name and
+;; outputs the response, for the crypto integration test. Rebuild
+;; crypto_forward.wasm with:
+;;   wasm-tools parse crypto_forward.wat -o crypto_forward.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "crypto_hash" (func $crypto_hash (param i64) (result i64)))
+  (import "extism:host/user" "crypto_generate_key" (func $crypto_generate_key (param i64) (result i64)))
+  (import "extism:host/user" "crypto_import_key" (func $crypto_import_key (param i64) (result i64)))
+  (import "extism:host/user" "crypto_delete_key" (func $crypto_delete_key (param i64) (result i64)))
+  (import "extism:host/user" "crypto_hmac_sign" (func $crypto_hmac_sign (param i64) (result i64)))
+  (import "extism:host/user" "crypto_hmac_verify" (func $crypto_hmac_verify (param i64) (result i64)))
+  (import "extism:host/user" "crypto_encrypt" (func $crypto_encrypt (param i64) (result i64)))
+  (import "extism:host/user" "crypto_decrypt" (func $crypto_decrypt (param i64) (result i64)))
+
+  ;; Copy the input into a new memory block
+  (func $input (result i64)
+    (local $length i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $block (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  (func $output (param $block i64)
+    (call $output_set (local.get $block) (call $length (local.get $block))))
+
+  (func (export "hash") (result i32)
+    (call $output (call $crypto_hash (call $input)))
+    (i32.const 0))
+
+  (func (export "generate_key") (result i32)
+    (call $output (call $crypto_generate_key (call $input)))
+    (i32.const 0))
+
+  (func (export "import_key") (result i32)
+    (call $output (call $crypto_import_key (call $input)))
+    (i32.const 0))
+
+  (func (export "delete_key") (result i32)
+    (call $output (call $crypto_delete_key (call $input)))
+    (i32.const 0))
+
+  (func (export "hmac_sign") (result i32)
+    (call $output (call $crypto_hmac_sign (call $input)))
+    (i32.const 0))
+
+  (func (export "hmac_verify") (result i32)
+    (call $output (call $crypto_hmac_verify (call $input)))
+    (i32.const 0))
+
+  (func (export "encrypt") (result i32)
+    (call $output (call $crypto_encrypt (call $input)))
+    (i32.const 0))
+
+  (func (export "decrypt") (result i32)
+    (call $output (call $crypto_decrypt (call $input)))
+    (i32.const 0)))
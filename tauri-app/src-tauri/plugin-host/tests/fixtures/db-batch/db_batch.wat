@@ -0,0 +1,35 @@
+;; Passes its input to db_batch, for the batch integration test.
+;; Rebuild db_batch.wasm with:
+;;   wasm-tools parse db_batch.wat -o db_batch.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "db_batch" (func $db_batch (param i64) (result i64)))
+
+  ;; Copy the input into host memory
+  (func $input (result i64)
+    (local $length i64)
+    (local $offset i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $offset (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  ;; Output db_batch(input)
+  (func (export "batch") (result i32)
+    (local $response i64)
+    (local.set $response (call $db_batch (call $input)))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
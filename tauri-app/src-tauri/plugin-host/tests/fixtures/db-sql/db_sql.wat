@@ -0,0 +1,65 @@
+;; Runs the SQL on the first line of its input with the JSON parameters on
+;; the rest, for the db_query and db_execute integration test.
+;; Rebuild db_sql.wasm with:
+;;   wasm-tools parse db_sql.wat -o db_sql.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "db_query" (func $db_query (param i64 i64) (result i64)))
+  (import "extism:host/user" "db_execute" (func $db_execute (param i64 i64) (result i64)))
+
+  ;; Offset of the first newline in the input, or its length if it has none
+  (func $newline (result i64)
+    (local $length i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (br_if $done (i32.eq (call $input_load_u8 (local.get $i)) (i32.const 10)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $i))
+
+  ;; Copy input bytes [start, end) into host memory
+  (func $part (param $start i64) (param $end i64) (result i64)
+    (local $offset i64)
+    (local $i i64)
+    (if (i64.gt_u (local.get $start) (local.get $end))
+      (then (local.set $start (local.get $end))))
+    (local.set $offset (call $alloc (i64.sub (local.get $end) (local.get $start))))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (i64.add (local.get $start) (local.get $i)) (local.get $end)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (i64.add (local.get $start) (local.get $i))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $response i64) (result i32)
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0))
+
+  ;; Output db_query(first line, rest)
+  (func (export "query") (result i32)
+    (local $newline i64)
+    (local.set $newline (call $newline))
+    (call $output
+      (call $db_query
+        (call $part (i64.const 0) (local.get $newline))
+        (call $part (i64.add (local.get $newline) (i64.const 1)) (call $input_length)))))
+
+  ;; Output db_execute(first line, rest)
+  (func (export "execute") (result i32)
+    (local $newline i64)
+    (local.set $newline (call $newline))
+    (call $output
+      (call $db_execute
+        (call $part (i64.const 0) (local.get $newline))
+        (call $part (i64.add (local.get $newline) (i64.const 1)) (call $input_length))))))
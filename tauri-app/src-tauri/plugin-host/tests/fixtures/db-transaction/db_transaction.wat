@@ -0,0 +1,82 @@
+;; Runs its input through db_batch inside a transaction, for the transaction
+;; integration test.
+;; Rebuild db_transaction.wasm with:
+;;   wasm-tools parse db_transaction.wat -o db_transaction.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "load_u8" (func $load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "db_begin" (func $db_begin (result i64)))
+  (import "extism:host/user" "db_commit" (func $db_commit (param i64) (result i64)))
+  (import "extism:host/user" "db_rollback" (func $db_rollback (param i64) (result i64)))
+  (import "extism:host/user" "db_batch" (func $db_batch (param i64) (result i64)))
+
+  ;; Copy the input into host memory
+  (func $input (result i64)
+    (local $length i64)
+    (local $offset i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $offset (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $offset i64)
+    (call $output_set (local.get $offset) (call $length (local.get $offset))))
+
+  ;; Cut {"transaction_id": ...} out of db_begin's response, which is
+  ;; {"success":true,"data":{"transaction_id":"..."},"error":null}
+  (func $handle (param $response i64) (result i64)
+    (local $length i64)
+    (local $handle i64)
+    (local $i i64)
+    (local.set $length (i64.sub (call $length (local.get $response)) (i64.const 37)))
+    (local.set $handle (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $handle) (local.get $i))
+          (call $load_u8 (i64.add (local.get $response) (i64.add (local.get $i) (i64.const 23)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $handle))
+
+  ;; Begin, run the batch and output db_commit's response
+  (func (export "commit") (result i32)
+    (local $handle i64)
+    (local.set $handle (call $handle (call $db_begin)))
+    (drop (call $db_batch (call $input)))
+    (call $output (call $db_commit (local.get $handle)))
+    (i32.const 0))
+
+  ;; Begin, run the batch and output db_rollback's response
+  (func (export "rollback") (result i32)
+    (local $handle i64)
+    (local.set $handle (call $handle (call $db_begin)))
+    (drop (call $db_batch (call $input)))
+    (call $output (call $db_rollback (local.get $handle)))
+    (i32.const 0))
+
+  ;; Begin, run the batch and return with the transaction still open
+  (func (export "abandon") (result i32)
+    (drop (call $db_begin))
+    (call $output (call $db_batch (call $input)))
+    (i32.const 0))
+
+  ;; Begin twice and output the second db_begin's response
+  (func (export "begin_twice") (result i32)
+    (drop (call $db_begin))
+    (call $output (call $db_begin))
+    (i32.const 0)))
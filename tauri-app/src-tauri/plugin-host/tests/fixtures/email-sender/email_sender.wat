@@ -0,0 +1,62 @@
+;; Sends the email its input describes, one part per line: recipients,
+;; subject, HTML body and the rest as the text body, for the send_email
+;; integration test.
+;; Rebuild email_sender.wasm with:
+;;   wasm-tools parse email_sender.wat -o email_sender.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "send_email" (func $send_email (param i64 i64 i64 i64) (result i64)))
+
+  ;; Offset of the first newline in the input at or after $i, or its length
+  ;; if there is none
+  (func $newline (param $i i64) (result i64)
+    (local $length i64)
+    (local.set $length (call $input_length))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (br_if $done (i32.eq (call $input_load_u8 (local.get $i)) (i32.const 10)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $i))
+
+  ;; Copy input bytes [start, end) into host memory
+  (func $part (param $start i64) (param $end i64) (result i64)
+    (local $offset i64)
+    (local $i i64)
+    (if (i64.gt_u (local.get $start) (local.get $end))
+      (then (local.set $start (local.get $end))))
+    (local.set $offset (call $alloc (i64.sub (local.get $end) (local.get $start))))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (i64.add (local.get $start) (local.get $i)) (local.get $end)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (i64.add (local.get $start) (local.get $i))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $response i64) (result i32)
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0))
+
+  ;; Output send_email(first line, second line, third line, rest)
+  (func (export "send") (result i32)
+    (local $first i64)
+    (local $second i64)
+    (local $third i64)
+    (local.set $first (call $newline (i64.const 0)))
+    (local.set $second (call $newline (i64.add (local.get $first) (i64.const 1))))
+    (local.set $third (call $newline (i64.add (local.get $second) (i64.const 1))))
+    (call $output
+      (call $send_email
+        (call $part (i64.const 0) (local.get $first))
+        (call $part (i64.add (local.get $first) (i64.const 1)) (local.get $second))
+        (call $part (i64.add (local.get $second) (i64.const 1)) (local.get $third))
+        (call $part (i64.add (local.get $third) (i64.const 1)) (call $input_length))))))
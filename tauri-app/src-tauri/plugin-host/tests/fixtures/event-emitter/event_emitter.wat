@@ -0,0 +1,45 @@
+;; Emits its input as the payload of a "progress" event, for the emit_event
+;; integration test. Rebuild event_emitter.wasm with:
+;;   wasm-tools parse event_emitter.wat -o event_emitter.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "emit_event" (func $emit_event (param i64 i64) (result i64)))
+
+  (memory 1)
+  (data (i32.const 0) "progress")
+
+  ;; Output emit_event("progress", input)
+  (func (export "announce") (result i32)
+    (local $length i64)
+    (local $payload i64)
+    (local $name i64)
+    (local $response i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $payload (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $payload) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $name (call $alloc (i64.const 8)))
+    (local.set $i (i64.const 0))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (i64.const 8)))
+        (call $store_u8
+          (i64.add (local.get $name) (local.get $i))
+          (i32.load8_u (i32.wrap_i64 (local.get $i))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $response (call $emit_event (local.get $name) (local.get $payload)))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
@@ -0,0 +1,14 @@
+;; Outputs the response of list_host_functions, for the introspection integration test.
+;; Rebuild function_lister.wasm with:
+;;   wasm-tools parse function_lister.wat -o function_lister.wasm
+(module
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "list_host_functions" (func $list_host_functions (result i64)))
+
+  ;; Output list_host_functions()
+  (func (export "list") (result i32)
+    (local $functions i64)
+    (local.set $functions (call $list_host_functions))
+    (call $output_set (local.get $functions) (call $length (local.get $functions)))
+    (i32.const 0)))
@@ -0,0 +1,31 @@
+;; Passes its input to a host function the embedding application provides,
+;; for the HostBuilder integration test. Rebuild greeter.wasm with:
+;;   wasm-tools parse greeter.wat -o greeter.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "app_greeting" (func $app_greeting (param i64) (result i64)))
+
+  ;; Output app_greeting(input)
+  (func (export "greet") (result i32)
+    (local $length i64)
+    (local $name i64)
+    (local $greeting i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $name (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $name) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $greeting (call $app_greeting (local.get $name)))
+    (call $output_set (local.get $greeting) (call $length (local.get $greeting)))
+    (i32.const 0)))
@@ -0,0 +1,14 @@
+;; Outputs the response of get_host_info, for the host info integration test.
+;; Rebuild host_info.wasm with:
+;;   wasm-tools parse host_info.wat -o host_info.wasm
+(module
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "get_host_info" (func $get_host_info (result i64)))
+
+  ;; Output get_host_info()
+  (func (export "info") (result i32)
+    (local $info i64)
+    (local.set $info (call $get_host_info))
+    (call $output_set (local.get $info) (call $length (local.get $info)))
+    (i32.const 0)))
@@ -0,0 +1,54 @@
+;; Logs its input through host_log at debug level, for the leveled logging
+;; integration test. Rebuild host_log.wasm with:
+;;   wasm-tools parse host_log.wat -o host_log.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "host_log" (func $host_log (param i64 i64 i64) (result i64)))
+
+  (memory 1)
+  (data (i32.const 0) "debug")
+  (data (i32.const 16) "{\"source\":\"fixture\"}")
+
+  ;; Copy bytes of this module's memory into a new Extism memory block
+  (func $copy (param $offset i32) (param $size i64) (result i64)
+    (local $block i64)
+    (local $i i64)
+    (local.set $block (call $alloc (local.get $size)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $size)))
+        (call $store_u8
+          (i64.add (local.get $block) (local.get $i))
+          (i32.load8_u (i32.add (local.get $offset) (i32.wrap_i64 (local.get $i)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $block))
+
+  ;; Output host_log("debug", input, {"source":"fixture"})
+  (func (export "note") (result i32)
+    (local $length i64)
+    (local $message i64)
+    (local $response i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $message (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $message) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $response
+      (call $host_log
+        (call $copy (i32.const 0) (i64.const 5))
+        (local.get $message)
+        (call $copy (i32.const 16) (i64.const 20))))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
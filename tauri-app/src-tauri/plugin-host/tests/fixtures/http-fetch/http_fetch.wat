@@ -0,0 +1,32 @@
+;; Makes the HTTP request its input describes, for the integration tests.
+;; Rebuild http_fetch.wasm with:
+;;   wasm-tools parse http_fetch.wat -o http_fetch.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/env" "http_request" (func $http_request (param i64 i64) (result i64)))
+
+  ;; Send the input, an Extism HttpRequest as JSON, and output the response body
+  (func (export "fetch") (result i32)
+    (local $length i64)
+    (local $request i64)
+    (local $response i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $request (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $request) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $response (call $http_request (local.get $request) (i64.const 0)))
+    (if (i64.ne (local.get $response) (i64.const 0))
+      (then (call $output_set (local.get $response) (call $length (local.get $response)))))
+    (i32.const 0)))
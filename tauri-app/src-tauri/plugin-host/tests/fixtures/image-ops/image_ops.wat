@@ -0,0 +1,76 @@
+;; Passes its input to the image host functions and outputs the result, for
+;; the image integration test: info and decode take an encoded image, resize
+;; and encode take JSON options on the first line and RGBA pixels after it.
+;; Rebuild image_ops.wasm with:
+;;   wasm-tools parse image_ops.wat -o image_ops.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "image_info" (func $image_info (param i64) (result i64)))
+  (import "extism:host/user" "image_decode" (func $image_decode (param i64) (result i64)))
+  (import "extism:host/user" "image_resize" (func $image_resize (param i64 i64) (result i64)))
+  (import "extism:host/user" "image_encode" (func $image_encode (param i64 i64) (result i64)))
+
+  ;; Offset of the first newline in the input, or its length if it has none
+  (func $newline (result i64)
+    (local $length i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (br_if $done (i32.eq (call $input_load_u8 (local.get $i)) (i32.const 10)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $i))
+
+  ;; Copy input bytes [start, end) into host memory
+  (func $part (param $start i64) (param $end i64) (result i64)
+    (local $offset i64)
+    (local $i i64)
+    (if (i64.gt_u (local.get $start) (local.get $end))
+      (then (local.set $start (local.get $end))))
+    (local.set $offset (call $alloc (i64.sub (local.get $end) (local.get $start))))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (i64.add (local.get $start) (local.get $i)) (local.get $end)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (i64.add (local.get $start) (local.get $i))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $response i64) (result i32)
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0))
+
+  ;; Output image_info(input)
+  (func (export "info") (result i32)
+    (call $output (call $image_info (call $part (i64.const 0) (call $input_length)))))
+
+  ;; Output image_decode(input)
+  (func (export "decode") (result i32)
+    (call $output (call $image_decode (call $part (i64.const 0) (call $input_length)))))
+
+  ;; Output image_resize(rest, first line)
+  (func (export "resize") (result i32)
+    (local $newline i64)
+    (local.set $newline (call $newline))
+    (call $output
+      (call $image_resize
+        (call $part (i64.add (local.get $newline) (i64.const 1)) (call $input_length))
+        (call $part (i64.const 0) (local.get $newline)))))
+
+  ;; Output image_encode(rest, first line)
+  (func (export "encode") (result i32)
+    (local $newline i64)
+    (local.set $newline (call $newline))
+    (call $output
+      (call $image_encode
+        (call $part (i64.add (local.get $newline) (i64.const 1)) (call $input_length))
+        (call $part (i64.const 0) (local.get $newline))))))
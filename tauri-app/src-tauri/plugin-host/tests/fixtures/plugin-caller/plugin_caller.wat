@@ -0,0 +1,32 @@
+;; Passes its input to call_plugin and outputs the response, to test calls
+;; between plugins.
+;; Rebuild plugin_caller.wasm with:
+;;   wasm-tools parse plugin_caller.wat -o plugin_caller.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "call_plugin" (func $call_plugin (param i64) (result i64)))
+
+  ;; Output call_plugin(input)
+  (func (export "forward") (result i32)
+    (local $length i64)
+    (local $request i64)
+    (local $i i64)
+    (local $response i64)
+    (local.set $length (call $input_length))
+    (local.set $request (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $request) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $response (call $call_plugin (local.get $request)))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
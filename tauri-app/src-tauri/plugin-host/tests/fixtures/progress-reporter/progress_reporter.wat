@@ -0,0 +1,67 @@
+;; Reports the percentage on the first line of its input with the rest as
+;; the message, for the report_progress integration test.
+;; Rebuild progress_reporter.wasm with:
+;;   wasm-tools parse progress_reporter.wat -o progress_reporter.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "report_progress" (func $report_progress (param f64 i64) (result i64)))
+
+  ;; Offset of the first newline in the input at or after $i, or its length
+  ;; if there is none
+  (func $newline (param $i i64) (result i64)
+    (local $length i64)
+    (local.set $length (call $input_length))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (br_if $done (i32.eq (call $input_load_u8 (local.get $i)) (i32.const 10)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $i))
+
+  ;; Copy input bytes [start, end) into host memory
+  (func $part (param $start i64) (param $end i64) (result i64)
+    (local $offset i64)
+    (local $i i64)
+    (if (i64.gt_u (local.get $start) (local.get $end))
+      (then (local.set $start (local.get $end))))
+    (local.set $offset (call $alloc (i64.sub (local.get $end) (local.get $start))))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (i64.add (local.get $start) (local.get $i)) (local.get $end)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (i64.add (local.get $start) (local.get $i))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  (func $output (param $response i64) (result i32)
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0))
+
+  ;; Output report_progress(first line as a decimal number, rest)
+  (func (export "report") (result i32)
+    (local $first i64)
+    (local $i i64)
+    (local $percent i64)
+    (local.set $first (call $newline (i64.const 0)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $first)))
+        (local.set $percent
+          (i64.add
+            (i64.mul (local.get $percent) (i64.const 10))
+            (i64.extend_i32_u
+              (i32.sub (call $input_load_u8 (local.get $i)) (i32.const 48)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (call $output
+      (call $report_progress
+        (f64.convert_i64_u (local.get $percent))
+        (call $part (i64.add (local.get $first) (i64.const 1)) (call $input_length))))))
@@ -0,0 +1,33 @@
+;; Spins forever or echoes its input, to test resource quotas.
+;; Rebuild quota_limits.wasm with:
+;;   wasm-tools parse quota_limits.wat -o quota_limits.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+
+  ;; Never return
+  (func (export "spin") (result i32)
+    (loop $forever
+      (br $forever))
+    (i32.const 0))
+
+  ;; Output the input unchanged
+  (func (export "echo") (result i32)
+    (local $length i64)
+    (local $output i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $output (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $output) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (call $output_set (local.get $output) (local.get $length))
+    (i32.const 0)))
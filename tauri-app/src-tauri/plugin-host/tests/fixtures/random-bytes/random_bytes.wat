@@ -0,0 +1,45 @@
+;; Passes its input, a little-endian i64 count, to generate_random_bytes or
+;; the deprecated generate_random_bytes_json and outputs the result, for the
+;; byte payload integration test.
+;; Rebuild random_bytes.wasm with:
+;;   wasm-tools parse random_bytes.wat -o random_bytes.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+  (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "generate_random_bytes" (func $generate_random_bytes (param i64) (result i64)))
+  (import "extism:host/user" "generate_random_bytes_json" (func $generate_random_bytes_json (param i64) (result i64)))
+
+  ;; Copy the input into host memory
+  (func $input (result i64)
+    (local $length i64)
+    (local $offset i64)
+    (local $i i64)
+    (local.set $length (call $input_length))
+    (local.set $offset (call $alloc (local.get $length)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (call $store_u8
+          (i64.add (local.get $offset) (local.get $i))
+          (call $input_load_u8 (local.get $i)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $offset))
+
+  ;; Output generate_random_bytes(input)
+  (func (export "random") (result i32)
+    (local $bytes i64)
+    (local.set $bytes (call $generate_random_bytes (call $input)))
+    (call $output_set (local.get $bytes) (call $length (local.get $bytes)))
+    (i32.const 0))
+
+  ;; Output generate_random_bytes_json(input)
+  (func (export "random_json") (result i32)
+    (local $bytes i64)
+    (local.set $bytes (call $generate_random_bytes_json (call $input)))
+    (call $output_set (local.get $bytes) (call $length (local.get $bytes)))
+    (i32.const 0)))
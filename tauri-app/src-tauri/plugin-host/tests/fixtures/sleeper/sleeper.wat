@@ -0,0 +1,31 @@
+;; Sleeps for the number of milliseconds in its input, for the host_sleep
+;; integration test.
+;; Rebuild sleeper.wasm with:
+;;   wasm-tools parse sleeper.wat -o sleeper.wasm
+(module
+  (import "extism:host/env" "input_length" (func $input_length (result i64)))
+  (import "extism:host/env" "input_load_u8" (func $input_load_u8 (param i64) (result i32)))
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "host_sleep" (func $host_sleep (param i64) (result i64)))
+
+  ;; Output host_sleep(input as a decimal number)
+  (func (export "nap") (result i32)
+    (local $length i64)
+    (local $i i64)
+    (local $ms i64)
+    (local $response i64)
+    (local.set $length (call $input_length))
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $length)))
+        (local.set $ms
+          (i64.add
+            (i64.mul (local.get $ms) (i64.const 10))
+            (i64.extend_i32_u
+              (i32.sub (call $input_load_u8 (local.get $i)) (i32.const 48)))))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.set $response (call $host_sleep (local.get $ms)))
+    (call $output_set (local.get $response) (call $length (local.get $response)))
+    (i32.const 0)))
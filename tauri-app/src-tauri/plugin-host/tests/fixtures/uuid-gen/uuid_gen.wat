@@ -0,0 +1,14 @@
+;; Outputs a UUID from generate_uuid_v4, for the UUID integration test.
+;; Rebuild uuid_gen.wasm with:
+;;   wasm-tools parse uuid_gen.wat -o uuid_gen.wasm
+(module
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "generate_uuid_v4" (func $generate_uuid_v4 (result i64)))
+
+  ;; Output generate_uuid_v4()
+  (func (export "uuid") (result i32)
+    (local $uuid i64)
+    (local.set $uuid (call $generate_uuid_v4))
+    (call $output_set (local.get $uuid) (call $length (local.get $uuid)))
+    (i32.const 0)))
@@ -0,0 +1,15 @@
+;; Outputs the response of get_current_user, for the calling user
+;; integration test.
+;; Rebuild whoami.wasm with:
+;;   wasm-tools parse whoami.wat -o whoami.wasm
+(module
+  (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+  (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+  (import "extism:host/user" "get_current_user" (func $get_current_user (result i64)))
+
+  ;; Output get_current_user()
+  (func (export "whoami") (result i32)
+    (local $user i64)
+    (local.set $user (call $get_current_user))
+    (call $output_set (local.get $user) (call $length (local.get $user)))
+    (i32.const 0)))
@@ -0,0 +1,585 @@
+//! End-to-end tests of the host functions other than the database ones
+//!
+//! Prebuilt plugins from tests/fixtures are installed through
+//! PluginManager and call the real host functions against a fresh in-memory
+//! database.
+mod common;
+
+use common::*;
+use plugin_host::db::operations;
+use plugin_host::error::{AppError, Quota};
+use extism::{Function, UserData, PTR};
+use plugin_host::host_functions::{emit, HOST_API_LEVEL, HOST_FUNCTION_NAMES, MAX_RANDOM_BYTES};
+use plugin_host::plugins::{Capability, ExecutionContext, SandboxProfile};
+use plugin_host::clipboard::Clipboard;
+use plugin_host::mail::{Email, Mailer, SmtpSettings};
+use plugin_host::settings::{SettingsStore, PERSIST_EXECUTION_TRACES_KEY, SMTP_KEY};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_log_records_level_fields_and_execution() {
+    let app = TestApp::new();
+    app.install("host-log").await;
+
+    let output = app.manager.execute_plugin("host-log", "note", b"cache warmed").await.expect("note failed");
+    let response: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(response["success"], true, "host_log failed: {}", response);
+
+    let logs = app.manager.get_logs("host-log", None).await;
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].level, "debug");
+    assert_eq!(logs[0].message, "cache warmed");
+    assert_eq!(logs[0].fields, json!({ "source": "fixture" }));
+    assert!(logs[0].execution_id.is_some(), "Entries logged during a call should name the execution");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_emitted_events_reach_subscribers() {
+    let app = TestApp::new();
+    app.install("event-emitter").await;
+    let mut emitted = emit::subscribe();
+
+    let response = app.call("event-emitter", "announce", json!({ "done": 3, "total": 10 })).await;
+    assert_eq!(response["success"], true, "emit_event failed: {}", response);
+
+    // The channel is shared by every test running in this process
+    let event = loop {
+        let event = emitted.recv().await.expect("Event channel closed");
+        if event.plugin == "event-emitter" {
+            break event;
+        }
+    };
+    assert_eq!(event.event, "progress");
+    assert_eq!(event.payload, json!({ "done": 3, "total": 10 }));
+    assert!(event.execution_id.is_some());
+
+    // Payloads over the limit are refused and never published
+    let oversized = app.call("event-emitter", "announce", json!("x".repeat(emit::MAX_EVENT_PAYLOAD_BYTES))).await;
+    assert_eq!(oversized["success"], false);
+    assert!(oversized["error"].as_str().unwrap().contains("at most"), "Unexpected error: {}", oversized);
+
+    assert!(emit::is_emitted_event_name("sync/progress:1"));
+    assert!(!emit::is_emitted_event_name("progress update"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_crypto_host_functions_keep_keys_host_side() {
+    let app = TestApp::new();
+    app.install("crypto-forward").await;
+    let call = |function: &'static str, input: Value| app.call("crypto-forward", function, input);
+
+    let digest = call("hash", json!({ "algorithm": "sha256", "data": "abc" })).await;
+    assert_eq!(digest["data"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+    // RFC 4231 test case 2
+    let imported = call("import_key", json!({ "algorithm": "hmac-sha256", "key": "SmVmZQ==" })).await;
+    let hmac_key = imported["data"]["key_id"].as_str().expect("No key ID").to_string();
+    let data = "what do ya want for nothing?";
+    let signature = call("hmac_sign", json!({ "key_id": hmac_key, "data": data })).await;
+    assert_eq!(signature["data"], "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    let verified = call("hmac_verify", json!({ "key_id": hmac_key, "data": data, "signature": signature["data"] })).await;
+    assert_eq!(verified["data"], true);
+    let verified = call("hmac_verify", json!({ "key_id": hmac_key, "data": "tampered", "signature": signature["data"] })).await;
+    assert_eq!(verified["data"], false);
+
+    let generated = call("generate_key", json!({ "algorithm": "aes-256-gcm" })).await;
+    let aes_key = generated["data"]["key_id"].as_str().expect("No key ID").to_string();
+    let sealed = call("encrypt", json!({ "key_id": aes_key, "plaintext": "secret notes", "aad": "note-1" })).await;
+    let ciphertext = sealed["data"].as_str().expect("No ciphertext").to_string();
+    let opened = call("decrypt", json!({ "key_id": aes_key, "ciphertext": ciphertext, "aad": "note-1" })).await;
+    assert_eq!(opened["data"], "secret notes");
+    let opened = call("decrypt", json!({ "key_id": aes_key, "ciphertext": ciphertext, "aad": "note-2" })).await;
+    assert_eq!(opened["success"], false, "Decrypting with different AAD should fail");
+
+    // Keys only work for their algorithm, and are gone once the plugin is uninstalled
+    let misused = call("hmac_sign", json!({ "key_id": aes_key, "data": data })).await;
+    assert_eq!(misused["success"], false);
+    let deleted = call("delete_key", json!({ "key_id": hmac_key })).await;
+    assert_eq!(deleted["data"], true);
+    app.manager.uninstall_plugin("crypto-forward").await.expect("Uninstall failed");
+    let remaining = app
+        .database
+        .with_connection(|conn| operations::count_plugin_keys(conn, "crypto-forward"))
+        .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_uuid_v4_returns_random_uuids() {
+    let app = TestApp::new();
+    app.install("uuid-gen").await;
+
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..3 {
+        let output = app.manager.execute_plugin("uuid-gen", "uuid", b"").await.expect("uuid failed");
+        let uuid = uuid::Uuid::parse_str(std::str::from_utf8(&output).unwrap()).expect("Output should be a UUID");
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_eq!(uuid.get_variant(), uuid::Variant::RFC4122);
+        assert!(seen.insert(uuid));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bus_messages_are_pushed_and_polled_between_plugins() {
+    let app = TestApp::new();
+    app.install("bus-client").await;
+    // A second copy under another ID, to receive what the first publishes
+    let listener_dir = app.root.join("staging/bus-listener");
+    std::fs::create_dir_all(&listener_dir).unwrap();
+    for entry in std::fs::read_dir(fixture_dir("bus-client")).unwrap() {
+        let entry = entry.unwrap();
+        std::fs::copy(entry.path(), listener_dir.join(entry.file_name())).unwrap();
+    }
+    let mut manifest: Value = serde_json::from_str(&std::fs::read_to_string(listener_dir.join("plugin.json")).unwrap()).unwrap();
+    manifest["name"] = json!("bus-listener");
+    std::fs::write(listener_dir.join("plugin.json"), manifest.to_string()).unwrap();
+    app.install_dir(&listener_dir, &[]).await.expect("Failed to install bus-listener");
+
+    let manager = &app.manager;
+    let poll = |plugin: &'static str| async move {
+        let output = manager.execute_plugin(plugin, "poll", b"auth.*").await.expect("poll failed");
+        serde_json::from_slice::<Value>(&output).unwrap()
+    };
+    // The first poll of a pattern starts queueing
+    assert_eq!(poll("bus-listener").await["data"], json!([]));
+    assert_eq!(poll("bus-client").await["data"], json!([]));
+
+    let mut published = app.manager.bus().subscribe();
+    let mut emitted = emit::subscribe();
+    let response = app.call("bus-client", "publish", json!({ "user": "ada" })).await;
+    assert_eq!(response["success"], true, "bus_publish failed: {}", response);
+
+    let messages = poll("bus-listener").await["data"].clone();
+    assert_eq!(messages.as_array().map(Vec::len), Some(1));
+    assert_eq!(messages[0]["topic"], "auth.login");
+    assert_eq!(messages[0]["source"], "bus-client");
+    assert_eq!(messages[0]["payload"], json!({ "user": "ada" }));
+    assert_eq!(poll("bus-listener").await["data"], json!([]), "Polled messages should not be returned again");
+    assert_eq!(poll("bus-client").await["data"], json!([]), "Plugins should not receive their own messages");
+
+    // Pushed to the manifest subscription of every plugin but the publisher
+    let message = published.recv().await.expect("Bus closed");
+    app.manager.dispatch_bus_message(&message).await;
+    let event = loop {
+        let event = emitted.recv().await.expect("Event channel closed");
+        if event.event == "bus-received" && event.plugin.starts_with("bus-") {
+            break event;
+        }
+    };
+    assert_eq!(event.plugin, "bus-listener");
+    assert_eq!(event.payload["topic"], "auth.login");
+    assert_eq!(event.payload["source"], "bus-client");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_sleep_is_bounded_and_cancellable() {
+    let app = TestApp::new();
+    app.install("sleeper").await;
+    let nap = |context: ExecutionContext, ms: u64| {
+        let manager = &app.manager;
+        async move { manager.execute_plugin_with_context("sleeper", "nap", ms.to_string().as_bytes(), &context).await }
+    };
+
+    let started = std::time::Instant::now();
+    let slept: Value = serde_json::from_slice(&nap(ExecutionContext::new(), 50).await.expect("nap failed")).unwrap();
+    assert_eq!(slept["success"], true);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+
+    // Over the manifest's max_sleep_ms
+    let refused: Value = serde_json::from_slice(&nap(ExecutionContext::new(), 6000).await.expect("nap failed")).unwrap();
+    assert_eq!(refused["success"], false);
+    assert!(refused["error"].as_str().unwrap().contains("0 to 5000 ms"), "Unexpected error: {}", refused["error"]);
+
+    // Cancelling wakes the sleep rather than waiting it out
+    let context = ExecutionContext::new();
+    let started = std::time::Instant::now();
+    let cancel = async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(app.manager.cancel_execution(&context.execution_id));
+    };
+    let (cancelled, _) = tokio::join!(nap(context.clone(), 900), cancel);
+    assert!(cancelled.is_err(), "Cancelled call should fail");
+    assert!(started.elapsed() < std::time::Duration::from_millis(600), "Took {:?}", started.elapsed());
+
+    // So does running past max_execution_ms
+    let started = std::time::Instant::now();
+    let timed_out = AppError::from(nap(ExecutionContext::new(), 4000).await.expect_err("Call should time out"));
+    assert_eq!(timed_out.quota.unwrap().quota, Quota::ExecutionTime);
+    assert!(started.elapsed() < std::time::Duration::from_millis(2000), "Took {:?}", started.elapsed());
+}
+
+/// Clipboard kept in memory, standing in for the desktop's
+#[derive(Default)]
+struct MemoryClipboard(std::sync::Mutex<Option<String>>);
+
+impl Clipboard for MemoryClipboard {
+    fn read_text(&self) -> anyhow::Result<Option<String>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn write_text(&self, text: &str) -> anyhow::Result<()> {
+        *self.0.lock().unwrap() = Some(text.to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clipboard_needs_an_embedder_clipboard_and_the_capability() {
+    let app = TestApp::new();
+    let manager = &app.manager;
+    let call = |function: &'static str, input: &'static str| async move {
+        let output = manager.execute_plugin("clipboard-user", function, input.as_bytes()).await;
+        serde_json::from_slice::<Value>(&output.expect("Call failed")).unwrap()
+    };
+
+    // Without a clipboard the functions are linked but fail
+    app.install_granting("clipboard-user", &[Capability::Clipboard]).await;
+    let paste = call("paste", "").await;
+    assert_eq!(paste["success"], false);
+    assert_eq!(paste["error"], "No clipboard is available to plugins");
+
+    let clipboard = Arc::new(MemoryClipboard::default());
+    app.manager.set_clipboard(clipboard.clone());
+    app.install_granting("clipboard-user", &[Capability::Clipboard]).await;
+    assert_eq!(call("paste", "").await["data"], Value::Null, "An empty clipboard should read as null");
+    assert_eq!(call("copy", "hello clipboard").await["success"], true);
+    assert_eq!(clipboard.0.lock().unwrap().as_deref(), Some("hello clipboard"));
+    *clipboard.0.lock().unwrap() = Some("pasted by the user".to_string());
+    assert_eq!(call("paste", "").await["data"], "pasted by the user");
+
+    // The strict sandbox withholds it like the other sensitive capabilities
+    app.manager.set_sandbox_overrides([("clipboard-user".to_string(), SandboxProfile::Strict)]);
+    let error = format!("{:#}", app.install_dir(&fixture_dir("clipboard-user"), &[Capability::Clipboard]).await.unwrap_err());
+    assert!(error.contains("missing host functions clipboard_read_text, clipboard_write_text"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_generate_random_bytes_returns_raw_bytes_from_level_15() {
+    let app = TestApp::new();
+    app.install("random-bytes").await;
+    let manager = &app.manager;
+    let random = |count: i64| async move { manager.execute_plugin("random-bytes", "random", &count.to_le_bytes()).await };
+
+    let first = random(16).await.expect("random failed");
+    let second = random(16).await.expect("random failed");
+    assert_eq!((first.len(), second.len()), (16, 16));
+    assert_ne!(first, second);
+    assert!(random(0).await.expect("random failed").is_empty());
+    assert!(random(MAX_RANDOM_BYTES + 1).await.is_err());
+    assert!(random(-1).await.is_err());
+
+    // The JSON form stays available under its deprecated name
+    let output = manager.execute_plugin("random-bytes", "random_json", &16i64.to_le_bytes()).await.expect("random_json failed");
+    let bytes: Vec<u8> = serde_json::from_slice(&output).expect("Output should be a JSON array");
+    assert_eq!(bytes.len(), 16);
+
+    // Plugins built against an older level still get a JSON array
+    let legacy = TestApp::new();
+    let staging = legacy.root.join("staging");
+    copy_fixture("random-bytes", &staging);
+    let manifest_path = staging.join("random-bytes/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest.as_object_mut().unwrap().remove("host_api_level");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    legacy
+        .install_dir(&staging.join("random-bytes"), &[])
+        .await
+        .expect("Failed to install the legacy build");
+    let output = legacy
+        .manager
+        .execute_plugin("random-bytes", "random", &16i64.to_le_bytes())
+        .await
+        .expect("random failed");
+    let bytes: Vec<u8> = serde_json::from_slice(&output).expect("Output should be a JSON array");
+    assert_eq!(bytes.len(), 16);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blobs_move_files_in_chunks_within_mounts() {
+    let app = TestApp::new();
+    app.install("blob-copy").await;
+    let manifest = app.manager.get_plugin("blob-copy").await.unwrap();
+    let data_dir = app.manager.plugin_data_dir(&manifest);
+    let contents: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    std::fs::write(data_dir.join("in.bin"), &contents).unwrap();
+
+    let copied = app.call("blob-copy", "copy", json!({})).await;
+    assert_eq!(copied["success"], true, "Copy failed: {}", copied);
+    assert_eq!(std::fs::read(data_dir.join("out.bin")).unwrap(), contents);
+
+    let opened = app.call("blob-copy", "open", json!({ "path": "/data/in.bin" })).await;
+    assert_eq!(opened["success"], true, "Open failed: {}", opened);
+    assert_eq!(opened["data"]["size"], 1000);
+
+    // Only paths under the plugin's mounts can be opened
+    let refused = [
+        (json!({ "path": "/etc/passwd" }), "not in a directory this plugin may access"),
+        (json!({ "path": "/data/../in.bin" }), "without '..'"),
+        (json!({ "path": "data/in.bin" }), "must be absolute"),
+        (json!({ "path": "/data/missing.bin" }), "Failed to open"),
+    ];
+    for (request, error) in refused {
+        let response = app.call("blob-copy", "open", request.clone()).await;
+        assert_eq!(response["success"], false, "{} should be refused", request);
+        assert!(response["error"].as_str().unwrap().contains(error), "Unexpected error for {}: {}", request, response);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_image_functions_decode_resize_and_encode() {
+    let app = TestApp::new();
+    app.install("image-ops").await;
+    let manager = &app.manager;
+    let run = |function: &'static str, input: Vec<u8>| async move { manager.execute_plugin("image-ops", function, &input).await };
+    let with_options = |options: Value, pixels: &[u8]| [options.to_string().as_bytes(), b"\n", pixels].concat();
+
+    // A 4x2 image, red on the left and blue on the right
+    let source = image::RgbaImage::from_fn(4, 2, |x, _| {
+        if x < 2 { image::Rgba([255, 0, 0, 255]) } else { image::Rgba([0, 0, 255, 255]) }
+    });
+    let mut png = Vec::new();
+    source.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+
+    let info: Value = serde_json::from_slice(&run("info", png.clone()).await.expect("info failed")).unwrap();
+    assert_eq!(info["data"], json!({ "format": "png", "width": 4, "height": 2 }));
+    let pixels = run("decode", png).await.expect("decode failed");
+    assert_eq!(pixels, source.as_raw().as_slice());
+
+    let resize = json!({ "width": 4, "height": 2, "to_width": 2, "to_height": 1, "filter": "nearest" });
+    let resized = run("resize", with_options(resize, &pixels)).await.expect("resize failed");
+    assert_eq!(resized, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+    for (format, guessed) in [("png", image::ImageFormat::Png), ("jpeg", image::ImageFormat::Jpeg), ("webp", image::ImageFormat::WebP)] {
+        let options = json!({ "width": 2, "height": 1, "format": format, "quality": 90 });
+        let encoded = run("encode", with_options(options, &resized)).await.expect("encode failed");
+        let decoded = image::load_from_memory(&encoded).unwrap_or_else(|e| panic!("{} output does not decode: {}", format, e));
+        assert_eq!(image::guess_format(&encoded).unwrap(), guessed);
+        assert_eq!((decoded.width(), decoded.height()), (2, 1));
+    }
+
+    // Pixels must match the dimensions they are given, and sizes are capped
+    let mismatched = json!({ "width": 3, "height": 2, "format": "png" });
+    assert!(run("encode", with_options(mismatched, &pixels)).await.is_err());
+    let huge = json!({ "width": 4, "height": 2, "to_width": 100_000, "to_height": 1 });
+    assert!(run("resize", with_options(huge, &pixels)).await.is_err());
+    assert!(run("decode", b"not an image".to_vec()).await.is_err());
+    let unknown: Value = serde_json::from_slice(&run("info", b"not an image".to_vec()).await.expect("info failed")).unwrap();
+    assert_eq!(unknown["success"], false);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_host_info_describes_the_host() {
+    let app = TestApp::new();
+    app.manager.set_app_version(semver::Version::new(2, 5, 0));
+    app.install("host-info").await;
+
+    let info = app.call("host-info", "info", json!({})).await;
+    assert_eq!(info["success"], true, "get_host_info failed: {}", info);
+    let info = &info["data"];
+    assert_eq!(info["os"], std::env::consts::OS);
+    assert_eq!(info["arch"], std::env::consts::ARCH);
+    assert_eq!(info["platform"], format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH));
+    assert_eq!(info["path_separator"], std::path::MAIN_SEPARATOR_STR);
+    assert_eq!(info["app_version"], "2.5.0");
+    assert_eq!(info["host_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["host_api_level"], HOST_API_LEVEL);
+    assert_eq!(info["data_dir"], "/data");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_list_host_functions_describes_linked_functions() {
+    let app = TestApp::new();
+    app.manager.set_host_functions(Arc::new(|_plugin_id: &str| {
+        vec![Function::new("app_greeting", [PTR], [PTR], UserData::default(), app_greeting)]
+    }));
+    app.install("function-lister").await;
+
+    let listed = app.call("function-lister", "list", json!({})).await;
+    assert_eq!(listed["success"], true, "list_host_functions failed: {}", listed);
+    let functions = listed["data"].as_array().unwrap();
+    let names: Vec<&str> = functions.iter().map(|function| function["name"].as_str().unwrap()).collect();
+    assert!(names.is_sorted(), "Functions should be sorted by name: {:?}", names);
+    let function = |name: &str| functions.iter().find(|function| function["name"] == name);
+    assert_eq!(
+        function("list_host_functions").unwrap(),
+        &json!({ "name": "list_host_functions", "namespace": "extism:host/user", "params": [], "results": ["i64"], "capability": null, "replaced_by": null, "builtin": true })
+    );
+    assert_eq!(function("db_get_user_audit_logs").unwrap()["capability"], "db:audit:read");
+    assert_eq!(function("http_request").unwrap()["namespace"], "extism:host/env");
+    assert_eq!(function("app_greeting").unwrap()["builtin"], false);
+    // Only the functions linked into the plugin are listed
+    assert!(["db_create_user", "db_execute", "db_begin"].iter().all(|name| function(name).is_none()));
+
+    // The host lists every function, linked or not
+    let catalog = app.manager.host_functions();
+    for name in HOST_FUNCTION_NAMES {
+        assert!(catalog.iter().any(|function| function.name == *name), "{} is not listed", name);
+    }
+    let function = |name: &str| catalog.iter().find(|function| function.name == name).unwrap();
+    assert_eq!(function("db_create_user").capability.as_deref(), Some("db:users:write"));
+    assert_eq!(function("send_email").capability.as_deref(), Some("email"));
+    assert_eq!(function("db_begin").capability.as_deref(), Some("db:<resource>:write"));
+    let progress = function("report_progress");
+    assert_eq!((progress.params.join(","), progress.results.join(",")), ("f64,i64".to_string(), "i64".to_string()));
+    assert_eq!(function("generate_random_bytes_json").replaced_by.as_deref(), Some("generate_random_bytes"));
+    assert!(!function("app_greeting").builtin);
+}
+
+/// Mailer keeping the messages it is given
+#[derive(Default)]
+struct MemoryMailer(std::sync::Mutex<Vec<Email>>);
+
+impl Mailer for MemoryMailer {
+    fn send(&self, email: &Email) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_send_email_delivers_through_the_mailer() {
+    let app = TestApp::new();
+    let manager = &app.manager;
+    let send = |input: &'static str| async move {
+        let output = manager.execute_plugin("email-sender", "send", input.as_bytes()).await;
+        serde_json::from_slice::<Value>(&output.expect("Call failed")).unwrap()
+    };
+
+    // By default mail goes through the SMTP server in settings
+    app.install_granting("email-sender", &[Capability::Email]).await;
+    let unconfigured = send("ada@example.com\nHi\n\nHello").await;
+    assert_eq!(unconfigured["success"], false);
+    assert!(unconfigured["error"].as_str().unwrap().contains("No SMTP server is configured"), "Unexpected error: {}", unconfigured);
+    let invalid = SmtpSettings {
+        host: "smtp.example.com".to_string(),
+        port: None,
+        security: Default::default(),
+        username: None,
+        password_secret: Some("smtp-password".to_string()),
+        from: "App <noreply@example.com>".to_string(),
+    };
+    assert!(invalid.validate().is_err(), "A password without a username should be refused");
+    SettingsStore::new(app.database.clone())
+        .set(SMTP_KEY, &SmtpSettings { password_secret: None, ..invalid })
+        .unwrap();
+
+    let mailer = Arc::new(MemoryMailer::default());
+    app.manager.set_mailer(mailer.clone());
+    app.install_granting("email-sender", &[Capability::Email]).await;
+    let sent = send("ada@example.com, Grace <grace@example.com>\nVerify your email\n<p>Click <a href=\"https://example.com\">here</a></p>\nClick https://example.com").await;
+    assert_eq!(sent["success"], true, "Unexpected response: {}", sent);
+    assert_eq!(
+        mailer.0.lock().unwrap().as_slice(),
+        [Email {
+            to: vec!["ada@example.com".to_string(), "Grace <grace@example.com>".to_string()],
+            subject: "Verify your email".to_string(),
+            body_html: Some("<p>Click <a href=\"https://example.com\">here</a></p>".to_string()),
+            body_text: Some("Click https://example.com".to_string()),
+        }]
+    );
+
+    // Bad addresses, missing bodies and too many recipients are refused before the mailer sees them
+    for input in ["not an address\nHi\n\nHello", "ada@example.com\nHi\n\n", "\nHi\n\nHello"] {
+        let refused = send(input).await;
+        assert_eq!(refused["success"], false, "{:?} should be refused", input);
+    }
+    let many = (0..=plugin_host::mail::MAX_RECIPIENTS).map(|i| format!("user{}@example.com", i)).collect::<Vec<_>>().join(",");
+    let output = manager.execute_plugin("email-sender", "send", format!("{}\nHi\n\nHello", many).as_bytes()).await.unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&output).unwrap()["success"], false);
+    assert_eq!(mailer.0.lock().unwrap().len(), 1);
+
+    // The strict sandbox withholds it like the other sensitive capabilities
+    app.manager.set_sandbox_overrides([("email-sender".to_string(), SandboxProfile::Strict)]);
+    let error = format!("{:#}", app.install_dir(&fixture_dir("email-sender"), &[Capability::Email]).await.unwrap_err());
+    assert!(error.contains("missing host function send_email"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_function_calls_are_traced_per_execution() {
+    let app = TestApp::new();
+    app.install("host-info").await;
+    app.install_granting("clipboard-user", &[Capability::Clipboard]).await;
+    let run = |plugin: &'static str, function: &'static str, execution_id: &'static str| {
+        let manager = &app.manager;
+        async move {
+            manager
+                .execute_plugin_with_context(plugin, function, b"{}", &ExecutionContext::with_id(execution_id.to_string()))
+                .await
+                .expect("Call failed")
+        }
+    };
+
+    let output = run("host-info", "info", "exec-info").await;
+    let trace = app.manager.execution_trace("exec-info").unwrap().expect("The execution should be traced");
+    assert_eq!((trace.plugin.as_str(), trace.function.as_str(), trace.error.as_deref()), ("host-info", "info", None));
+    assert_eq!(trace.calls.len(), 1, "Unexpected calls: {:?}", trace.calls);
+    let call = &trace.calls[0];
+    assert_eq!((call.plugin.as_str(), call.function.as_str(), call.error.as_deref()), ("host-info", "get_host_info", None));
+    assert_eq!((call.input_bytes, call.output_bytes), (0, output.len() as u64));
+    assert!(call.duration_us <= trace.duration_us);
+
+    // Error responses are recorded as errors
+    run("clipboard-user", "paste", "exec-paste").await;
+    let trace = app.manager.execution_trace("exec-paste").unwrap().unwrap();
+    assert_eq!(trace.calls[0].function, "clipboard_read_text");
+    assert_eq!(trace.calls[0].error.as_deref(), Some("No clipboard is available to plugins"));
+
+    // Only kept in memory unless persistence is on
+    let stored = |execution_id: &str| {
+        app.database
+            .with_connection(|conn| operations::get_execution_trace(conn, execution_id))
+            .unwrap()
+    };
+    assert_eq!(stored("exec-info"), None);
+    SettingsStore::new(app.database.clone()).set(PERSIST_EXECUTION_TRACES_KEY, &true).unwrap();
+    run("host-info", "info", "exec-stored").await;
+    assert_eq!(stored("exec-stored"), app.manager.execution_trace("exec-stored").unwrap());
+    assert!(stored("exec-stored").is_some());
+    assert_eq!(app.manager.execution_trace("exec-unknown").unwrap(), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_report_progress_updates_the_job_and_emits_events() {
+    let app = TestApp::new();
+    app.install("progress-reporter").await;
+    let mut emitted = emit::subscribe();
+    app.database
+        .with_connection(|conn| operations::create_job(conn, "job-progress", "progress-reporter", "report", "", 1, None, 0))
+        .unwrap();
+    let context = ExecutionContext::with_id("job-progress".to_string());
+    let report = |input: &'static str| {
+        let (manager, context) = (&app.manager, &context);
+        async move {
+            let output = manager.execute_plugin_with_context("progress-reporter", "report", input.as_bytes(), context).await;
+            serde_json::from_slice::<Value>(&output.expect("Call failed")).unwrap()
+        }
+    };
+    let job = || app.database.with_connection(|conn| operations::get_job(conn, "job-progress")).unwrap().unwrap();
+    // The channel is shared by every test running in this process
+    async fn next_event(emitted: &mut tokio::sync::broadcast::Receiver<emit::PluginEmittedEvent>) -> emit::PluginEmittedEvent {
+        loop {
+            let event = emitted.recv().await.expect("Event channel closed");
+            if event.plugin == "progress-reporter" {
+                return event;
+            }
+        }
+    }
+
+    assert_eq!(report("25\nReading").await["success"], true);
+    assert_eq!((job().progress, job().progress_message.as_deref()), (0.25, Some("Reading")));
+    let event = next_event(&mut emitted).await;
+    assert_eq!((event.event.as_str(), event.execution_id.as_deref()), ("progress", Some("job-progress")));
+    assert_eq!(event.payload, json!({ "percent": 25.0, "message": "Reading" }));
+
+    // Reports right after another are dropped, except the last
+    assert_eq!(report("50\nConverting").await["success"], true);
+    assert_eq!(job().progress, 0.25);
+    assert_eq!(report("100\n").await["success"], true);
+    assert_eq!((job().progress, job().progress_message), (1.0, None));
+    assert_eq!(next_event(&mut emitted).await.payload, json!({ "percent": 100.0, "message": null }));
+
+    let refused = report("150\nToo far").await;
+    assert_eq!(refused["success"], false);
+    assert_eq!(refused["error"], "Progress must be 0 to 100 percent, not 150");
+}
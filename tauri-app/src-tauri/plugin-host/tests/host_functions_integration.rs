@@ -6,9 +6,9 @@ fn test_plugin_loading_infrastructure() {
     // This test verifies the plugin infrastructure is set up correctly
     // Full integration testing requires the Tauri app to be running
     
-    // Verify WASM plugin exists
+    // Verify the prebuilt WASM plugin exists
     let plugin_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("../../wasm-plugins/auth-plugin/target/wasm32-unknown-unknown/release/auth_plugin.wasm");
+        .join("tests/fixtures/auth-plugin/auth_plugin.wasm");
     
     assert!(
         plugin_path.exists(),
@@ -18,7 +18,7 @@ fn test_plugin_loading_infrastructure() {
     
     // Verify manifest exists
     let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("../../wasm-plugins/auth-plugin/plugin.json");
+        .join("tests/fixtures/auth-plugin/plugin.json");
     
     assert!(
         manifest_path.exists(),
@@ -36,37 +36,14 @@ fn test_database_operations_available() {
     // Verify all database operation functions are exported
     // This is a compile-time check that operations module has all required functions
     
-    use anything_to_everything_lib::db::operations;
+    use plugin_host::db::{migrations, operations};
     use rusqlite::Connection;
     
     // Create in-memory database for testing
     let conn = Connection::open_in_memory().expect("Failed to create test database");
     
     // Initialize schema
-    conn.execute_batch(
-        r#"
-        CREATE TABLE users (
-            id INTEGER PRIMARY KEY,
-            uuid TEXT NOT NULL UNIQUE,
-            name TEXT NOT NULL,
-            email TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL,
-            email_verified INTEGER NOT NULL DEFAULT 0,
-            bio TEXT,
-            avatar TEXT,
-            created_at INTEGER NOT NULL,
-            updated_at INTEGER NOT NULL
-        );
-        
-        CREATE TABLE sessions (
-            id TEXT PRIMARY KEY,
-            user_uuid TEXT NOT NULL,
-            created_at INTEGER NOT NULL,
-            expires_at INTEGER NOT NULL,
-            FOREIGN KEY (user_uuid) REFERENCES users(uuid) ON DELETE CASCADE
-        );
-        "#
-    ).expect("Failed to create tables");
+    migrations::run_migrations(&conn).expect("Failed to run migrations");
     
     // Test that operations functions exist and can be called
     let now = chrono::Utc::now().timestamp();
@@ -114,10 +91,41 @@ fn test_database_operations_available() {
     println!("✅ All database operations verified");
 }
 
+#[test]
+fn test_plugin_kv_store_is_scoped_per_plugin() {
+    use plugin_host::db::{migrations, operations};
+    use rusqlite::Connection;
+    
+    let conn = Connection::open_in_memory().expect("Failed to create test database");
+    migrations::run_migrations(&conn).expect("Failed to run migrations");
+    let now = chrono::Utc::now().timestamp();
+    
+    for key in ["cache:b", "cache:a", "cursor"] {
+        operations::kv_set(&conn, "alpha", key, "1", now).unwrap();
+    }
+    operations::kv_set(&conn, "alpha", "cursor", "{\"page\":2}", now).unwrap();
+    operations::kv_set(&conn, "beta", "cursor", "3", now).unwrap();
+    
+    assert_eq!(operations::kv_get(&conn, "alpha", "cursor").unwrap().as_deref(), Some("{\"page\":2}"));
+    assert_eq!(operations::kv_get(&conn, "beta", "cursor").unwrap().as_deref(), Some("3"));
+    assert_eq!(operations::kv_get(&conn, "beta", "cache:a").unwrap(), None);
+    
+    // Listing is ordered, filtered by prefix and paged with `after`
+    assert_eq!(operations::kv_list(&conn, "alpha", "cache:", None, 10).unwrap(), vec!["cache:a", "cache:b"]);
+    assert_eq!(operations::kv_list(&conn, "alpha", "", Some("cache:a"), 1).unwrap(), vec!["cache:b"]);
+    assert_eq!(operations::kv_usage(&conn, "alpha").unwrap(), (3, 7 + 1 + 7 + 1 + 6 + 10));
+    
+    assert!(operations::kv_delete(&conn, "alpha", "cache:a").unwrap());
+    assert!(!operations::kv_delete(&conn, "alpha", "cache:a").unwrap());
+    
+    // Uninstalling one plugin leaves the others' keys alone
+    operations::delete_plugin_kv(&conn, "alpha").unwrap();
+    assert_eq!(operations::kv_usage(&conn, "alpha").unwrap().0, 0);
+    assert_eq!(operations::kv_get(&conn, "beta", "cursor").unwrap().as_deref(), Some("3"));
+}
+
 #[cfg(test)]
 mod host_function_tests {
-    use super::*;
-    
     #[test]
     fn verify_host_functions_compile() {
         // This test verifies that host functions module compiles
@@ -0,0 +1,661 @@
+//! End-to-end tests of installing, loading and describing plugins
+//!
+//! Prebuilt plugins from tests/fixtures are installed through
+//! PluginManager and call the real host functions against a fresh in-memory
+//! database.
+mod common;
+
+use common::*;
+use plugin_host::db::operations;
+use plugin_host::error::{AppError, ErrorCode};
+use plugin_host::events::{self, HostEvent};
+use extism::{Function, UserData, PTR};
+use plugin_host::host_functions::HOST_API_LEVEL;
+use plugin_host::plugins::{Capability, PayloadFormat, PluginQuery, UiContributionKind};
+use plugin_host::settings::SettingsStore;
+use plugin_host::HostBuilder;
+use serde_json::{json, Value};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_conversion_roundtrip() {
+    let app = TestApp::new();
+    let id = app.install("text-converter").await;
+    assert_eq!(id, "text-converter");
+
+    let output = app
+        .manager
+        .execute_plugin("text-converter", "to_uppercase", b"Hello, world! 123")
+        .await
+        .expect("Conversion failed");
+    assert_eq!(output, b"HELLO, WORLD! 123");
+
+    let empty = app
+        .manager
+        .execute_plugin("text-converter", "to_uppercase", b"")
+        .await
+        .expect("Conversion of empty input failed");
+    assert!(empty.is_empty());
+
+    let missing = app
+        .manager
+        .execute_plugin("text-converter", "to_lowercase", b"Hello")
+        .await;
+    assert!(missing.is_err(), "Calling a function the plugin doesn't export should fail");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_declared_payload_formats_are_enforced() {
+    let app = TestApp::new();
+    app.install("text-converter").await;
+    app.install_granting("http-fetch", &[Capability::Network]).await;
+    let code = |result: anyhow::Result<Vec<u8>>| {
+        let error = result.expect_err("Call should have been rejected");
+        error.downcast_ref::<AppError>().map(|e| e.code)
+    };
+
+    // Text input must be UTF-8, JSON input must parse; neither reaches the plugin
+    let invalid_text = app.manager.execute_plugin("text-converter", "to_uppercase", b"caf\xe9").await;
+    assert_eq!(code(invalid_text), Some(ErrorCode::InvalidInput));
+    let invalid_json = app.manager.execute_plugin("http-fetch", "fetch", b"http://127.0.0.1/").await;
+    assert_eq!(code(invalid_json), Some(ErrorCode::InvalidInput));
+
+    // A binary function takes any bytes, but its text output must still be UTF-8
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["entry_points"][0]["input_format"] = json!("binary");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[]).await.expect("Reinstall failed");
+    let invalid_output = app.manager.execute_plugin("text-converter", "to_uppercase", b"caf\xe9").await;
+    assert_eq!(code(invalid_output), Some(ErrorCode::PluginError));
+    assert_eq!(
+        app.manager.payload_formats("text-converter", "to_uppercase").await,
+        (PayloadFormat::Binary, PayloadFormat::Text)
+    );
+
+    // Callers holding JSON pass binary input as base64 or bytes
+    let encoded = PayloadFormat::Binary.encode_input(&json!("aGk=")).unwrap();
+    assert_eq!(encoded, PayloadFormat::Binary.encode_input(&json!([104, 105])).unwrap());
+    let output = app.manager.execute_plugin("text-converter", "to_uppercase", &encoded).await.unwrap();
+    assert_eq!(PayloadFormat::Text.decode_output(&output).unwrap(), json!("HI"));
+    assert!(PayloadFormat::Text.encode_input(&json!({ "text": "hi" })).is_err());
+
+    // Unknown formats are manifest problems
+    manifest["entry_points"][0]["input_format"] = json!("yaml");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("/entry_points/0/input_format"), "Unexpected error: {:#}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_discover_loads_fixtures() {
+    let app = TestApp::new();
+    for fixture in ["auth-plugin", "audit-plugin", "text-converter"] {
+        app.preinstall(fixture);
+    }
+    // Granted when they were installed, before this run of the app
+    for plugin in ["auth-plugin", "audit-plugin"] {
+        app.database
+            .with_connection(|conn| operations::set_plugin_capability(conn, plugin, "db:write", true, 0))
+            .unwrap();
+    }
+
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert!(report.failed.is_empty(), "Plugins failed to load: {:?}", report.failed);
+    let mut loaded = report.loaded.clone();
+    loaded.sort();
+    assert_eq!(loaded, ["audit-plugin", "auth-plugin", "text-converter"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_env_is_filled_in_from_settings() {
+    let app = TestApp::new();
+    app.preinstall("config-echo");
+
+    // A placeholder for a setting that is not set keeps the plugin from loading
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.failed.len(), 1);
+    assert!(
+        report.failed[0].error.contains("is not set"),
+        "Unexpected error: {}",
+        report.failed[0].error
+    );
+
+    let settings = SettingsStore::new(app.database.clone());
+    settings.set("api_base_url", &"https://api.example.com").unwrap();
+    settings.set("feature_flags", &json!({ "beta": true })).unwrap();
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.loaded, ["config-echo"]);
+
+    let get = |key: &'static str| app.manager.execute_plugin("config-echo", "get", key.as_bytes());
+    assert_eq!(get("API_URL").await.unwrap(), b"https://api.example.com/v2");
+    assert_eq!(get("FEATURES").await.unwrap(), br#"{"beta":true}"#);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_secret_references_are_resolved_at_load() {
+    let app = TestApp::new();
+    app.preinstall("config-echo");
+    let manifest_path = app.root.join("plugins/config-echo/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest.as_object_mut().unwrap().remove("env");
+    manifest["wasm_config"] = json!({ "config": { "API_KEY": "secret://echo_key" } });
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.failed.len(), 1);
+    assert!(
+        report.failed[0].error.contains("refers to secret 'echo_key', which is not set"),
+        "Unexpected error: {}",
+        report.failed[0].error
+    );
+
+    assert!(app.manager.set_secret("echo_key", "sk-first").await.unwrap().is_empty());
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.loaded, ["config-echo"]);
+    let get = || app.manager.execute_plugin("config-echo", "get", b"API_KEY");
+    assert_eq!(get().await.unwrap(), b"sk-first");
+
+    // Replacing the secret reloads the plugins that refer to it
+    assert_eq!(app.manager.set_secret("echo_key", "sk-second").await.unwrap(), ["config-echo"]);
+    assert_eq!(get().await.unwrap(), b"sk-second");
+
+    // Only the reference is visible, and the value is not stored in plaintext
+    let config = app.manager.get_plugin_config("config-echo").await.unwrap();
+    assert_eq!(config.effective["API_KEY"], "secret://echo_key");
+    assert_eq!(app.manager.secret_names().unwrap(), ["echo_key"]);
+    let stored = std::fs::read_to_string(app.root.join("secrets/secrets.json")).unwrap();
+    assert!(stored.contains("echo_key") && !stored.contains("sk-second"), "Secret stored in plaintext: {}", stored);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugins_for_newer_host_are_refused() {
+    let app = TestApp::new();
+    let plugins_dir = app.root.join("plugins");
+    app.preinstall("text-converter");
+    let manifest_path = plugins_dir.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["host_api_level"] = json!(HOST_API_LEVEL + 1);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+
+    // Not loaded, and listed as unsupported with the reason
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert!(report.loaded.is_empty());
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].error.contains("host API level"), "Unexpected error: {}", report.failed[0].error);
+    let unsupported = app.manager.unsupported_plugins().await.expect("Failed to list unsupported plugins");
+    assert_eq!(unsupported.len(), 1);
+    assert_eq!(unsupported[0].0.id(), "text-converter");
+
+    // Installing it is refused before anything is copied
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    std::fs::write(staging.join("text-converter/plugin.json"), manifest.to_string()).unwrap();
+    std::fs::remove_dir_all(plugins_dir.join("text-converter")).unwrap();
+    let error = app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("this host supports up to level"), "Unexpected error: {:#}", error);
+    assert!(!plugins_dir.join("text-converter").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugins_for_other_platforms_are_skipped() {
+    let app = TestApp::new();
+    let plugins_dir = app.root.join("plugins");
+    let other = if cfg!(windows) { "macos" } else { "windows" };
+    for (fixture, platforms) in [("text-converter", json!([other])), ("config-echo", json!(["desktop", other]))] {
+        app.preinstall(fixture);
+        let manifest_path = plugins_dir.join(fixture).join("plugin.json");
+        let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        manifest.as_object_mut().unwrap().remove("env");
+        manifest["platforms"] = platforms;
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    }
+
+    // Skipped rather than failed, and listed as unsupported with the reason
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.loaded, ["config-echo"]);
+    assert!(report.failed.is_empty(), "Plugins failed to load: {:?}", report.failed);
+    assert_eq!(report.unsupported.len(), 1);
+    assert!(
+        report.unsupported[0].error.starts_with("Unsupported on this platform"),
+        "Unexpected reason: {}",
+        report.unsupported[0].error
+    );
+    let unsupported = app.manager.unsupported_plugins().await.expect("Failed to list unsupported plugins");
+    assert_eq!(unsupported.len(), 1);
+    assert_eq!(unsupported[0].0.id(), "text-converter");
+
+    // Unknown platforms are manifest problems
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["platforms"] = json!(["linux-riscv64"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let report = app.manager.validate_plugin(staging.join("text-converter").to_str().unwrap()).await;
+    let error = &report.checks[0].message;
+    assert!(error.contains("/platforms/0: Unknown architecture 'riscv64'"), "Unexpected error: {}", error);
+}
+
+/// text-converter's manifest as TOML
+const TEXT_CONVERTER_TOML: &str = r#"
+name = "text-converter"
+version = "0.1.0"
+description = "Upper-cases text; a stand-in converter for the integration tests"
+plugin_type = "converter"
+wasm_module = "text_converter.wasm"
+
+[[entry_points]]
+name = "to_uppercase"
+function = "to_uppercase"
+description = "Convert ASCII letters to upper case"
+input_format = "text"
+output_format = "text"
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_toml_manifests_are_accepted() {
+    let app = TestApp::new();
+    let plugins_dir = app.root.join("plugins");
+
+    // Discovered as it is
+    app.preinstall("text-converter");
+    std::fs::remove_file(plugins_dir.join("text-converter/plugin.json")).unwrap();
+    std::fs::write(plugins_dir.join("text-converter/plugin.toml"), TEXT_CONVERTER_TOML).unwrap();
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert!(report.failed.is_empty(), "Plugins failed to load: {:?}", report.failed);
+    assert_eq!(report.loaded, vec!["text-converter".to_string()]);
+    std::fs::remove_dir_all(plugins_dir.join("text-converter")).unwrap();
+
+    // Installed as plugin.json
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    std::fs::remove_file(staging.join("text-converter/plugin.json")).unwrap();
+    std::fs::write(staging.join("text-converter/plugin.toml"), TEXT_CONVERTER_TOML).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install text-converter: {:#}", e));
+    assert!(plugins_dir.join("text-converter/plugin.json").exists());
+    assert!(!plugins_dir.join("text-converter/plugin.toml").exists());
+    let output = app
+        .manager
+        .execute_plugin("text-converter", "to_uppercase", b"toml")
+        .await
+        .expect("Conversion failed");
+    assert_eq!(output, b"TOML");
+
+    // Held to the same schema
+    std::fs::write(staging.join("text-converter/plugin.toml"), TEXT_CONVERTER_TOML.replace("0.1.0", "one")).unwrap();
+    let error = app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("/version"), "Unexpected error: {:#}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_license_report_aggregates_installed_plugins() {
+    let app = TestApp::new();
+    let plugins_dir = app.root.join("plugins");
+    for (fixture, license) in [("text-converter", json!("Apache-2.0 OR MIT")), ("config-echo", json!("MIT")), ("greeter", Value::Null)] {
+        app.preinstall(fixture);
+        let manifest_path = plugins_dir.join(fixture).join("plugin.json");
+        let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        manifest["license"] = license;
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    }
+
+    // Installed plugins count whether or not they are loaded
+    let report = app.manager.license_report().unwrap();
+    assert_eq!(report.plugins.len(), 3);
+    assert_eq!(report.licenses["MIT"], ["config-echo", "text-converter"]);
+    assert_eq!(report.licenses["Apache-2.0"], ["text-converter"]);
+    assert_eq!(report.unlicensed, ["greeter"]);
+
+    // Operators are upper case
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["license"] = json!("MIT or Apache-2.0");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    assert!(error.contains("/license: Unexpected 'or'"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugins_are_searched_by_categories_tags_and_text() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    let labels = [
+        ("text-converter", json!(["Text"]), json!(["case", "uppercase"])),
+        ("quota-limits", json!(["developer"]), json!(["limits"])),
+        ("http-fetch", json!(["network", "developer"]), json!(["http"])),
+    ];
+    for (fixture, categories, tags) in labels {
+        copy_fixture(fixture, &staging);
+        let manifest_path = staging.join(fixture).join("plugin.json");
+        let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        manifest["categories"] = categories;
+        manifest["tags"] = tags;
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+        app.install_dir(&staging.join(fixture), &[]).await.expect("Install failed");
+    }
+    let plugins = app.manager.list_plugins().await;
+    let search = |query: Value| -> Vec<String> {
+        let query: PluginQuery = serde_json::from_value(query).unwrap();
+        query.rank(plugins.clone(), |plugin| plugin).iter().map(|plugin| plugin.id()).collect()
+    };
+
+    // Without a query everything matches, by ID
+    assert_eq!(search(json!({})), ["http-fetch", "quota-limits", "text-converter"]);
+    // Any of the categories, all of the tags, ignoring case
+    assert_eq!(search(json!({ "categories": ["DEVELOPER"] })), ["http-fetch", "quota-limits"]);
+    assert_eq!(search(json!({ "categories": ["text", "network"] })), ["http-fetch", "text-converter"]);
+    assert_eq!(search(json!({ "tags": ["case", "UpperCase"] })), ["text-converter"]);
+    assert!(search(json!({ "tags": ["case", "http"] })).is_empty());
+    assert_eq!(search(json!({ "plugin_type": "converter" })), ["text-converter"]);
+
+    // Every word must match; name matches outrank tag and description matches
+    assert_eq!(search(json!({ "text": "upper cases" })), ["text-converter"]);
+    assert_eq!(search(json!({ "text": "quota" })), ["quota-limits"]);
+    let query = PluginQuery { text: Some("integration".to_string()), ..Default::default() };
+    let quota = plugins.iter().find(|plugin| plugin.name == "quota-limits").unwrap();
+    let converter = plugins.iter().find(|plugin| plugin.name == "text-converter").unwrap();
+    assert_eq!(query.score(quota), Some(1));
+    assert_eq!(query.score(converter), Some(1));
+    let query = PluginQuery { text: Some("Text".to_string()), ..Default::default() };
+    assert_eq!(query.score(converter), Some(4));
+
+    // Labels are listed once
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["tags"] = json!(["case", "Case"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    assert!(error.contains("/tags/1: 'Case' is listed twice"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ui_contributions_must_refer_to_entry_points_and_panels() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["ui"] = json!({
+        "panels": [{ "id": "preview", "title": "Preview", "entry": "preview.html" }],
+        "contributions": [
+            { "id": "shout", "kind": "menu_item", "label": "Upper-case", "menu": "tools", "entry_point": "to_uppercase" },
+            { "id": "preview", "kind": "route", "label": "Preview", "path": "/preview", "panel": "preview", "order": -1 }
+        ]
+    });
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[]).await.expect("Install failed");
+    let contributions = app.manager.get_plugin("text-converter").await.unwrap().ui.contributions;
+    assert_eq!(contributions.len(), 2);
+    assert_eq!(contributions[0].kind, UiContributionKind::MenuItem);
+    assert_eq!(contributions[1].path.as_deref(), Some("/preview"));
+
+    manifest["ui"]["contributions"] = json!([
+        { "id": "a", "kind": "menu_item", "label": "A", "entry_point": "to_lowercase" },
+        { "id": "a", "kind": "panel", "label": "B", "panel": "missing", "path": "/b" },
+        { "id": "c", "kind": "route", "label": "C", "path": "../c", "entry_point": "to_uppercase" },
+        { "id": "d", "kind": "panel", "label": "D" }
+    ]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    for expected in [
+        "/ui/contributions/0/entry_point: Unknown entry point 'to_lowercase'",
+        "/ui/contributions/1/id: Contribution 'a' is declared twice",
+        "/ui/contributions/1/panel: Unknown panel 'missing'",
+        "/ui/contributions/1/path: Only routes have a path",
+        "/ui/contributions/2/path: '../c' must start with '/'",
+        "/ui/contributions/3: Contribution 'd' needs an entry_point or a panel to open",
+    ] {
+        assert!(error.contains(expected), "Missing {:?} in: {}", expected, error);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dependency_version_ranges_are_enforced_at_load() {
+    let app = TestApp::new();
+    app.install("text-converter").await;
+    let staging = app.root.join("staging");
+    let set = |fixture: &str, field: &str, value: Value| {
+        let manifest_path = staging.join(fixture).join("plugin.json");
+        let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        manifest[field] = value;
+        std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    };
+    copy_fixture("quota-limits", &staging);
+    copy_fixture("text-converter", &staging);
+
+    // text-converter is at 0.1.0
+    set("quota-limits", "dependencies", json!({ "text-converter": ">=1.0, <3" }));
+    let report = app.manager.validate_plugin(staging.join("quota-limits").to_str().unwrap()).await;
+    let check = report.checks.iter().find(|check| check.name == "dependencies").unwrap();
+    assert!(!check.passed);
+    let error = format!("{:#}", app.install_dir(&staging.join("quota-limits"), &[]).await.unwrap_err());
+    assert!(
+        error.contains("Dependency 'text-converter' is installed at 0.1.0, but 'quota-limits' requires >=1.0, <3"),
+        "Unexpected error: {}",
+        error
+    );
+
+    set("quota-limits", "dependencies", json!({ "text-converter": "^0.1" }));
+    app.install_dir(&staging.join("quota-limits"), &[]).await.expect("Install failed");
+    let graph = app.manager.dependency_graph().await;
+    assert!(graph.edges[0].satisfied && graph.edges[0].issue.is_none());
+
+    // Updating the dependency out of range leaves the dependent unsatisfied until it is updated too
+    set("text-converter", "version", json!("2.0.0"));
+    app.install_dir(&staging.join("text-converter"), &[]).await.expect("Update failed");
+    let graph = app.manager.dependency_graph().await;
+    assert!(!graph.edges[0].satisfied);
+    assert!(graph.nodes.iter().any(|node| node.id == "quota-limits" && !node.available));
+    let report = app.manager.discover_plugins().await.unwrap();
+    assert_eq!(report.failed.len(), 1, "quota-limits should no longer load");
+    assert!(report.failed[0].error.contains("requires ^0.1"), "Unexpected error: {}", report.failed[0].error);
+
+    // Ranges must parse
+    set("quota-limits", "dependencies", json!({ "text-converter": "one or two" }));
+    let error = format!("{:#}", app.install_dir(&staging.join("quota-limits"), &[]).await.unwrap_err());
+    assert!(error.contains("/dependencies/text-converter"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_deprecated_functions_still_run_with_warnings() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+
+    // A replacement without the deprecation is refused
+    manifest["replacement"] = json!("text-tools");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("/replacement"), "Unexpected error: {:#}", error);
+
+    manifest["deprecated"] = json!(true);
+    manifest["entry_points"][0]["deprecated"] = json!(true);
+    manifest["entry_points"][0]["replacement"] = json!("shout");
+    manifest["entry_points"].as_array_mut().unwrap().push(json!({
+        "name": "shout", "function": "to_uppercase", "description": "Convert letters to upper case"
+    }));
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install text-converter: {:#}", e));
+
+    let output = app
+        .manager
+        .execute_plugin("text-converter", "to_uppercase", b"still works")
+        .await
+        .expect("Deprecated function failed");
+    assert_eq!(output, b"STILL WORKS");
+    assert_eq!(
+        app.manager.deprecation_warnings("text-converter", "to_uppercase").await,
+        vec![
+            "Function 'to_uppercase' of plugin 'text-converter' is deprecated; use 'shout' instead".to_string(),
+            "Plugin 'text-converter' is deprecated; use 'text-tools' instead".to_string(),
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_aliases_and_default_function() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+
+    // Without a default, a call has to name its function
+    app.install("text-converter").await;
+    let error = app.manager.execute_plugin("text-converter", "", b"hi").await.unwrap_err();
+    assert_eq!(error.downcast_ref::<AppError>().map(|e| e.code), Some(ErrorCode::NotFound));
+
+    manifest["entry_points"][0]["aliases"] = json!(["upper", "shout"]);
+    manifest["entry_points"][0]["default"] = json!(true);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install text-converter: {:#}", e));
+    for function in ["", "upper", "shout", "to_uppercase"] {
+        let output = app
+            .manager
+            .execute_plugin("text-converter", function, b"hi")
+            .await
+            .unwrap_or_else(|e| panic!("Calling '{}' failed: {:#}", function, e));
+        assert_eq!(output, b"HI");
+    }
+
+    // Two defaults, or an alias that shadows a function, are refused
+    manifest["entry_points"].as_array_mut().unwrap().push(json!({
+        "name": "again", "function": "to_uppercase", "description": "Same again", "aliases": ["to_uppercase"], "default": true
+    }));
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    assert!(error.contains("2 entry points are marked default"), "Unexpected error: {}", error);
+    assert!(error.contains("/entry_points/1/aliases/0"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_required_host_functions_are_checked_at_load() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+
+    // Not linked without the capability it needs
+    manifest["required_host_functions"] = json!(["log", "db_create_audit_log"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    assert!(error.contains("Plugin 'text-converter': missing host function db_create_audit_log"), "Unexpected error: {}", error);
+
+    manifest["capabilities"] = json!(["db:audit:write"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[Capability::DB_WRITE])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install text-converter: {:#}", e));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_subscribed_events_are_routed_to_plugins() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let manifest_path = staging.join("text-converter/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["subscriptions"] = json!([
+        { "event": "tick", "every": 20, "function": "to_uppercase" },
+        { "event": "user.created", "function": "to_uppercase" }
+    ]);
+
+    // User events reveal who signed up, so they need read access to users
+    manifest["capabilities"] = json!(["tick"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("text-converter"), &[]).await.unwrap_err());
+    assert!(
+        error.contains("Subscribing to 'user.created' needs the 'db:users:read' capability"),
+        "Unexpected error: {}",
+        error
+    );
+
+    manifest["capabilities"] = json!(["tick", "db:users:read"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("text-converter"), &[])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install text-converter: {:#}", e));
+
+    let event = |name: &str, source: Option<&str>, payload: Value| HostEvent {
+        event: name.to_string(),
+        source: source.map(str::to_string),
+        payload,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    };
+    for tick in 19..=41 {
+        app.manager.dispatch_event(&event(events::TICK, None, json!({ "tick": tick }))).await;
+    }
+    let user = json!({ "uuid": uuid::Uuid::new_v4().to_string(), "name": "Ada", "email": "ada@example.com" });
+    app.manager.dispatch_event(&event(events::USER_CREATED, None, user.clone())).await;
+    // Not called with events its own host calls caused
+    app.manager
+        .dispatch_event(&event(events::USER_CREATED, Some("text-converter"), user))
+        .await;
+    app.manager.dispatch_event(&event(events::SESSION_CREATED, None, json!({}))).await;
+
+    let metrics = app.manager.get_metrics(Some("text-converter")).await;
+    let stats = &metrics[0].functions["to_uppercase"];
+    assert_eq!(stats.total_calls, 3, "Ticks 20 and 40 and one user.created");
+    assert_eq!(stats.error_count, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_host_builder_links_embedder_functions() {
+    let root = std::env::temp_dir().join(format!("a2e-builder-{}", uuid::Uuid::new_v4()));
+    copy_fixture("greeter", &root.join("plugins"));
+
+    let host = HostBuilder::new(root.join("plugins"))
+        .with_db_path(":memory:")
+        .with_trusted_plugins(["greeter".to_string()])
+        .with_host_fns(|_plugin_id| {
+            vec![Function::new("app_greeting", [PTR], [PTR], UserData::default(), app_greeting)]
+        })
+        .build()
+        .expect("Failed to build host");
+    assert!(host.jobs.is_some() && host.scheduler.is_some());
+    record_install(host.database.as_ref().unwrap(), &root.join("plugins/greeter"));
+
+    let report = host.discover_plugins().await.expect("Discovery failed");
+    assert!(report.failed.is_empty(), "Plugins failed to load: {:?}", report.failed);
+    let output = host
+        .plugins
+        .read()
+        .await
+        .execute_plugin("greeter", "greet", b"Ada")
+        .await
+        .expect("greet failed");
+    assert_eq!(output, b"Hello, Ada!");
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plugins_read_their_config_with_overrides_applied() {
+    let app = TestApp::new();
+    app.install("config-reader").await;
+
+    let theme = app.call("config-reader", "get", json!({ "key": "theme" })).await;
+    assert_eq!(theme["data"], "light");
+    let missing = app.call("config-reader", "get", json!({ "key": "missing" })).await;
+    assert_eq!(missing["success"], true);
+    assert_eq!(missing["data"], Value::Null);
+
+    // Overrides reload the plugin, which then sees them
+    let values = [("theme".to_string(), Some("dark".to_string())), ("locale".to_string(), Some("fr".to_string()))].into();
+    app.manager.set_plugin_config("config-reader", &values).await.expect("Failed to set config");
+    let config = app.call("config-reader", "list", json!({})).await;
+    assert_eq!(config["data"], json!({ "locale": "fr", "page_size": "20", "theme": "dark" }));
+}
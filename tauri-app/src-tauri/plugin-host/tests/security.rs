@@ -0,0 +1,505 @@
+//! End-to-end tests of what keeps plugins in check: trust, quarantine,
+//! capability approval, sandboxes and quotas
+//!
+//! Prebuilt plugins from tests/fixtures are installed through
+//! PluginManager and call the real host functions against a fresh in-memory
+//! database.
+mod common;
+
+use common::*;
+use plugin_host::db::operations;
+use plugin_host::error::{AppError, ErrorCode, Quota, QuotaViolation};
+use plugin_host::plugins::{generate_author_key, Capability, sign_plugin, verify_plugin, PluginManager, SandboxProfile};
+use plugin_host::settings::{SettingsStore, DISABLED_PLUGINS_KEY};
+use base64::Engine;
+use serde_json::{json, Value};
+
+/// Serve `hello` to every request on a local port; returns the port
+async fn serve_hello() -> u16 {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello")
+                .await;
+        }
+    });
+    port
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_network_allow_and_deny_lists() {
+    let app = TestApp::new();
+    app.install_granting("http-fetch", &[Capability::Network]).await;
+    let port = serve_hello().await;
+    let fetch = |url: String| {
+        let input = json!({ "url": url }).to_string();
+        let manager = &app.manager;
+        async move { manager.execute_plugin("http-fetch", "fetch", input.as_bytes()).await }
+    };
+
+    // 127.0.0.1 matches the wildcard in allowed_hosts
+    let body = fetch(format!("http://127.0.0.1:{}/", port)).await.expect("Allowed request failed");
+    assert_eq!(body, b"hello");
+
+    let unlisted = fetch(format!("http://localhost:{}/", port)).await;
+    assert!(unlisted.is_err(), "Hosts not in allowed_hosts should be refused");
+
+    let denied = fetch(format!("http://blocked.localhost:{}/", port)).await;
+    let error = format!("{:#}", denied.expect_err("denied_hosts should win over allowed_hosts"));
+    assert!(error.contains("not allowed"), "Unexpected error: {}", error);
+
+    // The global deny list applies on the next request
+    app.database
+        .with_connection(|conn| operations::set_setting(conn, "network_denied_hosts", r#"["127.0.0.1"]"#, 0))
+        .unwrap();
+    let denied = fetch(format!("http://127.0.0.1:{}/", port)).await;
+    assert!(denied.is_err(), "The global deny list should apply to every plugin");
+
+    // Every attempt is in the egress log, newest first
+    let log = app
+        .database
+        .with_connection(|conn| operations::get_egress_logs(conn, Some("http-fetch"), None, None, 100, 0))
+        .unwrap();
+    assert_eq!(log.len(), 4);
+    let allowed = log.last().unwrap();
+    assert_eq!((allowed.method.as_str(), allowed.host.as_str(), allowed.path.as_str()), ("GET", "127.0.0.1", "/"));
+    assert_eq!(allowed.status, Some(200));
+    assert_eq!(allowed.bytes_received, 5);
+    assert!(log[..3].iter().all(|entry| entry.status.is_none() && entry.error.is_some()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_network_requests_need_the_net_capability_granted() {
+    let app = TestApp::new();
+    // Declared in the manifest but turned down at install
+    app.install("http-fetch").await;
+    let port = serve_hello().await;
+
+    let input = json!({ "url": format!("http://127.0.0.1:{}/", port) }).to_string();
+    let refused = app.manager.execute_plugin("http-fetch", "fetch", input.as_bytes()).await;
+    assert!(refused.is_err(), "A plugin denied 'net' should not reach the network");
+    let log = app
+        .database
+        .with_connection(|conn| operations::get_egress_logs(conn, Some("http-fetch"), None, None, 100, 0))
+        .unwrap();
+    assert!(log.iter().all(|entry| entry.status.is_none()), "No request should have been answered");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sandbox_profiles_can_only_be_lowered() {
+    let app = TestApp::new();
+    let port = serve_hello().await;
+    let url = json!({ "url": format!("http://127.0.0.1:{}/", port) }).to_string();
+    let staging = app.root.join("staging");
+    copy_fixture("http-fetch", &staging);
+    let manifest_path = staging.join("http-fetch/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    app.manager.set_trusted_plugins(["http-fetch".to_string()]);
+    async fn sandbox(manager: &PluginManager) -> SandboxProfile {
+        manager.sandbox_profile(&manager.get_plugin("http-fetch").await.unwrap())
+    }
+
+    // Trusted plugins get the sandbox they ask for, up to the host's cap
+    manifest["sandbox"] = json!("trusted");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("http-fetch"), &[Capability::Network]).await.expect("Install failed");
+    assert_eq!(sandbox(&app.manager).await, SandboxProfile::Trusted);
+    app.manager.set_max_sandbox(SandboxProfile::Standard);
+    assert_eq!(sandbox(&app.manager).await, SandboxProfile::Standard);
+    let body = app.manager.execute_plugin("http-fetch", "fetch", url.as_bytes()).await.expect("Request failed");
+    assert_eq!(body, b"hello");
+
+    // The user moved it to the strict sandbox, which has no network
+    app.manager.set_sandbox_overrides([("http-fetch".to_string(), SandboxProfile::Strict)]);
+    app.install_dir(&staging.join("http-fetch"), &[Capability::Network]).await.expect("Reinstall failed");
+    assert_eq!(sandbox(&app.manager).await, SandboxProfile::Strict);
+    let refused = app.manager.execute_plugin("http-fetch", "fetch", url.as_bytes()).await;
+    assert!(refused.is_err(), "The strict sandbox should withhold the network");
+
+    // WASI is only for the trusted sandbox, so the override keeps it out too
+    manifest["capabilities"] = json!(["wasi"]);
+    manifest["wasm_config"]["wasi"] = json!(true);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.install_dir(&staging.join("http-fetch"), &[Capability::Network]).await.unwrap_err());
+    assert!(error.contains("enables WASI but runs in the 'strict' sandbox"), "Unexpected error: {}", error);
+    manifest["sandbox"] = json!("standard");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let report = app.manager.validate_plugin(staging.join("http-fetch").to_str().unwrap()).await;
+    let error = &report.checks[0].message;
+    assert!(error.contains("/wasm_config/wasi: WASI needs the 'trusted' sandbox"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quotas_fail_calls_with_quota_errors() {
+    let app = TestApp::new();
+    app.install("quota-limits").await;
+    let call = |function: &'static str, input: &'static str| {
+        app.manager.execute_plugin("quota-limits", function, input.as_bytes())
+    };
+    let quota_error = |result: anyhow::Result<Vec<u8>>| {
+        let error = AppError::from(result.expect_err("Call should go over its quota"));
+        assert_eq!(error.code, ErrorCode::QuotaExceeded, "Unexpected error: {}", error);
+        assert_eq!(error.plugin.as_deref(), Some("quota-limits"));
+        error
+    };
+
+    assert_eq!(call("echo", "short").await.unwrap(), b"short");
+    let error = quota_error(call("echo", "over eight bytes").await);
+    assert_eq!(error.quota, Some(QuotaViolation { quota: Quota::OutputSize, limit: 8, actual: 16 }));
+    assert!(!error.retriable);
+
+    // A second call while the first one spins is refused; the first is cut off
+    let (spin, refused) = tokio::join!(call("spin", ""), async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        call("echo", "hi").await
+    });
+    let refused = quota_error(refused);
+    assert_eq!(refused.quota.unwrap().quota, Quota::ConcurrentCalls);
+    assert!(refused.retriable);
+    let timed_out = quota_error(spin).quota.unwrap();
+    assert_eq!((timed_out.quota, timed_out.limit), (Quota::ExecutionTime, 300));
+    assert!(timed_out.actual >= 300);
+
+    // Quotas apply per call, so the plugin keeps working
+    assert_eq!(call("echo", "again").await.unwrap(), b"again");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nested_calls_get_the_targets_quotas_and_aliases() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("quota-limits", &staging);
+    let manifest_path = staging.join("quota-limits").join("plugin.json");
+    let mut manifest: Value = serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    manifest["entry_points"][1]["aliases"] = json!(["repeat"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("quota-limits"), &[]).await.expect("Install failed");
+    app.install("plugin-caller").await;
+    let manager = &app.manager;
+    let call = |function: &'static str, payload: &'static str| async move {
+        let request = json!({ "target": "quota-limits", "function": function, "payload": payload });
+        let output = manager
+            .execute_plugin("plugin-caller", "forward", request.to_string().as_bytes())
+            .await
+            .expect("The caller itself should not fail");
+        serde_json::from_slice::<Value>(&output).unwrap()
+    };
+    let quota_error = |response: Value| {
+        assert_eq!(response["success"], false, "Call should go over its quota: {}", response);
+        response["error"].as_str().unwrap().to_string()
+    };
+
+    // Payloads are sent as JSON, so the quoted string is what is echoed
+    let response = call("repeat", "hi").await;
+    assert_eq!(response["success"], true, "Unexpected response: {}", response);
+    assert_eq!(response["data"], "hi");
+    let error = quota_error(call("echo", "over eight bytes").await);
+    assert!(error.contains("limit of 8 bytes"), "Unexpected error: {}", error);
+    let error = quota_error(call("spin", "").await);
+    assert!(error.contains("limit of 300 ms"), "Unexpected error: {}", error);
+
+    // A nested call counts against the target's concurrency limit too; the
+    // caller needs a worker of its own while the target spins
+    app.manager.set_execution_workers(2);
+    let (spin, refused) = tokio::join!(app.manager.execute_plugin("quota-limits", "spin", b""), async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        call("echo", "hi").await
+    });
+    let error = quota_error(refused);
+    assert!(error.contains("call(s) in flight"), "Unexpected error: {}", error);
+    assert!(spin.is_err());
+
+    // Nested calls that ran are counted like direct ones
+    let metrics = app.manager.get_metrics(Some("quota-limits")).await;
+    assert_eq!(metrics[0].functions["echo"].total_calls, 2);
+    assert_eq!(metrics[0].functions["echo"].error_count, 1);
+    assert_eq!(metrics[0].functions["spin"].error_count, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_untrusted_authors_are_quarantined() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let source = staging.join("text-converter");
+
+    // Unsigned: held back until approved
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(app.manager.get_plugin("text-converter").await.is_none());
+    let quarantined = app.manager.quarantined_plugins().unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].id, "text-converter");
+    assert_eq!(quarantined[0].public_key, None);
+    app.manager.approve_quarantined_plugin("text-converter", false).await.expect("Approval failed");
+    assert!(app.manager.quarantined_plugins().unwrap().is_empty());
+    let output = app
+        .manager
+        .execute_plugin("text-converter", "to_uppercase", b"approved")
+        .await
+        .expect("Conversion failed");
+    assert_eq!(output, b"APPROVED");
+
+    // Signed by a trusted author: installed straight away
+    app.manager.uninstall_plugin("text-converter").await.expect("Uninstall failed");
+    let key = generate_author_key().unwrap();
+    assert_eq!(sign_plugin(&source, &key.pkcs8).unwrap(), key.public_key);
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(app.manager.is_quarantined("text-converter"));
+    app.manager.reject_quarantined_plugin("text-converter").unwrap();
+    app.manager.trust_author(&key.public_key, "Text Tools").unwrap();
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(!app.manager.is_quarantined("text-converter"));
+    assert!(app.manager.get_plugin("text-converter").await.is_some());
+
+    // Tampered with after signing: refused
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["version"] = json!("9.9.9");
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = format!("{:#}", app.manager.install_plugin(&source).await.unwrap_err());
+    assert!(error.contains("invalid signature"), "Unexpected error: {}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signatures_cover_migrations_and_ui_assets() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let source = staging.join("text-converter");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["capabilities"] = json!(["db:write"]);
+    manifest["migrations"] = json!(["migrations/001_notes.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let migration = source.join("migrations/001_notes.sql");
+    std::fs::create_dir(source.join("migrations")).unwrap();
+    std::fs::write(&migration, "CREATE TABLE text_converter_notes (text TEXT NOT NULL);").unwrap();
+    let panel = source.join("ui/panels/notes.html");
+    std::fs::create_dir_all(panel.parent().unwrap()).unwrap();
+    std::fs::write(&panel, "<p>Notes</p>").unwrap();
+
+    let key = generate_author_key().unwrap();
+    sign_plugin(&source, &key.pkcs8).unwrap();
+    assert_eq!(verify_plugin(&source).unwrap(), Some(key.public_key.clone()));
+
+    // Each file the manifest refers to is covered, not just the modules
+    std::fs::write(&panel, "<script>steal()</script>").unwrap();
+    assert!(verify_plugin(&source).is_err(), "A changed UI asset should break the signature");
+    std::fs::write(&panel, "<p>Notes</p>").unwrap();
+    std::fs::write(source.join("ui/extra.js"), "steal()").unwrap();
+    assert!(verify_plugin(&source).is_err(), "An added UI asset should break the signature");
+    std::fs::remove_file(source.join("ui/extra.js")).unwrap();
+    assert!(verify_plugin(&source).is_ok());
+
+    std::fs::write(&migration, "DROP TABLE users;").unwrap();
+    let error = format!("{:#}", verify_plugin(&source).unwrap_err());
+    assert!(error.contains("does not match"), "Unexpected error: {}", error);
+    app.manager.trust_author(&key.public_key, "Text Tools").unwrap();
+    let error = format!("{:#}", app.install_dir(&source, &[Capability::DB_WRITE]).await.unwrap_err());
+    assert!(error.contains("invalid signature"), "Unexpected error: {}", error);
+    assert!(app.manager.get_plugin("text-converter").await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_modules_changed_or_placed_outside_the_installer_are_refused() {
+    let app = TestApp::new();
+    app.install("text-converter").await;
+    let module = app.root.join("plugins/text-converter/text_converter.wasm");
+
+    // Tampered with while loaded: unloaded, and disabled until reinstalled
+    let mut tampered = std::fs::read(&module).unwrap();
+    tampered.extend_from_slice(b"\0tampered");
+    std::fs::write(&module, tampered).unwrap();
+    let violations = app.manager.verify_plugins().await.unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].plugin, "text-converter");
+    assert!(app.manager.get_plugin("text-converter").await.is_none());
+    assert!(app.manager.is_disabled("text-converter"));
+    let disabled: Vec<String> = SettingsStore::new(app.database.clone()).get_or_default(DISABLED_PLUGINS_KEY).unwrap();
+    assert_eq!(disabled, ["text-converter"]);
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert!(report.loaded.is_empty(), "A disabled plugin should stay unloaded: {:?}", report.loaded);
+
+    // Copied into the plugins directory by hand: no checksum was recorded for it
+    copy_fixture("uuid-gen", &app.root.join("plugins"));
+    let report = app.manager.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].error.contains("not installed through the installer"), "Unexpected error: {}", report.failed[0].error);
+    assert!(app.manager.get_plugin("uuid-gen").await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_migrations_and_ui_assets_changed_outside_the_installer_are_refused() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("text-converter", &staging);
+    let source = staging.join("text-converter");
+    let manifest_path = source.join("plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+    manifest["capabilities"] = json!(["db:write"]);
+    manifest["migrations"] = json!(["migrations/001_notes.sql"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    std::fs::create_dir(source.join("migrations")).unwrap();
+    std::fs::write(source.join("migrations/001_notes.sql"), "CREATE TABLE text_converter_notes (text TEXT NOT NULL);").unwrap();
+    std::fs::create_dir_all(source.join("ui/panels")).unwrap();
+    std::fs::write(source.join("ui/panels/notes.html"), "<p>Notes</p>").unwrap();
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Install failed");
+    let installed = app.root.join("plugins/text-converter");
+
+    // Changed while loaded: unloaded by the verifier
+    std::fs::write(installed.join("ui/panels/notes.html"), "<script>steal()</script>").unwrap();
+    let violations = app.manager.verify_plugins().await.unwrap();
+    assert_eq!(violations.len(), 1);
+    assert!(app.manager.get_plugin("text-converter").await.is_none());
+
+    // Changed while the app was closed: refused at load
+    app.install_dir(&source, &[Capability::DB_WRITE]).await.expect("Reinstall failed");
+    std::fs::write(installed.join("migrations/001_notes.sql"), "DROP TABLE users;").unwrap();
+    let restarted = PluginManager::new_with_database(app.root.join("plugins"), app.database.clone()).unwrap();
+    let report = restarted.discover_plugins().await.expect("Discovery failed");
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].error.contains("changed outside the installer"), "Unexpected error: {}", report.failed[0].error);
+
+    // Installs that recorded a checksum of the module alone cover the rest
+    // from their next load on
+    let legacy = TestApp::new();
+    let dir = legacy.preinstall("text-converter");
+    std::fs::create_dir(dir.join("ui")).unwrap();
+    std::fs::write(dir.join("ui/notes.html"), "<p>Notes</p>").unwrap();
+    legacy.manager.discover_plugins().await.expect("Discovery failed");
+    assert!(legacy.manager.get_plugin("text-converter").await.is_some());
+    std::fs::write(dir.join("ui/notes.html"), "<script>steal()</script>").unwrap();
+    assert_eq!(legacy.manager.verify_plugins().await.unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_trusted_plugins_need_the_signer_they_were_first_installed_with() {
+    let app = TestApp::new();
+    app.manager.set_trusted_plugins(["http-fetch".to_string()]);
+    let staging = app.root.join("staging");
+    copy_fixture("http-fetch", &staging);
+    let source = staging.join("http-fetch");
+    let port = serve_hello().await;
+    let fetch = || {
+        let input = json!({ "url": format!("http://127.0.0.1:{}/", port) }).to_string();
+        let manager = &app.manager;
+        async move { manager.execute_plugin("http-fetch", "fetch", input.as_bytes()).await }
+    };
+
+    // The trusted ID alone skips neither the signature check nor quarantine
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(app.manager.is_quarantined("http-fetch"), "An unsigned package claiming a trusted ID should wait for approval");
+    app.manager.reject_quarantined_plugin("http-fetch").unwrap();
+    let first = generate_author_key().unwrap();
+    sign_plugin(&source, &first.pkcs8).unwrap();
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(app.manager.is_quarantined("http-fetch"), "The first install has no recorded signer to match");
+
+    // Approved with nobody to answer the prompt, so 'net' is denied
+    app.manager.approve_quarantined_plugin("http-fetch", false).await.expect("Approval failed");
+    assert!(fetch().await.is_err());
+
+    // Later packages from the recorded signer get the trusted plugin's capabilities
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    assert!(!app.manager.is_quarantined("http-fetch"));
+    assert_eq!(fetch().await.expect("Trusted request failed"), b"hello");
+
+    // A package signed by anyone else is an untrusted plugin under the same ID
+    let other = generate_author_key().unwrap();
+    sign_plugin(&source, &other.pkcs8).unwrap();
+    app.manager.install_plugin(&source).await.expect("Install failed");
+    let quarantined = app.manager.quarantined_plugins().unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].public_key.as_deref(), Some(other.public_key.as_str()));
+    app.manager.approve_quarantined_plugin("http-fetch", false).await.expect("Approval failed");
+    assert!(fetch().await.is_err(), "The other signer's package should not get 'net' without a grant");
+    let recorded = app
+        .database
+        .with_connection(|conn| operations::get_plugin_signer(conn, "http-fetch"))
+        .unwrap();
+    assert_eq!(recorded, Some(first.public_key));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_capabilities_are_checked_at_install() {
+    let app = TestApp::new();
+    let staging = app.root.join("staging");
+    copy_fixture("audit-plugin", &staging);
+    let manifest_path = staging.join("audit-plugin/plugin.json");
+    let mut manifest: Value = serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+
+    // A capability outside the taxonomy is refused
+    manifest["capabilities"] = json!(["db:audit:read", "db:audits:write"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    let error = app.install_dir(&staging.join("audit-plugin"), &[Capability::DB_WRITE]).await.unwrap_err();
+    let error = format!("{:#}", error);
+    assert!(error.contains("/capabilities/1"), "Unexpected error: {}", error);
+    assert!(error.contains("Unknown capability 'db:audits:write'"), "Unexpected error: {}", error);
+    assert!(!app.root.join("plugins/audit-plugin").exists());
+
+    // Access to the whole database covers the audit functions
+    manifest["capabilities"] = json!(["db:read", "db:write"]);
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+    app.install_dir(&staging.join("audit-plugin"), &[Capability::DB_WRITE])
+        .await
+        .unwrap_or_else(|e| panic!("Failed to install audit-plugin: {:#}", e));
+    let logs = app
+        .call("audit-plugin", "get_user_audit_logs", json!({ "user_uuid": uuid::Uuid::new_v4().to_string() }))
+        .await;
+    assert_eq!(logs["success"], true, "get_user_audit_logs failed: {}", logs);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_exec_command_needs_approval_the_trusted_sandbox_and_an_allowed_program() {
+    // `exec` needs the trusted sandbox, so only trusted plugins get it
+    let untrusted = TestApp::new();
+    let error = format!("{:#}", untrusted.install_dir(&fixture_dir("exec-runner"), &[Capability::Exec]).await.unwrap_err());
+    assert!(error.contains("missing host function exec_command"), "Unexpected error: {}", error);
+
+    // Even a trusted plugin is asked for it, and denied if the user says no
+    let app = TestApp::new();
+    app.manager.set_trusted_plugins(["exec-runner".to_string()]);
+    let error = format!("{:#}", app.install_dir(&fixture_dir("exec-runner"), &[]).await.unwrap_err());
+    assert!(error.contains("missing host function exec_command"), "Unexpected error: {}", error);
+    let app = TestApp::new();
+    app.manager.set_trusted_plugins(["exec-runner".to_string()]);
+    app.install_granting("exec-runner", &[Capability::Exec]).await;
+    let manager = &app.manager;
+    let run = |input: &'static str| async move {
+        let output = manager.execute_plugin("exec-runner", "run", input.as_bytes()).await;
+        serde_json::from_slice::<Value>(&output.expect("run failed")).unwrap()
+    };
+
+    let cat = run("cat\n[]\nhello from stdin").await;
+    assert_eq!(cat["success"], true, "cat failed: {}", cat);
+    assert_eq!(cat["data"]["exit_code"], 0);
+    let stdout = base64::engine::general_purpose::STANDARD.decode(cat["data"]["stdout"].as_str().unwrap()).unwrap();
+    assert_eq!(stdout, b"hello from stdin");
+
+    let failing = run("false\n[]\n").await;
+    assert_eq!(failing["success"], true, "A failing program is still a completed call: {}", failing);
+    assert_eq!(failing["data"]["exit_code"], 1);
+    let missing = run("cat\n[\"/nonexistent/file\"]\n").await;
+    assert_ne!(missing["data"]["exit_code"], 0);
+    assert!(missing["data"]["stderr"].as_str().unwrap().contains("/nonexistent/file"), "Unexpected output: {}", missing);
+
+    // Only the manifest's allowed_commands run, so neither a shell nor a path
+    // to a listed program does, and arguments never reach a shell
+    for input in ["ls\n[]\n", "sh\n[\"-c\", \"echo injected\"]\n", "/bin/cat\n[]\n"] {
+        let refused = run(input).await;
+        assert_eq!(refused["success"], false, "{:?} should be refused: {}", input, refused);
+        assert!(refused["error"].as_str().unwrap().contains("not one of this plugin's allowed_commands"));
+    }
+    let literal = run("cat\n[\"; echo injected\"]\n").await;
+    assert_ne!(literal["data"]["exit_code"], 0, "The argument should be a file name cat can't open");
+
+    // Outside the trusted sandbox it is withheld whatever the user approved
+    app.manager.set_sandbox_overrides([("exec-runner".to_string(), SandboxProfile::Standard)]);
+    let error = format!("{:#}", app.install_dir(&fixture_dir("exec-runner"), &[Capability::Exec]).await.unwrap_err());
+    assert!(error.contains("missing host function exec_command"), "Unexpected error: {}", error);
+}
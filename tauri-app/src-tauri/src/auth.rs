@@ -0,0 +1,524 @@
+//! Session-aware authorization for Tauri commands
+//!
+//! The frontend sends its session ID as an `Authorization: Bearer <id>` header
+//! on invokes; automation sends a service account API key the same way.
+//! Commands are denied unless they are listed: [`PUBLIC_COMMANDS`] run for
+//! anyone, [`SIGNED_IN_COMMANDS`] need a live session or API key, and
+//! [`COMMAND_ROLES`] additionally need the caller to hold a role.
+//! Invocations of commands in [`AUDITED_COMMANDS`] are written to the audit
+//! log, attributed to the calling user, with sensitive arguments redacted.
+//! Users signed in with a temporary password may only change it, see
+//! [`check_password_change`].
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::http::HeaderMap;
+
+use crate::db::{operations, Database};
+use crate::ids::{self, IdKind};
+use crate::service_accounts::{self, API_KEY_PREFIX};
+
+pub use crate::db::operations::ROLE_ADMIN;
+
+/// Commands anyone may run, signed in or not
+pub const PUBLIC_COMMANDS: &[&str] = &[
+    "list_plugins",
+    "search_plugins",
+    "get_plugin_info",
+    "get_plugin_assets",
+    "get_ui_contributions",
+    // Sign-in and registration are plugin calls, made before there is a session
+    "execute_plugin",
+    "execute_plugin_stream",
+    "list_quarantined_plugins",
+    "list_trusted_authors",
+    "check_plugin_updates",
+    "get_plugin_compatibility_report",
+    "list_host_functions",
+    "get_license_report",
+    "list_plugin_canaries",
+    "get_plugin_dependency_graph",
+    "get_plugin_profiles",
+    "get_capability_requests",
+    "get_plugin_capabilities",
+    "get_plugin_metrics",
+    "get_plugin_logs",
+    "get_persist_execution_traces",
+    "db_test_connection",
+    "db_get_schema_version",
+    "json_diff",
+    "json_patch",
+    "tick_get_status",
+    "tick_get_current_tick",
+    "tick_get_session_info",
+    "tick_get_active_sessions",
+    "get_background_tasks",
+    "list_schedules",
+    "get_worker_counts",
+    "get_output_policy",
+    "get_network_denied_hosts",
+    "list_trashed_files",
+    "get_current_user_context",
+    "get_update_channel",
+    "check_app_update",
+];
+
+/// Commands any signed-in caller may run, whatever their roles; the job
+/// commands only reach the caller's own jobs unless they are an admin
+pub const SIGNED_IN_COMMANDS: &[&str] = &[
+    "execute_plugin_async",
+    "get_job_status",
+    "await_job",
+    "subscribe_job",
+    "list_jobs",
+    "diff_executions",
+    "cancel_job",
+    "retry_job",
+    "tick_register_session",
+    "tick_unregister_session",
+    "tick_add_client",
+    "tick_remove_client",
+    "claim_admin",
+];
+
+/// Commands that require a role, checked before the command runs
+pub const COMMAND_ROLES: &[(&str, &str)] = &[
+    ("tick_start", ROLE_ADMIN),
+    ("tick_stop", ROLE_ADMIN),
+    ("tick_set_rate", ROLE_ADMIN),
+    ("install_plugin", ROLE_ADMIN),
+    ("install_plugin_from_url", ROLE_ADMIN),
+    ("validate_plugin", ROLE_ADMIN),
+    ("uninstall_plugin", ROLE_ADMIN),
+    ("install_plugin_canary", ROLE_ADMIN),
+    ("approve_quarantined_plugin", ROLE_ADMIN),
+    ("reject_quarantined_plugin", ROLE_ADMIN),
+    ("add_trusted_author", ROLE_ADMIN),
+    ("remove_trusted_author", ROLE_ADMIN),
+    ("list_secrets", ROLE_ADMIN),
+    ("set_secret", ROLE_ADMIN),
+    ("delete_secret", ROLE_ADMIN),
+    ("promote_plugin_canary", ROLE_ADMIN),
+    ("rollback_plugin_canary", ROLE_ADMIN),
+    ("set_plugin_trusted", ROLE_ADMIN),
+    ("set_plugin_sandbox", ROLE_ADMIN),
+    ("save_plugin_profile", ROLE_ADMIN),
+    ("delete_plugin_profile", ROLE_ADMIN),
+    ("apply_plugin_profile", ROLE_ADMIN),
+    ("set_plugins_enabled", ROLE_ADMIN),
+    ("respond_capability_request", ROLE_ADMIN),
+    ("set_plugin_capability", ROLE_ADMIN),
+    ("set_plugin_config", ROLE_ADMIN),
+    ("set_worker_counts", ROLE_ADMIN),
+    ("set_output_policy", ROLE_ADMIN),
+    ("get_smtp_settings", ROLE_ADMIN),
+    ("set_smtp_settings", ROLE_ADMIN),
+    ("set_network_denied_hosts", ROLE_ADMIN),
+    ("get_egress_logs", ROLE_ADMIN),
+    ("get_execution_trace", ROLE_ADMIN),
+    ("set_persist_execution_traces", ROLE_ADMIN),
+    ("preview_migrations", ROLE_ADMIN),
+    ("set_user_role", ROLE_ADMIN),
+    ("preview_user_import", ROLE_ADMIN),
+    ("commit_user_import", ROLE_ADMIN),
+    ("create_service_account", ROLE_ADMIN),
+    ("list_service_accounts", ROLE_ADMIN),
+    ("rotate_service_account_key", ROLE_ADMIN),
+    ("set_service_account_disabled", ROLE_ADMIN),
+    ("set_update_channel", ROLE_ADMIN),
+    ("apply_app_update", ROLE_ADMIN),
+    ("create_schedule", ROLE_ADMIN),
+    ("set_schedule_enabled", ROLE_ADMIN),
+    ("delete_schedule", ROLE_ADMIN),
+    ("restore_trashed_file", ROLE_ADMIN),
+    ("verify_plugins", ROLE_ADMIN),
+    ("discover_plugins", ROLE_ADMIN),
+    ("get_plugin_config", ROLE_ADMIN),
+    ("get_trusted_plugins", ROLE_ADMIN),
+];
+
+/// Commands audited on invocation, with the argument naming the affected resource
+pub const AUDITED_COMMANDS: &[(&str, Option<&str>)] = &[
+    ("install_plugin", Some("path")),
+    ("install_plugin_from_url", Some("url")),
+    ("uninstall_plugin", Some("pluginName")),
+    ("install_plugin_canary", Some("path")),
+    ("approve_quarantined_plugin", Some("pluginName")),
+    ("reject_quarantined_plugin", Some("pluginName")),
+    ("add_trusted_author", Some("publicKey")),
+    ("remove_trusted_author", Some("publicKey")),
+    ("set_secret", Some("name")),
+    ("delete_secret", Some("name")),
+    ("promote_plugin_canary", Some("name")),
+    ("rollback_plugin_canary", Some("name")),
+    ("set_plugin_trusted", Some("pluginName")),
+    ("set_plugin_sandbox", Some("pluginName")),
+    ("save_plugin_profile", None),
+    ("delete_plugin_profile", Some("name")),
+    ("apply_plugin_profile", Some("name")),
+    ("set_plugins_enabled", None),
+    ("respond_capability_request", Some("requestId")),
+    ("set_plugin_capability", Some("pluginName")),
+    ("set_plugin_config", Some("pluginName")),
+    ("tick_set_rate", None),
+    ("set_worker_counts", None),
+    ("set_output_policy", None),
+    ("set_smtp_settings", None),
+    ("set_network_denied_hosts", None),
+    ("set_persist_execution_traces", None),
+    ("restore_trashed_file", Some("id")),
+    ("set_user_role", Some("userUuid")),
+    ("commit_user_import", Some("path")),
+    ("create_service_account", Some("name")),
+    ("rotate_service_account_key", Some("uuid")),
+    ("set_service_account_disabled", Some("uuid")),
+    ("set_update_channel", Some("channel")),
+    ("apply_app_update", None),
+    ("create_schedule", Some("pluginName")),
+    ("set_schedule_enabled", Some("id")),
+    ("delete_schedule", Some("id")),
+    ("claim_admin", None),
+];
+
+/// Commands a user who must change their password may still run
+pub const PASSWORD_CHANGE_COMMANDS: &[&str] = &["get_current_user_context"];
+
+/// Plugin functions a user who must change their password may still call,
+/// through `execute_plugin`
+pub const PASSWORD_CHANGE_PLUGIN_CALLS: &[(&str, &str)] = &[
+    ("auth-plugin", "change_password"),
+    ("auth-plugin", "verify_session"),
+    ("auth-plugin", "logout"),
+];
+
+/// Argument names whose values are never written to the audit log
+const REDACTED_ARGS: &[&str] = &["password", "secret", "token", "authorization", "api_key", "apikey"];
+
+/// The authenticated caller of a command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserContext {
+    pub user_uuid: String,
+    /// Set for users signed in with a session
+    pub session_id: Option<String>,
+    /// Set for service accounts authenticated with an API key
+    pub api_key_id: Option<String>,
+    pub roles: Vec<String>,
+    /// Set while the user still signs in with a temporary password
+    #[serde(default)]
+    pub password_reset_required: bool,
+}
+
+impl UserContext {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+/// Role required to run a command, if any
+pub fn required_role(command: &str) -> Option<&'static str> {
+    COMMAND_ROLES
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map(|(_, role)| *role)
+}
+
+/// Extract the session token from a bearer `Authorization` header
+pub fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Resolve the caller of a request from its session header or API key
+pub fn resolve_user(database: &Database, headers: &HeaderMap) -> Result<Option<UserContext>> {
+    let Some(token) = session_token(headers) else {
+        return Ok(None);
+    };
+
+    if token.starts_with(API_KEY_PREFIX) {
+        let Some((user_uuid, api_key_id)) = service_accounts::authenticate(database, token)? else {
+            return Ok(None);
+        };
+        let roles = database.with_connection(|conn| operations::get_user_roles(conn, &user_uuid))?;
+        return Ok(Some(UserContext {
+            user_uuid,
+            session_id: None,
+            api_key_id: Some(api_key_id),
+            roles,
+            password_reset_required: false,
+        }));
+    }
+
+    database
+        .with_connection(|conn| {
+            let Some(session) = operations::get_session(conn, token)? else {
+                return Ok(None);
+            };
+            let roles = operations::get_user_roles(conn, &session.user_uuid)?;
+            let password_reset_required = operations::get_user_by_uuid(conn, &session.user_uuid)?
+                .is_some_and(|user| user.password_reset_required);
+            Ok(Some(UserContext {
+                user_uuid: session.user_uuid,
+                session_id: Some(session.id),
+                api_key_id: None,
+                roles,
+                password_reset_required,
+            }))
+        })
+        .map_err(Into::into)
+}
+
+/// Whether anyone may run `command` without signing in
+pub fn is_public(command: &str) -> bool {
+    PUBLIC_COMMANDS.contains(&command)
+}
+
+/// Check that `user` may run `command`
+///
+/// Commands missing from every list are refused, so a newly registered
+/// command stays unreachable until it is classified here.
+pub fn authorize(command: &str, user: Option<&UserContext>) -> Result<()> {
+    if is_public(command) {
+        return Ok(());
+    }
+    let role = required_role(command);
+    if role.is_none() && !SIGNED_IN_COMMANDS.contains(&command) {
+        return Err(anyhow!("Forbidden: '{}' is not a command the app exposes", command));
+    }
+
+    match (user, role) {
+        (None, _) => Err(anyhow!("Unauthorized: '{}' requires a signed-in session", command)),
+        (Some(user), Some(role)) if !user.has_role(role) => Err(anyhow!(
+            "Forbidden: '{}' requires the '{}' role (user {})",
+            command,
+            role,
+            user.user_uuid
+        )),
+        (Some(_), _) => Ok(()),
+    }
+}
+
+/// Check that `user` may run `command` with `args` before changing their
+/// temporary password
+///
+/// Until they do, everything but [`PASSWORD_CHANGE_COMMANDS`] and the plugin
+/// calls in [`PASSWORD_CHANGE_PLUGIN_CALLS`] is refused, public commands too.
+pub fn check_password_change(command: &str, args: &Value, user: Option<&UserContext>) -> Result<()> {
+    let Some(user) = user.filter(|user| user.password_reset_required) else {
+        return Ok(());
+    };
+    if PASSWORD_CHANGE_COMMANDS.contains(&command) {
+        return Ok(());
+    }
+    if command == "execute_plugin" {
+        let plugin = args.get("pluginName").and_then(Value::as_str).unwrap_or_default();
+        let function = args.get("function").and_then(Value::as_str).unwrap_or_default();
+        if PASSWORD_CHANGE_PLUGIN_CALLS.contains(&(plugin, function)) {
+            return Ok(());
+        }
+    }
+    Err(anyhow!(
+        "Password change required: user {} must change their temporary password before running '{}'",
+        user.user_uuid,
+        command
+    ))
+}
+
+/// Whether invocations of `command` are audited
+pub fn is_audited(command: &str) -> bool {
+    AUDITED_COMMANDS.iter().any(|(name, _)| *name == command)
+}
+
+/// Replace the values of sensitive arguments, recursively
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let lowered = key.to_ascii_lowercase();
+                    if REDACTED_ARGS.iter().any(|name| lowered.contains(name)) {
+                        (key.clone(), Value::String("[REDACTED]".to_string()))
+                    } else {
+                        (key.clone(), redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Write an audit entry for a command invocation
+///
+/// `outcome` is `"invoked"` for authorized calls or `"denied"` for calls that
+/// failed authorization. Anonymous calls are not recorded since audit entries
+/// belong to a user.
+pub fn audit_invocation(
+    database: &Database,
+    command: &str,
+    args: &Value,
+    user: Option<&UserContext>,
+    outcome: &str,
+) -> Result<()> {
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let resource_id = AUDITED_COMMANDS
+        .iter()
+        .find(|(name, _)| *name == command)
+        .and_then(|(_, arg)| *arg)
+        .and_then(|arg| args.get(arg))
+        .map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        });
+    // Session IDs are bearer tokens, so only a digest identifies the session
+    let session = user
+        .session_id
+        .as_ref()
+        .map(|id| hex::encode(&Sha256::digest(id.as_bytes())[..8]));
+    let metadata = serde_json::json!({
+        "args": redact(args),
+        "outcome": outcome,
+        "session": session,
+        "api_key_id": user.api_key_id,
+    });
+
+    database
+        .with_connection(|conn| {
+            operations::create_audit_log(
+                conn,
+                &ids::new_id(IdKind::AuditLog),
+                &user.user_uuid,
+                &format!("command:{}", command),
+                Some("command"),
+                resource_id.as_deref(),
+                Some(&metadata.to_string()),
+                None,
+                None,
+                chrono::Utc::now().timestamp(),
+            )
+        })
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(roles: &[&str]) -> UserContext {
+        UserContext {
+            user_uuid: "user-1".to_string(),
+            session_id: Some("session-1".to_string()),
+            api_key_id: None,
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            password_reset_required: false,
+        }
+    }
+
+    /// Commands registered with `generate_handler!` in lib.rs
+    fn registered_commands() -> Vec<&'static str> {
+        let source = include_str!("lib.rs");
+        let start = source.find("generate_handler![").expect("lib.rs registers commands") + "generate_handler![".len();
+        let end = start + source[start..].find(']').unwrap();
+        source[start..end]
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_public_commands_need_no_session() {
+        assert!(authorize("list_plugins", None).is_ok());
+        assert!(authorize("execute_plugin", None).is_ok());
+        assert!(authorize("get_current_user_context", Some(&user(&[]))).is_ok());
+    }
+
+    #[test]
+    fn test_signed_in_commands_need_a_user() {
+        let error = authorize("execute_plugin_async", None).unwrap_err().to_string();
+        assert!(error.starts_with("Unauthorized"), "{}", error);
+        assert!(authorize("execute_plugin_async", Some(&user(&[]))).is_ok());
+        assert!(authorize("claim_admin", Some(&user(&[]))).is_ok());
+    }
+
+    #[test]
+    fn test_job_commands_are_not_public() {
+        for command in ["get_job_status", "await_job", "subscribe_job", "list_jobs", "diff_executions", "cancel_job", "retry_job"] {
+            assert!(!is_public(command), "'{}' is public", command);
+            assert!(authorize(command, None).is_err());
+            assert!(authorize(command, Some(&user(&[]))).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_role_commands_need_the_role() {
+        for command in ["create_schedule", "restore_trashed_file", "get_plugin_config", "get_trusted_plugins"] {
+            assert!(authorize(command, None).unwrap_err().to_string().starts_with("Unauthorized"));
+            let error = authorize(command, Some(&user(&["viewer"]))).unwrap_err().to_string();
+            assert!(error.contains("requires the 'admin' role"), "{}", error);
+            assert!(authorize(command, Some(&user(&[ROLE_ADMIN]))).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_temporary_passwords_must_be_changed_first() {
+        let mut imported = user(&[ROLE_ADMIN]);
+        imported.password_reset_required = true;
+        let call = |plugin: &str, function: &str| {
+            serde_json::json!({ "pluginName": plugin, "function": function, "input": {} })
+        };
+
+        for (command, args) in [
+            ("list_plugins", Value::Null),
+            ("install_plugin", Value::Null),
+            ("execute_plugin_async", call("auth-plugin", "change_password")),
+            ("execute_plugin", call("auth-plugin", "login")),
+            ("execute_plugin", call("hello", "change_password")),
+            ("execute_plugin_stream", call("auth-plugin", "change_password")),
+        ] {
+            let error = check_password_change(command, &args, Some(&imported)).unwrap_err().to_string();
+            assert!(error.starts_with("Password change required"), "{}", error);
+            assert!(check_password_change(command, &args, Some(&user(&[ROLE_ADMIN]))).is_ok());
+            assert!(check_password_change(command, &args, None).is_ok());
+        }
+        assert!(check_password_change("get_current_user_context", &Value::Null, Some(&imported)).is_ok());
+        for function in ["change_password", "verify_session", "logout"] {
+            assert!(check_password_change("execute_plugin", &call("auth-plugin", function), Some(&imported)).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_unlisted_commands_are_denied() {
+        let admin = user(&[ROLE_ADMIN]);
+        let error = authorize("drop_everything", Some(&admin)).unwrap_err().to_string();
+        assert!(error.contains("not a command the app exposes"), "{}", error);
+        assert!(authorize("drop_everything", None).is_err());
+    }
+
+    #[test]
+    fn test_every_registered_command_is_classified_once() {
+        let commands = registered_commands();
+        assert!(commands.contains(&"claim_admin"));
+        for command in &commands {
+            let lists = [
+                is_public(command),
+                SIGNED_IN_COMMANDS.contains(command),
+                required_role(command).is_some(),
+            ];
+            assert_eq!(lists.iter().filter(|listed| **listed).count(), 1, "'{}' must be in exactly one list", command);
+        }
+        for (command, _) in AUDITED_COMMANDS {
+            assert!(commands.contains(command), "audited '{}' is not registered", command);
+            assert!(!is_public(command), "audited '{}' is public", command);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! The desktop clipboard plugins with the `clipboard` capability use
+//!
+//! Reads and writes go through the platform's clipboard tools: `pbpaste` and
+//! `pbcopy` on macOS, PowerShell on Windows, and `wl-paste`/`wl-copy` under
+//! Wayland or `xclip` under X11 elsewhere.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use plugin_host::clipboard::Clipboard;
+
+pub struct SystemClipboard;
+
+/// Program and arguments that print the clipboard's text
+fn read_command() -> (&'static str, &'static [&'static str]) {
+    if cfg!(target_os = "macos") {
+        ("pbpaste", &[])
+    } else if cfg!(windows) {
+        ("powershell", &["-NoProfile", "-Command", "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Clipboard -Raw"])
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-paste", &["--no-newline", "--type", "text/plain"])
+    } else {
+        ("xclip", &["-selection", "clipboard", "-out"])
+    }
+}
+
+/// Program and arguments that put their standard input on the clipboard
+fn write_command() -> (&'static str, &'static [&'static str]) {
+    if cfg!(target_os = "macos") {
+        ("pbcopy", &[])
+    } else if cfg!(windows) {
+        ("powershell", &["-NoProfile", "-Command", "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())"])
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-copy", &["--type", "text/plain"])
+    } else {
+        ("xclip", &["-selection", "clipboard", "-in"])
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn read_text(&self) -> Result<Option<String>> {
+        let (program, args) = read_command();
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {}", program))?;
+        if !output.status.success() {
+            // The tools fail when the clipboard is empty or holds no text
+            return Ok(None);
+        }
+        let text = String::from_utf8(output.stdout).context("Clipboard text is not valid UTF-8")?;
+        Ok(Some(text))
+    }
+
+    fn write_text(&self, text: &str) -> Result<()> {
+        let (program, args) = write_command();
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+        child
+            .stdin
+            .take()
+            .context("No stdin to write to")?
+            .write_all(text.as_bytes())
+            .with_context(|| format!("Failed to write to {}", program))?;
+        let status = child.wait()?;
+        anyhow::ensure!(status.success(), "{} exited with {}", program, status);
+        Ok(())
+    }
+}
@@ -1,38 +1,137 @@
 //! Tauri commands for plugin management
 
-use crate::plugins::{PluginManager, PluginManifest};
-use crate::db::Database;
+use crate::plugins::{
+    Capability, CapabilityApprovals, CapabilityDecisions, CapabilityRequest, CompatibilityReport, CurrentUser, DependencyGraph, ExecutionContext, IntegrityViolation, LicenseReport, PluginCanary, PluginConfig, PluginLogEntry, PluginManager, PluginManifest,
+    PluginMetricsSnapshot, PluginQuery, PluginSetChange, PluginUpdate, QuarantinedPlugin, SandboxProfile, UiContributionKind, UiPanel, ValidationReport,
+};
+use crate::db::schema::{EgressLog, ExecutionTrace, Job, Schedule, ServiceAccount, TrashedFile, TrustedAuthor};
+use crate::db::migrations::{self, MigrationPreview};
+use crate::host_functions::introspection::HostFunctionInfo;
+use crate::db::{operations, Database};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::State;
+use tauri::{Emitter, State};
 use tokio::sync::RwLock;
 
+use crate::auth::{self, UserContext};
+use crate::error::AppError;
+use crate::execution_diff::{self, ExecutionDiff};
+use crate::hosts;
+use crate::ids::{self, IdKind};
+use crate::jobs::{JobEvent, JobManager, JobScope, JobStatus};
+use crate::mail::SmtpSettings;
+use crate::plugin_ui::{self, PLUGIN_ASSET_SCHEME, PLUGIN_UI_SCHEME};
+use crate::scheduler::Scheduler;
+use crate::service_accounts::{self, IssuedKey};
+use crate::settings::{
+    OutputPolicy, PluginProfile, SettingsStore, WorkerCounts, ACTIVE_PLUGIN_PROFILE_KEY,
+    DISABLED_PLUGINS_KEY, NETWORK_DENIED_HOSTS_KEY, OUTPUT_POLICY_KEY, PERSIST_EXECUTION_TRACES_KEY, PLUGIN_PROFILES_KEY, PLUGIN_SANDBOXES_KEY, TRUSTED_PLUGINS_KEY,
+    SMTP_KEY, UPDATE_CHANNEL_KEY, WORKER_COUNTS_KEY,
+};
+use crate::supervisor::{TaskInfo, TaskSupervisor};
 use crate::tick_manager::TickManager;
+use crate::trash::TrashBin;
+use crate::updater::{self, AppUpdateInfo, UpdateChannel};
+use crate::user_import::{self, ImportOptions, ImportPreview, ImportResult};
 
 pub struct AppState {
     pub plugin_manager: Arc<RwLock<PluginManager>>,
     pub database: Arc<Database>,
     pub tick_manager: Arc<RwLock<TickManager>>,
+    pub supervisor: TaskSupervisor,
+    pub settings: Arc<SettingsStore>,
+    pub jobs: Arc<JobManager>,
+    pub scheduler: Arc<Scheduler>,
+    /// Kept outside the plugin manager lock, which an install holds while it waits for approval
+    pub capability_approvals: Arc<CapabilityApprovals>,
+    pub trash: Arc<TrashBin>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PluginInfo {
+    /// Unique ID, `namespace/name` for namespaced plugins
+    pub id: String,
     pub name: String,
+    /// Name to show users, translated if the manifest has a match for the requested locale
+    pub display_name: String,
+    pub namespace: Option<String>,
     pub version: String,
     pub description: String,
     pub plugin_type: String,
-    pub capabilities: Vec<String>,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    pub capabilities: Vec<Capability>,
     pub entry_points: Vec<EntryPointInfo>,
+    /// Bundled panels, served from `plugin-ui://localhost/<id>/<entry>`
+    pub ui_panels: Vec<UiPanel>,
+    /// URL of the plugin's icon, if it has one
+    pub icon_url: Option<String>,
+    /// Why the plugin cannot run on this host; such plugins are listed but not loaded
+    pub incompatible: Vec<String>,
+    /// Whether the plugin should no longer be used, and the ID of the plugin replacing it
+    pub deprecated: bool,
+    pub replacement: Option<String>,
+    /// Sandbox the plugin runs in, after the user's and host's downgrades
+    pub sandbox: SandboxProfile,
+}
+
+/// Plugins matching a search, and how many installed plugins are in each
+/// category and have each tag, for offering filters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginSearchResults {
+    pub plugins: Vec<PluginInfo>,
+    pub categories: BTreeMap<String, usize>,
+    pub tags: BTreeMap<String, usize>,
+}
+
+/// A menu item, panel or route a loaded plugin adds to the frontend's navigation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UiContributionInfo {
+    /// ID of the contributing plugin
+    pub plugin: String,
+    /// Plugin's name to show users, translated for the requested locale
+    pub plugin_name: String,
+    pub id: String,
+    pub kind: UiContributionKind,
+    pub label: String,
+    /// Function to pass to `execute_plugin`, if the contribution runs an entry point
+    pub function: Option<String>,
+    /// URL of the panel it shows, served from `plugin-ui://localhost/<id>/<entry>`
+    pub panel_url: Option<String>,
+    /// Full path of a route, `/plugins/<plugin ID><path>`
+    pub route: Option<String>,
+    pub menu: Option<String>,
+    pub order: i32,
+}
+
+/// URLs of a plugin's catalog images
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginAssetUrls {
+    pub icon: Option<String>,
+    pub screenshots: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EntryPointInfo {
     pub name: String,
+    /// Function to pass to `execute_plugin`
+    pub function: String,
+    /// Other names the function can be called by
+    pub aliases: Vec<String>,
+    /// Whether calls that name no function run this one
+    pub default: bool,
     pub description: String,
     pub input_format: String,
     pub output_format: String,
+    /// JSON Schema of the input, for generating a form
+    pub input_schema: Option<serde_json::Value>,
+    pub output_schema: Option<serde_json::Value>,
+    /// Whether the function should no longer be called, and the entry point replacing it
+    pub deprecated: bool,
+    pub replacement: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,70 +141,336 @@ pub struct ExecuteRequest {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExecuteResponse {
+    /// Output decoded per the entry point's `output_format`: parsed JSON, a
+    /// string for text and a base64 string for binary
     pub output: serde_json::Value,
+    /// Deprecation warnings for the plugin or function called
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
-impl From<PluginManifest> for PluginInfo {
-    fn from(manifest: PluginManifest) -> Self {
+impl PluginInfo {
+    /// Describe a plugin, with its name and descriptions in the best match for `locale`
+    fn new(manifest: PluginManifest, manager: &PluginManager, locale: Option<&str>) -> Self {
+        let id = manifest.id();
+        let sandbox = manager.sandbox_profile(&manifest);
+        let localized = locale
+            .and_then(|locale| manifest.localized(locale))
+            .cloned()
+            .unwrap_or_default();
         PluginInfo {
+            display_name: localized.name.unwrap_or_else(|| manifest.name.clone()),
             name: manifest.name,
+            namespace: manifest.namespace,
             version: manifest.version,
-            description: manifest.description,
+            description: localized.description.unwrap_or(manifest.description),
             plugin_type: manifest.plugin_type,
+            categories: manifest.categories,
+            tags: manifest.tags,
             capabilities: manifest.capabilities,
             entry_points: manifest
                 .entry_points
                 .into_iter()
                 .map(|ep| EntryPointInfo {
+                    description: localized.entry_points.get(&ep.name).cloned().unwrap_or(ep.description),
                     name: ep.name,
-                    description: ep.description,
+                    function: ep.function,
+                    aliases: ep.aliases,
+                    default: ep.default,
                     input_format: ep.input_format,
                     output_format: ep.output_format,
+                    input_schema: ep.input_schema,
+                    output_schema: ep.output_schema,
+                    deprecated: ep.deprecated,
+                    replacement: ep.replacement,
                 })
                 .collect(),
+            ui_panels: manifest.ui.panels,
+            icon_url: manifest
+                .assets
+                .icon
+                .map(|icon| plugin_ui::file_url(PLUGIN_ASSET_SCHEME, &id, &icon)),
+            id,
+            incompatible: Vec::new(),
+            deprecated: manifest.deprecated,
+            replacement: manifest.replacement,
+            sandbox,
         }
     }
 }
 
 #[tauri::command]
-pub async fn list_plugins(state: State<'_, AppState>) -> Result<Vec<PluginInfo>, String> {
+pub async fn list_plugins(
+    state: State<'_, AppState>,
+    locale: Option<String>,
+    query: Option<PluginQuery>,
+) -> Result<Vec<PluginInfo>, String> {
     let manager = state.plugin_manager.read().await;
-    let plugins = manager.list_plugins().await;
-    Ok(plugins.into_iter().map(PluginInfo::from).collect())
+    let plugins = installed_plugins(&manager).await?;
+    Ok(describe_matches(&manager, plugins, &query.unwrap_or_default(), locale.as_deref()))
+}
+
+/// Search installed plugins by text, categories, tags and type, best matches first
+#[tauri::command]
+pub async fn search_plugins(
+    state: State<'_, AppState>,
+    query: PluginQuery,
+    locale: Option<String>,
+) -> Result<PluginSearchResults, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugins = installed_plugins(&manager).await?;
+    let mut categories = BTreeMap::new();
+    let mut tags = BTreeMap::new();
+    for (plugin, _) in &plugins {
+        for category in &plugin.categories {
+            *categories.entry(category.to_lowercase()).or_insert(0) += 1;
+        }
+        for tag in &plugin.tags {
+            *tags.entry(tag.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+    Ok(PluginSearchResults {
+        plugins: describe_matches(&manager, plugins, &query, locale.as_deref()),
+        categories,
+        tags,
+    })
+}
+
+/// Every installed plugin with the reasons it cannot run here; plugins built
+/// for a newer host or another platform are not loaded, but are listed and
+/// flagged rather than left out
+async fn installed_plugins(manager: &PluginManager) -> Result<Vec<(PluginManifest, Vec<String>)>, String> {
+    let mut plugins: Vec<_> = manager.list_plugins().await.into_iter().map(|plugin| (plugin, Vec::new())).collect();
+    plugins.extend(manager.unsupported_plugins().await.map_err(|e| e.to_string())?);
+    Ok(plugins)
+}
+
+fn describe_matches(
+    manager: &PluginManager,
+    plugins: Vec<(PluginManifest, Vec<String>)>,
+    query: &PluginQuery,
+    locale: Option<&str>,
+) -> Vec<PluginInfo> {
+    query
+        .rank(plugins, |(plugin, _)| plugin)
+        .into_iter()
+        .map(|(plugin, issues)| PluginInfo {
+            incompatible: issues,
+            ..PluginInfo::new(plugin, manager, locale)
+        })
+        .collect()
 }
 
 #[tauri::command]
 pub async fn get_plugin_info(
     state: State<'_, AppState>,
     name: String,
+    locale: Option<String>,
 ) -> Result<PluginInfo, String> {
     let manager = state.plugin_manager.read().await;
     let plugin = manager
         .get_plugin(&name)
         .await
         .ok_or_else(|| format!("Plugin not found: {}", name))?;
-    Ok(PluginInfo::from(plugin))
+    Ok(PluginInfo::new(plugin, &manager, locale.as_deref()))
+}
+
+#[tauri::command]
+pub async fn get_plugin_assets(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<PluginAssetUrls, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugin = manager
+        .get_plugin(&name)
+        .await
+        .ok_or_else(|| format!("Plugin not found: {}", name))?;
+    
+    let id = plugin.id();
+    let url = |path: &String| plugin_ui::file_url(PLUGIN_ASSET_SCHEME, &id, path);
+    Ok(PluginAssetUrls {
+        icon: plugin.assets.icon.as_ref().map(url),
+        screenshots: plugin.assets.screenshots.iter().map(url).collect(),
+    })
 }
 
+/// Menu items, panels and routes of the loaded plugins, by kind and then
+/// order, so the frontend can build its navigation from them
+#[tauri::command]
+pub async fn get_ui_contributions(
+    state: State<'_, AppState>,
+    locale: Option<String>,
+) -> Result<Vec<UiContributionInfo>, String> {
+    let manager = state.plugin_manager.read().await;
+    let mut contributions = Vec::new();
+    for plugin in manager.list_plugins().await {
+        let id = plugin.id();
+        let plugin_name = locale
+            .as_deref()
+            .and_then(|locale| plugin.localized(locale))
+            .and_then(|localized| localized.name.clone())
+            .unwrap_or_else(|| plugin.name.clone());
+        for contribution in &plugin.ui.contributions {
+            let function = contribution.entry_point.as_ref().and_then(|name| {
+                let entry_point = plugin.entry_points.iter().find(|ep| &ep.name == name)?;
+                Some(entry_point.function.clone())
+            });
+            let panel_url = contribution.panel.as_ref().and_then(|panel| {
+                let panel = plugin.ui.panels.iter().find(|p| &p.id == panel)?;
+                Some(plugin_ui::file_url(PLUGIN_UI_SCHEME, &id, &panel.entry))
+            });
+            contributions.push(UiContributionInfo {
+                plugin: id.clone(),
+                plugin_name: plugin_name.clone(),
+                id: contribution.id.clone(),
+                kind: contribution.kind,
+                label: contribution.label.clone(),
+                function,
+                panel_url,
+                route: contribution
+                    .path
+                    .as_ref()
+                    .map(|path| format!("/plugins/{}{}", id, path.trim_end_matches('/'))),
+                menu: contribution.menu.clone(),
+                order: contribution.order,
+            });
+        }
+    }
+    contributions.sort_by(|a, b| {
+        (a.kind as u8, a.order, &a.label, &a.plugin).cmp(&(b.kind as u8, b.order, &b.label, &b.plugin))
+    });
+    Ok(contributions)
+}
+
+/// The signed-in user behind an invoke, whom the plugin calls it makes are
+/// made for
+fn calling_user(database: &Database, request: &tauri::ipc::Request<'_>) -> Result<Option<CurrentUser>> {
+    Ok(auth::resolve_user(database, request.headers())?.map(|user| CurrentUser {
+        user_uuid: user.user_uuid,
+        session_id: user.session_id,
+        roles: user.roles,
+    }))
+}
+
+/// Execute a plugin function on behalf of the invoking session's user, if any
 #[tauri::command]
 pub async fn execute_plugin(
     state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
     plugin_name: String,
     function: String,
     input: serde_json::Value,
-) -> Result<ExecuteResponse, String> {
-    let input_bytes = serde_json::to_vec(&input).map_err(|e| e.to_string())?;
-
+) -> Result<ExecuteResponse, AppError> {
+    let context = ExecutionContext::new().with_user(calling_user(&state.database, &request)?);
     let manager = state.plugin_manager.read().await;
+    let (input_format, output_format) = manager.payload_formats(&plugin_name, &function).await;
+    let input_bytes = input_format.encode_input(&input)?;
+
     let output_bytes = manager
-        .execute_plugin(&plugin_name, &function, &input_bytes)
-        .await
-        .map_err(|e| e.to_string())?;
+        .execute_plugin_with_context(&plugin_name, &function, &input_bytes, &context)
+        .await?;
 
-    let output: serde_json::Value =
-        serde_json::from_slice(&output_bytes).map_err(|e| e.to_string())?;
+    let output = output_format.decode_output(&output_bytes)?;
+    let warnings = manager.deprecation_warnings(&plugin_name, &function).await;
 
-    Ok(ExecuteResponse { output })
+    Ok(ExecuteResponse { output, warnings })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamChunkEvent {
+    pub execution_id: String,
+    pub seq: u64,
+    /// Base64-encoded chunk bytes
+    pub data: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamedExecuteResponse {
+    pub execution_id: String,
+    pub chunks: u64,
+    pub output: serde_json::Value,
+    /// Deprecation warnings for the plugin or function called
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Execute a plugin function in streaming mode
+///
+/// Chunks emitted through the `stream_chunk` host function are forwarded as
+/// `plugin-stream:<execution_id>` events as they arrive, followed by a final
+/// event with `done: true`. Callers that need to subscribe before the first
+/// chunk can pass their own `execution_id`.
+#[tauri::command]
+pub async fn execute_plugin_stream(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    app_handle: tauri::AppHandle,
+    plugin_name: String,
+    function: String,
+    input: serde_json::Value,
+    execution_id: Option<String>,
+) -> Result<StreamedExecuteResponse, AppError> {
+    use base64::Engine;
+    
+    let manager = state.plugin_manager.read().await;
+    let (input_format, output_format) = manager.payload_formats(&plugin_name, &function).await;
+    let input_bytes = input_format.encode_input(&input)?;
+    let execution_id = execution_id.unwrap_or_else(|| ids::new_id(IdKind::Execution));
+    let event_name = format!("plugin-stream:{}", execution_id);
+    
+    let sink_handle = app_handle.clone();
+    let sink_event = event_name.clone();
+    let sink_execution_id = execution_id.clone();
+    let user = calling_user(&state.database, &request)?;
+    let context = ExecutionContext::with_id(execution_id.clone()).with_user(user).with_stream(Arc::new(
+        move |seq: u64, chunk: &[u8]| {
+            let _ = sink_handle.emit(
+                &sink_event,
+                StreamChunkEvent {
+                    execution_id: sink_execution_id.clone(),
+                    seq,
+                    data: base64::engine::general_purpose::STANDARD.encode(chunk),
+                    done: false,
+                },
+            );
+        },
+    ));
+    
+    let result = manager
+        .execute_plugin_with_context(&plugin_name, &function, &input_bytes, &context)
+        .await;
+    
+    let chunks = context.chunks_sent();
+    let _ = app_handle.emit(
+        &event_name,
+        StreamChunkEvent {
+            execution_id: execution_id.clone(),
+            seq: chunks,
+            data: String::new(),
+            done: true,
+        },
+    );
+    
+    let output_bytes = result?;
+    // Declared formats were enforced by the call; output of undeclared
+    // functions that is not JSON is passed on as text
+    let output = if output_bytes.is_empty() {
+        serde_json::Value::Null
+    } else {
+        output_format.decode_output(&output_bytes).unwrap_or_else(|_| {
+            serde_json::Value::String(String::from_utf8_lossy(&output_bytes).into_owned())
+        })
+    };
+    
+    let warnings = manager.deprecation_warnings(&plugin_name, &function).await;
+    
+    Ok(StreamedExecuteResponse {
+        execution_id,
+        chunks,
+        output,
+        warnings,
+    })
 }
 
 #[tauri::command]
@@ -115,32 +480,619 @@ pub async fn install_plugin(
 ) -> Result<String, String> {
     let plugin_path = PathBuf::from(path);
     let manager = state.plugin_manager.read().await;
-    manager
+    let plugin_name = manager
         .install_plugin(&plugin_path)
         .await
         .map_err(|e| e.to_string())?;
+    if manager.is_quarantined(&plugin_name) {
+        return Ok("Plugin quarantined until it is approved".to_string());
+    }
+    clear_disabled(&state, &manager, &plugin_name);
+    sync_schedules(&state, &manager, &plugin_name).await;
     Ok("Plugin installed successfully".to_string())
 }
 
 #[tauri::command]
-pub async fn install_plugin_from_url(
+pub async fn install_plugin_from_url(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    let install = manager
+        .install_plugin_from_url(&url)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !install.updated {
+        return Ok("Plugin is already up to date".to_string());
+    }
+    if manager.is_quarantined(&install.plugin) {
+        return Ok("Plugin quarantined until it is approved".to_string());
+    }
+    clear_disabled(&state, &manager, &install.plugin);
+    sync_schedules(&state, &manager, &install.plugin).await;
+    Ok("Plugin installed successfully from URL".to_string())
+}
+
+/// List plugins waiting for approval because they are unsigned or their
+/// author is not trusted
+#[tauri::command]
+pub async fn list_quarantined_plugins(state: State<'_, AppState>) -> Result<Vec<QuarantinedPlugin>, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .quarantined_plugins()
+        .map_err(|e| e.to_string())
+}
+
+/// Install a quarantined plugin, trusting its author from now on if asked to
+#[tauri::command]
+pub async fn approve_quarantined_plugin(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    trust_author: bool,
+) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugin_name = manager
+        .approve_quarantined_plugin(&plugin_name, trust_author)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    clear_disabled(&state, &manager, &plugin_name);
+    sync_schedules(&state, &manager, &plugin_name).await;
+    Ok("Plugin installed successfully".to_string())
+}
+
+/// Delete a quarantined plugin without installing it
+#[tauri::command]
+pub async fn reject_quarantined_plugin(state: State<'_, AppState>, plugin_name: String) -> Result<(), String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .reject_quarantined_plugin(&plugin_name)
+        .map_err(|e| e.to_string())
+}
+
+/// List the authors whose signed plugins install without approval
+#[tauri::command]
+pub async fn list_trusted_authors(state: State<'_, AppState>) -> Result<Vec<TrustedAuthor>, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .trusted_authors()
+        .map_err(|e| e.to_string())
+}
+
+/// Add an author's public key to the trust store
+#[tauri::command]
+pub async fn add_trusted_author(
+    state: State<'_, AppState>,
+    public_key: String,
+    name: String,
+) -> Result<(), String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .trust_author(&public_key, &name)
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Remove an author from the trust store; their installed plugins stay installed
+#[tauri::command]
+pub async fn remove_trusted_author(state: State<'_, AppState>, public_key: String) -> Result<bool, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .untrust_author(&public_key)
+        .map_err(|e| e.to_string())
+}
+
+/// List the names of the secrets plugin config can refer to as `secret://<name>`
+#[tauri::command]
+pub async fn list_secrets(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .secret_names()
+        .map_err(|e| e.to_string())
+}
+
+/// Create or replace a secret; returns the plugins reloaded to pick it up
+#[tauri::command]
+pub async fn set_secret(state: State<'_, AppState>, name: String, secret: String) -> Result<Vec<String>, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .set_secret(&name, &secret)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Remove a secret
+#[tauri::command]
+pub async fn delete_secret(state: State<'_, AppState>, name: String) -> Result<bool, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .delete_secret(&name)
+        .map_err(|e| e.to_string())
+}
+
+/// Check every installed plugin against this version of the app
+#[tauri::command]
+pub async fn get_plugin_compatibility_report(state: State<'_, AppState>) -> Result<CompatibilityReport, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .compatibility_report()
+        .map_err(|e| e.to_string())
+}
+
+/// Describe every host function plugins can be linked to
+#[tauri::command]
+pub async fn list_host_functions(state: State<'_, AppState>) -> Result<Vec<HostFunctionInfo>, String> {
+    Ok(state.plugin_manager.read().await.host_functions())
+}
+
+/// List the licenses of every installed plugin, for distributing the app
+#[tauri::command]
+pub async fn get_license_report(state: State<'_, AppState>) -> Result<LicenseReport, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .license_report()
+        .map_err(|e| e.to_string())
+}
+
+/// Ask the servers of plugins installed from a URL whether newer versions are available
+#[tauri::command]
+pub async fn check_plugin_updates(state: State<'_, AppState>) -> Result<Vec<PluginUpdate>, String> {
+    state
+        .plugin_manager
+        .read()
+        .await
+        .check_plugin_updates()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Check a plugin directory or URL the way an install would, without installing it
+#[tauri::command]
+pub async fn validate_plugin(
+    state: State<'_, AppState>,
+    source: String,
+) -> Result<ValidationReport, String> {
+    let manager = state.plugin_manager.read().await;
+    Ok(manager.validate_plugin(&source).await)
+}
+
+#[tauri::command]
+pub async fn uninstall_plugin(
+    state: State<'_, AppState>,
+    plugin_name: String,
+) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugin_name = manager
+        .uninstall_plugin(&plugin_name)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = state.scheduler.remove_plugin(&plugin_name) {
+        tracing::warn!("Failed to remove schedules of {}: {:#}", plugin_name, e);
+    }
+    Ok("Plugin uninstalled successfully".to_string())
+}
+
+/// Install another version of a plugin next to the current one, callable
+/// as `id@version` until it is promoted or rolled back
+#[tauri::command]
+pub async fn install_plugin_canary(state: State<'_, AppState>, path: String) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .install_canary(&PathBuf::from(path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List the plugin versions installed as canaries
+#[tauri::command]
+pub async fn list_plugin_canaries(state: State<'_, AppState>) -> Result<Vec<PluginCanary>, String> {
+    Ok(state.plugin_manager.read().await.list_canaries().await)
+}
+
+/// Make a canary the current version of its plugin
+#[tauri::command]
+pub async fn promote_plugin_canary(state: State<'_, AppState>, name: String) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugin_name = manager
+        .promote_canary(&name)
+        .await
+        .map_err(|e| e.to_string())?;
+    sync_schedules(&state, &manager, &plugin_name).await;
+    Ok(plugin_name)
+}
+
+/// Remove a canary, keeping the current version of its plugin
+#[tauri::command]
+pub async fn rollback_plugin_canary(state: State<'_, AppState>, name: String) -> Result<String, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .rollback_canary(&name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Get a plugin's manifest config, runtime overrides and effective values
+#[tauri::command]
+pub async fn get_plugin_config(
+    state: State<'_, AppState>,
+    plugin_name: String,
+) -> Result<PluginConfig, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .get_plugin_config(&plugin_name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Override config values of a plugin (null removes an override) and reload it
+#[tauri::command]
+pub async fn set_plugin_config(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    values: HashMap<String, Option<String>>,
+) -> Result<PluginConfig, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .set_plugin_config(&plugin_name, &values)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+#[tauri::command]
+pub async fn get_trusted_plugins(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state
+        .settings
+        .get_or_default(TRUSTED_PLUGINS_KEY)
+        .map_err(|e| e.to_string())
+}
+
+/// Move a plugin to a stricter sandbox than its manifest asks for, or back
+/// to that one with `None`. Takes effect the next time the plugin is loaded.
+#[tauri::command]
+pub async fn set_plugin_sandbox(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    profile: Option<SandboxProfile>,
+) -> Result<HashMap<String, SandboxProfile>, String> {
+    let manager = state.plugin_manager.read().await;
+    let plugin_name = manager.resolve_id(&plugin_name).await.unwrap_or(plugin_name);
+    let overrides: HashMap<String, SandboxProfile> = state
+        .settings
+        .update(PLUGIN_SANDBOXES_KEY, |overrides: &mut HashMap<String, SandboxProfile>| {
+            match profile {
+                Some(profile) => overrides.insert(plugin_name, profile),
+                None => overrides.remove(&plugin_name),
+            };
+        })
+        .map_err(|e| e.to_string())?;
+    
+    manager.set_sandbox_overrides(overrides.clone());
+    Ok(overrides)
+}
+
+/// Mark a plugin as trusted (allowed to enable WASI). Takes effect the next
+/// time the plugin is loaded. Updates skip quarantine and capability prompts
+/// only while they are signed by the key the plugin was first installed with.
+#[tauri::command]
+pub async fn set_plugin_trusted(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    trusted: bool,
+) -> Result<Vec<String>, String> {
+    // Trust is recorded by ID; names of plugins not loaded yet are kept as given
+    let manager = state.plugin_manager.read().await;
+    let plugin_name = manager.resolve_id(&plugin_name).await.unwrap_or(plugin_name);
+    let names: Vec<String> = state
+        .settings
+        .update(TRUSTED_PLUGINS_KEY, |names: &mut Vec<String>| {
+            names.retain(|name| name != &plugin_name);
+            if trusted {
+                names.push(plugin_name);
+                names.sort();
+            }
+        })
+        .map_err(|e| e.to_string())?;
+    
+    manager.set_trusted_plugins(names.clone());
+    Ok(names)
+}
+
+/// Plugin profiles and which plugins are disabled
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PluginProfiles {
+    pub profiles: Vec<PluginProfile>,
+    /// Profile applied last, unless plugins were enabled or disabled individually since
+    pub active: Option<String>,
+    pub disabled: Vec<String>,
+}
+
+#[tauri::command]
+pub async fn get_plugin_profiles(state: State<'_, AppState>) -> Result<PluginProfiles, String> {
+    let settings = &state.settings;
+    Ok(PluginProfiles {
+        profiles: settings.get_or_default(PLUGIN_PROFILES_KEY).map_err(|e| e.to_string())?,
+        active: settings.get_or_default(ACTIVE_PLUGIN_PROFILE_KEY).map_err(|e| e.to_string())?,
+        disabled: settings.get_or_default(DISABLED_PLUGINS_KEY).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Create or replace a plugin profile
+#[tauri::command]
+pub async fn save_plugin_profile(
+    state: State<'_, AppState>,
+    profile: PluginProfile,
+) -> Result<Vec<PluginProfile>, String> {
+    profile.validate().map_err(|e| format!("{:#}", e))?;
+    state
+        .settings
+        .update(PLUGIN_PROFILES_KEY, |profiles: &mut Vec<PluginProfile>| {
+            profiles.retain(|existing| existing.name != profile.name);
+            profiles.push(profile);
+            profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_plugin_profile(state: State<'_, AppState>, name: String) -> Result<Vec<PluginProfile>, String> {
+    state
+        .settings
+        .update(PLUGIN_PROFILES_KEY, |profiles: &mut Vec<PluginProfile>| {
+            profiles.retain(|profile| profile.name != name);
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Enable the plugins of a profile and disable every other installed plugin,
+/// pausing the schedules of the disabled ones
+#[tauri::command]
+pub async fn apply_plugin_profile(state: State<'_, AppState>, name: String) -> Result<PluginSetChange, String> {
+    let profiles: Vec<PluginProfile> = state
+        .settings
+        .get_or_default(PLUGIN_PROFILES_KEY)
+        .map_err(|e| e.to_string())?;
+    let profile = profiles
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("Plugin profile not found: {}", name))?;
+    
+    // Held for writing so no other plugin operation sees a half-applied profile
+    let manager = state.plugin_manager.write().await;
+    let disabled: Vec<String> = manager
+        .installed_plugin_ids()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|id| !profile.plugins.contains(id))
+        .collect();
+    let change = apply_disabled_plugins(&state, &manager, disabled).await?;
+    state
+        .settings
+        .set(ACTIVE_PLUGIN_PROFILE_KEY, &Some(profile.name))
+        .map_err(|e| e.to_string())?;
+    Ok(change)
+}
+
+/// Enable or disable several plugins at once
+#[tauri::command]
+pub async fn set_plugins_enabled(
+    state: State<'_, AppState>,
+    plugins: Vec<String>,
+    enabled: bool,
+) -> Result<PluginSetChange, String> {
+    let manager = state.plugin_manager.write().await;
+    let mut ids = Vec::with_capacity(plugins.len());
+    for plugin in plugins {
+        // Disabled plugins are not loaded, so their IDs are taken as given
+        ids.push(manager.resolve_id(&plugin).await.unwrap_or(plugin));
+    }
+    let mut disabled: Vec<String> = state
+        .settings
+        .get_or_default(DISABLED_PLUGINS_KEY)
+        .map_err(|e| e.to_string())?;
+    disabled.retain(|id| !ids.contains(id));
+    if !enabled {
+        disabled.extend(ids);
+    }
+    let change = apply_disabled_plugins(&state, &manager, disabled).await?;
+    state
+        .settings
+        .set(ACTIVE_PLUGIN_PROFILE_KEY, &None::<String>)
+        .map_err(|e| e.to_string())?;
+    Ok(change)
+}
+
+/// Store the disabled plugins, then load and unload plugins and pause schedules to match
+async fn apply_disabled_plugins(
+    state: &AppState,
+    manager: &PluginManager,
+    mut disabled: Vec<String>,
+) -> Result<PluginSetChange, String> {
+    disabled.sort();
+    disabled.dedup();
+    state
+        .settings
+        .set(DISABLED_PLUGINS_KEY, &disabled)
+        .map_err(|e| e.to_string())?;
+    state.scheduler.set_paused_plugins(disabled.iter().cloned());
+    let change = manager
+        .apply_disabled_plugins(disabled)
+        .await
+        .map_err(|e| e.to_string())?;
+    for plugin in &change.enabled {
+        sync_schedules(state, manager, plugin).await;
+    }
+    Ok(change)
+}
+
+/// Installing a disabled plugin enables it
+fn clear_disabled(state: &AppState, manager: &PluginManager, plugin: &str) {
+    if !manager.is_disabled(plugin) {
+        return;
+    }
+    match state.settings.update(DISABLED_PLUGINS_KEY, |disabled: &mut Vec<String>| {
+        disabled.retain(|id| id != plugin);
+    }) {
+        Ok(disabled) => {
+            manager.set_disabled_plugins(disabled.iter().cloned());
+            state.scheduler.set_paused_plugins(disabled);
+        }
+        Err(e) => tracing::warn!("Failed to enable reinstalled plugin {}: {:#}", plugin, e),
+    }
+}
+
+/// Capability requests of installs waiting for the user
+#[tauri::command]
+pub async fn get_capability_requests(state: State<'_, AppState>) -> Result<Vec<CapabilityRequest>, String> {
+    Ok(state.capability_approvals.pending())
+}
+
+/// Answer a capability request, granting `granted` and denying the rest of
+/// what it asked for
+#[tauri::command]
+pub async fn respond_capability_request(
+    state: State<'_, AppState>,
+    request_id: String,
+    granted: Vec<Capability>,
+) -> Result<(), String> {
+    if state.capability_approvals.respond(&request_id, granted) {
+        Ok(())
+    } else {
+        Err(format!("Capability request not found: {}", request_id))
+    }
+}
+
+#[tauri::command]
+pub async fn get_plugin_capabilities(
+    state: State<'_, AppState>,
+    plugin_name: String,
+) -> Result<CapabilityDecisions, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .get_plugin_capabilities(&plugin_name)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Grant or revoke a sensitive capability of an installed plugin; the plugin
+/// is reloaded so it applies
+#[tauri::command]
+pub async fn set_plugin_capability(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    capability: String,
+    granted: bool,
+) -> Result<CapabilityDecisions, String> {
+    let manager = state.plugin_manager.read().await;
+    manager
+        .set_plugin_capability(&plugin_name, &capability, granted)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    manager
+        .get_plugin_capabilities(&plugin_name)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Re-verify installed plugin modules now, disabling any that changed
+#[tauri::command]
+pub async fn get_plugin_dependency_graph(state: State<'_, AppState>) -> Result<DependencyGraph, String> {
+    let manager = state.plugin_manager.read().await;
+    Ok(manager.dependency_graph().await)
+}
+
+#[tauri::command]
+pub async fn verify_plugins(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<IntegrityViolation>, String> {
+    crate::verification::verify_and_notify(&state.plugin_manager, &app_handle)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Register a freshly installed plugin's manifest schedules
+async fn sync_schedules(state: &AppState, manager: &PluginManager, plugin_name: &str) {
+    let Some(manifest) = manager.get_plugin(plugin_name).await else {
+        return;
+    };
+    if let Err(e) = state.scheduler.sync_manifest(&manifest) {
+        tracing::warn!("Failed to register schedules of {}: {:#}", plugin_name, e);
+    }
+}
+
+#[tauri::command]
+pub async fn discover_plugins(state: State<'_, AppState>) -> Result<usize, String> {
+    let manager = state.plugin_manager.read().await;
+    manager.discover_plugins().await.map_err(|e| e.to_string())?;
+    let plugins = manager.list_plugins().await;
+    Ok(plugins.len())
+}
+
+#[tauri::command]
+pub async fn get_plugin_metrics(
+    state: State<'_, AppState>,
+    name: Option<String>,
+) -> Result<Vec<PluginMetricsSnapshot>, String> {
+    let manager = state.plugin_manager.read().await;
+    Ok(manager.get_metrics(name.as_deref()).await)
+}
+
+#[tauri::command]
+pub async fn get_plugin_logs(
+    state: State<'_, AppState>,
+    name: String,
+    limit: Option<usize>,
+) -> Result<Vec<PluginLogEntry>, String> {
+    let manager = state.plugin_manager.read().await;
+    Ok(manager.get_logs(&name, limit).await)
+}
+
+/// Host function calls made during an execution, or None if its trace is
+/// neither among the recent ones kept in memory nor stored
+#[tauri::command]
+pub async fn get_execution_trace(
     state: State<'_, AppState>,
-    url: String,
-) -> Result<String, String> {
+    execution_id: String,
+) -> Result<Option<ExecutionTrace>, String> {
     let manager = state.plugin_manager.read().await;
-    manager
-        .install_plugin_from_url(&url)
-        .await
-        .map_err(|e| e.to_string())?;
-    Ok("Plugin installed successfully from URL".to_string())
+    manager.execution_trace(&execution_id).map_err(|e| format!("{:#}", e))
 }
 
 #[tauri::command]
-pub async fn discover_plugins(state: State<'_, AppState>) -> Result<usize, String> {
-    let manager = state.plugin_manager.read().await;
-    manager.discover_plugins().await.map_err(|e| e.to_string())?;
-    let plugins = manager.list_plugins().await;
-    Ok(plugins.len())
+pub async fn get_persist_execution_traces(state: State<'_, AppState>) -> Result<bool, String> {
+    state
+        .settings
+        .get_or_default(PERSIST_EXECUTION_TRACES_KEY)
+        .map_err(|e| e.to_string())
+}
+
+/// Set whether execution traces are stored in the database, so they outlive
+/// the few kept in memory and restarts; applies to the next execution
+#[tauri::command]
+pub async fn set_persist_execution_traces(state: State<'_, AppState>, enabled: bool) -> Result<bool, String> {
+    state
+        .settings
+        .set(PERSIST_EXECUTION_TRACES_KEY, &enabled)
+        .map_err(|e| e.to_string())?;
+    Ok(enabled)
 }
 
 // ============================================================================
@@ -172,6 +1124,55 @@ pub async fn db_get_schema_version(state: State<'_, AppState>) -> Result<i32, St
     .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_egress_logs(
+    state: State<'_, AppState>,
+    plugin_name: Option<String>,
+    host: Option<String>,
+    since: Option<i64>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<Vec<EgressLog>, String> {
+    state.database.with_connection(|conn| {
+        crate::db::operations::get_egress_logs(
+            conn,
+            plugin_name.as_deref(),
+            host.as_deref(),
+            since,
+            limit.unwrap_or(100),
+            offset.unwrap_or(0),
+        )
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// List pending database migrations with their SQL, dry-run in a rolled-back transaction
+#[tauri::command]
+pub async fn preview_migrations(state: State<'_, AppState>) -> Result<MigrationPreview, String> {
+    state
+        .database
+        .with_connection(|conn| Ok(migrations::preview_migrations(conn)))
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+// ============================================================================
+// JSON Utility Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn json_diff(a: serde_json::Value, b: serde_json::Value) -> Result<serde_json::Value, String> {
+    Ok(crate::json_diff::diff(&a, &b))
+}
+
+#[tauri::command]
+pub async fn json_patch(
+    doc: serde_json::Value,
+    patch: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    crate::json_diff::apply(&doc, &patch).map_err(|e| format!("{:#}", e))
+}
+
 // ============================================================================
 // Tick Manager Commands
 // ============================================================================
@@ -186,10 +1187,17 @@ pub async fn tick_start(
     let mut manager = state.tick_manager.write().await;
     manager.start()?;
     
-    // Start the tick loop in background
-    let tick_manager_clone = state.tick_manager.clone();
-    tauri::async_runtime::spawn(async move {
-        crate::tick_manager::start_tick_loop(tick_manager_clone, app_handle).await;
+    drop(manager);
+    
+    // Start the tick loop as a supervised background task
+    let tick_manager = state.tick_manager.clone();
+    state.supervisor.spawn("tick_loop", move || {
+        let tick_manager = tick_manager.clone();
+        let app_handle = app_handle.clone();
+        async move {
+            crate::tick_manager::start_tick_loop(tick_manager, app_handle).await;
+            Ok(())
+        }
     });
     
     Ok("Tick manager started".to_string())
@@ -278,3 +1286,552 @@ pub async fn tick_get_active_sessions(state: State<'_, AppState>) -> Result<Vec<
     let manager = state.tick_manager.read().await;
     Ok(manager.get_active_sessions())
 }
+
+// ============================================================================
+// Background Task Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_background_tasks(state: State<'_, AppState>) -> Result<Vec<TaskInfo>, String> {
+    Ok(state.supervisor.list())
+}
+
+// ============================================================================
+// Background Job Commands
+// ============================================================================
+
+/// The signed-in caller of a job command
+fn job_caller(database: &Database, request: &tauri::ipc::Request<'_>) -> Result<UserContext, String> {
+    auth::resolve_user(database, request.headers())
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Jobs require a signed-in session".to_string())
+}
+
+/// Jobs `user` may see and act on: every job for admins, otherwise their own
+fn job_scope(user: &UserContext) -> JobScope<'_> {
+    if user.has_role(auth::ROLE_ADMIN) {
+        JobScope::All
+    } else {
+        JobScope::Owner(&user.user_uuid)
+    }
+}
+
+/// Run a plugin function in the background and return its job ID immediately
+#[tauri::command]
+pub async fn execute_plugin_async(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    plugin_name: String,
+    function: String,
+    input: serde_json::Value,
+    max_attempts: Option<u32>,
+) -> Result<String, String> {
+    let user = job_caller(&state.database, &request)?;
+    state
+        .jobs
+        .submit(&plugin_name, &function, input, max_attempts.unwrap_or(1), Some(&user.user_uuid))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_job_status(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    job_id: String,
+) -> Result<Job, String> {
+    let user = job_caller(&state.database, &request)?;
+    state
+        .jobs
+        .get(&job_id, job_scope(&user))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Job not found: {}", job_id))
+}
+
+/// Longest a single `await_job` call may wait
+const MAX_AWAIT_JOB_MS: u64 = 5 * 60 * 1000;
+
+/// Wait for a job to finish, returning it once it has or when `timeout_ms` elapses
+///
+/// The returned job is still queued or running if the wait timed out.
+#[tauri::command]
+pub async fn await_job(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    job_id: String,
+    timeout_ms: Option<u64>,
+) -> Result<Job, String> {
+    let user = job_caller(&state.database, &request)?;
+    let timeout = std::time::Duration::from_millis(timeout_ms.unwrap_or(30_000).min(MAX_AWAIT_JOB_MS));
+    state
+        .jobs
+        .await_job(&job_id, timeout, job_scope(&user))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Job not found: {}", job_id))
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobSubscription {
+    pub job: Job,
+    /// Event the job's updates are emitted on
+    pub event: String,
+    /// Events emitted before the subscription, oldest first
+    pub events: Vec<JobEvent>,
+}
+
+/// Catch up on a job's events
+///
+/// Listen to the returned `event` before calling this; events received both
+/// live and here have the same status and can be dropped. No events follow
+/// one with a terminal status.
+#[tauri::command]
+pub async fn subscribe_job(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    job_id: String,
+) -> Result<JobSubscription, String> {
+    let user = job_caller(&state.database, &request)?;
+    let (job, events) = state
+        .jobs
+        .catch_up(&job_id, job_scope(&user))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    Ok(JobSubscription {
+        job,
+        event: format!("job:{}", job_id),
+        events,
+    })
+}
+
+/// Run an interrupted job again; returns false if it isn't interrupted
+#[tauri::command]
+pub async fn retry_job(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    job_id: String,
+) -> Result<bool, String> {
+    let user = job_caller(&state.database, &request)?;
+    state.jobs.retry(&job_id, job_scope(&user)).map_err(|e| e.to_string())
+}
+
+/// Cancel a job; returns false if it had already finished
+#[tauri::command]
+pub async fn cancel_job(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    job_id: String,
+) -> Result<bool, String> {
+    let user = job_caller(&state.database, &request)?;
+    state.jobs.cancel(&job_id, job_scope(&user)).await.map_err(|e| e.to_string())
+}
+
+/// The caller's jobs, or every job for admins, newest first
+#[tauri::command]
+pub async fn list_jobs(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    status: Option<JobStatus>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+) -> Result<Vec<Job>, String> {
+    let user = job_caller(&state.database, &request)?;
+    state
+        .jobs
+        .list(status, limit.unwrap_or(100), offset.unwrap_or(0), job_scope(&user))
+        .map_err(|e| e.to_string())
+}
+
+/// Compare the results and output files of two jobs, e.g. before and after a plugin upgrade
+#[tauri::command]
+pub async fn diff_executions(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    id_a: String,
+    id_b: String,
+) -> Result<ExecutionDiff, String> {
+    let user = job_caller(&state.database, &request)?;
+    for id in [&id_a, &id_b] {
+        if state.jobs.get(id, job_scope(&user)).map_err(|e| e.to_string())?.is_none() {
+            return Err(format!("Job not found: {}", id));
+        }
+    }
+    execution_diff::diff_executions(&state.database, &id_a, &id_b).map_err(|e| format!("{:#}", e))
+}
+
+// ============================================================================
+// Schedule Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn list_schedules(
+    state: State<'_, AppState>,
+    plugin_name: Option<String>,
+) -> Result<Vec<Schedule>, String> {
+    state
+        .scheduler
+        .list(plugin_name.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Schedule a plugin function to run every `every_seconds` seconds or on a
+/// five-field `cron` expression (local time)
+#[tauri::command]
+pub async fn create_schedule(
+    state: State<'_, AppState>,
+    plugin_name: String,
+    function: String,
+    input: serde_json::Value,
+    every_seconds: Option<i64>,
+    cron: Option<String>,
+) -> Result<Schedule, String> {
+    state
+        .scheduler
+        .create(&plugin_name, &function, &input, every_seconds, cron)
+        .map_err(|e| format!("{:#}", e))
+}
+
+#[tauri::command]
+pub async fn set_schedule_enabled(
+    state: State<'_, AppState>,
+    id: String,
+    enabled: bool,
+) -> Result<bool, String> {
+    state
+        .scheduler
+        .set_enabled(&id, enabled)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_schedule(state: State<'_, AppState>, id: String) -> Result<bool, String> {
+    state.scheduler.delete(&id).map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Worker Pool Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_worker_counts(state: State<'_, AppState>) -> Result<WorkerCounts, String> {
+    state
+        .settings
+        .get_or_default(WORKER_COUNTS_KEY)
+        .map_err(|e| e.to_string())
+}
+
+/// Update worker pool sizes. Plugin worker changes apply immediately; the
+/// blocking thread pool size takes effect on the next start.
+#[tauri::command]
+pub async fn set_worker_counts(
+    state: State<'_, AppState>,
+    counts: WorkerCounts,
+) -> Result<WorkerCounts, String> {
+    counts.validate().map_err(|e| e.to_string())?;
+    state
+        .settings
+        .set(WORKER_COUNTS_KEY, &counts)
+        .map_err(|e| e.to_string())?;
+    
+    let manager = state.plugin_manager.read().await;
+    manager.set_execution_workers(counts.plugin_workers);
+    state.jobs.set_workers(counts.job_workers);
+    
+    Ok(counts)
+}
+
+// ============================================================================
+// Output Policy Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_output_policy(state: State<'_, AppState>) -> Result<OutputPolicy, String> {
+    Ok(OutputPolicy::load(&state.settings))
+}
+
+/// Set where plugins write output files; applies to the next file written
+#[tauri::command]
+pub async fn set_output_policy(
+    state: State<'_, AppState>,
+    policy: OutputPolicy,
+) -> Result<OutputPolicy, String> {
+    policy.validate().map_err(|e| format!("{:#}", e))?;
+    state
+        .settings
+        .set(OUTPUT_POLICY_KEY, &policy)
+        .map_err(|e| e.to_string())?;
+    Ok(policy)
+}
+
+// ============================================================================
+// SMTP Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_smtp_settings(state: State<'_, AppState>) -> Result<Option<SmtpSettings>, String> {
+    state.settings.get(SMTP_KEY).map_err(|e| e.to_string())
+}
+
+/// Set the SMTP server plugins send email through; the password is stored
+/// separately as the secret `password_secret` names
+#[tauri::command]
+pub async fn set_smtp_settings(
+    state: State<'_, AppState>,
+    settings: SmtpSettings,
+) -> Result<SmtpSettings, String> {
+    settings.validate().map_err(|e| format!("{:#}", e))?;
+    state
+        .settings
+        .set(SMTP_KEY, &settings)
+        .map_err(|e| e.to_string())?;
+    Ok(settings)
+}
+
+// ============================================================================
+// Network Deny List Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_network_denied_hosts(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state
+        .settings
+        .get_or_default(NETWORK_DENIED_HOSTS_KEY)
+        .map_err(|e| e.to_string())
+}
+
+/// Set the hosts no plugin may reach, on top of each plugin's own
+/// `denied_hosts`; applies to the next request a plugin makes
+#[tauri::command]
+pub async fn set_network_denied_hosts(
+    state: State<'_, AppState>,
+    hosts: Vec<String>,
+) -> Result<Vec<String>, String> {
+    for host in &hosts {
+        hosts::validate(host).map_err(|e| e.to_string())?;
+    }
+    state
+        .settings
+        .set(NETWORK_DENIED_HOSTS_KEY, &hosts)
+        .map_err(|e| e.to_string())?;
+    Ok(hosts)
+}
+
+// ============================================================================
+// Trash Commands
+// ============================================================================
+
+/// Files plugins deleted or overwrote that can still be restored, newest first
+#[tauri::command]
+pub async fn list_trashed_files(state: State<'_, AppState>) -> Result<Vec<TrashedFile>, String> {
+    state.trash.list().map_err(|e| format!("{:#}", e))
+}
+
+/// Move a trashed file back to its original path
+#[tauri::command]
+pub async fn restore_trashed_file(state: State<'_, AppState>, id: String) -> Result<TrashedFile, AppError> {
+    Ok(state.trash.restore(&id)?)
+}
+
+// ============================================================================
+// Authorization Commands
+// ============================================================================
+
+/// The user behind the invoking session, if it is signed in
+#[tauri::command]
+pub async fn get_current_user_context(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+) -> Result<Option<UserContext>, String> {
+    auth::resolve_user(&state.database, request.headers()).map_err(|e| e.to_string())
+}
+
+/// First-run setup: make the signed-in user the admin while nobody is one yet
+///
+/// Returns the caller's roles. Service accounts cannot claim the role.
+#[tauri::command]
+pub async fn claim_admin(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+) -> Result<Vec<String>, String> {
+    let user = auth::resolve_user(&state.database, request.headers())
+        .map_err(|e| e.to_string())?
+        .filter(|user| user.session_id.is_some())
+        .ok_or_else(|| "Claiming the admin role requires a signed-in session".to_string())?;
+    state
+        .database
+        .with_connection(|conn| {
+            let now = chrono::Utc::now().timestamp();
+            if operations::grant_role_if_unclaimed(conn, &user.user_uuid, auth::ROLE_ADMIN, now)? {
+                tracing::info!("Granted admin role to {} during setup", user.user_uuid);
+            } else if !user.has_role(auth::ROLE_ADMIN) {
+                return Ok(Err("An admin already exists; ask them for the role".to_string()));
+            }
+            Ok(Ok(operations::get_user_roles(conn, &user.user_uuid)?))
+        })
+        .map_err(|e| e.to_string())?
+}
+
+/// Grant or revoke a role. The last admin cannot be demoted.
+#[tauri::command]
+pub async fn set_user_role(
+    state: State<'_, AppState>,
+    user_uuid: String,
+    role: String,
+    granted: bool,
+) -> Result<Vec<String>, String> {
+    state.database.with_connection(|conn| {
+        if granted && operations::is_service_account(conn, &user_uuid)? {
+            if let Err(e) = service_accounts::check_roles(std::slice::from_ref(&role)) {
+                return Ok(Err(e.to_string()));
+            }
+        }
+        if granted {
+            operations::grant_user_role(conn, &user_uuid, &role, chrono::Utc::now().timestamp())?;
+        } else if role == auth::ROLE_ADMIN
+            && operations::count_users_with_role(conn, auth::ROLE_ADMIN)? <= 1
+            && operations::get_user_roles(conn, &user_uuid)?.iter().any(|r| r == auth::ROLE_ADMIN)
+        {
+            return Ok(Err("Cannot revoke the role of the last admin".to_string()));
+        } else {
+            operations::revoke_user_role(conn, &user_uuid, &role)?;
+        }
+        Ok(Ok(operations::get_user_roles(conn, &user_uuid)?))
+    })
+    .map_err(|e| e.to_string())?
+}
+
+// ============================================================================
+// User Import Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn preview_user_import(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<ImportPreview, String> {
+    user_import::preview(&state.database, Path::new(&path)).map_err(|e| format!("{:#}", e))
+}
+
+#[tauri::command]
+pub async fn commit_user_import(
+    state: State<'_, AppState>,
+    path: String,
+    options: Option<ImportOptions>,
+) -> Result<ImportResult, String> {
+    user_import::commit(&state.database, Path::new(&path), &options.unwrap_or_default())
+        .map_err(|e| format!("{:#}", e))
+}
+
+// ============================================================================
+// Service Account Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn create_service_account(
+    state: State<'_, AppState>,
+    request: tauri::ipc::Request<'_>,
+    name: String,
+    description: Option<String>,
+    roles: Vec<String>,
+) -> Result<IssuedKey, String> {
+    let creator = auth::resolve_user(&state.database, request.headers()).map_err(|e| e.to_string())?;
+    service_accounts::create(
+        &state.database,
+        &name,
+        description.as_deref(),
+        &roles,
+        creator.as_ref().map(|user| user.user_uuid.as_str()),
+    )
+    .map_err(|e| format!("{:#}", e))
+}
+
+#[tauri::command]
+pub async fn list_service_accounts(state: State<'_, AppState>) -> Result<Vec<ServiceAccount>, String> {
+    state
+        .database
+        .with_connection(operations::list_service_accounts)
+        .map_err(|e| e.to_string())
+}
+
+/// Issue a new API key, revoking the previous ones
+#[tauri::command]
+pub async fn rotate_service_account_key(
+    state: State<'_, AppState>,
+    uuid: String,
+) -> Result<IssuedKey, String> {
+    service_accounts::rotate_key(&state.database, &uuid).map_err(|e| format!("{:#}", e))
+}
+
+#[tauri::command]
+pub async fn set_service_account_disabled(
+    state: State<'_, AppState>,
+    uuid: String,
+    disabled: bool,
+) -> Result<ServiceAccount, String> {
+    state
+        .database
+        .with_connection(|conn| operations::set_service_account_disabled(conn, &uuid, disabled))
+        .map_err(|e| e.to_string())?;
+    service_accounts::get(&state.database, &uuid).map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Application Update Commands
+// ============================================================================
+
+/// Result of installing an application update
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppUpdateResult {
+    pub update: AppUpdateInfo,
+    /// Database backup taken before installing
+    pub backup_path: PathBuf,
+}
+
+#[tauri::command]
+pub async fn get_update_channel(state: State<'_, AppState>) -> Result<UpdateChannel, String> {
+    state
+        .settings
+        .get_or_default(UPDATE_CHANNEL_KEY)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_update_channel(
+    state: State<'_, AppState>,
+    channel: UpdateChannel,
+) -> Result<UpdateChannel, String> {
+    state
+        .settings
+        .set(UPDATE_CHANNEL_KEY, &channel)
+        .map_err(|e| e.to_string())?;
+    Ok(channel)
+}
+
+/// Check the selected channel for a newer application version
+#[tauri::command]
+pub async fn check_app_update(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<AppUpdateInfo>, String> {
+    let channel = get_update_channel(state).await?;
+    updater::check(&app_handle, channel)
+        .await
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Back up the database, install the selected channel's latest version and
+/// restart. Download progress is emitted as `app-update-progress` events.
+#[tauri::command]
+pub async fn apply_app_update(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AppUpdateResult, String> {
+    let channel: UpdateChannel = state
+        .settings
+        .get_or_default(UPDATE_CHANNEL_KEY)
+        .map_err(|e| e.to_string())?;
+    let (update, backup_path) = updater::apply(&app_handle, &state.database, channel)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    app_handle.request_restart();
+    Ok(AppUpdateResult { update, backup_path })
+}
@@ -0,0 +1,154 @@
+//! Comparison of two executions of a plugin
+//!
+//! Used to check that a plugin upgrade did not change what a conversion
+//! produces: the results of two jobs are compared as JSON where possible, and
+//! the files each wrote through `write_output_file` by content hash.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::db::schema::{ExecutionOutput, Job};
+use crate::db::{operations, Database};
+use crate::json_diff;
+
+/// What was run in an execution being compared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionSummary {
+    pub id: String,
+    pub plugin_name: String,
+    pub function: String,
+    pub status: String,
+    pub finished_at: Option<i64>,
+}
+
+/// An output file of either execution, matched to the other's by file name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputFileDiff {
+    pub name: String,
+    /// None if only the other execution wrote a file by this name
+    pub path_a: Option<String>,
+    pub path_b: Option<String>,
+    pub sha256_a: Option<String>,
+    pub sha256_b: Option<String>,
+    pub changed: bool,
+}
+
+/// Differences between the outputs of two executions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionDiff {
+    pub a: ExecutionSummary,
+    pub b: ExecutionSummary,
+    /// Neither the result nor any output file changed
+    pub identical: bool,
+    pub result_changed: bool,
+    /// RFC 6902 JSON Patch from the result of `a` to that of `b`, when both are JSON
+    pub result_patch: Option<Value>,
+    pub files: Vec<OutputFileDiff>,
+    /// Reasons the comparison may not mean much, such as different inputs
+    pub notes: Vec<String>,
+}
+
+/// Compare the results and output files of two jobs
+pub fn diff_executions(database: &Database, id_a: &str, id_b: &str) -> Result<ExecutionDiff> {
+    let (job_a, outputs_a) = load(database, id_a)?;
+    let (job_b, outputs_b) = load(database, id_b)?;
+
+    let mut notes = Vec::new();
+    if (&job_a.plugin_name, &job_a.function) != (&job_b.plugin_name, &job_b.function) {
+        notes.push(format!(
+            "Different functions: {}/{} and {}/{}",
+            job_a.plugin_name, job_a.function, job_b.plugin_name, job_b.function
+        ));
+    }
+    if !same_json(&job_a.input, &job_b.input) {
+        notes.push("The executions had different inputs".to_string());
+    }
+    for job in [&job_a, &job_b] {
+        if job.status != "completed" {
+            notes.push(format!("Execution {} is {}, not completed", job.id, job.status));
+        }
+    }
+
+    let result_a = job_a.result.as_deref().unwrap_or_default();
+    let result_b = job_b.result.as_deref().unwrap_or_default();
+    let (result_changed, result_patch) = match (serde_json::from_str::<Value>(result_a), serde_json::from_str::<Value>(result_b)) {
+        (Ok(a), Ok(b)) => (a != b, Some(json_diff::diff(&a, &b))),
+        _ => (result_a != result_b, None),
+    };
+    let files = diff_outputs(&outputs_a, &outputs_b);
+
+    Ok(ExecutionDiff {
+        identical: !result_changed && files.iter().all(|file| !file.changed),
+        a: summary(&job_a),
+        b: summary(&job_b),
+        result_changed,
+        result_patch,
+        files,
+        notes,
+    })
+}
+
+fn load(database: &Database, id: &str) -> Result<(Job, Vec<ExecutionOutput>)> {
+    let job = database
+        .with_connection(|conn| operations::get_job(conn, id))?
+        .with_context(|| format!("Execution not found: {}", id))?;
+    let outputs = database.with_connection(|conn| operations::list_execution_outputs(conn, id))?;
+    Ok((job, outputs))
+}
+
+fn summary(job: &Job) -> ExecutionSummary {
+    ExecutionSummary {
+        id: job.id.clone(),
+        plugin_name: job.plugin_name.clone(),
+        function: job.function.clone(),
+        status: job.status.clone(),
+        finished_at: job.finished_at,
+    }
+}
+
+/// Whether two inputs are equal, ignoring JSON formatting
+fn same_json(a: &str, b: &str) -> bool {
+    match (serde_json::from_str::<Value>(a), serde_json::from_str::<Value>(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn file_name(output: &ExecutionOutput) -> String {
+    Path::new(&output.path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| output.path.clone())
+}
+
+/// Pair the files of both executions by file name, in the order `a` wrote them
+fn diff_outputs(outputs_a: &[ExecutionOutput], outputs_b: &[ExecutionOutput]) -> Vec<OutputFileDiff> {
+    let mut unmatched_b: Vec<&ExecutionOutput> = outputs_b.iter().collect();
+    let mut files = Vec::new();
+    for a in outputs_a {
+        let name = file_name(a);
+        let b = unmatched_b
+            .iter()
+            .position(|b| file_name(b) == name)
+            .map(|index| unmatched_b.remove(index));
+        files.push(OutputFileDiff {
+            changed: b.is_none_or(|b| b.sha256 != a.sha256),
+            path_a: Some(a.path.clone()),
+            path_b: b.map(|b| b.path.clone()),
+            sha256_a: Some(a.sha256.clone()),
+            sha256_b: b.map(|b| b.sha256.clone()),
+            name,
+        });
+    }
+    files.extend(unmatched_b.into_iter().map(|b| OutputFileDiff {
+        name: file_name(b),
+        path_a: None,
+        path_b: Some(b.path.clone()),
+        sha256_a: None,
+        sha256_b: Some(b.sha256.clone()),
+        changed: true,
+    }));
+    files
+}
@@ -0,0 +1,200 @@
+//! The OS keychain secrets plugin config refers to are kept in
+//!
+//! Secrets go in the macOS Keychain, the Windows Credential Manager, or the
+//! Secret Service (GNOME Keyring, KWallet) elsewhere, as entries of the app's
+//! service named `secret:<name>`. Keychains can't list a service's entries
+//! portably, so the names set are kept in an `index` entry of their own.
+
+use anyhow::{Context, Result};
+use keyring::Entry;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use plugin_host::secrets::{self, SecretStore};
+
+/// Entry holding the names of the secrets set, as a JSON array; secret
+/// names can't contain `:`, so it never collides with one
+const INDEX_ENTRY: &str = "index";
+
+pub struct KeyringSecretStore {
+    service: String,
+    /// Serializes read-modify-write cycles of the index
+    lock: Mutex<()>,
+}
+
+impl KeyringSecretStore {
+    /// Open the secrets of `service`, failing if the keychain can't be reached
+    pub fn open(service: &str) -> Result<Self> {
+        let store = Self { service: service.to_string(), lock: Mutex::new(()) };
+        store.load_names().context("The OS keychain is unavailable")?;
+        Ok(store)
+    }
+
+    /// Move every secret in `other` into the keychain, e.g. ones set while it
+    /// was unavailable; returns how many were moved
+    pub fn import(&self, other: &dyn SecretStore) -> Result<usize> {
+        let names = other.names()?;
+        for name in &names {
+            if let Some(value) = other.get(name)? {
+                self.set(name, &value)?;
+            }
+            other.delete(name)?;
+        }
+        Ok(names.len())
+    }
+
+    fn entry(&self, user: &str) -> Result<Entry> {
+        Entry::new(&self.service, user).with_context(|| format!("Invalid keychain entry '{}'", user))
+    }
+
+    fn secret_entry(&self, name: &str) -> Result<Entry> {
+        self.entry(&format!("secret:{}", name))
+    }
+
+    fn load_names(&self) -> Result<BTreeSet<String>> {
+        match read(&self.entry(INDEX_ENTRY)?)? {
+            Some(index) => serde_json::from_str(&index).context("The keychain's index of secrets is corrupt"),
+            None => Ok(BTreeSet::new()),
+        }
+    }
+
+    fn save_names(&self, names: &BTreeSet<String>) -> Result<()> {
+        self.entry(INDEX_ENTRY)?
+            .set_password(&serde_json::to_string(names)?)
+            .context("Failed to update the keychain's index of secrets")
+    }
+}
+
+impl SecretStore for KeyringSecretStore {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        read(&self.secret_entry(name)?).with_context(|| format!("Failed to read secret '{}'", name))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        secrets::validate_name(name)?;
+        let _guard = self.lock.lock().unwrap();
+        self.secret_entry(name)?
+            .set_password(value)
+            .with_context(|| format!("Failed to store secret '{}'", name))?;
+        let mut names = self.load_names()?;
+        if names.insert(name.to_string()) {
+            self.save_names(&names)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        let _guard = self.lock.lock().unwrap();
+        let deleted = match self.secret_entry(name)?.delete_credential() {
+            Ok(()) => true,
+            Err(keyring::Error::NoEntry) => false,
+            Err(e) => return Err(e).with_context(|| format!("Failed to delete secret '{}'", name)),
+        };
+        let mut names = self.load_names()?;
+        if names.remove(name) {
+            self.save_names(&names)?;
+        }
+        Ok(deleted)
+    }
+
+    fn names(&self) -> Result<Vec<String>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.load_names()?.into_iter().collect())
+    }
+}
+
+/// An entry's value, or None if it is not set
+fn read(entry: &Entry) -> Result<Option<String>> {
+    match entry.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use plugin_host::secrets::FileSecretStore;
+    use std::any::Any;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Once};
+
+    type Entries = Arc<Mutex<HashMap<(String, String), Vec<u8>>>>;
+
+    /// A keychain that keeps entries in memory, shared by every entry made
+    #[derive(Default)]
+    struct MemoryKeychain(Entries);
+
+    struct MemoryEntry {
+        entries: Entries,
+        key: (String, String),
+    }
+
+    impl CredentialApi for MemoryEntry {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            self.entries.lock().unwrap().insert(self.key.clone(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            self.entries.lock().unwrap().get(&self.key).cloned().ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            self.entries.lock().unwrap().remove(&self.key).map(|_| ()).ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    impl CredentialBuilderApi for MemoryKeychain {
+        fn build(&self, _target: Option<&str>, service: &str, user: &str) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(MemoryEntry { entries: self.0.clone(), key: (service.to_string(), user.to_string()) }))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    /// A store of its own service in the in-memory keychain
+    fn store() -> KeyringSecretStore {
+        static KEYCHAIN: Once = Once::new();
+        KEYCHAIN.call_once(|| keyring::set_default_credential_builder(Box::new(MemoryKeychain::default())));
+        KeyringSecretStore::open(&format!("a2e-test-{}", uuid::Uuid::new_v4())).unwrap()
+    }
+
+    #[test]
+    fn test_keyring_store_round_trip() {
+        let store = store();
+        assert!(store.names().unwrap().is_empty());
+        store.set("openai_key", "sk-1").unwrap();
+        store.set("smtp.password", "hunter2").unwrap();
+        store.set("openai_key", "sk-2").unwrap();
+        assert_eq!(store.get("openai_key").unwrap().as_deref(), Some("sk-2"));
+        assert_eq!(store.names().unwrap(), ["openai_key", "smtp.password"]);
+        assert!(store.set("not:valid", "x").is_err());
+
+        assert!(store.delete("openai_key").unwrap());
+        assert!(!store.delete("openai_key").unwrap());
+        assert_eq!(store.get("openai_key").unwrap(), None);
+        assert_eq!(store.names().unwrap(), ["smtp.password"]);
+    }
+
+    #[test]
+    fn test_keyring_store_imports_file_secrets() {
+        let dir = std::env::temp_dir().join(format!("a2e-secrets-{}", uuid::Uuid::new_v4()));
+        let file = FileSecretStore::new(dir.clone());
+        file.set("api_key", "from-file").unwrap();
+
+        let store = store();
+        assert_eq!(store.import(&file).unwrap(), 1);
+        assert_eq!(store.get("api_key").unwrap().as_deref(), Some("from-file"));
+        assert!(file.names().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
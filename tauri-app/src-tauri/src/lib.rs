@@ -1,16 +1,33 @@
-mod plugins;
+mod auth;
+mod clipboard;
 mod commands;
-pub mod db;  // Make public for testing
-mod host_functions;
+mod execution_diff;
+mod keychain;
+mod notifications;
+mod plugin_ui;
+mod service_accounts;
+mod supervisor;
 mod tick_manager;
+mod updater;
+mod user_import;
+mod verification;
+
+// The plugin runtime lives in the plugin-host crate
+pub use plugin_host::{db, plugins};
+use plugin_host::{bus, error, events, host_functions, hosts, ids, jobs, json_diff, mail, scheduler, secrets, settings, trash};
 
 use commands::*;
-use plugins::PluginManager;
+use plugins::{PluginManager, SandboxProfile};
 use db::Database;
+use settings::{SettingsStore, WorkerCounts, WORKER_COUNTS_KEY};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::Manager;
 use tokio::sync::RwLock;
 
+/// Dispatches an IPC invoke to its command
+type InvokeHandler = dyn Fn(tauri::ipc::Invoke) -> bool + Send + Sync;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Initialize tracing
@@ -21,8 +38,144 @@ pub fn run() {
         )
         .init();
 
+    let context = tauri::generate_context!();
+
+    // Build the async runtime ourselves so the blocking thread pool size can
+    // come from settings (read straight from the database before setup runs)
+    let worker_counts = dirs::data_dir()
+        .map(|dir| dir.join(&context.config().identifier).join("app.db"))
+        .map(|db_path| WorkerCounts::load_for_startup(&db_path))
+        .unwrap_or_default();
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .max_blocking_threads(worker_counts.db_blocking_threads)
+        .build()
+        .expect("Failed to build async runtime");
+    tauri::async_runtime::set(runtime.handle().clone());
+    // Lets the plugin runtime spawn tasks from setup, which runs outside it
+    let _runtime_guard = runtime.enter();
+
+    let handler: Box<InvokeHandler> = Box::new(tauri::generate_handler![
+        list_plugins,
+        search_plugins,
+        get_plugin_info,
+        get_plugin_assets,
+        get_ui_contributions,
+        execute_plugin,
+        execute_plugin_stream,
+        execute_plugin_async,
+        get_job_status,
+        cancel_job,
+        await_job,
+        retry_job,
+        subscribe_job,
+        list_jobs,
+        diff_executions,
+        install_plugin,
+        install_plugin_from_url,
+        list_quarantined_plugins,
+        approve_quarantined_plugin,
+        reject_quarantined_plugin,
+        list_trusted_authors,
+        add_trusted_author,
+        remove_trusted_author,
+        list_secrets,
+        set_secret,
+        delete_secret,
+        check_plugin_updates,
+        get_plugin_compatibility_report,
+        list_host_functions,
+        get_license_report,
+        validate_plugin,
+        uninstall_plugin,
+        install_plugin_canary,
+        list_plugin_canaries,
+        promote_plugin_canary,
+        rollback_plugin_canary,
+        get_trusted_plugins,
+        verify_plugins,
+        get_plugin_dependency_graph,
+        set_plugin_trusted,
+        set_plugin_sandbox,
+        get_plugin_profiles,
+        save_plugin_profile,
+        delete_plugin_profile,
+        apply_plugin_profile,
+        set_plugins_enabled,
+        get_capability_requests,
+        respond_capability_request,
+        get_plugin_capabilities,
+        set_plugin_capability,
+        get_plugin_config,
+        set_plugin_config,
+        discover_plugins,
+        get_plugin_metrics,
+        get_plugin_logs,
+        get_execution_trace,
+        get_persist_execution_traces,
+        set_persist_execution_traces,
+        db_test_connection,
+        db_get_schema_version,
+        get_egress_logs,
+        preview_migrations,
+        json_diff,
+        json_patch,
+        tick_start,
+        tick_stop,
+        tick_get_status,
+        tick_get_current_tick,
+        tick_set_rate,
+        tick_register_session,
+        tick_unregister_session,
+        tick_add_client,
+        tick_remove_client,
+        tick_get_session_info,
+        tick_get_active_sessions,
+        get_background_tasks,
+        list_schedules,
+        create_schedule,
+        set_schedule_enabled,
+        delete_schedule,
+        get_worker_counts,
+        set_worker_counts,
+        get_output_policy,
+        set_output_policy,
+        get_smtp_settings,
+        set_smtp_settings,
+        get_network_denied_hosts,
+        set_network_denied_hosts,
+        list_trashed_files,
+        restore_trashed_file,
+        get_current_user_context,
+        set_user_role,
+        claim_admin,
+        preview_user_import,
+        commit_user_import,
+        create_service_account,
+        list_service_accounts,
+        rotate_service_account_key,
+        set_service_account_disabled,
+        get_update_channel,
+        set_update_channel,
+        check_app_update,
+        apply_app_update,
+    ]);
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .register_asynchronous_uri_scheme_protocol(plugin_ui::PLUGIN_UI_SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(plugin_ui::handle_ui_request(&app, request).await);
+            });
+        })
+        .register_asynchronous_uri_scheme_protocol(plugin_ui::PLUGIN_ASSET_SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(plugin_ui::handle_asset_request(&app, request).await);
+            });
+        })
         .setup(|app| {
             // Get app data directory
             let app_data_dir = app.path().app_data_dir()
@@ -34,15 +187,55 @@ pub fn run() {
             let database = Database::new(db_path)
                 .expect("Failed to create database");
             
-            // Run migrations
-            database.with_connection(|conn| {
-                db::migrations::run_migrations(conn)
-            }).expect("Failed to run database migrations");
+            // Run migrations; refuses databases from a newer build
+            database.with_connection(|conn| Ok(db::migrations::run_migrations(conn)))
+                .expect("Failed to access database")
+                .expect("Failed to run database migrations");
+            
+            let settings = SettingsStore::new(Arc::new(database.clone()));
+            ids::set_strategy(settings.get_or_default(settings::ID_STRATEGY_KEY)
+                .expect("Failed to load ID strategy"));
+            let worker_counts: WorkerCounts = settings.get_or_default(WORKER_COUNTS_KEY)
+                .expect("Failed to load worker settings");
             
             // Create plugin manager with database and host functions
             let plugins_dir = app_data_dir.join("plugins");
             let mut plugin_manager = PluginManager::new_with_database(plugins_dir, Arc::new(database.clone()))
                 .expect("Failed to create plugin manager");
+            plugin_manager.set_execution_workers(worker_counts.plugin_workers);
+            plugin_manager.set_app_version(semver::Version::parse(env!("CARGO_PKG_VERSION")).expect("Package version is valid semver"));
+            plugin_manager.set_clipboard(Arc::new(clipboard::SystemClipboard));
+            // Secrets go in the OS keychain; without one they fall back to a
+            // file whose key is stored right beside it
+            let secrets_dir = app_data_dir.join("secrets");
+            let file_secrets = secrets::FileSecretStore::new(secrets_dir.clone());
+            match keychain::KeyringSecretStore::open(&app.config().identifier) {
+                Ok(keyring) => {
+                    match keyring.import(&file_secrets) {
+                        Ok(0) => {}
+                        Ok(moved) => tracing::info!("Moved {} secrets from {:?} to the OS keychain", moved, secrets_dir),
+                        Err(e) => tracing::warn!("Failed to move secrets from {:?} to the OS keychain: {:#}", secrets_dir, e),
+                    }
+                    plugin_manager.set_secret_store(Arc::new(keyring));
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "{:#}; keeping secrets in {:?}, encrypted with a key stored next to them",
+                        e,
+                        secrets_dir
+                    );
+                    plugin_manager.set_secret_store(Arc::new(file_secrets));
+                }
+            }
+            let trusted_plugins: Vec<String> = settings.get_or_default(settings::TRUSTED_PLUGINS_KEY)
+                .expect("Failed to load trusted plugins");
+            plugin_manager.set_trusted_plugins(trusted_plugins);
+            let sandboxes: HashMap<String, SandboxProfile> = settings.get_or_default(settings::PLUGIN_SANDBOXES_KEY)
+                .expect("Failed to load plugin sandboxes");
+            plugin_manager.set_sandbox_overrides(sandboxes);
+            let disabled_plugins: Vec<String> = settings.get_or_default(settings::DISABLED_PLUGINS_KEY)
+                .expect("Failed to load disabled plugins");
+            plugin_manager.set_disabled_plugins(disabled_plugins.iter().cloned());
             
             // Discover and load plugins
             tauri::async_runtime::block_on(async {
@@ -55,36 +248,128 @@ pub fn run() {
             let tick_manager = tick_manager::TickManager::new(60); // 60 ticks per second
             tracing::info!("Tick manager initialized with 60 TPS");
 
+            let capability_approvals = plugin_manager.capability_approvals();
+            let trash = plugin_manager.trash().expect("Plugin manager has a database");
+            let plugin_manager = Arc::new(RwLock::new(plugin_manager));
+            let database = Arc::new(database);
+            let jobs = Arc::new(jobs::JobManager::new(
+                database.clone(),
+                plugin_manager.clone(),
+                Some(Arc::new(notifications::JobEventEmitter(app.handle().clone()))),
+                worker_counts.job_workers,
+            ).expect("Failed to create job manager"));
+            
+            // Register manifest schedules and start the scheduler
+            let scheduler = Arc::new(scheduler::Scheduler::new(database.clone(), jobs.clone()));
+            scheduler.set_paused_plugins(disabled_plugins);
+            let manifests = tauri::async_runtime::block_on(async {
+                plugin_manager.read().await.list_plugins().await
+            });
+            for manifest in &manifests {
+                if let Err(e) = scheduler.sync_manifest(manifest) {
+                    tracing::warn!("Failed to register schedules of {}: {:#}", manifest.id(), e);
+                }
+            }
+            let supervisor = supervisor::TaskSupervisor::new();
+            let scheduler_task = scheduler.clone();
+            supervisor.spawn("scheduler", move || scheduler_task.clone().run());
+            let lease_jobs = jobs.clone();
+            supervisor.spawn("job_leases", move || lease_jobs.clone().run_leases());
+            let prompt_approvals = capability_approvals.clone();
+            let prompt_app = app.handle().clone();
+            let purge_trash = trash.clone();
+            supervisor.spawn("trash_purge", move || purge_trash.clone().run());
+            supervisor.spawn("capability_requests", move || {
+                notifications::forward_capability_requests(prompt_approvals.clone(), prompt_app.clone())
+            });
+            let emitted_app = app.handle().clone();
+            supervisor.spawn("plugin_emitted_events", move || notifications::forward_plugin_events(emitted_app.clone()));
+            // Check installed plugins once after the app was updated
+            let compat_app = app.handle().clone();
+            let compat_settings = SettingsStore::new(database.clone());
+            let compat_manager = plugin_manager.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = updater::check_plugins_after_update(compat_app, compat_settings, compat_manager).await {
+                    tracing::warn!("Plugin compatibility check failed: {:#}", e);
+                }
+            });
+            let changes_manager = plugin_manager.clone();
+            let changes_app = app.handle().clone();
+            supervisor.spawn("setting_changes", move || {
+                notifications::forward_setting_changes(changes_manager.clone(), changes_app.clone())
+            });
+            let events_manager = plugin_manager.clone();
+            supervisor.spawn("plugin_events", move || events::route_events(events_manager.clone()));
+            let bus_manager = plugin_manager.clone();
+            supervisor.spawn("plugin_bus", move || bus::route_messages(bus_manager.clone()));
+            
+            // Periodically re-verify plugin modules
+            let verify_interval = settings.get(settings::PLUGIN_VERIFY_INTERVAL_KEY)
+                .ok()
+                .flatten()
+                .unwrap_or(settings::DEFAULT_PLUGIN_VERIFY_INTERVAL_SECS)
+                .max(1);
+            let verify_manager = plugin_manager.clone();
+            let verify_app = app.handle().clone();
+            supervisor.spawn("plugin_verification", move || {
+                verification::run(
+                    verify_manager.clone(),
+                    verify_app.clone(),
+                    std::time::Duration::from_secs(verify_interval),
+                )
+            });
+
             // Store in app state
             app.manage(AppState {
-                plugin_manager: Arc::new(RwLock::new(plugin_manager)),
-                database: Arc::new(database),
+                plugin_manager,
+                database,
                 tick_manager: Arc::new(RwLock::new(tick_manager)),
+                supervisor,
+                settings: Arc::new(settings),
+                jobs,
+                scheduler,
+                capability_approvals,
+                trash,
             });
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            list_plugins,
-            get_plugin_info,
-            execute_plugin,
-            install_plugin,
-            install_plugin_from_url,
-            discover_plugins,
-            db_test_connection,
-            db_get_schema_version,
-            tick_start,
-            tick_stop,
-            tick_get_status,
-            tick_get_current_tick,
-            tick_set_rate,
-            tick_register_session,
-            tick_unregister_session,
-            tick_add_client,
-            tick_remove_client,
-            tick_get_session_info,
-            tick_get_active_sessions,
-        ])
-        .run(tauri::generate_context!())
+        .invoke_handler(move |invoke| {
+            // Authorize every non-public command, every command of a signed-in
+            // caller who may have to change their password first, and audit
+            // sensitive ones before dispatching
+            let command = invoke.message.command();
+            let signed_in = auth::session_token(invoke.message.headers()).is_some();
+            if !auth::is_public(command) || auth::is_audited(command) || signed_in {
+                let webview = invoke.message.webview();
+                let state = webview.state::<AppState>();
+                let user = match auth::resolve_user(&state.database, invoke.message.headers()) {
+                    Ok(user) => user,
+                    Err(e) => {
+                        invoke.resolver.reject(format!("Failed to resolve session: {}", e));
+                        return true;
+                    }
+                };
+                let args = match invoke.message.payload() {
+                    tauri::ipc::InvokeBody::Json(args) => args.clone(),
+                    tauri::ipc::InvokeBody::Raw(_) => serde_json::Value::Null,
+                };
+                let authorized = auth::authorize(command, user.as_ref())
+                    .and_then(|()| auth::check_password_change(command, &args, user.as_ref()));
+                if auth::is_audited(command) {
+                    let outcome = if authorized.is_ok() { "invoked" } else { "denied" };
+                    if let Err(e) = auth::audit_invocation(&state.database, command, &args, user.as_ref(), outcome) {
+                        tracing::warn!("Failed to audit '{}': {}", command, e);
+                    }
+                }
+                if let Err(e) = authorized {
+                    tracing::warn!("Rejected '{}': {}", command, e);
+                    invoke.resolver.reject(e.to_string());
+                    return true;
+                }
+            }
+            handler(invoke)
+        })
+        .run(context)
         .expect("error while running tauri application");
 }
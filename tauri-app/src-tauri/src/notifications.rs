@@ -0,0 +1,138 @@
+//! User-facing notifications raised by the backend
+//!
+//! Notifications are emitted to the frontend as `notification` events and
+//! logged at a matching level. Plugin capability requests are emitted as
+//! `plugin-capability-request` events for the frontend to prompt on,
+//! setting writes as `setting:changed` events, job status changes as
+//! `job:<id>` events (plus `job-completed` once a job has finished), and
+//! events plugins emit as `plugin:<id>:<event>` events.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+use crate::host_functions::emit::{self, PluginEmittedEvent};
+use crate::jobs::{JobEvent, JobEventSink};
+use crate::plugins::{CapabilityApprovals, PluginManager};
+use crate::settings::SettingsStore;
+
+/// Event notifications are emitted on
+pub const NOTIFICATION_EVENT: &str = "notification";
+
+/// Event capability requests are emitted on
+pub const CAPABILITY_REQUEST_EVENT: &str = "plugin-capability-request";
+
+/// Event setting changes are emitted on
+pub const SETTING_CHANGED_EVENT: &str = "setting:changed";
+
+/// Event emitted once a job has completed, failed or been cancelled
+pub const JOB_COMPLETED_EVENT: &str = "job-completed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationLevel {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub title: String,
+    pub message: String,
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+}
+
+/// Log a notification and send it to the frontend
+pub fn notify(app: &AppHandle, level: NotificationLevel, title: &str, message: String) {
+    match level {
+        NotificationLevel::Warning => tracing::warn!("{}: {}", title, message),
+        NotificationLevel::Critical => tracing::error!("{}: {}", title, message),
+    }
+
+    let notification = Notification {
+        level,
+        title: title.to_string(),
+        message,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let _ = app.emit(NOTIFICATION_EVENT, notification);
+}
+
+/// Emit capability requests to the frontend as they are opened; run under the task supervisor
+pub async fn forward_capability_requests(approvals: Arc<CapabilityApprovals>, app: AppHandle) -> Result<(), String> {
+    let mut requests = approvals.subscribe();
+    loop {
+        match requests.recv().await {
+            Ok(request) => {
+                let _ = app.emit(CAPABILITY_REQUEST_EVENT, request);
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Missed {} capability requests; they remain listed as pending", skipped);
+            }
+            Err(RecvError::Closed) => return Err("Capability request channel closed".to_string()),
+        }
+    }
+}
+
+/// Emit setting changes to the frontend and to the plugins watching them; run
+/// under the task supervisor
+pub async fn forward_setting_changes(plugin_manager: Arc<RwLock<PluginManager>>, app: AppHandle) -> Result<(), String> {
+    let mut changes = SettingsStore::subscribe();
+    loop {
+        match changes.recv().await {
+            Ok(change) => {
+                let _ = app.emit(SETTING_CHANGED_EVENT, &change);
+                plugin_manager.read().await.dispatch_setting_change(&change).await;
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Missed {} setting changes", skipped);
+            }
+            Err(RecvError::Closed) => return Err("Setting change channel closed".to_string()),
+        }
+    }
+}
+
+/// Frontend event name for an event a plugin emitted; characters of the
+/// plugin's ID that event names can't contain become `_`
+pub fn plugin_event_name(event: &PluginEmittedEvent) -> String {
+    let plugin: String = event
+        .plugin
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/') { c } else { '_' })
+        .collect();
+    format!("plugin:{}:{}", plugin, event.event)
+}
+
+/// Emit the events plugins emit to the frontend; run under the task supervisor
+pub async fn forward_plugin_events(app: AppHandle) -> Result<(), String> {
+    let mut events = emit::subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if let Err(e) = app.emit(&plugin_event_name(&event), &event) {
+                    tracing::warn!("Failed to emit event '{}' of plugin '{}': {}", event.event, event.plugin, e);
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Dropped {} plugin events", skipped);
+            }
+            Err(RecvError::Closed) => return Err("Plugin event channel closed".to_string()),
+        }
+    }
+}
+
+/// Emits job status changes to the frontend
+pub struct JobEventEmitter(pub AppHandle);
+
+impl JobEventSink for JobEventEmitter {
+    fn job_event(&self, event: &JobEvent) {
+        let _ = self.0.emit(&format!("job:{}", event.job_id), event.clone());
+        if event.status.is_terminal() {
+            let _ = self.0.emit(JOB_COMPLETED_EVENT, event.clone());
+        }
+    }
+}
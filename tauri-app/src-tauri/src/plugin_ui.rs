@@ -0,0 +1,141 @@
+//! Protocols serving files bundled with plugins
+//!
+//! - `plugin-ui` serves UI panels from the plugin's UI assets directory. The
+//!   frontend embeds them in a sandboxed iframe without `allow-same-origin`,
+//!   so panels have no access to the Tauri IPC and talk to the host only
+//!   through the postMessage bridge, which limits them to calling their own
+//!   plugin.
+//! - `plugin-asset` serves the icon and screenshots declared in the manifest,
+//!   and nothing else.
+//!
+//! Files are addressed as `<scheme>://localhost/<plugin>/<path>`
+//! (`http://<scheme>.localhost/<plugin>/<path>` on Windows and Android).
+
+use std::path::Path;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::AppState;
+
+/// URI scheme plugin panels are served from
+pub const PLUGIN_UI_SCHEME: &str = "plugin-ui";
+
+/// URI scheme plugin icons and screenshots are served from
+pub const PLUGIN_ASSET_SCHEME: &str = "plugin-asset";
+
+/// Panels may only load their own assets and cannot make network requests
+const PANEL_CSP: &str = "default-src 'self' 'unsafe-inline'; img-src 'self' data:; connect-src 'none'";
+
+/// Images never need to run anything
+const ASSET_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'";
+
+/// URL the frontend can load a plugin file from
+pub fn file_url(scheme: &str, plugin_name: &str, path: &str) -> String {
+    let encode = |s: &str| {
+        percent_encoding::utf8_percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+    };
+    let path = path.split('/').map(encode).collect::<Vec<_>>().join("/");
+
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}/{}", scheme, encode(plugin_name), path)
+    } else {
+        format!("{}://localhost/{}/{}", scheme, encode(plugin_name), path)
+    }
+}
+
+/// Serve a request for a plugin UI panel file
+pub async fn handle_ui_request(app: &AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some((plugin_name, path)) = split_path(&request) else {
+        return error_response(StatusCode::NOT_FOUND);
+    };
+
+    let state = app.state::<AppState>();
+    let assets_dir = match state.plugin_manager.read().await.get_ui_assets_dir(&plugin_name).await {
+        Some(dir) => dir,
+        None => return error_response(StatusCode::NOT_FOUND),
+    };
+
+    serve_file(&assets_dir, &path, PANEL_CSP).await
+}
+
+/// Serve a request for a plugin icon or screenshot
+pub async fn handle_asset_request(app: &AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some((plugin_name, path)) = split_path(&request) else {
+        return error_response(StatusCode::NOT_FOUND);
+    };
+
+    let state = app.state::<AppState>();
+    let manager = state.plugin_manager.read().await;
+    let (Some(manifest), Some(plugin_dir)) = (
+        manager.get_plugin(&plugin_name).await,
+        manager.get_plugin_dir(&plugin_name).await,
+    ) else {
+        return error_response(StatusCode::NOT_FOUND);
+    };
+    drop(manager);
+
+    if !manifest.assets.paths().any(|declared| declared == path) {
+        return error_response(StatusCode::NOT_FOUND);
+    }
+
+    serve_file(&plugin_dir, &path, ASSET_CSP).await
+}
+
+/// Split a request path into plugin name and relative file path
+fn split_path(request: &Request<Vec<u8>>) -> Option<(String, String)> {
+    // Split before decoding: namespaced plugin IDs contain an encoded '/'
+    let (plugin_name, file_path) = request.uri().path().trim_start_matches('/').split_once('/')?;
+    let decode = |s: &str| percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned();
+    Some((decode(plugin_name), decode(file_path)))
+}
+
+/// Serve a file, refusing anything outside `root`
+async fn serve_file(root: &Path, path: &str, csp: &str) -> Response<Vec<u8>> {
+    if !crate::plugins::is_relative_subpath(path) {
+        return error_response(StatusCode::FORBIDDEN);
+    }
+
+    // Resolve symlinks before checking the file is inside the root
+    let file = match (root.canonicalize(), root.join(path).canonicalize()) {
+        (Ok(root), Ok(file)) if file.starts_with(&root) && file.is_file() => file,
+        _ => return error_response(StatusCode::NOT_FOUND),
+    };
+
+    match tokio::fs::read(&file).await {
+        Ok(body) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, content_type(&file))
+            .header(header::CONTENT_SECURITY_POLICY, csp)
+            .header("X-Content-Type-Options", "nosniff")
+            .body(body)
+            .unwrap_or_else(|_| error_response(StatusCode::INTERNAL_SERVER_ERROR)),
+        Err(e) => {
+            tracing::warn!("Failed to read plugin file {:?}: {}", file, e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn error_response(status: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(status.canonical_reason().unwrap_or_default().as_bytes().to_vec());
+    *response.status_mut() = status;
+    response
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or_default() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
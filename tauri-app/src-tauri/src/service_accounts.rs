@@ -0,0 +1,147 @@
+//! Service accounts for automation
+//!
+//! A service account is a user without a password that can't open sessions;
+//! it authenticates only with an API key sent as a bearer token. Keys look
+//! like `a2e_<id>_<secret>`; only a SHA-256 of the secret is stored, and the
+//! full key is shown once when it is created or rotated.
+
+use anyhow::{Context, Result};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::ROLE_ADMIN;
+use crate::db::schema::ServiceAccount;
+use crate::db::{operations, Database};
+use crate::ids::{self, IdKind};
+
+/// Prefix that marks a bearer token as an API key
+pub const API_KEY_PREFIX: &str = "a2e_";
+
+/// Length of the random key ID
+const KEY_ID_LEN: usize = 12;
+
+/// Length of the random key secret
+const KEY_SECRET_LEN: usize = 40;
+
+/// Domain of the placeholder emails service accounts get (reserved, never deliverable)
+const SERVICE_EMAIL_DOMAIN: &str = "service-accounts.invalid";
+
+/// A service account together with a freshly issued key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedKey {
+    pub account: ServiceAccount,
+    /// Full API key; it cannot be retrieved again
+    pub api_key: String,
+}
+
+/// Create a service account with the given roles and issue its first key
+pub fn create(
+    database: &Database,
+    name: &str,
+    description: Option<&str>,
+    roles: &[String],
+    created_by: Option<&str>,
+) -> Result<IssuedKey> {
+    check_roles(roles)?;
+    let uuid = ids::new_id(IdKind::User);
+    let email = format!("{}@{}", name, SERVICE_EMAIL_DOMAIN);
+    let now = chrono::Utc::now().timestamp();
+    let (key_id, api_key, key_hash) = generate_key();
+
+    database.with_connection(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        operations::create_service_account(&tx, &uuid, name, &email, description, created_by, now)?;
+        for role in roles {
+            operations::grant_user_role(&tx, &uuid, role, now)?;
+        }
+        operations::create_api_key(&tx, &key_id, &uuid, &key_hash, now)?;
+        tx.commit()
+    })?;
+
+    tracing::info!("Created service account '{}' ({})", name, uuid);
+    Ok(IssuedKey {
+        account: get(database, &uuid)?,
+        api_key,
+    })
+}
+
+/// Revoke a service account's keys and issue a new one
+pub fn rotate_key(database: &Database, uuid: &str) -> Result<IssuedKey> {
+    get(database, uuid)?;
+    let (key_id, api_key, key_hash) = generate_key();
+    let now = chrono::Utc::now().timestamp();
+
+    let revoked = database.with_connection(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        let revoked = operations::revoke_api_keys(&tx, uuid, now)?;
+        operations::create_api_key(&tx, &key_id, uuid, &key_hash, now)?;
+        tx.commit()?;
+        Ok(revoked)
+    })?;
+
+    tracing::info!("Rotated API key of service account {} ({} revoked)", uuid, revoked);
+    Ok(IssuedKey {
+        account: get(database, uuid)?,
+        api_key,
+    })
+}
+
+/// Get a service account, failing if it doesn't exist
+pub fn get(database: &Database, uuid: &str) -> Result<ServiceAccount> {
+    database
+        .with_connection(|conn| operations::get_service_account(conn, uuid))?
+        .with_context(|| format!("Service account not found: {}", uuid))
+}
+
+/// Service accounts can't hold the admin role
+pub fn check_roles(roles: &[String]) -> Result<()> {
+    if roles.iter().any(|role| role == ROLE_ADMIN) {
+        anyhow::bail!("Service accounts cannot hold the '{}' role", ROLE_ADMIN);
+    }
+    Ok(())
+}
+
+/// Resolve an API key to its (service account UUID, key ID)
+///
+/// Returns None for unknown, revoked or mismatched keys and for disabled
+/// accounts.
+pub fn authenticate(database: &Database, api_key: &str) -> Result<Option<(String, String)>> {
+    let Some((key_id, secret)) = api_key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+    else {
+        return Ok(None);
+    };
+
+    let authenticated = database.with_connection(|conn| {
+        let Some(key) = operations::get_api_key(conn, key_id)? else {
+            return Ok(None);
+        };
+        if key.revoked_at.is_some() || key.key_hash != hash_secret(secret) {
+            return Ok(None);
+        }
+        match operations::get_service_account(conn, &key.user_uuid)? {
+            Some(account) if !account.disabled => {
+                operations::touch_api_key(conn, &key.id, chrono::Utc::now().timestamp())?;
+                Ok(Some((key.user_uuid, key.id)))
+            }
+            _ => Ok(None),
+        }
+    })?;
+    Ok(authenticated)
+}
+
+/// New key as (key ID, full key, secret hash)
+fn generate_key() -> (String, String, String) {
+    let mut rng = rand::thread_rng();
+    let key_id = Alphanumeric.sample_string(&mut rng, KEY_ID_LEN).to_ascii_lowercase();
+    let secret = Alphanumeric.sample_string(&mut rng, KEY_SECRET_LEN);
+    let api_key = format!("{}{}_{}", API_KEY_PREFIX, key_id, secret);
+    (key_id, api_key, hash_secret(&secret))
+}
+
+/// Keys are long random strings, so a plain SHA-256 is enough to store them
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
@@ -0,0 +1,170 @@
+//! Supervisor for long-running background tasks
+//!
+//! Every background loop (tick loop, scheduler, job workers, ...) is spawned
+//! through the supervisor, which restarts it with exponential backoff when it
+//! fails or panics and keeps track of its state for `get_background_tasks`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+
+/// Initial delay before restarting a failed task
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound for the restart delay
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Lifecycle state of a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    Restarting,
+    Completed,
+    Stopped,
+}
+
+/// Status information about a supervised task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub name: String,
+    pub state: TaskState,
+    pub restart_count: u32,
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds) of the most recent (re)start
+    pub started_at: i64,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Owns background tasks and restarts them when they crash
+#[derive(Clone, Default)]
+pub struct TaskSupervisor {
+    tasks: Arc<Mutex<HashMap<String, TaskEntry>>>,
+}
+
+impl TaskSupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a supervised task
+    ///
+    /// `factory` is called to create a fresh future for every (re)start. A task
+    /// that returns `Ok(())` is considered completed and is not restarted; an
+    /// `Err` or a panic triggers a restart after an exponential backoff.
+    /// Spawning a task with the name of an existing task replaces it.
+    pub fn spawn<F, Fut>(&self, name: &str, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.stop(name);
+
+        self.tasks.lock().unwrap().insert(
+            name.to_string(),
+            TaskEntry {
+                info: TaskInfo {
+                    name: name.to_string(),
+                    state: TaskState::Running,
+                    restart_count: 0,
+                    last_error: None,
+                    started_at: chrono::Utc::now().timestamp(),
+                },
+                handle: None,
+            },
+        );
+
+        let tasks = self.tasks.clone();
+        let task_name = name.to_string();
+
+        let handle = tauri::async_runtime::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                // Run each attempt in its own task so panics are caught; the
+                // guard aborts it if the supervising task itself is aborted.
+                let attempt = tokio::spawn(factory());
+                let _guard = AbortOnDrop(attempt.abort_handle());
+                let outcome = attempt.await;
+
+                let error = match outcome {
+                    Ok(Ok(())) => {
+                        update(&tasks, &task_name, |info| info.state = TaskState::Completed);
+                        tracing::info!("Background task '{}' completed", task_name);
+                        return;
+                    }
+                    Ok(Err(e)) => e,
+                    Err(e) if e.is_panic() => format!("Task panicked: {}", e),
+                    Err(_) => return,
+                };
+
+                tracing::error!(
+                    "Background task '{}' failed: {} (restarting in {:?})",
+                    task_name,
+                    error,
+                    backoff
+                );
+                update(&tasks, &task_name, |info| {
+                    info.state = TaskState::Restarting;
+                    info.restart_count += 1;
+                    info.last_error = Some(error.clone());
+                });
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+
+                update(&tasks, &task_name, |info| {
+                    info.state = TaskState::Running;
+                    info.started_at = chrono::Utc::now().timestamp();
+                });
+            }
+        });
+
+        if let Some(entry) = self.tasks.lock().unwrap().get_mut(name) {
+            entry.handle = Some(handle);
+        }
+    }
+
+    /// Abort a supervised task if it is still running
+    pub fn stop(&self, name: &str) {
+        let mut tasks = self.tasks.lock().unwrap();
+        if let Some(entry) = tasks.get_mut(name) {
+            if let Some(handle) = entry.handle.take() {
+                handle.abort();
+            }
+            if entry.info.state != TaskState::Completed {
+                entry.info.state = TaskState::Stopped;
+            }
+        }
+    }
+
+    /// List all supervised tasks
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let tasks = self.tasks.lock().unwrap();
+        let mut infos: Vec<TaskInfo> = tasks.values().map(|entry| entry.info.clone()).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+}
+
+/// Aborts the wrapped task when dropped
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn update(tasks: &Mutex<HashMap<String, TaskEntry>>, name: &str, f: impl FnOnce(&mut TaskInfo)) {
+    if let Some(entry) = tasks.lock().unwrap().get_mut(name) {
+        f(&mut entry.info);
+    }
+}
@@ -6,6 +6,8 @@ use tokio::sync::RwLock;
 use tokio::time;
 use tauri::{AppHandle, Emitter};
 
+use crate::events;
+
 /// Tick event data sent to clients
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TickEvent {
@@ -264,6 +266,10 @@ pub async fn start_tick_loop(
 
         // Emit global tick event
         let _ = app_handle.emit("tick", &tick_event);
+        // ...and route it to plugins subscribed to ticks
+        if let Ok(payload) = serde_json::to_value(&tick_event) {
+            events::publish(events::TICK, None, payload);
+        }
 
         // Emit session-specific tick events
         for session_event in session_events {
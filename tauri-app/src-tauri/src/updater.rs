@@ -0,0 +1,201 @@
+//! In-app updates of the host application
+//!
+//! Updates come from the Tauri updater. Each channel has its own release feed
+//! (`updater-<channel>/latest.json` on the project's releases), and the
+//! selected channel is stored in settings. The database is backed up into
+//! `<app data>/backups` before an update is installed, since the new version
+//! may migrate it. After an update, installed plugins are checked against
+//! the new version.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tokio::sync::RwLock;
+
+use crate::db::Database;
+use crate::notifications::{notify, NotificationLevel};
+use crate::plugins::PluginManager;
+use crate::settings::{SettingsStore, LAST_APP_VERSION_KEY};
+
+/// Base URL of the per-channel release feeds
+const RELEASES_URL: &str = "https://github.com/mikezamora/anything-to-everything/releases/download";
+
+/// Event download progress is emitted on while an update is installed
+pub const UPDATE_PROGRESS_EVENT: &str = "app-update-progress";
+
+/// Release channel the application updates from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl UpdateChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+
+    fn endpoint(&self) -> Result<tauri::Url> {
+        let url = format!("{}/updater-{}/latest.json", RELEASES_URL, self.as_str());
+        url.parse().with_context(|| format!("Invalid update endpoint {}", url))
+    }
+}
+
+/// An available update of the application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppUpdateInfo {
+    pub channel: UpdateChannel,
+    pub current_version: String,
+    pub version: String,
+    /// Release notes
+    pub notes: Option<String>,
+    /// Publish date (RFC 3339)
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateProgressEvent {
+    pub version: String,
+    pub downloaded: u64,
+    /// Total download size, if the server reports it
+    pub total: Option<u64>,
+}
+
+/// Check the channel's feed for a newer version
+pub async fn check(app: &AppHandle, channel: UpdateChannel) -> Result<Option<AppUpdateInfo>> {
+    Ok(find_update(app, channel)
+        .await?
+        .map(|update| update_info(&update, channel)))
+}
+
+/// Back up the database, then download and install the channel's latest version
+///
+/// Returns the installed update and the backup's path; the application must be
+/// restarted to run the new version.
+pub async fn apply(
+    app: &AppHandle,
+    database: &Database,
+    channel: UpdateChannel,
+) -> Result<(AppUpdateInfo, PathBuf)> {
+    let update = find_update(app, channel)
+        .await?
+        .context("No update available")?;
+    let info = update_info(&update, channel);
+
+    let backup = backup_database(app, database, &info.current_version)?;
+    tracing::info!("Backed up database to {:?} before updating to {}", backup, info.version);
+
+    let mut downloaded = 0u64;
+    let progress_app = app.clone();
+    let version = info.version.clone();
+    update
+        .download_and_install(
+            move |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = progress_app.emit(
+                    UPDATE_PROGRESS_EVENT,
+                    UpdateProgressEvent {
+                        version: version.clone(),
+                        downloaded,
+                        total,
+                    },
+                );
+            },
+            || {},
+        )
+        .await
+        .context("Failed to install update")?;
+
+    tracing::info!("Installed update {} from the {} channel", info.version, channel.as_str());
+    Ok((info, backup))
+}
+
+async fn find_update(app: &AppHandle, channel: UpdateChannel) -> Result<Option<Update>> {
+    let update = app
+        .updater_builder()
+        .endpoints(vec![channel.endpoint()?])?
+        .build()?
+        .check()
+        .await
+        .with_context(|| format!("Failed to check the {} channel for updates", channel.as_str()))?;
+    Ok(update)
+}
+
+fn update_info(update: &Update, channel: UpdateChannel) -> AppUpdateInfo {
+    AppUpdateInfo {
+        channel,
+        current_version: update.current_version.clone(),
+        version: update.version.clone(),
+        notes: update.body.clone(),
+        date: update
+            .date
+            .and_then(|date| chrono::DateTime::from_timestamp(date.unix_timestamp(), 0))
+            .map(|date| date.to_rfc3339()),
+    }
+}
+
+/// Copy the database to `<app data>/backups/app-<version>-<timestamp>.db`
+fn backup_database(app: &AppHandle, database: &Database, version: &str) -> Result<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .context("Failed to get app data directory")?
+        .join("backups");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+    let path = dir.join(format!("app-{}-{}.db", version, chrono::Utc::now().timestamp()));
+    database
+        .backup_to(&path)
+        .with_context(|| format!("Failed to back up database to {:?}", path))?;
+    Ok(path)
+}
+
+/// Check installed plugins if this is the first start of a new app version,
+/// raising a warning notification when some need updates
+pub async fn check_plugins_after_update(
+    app: AppHandle,
+    settings: SettingsStore,
+    plugin_manager: Arc<RwLock<PluginManager>>,
+) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let previous: Option<String> = settings.get(LAST_APP_VERSION_KEY)?;
+    if previous.as_deref() == Some(current) {
+        return Ok(());
+    }
+    settings.set(LAST_APP_VERSION_KEY, &current)?;
+    // A fresh install has no plugins to check
+    let Some(previous) = previous else {
+        return Ok(());
+    };
+
+    let report = plugin_manager.read().await.compatibility_report()?;
+    tracing::info!(
+        "Checked {} plugins after update from {} to {}: {} need updates",
+        report.checked,
+        previous,
+        current,
+        report.incompatible.len()
+    );
+    if !report.incompatible.is_empty() {
+        let plugins: Vec<&str> = report.incompatible.iter().map(|p| p.plugin.as_str()).collect();
+        notify(
+            &app,
+            NotificationLevel::Warning,
+            "Plugins need updates",
+            format!(
+                "After updating to {}, these plugins are not compatible: {}. See the plugin compatibility report for details.",
+                current,
+                plugins.join(", ")
+            ),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,456 @@
+//! Bulk user import from CSV files
+//!
+//! The file needs a header row with `name` and `email` columns; other columns
+//! are ignored. [`preview`] reports every problem without touching the
+//! database, and [`commit`] creates the accounts in a single transaction with
+//! random temporary passwords that must be changed at first sign-in.
+
+use anyhow::{Context, Result};
+use argon2::password_hash::{PasswordHasher, SaltString};
+use argon2::Argon2;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::db::{operations, Database};
+use crate::events;
+use crate::ids::{self, IdKind};
+
+/// Length of generated temporary passwords
+const TEMP_PASSWORD_LEN: usize = 16;
+
+/// A data row of the import file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRow {
+    /// 1-based line number in the file
+    pub line: usize,
+    pub name: String,
+    pub email: String,
+}
+
+/// A problem with one row of the import file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportIssue {
+    pub line: usize,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub rows: Vec<ImportRow>,
+    pub issues: Vec<ImportIssue>,
+    /// Rows without issues, which an import would create
+    pub valid_rows: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportOptions {
+    /// Import the valid rows even if others have issues
+    #[serde(default)]
+    pub skip_invalid: bool,
+}
+
+/// An account created by an import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedUser {
+    pub uuid: String,
+    pub name: String,
+    pub email: String,
+    /// Shown once; the user must change it at first sign-in
+    pub temp_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub created: Vec<ImportedUser>,
+    pub skipped: Vec<ImportIssue>,
+}
+
+/// Validate an import file without creating any accounts
+pub fn preview(database: &Database, path: &Path) -> Result<ImportPreview> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read import file {:?}", path))?;
+    let (rows, mut issues) = parse_rows(&content)?;
+
+    // Duplicates within the file; the first occurrence wins. Missing fields
+    // are already reported, so they don't count as duplicates of each other.
+    let mut seen_emails: HashMap<String, usize> = HashMap::new();
+    let mut seen_names: HashMap<String, usize> = HashMap::new();
+    for row in &rows {
+        if !row.email.is_empty() {
+            let first = *seen_emails.entry(row.email.to_ascii_lowercase()).or_insert(row.line);
+            if first != row.line {
+                issues.push(issue(row.line, "email", format!("Duplicate of line {}", first)));
+            }
+        }
+        if !row.name.is_empty() {
+            let first = *seen_names.entry(row.name.clone()).or_insert(row.line);
+            if first != row.line {
+                issues.push(issue(row.line, "name", format!("Duplicate of line {}", first)));
+            }
+        }
+    }
+
+    // Accounts that already exist
+    database.with_connection(|conn| {
+        for row in &rows {
+            if !row.email.is_empty() && operations::get_user_by_email(conn, &row.email)?.is_some() {
+                issues.push(issue(row.line, "email", "An account with this email already exists".to_string()));
+            }
+            if !row.name.is_empty() && operations::get_user_by_name(conn, &row.name)?.is_some() {
+                issues.push(issue(row.line, "name", "An account with this name already exists".to_string()));
+            }
+        }
+        Ok(())
+    })?;
+
+    issues.sort_by_key(|issue| issue.line);
+    let valid_rows = rows
+        .iter()
+        .filter(|row| !issues.iter().any(|issue| issue.line == row.line))
+        .count();
+
+    Ok(ImportPreview {
+        rows,
+        issues,
+        valid_rows,
+    })
+}
+
+/// Create the accounts listed in an import file
+///
+/// Fails without creating anything if any row has issues, unless
+/// `skip_invalid` is set.
+pub fn commit(database: &Database, path: &Path, options: &ImportOptions) -> Result<ImportResult> {
+    let preview = preview(database, path)?;
+    if !preview.issues.is_empty() && !options.skip_invalid {
+        anyhow::bail!(
+            "Import file has {} issue(s); fix them or skip invalid rows",
+            preview.issues.len()
+        );
+    }
+
+    let argon2 = Argon2::default();
+    let mut created = Vec::new();
+    for row in &preview.rows {
+        if preview.issues.iter().any(|issue| issue.line == row.line) {
+            continue;
+        }
+        let temp_password = Alphanumeric.sample_string(&mut rand::thread_rng(), TEMP_PASSWORD_LEN);
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!("Salt encoding error: {}", e))?;
+        let password_hash = argon2
+            .hash_password(temp_password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("Password hashing failed: {}", e))?
+            .to_string();
+
+        created.push((
+            ImportedUser {
+                uuid: ids::new_id(IdKind::User),
+                name: row.name.clone(),
+                email: row.email.clone(),
+                temp_password,
+            },
+            password_hash,
+        ));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    database.with_connection(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        for (user, password_hash) in &created {
+            operations::create_user(&tx, &user.uuid, &user.name, &user.email, password_hash, now)?;
+            operations::set_password_reset_required(&tx, &user.uuid, true)?;
+        }
+        tx.commit()
+    })?;
+
+    tracing::info!("Imported {} users from {:?}", created.len(), path);
+    for (user, _) in &created {
+        events::publish(
+            events::USER_CREATED,
+            None,
+            serde_json::json!({ "uuid": user.uuid, "name": user.name, "email": user.email }),
+        );
+    }
+    Ok(ImportResult {
+        created: created.into_iter().map(|(user, _)| user).collect(),
+        skipped: preview.issues,
+    })
+}
+
+fn issue(line: usize, field: &str, message: String) -> ImportIssue {
+    ImportIssue {
+        line,
+        field: Some(field.to_string()),
+        message,
+    }
+}
+
+/// Parse the file into rows, reporting rows with missing or malformed fields
+fn parse_rows(content: &str) -> Result<(Vec<ImportRow>, Vec<ImportIssue>)> {
+    let mut records = parse_csv(content).into_iter();
+    let (_, header) = records.next().context("Import file is empty")?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .with_context(|| format!("Import file has no '{}' column", name))
+    };
+    let name_col = column("name")?;
+    let email_col = column("email")?;
+
+    let mut rows = Vec::new();
+    let mut issues = Vec::new();
+    for (line, record) in records {
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |col: usize| record.get(col).map(|f| f.trim().to_string()).unwrap_or_default();
+        let row = ImportRow {
+            line,
+            name: field(name_col),
+            email: field(email_col),
+        };
+
+        if record.len() != header.len() {
+            issues.push(ImportIssue {
+                line,
+                field: None,
+                message: format!("Expected {} columns, found {}", header.len(), record.len()),
+            });
+        }
+        if row.name.is_empty() {
+            issues.push(issue(line, "name", "Name is required".to_string()));
+        }
+        if row.email.is_empty() {
+            issues.push(issue(line, "email", "Email is required".to_string()));
+        } else if !is_valid_email(&row.email) {
+            issues.push(issue(line, "email", format!("Invalid email address: {}", row.email)));
+        }
+        rows.push(row);
+    }
+
+    Ok((rows, issues))
+}
+
+/// Basic shape check: one `@`, a non-empty local part and a dotted domain
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(char::is_whitespace)
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+/// Split CSV text into records, each tagged with the line it starts on
+///
+/// Supports quoted fields containing commas, newlines and doubled quotes.
+fn parse_csv(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' => {
+                line += 1;
+                if in_quotes {
+                    field.push(c);
+                } else {
+                    record.push(std::mem::take(&mut field));
+                    records.push((record_line, std::mem::take(&mut record)));
+                    record_line = line;
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+    use argon2::password_hash::{PasswordHash, PasswordVerifier};
+    use std::path::PathBuf;
+
+    fn database() -> Database {
+        let database = Database::new(PathBuf::from(":memory:")).unwrap();
+        database
+            .with_connection(|conn| Ok(crate::db::migrations::run_migrations(conn)))
+            .unwrap()
+            .unwrap();
+        database
+    }
+
+    /// A temporary import file, removed when dropped
+    struct ImportFile(PathBuf);
+
+    impl std::ops::Deref for ImportFile {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for ImportFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn import_file(content: &str) -> ImportFile {
+        let path = std::env::temp_dir().join(format!("a2e-import-{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        ImportFile(path)
+    }
+
+    fn messages(issues: &[ImportIssue], line: usize) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|issue| issue.line == line)
+            .map(|issue| issue.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_preview_reads_rows_without_creating_accounts() {
+        let database = database();
+        // Columns are found by header, whatever their order; blank lines are skipped
+        let path = import_file("Email,Team,Name\nada@example.com,core,Ada\n\n\"grace@example.com\",navy,\"Hopper, Grace\"\n");
+
+        let preview = preview(&database, &path).unwrap();
+        assert!(preview.issues.is_empty(), "{:?}", preview.issues);
+        assert_eq!(preview.valid_rows, 2);
+        let rows: Vec<_> = preview.rows.iter().map(|row| (row.line, row.name.as_str(), row.email.as_str())).collect();
+        assert_eq!(rows, [(2, "Ada", "ada@example.com"), (4, "Hopper, Grace", "grace@example.com")]);
+        let created = database.with_connection(|conn| operations::get_user_by_email(conn, "ada@example.com")).unwrap();
+        assert!(created.is_none());
+    }
+
+    #[test]
+    fn test_preview_reports_bad_rows() {
+        let database = database();
+        let path = import_file("name,email\n,nameless@example.com\nNoMail,\nBadMail,not-an-email\nShort\nGood,good@example.com\n");
+
+        let preview = preview(&database, &path).unwrap();
+        assert_eq!(messages(&preview.issues, 2), ["Name is required"]);
+        assert_eq!(messages(&preview.issues, 3), ["Email is required"]);
+        assert_eq!(messages(&preview.issues, 4), ["Invalid email address: not-an-email"]);
+        assert_eq!(messages(&preview.issues, 5), ["Expected 2 columns, found 1", "Email is required"]);
+        assert!(messages(&preview.issues, 6).is_empty());
+        assert_eq!(preview.valid_rows, 1);
+
+        let error = super::preview(&database, &import_file("name,mail\nAda,ada@example.com\n")).unwrap_err();
+        assert_eq!(error.to_string(), "Import file has no 'email' column");
+        assert!(super::preview(&database, &import_file("")).is_err());
+    }
+
+    #[test]
+    fn test_preview_reports_duplicates() {
+        let database = database();
+        database
+            .with_connection(|conn| operations::create_user(conn, "existing", "Taken", "taken@example.com", "hash", 0))
+            .unwrap();
+        let path = import_file("name,email\nAda,ada@example.com\nAda,ADA@example.com\nTaken,new@example.com\nNew,taken@example.com\n");
+
+        let preview = preview(&database, &path).unwrap();
+        assert!(messages(&preview.issues, 2).is_empty());
+        assert_eq!(messages(&preview.issues, 3), ["Duplicate of line 2", "Duplicate of line 2"]);
+        assert_eq!(messages(&preview.issues, 4), ["An account with this name already exists"]);
+        assert_eq!(messages(&preview.issues, 5), ["An account with this email already exists"]);
+        assert_eq!(preview.valid_rows, 1);
+    }
+
+    #[test]
+    fn test_commit_creates_accounts_with_temporary_passwords() {
+        let database = database();
+        let path = import_file("name,email\nAda,ada@example.com\nGrace,grace@example.com\n");
+
+        let result = commit(&database, &path, &ImportOptions::default()).unwrap();
+        assert!(result.skipped.is_empty());
+        assert_eq!(result.created.len(), 2);
+        for imported in &result.created {
+            let user = database
+                .with_connection(|conn| operations::get_user_by_email(conn, &imported.email))
+                .unwrap()
+                .unwrap();
+            assert_eq!(user.uuid, imported.uuid);
+            assert!(user.password_reset_required);
+            assert_eq!(imported.temp_password.len(), TEMP_PASSWORD_LEN);
+            let hash = PasswordHash::new(&user.password_hash).unwrap();
+            assert!(Argon2::default().verify_password(imported.temp_password.as_bytes(), &hash).is_ok());
+        }
+
+        // Importing the same file again only finds duplicates
+        let error = commit(&database, &path, &ImportOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("4 issue(s)"), "{}", error);
+    }
+
+    #[test]
+    fn test_imported_users_must_change_their_password_first() {
+        let database = database();
+        let path = import_file("name,email\nAda,ada@example.com\n");
+        let ada = commit(&database, &path, &ImportOptions::default()).unwrap().created.remove(0);
+        let now = chrono::Utc::now().timestamp();
+        database
+            .with_connection(|conn| operations::create_session(conn, "session-ada", &ada.uuid, now, now + 3600))
+            .unwrap();
+        let mut headers = tauri::http::HeaderMap::new();
+        headers.insert("authorization", "Bearer session-ada".parse().unwrap());
+
+        let user = auth::resolve_user(&database, &headers).unwrap().unwrap();
+        assert!(user.password_reset_required);
+        let error = auth::check_password_change("list_plugins", &serde_json::Value::Null, Some(&user)).unwrap_err();
+        assert!(error.to_string().starts_with("Password change required"), "{}", error);
+
+        database
+            .with_connection(|conn| operations::update_user_password(conn, &ada.uuid, "new-hash", now))
+            .unwrap();
+        let user = auth::resolve_user(&database, &headers).unwrap().unwrap();
+        assert!(!user.password_reset_required);
+        assert!(auth::check_password_change("list_plugins", &serde_json::Value::Null, Some(&user)).is_ok());
+    }
+
+    #[test]
+    fn test_commit_refuses_bad_files_unless_skipping_invalid_rows() {
+        let database = database();
+        let path = import_file("name,email\nAda,ada@example.com\nBad,nope\n");
+
+        assert!(commit(&database, &path, &ImportOptions::default()).is_err());
+        let ada = database.with_connection(|conn| operations::get_user_by_email(conn, "ada@example.com")).unwrap();
+        assert!(ada.is_none(), "a refused import must not create anyone");
+
+        let result = commit(&database, &path, &ImportOptions { skip_invalid: true }).unwrap();
+        let created: Vec<_> = result.created.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(created, ["Ada"]);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].line, 3);
+    }
+}
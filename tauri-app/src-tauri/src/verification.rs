@@ -0,0 +1,79 @@
+//! Periodic re-verification of installed plugins' files
+
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+
+use crate::notifications::{notify, NotificationLevel};
+use crate::plugins::{IntegrityViolation, PluginManager};
+use crate::settings::{SettingsStore, PLUGIN_VERIFY_INTERVAL_KEY};
+
+/// Verify all loaded plugins, raising a critical notification for each one
+/// that was disabled
+pub async fn verify_and_notify(
+    plugin_manager: &RwLock<PluginManager>,
+    app: &AppHandle,
+) -> anyhow::Result<Vec<IntegrityViolation>> {
+    let violations = plugin_manager.read().await.verify_plugins().await?;
+
+    for violation in &violations {
+        let found = violation.actual_sha256.as_deref().unwrap_or("unreadable files");
+        notify(
+            app,
+            NotificationLevel::Critical,
+            "Plugin disabled",
+            format!(
+                "The files of '{}' changed outside the installer (expected sha256 {}, found {}). \
+                 The plugin has been disabled; reinstall it to re-enable it.",
+                violation.plugin, violation.expected_sha256, found
+            ),
+        );
+    }
+
+    Ok(violations)
+}
+
+/// Verification loop, run under the task supervisor; a new interval setting
+/// takes effect without a restart
+pub async fn run(
+    plugin_manager: Arc<RwLock<PluginManager>>,
+    app: AppHandle,
+    interval: Duration,
+) -> Result<(), String> {
+    let mut changes = SettingsStore::subscribe();
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick fires immediately; plugins were just verified on load
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            change = changes.recv() => {
+                match change {
+                    Ok(change) if change.key == PLUGIN_VERIFY_INTERVAL_KEY => {
+                        match serde_json::from_value::<u64>(change.value) {
+                            Ok(secs) => {
+                                let interval = Duration::from_secs(secs.max(1));
+                                ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+                            }
+                            Err(e) => tracing::warn!("Ignoring invalid plugin verification interval: {}", e),
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return Err("Setting change channel closed".to_string()),
+                }
+                continue;
+            }
+        }
+        if let Err(e) = verify_and_notify(&plugin_manager, &app).await {
+            notify(
+                &app,
+                NotificationLevel::Warning,
+                "Plugin verification failed",
+                format!("{:#}", e),
+            );
+        }
+    }
+}
@@ -123,8 +123,8 @@ function App() {
         {!loading && plugins.length > 0 && (
           <ul>
             {plugins.map((plugin) => (
-              <li key={plugin.name}>
-                <strong>{plugin.name}</strong> v{plugin.version} - {plugin.description}
+              <li key={plugin.id}>
+                <strong>{plugin.id}</strong> v{plugin.version} - {plugin.description}
               </li>
             ))}
           </ul>
@@ -149,8 +149,8 @@ function App() {
             >
               <option value="">Select a plugin...</option>
               {plugins.map((plugin) => (
-                <option key={plugin.name} value={plugin.name}>
-                  {plugin.name}
+                <option key={plugin.id} value={plugin.id}>
+                  {plugin.id}
                 </option>
               ))}
             </select>
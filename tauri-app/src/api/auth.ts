@@ -6,10 +6,13 @@ import type {
   VerifyEmailInput,
   RequestPasswordResetInput,
   ResetPasswordInput,
+  ChangePasswordInput,
   User,
   Session,
 } from './types';
+import { invoke } from '@tauri-apps/api/core';
 import { executePlugin } from './plugins';
+import { sessionOptions } from './session';
 
 /**
  * Execute an auth plugin function via Tauri command
@@ -86,3 +89,24 @@ export async function requestPasswordReset(data: RequestPasswordResetInput): Pro
 export async function resetPassword(data: ResetPasswordInput): Promise<void> {
   return executeAuthPlugin<void>('reset_password', data);
 }
+
+/**
+ * Change the signed-in user's password, e.g. the temporary one they were
+ * imported with; until then the app refuses everything else
+ */
+export async function changePassword(data: ChangePasswordInput): Promise<void> {
+  return executeAuthPlugin<void>('change_password', {
+    session_id: data.sessionId,
+    current_password: data.currentPassword,
+    new_password: data.newPassword,
+  });
+}
+
+/**
+ * First-run setup: make the signed-in user the admin if there is none yet
+ *
+ * Resolves to the user's roles.
+ */
+export async function claimAdmin(): Promise<string[]> {
+  return await invoke<string[]>('claim_admin', {}, sessionOptions());
+}
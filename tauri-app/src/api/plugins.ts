@@ -3,35 +3,91 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
-import type { PluginInfo, ExecuteResponse } from "../types/plugin";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { sessionOptions } from "./session";
+import type {
+  PluginInfo,
+  PluginQuery,
+  PluginSearchResults,
+  PluginAssetUrls,
+  UiContributionInfo,
+  ExecuteResponse,
+  DependencyGraph,
+  CapabilityRequest,
+  PluginEmittedEvent,
+  CapabilityDecisions,
+  SensitiveCapability,
+  PluginUpdate,
+  CompatibilityReport,
+  PluginProfile,
+  PluginProfiles,
+  PluginSetChange,
+  PluginCanary,
+} from "../types/plugin";
 
 /**
- * List all available plugins
+ * List all available plugins, or those matching a query, sorted by ID
  */
-export async function listPlugins(): Promise<PluginInfo[]> {
-  return await invoke<PluginInfo[]>("list_plugins");
+export async function listPlugins(locale: string = navigator.language, query?: PluginQuery): Promise<PluginInfo[]> {
+  return await invoke<PluginInfo[]>("list_plugins", { locale, query });
+}
+
+/**
+ * Search installed plugins, best matches first, with per-category and per-tag counts for filters
+ */
+export async function searchPlugins(query: PluginQuery, locale: string = navigator.language): Promise<PluginSearchResults> {
+  return await invoke<PluginSearchResults>("search_plugins", { query, locale });
 }
 
 /**
  * Get detailed information about a specific plugin
  */
-export async function getPluginInfo(name: string): Promise<PluginInfo> {
-  return await invoke<PluginInfo>("get_plugin_info", { name });
+export async function getPluginInfo(name: string, locale: string = navigator.language): Promise<PluginInfo> {
+  return await invoke<PluginInfo>("get_plugin_info", { name, locale });
+}
+
+/**
+ * Get URLs of a plugin's icon and screenshots
+ */
+export async function getPluginAssets(name: string): Promise<PluginAssetUrls> {
+  return await invoke<PluginAssetUrls>("get_plugin_assets", { name });
+}
+
+/**
+ * Get the menu items, panels and routes loaded plugins add to the navigation
+ */
+export async function getUiContributions(locale: string = navigator.language): Promise<UiContributionInfo[]> {
+  return await invoke<UiContributionInfo[]>("get_ui_contributions", { locale });
+}
+
+/**
+ * Get the dependency graph of the installed plugins
+ */
+export async function getPluginDependencyGraph(): Promise<DependencyGraph> {
+  return await invoke<DependencyGraph>("get_plugin_dependency_graph");
 }
 
 /**
  * Execute a plugin function with typed input/output
+ *
+ * Text entry points take and return strings; binary ones take a base64
+ * string or an array of bytes and return base64. The call is made on behalf
+ * of the signed-in user, whom the plugin can look up with `get_current_user`.
  */
 export async function executePlugin<TInput = any, TOutput = any>(
   pluginName: string,
   functionName: string,
   input: TInput
 ): Promise<TOutput> {
-  const response = await invoke<ExecuteResponse>("execute_plugin", {
-    pluginName,
-    function: functionName,
-    input,
-  });
+  const response = await invoke<ExecuteResponse>(
+    "execute_plugin",
+    {
+      pluginName,
+      function: functionName,
+      input,
+    },
+    sessionOptions()
+  );
   return response.output as TOutput;
 }
 
@@ -39,21 +95,154 @@ export async function executePlugin<TInput = any, TOutput = any>(
  * Install a plugin from a local path
  */
 export async function installPlugin(path: string): Promise<string> {
-  return await invoke<string>("install_plugin", { path });
+  return await invoke<string>("install_plugin", { path }, sessionOptions());
+}
+
+/**
+ * Call `handler` for each install asking for capability approval
+ */
+export async function onCapabilityRequest(
+  handler: (request: CapabilityRequest) => void
+): Promise<UnlistenFn> {
+  return await listen<CapabilityRequest>("plugin-capability-request", (event) => handler(event.payload));
+}
+
+/**
+ * Call `handler` for each `event` the plugin emits with `emit_event`
+ */
+export async function onPluginEvent<T = any>(
+  plugin: string,
+  event: string,
+  handler: (event: PluginEmittedEvent<T>) => void
+): Promise<UnlistenFn> {
+  // Matches the backend, which replaces characters event names can't contain
+  const id = plugin.replace(/[^A-Za-z0-9\-_/]/g, "_");
+  return await listen<PluginEmittedEvent<T>>(`plugin:${id}:${event}`, (e) => handler(e.payload));
+}
+
+/**
+ * Get capability requests still waiting for an answer
+ */
+export async function getCapabilityRequests(): Promise<CapabilityRequest[]> {
+  return await invoke<CapabilityRequest[]>("get_capability_requests");
+}
+
+/**
+ * Answer a capability request; capabilities not in `granted` are denied
+ */
+export async function respondCapabilityRequest(
+  requestId: string,
+  granted: SensitiveCapability[]
+): Promise<void> {
+  await invoke("respond_capability_request", { requestId, granted }, sessionOptions());
+}
+
+/**
+ * Get the capabilities granted and denied to a plugin
+ */
+export async function getPluginCapabilities(pluginName: string): Promise<CapabilityDecisions> {
+  return await invoke<CapabilityDecisions>("get_plugin_capabilities", { pluginName });
+}
+
+/**
+ * Grant or revoke a capability of an installed plugin
+ */
+export async function setPluginCapability(
+  pluginName: string,
+  capability: SensitiveCapability,
+  granted: boolean
+): Promise<CapabilityDecisions> {
+  return await invoke<CapabilityDecisions>(
+    "set_plugin_capability",
+    { pluginName, capability, granted },
+    sessionOptions()
+  );
 }
 
 /**
  * Install a plugin from a URL (WASM file or manifest JSON)
  */
 export async function installPluginFromUrl(url: string): Promise<string> {
-  return await invoke<string>("install_plugin_from_url", { url });
+  return await invoke<string>("install_plugin_from_url", { url }, sessionOptions());
+}
+
+/**
+ * Get plugin profiles, the profile applied last and the disabled plugins
+ */
+export async function getPluginProfiles(): Promise<PluginProfiles> {
+  return await invoke<PluginProfiles>("get_plugin_profiles");
+}
+
+/**
+ * Create or replace a plugin profile
+ */
+export async function savePluginProfile(profile: PluginProfile): Promise<PluginProfile[]> {
+  return await invoke<PluginProfile[]>("save_plugin_profile", { profile }, sessionOptions());
+}
+
+export async function deletePluginProfile(name: string): Promise<PluginProfile[]> {
+  return await invoke<PluginProfile[]>("delete_plugin_profile", { name }, sessionOptions());
+}
+
+/**
+ * Enable the plugins of a profile and disable every other installed plugin
+ */
+export async function applyPluginProfile(name: string): Promise<PluginSetChange> {
+  return await invoke<PluginSetChange>("apply_plugin_profile", { name }, sessionOptions());
+}
+
+/**
+ * Enable or disable several plugins at once
+ */
+export async function setPluginsEnabled(plugins: string[], enabled: boolean): Promise<PluginSetChange> {
+  return await invoke<PluginSetChange>("set_plugins_enabled", { plugins, enabled }, sessionOptions());
+}
+
+/**
+ * Install another version of a plugin next to the current one; call it as
+ * `id@version` until it is promoted or rolled back
+ */
+export async function installPluginCanary(path: string): Promise<string> {
+  return await invoke<string>("install_plugin_canary", { path }, sessionOptions());
+}
+
+export async function listPluginCanaries(): Promise<PluginCanary[]> {
+  return await invoke<PluginCanary[]>("list_plugin_canaries");
+}
+
+/**
+ * Make a canary the current version of its plugin
+ */
+export async function promotePluginCanary(name: string): Promise<string> {
+  return await invoke<string>("promote_plugin_canary", { name }, sessionOptions());
+}
+
+/**
+ * Remove a canary, keeping the current version of its plugin
+ */
+export async function rollbackPluginCanary(name: string): Promise<string> {
+  return await invoke<string>("rollback_plugin_canary", { name }, sessionOptions());
+}
+
+/**
+ * Check every installed plugin against this version of the app
+ */
+export async function getPluginCompatibilityReport(): Promise<CompatibilityReport> {
+  return await invoke<CompatibilityReport>("get_plugin_compatibility_report");
+}
+
+/**
+ * Check whether plugins installed from a URL have newer versions available there
+ */
+export async function checkPluginUpdates(): Promise<PluginUpdate[]> {
+  return await invoke<PluginUpdate[]>("check_plugin_updates");
 }
 
 /**
  * Discover and load all plugins from the plugins directory
  */
 export async function discoverPlugins(): Promise<number> {
-  return await invoke<number>("discover_plugins");
+  return await invoke<number>("discover_plugins", {}, sessionOptions());
 }
 
 // ============================================================================
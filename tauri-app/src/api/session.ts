@@ -0,0 +1,15 @@
+/**
+ * Session helpers for authorized Tauri commands
+ */
+
+import type { InvokeOptions } from "@tauri-apps/api/core";
+
+const SESSION_ID_KEY = "session_id";
+
+/**
+ * Invoke options carrying the current session, required by admin-only commands
+ */
+export function sessionOptions(): InvokeOptions {
+  const sessionId = localStorage.getItem(SESSION_ID_KEY);
+  return sessionId ? { headers: { Authorization: `Bearer ${sessionId}` } } : {};
+}
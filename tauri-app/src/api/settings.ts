@@ -0,0 +1,13 @@
+/**
+ * Settings API - live updates of application settings
+ */
+
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { SettingChange } from "./types";
+
+/**
+ * Call `handler` whenever a setting is written
+ */
+export async function onSettingChanged(handler: (change: SettingChange) => void): Promise<UnlistenFn> {
+  return await listen<SettingChange>("setting:changed", (event) => handler(event.payload));
+}
@@ -9,6 +9,8 @@ export interface User {
   emailVerified: boolean;
   createdAt: string;
   updatedAt: string;
+  /** Set while the user still has the temporary password they were imported with */
+  passwordResetRequired?: boolean;
 }
 
 export interface Session {
@@ -20,6 +22,8 @@ export interface Session {
 export interface AuthResult {
   user: User;
   sessionId: string;
+  /** The user must change their password before using the app */
+  passwordResetRequired?: boolean;
 }
 
 export interface PluginResult<T = unknown> {
@@ -47,7 +51,20 @@ export interface RequestPasswordResetInput {
   email: string;
 }
 
+export interface ChangePasswordInput {
+  sessionId: string;
+  currentPassword: string;
+  newPassword: string;
+}
+
 export interface ResetPasswordInput {
   token: string;
   newPassword: string;
 }
+
+/** Payload of the `setting:changed` event, sent whenever a setting is written */
+export interface SettingChange {
+  key: string;
+  value: unknown;
+  changed_at: number;
+}
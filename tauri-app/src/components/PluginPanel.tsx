@@ -0,0 +1,68 @@
+import { useEffect, useRef } from 'react';
+import { convertFileSrc } from '@tauri-apps/api/core';
+import { executePlugin } from '../api/plugins';
+import type { PluginInfo, UiPanel } from '../types/plugin';
+
+/** Message a panel posts to call one of its plugin's functions */
+interface PanelExecuteMessage {
+  type: 'execute';
+  id: string | number;
+  function: string;
+  input: unknown;
+}
+
+/**
+ * Embed a plugin-provided UI panel
+ *
+ * The panel runs in a sandboxed iframe without same-origin access, so it
+ * cannot reach the Tauri IPC. It calls its own plugin's entry points through
+ * postMessage: `{ type: 'execute', id, function, input }` is answered with
+ * `{ type: 'result', id, output }` or `{ type: 'error', id, error }`.
+ */
+export default function PluginPanel({ plugin, panel }: { plugin: PluginInfo; panel: UiPanel }) {
+  const frameRef = useRef<HTMLIFrameElement>(null);
+
+  useEffect(() => {
+    const allowedFunctions = new Set(plugin.entry_points.map((ep) => ep.name));
+
+    async function handleMessage(event: MessageEvent) {
+      const frame = frameRef.current;
+      if (!frame || event.source !== frame.contentWindow) return;
+
+      const message = event.data as PanelExecuteMessage;
+      if (message?.type !== 'execute') return;
+
+      const reply = (data: object) =>
+        frame.contentWindow?.postMessage({ id: message.id, ...data }, '*');
+
+      if (!allowedFunctions.has(message.function)) {
+        reply({ type: 'error', error: `Unknown function: ${message.function}` });
+        return;
+      }
+
+      try {
+        const output = await executePlugin(plugin.id, message.function, message.input);
+        reply({ type: 'result', output });
+      } catch (err) {
+        reply({ type: 'error', error: err instanceof Error ? err.message : String(err) });
+      }
+    }
+
+    window.addEventListener('message', handleMessage);
+    return () => window.removeEventListener('message', handleMessage);
+  }, [plugin]);
+
+  // The plugin ID is one path segment (namespaced IDs contain a '/')
+  const entry = panel.entry.split('/').map(encodeURIComponent).join('/');
+  const src = `${convertFileSrc('', 'plugin-ui')}${encodeURIComponent(plugin.id)}/${entry}`;
+
+  return (
+    <iframe
+      ref={frameRef}
+      title={panel.title}
+      src={src}
+      sandbox="allow-scripts allow-forms"
+      className="w-full h-full min-h-96 border-0 bg-white rounded-lg"
+    />
+  );
+}
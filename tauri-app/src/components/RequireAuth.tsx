@@ -3,10 +3,11 @@ import { useAuth } from '../contexts/AuthContext';
 
 /**
  * Require authentication to access routes
- * Redirects to /sign-in if not authenticated
+ * Redirects to /sign-in if not authenticated, and to /change-password while
+ * the user still has a temporary password
  */
 export default function RequireAuth() {
-  const { isAuthenticated, loading } = useAuth();
+  const { isAuthenticated, passwordResetRequired, loading } = useAuth();
   const location = useLocation();
 
   if (loading) {
@@ -22,5 +23,10 @@ export default function RequireAuth() {
     return <Navigate to="/sign-in" state={{ from: location }} replace />;
   }
 
+  if (passwordResetRequired && location.pathname !== '/change-password') {
+    // The app refuses everything else until the password is changed
+    return <Navigate to="/change-password" state={{ from: location }} replace />;
+  }
+
   return <Outlet />;
 }
@@ -8,7 +8,10 @@ interface AuthContextType {
   signIn: (email: string, password: string) => Promise<void>;
   signUp: (name: string, email: string, password: string) => Promise<void>;
   signOut: () => Promise<void>;
+  changePassword: (currentPassword: string, newPassword: string) => Promise<void>;
   isAuthenticated: boolean;
+  /** Set until a user signed in with a temporary password changes it */
+  passwordResetRequired: boolean;
 }
 
 const AuthContext = createContext<AuthContextType | undefined>(undefined);
@@ -19,6 +22,7 @@ const USER_ID_KEY = 'user_id';
 export function AuthProvider({ children }: { children: ReactNode }) {
   const [user, setUser] = useState<User | null>(null);
   const [loading, setLoading] = useState(true);
+  const [passwordResetRequired, setPasswordResetRequired] = useState(false);
 
   // Check for existing session on mount
   useEffect(() => {
@@ -34,6 +38,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
           // Fetch user data
           const userData = await authApi.getCurrentUser(userId);
           setUser(userData);
+          setPasswordResetRequired(!!userData.passwordResetRequired);
         }
       } catch (error) {
         // Session invalid or expired
@@ -56,6 +61,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     localStorage.setItem(USER_ID_KEY, result.user.id);
     
     setUser(result.user);
+    setPasswordResetRequired(!!result.passwordResetRequired);
   }
 
   async function handleSignUp(name: string, email: string, password: string) {
@@ -68,6 +74,15 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     setUser(result.user);
   }
 
+  async function handleChangePassword(currentPassword: string, newPassword: string) {
+    const sessionId = localStorage.getItem(SESSION_ID_KEY);
+    if (!sessionId) {
+      throw new Error('Not signed in');
+    }
+    await authApi.changePassword({ sessionId, currentPassword, newPassword });
+    setPasswordResetRequired(false);
+  }
+
   async function handleSignOut() {
     try {
       await authApi.signOut();
@@ -76,6 +91,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       localStorage.removeItem(SESSION_ID_KEY);
       localStorage.removeItem(USER_ID_KEY);
       setUser(null);
+      setPasswordResetRequired(false);
     }
   }
 
@@ -85,7 +101,9 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     signIn: handleSignIn,
     signUp: handleSignUp,
     signOut: handleSignOut,
+    changePassword: handleChangePassword,
     isAuthenticated: !!user,
+    passwordResetRequired,
   };
 
   return <AuthContext.Provider value={value}>{children}</AuthContext.Provider>;
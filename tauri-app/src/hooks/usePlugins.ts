@@ -10,8 +10,9 @@ import {
   installPlugin,
   installPluginFromUrl,
   discoverPlugins,
+  getUiContributions,
 } from "../api/plugins";
-import type { PluginInfo } from "../types/plugin";
+import type { PluginInfo, UiContributionInfo } from "../types/plugin";
 
 export function usePlugins() {
   const [plugins, setPlugins] = useState<PluginInfo[]>([]);
@@ -77,6 +78,35 @@ export function usePluginInfo(pluginName: string | null) {
   };
 }
 
+export function useUiContributions() {
+  const [contributions, setContributions] = useState<UiContributionInfo[]>([]);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const loadContributions = useCallback(async () => {
+    setLoading(true);
+    setError(null);
+    try {
+      setContributions(await getUiContributions());
+    } catch (err) {
+      setError(err instanceof Error ? err.message : "Failed to load UI contributions");
+    } finally {
+      setLoading(false);
+    }
+  }, []);
+
+  useEffect(() => {
+    loadContributions();
+  }, [loadContributions]);
+
+  return {
+    contributions,
+    loading,
+    error,
+    loadContributions,
+  };
+}
+
 export function usePluginExecution<TInput = any, TOutput = any>() {
   const [executing, setExecuting] = useState(false);
   const [result, setResult] = useState<TOutput | null>(null);
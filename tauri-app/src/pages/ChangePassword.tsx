@@ -0,0 +1,137 @@
+import { zodResolver } from "@hookform/resolvers/zod";
+import { useForm } from "react-hook-form";
+import { useNavigate, useLocation } from "react-router-dom";
+import { z } from "zod";
+import { useState } from "react";
+import { useAuth } from "../contexts/AuthContext";
+
+const changePasswordSchema = z
+  .object({
+    currentPassword: z.string().min(1, "Current password is required"),
+    newPassword: z
+      .string()
+      .min(8, "Password must be at least 8 characters")
+      .regex(/[A-Z]/, "Password must contain at least one uppercase letter")
+      .regex(/[a-z]/, "Password must contain at least one lowercase letter")
+      .regex(/[0-9]/, "Password must contain at least one number")
+      .regex(/[^A-Za-z0-9]/, "Password must contain at least one special character"),
+    confirmPassword: z.string(),
+  })
+  .refine((data) => data.newPassword === data.confirmPassword, {
+    message: "Passwords do not match",
+    path: ["confirmPassword"],
+  })
+  .refine((data) => data.newPassword !== data.currentPassword, {
+    message: "The new password must differ from the current one",
+    path: ["newPassword"],
+  });
+
+type ChangePasswordFormData = z.infer<typeof changePasswordSchema>;
+
+const fields: { name: keyof ChangePasswordFormData; label: string }[] = [
+  { name: "currentPassword", label: "Current password" },
+  { name: "newPassword", label: "New password" },
+  { name: "confirmPassword", label: "Confirm new password" },
+];
+
+export default function ChangePassword() {
+  const navigate = useNavigate();
+  const location = useLocation();
+  const { changePassword, passwordResetRequired, signOut } = useAuth();
+  const [error, setError] = useState<string | null>(null);
+  const [loading, setLoading] = useState(false);
+
+  const {
+    register,
+    handleSubmit,
+    formState: { errors },
+  } = useForm<ChangePasswordFormData>({
+    resolver: zodResolver(changePasswordSchema),
+    mode: "onChange",
+  });
+
+  async function onSubmit(data: ChangePasswordFormData) {
+    setLoading(true);
+    setError(null);
+
+    try {
+      await changePassword(data.currentPassword, data.newPassword);
+
+      // Continue to where the user was headed before being sent here
+      const from = (location.state as any)?.from?.pathname || '/dashboard';
+      navigate(from, { replace: true });
+    } catch (err) {
+      setError(err instanceof Error ? err.message : 'Failed to change password');
+    } finally {
+      setLoading(false);
+    }
+  }
+
+  return (
+    <div className="flex items-center justify-center min-h-screen bg-gray-50 p-4">
+      <div className="w-full max-w-md">
+        <div className="bg-white rounded-lg shadow-lg p-8">
+          <h1 className="text-2xl font-bold mb-2 text-center">Change Password</h1>
+          {passwordResetRequired && (
+            <p className="text-gray-600 mb-6 text-center">
+              You signed in with a temporary password. Choose a new one to continue.
+            </p>
+          )}
+
+          {error && (
+            <div className="mb-4 p-4 bg-red-50 border border-red-200 rounded-lg">
+              <p className="text-sm text-red-600">{error}</p>
+            </div>
+          )}
+
+          <form onSubmit={handleSubmit(onSubmit)} className="space-y-4">
+            {fields.map(({ name, label }) => (
+              <div key={name}>
+                <label htmlFor={name} className="block text-sm font-medium text-gray-700 mb-1">
+                  {label}
+                </label>
+                <input
+                  id={name}
+                  type="password"
+                  {...register(name)}
+                  className={`
+                    w-full px-4 py-2 border rounded-lg focus:ring-2 focus:ring-blue-500 focus:border-transparent
+                    ${errors[name] ? 'border-red-500' : 'border-gray-300'}
+                  `}
+                  placeholder="••••••••"
+                />
+                {errors[name] && (
+                  <p className="mt-1 text-sm text-red-500">{errors[name]?.message}</p>
+                )}
+              </div>
+            ))}
+
+            <button
+              type="submit"
+              disabled={loading}
+              className={`
+                w-full py-3 px-4 rounded-lg font-medium text-white transition
+                ${loading
+                  ? 'bg-blue-400 cursor-not-allowed'
+                  : 'bg-blue-600 hover:bg-blue-700'
+                }
+              `}
+            >
+              {loading ? 'Changing password...' : 'Change Password'}
+            </button>
+          </form>
+        </div>
+
+        <p className="mt-6 text-center text-sm text-gray-600">
+          <button
+            type="button"
+            onClick={() => signOut()}
+            className="text-blue-600 hover:text-blue-700 font-medium"
+          >
+            Sign out
+          </button>
+        </p>
+      </div>
+    </div>
+  );
+}
@@ -1,5 +1,6 @@
 import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { sessionOptions } from '../api/session';
 import { listen } from '@tauri-apps/api/event';
 
 interface TickManagerStatus {
@@ -57,7 +58,7 @@ export default function TickManager() {
 
   const handleStart = async () => {
     try {
-      await invoke('tick_start');
+      await invoke('tick_start', {}, sessionOptions());
       setError(null);
     } catch (err) {
       setError(String(err));
@@ -66,7 +67,7 @@ export default function TickManager() {
 
   const handleStop = async () => {
     try {
-      await invoke('tick_stop');
+      await invoke('tick_stop', {}, sessionOptions());
       setError(null);
     } catch (err) {
       setError(String(err));
@@ -77,7 +78,7 @@ export default function TickManager() {
     try {
       const rate = parseInt(tickRate);
       if (rate > 0) {
-        await invoke('tick_set_rate', { rate });
+        await invoke('tick_set_rate', { rate }, sessionOptions());
         setError(null);
       }
     } catch (err) {
@@ -87,7 +88,7 @@ export default function TickManager() {
 
   const handleRegisterSession = async () => {
     try {
-      await invoke('tick_register_session', { sessionId });
+      await invoke('tick_register_session', { sessionId }, sessionOptions());
       await fetchActiveSessions();
       setError(null);
     } catch (err) {
@@ -97,7 +98,7 @@ export default function TickManager() {
 
   const handleUnregisterSession = async () => {
     try {
-      await invoke('tick_unregister_session', { sessionId });
+      await invoke('tick_unregister_session', { sessionId }, sessionOptions());
       await fetchActiveSessions();
       setError(null);
     } catch (err) {
@@ -107,7 +108,7 @@ export default function TickManager() {
 
   const handleAddClient = async () => {
     try {
-      await invoke('tick_add_client', { sessionId, clientId });
+      await invoke('tick_add_client', { sessionId, clientId }, sessionOptions());
       setError(null);
     } catch (err) {
       setError(String(err));
@@ -116,7 +117,7 @@ export default function TickManager() {
 
   const handleRemoveClient = async () => {
     try {
-      await invoke('tick_remove_client', { sessionId, clientId });
+      await invoke('tick_remove_client', { sessionId, clientId }, sessionOptions());
       setError(null);
     } catch (err) {
       setError(String(err));
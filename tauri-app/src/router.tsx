@@ -10,6 +10,7 @@ const Login = lazy(() => import('./pages/Login'));
 const Register = lazy(() => import('./pages/Register'));
 const ForgotPassword = lazy(() => import('./pages/ForgotPassword'));
 const VerifyEmail = lazy(() => import('./pages/VerifyEmail'));
+const ChangePassword = lazy(() => import('./pages/ChangePassword'));
 const Dashboard = lazy(() => import('./pages/Dashboard'));
 const AuditLogs = lazy(() => import('./pages/AuditLogs'));
 const TickManager = lazy(() => import('./pages/TickManager'));
@@ -106,6 +107,10 @@ export const router = createBrowserRouter([
       {
         element: <RequireAuth />,
         children: [
+          {
+            path: "change-password",
+            element: <ChangePassword />,
+          },
           {
             path: "dashboard",
             element: <Dashboard />,
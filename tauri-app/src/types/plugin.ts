@@ -3,25 +3,341 @@
  */
 
 export interface PluginInfo {
+  /** Unique ID: `namespace/name`, or the name for plugins without a namespace */
+  id: string;
   name: string;
+  /** Name to show users, translated for the requested locale if the plugin has a translation */
+  display_name: string;
+  namespace: string | null;
   version: string;
   description: string;
   plugin_type: string;
-  capabilities: string[];
+  /** Catalog sections the plugin is listed under */
+  categories: string[];
+  /** Keywords search matches the plugin by */
+  tags: string[];
+  capabilities: Capability[];
   entry_points: EntryPointInfo[];
+  ui_panels: UiPanel[];
+  /** URL of the plugin's icon, if it has one */
+  icon_url: string | null;
+  /** Why the plugin cannot run on this host; such plugins are listed but not loaded */
+  incompatible: string[];
+  /** Whether the plugin should no longer be used */
+  deprecated: boolean;
+  /** ID of the plugin replacing it */
+  replacement: string | null;
+  /** Sandbox the plugin runs in, after the user's and host's downgrades */
+  sandbox: SandboxProfile;
+}
+
+/** Which plugins to list; omitted fields match every plugin */
+export interface PluginQuery {
+  /** Words that must each appear in the plugin's ID, name, description, categories or tags */
+  text?: string;
+  /** Plugins in any of these categories */
+  categories?: string[];
+  /** Plugins with every one of these tags */
+  tags?: string[];
+  plugin_type?: string;
+}
+
+/** Plugins matching a search, best matches first, and counts of installed plugins per category and tag */
+export interface PluginSearchResults {
+  plugins: PluginInfo[];
+  categories: Record<string, number>;
+  tags: Record<string, number>;
+}
+
+/** Preset sandbox limits, from strictest to loosest */
+export type SandboxProfile = "strict" | "standard" | "trusted";
+
+export interface PluginAssetUrls {
+  icon: string | null;
+  screenshots: string[];
+}
+
+export interface UiPanel {
+  id: string;
+  title: string;
+  /** HTML entry file, relative to the plugin's UI assets directory */
+  entry: string;
+}
+
+/** Where a UI contribution appears: a menu item, a panel next to the app's own, or a page under /plugins/<id> */
+export type UiContributionKind = "menu_item" | "panel" | "route";
+
+/** A menu item, panel or route a loaded plugin adds to the navigation */
+export interface UiContributionInfo {
+  /** ID of the contributing plugin */
+  plugin: string;
+  /** Plugin's name to show users, translated for the requested locale */
+  plugin_name: string;
+  id: string;
+  kind: UiContributionKind;
+  label: string;
+  /** Function to pass to `execute_plugin`, if the contribution runs an entry point */
+  function: string | null;
+  /** URL of the panel it shows */
+  panel_url: string | null;
+  /** Full path of a route, `/plugins/<plugin ID><path>` */
+  route: string | null;
+  /** Menu a menu item goes in */
+  menu: string | null;
+  order: number;
 }
 
 export interface EntryPointInfo {
   name: string;
+  /** Function to pass to `execute_plugin` */
+  function: string;
+  /** Other names the function can be called by */
+  aliases: string[];
+  /** Whether calls that name no function run this one */
+  default: boolean;
   description: string;
   input_format: string;
   output_format: string;
+  /** JSON Schema of the input, for generating a form */
+  input_schema: Record<string, unknown> | boolean | null;
+  output_schema: Record<string, unknown> | boolean | null;
+  /** Whether the function should no longer be called */
+  deprecated: boolean;
+  /** Name of the entry point replacing it */
+  replacement: string | null;
+}
+
+export interface DependencyNode {
+  /** Plugin ID, or the declared name of a dependency that is not installed */
+  id: string;
+  name: string;
+  version: string | null;
+  installed: boolean;
+  /** Whether all of its dependencies are satisfied, transitively */
+  available: boolean;
+}
+
+export interface DependencyEdge {
+  from: string;
+  to: string;
+  /** Semver range the dependent declared */
+  required_version: string;
+  /** Whether the dependency is installed at a version in that range */
+  satisfied: boolean;
+  /** Why the edge is not satisfied */
+  issue?: string;
+}
+
+export interface DependencyGraph {
+  nodes: DependencyNode[];
+  edges: DependencyEdge[];
+}
+
+/** Database access: `db:read`, `db:write`, or limited to one resource, e.g. `db:users:read` */
+export type DbCapability = `db:${string}`;
+
+/** What a plugin may do, as declared in its manifest */
+export type Capability = "net" | "fs" | "tick" | "wasi" | "clipboard" | "exec" | "email" | DbCapability;
+
+/** Sensitive capabilities a plugin must be granted before it can use them */
+export type SensitiveCapability = "net" | "fs" | "clipboard" | "exec" | "email" | "db:write";
+
+/** Payload of the `plugin-capability-request` event, sent while an install waits for approval */
+export interface CapabilityRequest {
+  id: string;
+  plugin: string;
+  version: string;
+  capabilities: SensitiveCapability[];
+}
+
+/** Payload of a `plugin:<id>:<event>` event, sent when a plugin calls `emit_event` */
+export interface PluginEmittedEvent<T = any> {
+  plugin: string;
+  event: string;
+  payload: T;
+  /** The plugin call that emitted it, if any */
+  execution_id: string | null;
+  /** Unix timestamp (milliseconds) */
+  timestamp: number;
+}
+
+export interface CapabilityDecisions {
+  granted: SensitiveCapability[];
+  denied: SensitiveCapability[];
 }
 
 export interface ExecuteResponse {
   output: any;
+  /** Deprecation warnings for the plugin or function called */
+  warnings?: string[];
+}
+
+export type ErrorCode =
+  | "invalid_input"
+  | "not_found"
+  | "unauthorized"
+  | "conflict"
+  | "timeout"
+  | "unavailable"
+  | "rate_limited"
+  | "cancelled"
+  | "quota_exceeded"
+  | "plugin_error"
+  | "internal";
+
+/**
+ * Error rejected by plugin execution commands
+ */
+export interface AppError {
+  code: ErrorCode;
+  message: string;
+  plugin: string | null;
+  function: string | null;
+  /** Whether the same call may succeed if tried again */
+  retriable: boolean;
+  /** The quota a call went over, for `quota_exceeded` errors */
+  quota?: QuotaViolation;
+}
+
+/**
+ * How a call went over one of its plugin's resource quotas; amounts are in
+ * milliseconds, bytes or calls
+ */
+export interface QuotaViolation {
+  quota: "execution_time" | "output_size" | "concurrent_calls";
+  limit: number;
+  /** What the call used, or would have */
+  actual: number;
+}
+
+/**
+ * A version of a plugin loaded next to the current one
+ */
+export interface PluginCanary {
+  /** Name the canary is called by: `id@version` */
+  key: string;
+  plugin: string;
+  version: string;
+  /** Version callers get when they do not name one */
+  current_version: string | null;
+}
+
+/**
+ * Whether a plugin installed from a URL has a newer version there
+ */
+export interface PluginUpdate {
+  plugin: string;
+  source_url: string;
+  update_available: boolean;
+  /** Why the check failed, if it did */
+  error: string | null;
 }
 
-export interface PluginError {
+/**
+ * An installed plugin that needs an update to run on this app version
+ */
+export interface PluginCompatibility {
+  /** Plugin ID, or the directory name if the manifest cannot be read */
+  plugin: string;
+  version: string | null;
+  issues: string[];
+  /** Where to get a newer version, if known */
+  link: string | null;
+}
+
+export interface CompatibilityReport {
+  app_version: string;
+  /** Number of plugins checked */
+  checked: number;
+  incompatible: PluginCompatibility[];
+  generated_at: number;
+}
+
+/** A host function plugins can be linked to, as list_host_functions describes it */
+export interface HostFunctionInfo {
+  name: string;
+  /** `extism:host/user`, or `extism:host/env` for the HTTP functions */
+  namespace: string;
+  /** Wasm types, e.g. `i64`; pointers to plugin memory are `i64` */
+  params: string[];
+  results: string[];
+  /** Capability the manifest must declare for it to be linked, if any */
+  capability: string | null;
+  /** Function to call instead, if this one is deprecated */
+  replaced_by: string | null;
+  /** False for functions the embedding application adds */
+  builtin: boolean;
+}
+
+/** License of one installed plugin */
+export interface PluginLicense {
+  plugin: string;
+  version: string;
+  author: string | null;
+  /** SPDX license expression, if the plugin declares one */
+  license: string | null;
+  homepage: string | null;
+}
+
+/** Licenses of every installed plugin, loaded or not */
+export interface LicenseReport {
+  plugins: PluginLicense[];
+  /** IDs of the plugins using each license identifier */
+  licenses: Record<string, string[]>;
+  /** IDs of the plugins that declare no license */
+  unlicensed: string[];
+  generated_at: number;
+}
+
+/**
+ * A plugin waiting for approval because it is unsigned or its author is not trusted
+ */
+export interface QuarantinedPlugin {
+  id: string;
+  version: string;
+  author: string | null;
+  /** Key the plugin is signed with; null if it is unsigned */
+  public_key: string | null;
+  reason: string;
+}
+
+/**
+ * An author whose signed plugins install without approval
+ */
+export interface TrustedAuthor {
+  /** Base64 of the author's Ed25519 public key */
+  public_key: string;
+  name: string;
+  added_at: number;
+}
+
+/**
+ * A named set of plugins; applying it enables these and disables every other installed plugin
+ */
+export interface PluginProfile {
+  name: string;
+  description?: string | null;
+  /** IDs of the plugins to enable */
+  plugins: string[];
+}
+
+export interface PluginProfiles {
+  profiles: PluginProfile[];
+  /** Profile applied last, unless plugins were enabled or disabled individually since */
+  active: string | null;
+  disabled: string[];
+}
+
+export interface PluginLoadFailure {
+  dir: string;
   error: string;
 }
+
+/**
+ * Plugins loaded and unloaded when the set of disabled plugins changed
+ */
+export interface PluginSetChange {
+  enabled: string[];
+  disabled: string[];
+  failed: PluginLoadFailure[];
+}
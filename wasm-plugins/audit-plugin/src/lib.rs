@@ -9,7 +9,7 @@ use serde::{Deserialize, Serialize};
 #[host_fn("extism:host/user")]
 extern "ExtismHost" {
     fn get_timestamp() -> i64;
-    fn get_timestamp_nanos() -> i64;
+    fn new_id(kind: String) -> String;
 }
 
 /// Database host functions
@@ -19,6 +19,7 @@ extern "ExtismHost" {
     fn db_get_user_audit_logs(json_request: String) -> String;
     fn db_get_audit_logs_filtered(json_request: String) -> String;
     fn db_count_user_audit_logs(json_request: String) -> String;
+    fn db_delete_old_audit_logs(json_request: String) -> String;
 }
 
 // ============================================================================
@@ -67,6 +68,18 @@ pub struct GetAuditLogsFilteredInput {
     pub limit: Option<i32>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CleanupAuditLogsInput {
+    /// Delete entries older than this many days (default 90)
+    pub retention_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CleanupAuditLogsResponse {
+    pub deleted: usize,
+    pub older_than: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuditLogsResponse {
     pub logs: Vec<AuditLog>,
@@ -228,18 +241,31 @@ fn call_db_count_user_audit_logs(user_uuid: &str) -> Result<i64, Error> {
     Ok(response.data.unwrap_or(0))
 }
 
+fn call_db_delete_old_audit_logs(older_than: i64) -> Result<usize, Error> {
+    let request = serde_json::json!({ "older_than": older_than });
+
+    let request_str = serde_json::to_string(&request)?;
+    let response_json = unsafe { db_delete_old_audit_logs(request_str)? };
+    let response: HostResponse<usize> = serde_json::from_str(&response_json)?;
+
+    if !response.success {
+        return Err(Error::msg(
+            response
+                .error
+                .unwrap_or_else(|| "Unknown database error".to_string()),
+        ));
+    }
+
+    Ok(response.data.unwrap_or(0))
+}
+
 // ============================================================================
 // Utility Functions
 // ============================================================================
 
+/// Time-ordered ID from the host, so entries sort by creation
 fn generate_id() -> FnResult<String> {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    
-    let timestamp = unsafe { get_timestamp_nanos()? };
-    let mut hasher = DefaultHasher::new();
-    timestamp.hash(&mut hasher);
-    Ok(format!("audit_{:x}", hasher.finish()))
+    Ok(unsafe { new_id("audit_log".to_string())? })
 }
 
 // ============================================================================
@@ -341,3 +367,22 @@ pub fn get_audit_logs_filtered(input: String) -> FnResult<String> {
 
     Ok(serde_json::to_string(&response)?)
 }
+
+/// Delete audit logs older than the retention period (run nightly by the host scheduler)
+#[plugin_fn]
+pub fn cleanup_audit_logs(input: String) -> FnResult<String> {
+    let input: CleanupAuditLogsInput = if input.trim().is_empty() {
+        CleanupAuditLogsInput { retention_days: None }
+    } else {
+        serde_json::from_str(&input)?
+    };
+
+    let retention_days = input.retention_days.unwrap_or(90).max(1);
+    let now = unsafe { get_timestamp()? };
+    let older_than = now - retention_days * 24 * 60 * 60;
+
+    let deleted = call_db_delete_old_audit_logs(older_than)?;
+
+    let response = PluginResponse::success(CleanupAuditLogsResponse { deleted, older_than });
+    Ok(serde_json::to_string(&response)?)
+}
@@ -31,7 +31,7 @@ if ($LASTEXITCODE -eq 0) {
             config = @{}
             memory_max_pages = $null
         }
-        capabilities = @()
+        capabilities = @("db:users:read", "db:users:write", "db:sessions:read", "db:sessions:write", "db:audit:write")
         entry_points = @(
             @{
                 name = "signup"
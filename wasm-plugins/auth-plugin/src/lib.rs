@@ -12,11 +12,14 @@ use argon2::{
 /// Utility host functions provided by the Tauri application
 #[host_fn("extism:host/user")]
 extern "ExtismHost" {
-    /// Generate random bytes - returns JSON array string of bytes
-    fn generate_random_bytes(length: i64) -> String;
+    /// Generate random bytes, returned raw
+    fn generate_random_bytes(length: i64) -> Vec<u8>;
     
     /// Get current timestamp in seconds
     fn get_timestamp() -> i64;
+    
+    /// Generate an ID of the given kind (user, session, ...)
+    fn new_id(kind: String) -> String;
 }
 
 /// Database host functions provided by the Tauri application
@@ -45,24 +48,63 @@ extern "ExtismHost" {
 
     /// Create an audit log entry
     fn db_create_audit_log(json_request: String) -> String;
+    
+    /// Open a transaction for the rest of this call
+    fn db_begin() -> String;
+    
+    /// Commit a transaction
+    fn db_commit(json_request: String) -> String;
+    
+    /// Roll back a transaction
+    fn db_rollback(json_request: String) -> String;
 }
 
 // ============================================================================
 // Utility Functions
 // ============================================================================
 
-/// Simple UUID generation using random bytes from host
-fn generate_uuid() -> FnResult<String> {
-    let json_bytes = unsafe { generate_random_bytes(16)? };
-    let random_bytes: Vec<u8> = serde_json::from_str(&json_bytes)
-        .map_err(|e| Error::msg(format!("Failed to parse random bytes: {}", e)))?;
-    Ok(format!("{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
-        random_bytes[0], random_bytes[1], random_bytes[2], random_bytes[3],
-        random_bytes[4], random_bytes[5],
-        random_bytes[6], random_bytes[7],
-        random_bytes[8], random_bytes[9],
-        random_bytes[10], random_bytes[11], random_bytes[12], random_bytes[13], random_bytes[14], random_bytes[15]
-    ))
+/// Generate an ID of the given kind using the host's ID strategy
+fn generate_id(kind: &str) -> FnResult<String> {
+    Ok(unsafe { new_id(kind.to_string())? })
+}
+
+/// Run `f` in a transaction, committing it if `f` succeeds and rolling it
+/// back otherwise; the host also rolls it back if the call ends first
+fn in_transaction<T>(f: impl FnOnce() -> FnResult<Result<T, String>>) -> FnResult<Result<T, String>> {
+    let begun: DbResponse<TransactionHandle> = serde_json::from_str(&unsafe { db_begin()? })
+        .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+    let handle = match begun.data {
+        Some(handle) if begun.success => serde_json::to_string(&handle)?,
+        _ => return Ok(Err(begun.error.unwrap_or_else(|| "Failed to open a transaction".to_string()))),
+    };
+    
+    let result = f();
+    if !matches!(result, Ok(Ok(_))) {
+        let _ = unsafe { db_rollback(handle) };
+        return result;
+    }
+    let committed: DbResponse<()> = serde_json::from_str(&unsafe { db_commit(handle)? })
+        .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+    if !committed.success {
+        return Ok(Err(committed.error.unwrap_or_else(|| "Failed to commit".to_string())));
+    }
+    result
+}
+
+/// Hash a password with Argon2, salted with random bytes from the host
+fn hash_password(password: &str) -> FnResult<String> {
+    let salt_bytes = unsafe { generate_random_bytes(16)? };
+    let salt_array: [u8; 16] = salt_bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| Error::msg(format!("Invalid salt length: expected 16, got {}", bytes.len())))?;
+    
+    let salt = SaltString::encode_b64(&salt_array)
+        .map_err(|e| Error::msg(format!("Salt encoding error: {}", e)))?;
+    
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| Error::msg(format!("Password hashing failed: {}", e)))?
+        .to_string())
 }
 
 // ============================================================================
@@ -94,6 +136,9 @@ pub struct LoginResponse {
     pub success: bool,
     pub session_id: Option<String>,
     pub user: Option<UserInfo>,
+    /// Set when the user signed in with a temporary password; the app
+    /// refuses everything but `change_password` and `logout` until it is changed
+    pub password_reset_required: bool,
     pub message: String,
 }
 
@@ -121,6 +166,13 @@ pub struct LogoutRequest {
     pub session_id: String,
 }
 
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub session_id: String,
+    pub current_password: String,
+    pub new_password: String,
+}
+
 #[derive(Serialize)]
 pub struct GenericResponse {
     pub success: bool,
@@ -135,12 +187,19 @@ struct DbResponse<T> {
     error: Option<String>,
 }
 
+#[derive(Deserialize, Serialize)]
+struct TransactionHandle {
+    transaction_id: String,
+}
+
 #[derive(Deserialize, Serialize)]
 struct User {
     uuid: String,
     name: String,
     email: String,
     password_hash: String,
+    #[serde(default)]
+    password_reset_required: bool,
 }
 
 #[derive(Deserialize)]
@@ -194,31 +253,10 @@ pub fn signup(Json(req): Json<SignupRequest>) -> FnResult<Json<SignupResponse>>
         }));
     }
     
-    // Generate salt using random bytes from host (returns JSON array string)
-    let json_salt = unsafe { generate_random_bytes(16)? };
-    let salt_bytes: Vec<u8> = serde_json::from_str(&json_salt)
-        .map_err(|e| Error::msg(format!("Failed to parse salt bytes: {}", e)))?;
-    
-    // Ensure we have exactly 16 bytes
-    if salt_bytes.len() != 16 {
-        return Err(Error::msg(format!("Invalid salt length: expected 16, got {}", salt_bytes.len())).into());
-    }
-    
-    // Convert Vec<u8> to [u8; 16] for SaltString
-    let mut salt_array = [0u8; 16];
-    salt_array.copy_from_slice(&salt_bytes);
-    
-    let salt = SaltString::encode_b64(&salt_array)
-        .map_err(|e| Error::msg(format!("Salt encoding error: {}", e)))?;
-    
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|e| Error::msg(format!("Password hashing failed: {}", e)))?
-        .to_string();
+    let password_hash = hash_password(&req.password)?;
     
     // Generate UUID for user
-    let user_uuid = generate_uuid()?;
+    let user_uuid = generate_id("user")?;
     let created_at = unsafe { get_timestamp()? };
     
     // Create user in database
@@ -230,40 +268,54 @@ pub fn signup(Json(req): Json<SignupRequest>) -> FnResult<Json<SignupResponse>>
         "created_at": created_at,
     });
     
-    let result = unsafe {
-        db_create_user(create_request.to_string())
-            .map_err(|e| Error::msg(format!("Database error: {}", e)))?
-    };
-    
-    let db_resp: DbResponse<i64> = serde_json::from_str(&result)
-        .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+    // Create the user and its audit log together or not at all
+    let created = in_transaction(|| {
+        let result = unsafe {
+            db_create_user(create_request.to_string())
+                .map_err(|e| Error::msg(format!("Database error: {}", e)))?
+        };
+        
+        let db_resp: DbResponse<i64> = serde_json::from_str(&result)
+            .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+        
+        if !db_resp.success {
+            return Ok(Err(db_resp.error.unwrap_or_else(|| "Failed to create user".to_string())));
+        }
+        
+        // Create audit log for signup
+        let audit_request = serde_json::json!({
+            "user_uuid": user_uuid,
+            "action": "user.signup",
+            "resource_type": "user",
+            "resource_id": user_uuid.clone(),
+            "metadata": serde_json::json!({
+                "name": req.name,
+                "email": req.email,
+            }).to_string(),
+            "ip_address": None::<String>,
+            "user_agent": None::<String>,
+        });
+        
+        let result = unsafe {
+            db_create_audit_log(audit_request.to_string())
+                .map_err(|e| Error::msg(format!("Database error: {}", e)))?
+        };
+        let db_resp: DbResponse<()> = serde_json::from_str(&result)
+            .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+        if !db_resp.success {
+            return Ok(Err(db_resp.error.unwrap_or_else(|| "Failed to record the signup".to_string())));
+        }
+        Ok(Ok(()))
+    })?;
     
-    if !db_resp.success {
+    if let Err(message) = created {
         return Ok(Json(SignupResponse {
             success: false,
             user_uuid: None,
-            message: db_resp.error.unwrap_or_else(|| "Failed to create user".to_string()),
+            message,
         }));
     }
     
-    // Create audit log for signup
-    let audit_request = serde_json::json!({
-        "user_uuid": user_uuid,
-        "action": "user.signup",
-        "resource_type": "user",
-        "resource_id": user_uuid.clone(),
-        "metadata": serde_json::json!({
-            "name": req.name,
-            "email": req.email,
-        }).to_string(),
-        "ip_address": None::<String>,
-        "user_agent": None::<String>,
-    });
-    
-    let _ = unsafe {
-        db_create_audit_log(audit_request.to_string())
-    };
-    
     Ok(Json(SignupResponse {
         success: true,
         user_uuid: Some(user_uuid),
@@ -310,6 +362,7 @@ pub fn login(Json(req): Json<LoginRequest>) -> FnResult<Json<LoginResponse>> {
                 success: false,
                 session_id: None,
                 user: None,
+                password_reset_required: false,
                 message: "Invalid email or password".to_string(),
             }));
         }
@@ -342,12 +395,13 @@ pub fn login(Json(req): Json<LoginRequest>) -> FnResult<Json<LoginResponse>> {
             success: false,
             session_id: None,
             user: None,
+            password_reset_required: false,
             message: "Invalid email or password".to_string(),
         }));
     }
     
     // Create session
-    let session_id = generate_uuid()?;
+    let session_id = generate_id("session")?;
     let created_at = unsafe { get_timestamp()? };
     let expires_at = created_at + (7 * 24 * 60 * 60); // 7 days from now
     
@@ -371,6 +425,7 @@ pub fn login(Json(req): Json<LoginRequest>) -> FnResult<Json<LoginResponse>> {
             success: false,
             session_id: None,
             user: None,
+            password_reset_required: false,
             message: "Failed to create session".to_string(),
         }));
     }
@@ -400,7 +455,12 @@ pub fn login(Json(req): Json<LoginRequest>) -> FnResult<Json<LoginResponse>> {
             name: user.name,
             email: user.email,
         }),
-        message: "Login successful".to_string(),
+        password_reset_required: user.password_reset_required,
+        message: if user.password_reset_required {
+            "Password change required".to_string()
+        } else {
+            "Login successful".to_string()
+        },
     }))
 }
 
@@ -502,6 +562,106 @@ pub fn logout(Json(req): Json<LogoutRequest>) -> FnResult<Json<GenericResponse>>
     }))
 }
 
+/// Change the password of a session's user, e.g. the temporary one an
+/// imported account signs in with, which clears `password_reset_required`
+#[plugin_fn]
+pub fn change_password(Json(req): Json<ChangePasswordRequest>) -> FnResult<Json<GenericResponse>> {
+    let failure = |message: &str| Ok(Json(GenericResponse { success: false, message: message.to_string() }));
+
+    if req.new_password.len() < 8 {
+        return failure("Password must be at least 8 characters");
+    }
+    if req.new_password == req.current_password {
+        return failure("The new password must differ from the current one");
+    }
+
+    let session = unsafe {
+        match db_get_session(req.session_id.clone()) {
+            Ok(response) => {
+                let db_resp: DbResponse<Session> = serde_json::from_str(&response)
+                    .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+                db_resp.data
+            }
+            Err(_) => None,
+        }
+    };
+    let now = unsafe { get_timestamp()? };
+    let session = match session {
+        Some(s) if s.expires_at >= now => s,
+        _ => return failure("Session is invalid or expired"),
+    };
+
+    let user = unsafe {
+        match db_get_user_by_uuid(session.user_uuid.clone()) {
+            Ok(response) => {
+                let db_resp: DbResponse<User> = serde_json::from_str(&response)
+                    .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+                db_resp.data
+            }
+            Err(_) => None,
+        }
+    };
+    let Some(user) = user else {
+        return failure("User not found");
+    };
+
+    let parsed_hash = PasswordHash::new(&user.password_hash)
+        .map_err(|e| Error::msg(format!("Invalid password hash: {}", e)))?;
+    if Argon2::default().verify_password(req.current_password.as_bytes(), &parsed_hash).is_err() {
+        return failure("Current password is incorrect");
+    }
+
+    let update_request = serde_json::json!({
+        "uuid": user.uuid,
+        "password_hash": hash_password(&req.new_password)?,
+        "updated_at": now,
+    });
+
+    // Change the password and record it together or not at all
+    let changed = in_transaction(|| {
+        let result = unsafe {
+            db_update_user_password(update_request.to_string())
+                .map_err(|e| Error::msg(format!("Database error: {}", e)))?
+        };
+        let db_resp: DbResponse<bool> = serde_json::from_str(&result)
+            .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+        if !db_resp.success {
+            return Ok(Err(db_resp.error.unwrap_or_else(|| "Failed to change the password".to_string())));
+        }
+
+        let audit_request = serde_json::json!({
+            "user_uuid": user.uuid.clone(),
+            "action": "user.password.changed",
+            "resource_type": "user",
+            "resource_id": user.uuid.clone(),
+            "metadata": serde_json::json!({
+                "was_temporary": user.password_reset_required,
+            }).to_string(),
+            "ip_address": None::<String>,
+            "user_agent": None::<String>,
+        });
+        let result = unsafe {
+            db_create_audit_log(audit_request.to_string())
+                .map_err(|e| Error::msg(format!("Database error: {}", e)))?
+        };
+        let db_resp: DbResponse<()> = serde_json::from_str(&result)
+            .map_err(|e| Error::msg(format!("Failed to parse response: {}", e)))?;
+        if !db_resp.success {
+            return Ok(Err(db_resp.error.unwrap_or_else(|| "Failed to record the password change".to_string())));
+        }
+        Ok(Ok(()))
+    })?;
+
+    if let Err(message) = changed {
+        return failure(&message);
+    }
+
+    Ok(Json(GenericResponse {
+        success: true,
+        message: "Password changed".to_string(),
+    }))
+}
+
 /// Get plugin info
 #[plugin_fn]
 pub fn get_info(Json(_): Json<serde_json::Value>) -> FnResult<Json<serde_json::Value>> {
@@ -525,6 +685,10 @@ pub fn get_info(Json(_): Json<serde_json::Value>) -> FnResult<Json<serde_json::V
             {
                 "name": "logout",
                 "description": "End user session"
+            },
+            {
+                "name": "change_password",
+                "description": "Replace the signed-in user's password"
             }
         ]
     })))
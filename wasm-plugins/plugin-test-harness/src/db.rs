@@ -0,0 +1,556 @@
+//! Mock `db_*` host functions backed by an in-memory SQLite database
+//!
+//! Each function takes and returns the same JSON as the app's version, so a
+//! plugin can't tell them apart. The schema covers the tables those
+//! functions touch; app-only rules such as user roles are left out.
+
+use anyhow::{Context, Result};
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+pub(crate) const SCHEMA: &str = "
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        uuid TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL UNIQUE,
+        email TEXT NOT NULL UNIQUE,
+        password_hash TEXT NOT NULL,
+        email_verified INTEGER NOT NULL DEFAULT 0,
+        avatar TEXT,
+        bio TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        password_reset_required INTEGER NOT NULL DEFAULT 0
+    );
+
+    CREATE TABLE sessions (
+        id TEXT PRIMARY KEY,
+        user_uuid TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE email_verification_tokens (
+        token TEXT PRIMARY KEY,
+        user_uuid TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE password_reset_tokens (
+        token TEXT PRIMARY KEY,
+        user_uuid TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE audit_logs (
+        id TEXT PRIMARY KEY,
+        user_uuid TEXT NOT NULL,
+        action TEXT NOT NULL,
+        resource_type TEXT,
+        resource_id TEXT,
+        metadata TEXT,
+        ip_address TEXT,
+        user_agent TEXT,
+        created_at INTEGER NOT NULL
+    );
+";
+
+/// Implementation of one host function: the connection and the raw input
+type Handler = fn(&Connection, &str) -> Result<Value>;
+
+struct DbFunction {
+    database: Arc<Mutex<Connection>>,
+    handler: Handler,
+}
+
+/// Wrap `handler` in a host function returning the app's response envelope
+fn db_function(name: &str, database: &Arc<Mutex<Connection>>, handler: Handler) -> Function {
+    let data = DbFunction {
+        database: database.clone(),
+        handler,
+    };
+    Function::new(
+        name,
+        [PTR],
+        [PTR],
+        UserData::new(data),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<DbFunction>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let data = user_data.get()?;
+            let data = data.lock().unwrap();
+            let result = (data.handler)(&data.database.lock().unwrap(), &input);
+            let response = match result {
+                Ok(value) => json!({ "success": true, "data": value, "error": null }),
+                Err(e) => json!({ "success": false, "data": null, "error": format!("{:#}", e) }),
+            };
+            plugin.memory_set_val(&mut outputs[0], response.to_string())?;
+            Ok(())
+        },
+    )
+}
+
+/// All mock database host functions
+pub(crate) fn functions(database: &Arc<Mutex<Connection>>) -> Vec<Function> {
+    let handlers: &[(&str, Handler)] = &[
+        ("db_create_user", create_user),
+        ("db_get_user_by_email", get_user_by_email),
+        ("db_get_user_by_uuid", get_user_by_uuid),
+        ("db_list_users", list_users),
+        ("db_update_user_password", update_user_password),
+        ("db_update_user_email_verified", update_user_email_verified),
+        ("db_update_user_profile", update_user_profile),
+        ("db_delete_user", delete_user),
+        ("db_create_session", create_session),
+        ("db_get_session", get_session),
+        ("db_delete_session", delete_session),
+        ("db_delete_user_sessions", delete_user_sessions),
+        ("db_cleanup_expired_sessions", cleanup_expired_sessions),
+        ("db_create_email_verification_token", create_email_verification_token),
+        ("db_get_email_verification_token", get_email_verification_token),
+        ("db_delete_email_verification_token", delete_email_verification_token),
+        ("db_create_password_reset_token", create_password_reset_token),
+        ("db_get_password_reset_token", get_password_reset_token),
+        ("db_delete_password_reset_token", delete_password_reset_token),
+        ("db_delete_user_password_reset_tokens", delete_user_password_reset_tokens),
+        ("db_create_audit_log", create_audit_log),
+        ("db_get_user_audit_logs", get_user_audit_logs),
+        ("db_get_audit_logs_filtered", get_audit_logs_filtered),
+        ("db_count_user_audit_logs", count_user_audit_logs),
+        ("db_delete_old_audit_logs", delete_old_audit_logs),
+    ];
+    handlers
+        .iter()
+        .map(|(name, handler)| db_function(name, database, *handler))
+        .collect()
+}
+
+fn parse<'a, T: Deserialize<'a>>(input: &'a str) -> Result<T> {
+    serde_json::from_str(input).context("JSON parse error")
+}
+
+// ============================================================================
+// Users
+// ============================================================================
+
+const USER_COLUMNS: &str = "id, uuid, name, email, password_hash, email_verified, avatar, bio,
+                            created_at, updated_at, password_reset_required";
+
+fn user_json(row: &Row) -> rusqlite::Result<Value> {
+    Ok(json!({
+        "id": row.get::<_, i64>(0)?,
+        "uuid": row.get::<_, String>(1)?,
+        "name": row.get::<_, String>(2)?,
+        "email": row.get::<_, String>(3)?,
+        "password_hash": row.get::<_, String>(4)?,
+        "email_verified": row.get::<_, bool>(5)?,
+        "avatar": row.get::<_, Option<String>>(6)?,
+        "bio": row.get::<_, Option<String>>(7)?,
+        "created_at": row.get::<_, i64>(8)?,
+        "updated_at": row.get::<_, i64>(9)?,
+        "password_reset_required": row.get::<_, bool>(10)?,
+    }))
+}
+
+fn get_user_where(conn: &Connection, column: &str, value: &str) -> Result<Value> {
+    let sql = format!("SELECT {} FROM users WHERE {} = ?1", USER_COLUMNS, column);
+    let user = conn.query_row(&sql, params![value], user_json).optional()?;
+    Ok(user.unwrap_or(Value::Null))
+}
+
+#[derive(Deserialize)]
+struct CreateUserRequest {
+    uuid: String,
+    name: String,
+    email: String,
+    password_hash: String,
+    created_at: i64,
+}
+
+fn create_user(conn: &Connection, input: &str) -> Result<Value> {
+    let r: CreateUserRequest = parse(input)?;
+    conn.execute(
+        "INSERT INTO users (uuid, name, email, password_hash, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![r.uuid, r.name, r.email, r.password_hash, r.created_at],
+    )?;
+    Ok(json!(conn.last_insert_rowid()))
+}
+
+fn get_user_by_email(conn: &Connection, email: &str) -> Result<Value> {
+    get_user_where(conn, "email", email)
+}
+
+fn get_user_by_uuid(conn: &Connection, uuid: &str) -> Result<Value> {
+    get_user_where(conn, "uuid", uuid)
+}
+
+#[derive(Deserialize)]
+struct ListUsersRequest {
+    search: Option<String>,
+    sort: Option<String>,
+    #[serde(default)]
+    descending: bool,
+    page: Option<u32>,
+    limit: Option<u32>,
+}
+
+fn list_users(conn: &Connection, input: &str) -> Result<Value> {
+    let r: ListUsersRequest = parse(input)?;
+    let (page, limit) = (r.page.unwrap_or(1), r.limit.unwrap_or(25));
+    anyhow::ensure!(page > 0, "Pages start at 1");
+    anyhow::ensure!((1..=100).contains(&limit), "The limit must be 1 to 100 users");
+    let column = match r.sort.as_deref().unwrap_or("created_at") {
+        column @ ("name" | "email" | "created_at") => column,
+        other => anyhow::bail!("JSON parse error: unknown sort '{}'", other),
+    };
+    let direction = if r.descending { "DESC" } else { "ASC" };
+    let pattern = r
+        .search
+        .filter(|search| !search.is_empty())
+        .map(|search| format!("%{}%", search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+    let filter = "?1 IS NULL OR name LIKE ?1 ESCAPE '\\' OR email LIKE ?1 ESCAPE '\\'";
+    let total: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM users WHERE {}", filter), params![pattern], |row| row.get(0))?;
+    let sql = format!(
+        "SELECT {} FROM users WHERE {} ORDER BY {} {}, id {} LIMIT ?2 OFFSET ?3",
+        USER_COLUMNS, filter, column, direction, direction
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let users = stmt
+        .query_map(params![pattern, limit, (page - 1).saturating_mul(limit)], user_json)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(json!({ "users": users, "total": total }))
+}
+
+#[derive(Deserialize)]
+struct UpdatePasswordRequest {
+    uuid: String,
+    password_hash: String,
+    updated_at: i64,
+}
+
+fn update_user_password(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UpdatePasswordRequest = parse(input)?;
+    conn.execute(
+        "UPDATE users SET password_hash = ?1, updated_at = ?2, password_reset_required = 0 WHERE uuid = ?3",
+        params![r.password_hash, r.updated_at, r.uuid],
+    )?;
+    Ok(json!(true))
+}
+
+#[derive(Deserialize)]
+struct UpdateEmailVerifiedRequest {
+    uuid: String,
+    verified: bool,
+}
+
+fn update_user_email_verified(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UpdateEmailVerifiedRequest = parse(input)?;
+    conn.execute(
+        "UPDATE users SET email_verified = ?1 WHERE uuid = ?2",
+        params![r.verified, r.uuid],
+    )?;
+    Ok(Value::Null)
+}
+
+#[derive(Deserialize)]
+struct UpdateUserProfileRequest {
+    uuid: String,
+    name: Option<String>,
+    avatar: Option<String>,
+    bio: Option<String>,
+}
+
+fn update_user_profile(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UpdateUserProfileRequest = parse(input)?;
+    conn.execute(
+        "UPDATE users SET name = COALESCE(?1, name), bio = COALESCE(?2, bio), avatar = COALESCE(?3, avatar),
+                          updated_at = strftime('%s', 'now')
+         WHERE uuid = ?4",
+        params![r.name, r.bio, r.avatar, r.uuid],
+    )?;
+    Ok(Value::Null)
+}
+
+#[derive(Deserialize)]
+struct UserRequest {
+    uuid: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteUserRequest {
+    uuid: String,
+    #[serde(default)]
+    delete_audit_logs: bool,
+}
+
+/// Roles, API keys and the last-admin check aren't mocked, so their counts are always 0
+fn delete_user(conn: &Connection, input: &str) -> Result<Value> {
+    let r: DeleteUserRequest = parse(input)?;
+    let tx = conn.unchecked_transaction()?;
+    let delete = |table: &str| tx.execute(&format!("DELETE FROM {} WHERE user_uuid = ?1", table), params![r.uuid]);
+    let deleted = json!({
+        "sessions": delete("sessions")?,
+        "email_verification_tokens": delete("email_verification_tokens")?,
+        "password_reset_tokens": delete("password_reset_tokens")?,
+        "roles": 0,
+        "api_keys": 0,
+        "audit_logs": if r.delete_audit_logs { delete("audit_logs")? } else { 0 },
+    });
+    anyhow::ensure!(
+        tx.execute("DELETE FROM users WHERE uuid = ?1", params![r.uuid])? > 0,
+        "No user with UUID '{}'",
+        r.uuid
+    );
+    tx.commit()?;
+    Ok(deleted)
+}
+
+// ============================================================================
+// Sessions and tokens
+// ============================================================================
+
+#[derive(Deserialize)]
+struct CreateExpiringRequest {
+    #[serde(alias = "token")]
+    id: String,
+    user_uuid: String,
+    created_at: i64,
+    expires_at: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    token: String,
+}
+
+fn insert_expiring(conn: &Connection, table: &str, key: &str, input: &str) -> Result<()> {
+    let r: CreateExpiringRequest = parse(input)?;
+    let sql = format!(
+        "INSERT INTO {} ({}, user_uuid, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        table, key
+    );
+    conn.execute(&sql, params![r.id, r.user_uuid, r.created_at, r.expires_at])?;
+    Ok(())
+}
+
+/// An unexpired session or token, or null
+fn get_expiring(conn: &Connection, table: &str, key: &str, value: &str) -> Result<Value> {
+    let sql = format!(
+        "SELECT {key}, user_uuid, created_at, expires_at FROM {table}
+         WHERE {key} = ?1 AND expires_at > strftime('%s', 'now')"
+    );
+    let record = conn
+        .query_row(&sql, params![value], |row| {
+            Ok(json!({
+                key: row.get::<_, String>(0)?,
+                "user_uuid": row.get::<_, String>(1)?,
+                "created_at": row.get::<_, i64>(2)?,
+                "expires_at": row.get::<_, i64>(3)?,
+            }))
+        })
+        .optional()?;
+    Ok(record.unwrap_or(Value::Null))
+}
+
+fn create_session(conn: &Connection, input: &str) -> Result<Value> {
+    insert_expiring(conn, "sessions", "id", input)?;
+    Ok(json!(true))
+}
+
+fn get_session(conn: &Connection, session_id: &str) -> Result<Value> {
+    get_expiring(conn, "sessions", "id", session_id)
+}
+
+fn delete_session(conn: &Connection, session_id: &str) -> Result<Value> {
+    conn.execute("DELETE FROM sessions WHERE id = ?1", params![session_id])?;
+    Ok(json!(true))
+}
+
+fn delete_user_sessions(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UserRequest = parse(input)?;
+    conn.execute("DELETE FROM sessions WHERE user_uuid = ?1", params![r.uuid])?;
+    Ok(Value::Null)
+}
+
+fn cleanup_expired_sessions(conn: &Connection, _input: &str) -> Result<Value> {
+    let deleted = conn.execute("DELETE FROM sessions WHERE expires_at <= strftime('%s', 'now')", [])?;
+    Ok(json!(deleted))
+}
+
+fn create_email_verification_token(conn: &Connection, input: &str) -> Result<Value> {
+    insert_expiring(conn, "email_verification_tokens", "token", input)?;
+    Ok(Value::Null)
+}
+
+fn get_email_verification_token(conn: &Connection, input: &str) -> Result<Value> {
+    let r: TokenRequest = parse(input)?;
+    get_expiring(conn, "email_verification_tokens", "token", &r.token)
+}
+
+fn delete_email_verification_token(conn: &Connection, input: &str) -> Result<Value> {
+    let r: TokenRequest = parse(input)?;
+    conn.execute("DELETE FROM email_verification_tokens WHERE token = ?1", params![r.token])?;
+    Ok(Value::Null)
+}
+
+fn create_password_reset_token(conn: &Connection, input: &str) -> Result<Value> {
+    insert_expiring(conn, "password_reset_tokens", "token", input)?;
+    Ok(Value::Null)
+}
+
+fn get_password_reset_token(conn: &Connection, input: &str) -> Result<Value> {
+    let r: TokenRequest = parse(input)?;
+    get_expiring(conn, "password_reset_tokens", "token", &r.token)
+}
+
+fn delete_password_reset_token(conn: &Connection, input: &str) -> Result<Value> {
+    let r: TokenRequest = parse(input)?;
+    conn.execute("DELETE FROM password_reset_tokens WHERE token = ?1", params![r.token])?;
+    Ok(Value::Null)
+}
+
+fn delete_user_password_reset_tokens(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UserRequest = parse(input)?;
+    conn.execute("DELETE FROM password_reset_tokens WHERE user_uuid = ?1", params![r.uuid])?;
+    Ok(Value::Null)
+}
+
+// ============================================================================
+// Audit logs
+// ============================================================================
+
+#[derive(Deserialize)]
+struct CreateAuditLogRequest {
+    id: String,
+    user_uuid: String,
+    action: String,
+    resource_type: Option<String>,
+    resource_id: Option<String>,
+    metadata: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    created_at: i64,
+}
+
+#[derive(Deserialize)]
+struct AuditLogFilter {
+    user_uuid: Option<String>,
+    action: Option<String>,
+    resource_type: Option<String>,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+    limit: i32,
+    offset: i32,
+}
+
+#[derive(Deserialize)]
+struct DeleteOldAuditLogsRequest {
+    older_than: i64,
+}
+
+fn create_audit_log(conn: &Connection, input: &str) -> Result<Value> {
+    let r: CreateAuditLogRequest = parse(input)?;
+    conn.execute(
+        "INSERT INTO audit_logs (id, user_uuid, action, resource_type, resource_id, metadata,
+                                 ip_address, user_agent, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            r.id,
+            r.user_uuid,
+            r.action,
+            r.resource_type,
+            r.resource_id,
+            r.metadata,
+            r.ip_address,
+            r.user_agent,
+            r.created_at
+        ],
+    )?;
+    Ok(Value::Null)
+}
+
+/// Audit logs matching every filter that is set, newest first
+fn query_audit_logs(conn: &Connection, filter: &AuditLogFilter) -> Result<Value> {
+    let mut stmt = conn.prepare(
+        "SELECT id, user_uuid, action, resource_type, resource_id, metadata, ip_address, user_agent, created_at
+         FROM audit_logs
+         WHERE (?1 IS NULL OR user_uuid = ?1)
+           AND (?2 IS NULL OR action = ?2)
+           AND (?3 IS NULL OR resource_type = ?3)
+           AND (?4 IS NULL OR created_at >= ?4)
+           AND (?5 IS NULL OR created_at <= ?5)
+         ORDER BY created_at DESC LIMIT ?6 OFFSET ?7",
+    )?;
+    let logs = stmt
+        .query_map(
+            params![
+                filter.user_uuid,
+                filter.action,
+                filter.resource_type,
+                filter.start_time,
+                filter.end_time,
+                filter.limit,
+                filter.offset
+            ],
+            |row| {
+                Ok(json!({
+                    "id": row.get::<_, String>(0)?,
+                    "user_uuid": row.get::<_, String>(1)?,
+                    "action": row.get::<_, String>(2)?,
+                    "resource_type": row.get::<_, Option<String>>(3)?,
+                    "resource_id": row.get::<_, Option<String>>(4)?,
+                    "metadata": row.get::<_, Option<String>>(5)?,
+                    "ip_address": row.get::<_, Option<String>>(6)?,
+                    "user_agent": row.get::<_, Option<String>>(7)?,
+                    "created_at": row.get::<_, i64>(8)?,
+                }))
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Value::Array(logs))
+}
+
+fn get_user_audit_logs(conn: &Connection, input: &str) -> Result<Value> {
+    #[derive(Deserialize)]
+    struct Request {
+        user_uuid: String,
+        limit: i32,
+        offset: i32,
+    }
+    let r: Request = parse(input)?;
+    let filter = AuditLogFilter {
+        user_uuid: Some(r.user_uuid),
+        action: None,
+        resource_type: None,
+        start_time: None,
+        end_time: None,
+        limit: r.limit,
+        offset: r.offset,
+    };
+    query_audit_logs(conn, &filter)
+}
+
+fn get_audit_logs_filtered(conn: &Connection, input: &str) -> Result<Value> {
+    query_audit_logs(conn, &parse(input)?)
+}
+
+fn count_user_audit_logs(conn: &Connection, input: &str) -> Result<Value> {
+    let r: UserRequest = parse(input)?;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM audit_logs WHERE user_uuid = ?1",
+        params![r.uuid],
+        |row| row.get(0),
+    )?;
+    Ok(json!(count))
+}
+
+fn delete_old_audit_logs(conn: &Connection, input: &str) -> Result<Value> {
+    let r: DeleteOldAuditLogsRequest = parse(input)?;
+    let deleted = conn.execute("DELETE FROM audit_logs WHERE created_at < ?1", params![r.older_than])?;
+    Ok(json!(deleted))
+}
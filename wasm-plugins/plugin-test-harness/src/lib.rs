@@ -0,0 +1,198 @@
+//! Integration tests for plugins without the app
+//!
+//! [`MockHost`] loads a built plugin with stand-ins for the app's host
+//! functions: the `db_*` functions run against an in-memory SQLite database,
+//! `log` and `stream_chunk` output is captured for assertions, and the
+//! utility functions behave as in the app. Anything else a plugin imports can
+//! be supplied with [`MockHost::with_function`].
+//!
+//! ```no_run
+//! use plugin_test_harness::MockHost;
+//! use serde_json::{json, Value};
+//!
+//! #[test]
+//! fn signup_creates_user() -> anyhow::Result<()> {
+//!     let host = MockHost::new()?;
+//!     let mut plugin = host.load_dir(env!("CARGO_MANIFEST_DIR"))?;
+//!
+//!     let output: Value = plugin.call_json("signup", &json!({"name": "ada", "email": "ada@example.com", "password": "hunter22"}))?;
+//!     assert_eq!(output["success"], true);
+//!
+//!     let users: i64 = host.database().query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
+//!     assert_eq!(users, 1);
+//!     Ok(())
+//! }
+//! ```
+
+mod db;
+mod utility;
+
+use anyhow::{Context, Result};
+use extism::{Function, Manifest, Plugin, PluginBuilder, Wasm};
+use rusqlite::Connection;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub use utility::{LogEntry, PLUGIN_DATA_GUEST_PATH};
+
+/// The parts of `plugin.json` needed to load a plugin
+#[derive(Deserialize)]
+struct PluginManifest {
+    wasm_module: WasmModules,
+    #[serde(default)]
+    wasm_config: WasmConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WasmModules {
+    Single(String),
+    Multiple(Vec<WasmModule>),
+}
+
+#[derive(Deserialize)]
+struct WasmModule {
+    name: Option<String>,
+    path: String,
+}
+
+#[derive(Default, Deserialize)]
+struct WasmConfig {
+    #[serde(default)]
+    config: HashMap<String, String>,
+    #[serde(default)]
+    fuel_limit: Option<u64>,
+    #[serde(default)]
+    wasi: bool,
+}
+
+/// An in-process stand-in for the app's plugin host
+pub struct MockHost {
+    database: Arc<Mutex<Connection>>,
+    logs: Arc<Mutex<Vec<LogEntry>>>,
+    chunks: Arc<Mutex<Vec<Vec<u8>>>>,
+    config: HashMap<String, String>,
+    functions: Vec<Function>,
+}
+
+impl MockHost {
+    /// A host with an empty database
+    pub fn new() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(db::SCHEMA).context("Failed to create mock database schema")?;
+        Ok(Self {
+            database: Arc::new(Mutex::new(conn)),
+            logs: Arc::new(Mutex::new(Vec::new())),
+            chunks: Arc::new(Mutex::new(Vec::new())),
+            config: HashMap::new(),
+            functions: Vec::new(),
+        })
+    }
+
+    /// Set a config value for plugins loaded afterwards, overriding `plugin.json`
+    pub fn with_config(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.insert(key.into(), value.into());
+        self
+    }
+
+    /// Add a host function, replacing a mock of the same name
+    pub fn with_function(mut self, function: Function) -> Self {
+        self.functions.retain(|f| f.name() != function.name());
+        self.functions.push(function);
+        self
+    }
+
+    /// The mock database, to seed it before a call or check it afterwards
+    pub fn database(&self) -> MutexGuard<'_, Connection> {
+        self.database.lock().unwrap()
+    }
+
+    /// Everything plugins logged so far
+    pub fn logs(&self) -> Vec<LogEntry> {
+        self.logs.lock().unwrap().clone()
+    }
+
+    /// Chunks streamed since the last call to this method
+    pub fn take_chunks(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.chunks.lock().unwrap())
+    }
+
+    /// Load a plugin directory the way the app does, from its `plugin.json`
+    pub fn load_dir(&self, dir: impl AsRef<Path>) -> Result<TestPlugin> {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join("plugin.json");
+        let content = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read {:?}", manifest_path))?;
+        let manifest: PluginManifest =
+            serde_json::from_str(&content).with_context(|| format!("Invalid manifest {:?}", manifest_path))?;
+
+        let modules = match manifest.wasm_module {
+            WasmModules::Single(path) => vec![Wasm::file(dir.join(path))],
+            WasmModules::Multiple(modules) => modules
+                .into_iter()
+                .map(|module| {
+                    let wasm = Wasm::file(dir.join(module.path));
+                    match module.name {
+                        Some(name) => wasm.with_name(name),
+                        None => wasm,
+                    }
+                })
+                .collect(),
+        };
+        let mut config = manifest.wasm_config.config;
+        config.extend(self.config.clone());
+        self.build(
+            Manifest::new(modules).with_config(config.into_iter()),
+            manifest.wasm_config.wasi,
+            manifest.wasm_config.fuel_limit,
+        )
+    }
+
+    /// Load a single built module, e.g. `target/wasm32-unknown-unknown/release/my_plugin.wasm`
+    pub fn load(&self, wasm: impl AsRef<Path>) -> Result<TestPlugin> {
+        let manifest = Manifest::new([Wasm::file(wasm.as_ref())]).with_config(self.config.clone().into_iter());
+        self.build(manifest, false, None)
+    }
+
+    fn build(&self, manifest: Manifest, wasi: bool, fuel_limit: Option<u64>) -> Result<TestPlugin> {
+        let mut functions = utility::functions(&self.logs, &self.chunks);
+        functions.extend(db::functions(&self.database));
+        functions.retain(|f| !self.functions.iter().any(|custom| custom.name() == f.name()));
+        functions.extend(self.functions.iter().cloned());
+
+        let mut builder = PluginBuilder::new(manifest).with_wasi(wasi).with_functions(functions);
+        if let Some(fuel) = fuel_limit {
+            builder = builder.with_fuel_limit(fuel);
+        }
+        let plugin = builder.build().context("Failed to load plugin")?;
+        Ok(TestPlugin { plugin })
+    }
+}
+
+/// A loaded plugin
+pub struct TestPlugin {
+    plugin: Plugin,
+}
+
+impl TestPlugin {
+    /// Whether the plugin exports `function`
+    pub fn function_exists(&self, function: &str) -> bool {
+        self.plugin.function_exists(function)
+    }
+
+    /// Call `function` with raw bytes
+    pub fn call(&mut self, function: &str, input: &[u8]) -> Result<Vec<u8>> {
+        self.plugin
+            .call::<&[u8], Vec<u8>>(function, input)
+            .with_context(|| format!("Plugin function '{}' failed", function))
+    }
+
+    /// Call `function` with JSON input, parsing its JSON output
+    pub fn call_json<I: Serialize, O: DeserializeOwned>(&mut self, function: &str, input: &I) -> Result<O> {
+        let output = self.call(function, &serde_json::to_vec(input)?)?;
+        serde_json::from_slice(&output).with_context(|| format!("Output of '{}' is not the expected JSON", function))
+    }
+}
@@ -0,0 +1,151 @@
+//! Mock utility host functions: randomness, clocks, IDs, logging and streaming
+
+use extism::{CurrentPlugin, Function, UserData, Val, PTR};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the app mounts a plugin's data directory inside the guest
+pub const PLUGIN_DATA_GUEST_PATH: &str = "/data";
+
+/// Levels `log` accepts
+const LOG_LEVELS: &[&str] = &["trace", "debug", "info", "warn", "warning", "error"];
+
+/// ID kinds `new_id` accepts
+const ID_KINDS: &[&str] = &["user", "session", "token", "audit_log", "job", "execution", "schedule", "other"];
+
+/// A message a plugin logged through the `log` host function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub level: String,
+    pub message: String,
+    #[serde(default)]
+    pub fields: serde_json::Value,
+}
+
+fn random_bytes(length: i64) -> Vec<u8> {
+    use rand::RngCore;
+    let mut bytes = vec![0u8; length as usize];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+extism::host_fn!(generate_random_bytes(user_data: (); length: i64) -> Vec<u8> {
+    Ok(random_bytes(length))
+});
+
+extism::host_fn!(generate_random_bytes_json(user_data: (); length: i64) -> String {
+    Ok(serde_json::to_string(&random_bytes(length)).unwrap_or_default())
+});
+
+extism::host_fn!(new_id(user_data: (); kind: String) -> String {
+    if !ID_KINDS.contains(&kind.as_str()) {
+        return Err(extism::Error::msg(format!(
+            "Unknown ID kind '{}'; expected one of: {}",
+            kind,
+            ID_KINDS.join(", ")
+        )));
+    }
+    Ok(uuid::Uuid::now_v7().to_string())
+});
+
+fn generate_uuid_v4_function() -> Function {
+    Function::new(
+        "generate_uuid_v4",
+        [],
+        [PTR],
+        UserData::new(()),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+            plugin.memory_set_val(&mut outputs[0], uuid::Uuid::new_v4().to_string())?;
+            Ok(())
+        },
+    )
+}
+
+fn now() -> std::time::Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+fn timestamp_function(name: &str, value: fn() -> i64) -> Function {
+    Function::new(
+        name,
+        [],
+        [PTR],
+        UserData::new(value),
+        |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], user_data: UserData<fn() -> i64>| {
+            let value = *user_data.get()?.lock().unwrap();
+            plugin.memory_set_val(&mut outputs[0], value())?;
+            Ok(())
+        },
+    )
+}
+
+fn log_function(logs: Arc<Mutex<Vec<LogEntry>>>) -> Function {
+    Function::new(
+        "log",
+        [PTR],
+        [PTR],
+        UserData::new(logs),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], outputs: &mut [Val], user_data: UserData<Arc<Mutex<Vec<LogEntry>>>>| {
+            let input: String = plugin.memory_get_val(&inputs[0])?;
+            let response = match serde_json::from_str::<serde_json::Value>(&input) {
+                Ok(request) => {
+                    let level = request["level"].as_str().unwrap_or("info").to_ascii_lowercase();
+                    if LOG_LEVELS.contains(&level.as_str()) {
+                        let entry = LogEntry {
+                            level: if level == "warning" { "warn".to_string() } else { level },
+                            message: request["message"].as_str().unwrap_or_default().to_string(),
+                            fields: request["fields"].clone(),
+                        };
+                        user_data.get()?.lock().unwrap().lock().unwrap().push(entry);
+                        json!({ "success": true, "data": null, "error": null })
+                    } else {
+                        json!({ "success": false, "data": null, "error": format!("Unknown log level: {}", level) })
+                    }
+                }
+                Err(e) => json!({ "success": false, "data": null, "error": format!("JSON parse error: {}", e) }),
+            };
+            plugin.memory_set_val(&mut outputs[0], response.to_string())?;
+            Ok(())
+        },
+    )
+}
+
+fn stream_chunk_function(chunks: Arc<Mutex<Vec<Vec<u8>>>>) -> Function {
+    Function::new(
+        "stream_chunk",
+        [PTR],
+        [],
+        UserData::new(chunks),
+        |plugin: &mut CurrentPlugin, inputs: &[Val], _outputs: &mut [Val], user_data: UserData<Arc<Mutex<Vec<Vec<u8>>>>>| {
+            let chunk: Vec<u8> = plugin.memory_get_val(&inputs[0])?;
+            user_data.get()?.lock().unwrap().lock().unwrap().push(chunk);
+            Ok(())
+        },
+    )
+}
+
+/// All mock utility host functions
+pub(crate) fn functions(logs: &Arc<Mutex<Vec<LogEntry>>>, chunks: &Arc<Mutex<Vec<Vec<u8>>>>) -> Vec<Function> {
+    vec![
+        Function::new("generate_random_bytes", [PTR], [PTR], UserData::new(()), generate_random_bytes),
+        Function::new("generate_random_bytes_json", [PTR], [PTR], UserData::new(()), generate_random_bytes_json),
+        timestamp_function("get_timestamp", || now().as_secs() as i64),
+        timestamp_function("get_timestamp_nanos", || now().as_nanos() as i64),
+        Function::new(
+            "get_plugin_data_dir",
+            [],
+            [PTR],
+            UserData::new(()),
+            |plugin: &mut CurrentPlugin, _inputs: &[Val], outputs: &mut [Val], _user_data: UserData<()>| {
+                plugin.memory_set_val(&mut outputs[0], PLUGIN_DATA_GUEST_PATH)?;
+                Ok(())
+            },
+        ),
+        Function::new("new_id", [PTR], [PTR], UserData::new(()), new_id),
+        generate_uuid_v4_function(),
+        log_function(logs.clone()),
+        stream_chunk_function(chunks.clone()),
+    ]
+}
@@ -49,13 +49,13 @@ $manifest = @{
     author = "Your Name"
     plugin_type = "utility"
     wasm_module = "plugin.wasm"
+    sandbox = "standard"
     wasm_config = @{
         allowed_hosts = @()
         allowed_paths = @()
         config = @{}
-        memory_max_pages = 5
     }
-    capabilities = @("json_processing", "host_functions")
+    capabilities = @()
     entry_points = @(
         @{
             name = "greet"
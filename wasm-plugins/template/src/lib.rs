@@ -15,11 +15,27 @@ pub struct ExampleOutput {
     pub timestamp: u64,
 }
 
-/// Example error structure
+/// Structured error the host maps into its `AppError`
+///
+/// `code` is one of the host's error codes (`invalid_input`, `not_found`,
+/// `timeout`, `unavailable`, ...); `retriable` tells callers whether trying
+/// again may succeed.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PluginError {
-    pub error: String,
     pub code: String,
+    pub message: String,
+    pub retriable: bool,
+}
+
+/// Build a structured error to return from a plugin function
+fn plugin_error(code: &str, message: &str, retriable: bool, status: i32) -> WithReturnCode<Error> {
+    let error = PluginError {
+        code: code.to_string(),
+        message: message.to_string(),
+        retriable,
+    };
+    let json = serde_json::to_string(&error).unwrap_or_else(|_| message.to_string());
+    WithReturnCode::new(Error::msg(json), status)
 }
 
 /// Example host function call
@@ -77,18 +93,12 @@ pub fn repeat(Json(input): Json<ExampleInput>) -> FnResult<Json<ExampleOutput>>
 #[plugin_fn]
 pub fn validate(Json(input): Json<ExampleInput>) -> FnResult<Json<ExampleOutput>> {
     if input.message.is_empty() {
-        return Err(WithReturnCode::new(
-            Error::msg("Message cannot be empty"),
-            1,
-        ));
+        return Err(plugin_error("invalid_input", "Message cannot be empty", false, 1));
     }
     
     if let Some(count) = input.count {
         if count == 0 || count > 100 {
-            return Err(WithReturnCode::new(
-                Error::msg("Count must be between 1 and 100"),
-                2,
-            ));
+            return Err(plugin_error("invalid_input", "Count must be between 1 and 100", false, 2));
         }
     }
     